@@ -1,9 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-#[cfg(feature = "experimental")]
-use criterion::Throughput;
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 #[cfg(feature = "experimental")]
+use criterion::{BatchSize, Throughput};
+#[cfg(feature = "experimental")]
 use fixed::types::{I1F15, I1F31};
 #[cfg(feature = "experimental")]
 use fixed_macro::fixed;
@@ -27,11 +27,17 @@ use prio::{
 };
 #[cfg(feature = "experimental")]
 use prio::{
-    field::{Field255, Field64},
+    field::{Field255, Field64, FieldPrio2},
     flp::types::fixedpoint_l2::FixedPointBoundedL2VecSum,
     idpf::{Idpf, IdpfInput, RingBufferCache},
-    vdaf::poplar1::{Poplar1, Poplar1AggregationParam, Poplar1IdpfValue},
+    vdaf::{
+        accumulator::LaneAccumulator,
+        poplar1::{Poplar1, Poplar1AggregationParam, Poplar1IdpfValue},
+        Aggregatable, AggregateShare, OutputShare,
+    },
 };
+#[cfg(feature = "insecure")]
+use prio::{flp::types::SumVec, insecure::Prio3SumVecInsecure};
 #[cfg(feature = "experimental")]
 use rand::prelude::*;
 #[cfg(feature = "experimental")]
@@ -57,6 +63,40 @@ fn prng(c: &mut Criterion) {
     group.finish();
 }
 
+/// Speed test for adding a vector of field elements into an accumulator.
+fn accumulate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("accumulate");
+    let test_sizes = [16, 256, 1024, 4096, 65536];
+    for size in test_sizes {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, size| {
+            let mut accumulator = random_vector::<F>(*size).unwrap();
+            let share = random_vector::<F>(*size).unwrap();
+            b.iter(|| benchmarked_merge_vector(&mut accumulator, &share).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Speed test for adding output shares of a small field into an accumulator, with and without
+/// deferred reduction.
+#[cfg(feature = "experimental")]
+fn accumulate_lanes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("accumulate_lanes");
+    let test_sizes = [256, 4096, 65536];
+    for size in test_sizes {
+        let share = OutputShare::from(random_vector::<FieldPrio2>(size).unwrap());
+        group.bench_with_input(BenchmarkId::new("field", size), &size, |b, size| {
+            let mut accumulator = AggregateShare::from(vec![FieldPrio2::zero(); *size]);
+            b.iter(|| accumulator.accumulate(&share).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("lanes", size), &size, |b, size| {
+            let mut accumulator = LaneAccumulator::new(*size);
+            b.iter(|| accumulator.accumulate(&share).unwrap())
+        });
+    }
+    group.finish();
+}
+
 /// Speed test for generating samples from the discrete gaussian distribution using different
 /// standard deviations.
 #[cfg(feature = "experimental")]
@@ -156,6 +196,26 @@ fn prio2(c: &mut Criterion) {
         );
     }
     group.finish();
+
+    let mut group = c.benchmark_group("prio2_aggregate");
+    for input_length in [100, 1_000, 10_000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(input_length),
+            &input_length,
+            |b, input_length| {
+                let vdaf = Prio2::new(*input_length).unwrap();
+                let output_shares = (0..100)
+                    .map(|_| OutputShare::from(random_vector::<FieldPrio2>(*input_length).unwrap()))
+                    .collect::<Vec<_>>();
+                b.iter_batched(
+                    || output_shares.clone(),
+                    |output_shares| vdaf.aggregate(&(), output_shares).unwrap(),
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+    group.finish();
 }
 
 /// Benchmark prio3.
@@ -305,6 +365,42 @@ fn prio3(c: &mut Criterion) {
             );
         }
     }
+    #[cfg(feature = "insecure")]
+    {
+        for (input_length, chunk_length) in [(10, 3), (100, 10), (1_000, 31)] {
+            group.bench_with_input(
+                BenchmarkId::new("insecure_xof", input_length),
+                &(input_length, chunk_length),
+                |b, (input_length, chunk_length)| {
+                    let vdaf = Prio3SumVecInsecure::new(
+                        num_shares,
+                        1,
+                        0xFFFF_0000,
+                        SumVec::new(1, *input_length, *chunk_length).unwrap(),
+                    )
+                    .unwrap();
+                    let measurement = (0..u128::try_from(*input_length).unwrap())
+                        .map(|i| i & 1)
+                        .collect::<Vec<_>>();
+                    let nonce = black_box([0u8; 16]);
+                    let verify_key = black_box([0u8; 16]);
+                    let (public_share, input_shares) = vdaf.shard(&measurement, &nonce).unwrap();
+                    b.iter(|| {
+                        vdaf.prepare_init(
+                            &verify_key,
+                            0,
+                            &(),
+                            &nonce,
+                            &public_share,
+                            &input_shares[0],
+                        )
+                        .unwrap()
+                    });
+                },
+            );
+        }
+    }
+
     group.finish();
 
     let mut group = c.benchmark_group("prio3histogram_shard");
@@ -701,6 +797,55 @@ fn idpf(c: &mut Criterion) {
         });
     }
     group.finish();
+
+    // Evaluate many candidate prefixes at one level, one at a time and in a batch.
+    let mut group = c.benchmark_group("idpf_eval_prefixes");
+    let bits = 32;
+    let level_bits = iter::repeat_with(random).take(bits).collect::<Vec<bool>>();
+    let input = IdpfInput::from_bools(&level_bits);
+    let inner_values = random_vector::<Field64>(bits - 1)
+        .unwrap()
+        .into_iter()
+        .map(|random_element| Poplar1IdpfValue::new([Field64::one(), random_element]))
+        .collect::<Vec<_>>();
+    let leaf_value = Poplar1IdpfValue::new([Field255::one(), random_vector(1).unwrap()[0]]);
+    let idpf = Idpf::new((), ());
+    let (public_share, keys) = idpf
+        .gen(&input, inner_values, leaf_value, &[0; 16])
+        .unwrap();
+    for num_prefixes in [16, 256] {
+        let mut prefixes = (0..num_prefixes)
+            .map(|_| {
+                let prefix_bits = iter::repeat_with(random).take(16).collect::<Vec<bool>>();
+                IdpfInput::from_bools(&prefix_bits)
+            })
+            .collect::<Vec<_>>();
+        prefixes.sort();
+        group.bench_with_input(
+            BenchmarkId::new("each", num_prefixes),
+            &prefixes,
+            |b, prefixes| {
+                b.iter(|| {
+                    let mut cache = RingBufferCache::new(prefixes.len());
+                    for prefix in prefixes {
+                        idpf.eval(0, &public_share, &keys[0], prefix, &[0; 16], &mut cache)
+                            .unwrap();
+                    }
+                });
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("batch", num_prefixes),
+            &prefixes,
+            |b, prefixes| {
+                b.iter(|| {
+                    idpf.eval_batch(0, &public_share, &keys[0], prefixes, &[0; 16])
+                        .unwrap();
+                });
+            },
+        );
+    }
+    group.finish();
 }
 
 /// Benchmark Poplar1.
@@ -821,8 +966,20 @@ fn vidpf(c: &mut Criterion) {
 }
 
 #[cfg(feature = "experimental")]
-criterion_group!(benches, poplar1, prio3, prio2, poly_mul, prng, idpf, dp_noise, vidpf);
+criterion_group!(
+    benches,
+    poplar1,
+    prio3,
+    prio2,
+    poly_mul,
+    prng,
+    accumulate,
+    accumulate_lanes,
+    idpf,
+    dp_noise,
+    vidpf
+);
 #[cfg(not(feature = "experimental"))]
-criterion_group!(benches, prio3, prng, poly_mul);
+criterion_group!(benches, prio3, prng, accumulate, poly_mul);
 
 criterion_main!(benches);
@@ -0,0 +1,265 @@
+//! Command-line tool for generating and checking Prio3 messages offline.
+//!
+//! Every message is read and written as base64. Run without arguments for usage.
+
+use std::{convert::TryInto, env, error::Error, process::ExitCode};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use prio::{
+    codec::{Encode, ParameterizedDecode},
+    vdaf::{
+        prio3::{Prio3Count, Prio3Histogram, Prio3Sum, Prio3SumVec},
+        Aggregatable, Aggregator, Client, Collector, PrepareTransition,
+    },
+};
+use rand::prelude::*;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const VERIFY_KEY_SIZE: usize = 16;
+const NONCE_SIZE: usize = 16;
+
+const USAGE: &str = "\
+usage: mastic-cli <command> [args...]
+
+commands:
+  keygen
+      Print a random verification key.
+  shard <vdaf> <measurement>
+      Print a random nonce, the public share, and one input share per aggregator.
+  verify <vdaf> <verify-key> <nonce> <public-share> <input-share>...
+      Prepare a report with every aggregator and print each output share.
+  aggregate <vdaf> <output-share>...
+      Sum one aggregator's output shares (or aggregate shares) into an aggregate share.
+  unshard <vdaf> <num-measurements> <aggregate-share>...
+      Combine one aggregate share per aggregator and print the aggregate result.
+
+<vdaf> is one of:
+  count
+  sum:<bits>
+  sumvec:<bits>:<length>:<chunk-length>
+  histogram:<length>:<chunk-length>
+
+Measurements are 0 or 1 for count, an integer for sum, a comma-separated list of integers for
+sumvec, and a bucket index for histogram. Prio3 is run with two aggregators.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let Some((command, args)) = args.split_first() else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+
+    if command == "keygen" {
+        let verify_key: [u8; VERIFY_KEY_SIZE] = random();
+        println!("{}", STANDARD.encode(verify_key));
+        return Ok(());
+    }
+
+    let Some((vdaf, args)) = args.split_first() else {
+        return Err(format!("missing <vdaf>\n\n{USAGE}").into());
+    };
+    let params: Vec<&str> = vdaf.split(':').collect();
+    match params.as_slice() {
+        ["count"] => dispatch(&Prio3Count::new_count(2)?, command, args, |s| match s {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            _ => Err(format!("invalid count measurement {s:?}").into()),
+        }),
+        ["sum", bits] => dispatch(&Prio3Sum::new_sum(2, bits.parse()?)?, command, args, |s| {
+            Ok(s.parse()?)
+        }),
+        ["sumvec", bits, length, chunk_length] => dispatch(
+            &Prio3SumVec::new_sum_vec(2, bits.parse()?, length.parse()?, chunk_length.parse()?)?,
+            command,
+            args,
+            |s| s.split(',').map(|x| Ok(x.trim().parse()?)).collect(),
+        ),
+        ["histogram", length, chunk_length] => dispatch(
+            &Prio3Histogram::new_histogram(2, length.parse()?, chunk_length.parse()?)?,
+            command,
+            args,
+            |s| Ok(s.parse()?),
+        ),
+        _ => Err(format!("unrecognized VDAF {vdaf:?}").into()),
+    }
+}
+
+fn dispatch<V>(
+    vdaf: &V,
+    command: &str,
+    args: &[String],
+    parse_measurement: impl Fn(&str) -> Result<V::Measurement>,
+) -> Result<()>
+where
+    V: Client<NONCE_SIZE>
+        + Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE, AggregationParam = ()>
+        + Collector,
+{
+    match (command, args) {
+        ("shard", [measurement]) => shard(vdaf, &parse_measurement(measurement)?),
+        ("verify", [verify_key, nonce, public_share, input_shares @ ..]) => {
+            verify(vdaf, verify_key, nonce, public_share, input_shares)
+        }
+        ("aggregate", [first, rest @ ..]) => aggregate(vdaf, first, rest),
+        ("unshard", [num_measurements, agg_shares @ ..]) => {
+            unshard(vdaf, num_measurements.parse()?, agg_shares)
+        }
+        ("shard" | "verify" | "aggregate" | "unshard", _) => {
+            Err(format!("wrong number of arguments for {command}\n\n{USAGE}").into())
+        }
+        _ => Err(format!("unrecognized command {command:?}\n\n{USAGE}").into()),
+    }
+}
+
+fn shard<V: Client<NONCE_SIZE>>(vdaf: &V, measurement: &V::Measurement) -> Result<()> {
+    let nonce: [u8; NONCE_SIZE] = random();
+    let (public_share, input_shares) = vdaf.shard(measurement, &nonce)?;
+    println!("nonce: {}", STANDARD.encode(nonce));
+    println!(
+        "public share: {}",
+        STANDARD.encode(public_share.get_encoded()?)
+    );
+    for (agg_id, input_share) in input_shares.iter().enumerate() {
+        println!(
+            "input share {agg_id}: {}",
+            STANDARD.encode(input_share.get_encoded()?)
+        );
+    }
+    Ok(())
+}
+
+fn verify<V>(
+    vdaf: &V,
+    verify_key: &str,
+    nonce: &str,
+    public_share: &str,
+    input_shares: &[String],
+) -> Result<()>
+where
+    V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE, AggregationParam = ()>,
+{
+    let verify_key = decode_array::<VERIFY_KEY_SIZE>("verification key", verify_key)?;
+    let nonce = decode_array::<NONCE_SIZE>("nonce", nonce)?;
+    let public_share =
+        V::PublicShare::get_decoded_with_param(vdaf, &STANDARD.decode(public_share)?)
+            .map_err(|e| format!("invalid public share: {e}"))?;
+    if input_shares.len() != vdaf.num_aggregators() {
+        return Err(format!(
+            "expected {} input shares, got {}",
+            vdaf.num_aggregators(),
+            input_shares.len()
+        )
+        .into());
+    }
+
+    let mut states = Vec::new();
+    let mut prep_shares = Vec::new();
+    for (agg_id, input_share) in input_shares.iter().enumerate() {
+        let input_share =
+            V::InputShare::get_decoded_with_param(&(vdaf, agg_id), &STANDARD.decode(input_share)?)
+                .map_err(|e| format!("invalid input share {agg_id}: {e}"))?;
+        let (state, prep_share) = vdaf
+            .prepare_init(
+                &verify_key,
+                agg_id,
+                &(),
+                &nonce,
+                &public_share,
+                &input_share,
+            )
+            .map_err(|e| format!("aggregator {agg_id} rejected its input share: {e}"))?;
+        states.push(state);
+        prep_shares.push(prep_share);
+    }
+
+    let mut round = 0;
+    let out_shares = loop {
+        let prep_msg = vdaf
+            .prepare_shares_to_prepare_message(&(), prep_shares)
+            .map_err(|e| format!("report rejected in round {round}: {e}"))?;
+        prep_shares = Vec::new();
+        let mut out_shares = Vec::new();
+        for (agg_id, state) in std::mem::take(&mut states).into_iter().enumerate() {
+            match vdaf
+                .prepare_next(state, prep_msg.clone())
+                .map_err(|e| format!("aggregator {agg_id} rejected the report: {e}"))?
+            {
+                PrepareTransition::Continue(state, prep_share) => {
+                    states.push(state);
+                    prep_shares.push(prep_share);
+                }
+                PrepareTransition::Finish(out_share) => out_shares.push(out_share),
+            }
+        }
+        if states.is_empty() {
+            break out_shares;
+        }
+        round += 1;
+    };
+
+    println!("report is valid");
+    for (agg_id, out_share) in out_shares.iter().enumerate() {
+        println!(
+            "output share {agg_id}: {}",
+            STANDARD.encode(out_share.get_encoded()?)
+        );
+    }
+    Ok(())
+}
+
+fn aggregate<V>(vdaf: &V, first: &str, rest: &[String]) -> Result<()>
+where
+    V: Collector<AggregationParam = ()>,
+{
+    let mut agg_share = decode_agg_share(vdaf, 0, first)?;
+    for (i, share) in rest.iter().enumerate() {
+        agg_share.merge(&decode_agg_share(vdaf, i + 1, share)?)?;
+    }
+    println!("{}", STANDARD.encode(agg_share.get_encoded()?));
+    Ok(())
+}
+
+fn unshard<V>(vdaf: &V, num_measurements: usize, agg_shares: &[String]) -> Result<()>
+where
+    V: Collector<AggregationParam = ()>,
+{
+    let agg_shares = agg_shares
+        .iter()
+        .enumerate()
+        .map(|(i, agg_share)| decode_agg_share(vdaf, i, agg_share))
+        .collect::<Result<Vec<_>>>()?;
+    let agg_result = vdaf.unshard(&(), agg_shares, num_measurements)?;
+    println!("{agg_result:?}");
+    Ok(())
+}
+
+/// Output shares of this crate's VDAFs have the same encoding as aggregate shares, so this accepts
+/// either.
+fn decode_agg_share<V: Collector<AggregationParam = ()>>(
+    vdaf: &V,
+    i: usize,
+    encoded: &str,
+) -> Result<V::AggregateShare> {
+    Ok(
+        V::AggregateShare::get_decoded_with_param(&(vdaf, &()), &STANDARD.decode(encoded)?)
+            .map_err(|e| format!("invalid share {i}: {e}"))?,
+    )
+}
+
+fn decode_array<const N: usize>(name: &str, encoded: &str) -> Result<[u8; N]> {
+    STANDARD
+        .decode(encoded)?
+        .try_into()
+        .map_err(|v: Vec<u8>| format!("{name} must be {N} bytes, got {}", v.len()).into())
+}
@@ -0,0 +1,75 @@
+/* SPDX-License-Identifier: MPL-2.0 */
+
+/*
+ * C interface for generating Prio3 reports, exported by the `prio` crate when
+ * the `capi` feature is enabled. See the `capi` module documentation for
+ * details. This file is written by hand and must be kept in sync with
+ * src/capi.rs, whose tests derive every declaration below from the Rust
+ * definitions and check that this file matches.
+ *
+ * To build a static library (libprio.a) and a shared library:
+ *
+ *     cargo build --release --features capi
+ */
+
+#ifndef MASTIC_H
+#define MASTIC_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Size in bytes of the nonce passed to mastic_client_encode(). */
+#define MASTIC_NONCE_SIZE 16
+
+typedef enum {
+    MASTIC_STATUS_OK = 0,
+    MASTIC_STATUS_NULL_POINTER = 1,
+    MASTIC_STATUS_INVALID_ARGUMENT = 2,
+    MASTIC_STATUS_INVALID_MEASUREMENT = 3,
+    MASTIC_STATUS_INTERNAL = 4,
+} mastic_status;
+
+/* A byte buffer allocated by the library. Release with mastic_buffer_free(). */
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} mastic_buffer;
+
+/* Opaque client handle. Release with mastic_client_free(). */
+typedef struct MasticClient mastic_client;
+
+/* Constructors return NULL if the parameters are invalid. */
+mastic_client *mastic_client_new_count(uint8_t num_aggregators);
+mastic_client *mastic_client_new_sum(uint8_t num_aggregators, size_t bits);
+mastic_client *mastic_client_new_sum_vec(uint8_t num_aggregators, size_t bits, size_t len,
+                                         size_t chunk_length);
+mastic_client *mastic_client_new_histogram(uint8_t num_aggregators, size_t length,
+                                           size_t chunk_length);
+
+void mastic_client_free(mastic_client *client);
+
+size_t mastic_client_num_aggregators(const mastic_client *client);
+
+/*
+ * Shards `measurement` into an encoded public share and `num_input_shares`
+ * encoded input shares, one per Aggregator. `nonce` points to
+ * MASTIC_NONCE_SIZE bytes. On success the caller owns the output buffers; on
+ * failure they are set to empty.
+ */
+mastic_status mastic_client_encode(const mastic_client *client, const uint64_t *measurement,
+                                   size_t measurement_len,
+                                   const uint8_t *nonce,
+                                   mastic_buffer *public_share, mastic_buffer *input_shares,
+                                   size_t num_input_shares);
+
+void mastic_buffer_free(mastic_buffer buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MASTIC_H */
@@ -6,7 +6,7 @@
 //! benchmark, but which we don't want to expose in the public API.
 
 use crate::fft::discrete_fourier_transform;
-use crate::field::FftFriendlyFieldElement;
+use crate::field::{merge_vector, FftFriendlyFieldElement, FieldElement, FieldError};
 use crate::flp::gadgets::Mul;
 use crate::flp::FlpError;
 use crate::polynomial::{fft_get_roots, poly_fft, PolyFFTTempMemory};
@@ -42,3 +42,11 @@ pub fn benchmarked_gadget_mul_call_poly_direct<F: FftFriendlyFieldElement>(
 ) -> Result<(), FlpError> {
     g.call_poly_direct(outp, inp)
 }
+
+/// Adds `other` into `accumulator` element-wise.
+pub fn benchmarked_merge_vector<F: FieldElement>(
+    accumulator: &mut [F],
+    other: &[F],
+) -> Result<(), FieldError> {
+    merge_vector(accumulator, other)
+}
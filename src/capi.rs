@@ -0,0 +1,569 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A C ABI for generating reports on the client.
+//!
+//! This exposes the client side of [`Prio3Count`], [`Prio3Sum`], [`Prio3SumVec`], and
+//! [`Prio3Histogram`] to applications that are not written in Rust. The declarations are mirrored
+//! in `include/mastic.h`, which must be kept in sync with this module. The header is written by
+//! hand rather than generated with cbindgen, so that building the crate does not require it. In
+//! its place, a test derives each function declaration, the status codes, and the layout of
+//! [`MasticBuffer`] from this module and checks that the header declares them identically.
+//!
+//! With the `capi` feature enabled, `cargo build --release --features capi` produces a static
+//! library and a shared library exporting these functions.
+//!
+//! A client is created with one of the `mastic_client_new_*` functions and released with
+//! [`mastic_client_free`]. [`mastic_client_encode`] shards a measurement into an encoded public
+//! share and one encoded input share per Aggregator. Each output is returned in a
+//! [`MasticBuffer`] owned by the caller, which must be released with [`mastic_buffer_free`].
+
+use crate::{
+    codec::Encode,
+    vdaf::{
+        prio3::{Prio3Count, Prio3Histogram, Prio3Sum, Prio3SumVec},
+        Client, VdafError,
+    },
+};
+use std::{
+    convert::TryFrom,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// Size in bytes of the nonce passed to [`mastic_client_encode`].
+pub const MASTIC_NONCE_SIZE: usize = 16;
+
+/// Status codes returned by the functions in this module.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MasticStatus {
+    /// The operation succeeded.
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer = 1,
+    /// The number of output buffers does not match the number of Aggregators.
+    InvalidArgument = 2,
+    /// The measurement is not valid for the client's measurement type.
+    InvalidMeasurement = 3,
+    /// An unexpected error occurred.
+    Internal = 4,
+}
+
+/// A byte buffer allocated by this library.
+#[repr(C)]
+#[derive(Debug)]
+pub struct MasticBuffer {
+    /// Pointer to the first byte of the buffer.
+    pub data: *mut u8,
+    /// Length of the buffer in bytes.
+    pub len: usize,
+}
+
+impl MasticBuffer {
+    fn empty() -> Self {
+        Self {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+/// An opaque handle to a client for one Prio3 instance.
+#[derive(Debug)]
+pub struct MasticClient(Inner);
+
+#[derive(Debug)]
+enum Inner {
+    Count(Prio3Count),
+    Sum(Prio3Sum),
+    SumVec(Prio3SumVec),
+    Histogram(Prio3Histogram),
+}
+
+impl MasticClient {
+    fn num_aggregators(&self) -> usize {
+        use crate::vdaf::Vdaf;
+        match &self.0 {
+            Inner::Count(vdaf) => vdaf.num_aggregators(),
+            Inner::Sum(vdaf) => vdaf.num_aggregators(),
+            Inner::SumVec(vdaf) => vdaf.num_aggregators(),
+            Inner::Histogram(vdaf) => vdaf.num_aggregators(),
+        }
+    }
+
+    /// Shard `measurement` and return the encoded public share followed by the encoded input
+    /// shares.
+    fn encode(
+        &self,
+        measurement: &[u64],
+        nonce: &[u8; MASTIC_NONCE_SIZE],
+    ) -> Result<Vec<Vec<u8>>, MasticStatus> {
+        fn encode_all<P: Encode, S: Encode>(
+            sharded: Result<(P, Vec<S>), VdafError>,
+        ) -> Result<Vec<Vec<u8>>, MasticStatus> {
+            let (public_share, input_shares) =
+                sharded.map_err(|_| MasticStatus::InvalidMeasurement)?;
+            std::iter::once(public_share.get_encoded())
+                .chain(input_shares.iter().map(Encode::get_encoded))
+                .collect::<Result<_, _>>()
+                .map_err(|_| MasticStatus::Internal)
+        }
+
+        let scalar = || match measurement {
+            [x] => Ok(*x),
+            _ => Err(MasticStatus::InvalidMeasurement),
+        };
+        match &self.0 {
+            Inner::Count(vdaf) => {
+                let measurement = match scalar()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(MasticStatus::InvalidMeasurement),
+                };
+                encode_all(vdaf.shard(&measurement, nonce))
+            }
+            Inner::Sum(vdaf) => encode_all(vdaf.shard(&u128::from(scalar()?), nonce)),
+            Inner::SumVec(vdaf) => {
+                let measurement = measurement.iter().copied().map(u128::from).collect();
+                encode_all(vdaf.shard(&measurement, nonce))
+            }
+            Inner::Histogram(vdaf) => {
+                let index =
+                    usize::try_from(scalar()?).map_err(|_| MasticStatus::InvalidMeasurement)?;
+                encode_all(vdaf.shard(&index, nonce))
+            }
+        }
+    }
+}
+
+fn new_client(new: impl FnOnce() -> Result<Inner, VdafError>) -> *mut MasticClient {
+    // Unwinding across the C ABI is undefined behavior, so report a panic like invalid parameters.
+    match catch_unwind(AssertUnwindSafe(new)) {
+        Ok(Ok(inner)) => Box::into_raw(Box::new(MasticClient(inner))),
+        Ok(Err(_)) | Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Creates a client for [`Prio3Count`]. Measurements are a single value, either 0 or 1.
+///
+/// Returns null if the parameters are invalid.
+#[no_mangle]
+pub extern "C" fn mastic_client_new_count(num_aggregators: u8) -> *mut MasticClient {
+    new_client(|| Prio3Count::new_count(num_aggregators).map(Inner::Count))
+}
+
+/// Creates a client for [`Prio3Sum`]. Measurements are a single value less than `2^bits`.
+///
+/// Returns null if the parameters are invalid.
+#[no_mangle]
+pub extern "C" fn mastic_client_new_sum(num_aggregators: u8, bits: usize) -> *mut MasticClient {
+    new_client(|| Prio3Sum::new_sum(num_aggregators, bits).map(Inner::Sum))
+}
+
+/// Creates a client for [`Prio3SumVec`]. Measurements are `len` values, each less than `2^bits`.
+///
+/// Returns null if the parameters are invalid.
+#[no_mangle]
+pub extern "C" fn mastic_client_new_sum_vec(
+    num_aggregators: u8,
+    bits: usize,
+    len: usize,
+    chunk_length: usize,
+) -> *mut MasticClient {
+    new_client(|| {
+        Prio3SumVec::new_sum_vec(num_aggregators, bits, len, chunk_length).map(Inner::SumVec)
+    })
+}
+
+/// Creates a client for [`Prio3Histogram`]. Measurements are a single bucket index less than
+/// `length`.
+///
+/// Returns null if the parameters are invalid.
+#[no_mangle]
+pub extern "C" fn mastic_client_new_histogram(
+    num_aggregators: u8,
+    length: usize,
+    chunk_length: usize,
+) -> *mut MasticClient {
+    new_client(|| {
+        Prio3Histogram::new_histogram(num_aggregators, length, chunk_length).map(Inner::Histogram)
+    })
+}
+
+/// Releases a client. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `client` must be null or a pointer returned by one of the `mastic_client_new_*` functions that
+/// has not already been released.
+#[no_mangle]
+pub unsafe extern "C" fn mastic_client_free(client: *mut MasticClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Returns the number of input shares produced by [`mastic_client_encode`], or 0 if `client` is
+/// null.
+///
+/// # Safety
+///
+/// `client` must be null or a live pointer returned by one of the `mastic_client_new_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn mastic_client_num_aggregators(client: *const MasticClient) -> usize {
+    client.as_ref().map_or(0, MasticClient::num_aggregators)
+}
+
+/// Shards a measurement into an encoded public share and one encoded input share per Aggregator.
+///
+/// `measurement` points to `measurement_len` integers whose interpretation depends on the
+/// measurement type the client was created for. `nonce` points to [`MASTIC_NONCE_SIZE`] bytes.
+/// On success, `public_share` and each of the `num_input_shares` buffers at `input_shares` are
+/// filled in, and the caller must release them with [`mastic_buffer_free`]. On failure, the
+/// output buffers are set to empty.
+///
+/// # Safety
+///
+/// `client` must be a live pointer returned by one of the `mastic_client_new_*` functions.
+/// `measurement` must be valid for reads of `measurement_len` values, `nonce` must be valid for
+/// reads of [`MASTIC_NONCE_SIZE`] bytes, `public_share` must be valid for writes, and
+/// `input_shares` must be valid for writes of `num_input_shares` buffers.
+#[no_mangle]
+pub unsafe extern "C" fn mastic_client_encode(
+    client: *const MasticClient,
+    measurement: *const u64,
+    measurement_len: usize,
+    nonce: *const u8,
+    public_share: *mut MasticBuffer,
+    input_shares: *mut MasticBuffer,
+    num_input_shares: usize,
+) -> MasticStatus {
+    if client.is_null()
+        || (measurement.is_null() && measurement_len > 0)
+        || nonce.is_null()
+        || public_share.is_null()
+        || input_shares.is_null()
+    {
+        return MasticStatus::NullPointer;
+    }
+    let public_share = &mut *public_share;
+    let input_shares = slice::from_raw_parts_mut(input_shares, num_input_shares);
+    *public_share = MasticBuffer::empty();
+    for buf in input_shares.iter_mut() {
+        *buf = MasticBuffer::empty();
+    }
+
+    let client = &*client;
+    if num_input_shares != client.num_aggregators() {
+        return MasticStatus::InvalidArgument;
+    }
+    let measurement = if measurement_len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(measurement, measurement_len)
+    };
+    let nonce = &*(nonce as *const [u8; MASTIC_NONCE_SIZE]);
+
+    // Unwinding across the C ABI is undefined behavior, so report a panic as an internal error.
+    let encoded = match catch_unwind(AssertUnwindSafe(|| client.encode(measurement, nonce))) {
+        Ok(Ok(encoded)) => encoded,
+        Ok(Err(status)) => return status,
+        Err(_) => return MasticStatus::Internal,
+    };
+
+    let mut encoded = encoded.into_iter();
+    // Unwrap safety: `encode()` returns the public share followed by one share per Aggregator.
+    *public_share = MasticBuffer::from_vec(encoded.next().unwrap());
+    for (buf, bytes) in input_shares.iter_mut().zip(encoded) {
+        *buf = MasticBuffer::from_vec(bytes);
+    }
+    MasticStatus::Ok
+}
+
+/// Releases a buffer returned by this library. Releasing an empty buffer is a no-op.
+///
+/// # Safety
+///
+/// `buffer` must be empty or have been returned by this library and not already released.
+#[no_mangle]
+pub unsafe extern "C" fn mastic_buffer_free(buffer: MasticBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::ParameterizedDecode,
+        vdaf::{
+            prio3::{Prio3InputShare, Prio3PublicShare},
+            test_utils::run_vdaf_sharded,
+        },
+    };
+
+    fn encode(
+        client: *const MasticClient,
+        measurement: &[u64],
+        nonce: &[u8; MASTIC_NONCE_SIZE],
+    ) -> Result<(Vec<u8>, Vec<Vec<u8>>), MasticStatus> {
+        let mut public_share = MasticBuffer::empty();
+        let mut input_shares = [MasticBuffer::empty(), MasticBuffer::empty()];
+        let status = unsafe {
+            mastic_client_encode(
+                client,
+                measurement.as_ptr(),
+                measurement.len(),
+                nonce.as_ptr(),
+                &mut public_share,
+                input_shares.as_mut_ptr(),
+                input_shares.len(),
+            )
+        };
+        if status != MasticStatus::Ok {
+            assert!(public_share.data.is_null());
+            return Err(status);
+        }
+
+        let to_vec = |buf: MasticBuffer| {
+            let bytes = unsafe { slice::from_raw_parts(buf.data, buf.len) }.to_vec();
+            unsafe { mastic_buffer_free(buf) };
+            bytes
+        };
+        Ok((to_vec(public_share), input_shares.map(to_vec).to_vec()))
+    }
+
+    #[test]
+    fn histogram() {
+        let vdaf = Prio3Histogram::new_histogram(2, 4, 2).unwrap();
+        let client = mastic_client_new_histogram(2, 4, 2);
+        assert!(!client.is_null());
+        assert_eq!(unsafe { mastic_client_num_aggregators(client) }, 2);
+
+        let mut reports = Vec::new();
+        for (i, measurement) in [0, 3, 3, 1].into_iter().enumerate() {
+            let nonce = [i as u8; MASTIC_NONCE_SIZE];
+            let (public_share, input_shares) = encode(client, &[measurement], &nonce).unwrap();
+            let public_share =
+                Prio3PublicShare::get_decoded_with_param(&vdaf, &public_share).unwrap();
+            let input_shares = input_shares
+                .iter()
+                .enumerate()
+                .map(|(agg_id, bytes)| {
+                    Prio3InputShare::get_decoded_with_param(&(&vdaf, agg_id), bytes).unwrap()
+                })
+                .collect::<Vec<_>>();
+            reports.push((public_share, nonce, input_shares));
+        }
+        assert_eq!(
+            run_vdaf_sharded(&vdaf, &(), reports).unwrap(),
+            vec![1, 1, 0, 2]
+        );
+
+        unsafe { mastic_client_free(client) };
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert!(mastic_client_new_sum(2, 65).is_null());
+        assert!(mastic_client_new_count(0).is_null());
+
+        let client = mastic_client_new_count(2);
+        let nonce = [0; MASTIC_NONCE_SIZE];
+        assert_eq!(
+            encode(client, &[2], &nonce),
+            Err(MasticStatus::InvalidMeasurement)
+        );
+        assert_eq!(
+            encode(client, &[1, 1], &nonce),
+            Err(MasticStatus::InvalidMeasurement)
+        );
+        assert_eq!(
+            encode(ptr::null(), &[1], &nonce),
+            Err(MasticStatus::NullPointer)
+        );
+        assert!(encode(client, &[1], &nonce).is_ok());
+        unsafe { mastic_client_free(client) };
+
+        let client = mastic_client_new_sum_vec(3, 4, 2, 1);
+        assert_eq!(
+            encode(client, &[1, 15], &nonce),
+            Err(MasticStatus::InvalidArgument)
+        );
+        unsafe { mastic_client_free(client) };
+    }
+
+    /// Returns the C type the header uses for a Rust type in this module.
+    fn c_type(rust: &str) -> String {
+        if let Some(pointee) = rust.strip_prefix("*const ") {
+            return format!("const {} *", c_type(pointee));
+        }
+        if let Some(pointee) = rust.strip_prefix("*mut ") {
+            return format!("{} *", c_type(pointee));
+        }
+        match rust {
+            "u8" => "uint8_t",
+            "u64" => "uint64_t",
+            "usize" => "size_t",
+            "MasticClient" => "mastic_client",
+            "MasticBuffer" => "mastic_buffer",
+            "MasticStatus" => "mastic_status",
+            _ => panic!("no C type for {rust}"),
+        }
+        .to_string()
+    }
+
+    /// Joins a C type and a name into a declarator.
+    fn declarator(ty: &str, name: &str) -> String {
+        if ty.ends_with('*') {
+            format!("{ty}{name}")
+        } else {
+            format!("{ty} {name}")
+        }
+    }
+
+    #[test]
+    fn header_matches() {
+        let header = include_str!("../include/mastic.h");
+        // Strip comments and collapse whitespace, so that declarations can be compared as strings.
+        let mut code = String::new();
+        let mut rest = header;
+        while let Some(start) = rest.find("/*") {
+            code.push_str(&rest[..start]);
+            let end = rest[start..].find("*/").unwrap();
+            rest = &rest[start + end + 2..];
+        }
+        code.push_str(rest);
+        let code = code
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .replace("( ", "(")
+            .replace(" )", ")");
+
+        // Derive the declaration of each exported function from its definition in this module.
+        let source = include_str!("capi.rs")
+            .split("#[cfg(test)]")
+            .next()
+            .unwrap();
+        let mut names = Vec::new();
+        let mut declarations = Vec::new();
+        for definition in source.split("#[no_mangle]").skip(1) {
+            let signature = definition
+                .split_once("fn ")
+                .unwrap()
+                .1
+                .split_once('{')
+                .unwrap()
+                .0
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let (name, rest) = signature.split_once('(').unwrap();
+            let (params, ret) = rest.split_once(')').unwrap();
+            let params = params
+                .split(',')
+                .map(str::trim)
+                .filter(|param| !param.is_empty())
+                .map(|param| {
+                    let (name, ty) = param.split_once(": ").unwrap();
+                    declarator(&c_type(ty), name)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let ret = match ret.trim().strip_prefix("-> ") {
+                Some(ty) => c_type(ty),
+                None => "void".to_string(),
+            };
+            declarations.push(format!("{}({params});", declarator(&ret, name)));
+            names.push(name.to_string());
+        }
+
+        for declaration in &declarations {
+            assert!(
+                code.contains(declaration),
+                "include/mastic.h does not declare `{declaration}`"
+            );
+        }
+        let declared = code
+            .split('(')
+            .filter_map(|before| before.rsplit([' ', '*']).next())
+            .filter(|name| name.starts_with("mastic_"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            declared, names,
+            "include/mastic.h declares other functions than this module"
+        );
+
+        assert!(code.contains(&format!("#define MASTIC_NONCE_SIZE {MASTIC_NONCE_SIZE}")));
+        assert!(code.contains("typedef struct MasticClient mastic_client;"));
+
+        // Derive the status enum, with every variant in order, from its definition.
+        let variants = item_body(source, "pub enum MasticStatus")
+            .into_iter()
+            .map(|variant| {
+                let (name, value) = variant.trim_end_matches(',').split_once(" = ").unwrap();
+                let mut constant = "MASTIC_STATUS".to_string();
+                for c in name.chars() {
+                    if c.is_ascii_uppercase() {
+                        constant.push('_');
+                    }
+                    constant.push(c.to_ascii_uppercase());
+                }
+                format!("{constant} = {value},")
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let status = format!("typedef enum {{ {variants} }} mastic_status;");
+        assert!(
+            code.contains(&status),
+            "include/mastic.h does not declare `{status}`"
+        );
+
+        // Likewise for the fields of the buffer.
+        let fields = item_body(source, "pub struct MasticBuffer")
+            .into_iter()
+            .map(|field| {
+                let (name, ty) = field
+                    .trim_start_matches("pub ")
+                    .trim_end_matches(',')
+                    .split_once(": ")
+                    .unwrap();
+                format!("{};", declarator(&c_type(ty), name))
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let buffer = format!("typedef struct {{ {fields} }} mastic_buffer;");
+        assert!(
+            code.contains(&buffer),
+            "include/mastic.h does not declare `{buffer}`"
+        );
+    }
+
+    /// Returns the lines of the body of the item in `source` that starts with `header`, without
+    /// attributes and doc comments.
+    fn item_body<'a>(source: &'a str, header: &str) -> Vec<&'a str> {
+        source
+            .split_once(&format!("{header} {{"))
+            .unwrap()
+            .1
+            .split_once("\n}")
+            .unwrap()
+            .0
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("///") && !line.starts_with("#["))
+            .collect()
+    }
+}
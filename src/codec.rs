@@ -9,6 +9,10 @@
 //!
 //! [1]: https://datatracker.ietf.org/doc/html/rfc8446#section-3
 
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod compression;
+
 use byteorder::{BigEndian, ReadBytesExt};
 use std::{
     convert::TryInto,
@@ -90,7 +94,7 @@ pub trait ParameterizedDecode<P>: Sized {
 
 /// Provide a blanket implementation so that any [`Decode`] can be used as a
 /// `ParameterizedDecode<T>` for any `T`.
-impl<D: Decode + ?Sized, T> ParameterizedDecode<T> for D {
+impl<D: Decode, T> ParameterizedDecode<T> for D {
     fn decode_with_param(
         _decoding_parameter: &T,
         bytes: &mut Cursor<&[u8]>,
@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Framing of encoded messages with a header that says how the payload is compressed.
+//!
+//! Where upload bandwidth is the limiting factor, it can pay to compress large messages. How much
+//! compression saves depends on the message: shares of measurements and aggregates are uniformly
+//! distributed field elements and do not compress, while messages with structure, such as batches
+//! of reports with similar headers, do. The length of a compressed message depends on its
+//! contents, so messages that are encrypted after compression may reveal what they contain.
+//!
+//! The peers first agree on an algorithm: each advertises the algorithms it supports as
+//! [`CompressionFlags`], and [`CompressionFlags::negotiate`] picks the preferred algorithm they
+//! have in common, falling back to [`Compression::None`]. This crate does not implement any
+//! compression algorithm yet, so every frame is currently uncompressed; the header lets an
+//! algorithm be added later without changing the format of existing frames.
+//!
+//! A frame consists of the algorithm's code, one byte, then the length of the uncompressed message
+//! as a 32-bit integer, then the payload. The length lets the receiver bound the memory it
+//! allocates before decoding: [`decompress`] rejects frames that claim to be longer than the limit
+//! its caller sets.
+
+use crate::codec::{CodecError, Decode, Encode};
+use std::io::{Cursor, Read};
+
+/// A compression algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Compression {
+    /// The payload is not compressed.
+    None,
+}
+
+impl Compression {
+    /// The algorithms in order of preference, most preferred first.
+    const PREFERENCE: [Self; 1] = [Self::None];
+
+    /// Returns the code of this algorithm in a frame header.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::None => 0,
+        }
+    }
+
+    /// Returns the algorithm with code `code`, if there is one.
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::PREFERENCE
+            .into_iter()
+            .find(|algorithm| algorithm.code() == code)
+    }
+
+    fn flag(self) -> u8 {
+        1 << self.code()
+    }
+}
+
+/// A set of compression algorithms, advertised by a peer when the algorithm is negotiated.
+/// [`Compression::None`] is always in the set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CompressionFlags(u8);
+
+impl CompressionFlags {
+    /// Returns the set of `algorithms`, and [`Compression::None`].
+    pub fn new(algorithms: impl IntoIterator<Item = Compression>) -> Self {
+        Self(
+            algorithms
+                .into_iter()
+                .fold(Compression::None.flag(), |flags, algorithm| {
+                    flags | algorithm.flag()
+                }),
+        )
+    }
+
+    /// Returns true if `algorithm` is in the set.
+    pub fn contains(&self, algorithm: Compression) -> bool {
+        self.0 & algorithm.flag() != 0
+    }
+
+    /// Returns the most preferred algorithm in both this set and `other`.
+    pub fn negotiate(&self, other: &Self) -> Compression {
+        Compression::PREFERENCE
+            .into_iter()
+            .find(|algorithm| self.contains(*algorithm) && other.contains(*algorithm))
+            .unwrap_or(Compression::None)
+    }
+}
+
+impl Default for CompressionFlags {
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+
+impl Encode for CompressionFlags {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.0.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+impl Decode for CompressionFlags {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let flags = u8::decode(bytes)?;
+        let known = Self::new(Compression::PREFERENCE).0;
+        if flags & !known != 0 || flags & Compression::None.flag() == 0 {
+            return Err(CodecError::UnexpectedValue);
+        }
+        Ok(Self(flags))
+    }
+}
+
+/// The length of a frame header.
+const HEADER_LEN: usize = 1 + 4;
+
+/// Encodes `message` and frames it.
+pub fn compress<T: Encode>(message: &T) -> Result<Vec<u8>, CodecError> {
+    let encoded = message.get_encoded()?;
+    let len = u32::try_from(encoded.len()).map_err(|_| CodecError::LengthPrefixOverflow)?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + encoded.len());
+    Compression::None.code().encode(&mut bytes)?;
+    len.encode(&mut bytes)?;
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+/// Returns the algorithm a frame was compressed with, without decompressing it.
+pub fn peek_compression(frame: &[u8]) -> Result<Compression, CodecError> {
+    let code = u8::decode(&mut Cursor::new(frame))?;
+    Compression::from_code(code).ok_or(CodecError::UnexpectedValue)
+}
+
+/// Returns the encoded message in `frame`. Returns an error if the frame's algorithm is not
+/// supported, if the message is longer than `max_len` bytes, or if the payload does not have the
+/// length in the header. The result can then be decoded like any other encoded message.
+pub fn decompress(frame: &[u8], max_len: usize) -> Result<Vec<u8>, CodecError> {
+    let algorithm = peek_compression(frame)?;
+    let mut cursor = Cursor::new(frame);
+    cursor.set_position(1);
+    let len = usize::try_from(u32::decode(&mut cursor)?)
+        .map_err(|_| CodecError::LengthPrefixTooBig(usize::MAX))?;
+    if len > max_len {
+        return Err(CodecError::LengthPrefixTooBig(len));
+    }
+    let payload = &frame[HEADER_LEN..];
+
+    match algorithm {
+        Compression::None => {
+            let mut message = vec![0; len];
+            let mut cursor = Cursor::new(payload);
+            cursor.read_exact(&mut message)?;
+            if cursor.position() as usize != payload.len() {
+                return Err(CodecError::BytesLeftOver(
+                    payload.len() - cursor.position() as usize,
+                ));
+            }
+            Ok(message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::ParameterizedDecode,
+        vdaf::{
+            prio3::{Prio3, Prio3InputShare},
+            Client,
+        },
+    };
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn negotiate() {
+        let none = CompressionFlags::default();
+        assert!(none.contains(Compression::None));
+        assert_eq!(none, CompressionFlags::new([Compression::None]));
+        assert_eq!(none.negotiate(&none), Compression::None);
+
+        assert_eq!(
+            CompressionFlags::get_decoded(&none.get_encoded().unwrap()).unwrap(),
+            none
+        );
+        // Unknown algorithms, and sets without `Compression::None`, are rejected.
+        for bad in [[0], [2], [3]] {
+            assert_matches!(
+                CompressionFlags::get_decoded(&bad),
+                Err(CodecError::UnexpectedValue)
+            );
+        }
+    }
+
+    #[test]
+    fn framed_input_share() {
+        let vdaf = Prio3::new_sum_vec(2, 1, 1000, 31).unwrap();
+        let (_, input_shares) = vdaf.shard(&vec![0; 1000], &[0; 16]).unwrap();
+        let leader = &input_shares[0];
+        let encoded = leader.get_encoded().unwrap();
+        let param = (&vdaf, 0);
+
+        let frame = compress(leader).unwrap();
+        assert_eq!(frame.len(), HEADER_LEN + encoded.len());
+        assert_eq!(peek_compression(&frame).unwrap(), Compression::None);
+        let message = decompress(&frame, encoded.len()).unwrap();
+        assert_eq!(message, encoded);
+        let decoded = Prio3InputShare::get_decoded_with_param(&param, &message).unwrap();
+        assert_eq!(&decoded, leader);
+
+        // The frame uses an unknown algorithm.
+        let mut bad = frame.clone();
+        bad[0] = 1;
+        assert_matches!(
+            decompress(&bad, encoded.len()),
+            Err(CodecError::UnexpectedValue)
+        );
+
+        // The message is longer than the receiver allows.
+        assert_matches!(
+            decompress(&frame, encoded.len() - 1),
+            Err(CodecError::LengthPrefixTooBig(_))
+        );
+
+        // The payload must have exactly the length in the header.
+        let mut bad = frame.clone();
+        bad.push(0);
+        assert_matches!(
+            decompress(&bad, encoded.len()),
+            Err(CodecError::BytesLeftOver(1))
+        );
+        assert!(decompress(&frame[..frame.len() - 1], encoded.len()).is_err());
+    }
+}
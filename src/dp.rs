@@ -16,6 +16,7 @@
 //!
 use num_bigint::{BigInt, BigUint, TryFromBigIntError};
 use num_rational::{BigRational, Ratio};
+use num_traits::{One, ToPrimitive, Zero};
 use serde::{Deserialize, Serialize};
 
 /// Errors propagated by methods in this module.
@@ -36,6 +37,14 @@ pub enum DpError {
     /// Tried to convert BigInt into something incompatible.
     #[error("DP error: {0}")]
     BigIntConversion(#[from] TryFromBigIntError<BigInt>),
+
+    /// A privacy parameter was outside of its valid range.
+    #[error("DP error: {0}")]
+    InvalidParameter(&'static str),
+
+    /// Spending the requested budget would exceed the configured limit.
+    #[error("DP error: privacy budget exceeded")]
+    BudgetExceeded,
 }
 
 /// Positive arbitrary precision rational number to represent DP and noise distribution parameters in
@@ -102,6 +111,84 @@ impl ZCdpBudget {
 
 impl DifferentialPrivacyBudget for ZCdpBudget {}
 
+/// Pure differential privacy budget, i.e. `epsilon`-DP or `(epsilon, 0)`-DP.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Ord, PartialOrd)]
+pub struct PureDpBudget {
+    epsilon: Ratio<BigUint>,
+}
+
+impl PureDpBudget {
+    /// Create a budget for parameter `epsilon`. Errors if `epsilon` is zero.
+    pub fn new(epsilon: Rational) -> Result<Self, DpError> {
+        if epsilon.0.is_zero() {
+            return Err(DpError::InvalidParameter("epsilon must be positive"));
+        }
+        Ok(Self { epsilon: epsilon.0 })
+    }
+}
+
+impl DifferentialPrivacyBudget for PureDpBudget {}
+
+/// Approximate differential privacy budget, i.e. `(epsilon, delta)`-DP.
+///
+/// There is no noise distribution in this module that is calibrated to this budget directly.
+/// Instead, [`ApproximateDpBudget::to_zcdp`] finds a zCDP budget that implies it, so that a task
+/// configured with `(epsilon, delta)` can use
+/// [`ZCdpDiscreteGaussian`](distributions::ZCdpDiscreteGaussian).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Ord, PartialOrd)]
+pub struct ApproximateDpBudget {
+    epsilon: Ratio<BigUint>,
+    delta: Ratio<BigUint>,
+}
+
+impl ApproximateDpBudget {
+    /// Create a budget for parameters `epsilon` and `delta`. Errors unless `epsilon` is positive
+    /// and `0 < delta < 1`.
+    pub fn new(epsilon: Rational, delta: Rational) -> Result<Self, DpError> {
+        if epsilon.0.is_zero() {
+            return Err(DpError::InvalidParameter("epsilon must be positive"));
+        }
+        if delta.0.is_zero() || delta.0 >= Ratio::one() {
+            return Err(DpError::InvalidParameter("delta must be in (0, 1)"));
+        }
+        Ok(Self {
+            epsilon: epsilon.0,
+            delta: delta.0,
+        })
+    }
+
+    /// Returns a zCDP budget whose guarantee implies this budget.
+    ///
+    /// By Proposition 1.3 of [[BS16]], `rho`-zCDP implies
+    /// `(rho + 2 * sqrt(rho * ln(1/delta)), delta)`-DP. This solves for the largest such `rho`.
+    /// The computation is carried out with floating-point numbers, so the result is rounded down
+    /// with a generous margin to make sure the returned budget is never weaker than required.
+    ///
+    /// [BS16]: https://arxiv.org/pdf/1605.02065.pdf
+    pub fn to_zcdp(&self) -> Result<ZCdpBudget, DpError> {
+        let epsilon = self.epsilon.to_f64().ok_or(DpError::InvalidFloat)?;
+        let delta = self.delta.to_f64().ok_or(DpError::InvalidFloat)?;
+        let log_inv_delta = -delta.ln();
+
+        // sqrt(rho) = sqrt(ln(1/delta) + epsilon) - sqrt(ln(1/delta)), rearranged to avoid
+        // cancellation. The budget is parametrized by `sqrt(2 * rho)`.
+        let sqrt_rho = epsilon / ((log_inv_delta + epsilon).sqrt() + log_inv_delta.sqrt());
+        let zcdp_epsilon = (2.0f64.sqrt() * sqrt_rho) * (1.0 - 1e-9);
+
+        match BigRational::from_float(zcdp_epsilon) {
+            Some(y) if zcdp_epsilon > 0.0 => Ok(ZCdpBudget {
+                epsilon: Ratio::<BigUint>::new(
+                    y.numer().clone().try_into()?,
+                    y.denom().clone().try_into()?,
+                ),
+            }),
+            _ => Err(DpError::InvalidFloat),
+        }
+    }
+}
+
+impl DifferentialPrivacyBudget for ApproximateDpBudget {}
+
 /// Strategy to make aggregate results differentially private, e.g. by adding noise from a specific
 /// type of distribution instantiated with a given DP budget.
 pub trait DifferentialPrivacyStrategy {
@@ -125,4 +212,50 @@ pub trait DifferentialPrivacyStrategy {
     fn create_distribution(&self, s: Self::Sensitivity) -> Result<Self::Distribution, DpError>;
 }
 
+/// A [`DifferentialPrivacyStrategy`] that keeps the budget it was created from, so that a
+/// [`PrivacyAccountant`](accountant::PrivacyAccountant) can charge it.
+pub trait DifferentialPrivacyStrategyWithBudget: DifferentialPrivacyStrategy {
+    /// Returns the budget this strategy was created from.
+    fn budget(&self) -> &Self::Budget;
+}
+
+pub mod accountant;
 pub mod distributions;
+pub mod randomized_response;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_validation() {
+        let zero = Rational::from_unsigned(0u8, 1).unwrap();
+        let one = Rational::from_unsigned(1u8, 1).unwrap();
+        let tiny = Rational::from_unsigned(1u32, 1_000_000).unwrap();
+
+        assert!(PureDpBudget::new(one.clone()).is_ok());
+        assert!(PureDpBudget::new(zero.clone()).is_err());
+
+        assert!(ApproximateDpBudget::new(one.clone(), tiny.clone()).is_ok());
+        assert!(ApproximateDpBudget::new(zero, tiny).is_err());
+        assert!(ApproximateDpBudget::new(one.clone(), one.clone()).is_err());
+        assert!(ApproximateDpBudget::new(one, Rational::from_unsigned(0u8, 1).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_approximate_dp_to_zcdp() {
+        let budget = ApproximateDpBudget::new(
+            Rational::from_unsigned(1u8, 1).unwrap(),
+            Rational::from_unsigned(1u32, 1_000_000).unwrap(),
+        )
+        .unwrap();
+        let zcdp = budget.to_zcdp().unwrap();
+
+        // rho = epsilon^2 / 2 must satisfy rho + 2 * sqrt(rho * ln(1/delta)) <= 1.
+        let zcdp_epsilon = zcdp.epsilon.to_f64().unwrap();
+        let rho = zcdp_epsilon * zcdp_epsilon / 2.0;
+        let implied_epsilon = rho + 2.0 * (rho * 1e6f64.ln()).sqrt();
+        assert!(implied_epsilon <= 1.0);
+        assert!(implied_epsilon > 0.999);
+    }
+}
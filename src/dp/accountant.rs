@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Accounting of the privacy budget spent across batches.
+//!
+//! Every aggregate released with differential privacy spends part of the privacy budget of the
+//! measurements in it. When a task collects the same Clients' measurements in many batches (e.g.,
+//! one per collection interval), the privacy loss adds up. [`PrivacyAccountant`] records how much
+//! of the budget each task has spent and refuses to noise an aggregate share once the next batch
+//! would exceed a configured limit.
+//!
+//! Budgets are composed with the basic composition theorem of their DP variant: `epsilon` is
+//! additive for pure DP, and `rho = (epsilon**2)/2` is additive for zCDP.
+
+use std::{collections::HashMap, hash::Hash};
+
+use num_bigint::BigUint;
+use num_rational::Ratio;
+use num_traits::Zero;
+
+use super::{
+    DifferentialPrivacyBudget, DifferentialPrivacyStrategyWithBudget, DpError, PureDpBudget,
+    ZCdpBudget,
+};
+use crate::vdaf::{AggregatorWithNoise, VdafError};
+
+mod private {
+    use num_bigint::BigUint;
+    use num_rational::Ratio;
+
+    pub trait Sealed {
+        /// The amount of privacy lost, in units that are additive under composition.
+        fn cost(&self) -> Ratio<BigUint>;
+    }
+}
+
+/// A differential privacy budget that can be tracked by a [`PrivacyAccountant`].
+///
+/// This trait is sealed.
+pub trait ComposableBudget: DifferentialPrivacyBudget + private::Sealed {}
+
+impl private::Sealed for PureDpBudget {
+    fn cost(&self) -> Ratio<BigUint> {
+        self.epsilon.clone()
+    }
+}
+
+impl ComposableBudget for PureDpBudget {}
+
+impl private::Sealed for ZCdpBudget {
+    fn cost(&self) -> Ratio<BigUint> {
+        self.epsilon.pow(2) / BigUint::from(2u8)
+    }
+}
+
+impl ComposableBudget for ZCdpBudget {}
+
+/// Tracks the privacy budget spent by each task, identified by a key of type `K`.
+///
+/// The accountant keeps its state in memory. An Aggregator that must enforce the limit across
+/// restarts has to persist which batches it has released and replay them with
+/// [`PrivacyAccountant::spend`] on startup.
+#[derive(Clone, Debug)]
+pub struct PrivacyAccountant<K, B> {
+    limit: B,
+    spent: HashMap<K, Ratio<BigUint>>,
+}
+
+impl<K: Eq + Hash, B: ComposableBudget> PrivacyAccountant<K, B> {
+    /// Creates an accountant that allows each task to spend at most `limit` in total.
+    pub fn new(limit: B) -> Self {
+        Self {
+            limit,
+            spent: HashMap::new(),
+        }
+    }
+
+    /// Returns true if `task` can spend `cost` without exceeding the limit.
+    pub fn can_spend(&self, task: &K, cost: &B) -> bool {
+        let spent = self.spent.get(task).cloned().unwrap_or_else(Ratio::zero);
+        spent + cost.cost() <= self.limit.cost()
+    }
+
+    /// Records that `task` spent `cost`. Errors, without recording anything, if this would exceed
+    /// the limit.
+    pub fn spend(&mut self, task: K, cost: &B) -> Result<(), DpError> {
+        if !self.can_spend(&task, cost) {
+            return Err(DpError::BudgetExceeded);
+        }
+        let spent = self.spent.entry(task).or_insert_with(Ratio::zero);
+        *spent += cost.cost();
+        Ok(())
+    }
+
+    /// Adds noise to an aggregate share with [`AggregatorWithNoise::add_noise_to_agg_share`] and
+    /// charges the budget of `dp_strategy` to `task`.
+    ///
+    /// If the task's remaining budget is insufficient, the aggregate share is left unchanged and
+    /// [`DpError::BudgetExceeded`] is returned. The aggregate share must then not be released.
+    pub fn add_noise_to_agg_share<V, S, const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize>(
+        &mut self,
+        task: K,
+        vdaf: &V,
+        dp_strategy: &S,
+        agg_param: &V::AggregationParam,
+        agg_share: &mut V::AggregateShare,
+        num_measurements: usize,
+    ) -> Result<(), VdafError>
+    where
+        V: AggregatorWithNoise<VERIFY_KEY_SIZE, NONCE_SIZE, S>,
+        S: DifferentialPrivacyStrategyWithBudget<Budget = B>,
+    {
+        if !self.can_spend(&task, dp_strategy.budget()) {
+            return Err(DpError::BudgetExceeded.into());
+        }
+        vdaf.add_noise_to_agg_share(dp_strategy, agg_param, agg_share, num_measurements)?;
+        self.spend(task, dp_strategy.budget())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dp::{distributions::PureDpDiscreteLaplace, DifferentialPrivacyStrategy, Rational},
+        vdaf::{prio3::Prio3, Aggregator},
+    };
+    use assert_matches::assert_matches;
+
+    fn pure_dp(n: u8, d: u8) -> PureDpBudget {
+        PureDpBudget::new(Rational::from_unsigned(n, d).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_spend() {
+        let mut accountant = PrivacyAccountant::new(pure_dp(1, 1));
+        accountant.spend("a", &pure_dp(1, 2)).unwrap();
+        accountant.spend("a", &pure_dp(1, 2)).unwrap();
+        assert!(!accountant.can_spend(&"a", &pure_dp(1, 100)));
+        assert_matches!(
+            accountant.spend("a", &pure_dp(1, 100)),
+            Err(DpError::BudgetExceeded)
+        );
+
+        // Tasks are tracked separately.
+        assert!(accountant.can_spend(&"b", &pure_dp(1, 1)));
+        assert_matches!(
+            accountant.spend("b", &pure_dp(2, 1)),
+            Err(DpError::BudgetExceeded)
+        );
+        accountant.spend("b", &pure_dp(1, 1)).unwrap();
+    }
+
+    #[test]
+    fn test_zcdp_composition() {
+        // Four batches at epsilon = 1/2 compose to rho = 4 * 1/8 = 1/2, i.e. epsilon = 1.
+        let zcdp = |n: u8, d: u8| ZCdpBudget::new(Rational::from_unsigned(n, d).unwrap());
+        let mut accountant = PrivacyAccountant::new(zcdp(1, 1));
+        for _ in 0..4 {
+            accountant.spend(0, &zcdp(1, 2)).unwrap();
+        }
+        assert!(!accountant.can_spend(&0, &zcdp(1, 100)));
+    }
+
+    #[test]
+    fn test_add_noise_to_agg_share() {
+        let vdaf = Prio3::new_histogram(2, 4, 2).unwrap();
+        let strategy = PureDpDiscreteLaplace::from_budget(pure_dp(1, 1));
+        let mut agg_share = vdaf.aggregate(&(), []).unwrap();
+        let mut accountant = PrivacyAccountant::new(pure_dp(3, 2));
+
+        accountant
+            .add_noise_to_agg_share(1, &vdaf, &strategy, &(), &mut agg_share, 0)
+            .unwrap();
+
+        let before = agg_share.clone();
+        assert_matches!(
+            accountant.add_noise_to_agg_share(1, &vdaf, &strategy, &(), &mut agg_share, 0),
+            Err(VdafError::Dp(DpError::BudgetExceeded))
+        );
+        assert_eq!(agg_share, before);
+    }
+}
@@ -44,7 +44,7 @@
 //   The following code is adapted from the opendp implementation to reduce dependencies:
 //       https://github.com/opendp/opendp/blob/main/rust/src/traits/samplers/cks20
 
-//! Implementation of a sampler from the Discrete Gaussian Distribution.
+//! Implementation of samplers from the Discrete Gaussian and Discrete Laplace Distributions.
 //!
 //! Follows
 //!     Clément Canonne, Gautam Kamath, Thomas Steinke. The Discrete Gaussian for Differential Privacy. 2020.
@@ -60,7 +60,7 @@ use serde::{Deserialize, Serialize};
 
 use super::{
     DifferentialPrivacyBudget, DifferentialPrivacyDistribution, DifferentialPrivacyStrategy,
-    DpError, ZCdpBudget,
+    DifferentialPrivacyStrategyWithBudget, DpError, PureDpBudget, ZCdpBudget,
 };
 
 /// Sample from the Bernoulli(gamma) distribution, where $gamma /leq 1$.
@@ -70,7 +70,7 @@ use super::{
 /// on page 30 of [[CKS20]].
 ///
 /// [CKS20]: https://arxiv.org/pdf/2004.00010.pdf
-fn sample_bernoulli<R: Rng + ?Sized>(gamma: &Ratio<BigUint>, rng: &mut R) -> bool {
+pub(crate) fn sample_bernoulli<R: Rng + ?Sized>(gamma: &Ratio<BigUint>, rng: &mut R) -> bool {
     let d = gamma.denom();
     assert!(!d.is_zero());
     assert!(gamma <= &Ratio::<BigUint>::one());
@@ -283,10 +283,92 @@ impl DifferentialPrivacyStrategy for DiscreteGaussianDpStrategy<ZCdpBudget> {
         &self,
         sensitivity: Ratio<BigUint>,
     ) -> Result<DiscreteGaussian, DpError> {
+        if self.budget.epsilon.is_zero() {
+            return Err(DpError::InvalidParameter("epsilon must be positive"));
+        }
         DiscreteGaussian::new(sensitivity / self.budget.epsilon.clone())
     }
 }
 
+impl DifferentialPrivacyStrategyWithBudget for DiscreteGaussianDpStrategy<ZCdpBudget> {
+    fn budget(&self) -> &ZCdpBudget {
+        &self.budget
+    }
+}
+
+/// Samples `BigInt` numbers according to the discrete Laplace distribution with mean zero.
+/// The distribution is defined over the integers, represented by arbitrary-precision integers.
+/// The sampling procedure follows [[CKS20]].
+///
+/// [CKS20]: https://arxiv.org/pdf/2004.00010.pdf
+#[derive(Clone, Debug)]
+pub struct DiscreteLaplace {
+    /// The scale parameter of the distribution.
+    scale: Ratio<BigUint>,
+}
+
+impl DiscreteLaplace {
+    /// Create a new sampler from the Discrete Laplace Distribution with the given
+    /// scale parameter and mean zero. Errors if the input has denominator zero.
+    pub fn new(scale: Ratio<BigUint>) -> Result<DiscreteLaplace, DpError> {
+        if scale.denom().is_zero() {
+            return Err(DpError::ZeroDenominator);
+        }
+        Ok(DiscreteLaplace { scale })
+    }
+}
+
+impl Distribution<BigInt> for DiscreteLaplace {
+    fn sample<R>(&self, rng: &mut R) -> BigInt
+    where
+        R: Rng + ?Sized,
+    {
+        sample_discrete_laplace(&self.scale, rng)
+    }
+}
+
+impl DifferentialPrivacyDistribution for DiscreteLaplace {}
+
+/// A DP strategy using the discrete Laplace distribution.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Ord, PartialOrd)]
+pub struct DiscreteLaplaceDpStrategy<B>
+where
+    B: DifferentialPrivacyBudget,
+{
+    budget: B,
+}
+
+/// A DP strategy using the discrete Laplace distribution providing pure DP.
+pub type PureDpDiscreteLaplace = DiscreteLaplaceDpStrategy<PureDpBudget>;
+
+impl DifferentialPrivacyStrategy for DiscreteLaplaceDpStrategy<PureDpBudget> {
+    type Budget = PureDpBudget;
+    type Distribution = DiscreteLaplace;
+    type Sensitivity = Ratio<BigUint>;
+
+    fn from_budget(budget: PureDpBudget) -> DiscreteLaplaceDpStrategy<PureDpBudget> {
+        DiscreteLaplaceDpStrategy { budget }
+    }
+
+    /// Create a new sampler from the Discrete Laplace Distribution with a scale calibrated to
+    /// provide `epsilon`-differential privacy when added to the result of an integer-valued
+    /// function with L1 sensitivity `sensitivity`, following Theorem 29 from [[CKS20]]
+    ///
+    /// [CKS20]: https://arxiv.org/pdf/2004.00010.pdf
+    fn create_distribution(&self, sensitivity: Ratio<BigUint>) -> Result<DiscreteLaplace, DpError> {
+        if self.budget.epsilon.is_zero() {
+            return Err(DpError::InvalidParameter("epsilon must be positive"));
+        }
+        DiscreteLaplace::new(sensitivity / self.budget.epsilon.clone())
+    }
+}
+
+impl DifferentialPrivacyStrategyWithBudget for DiscreteLaplaceDpStrategy<PureDpBudget> {
+    fn budget(&self) -> &PureDpBudget {
+        &self.budget
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -345,6 +427,69 @@ mod tests {
         assert_eq!(samples2, samples1);
     }
 
+    #[test]
+    /// Make sure that the distribution created by `create_distribution`
+    /// of `PureDpDiscreteLaplace` is the same one as manually creating one
+    /// by using the constructor of `DiscreteLaplace` directly.
+    fn test_pure_dp_discrete_laplace() {
+        // sample from a manually created distribution
+        let sampler1 =
+            DiscreteLaplace::new(Ratio::<BigUint>::from_integer(BigUint::from(4u8))).unwrap();
+        let mut rng = SeedStreamTurboShake128::from_seed([0u8; 16]);
+        let samples1: Vec<i8> = (0..10)
+            .map(|_| i8::try_from(sampler1.sample(&mut rng)).unwrap())
+            .collect();
+
+        // sample from the distribution created by the `pure_dp` strategy
+        let pure_dp = PureDpDiscreteLaplace::from_budget(
+            PureDpBudget::new(Rational::try_from(0.25).unwrap()).unwrap(),
+        );
+        let sampler2 = pure_dp
+            .create_distribution(Ratio::<BigUint>::from_integer(1u8.into()))
+            .unwrap();
+        let mut rng2 = SeedStreamTurboShake128::from_seed([0u8; 16]);
+        let samples2: Vec<i8> = (0..10)
+            .map(|_| i8::try_from(sampler2.sample(&mut rng2)).unwrap())
+            .collect();
+
+        assert_eq!(samples2, samples1);
+    }
+
+    #[test]
+    /// A budget with epsilon zero, which `PureDpBudget::new` rejects but deserialization does not,
+    /// is an error rather than a division by zero.
+    fn test_pure_dp_discrete_laplace_zero_epsilon() {
+        let encoded = serde_json::to_string(&PureDpBudget {
+            epsilon: Ratio::from_integer(BigUint::zero()),
+        })
+        .unwrap();
+        let pure_dp = PureDpDiscreteLaplace::from_budget(serde_json::from_str(&encoded).unwrap());
+        assert!(matches!(
+            pure_dp.create_distribution(Ratio::<BigUint>::from_integer(1u8.into())),
+            Err(DpError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_discrete_laplace_mean() {
+        // The variance of the discrete Laplace distribution with scale t is
+        // 2 * e^(-1/t) / (1 - e^(-1/t))^2.
+        let scale = 3.0f64;
+        let q = (-1.0 / scale).exp();
+        let var = 2.0 * q / ((1.0 - q) * (1.0 - q));
+
+        let sampler =
+            DiscreteLaplace::new(Ratio::<BigUint>::from_integer(BigUint::from(3u8))).unwrap();
+        let mut rng = SeedStreamTurboShake128::from_seed([0u8; 16]);
+        assert!(test_mean(
+            || sampler.sample(&mut rng),
+            0.0,
+            var,
+            0.00001,
+            1000
+        ));
+    }
+
     pub fn test_mean<FS: FnMut() -> BigInt>(
         mut sampler: FS,
         hyp_mean: f64,
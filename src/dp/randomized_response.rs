@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Client-side randomized response, a local differential privacy mechanism.
+//!
+//! With [`RandomizedResponse`], each Client perturbs its own measurement before sharing it, so the
+//! privacy of the measurement does not depend on the Aggregators at all. A Client with a boolean
+//! measurement for [`Prio3Count`](crate::vdaf::prio3::Prio3Count) flips it with probability `q`;
+//! this provides `ln((1 - q) / q)`-DP. A Client with a bucket index for
+//! [`Prio3Histogram`](crate::vdaf::prio3::Prio3Histogram) keeps it with probability `1 - q` and
+//! otherwise replaces it with one of the other buckets, chosen uniformly at random.
+//!
+//! The aggregate of perturbed measurements is biased towards the uniform distribution. The
+//! Collector removes the bias with [`RandomizedResponse::debias_count`] or
+//! [`RandomizedResponse::debias_histogram`], which return unbiased (but noisy) estimates of the
+//! true aggregate.
+
+use num_bigint::BigUint;
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+use rand::Rng;
+
+use super::{distributions::sample_bernoulli, DpError, Rational};
+
+/// Randomized response with a fixed flip probability.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RandomizedResponse {
+    flip_probability: Ratio<BigUint>,
+}
+
+impl RandomizedResponse {
+    /// Create a mechanism that perturbs each measurement with probability `flip_probability`.
+    /// Errors unless `flip_probability` is less than `1/2`.
+    pub fn new(flip_probability: Rational) -> Result<Self, DpError> {
+        if flip_probability.0 >= Ratio::new(1u8.into(), 2u8.into()) {
+            return Err(DpError::InvalidParameter(
+                "flip probability must be less than 1/2",
+            ));
+        }
+        Ok(Self {
+            flip_probability: flip_probability.0,
+        })
+    }
+
+    /// Perturb a boolean measurement: flip it with the configured probability.
+    pub fn privatize_count<R: Rng + ?Sized>(&self, measurement: bool, rng: &mut R) -> bool {
+        measurement ^ sample_bernoulli(&self.flip_probability, rng)
+    }
+
+    /// Perturb a bucket index for a histogram with `length` buckets: with the configured
+    /// probability, replace it with one of the other buckets, chosen uniformly at random.
+    pub fn privatize_histogram<R: Rng + ?Sized>(
+        &self,
+        measurement: usize,
+        length: usize,
+        rng: &mut R,
+    ) -> Result<usize, DpError> {
+        if length < 2 {
+            return Err(DpError::InvalidParameter(
+                "histogram must have at least two buckets",
+            ));
+        }
+        if measurement >= length {
+            return Err(DpError::InvalidParameter(
+                "measurement is out of range for the histogram",
+            ));
+        }
+
+        if !sample_bernoulli(&self.flip_probability, rng) {
+            return Ok(measurement);
+        }
+        let other = rng.gen_range(0..length - 1);
+        Ok(if other >= measurement {
+            other + 1
+        } else {
+            other
+        })
+    }
+
+    /// Estimate the number of true measurements from the aggregate of `num_measurements`
+    /// perturbed boolean measurements.
+    ///
+    /// The estimate is unbiased, and so may be negative or exceed `num_measurements`.
+    pub fn debias_count(&self, aggregate: u64, num_measurements: usize) -> Result<f64, DpError> {
+        let q = self.flip_probability_f64()?;
+        // E[aggregate] = count * (1 - q) + (num_measurements - count) * q
+        Ok((aggregate as f64 - num_measurements as f64 * q) / (1.0 - 2.0 * q))
+    }
+
+    /// Estimate the number of measurements in each bucket from the aggregate of
+    /// `num_measurements` perturbed bucket indices.
+    ///
+    /// The estimates are unbiased, and so may be negative or exceed `num_measurements`.
+    pub fn debias_histogram(
+        &self,
+        aggregate: &[u128],
+        num_measurements: usize,
+    ) -> Result<Vec<f64>, DpError> {
+        if aggregate.len() < 2 {
+            return Err(DpError::InvalidParameter(
+                "histogram must have at least two buckets",
+            ));
+        }
+        let q = self.flip_probability_f64()?;
+        // The probability that a measurement is replaced with a particular other bucket.
+        let r = q / (aggregate.len() - 1) as f64;
+        // E[aggregate[i]] = count[i] * (1 - q) + (num_measurements - count[i]) * r
+        Ok(aggregate
+            .iter()
+            .map(|c| (*c as f64 - num_measurements as f64 * r) / (1.0 - q - r))
+            .collect())
+    }
+
+    fn flip_probability_f64(&self) -> Result<f64, DpError> {
+        self.flip_probability.to_f64().ok_or(DpError::InvalidFloat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::xof::SeedStreamTurboShake128;
+    use crate::vdaf::{
+        prio3::{Prio3Count, Prio3Histogram},
+        test_utils::run_vdaf,
+    };
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(RandomizedResponse::new(Rational::from_unsigned(1u8, 2).unwrap()).is_err());
+        let rr = RandomizedResponse::new(Rational::from_unsigned(1u8, 4).unwrap()).unwrap();
+        let mut rng = SeedStreamTurboShake128::from_seed([0u8; 16]);
+        assert!(rr.privatize_histogram(0, 1, &mut rng).is_err());
+        assert!(rr.privatize_histogram(4, 4, &mut rng).is_err());
+        assert!(rr.debias_histogram(&[1], 1).is_err());
+    }
+
+    #[test]
+    fn test_no_flips() {
+        let rr = RandomizedResponse::new(Rational::from_unsigned(0u8, 1).unwrap()).unwrap();
+        let mut rng = SeedStreamTurboShake128::from_seed([0u8; 16]);
+        assert!(rr.privatize_count(true, &mut rng));
+        assert!(!rr.privatize_count(false, &mut rng));
+        assert_eq!(rr.privatize_histogram(2, 3, &mut rng).unwrap(), 2);
+        assert_eq!(rr.debias_count(3, 5).unwrap(), 3.0);
+        assert_eq!(rr.debias_histogram(&[1, 2, 3], 6).unwrap(), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_count() {
+        let rr = RandomizedResponse::new(Rational::from_unsigned(1u8, 4).unwrap()).unwrap();
+        let mut rng = SeedStreamTurboShake128::from_seed([0u8; 16]);
+        let measurements: Vec<bool> = (0..2000)
+            .map(|i| rr.privatize_count(i % 4 == 0, &mut rng))
+            .collect();
+
+        let vdaf = Prio3Count::new_count(2).unwrap();
+        let aggregate = run_vdaf(&vdaf, &(), measurements).unwrap();
+        let estimate = rr.debias_count(aggregate, 2000).unwrap();
+        // The true count is 500; the standard deviation of the estimate is about 39.
+        assert!((estimate - 500.0).abs() < 200.0, "estimate {estimate}");
+    }
+
+    #[test]
+    fn test_histogram() {
+        let rr = RandomizedResponse::new(Rational::from_unsigned(1u8, 5).unwrap()).unwrap();
+        let mut rng = SeedStreamTurboShake128::from_seed([0u8; 16]);
+        let measurements: Vec<usize> = (0..2000)
+            .map(|i| rr.privatize_histogram(i % 2, 4, &mut rng).unwrap())
+            .collect();
+
+        let vdaf = Prio3Histogram::new_histogram(2, 4, 2).unwrap();
+        let aggregate = run_vdaf(&vdaf, &(), measurements).unwrap();
+        let estimate = rr.debias_histogram(&aggregate, 2000).unwrap();
+        for (estimate, want) in estimate.into_iter().zip([1000.0, 1000.0, 0.0, 0.0]) {
+            assert!((estimate - want).abs() < 200.0, "estimate {estimate}");
+        }
+    }
+}
@@ -1,12 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! This module implements an iterative FFT algorithm for computing the (inverse) Discrete Fourier
-//! Transform (DFT) over a slice of field elements.
+//! Transform (DFT) over a slice of field elements. The [`FftBackend`] trait allows the transforms
+//! performed during Prio2 validation to be computed elsewhere.
 
 use crate::field::FftFriendlyFieldElement;
 use crate::fp::{log2, MAX_ROOTS};
+use crate::polynomial::poly_eval;
 
-use std::convert::TryFrom;
+use core::{convert::TryFrom, fmt::Debug};
 
 /// An error returned by an FFT operation.
 #[derive(Debug, PartialEq, Eq, thiserror::Error)]
@@ -21,6 +23,9 @@ pub enum FftError {
     /// The specified size is not a power of 2.
     #[error("size is not a power of 2")]
     SizeInvalid,
+    /// A batch has a different number of inputs and outputs.
+    #[error("number of inputs and outputs in batch do not match")]
+    BatchLengthMismatch,
 }
 
 /// Sets `outp` to the DFT of `inp`.
@@ -29,10 +34,26 @@ pub enum FftError {
 /// evaluated at points `p^0, p^1, ... p^(size-1)`, where `p` is the `2^size`-th principal root of
 /// unity.
 #[allow(clippy::many_single_char_names)]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "trace", skip_all, fields(size))
+)]
 pub fn discrete_fourier_transform<F: FftFriendlyFieldElement>(
     outp: &mut [F],
     inp: &[F],
     size: usize,
+) -> Result<(), FftError> {
+    dft(outp, inp, size, None)
+}
+
+/// Sets `outp` to the DFT of `inp`, taking the twiddle factors from `roots` if given, or computing
+/// them otherwise. `roots` holds the first `size / 2` powers of the principal `size`-th root of
+/// unity.
+fn dft<F: FftFriendlyFieldElement>(
+    outp: &mut [F],
+    inp: &[F],
+    size: usize,
+    roots: Option<&[F]>,
 ) -> Result<(), FftError> {
     let d = usize::try_from(log2(size as u128)).map_err(|_| FftError::SizeTooLarge)?;
 
@@ -74,7 +95,11 @@ pub fn discrete_fourier_transform<F: FftFriendlyFieldElement>(
         }
 
         for i in 1..y {
-            w *= r;
+            w = match roots {
+                // The 2^l-th root of unity is the 2^(d-l)-th power of the 2^d-th root.
+                Some(roots) => roots[i << (d - l)],
+                None => w * r,
+            };
             for j in 0..chunk {
                 let x = (j << l) + i;
                 let u = outp[x];
@@ -88,6 +113,58 @@ pub fn discrete_fourier_transform<F: FftFriendlyFieldElement>(
     Ok(())
 }
 
+/// The roots of unity used by a DFT of a fixed size, and the inverse of the size. A caller that
+/// computes many transforms of the same size, such as a Client proving many measurements of the
+/// same type, can compute these once rather than in every transform.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FftRoots<F> {
+    size: usize,
+    /// The first `size / 2` powers of the principal `size`-th root of unity.
+    roots: Vec<F>,
+    size_inv: F,
+}
+
+impl<F: FftFriendlyFieldElement> FftRoots<F> {
+    /// Computes the roots for transforms of `size` elements, which must be a power of 2.
+    pub(crate) fn new(size: usize) -> Result<Self, FftError> {
+        let d = usize::try_from(log2(size as u128)).map_err(|_| FftError::SizeTooLarge)?;
+        if size > 1 << MAX_ROOTS {
+            return Err(FftError::SizeTooLarge);
+        }
+        if size != 1 << d {
+            return Err(FftError::SizeInvalid);
+        }
+        let root = F::root(d).unwrap();
+        let roots = std::iter::successors(Some(F::one()), |x| Some(*x * root))
+            .take(size / 2)
+            .collect();
+        let size_inv =
+            F::from(F::Integer::try_from(size).map_err(|_| FftError::SizeTooLarge)?).inv();
+        Ok(Self {
+            size,
+            roots,
+            size_inv,
+        })
+    }
+
+    /// Returns the size of the transforms.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Sets `outp` to the DFT of `inp`, as in [`discrete_fourier_transform`].
+    pub(crate) fn dft(&self, outp: &mut [F], inp: &[F]) -> Result<(), FftError> {
+        dft(outp, inp, self.size, Some(&self.roots))
+    }
+
+    /// Sets `outp` to the inverse of the DFT of `inp`.
+    pub(crate) fn inv_dft(&self, outp: &mut [F], inp: &[F]) -> Result<(), FftError> {
+        self.dft(outp, inp)?;
+        discrete_fourier_transform_inv_finish(outp, self.size, self.size_inv);
+        Ok(())
+    }
+}
+
 /// Sets `outp` to the inverse of the DFT of `inp`.
 #[cfg(test)]
 pub(crate) fn discrete_fourier_transform_inv<F: FftFriendlyFieldElement>(
@@ -118,6 +195,76 @@ pub(crate) fn discrete_fourier_transform_inv_finish<F: FftFriendlyFieldElement>(
     }
 }
 
+/// An implementation of the DFT that can be swapped in where many transforms of the same size are
+/// computed together, such as when validating a batch of reports.
+///
+/// [`CpuFftBackend`] is the default. Other implementations may offload the work to an accelerator;
+/// they must produce the same output as [`discrete_fourier_transform`].
+pub trait FftBackend<F: FftFriendlyFieldElement>: Debug + Send + Sync {
+    /// Sets `outp` to the DFT of `inp`, as in [`discrete_fourier_transform`].
+    fn fft(&self, outp: &mut [F], inp: &[F], size: usize) -> Result<(), FftError>;
+
+    /// Sets each of `outps` to the DFT of the corresponding element of `inps`. Every transform has
+    /// the same `size`.
+    ///
+    /// The default implementation calls [`FftBackend::fft`] once per input.
+    fn fft_batch(
+        &self,
+        outps: &mut [&mut [F]],
+        inps: &[&[F]],
+        size: usize,
+    ) -> Result<(), FftError> {
+        if outps.len() != inps.len() {
+            return Err(FftError::BatchLengthMismatch);
+        }
+        for (outp, inp) in outps.iter_mut().zip(inps) {
+            self.fft(outp, inp, size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the DFT on the CPU using [`discrete_fourier_transform`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CpuFftBackend;
+
+impl<F: FftFriendlyFieldElement> FftBackend<F> for CpuFftBackend {
+    fn fft(&self, outp: &mut [F], inp: &[F], size: usize) -> Result<(), FftError> {
+        discrete_fourier_transform(outp, inp, size)
+    }
+}
+
+/// Computes the DFT on the CPU by evaluating the input polynomial at each power of the root of
+/// unity with Horner's method. This takes time quadratic in the size, so it is only meant as an
+/// independent reference for the FFT, e.g. to check an optimized backend in production with a
+/// `DualAccumulator`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NaiveFftBackend;
+
+impl<F: FftFriendlyFieldElement> FftBackend<F> for NaiveFftBackend {
+    fn fft(&self, outp: &mut [F], inp: &[F], size: usize) -> Result<(), FftError> {
+        let d = usize::try_from(log2(size as u128)).map_err(|_| FftError::SizeTooLarge)?;
+        if size > outp.len() {
+            return Err(FftError::OutputTooSmall);
+        }
+        if size > 1 << MAX_ROOTS {
+            return Err(FftError::SizeTooLarge);
+        }
+        if size != 1 << d {
+            return Err(FftError::SizeInvalid);
+        }
+
+        let coeffs = &inp[..inp.len().min(size)];
+        let root = F::root(d).unwrap();
+        let mut x = F::one();
+        for outp_val in outp[..size].iter_mut() {
+            *outp_val = poly_eval(coeffs, x);
+            x *= root;
+        }
+        Ok(())
+    }
+}
+
 // bitrev returns the first d bits of x in reverse order. (Thanks, OEIS! https://oeis.org/A030109)
 fn bitrev(d: usize, x: usize) -> usize {
     x.reverse_bits() >> (usize::BITS - d as u32)
@@ -161,6 +308,86 @@ mod tests {
         discrete_fourier_transform_then_inv_test::<Field128>().expect("unexpected error");
     }
 
+    #[test]
+    fn test_fft_roots() {
+        for size in [1, 2, 4, 8, 256, 2048] {
+            let roots = FftRoots::<Field64>::new(size).unwrap();
+            assert_eq!(roots.size(), size);
+            let inp = random_vector(size).unwrap();
+            let mut want = vec![Field64::zero(); size];
+            let mut got = vec![Field64::zero(); size];
+            discrete_fourier_transform(&mut want, &inp, size).unwrap();
+            roots.dft(&mut got, &inp).unwrap();
+            assert_eq!(got, want);
+
+            roots.inv_dft(&mut got, &want).unwrap();
+            assert_eq!(got, inp);
+        }
+        assert_eq!(
+            FftRoots::<Field64>::new(3).unwrap_err(),
+            FftError::SizeInvalid
+        );
+        assert_eq!(
+            FftRoots::<Field64>::new(4)
+                .unwrap()
+                .dft(&mut [Field64::zero(); 2], &[Field64::one()]),
+            Err(FftError::OutputTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_fft_batch() {
+        let size = 64;
+        let inps: Vec<Vec<Field64>> = (0..3).map(|_| random_vector(size).unwrap()).collect();
+        let inps: Vec<&[Field64]> = inps.iter().map(Vec::as_slice).collect();
+        let mut got = vec![vec![Field64::zero(); size]; 3];
+        let mut outps: Vec<&mut [Field64]> = got.iter_mut().map(Vec::as_mut_slice).collect();
+        CpuFftBackend.fft_batch(&mut outps, &inps, size).unwrap();
+
+        for (inp, got) in inps.iter().zip(got.iter()) {
+            let mut want = vec![Field64::zero(); size];
+            discrete_fourier_transform(&mut want, inp, size).unwrap();
+            assert_eq!(got, &want);
+        }
+
+        let mut outps: Vec<&mut [Field64]> = got.iter_mut().map(Vec::as_mut_slice).collect();
+        assert_eq!(
+            CpuFftBackend.fft_batch(&mut outps, &inps[..2], size),
+            Err(FftError::BatchLengthMismatch)
+        );
+    }
+
+    #[test]
+    fn test_naive_fft_backend() {
+        for size in [1, 2, 16, 128] {
+            let inp: Vec<FieldPrio2> = random_vector(size).unwrap();
+            let mut want = vec![FieldPrio2::zero(); size];
+            let mut got = vec![FieldPrio2::zero(); size];
+            discrete_fourier_transform(&mut want, &inp, size).unwrap();
+            NaiveFftBackend.fft(&mut got, &inp, size).unwrap();
+            assert_eq!(got, want);
+
+            // Inputs shorter than the size are padded with zeros.
+            if size > 1 {
+                discrete_fourier_transform(&mut want, &inp[..size / 2], size).unwrap();
+                NaiveFftBackend
+                    .fft(&mut got, &inp[..size / 2], size)
+                    .unwrap();
+                assert_eq!(got, want);
+            }
+        }
+
+        let mut outp = vec![Field64::zero(); 6];
+        assert_eq!(
+            NaiveFftBackend.fft(&mut outp, &[Field64::one()], 6),
+            Err(FftError::SizeInvalid)
+        );
+        assert_eq!(
+            NaiveFftBackend.fft(&mut outp, &[Field64::one()], 8),
+            Err(FftError::OutputTooSmall)
+        );
+    }
+
     #[test]
     fn test_recursive_fft() {
         let size = 128;
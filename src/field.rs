@@ -618,6 +618,18 @@ macro_rules! make_field {
             }
         }
 
+        /// Generates a field element by reducing an arbitrary integer modulo the field prime.
+        #[cfg(feature = "test-util")]
+        impl<'a> arbitrary::Arbitrary<'a> for $elem {
+            fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+                Ok(Self::from(<$int_conversion as arbitrary::Arbitrary>::arbitrary(u)?))
+            }
+
+            fn size_hint(depth: usize) -> (usize, Option<usize>) {
+                <$int_conversion as arbitrary::Arbitrary>::size_hint(depth)
+            }
+        }
+
         impl From<$int_conversion> for $elem {
             fn from(x: $int_conversion) -> Self {
                 // FieldParameters::montgomery() will return a value that has been fully reduced
@@ -841,6 +853,43 @@ make_field!(
     16,
 );
 
+pub(crate) mod lanes {
+    pub trait Sealed: Copy {
+        /// The modulus of the field.
+        const LANE_MODULUS: u64;
+
+        /// Returns the internal representation of the element. Sums of internal representations
+        /// reduced modulo the modulus are the internal representations of the sums of elements.
+        fn to_lane(self) -> u64;
+
+        /// Returns the element with internal representation `lane`, which must be less than the
+        /// modulus.
+        fn from_lane(lane: u64) -> Self;
+    }
+}
+
+/// A field whose elements can be summed as `u64` integers with deferred reduction, as
+/// [`LaneAccumulator`](crate::vdaf::accumulator::LaneAccumulator) does.
+///
+/// This trait is sealed.
+pub trait SmallFieldElement: FieldElement + lanes::Sealed {}
+
+impl lanes::Sealed for FieldPrio2 {
+    const LANE_MODULUS: u64 = FP32.p as u64;
+
+    fn to_lane(self) -> u64 {
+        // The internal representation is less than the modulus, which fits in 32 bits.
+        self.0 as u64
+    }
+
+    fn from_lane(lane: u64) -> Self {
+        debug_assert!(lane < Self::LANE_MODULUS);
+        Self(u128::from(lane))
+    }
+}
+
+impl SmallFieldElement for FieldPrio2 {}
+
 /// Merge two vectors of fields by summing other_vector into accumulator.
 ///
 /// # Errors
@@ -1157,6 +1206,23 @@ mod tests {
         assert_matches!(result, Err(FieldError::InputSizeMismatch));
     }
 
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let bytes = [0xff; 32];
+        let mut u = Unstructured::new(&bytes);
+        assert_eq!(Field64::arbitrary(&mut u).unwrap(), Field64::from(u64::MAX));
+        assert_eq!(
+            Field128::arbitrary(&mut u).unwrap(),
+            Field128::from(u128::MAX)
+        );
+        assert_eq!(
+            FieldPrio2::arbitrary(&mut u).unwrap(),
+            FieldPrio2::from(u32::MAX)
+        );
+    }
+
     fn field_element_test<F: FftFriendlyFieldElement + Hash>() {
         field_element_test_common::<F>();
 
@@ -48,16 +48,25 @@
 
 #[cfg(feature = "experimental")]
 use crate::dp::DifferentialPrivacyStrategy;
-use crate::fft::{discrete_fourier_transform, discrete_fourier_transform_inv_finish, FftError};
+use crate::fft::{
+    discrete_fourier_transform, discrete_fourier_transform_inv_finish, FftError, FftRoots,
+};
 use crate::field::{FftFriendlyFieldElement, FieldElement, FieldElementWithInteger, FieldError};
 use crate::fp::log2;
 use crate::polynomial::poly_eval;
 use std::any::Any;
+use std::borrow::Cow;
 use std::convert::TryFrom;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use subtle::ConstantTimeEq;
 
 pub mod gadgets;
-#[cfg(all(feature = "experimental", test))]
+pub mod soundness;
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "crypto-dependencies", feature = "experimental")))
+)]
 pub mod szk;
 pub mod types;
 
@@ -227,6 +236,19 @@ pub trait Type: Sized + Eq + Clone + Debug {
         input: &[Self::Field],
         prove_rand: &[Self::Field],
         joint_rand: &[Self::Field],
+    ) -> Result<Vec<Self::Field>, FlpError> {
+        self.prove_with_roots(input, prove_rand, joint_rand, &WireRoots::default())
+    }
+
+    /// Like [`Self::prove`], but interpolates the wire polynomials with the roots of unity in
+    /// `roots`, so that a prover that generates many proofs for this type computes them only once.
+    /// Roots of sizes that are missing from `roots` are computed for this proof.
+    fn prove_with_roots(
+        &self,
+        input: &[Self::Field],
+        prove_rand: &[Self::Field],
+        joint_rand: &[Self::Field],
+        roots: &WireRoots<Self::Field>,
     ) -> Result<Vec<Self::Field>, FlpError> {
         if input.len() != self.input_len() {
             return Err(FlpError::Prove(format!(
@@ -311,18 +333,17 @@ pub trait Type: Sized + Eq + Clone + Debug {
             // Interpolate the wire polynomials `f[0], ..., f[g_arity-1]` from the input wires of each
             // evaluation of the gadget.
             let m = wire_poly_len(gadget.calls());
-            let m_inv = Self::Field::from(
-                <Self::Field as FieldElementWithInteger>::Integer::try_from(m).unwrap(),
-            )
-            .inv();
+            let roots = match roots.get(m) {
+                Some(roots) => Cow::Borrowed(roots),
+                None => Cow::Owned(FftRoots::new(m)?),
+            };
             let mut f = vec![vec![Self::Field::zero(); m]; gadget.arity()];
             for ((coefficients, values), proof_val) in f[..gadget.arity()]
                 .iter_mut()
                 .zip(gadget.f_vals[..gadget.arity()].iter())
                 .zip(proof[proof_len..proof_len + gadget.arity()].iter_mut())
             {
-                discrete_fourier_transform(coefficients, values, m)?;
-                discrete_fourier_transform_inv_finish(coefficients, m, m_inv);
+                roots.inv_dft(coefficients, values)?;
 
                 // The first point on each wire polynomial is a random value chosen by the prover. This
                 // point is stored in the proof so that the verifier can reconstruct the wire
@@ -482,10 +503,11 @@ pub trait Type: Sized + Eq + Clone + Debug {
             )));
         }
 
+        // The checks below are combined in constant time, and every check is performed even if
+        // an earlier one fails, so that the time taken doesn't reveal which check failed.
+
         // Check if the output of the circuit is 0.
-        if verifier[0] != Self::Field::zero() {
-            return Ok(false);
-        }
+        let mut valid = verifier[0].ct_eq(&Self::Field::zero());
 
         // Check that each of the proof polynomials are well-formed.
         let mut gadgets = self.gadget();
@@ -494,14 +516,12 @@ pub trait Type: Sized + Eq + Clone + Debug {
             let next_len = 1 + gadget.arity();
 
             let e = gadget.call(&verifier[verifier_len..verifier_len + next_len - 1])?;
-            if e != verifier[verifier_len + next_len - 1] {
-                return Ok(false);
-            }
+            valid &= e.ct_eq(&verifier[verifier_len + next_len - 1]);
 
             verifier_len += next_len;
         }
 
-        Ok(true)
+        Ok(valid.into())
     }
 
     /// Check whether `input` and `joint_rand` have the length expected by `self`,
@@ -737,6 +757,43 @@ impl<F: FftFriendlyFieldElement> Gadget<F> for QueryShimGadget<F> {
     }
 }
 
+/// The roots of unity used to interpolate the wire polynomials of a type's proofs, for
+/// [`Type::prove_with_roots`]. They depend only on the type, so a prover can compute them once with
+/// [`WireRoots::new`] and use them for every proof.
+#[derive(Clone, PartialEq, Eq)]
+pub struct WireRoots<F>(Vec<FftRoots<F>>);
+
+impl<F: FftFriendlyFieldElement> WireRoots<F> {
+    /// Computes the roots for the wire polynomials of each gadget of `typ`.
+    pub fn new<T: Type<Field = F>>(typ: &T) -> Result<Self, FlpError> {
+        let mut roots: Vec<FftRoots<F>> = Vec::new();
+        for gadget in typ.gadget() {
+            let m = wire_poly_len(gadget.calls());
+            if roots.iter().all(|roots| roots.size() != m) {
+                roots.push(FftRoots::new(m)?);
+            }
+        }
+        Ok(Self(roots))
+    }
+
+    /// Returns the roots for wire polynomials of length `size`, if there are any.
+    fn get(&self, size: usize) -> Option<&FftRoots<F>> {
+        self.0.iter().find(|roots| roots.size() == size)
+    }
+}
+
+impl<F> Default for WireRoots<F> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<F> Debug for WireRoots<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WireRoots").finish_non_exhaustive()
+    }
+}
+
 /// Compute the length of the wire polynomial constructed from the given number of gadget calls.
 #[inline]
 pub(crate) fn wire_poly_len(num_calls: usize) -> usize {
@@ -874,6 +931,18 @@ pub mod test_utils {
                 self.flp.proof_len(),
                 "{name}: unexpected proof length"
             );
+            assert_eq!(
+                self.flp
+                    .prove_with_roots(
+                        self.input,
+                        &prove_rand,
+                        &joint_rand,
+                        &WireRoots::new(self.flp).unwrap()
+                    )
+                    .unwrap(),
+                proof,
+                "{name}: proof with precomputed roots differs"
+            );
 
             // Query the proof.
             let verifier = self
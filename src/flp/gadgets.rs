@@ -131,6 +131,22 @@ impl<F: FftFriendlyFieldElement> PolyEval<F> {
             num_calls,
         }
     }
+
+    /// Returns a gadget that outputs zero if and only if its input is an integer in the inclusive
+    /// range `[min, max]`. The gadget evaluates `(x - min) * (x - min - 1) * ... * (x - max)`, so
+    /// its degree, and hence the size of the proof, grows with the number of integers in the
+    /// range. This makes it a good fit for small ranges that are not of the form `[0, 2^bits)`;
+    /// large ranges are better served by a bit decomposition.
+    pub fn range_check(min: usize, max: usize, num_calls: usize) -> Self {
+        let mut poly = vec![F::one()];
+        for i in min..=max {
+            poly = poly_mul(
+                &poly,
+                &[-F::from(F::Integer::try_from(i).unwrap()), F::one()],
+            );
+        }
+        Self::new(poly, num_calls)
+    }
 }
 
 impl<F: FftFriendlyFieldElement> PolyEval<F> {
@@ -468,9 +484,7 @@ fn gadget_poly_fft_mem_len(degree: usize, num_calls: usize) -> usize {
 mod tests {
     use super::*;
 
-    #[cfg(feature = "multithreaded")]
-    use crate::field::FieldElement;
-    use crate::field::{random_vector, Field64 as TestField};
+    use crate::field::{random_vector, Field64 as TestField, FieldElement};
     use crate::prng::Prng;
 
     #[test]
@@ -501,6 +515,17 @@ mod tests {
         gadget_test(&mut g, num_calls);
     }
 
+    #[test]
+    fn test_poly_eval_range_check() {
+        let mut g: PolyEval<TestField> = PolyEval::range_check(3, 7, 4);
+        assert_eq!(g.degree(), 5);
+        for x in 0..12 {
+            let out = g.call(&[TestField::from(x)]).unwrap();
+            assert_eq!(out == TestField::zero(), (3..=7).contains(&x), "{x}");
+        }
+        gadget_test(&mut g, 4);
+    }
+
     #[test]
     fn test_parallel_sum() {
         let num_calls = 10;
@@ -569,8 +594,8 @@ mod tests {
 
         let r = prng.get();
         for i in 0..g.arity() {
-            for j in 0..wire_poly_len {
-                wire_polys[i][j] = prng.get();
+            for x in wire_polys[i].iter_mut() {
+                *x = prng.get();
             }
             inp[i] = poly_eval(&wire_polys[i], r);
         }
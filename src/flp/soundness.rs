@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Soundness of the FLP.
+//!
+//! A malicious prover can get an invalid input accepted only if the verifier's random query point
+//! happens to be a root of a nonzero polynomial that the prover controls. For a gadget `G` called
+//! `M` times, this polynomial has degree at most `deg(G) * (P - 1)`, where `P` is the smallest power
+//! of two larger than `M`, and the query point is drawn from the `|F| - P` field elements that are
+//! not used for interpolation ([[BBCG+19], Theorem 4.3]). [`SoundnessError`] sums this bound over
+//! the gadgets of a validity circuit and raises it to the power of the number of proofs, which are
+//! verified at independent points.
+//!
+//! The bound does not account for the probability of guessing the joint randomness, which depends
+//! on the XOF rather than on the field. A deployment should pick a threshold, such as `-64.0`, and
+//! reject any task whose configuration does not meet it with [`SoundnessError::check`].
+//!
+//! [BBCG+19]: https://ia.cr/2019/188
+
+use crate::{
+    field::{FieldElementWithInteger, Integer},
+    flp::{wire_poly_len, FlpError, Type},
+};
+
+/// An upper bound on the probability that the verifier accepts a proof of an invalid input, held
+/// as its base-2 logarithm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundnessError {
+    log2: f64,
+}
+
+impl SoundnessError {
+    /// Computes the bound for a circuit with a single gadget of degree `degree`, called
+    /// `gadget_calls` times, evaluated in a field of size `field_size` with `num_proofs` proofs.
+    /// For Prio2, `gadget_calls` is the dimension and `degree` is 2.
+    ///
+    /// Fails if the field is too small for the bound to be meaningful.
+    pub fn new(
+        field_size: u128,
+        gadget_calls: usize,
+        degree: usize,
+        num_proofs: u8,
+    ) -> Result<Self, FlpError> {
+        Self::from_gadgets(field_size, [(gadget_calls, degree)], num_proofs)
+    }
+
+    /// Computes the bound for the validity circuit of `typ` with `num_proofs` proofs.
+    pub fn for_type<T: Type>(typ: &T, num_proofs: u8) -> Result<Self, FlpError> {
+        let gadgets = typ.gadget();
+        Self::from_gadgets(
+            field_size::<T::Field>(),
+            gadgets
+                .iter()
+                .map(|gadget| (gadget.calls(), gadget.degree())),
+            num_proofs,
+        )
+    }
+
+    fn from_gadgets(
+        field_size: u128,
+        gadgets: impl IntoIterator<Item = (usize, usize)>,
+        num_proofs: u8,
+    ) -> Result<Self, FlpError> {
+        let mut error = 0.0;
+        for (calls, degree) in gadgets {
+            let points = wire_poly_len(calls);
+            if field_size <= points as u128 {
+                return Err(FlpError::InvalidParameter(
+                    "field is too small for the number of gadget calls".into(),
+                ));
+            }
+            error += (degree * (points - 1)) as f64 / (field_size - points as u128) as f64;
+        }
+        Ok(Self {
+            log2: f64::from(num_proofs) * error.min(1.0).log2(),
+        })
+    }
+
+    /// Returns the base-2 logarithm of the bound. This is `-inf` if no gadgets are called.
+    pub fn log2(&self) -> f64 {
+        self.log2
+    }
+
+    /// Returns the bound as a probability. This may round to zero.
+    pub fn probability(&self) -> f64 {
+        self.log2.exp2()
+    }
+
+    /// Fails if the bound exceeds `2^max_log2`.
+    pub fn check(&self, max_log2: f64) -> Result<(), FlpError> {
+        if self.log2 > max_log2 {
+            return Err(FlpError::InvalidParameter(format!(
+                "soundness error 2^{:.1} exceeds the threshold 2^{max_log2}",
+                self.log2
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the modulus of `F`, or `u128::MAX` if it does not fit, which understates the field size
+/// and so overstates the soundness error.
+fn field_size<F: FieldElementWithInteger>() -> u128 {
+    let mask = F::Integer::try_from(0xffff).unwrap();
+    let mut modulus = F::modulus();
+    let mut size = 0u128;
+    let mut shift = 0;
+    while modulus != F::Integer::zero() {
+        if shift >= 128 {
+            return u128::MAX;
+        }
+        let chunk: u64 = (modulus & mask).try_into().unwrap();
+        size |= u128::from(chunk) << shift;
+        modulus = modulus >> 16;
+        shift += 16;
+    }
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        field::{Field128, Field64, FieldElementWithInteger, FieldPrio2},
+        flp::gadgets::{Mul, ParallelSum},
+        flp::types::{Count, SumVec},
+    };
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn field_sizes() {
+        assert_eq!(
+            field_size::<FieldPrio2>(),
+            u128::from(FieldPrio2::modulus())
+        );
+        assert_eq!(field_size::<Field64>(), u128::from(Field64::modulus()));
+        assert_eq!(field_size::<Field128>(), Field128::modulus());
+    }
+
+    #[test]
+    fn soundness_error() {
+        // Count calls a degree-2 gadget once: 2 * (2 - 1) / (p - 2).
+        let count = SoundnessError::for_type(&Count::<Field64>::new(), 1).unwrap();
+        assert!((count.log2() + 63.0).abs() < 1e-6);
+        let two_proofs = SoundnessError::for_type(&Count::<Field64>::new(), 2).unwrap();
+        assert!((two_proofs.log2() - 2.0 * count.log2()).abs() < 1e-6);
+        count.check(-62.0).unwrap();
+        assert_matches!(count.check(-64.0), Err(FlpError::InvalidParameter(_)));
+
+        let sum_vec =
+            SumVec::<Field128, ParallelSum<Field128, Mul<Field128>>>::new(1, 1000, 31).unwrap();
+        let sum_vec = SoundnessError::for_type(&sum_vec, 1).unwrap();
+        assert!(sum_vec.log2() < -100.0);
+        assert_eq!(sum_vec.probability(), sum_vec.log2().exp2());
+
+        // Prio2 with a 32-bit field and a large dimension is far from 64-bit soundness.
+        let prio2 = SoundnessError::new(u128::from(FieldPrio2::modulus()), 100_000, 2, 1).unwrap();
+        assert!(prio2.log2() > -20.0);
+        assert!(prio2.check(-64.0).is_err());
+
+        assert_matches!(
+            SoundnessError::new(7, 10, 2, 1),
+            Err(FlpError::InvalidParameter(_))
+        );
+    }
+}
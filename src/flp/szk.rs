@@ -16,7 +16,7 @@ use crate::{
     field::{FftFriendlyFieldElement, FieldElement},
     flp::{FlpError, Type},
     prng::{Prng, PrngError},
-    vdaf::xof::{IntoFieldVec, Seed, Xof, XofTurboShake128},
+    vdaf::xof::{hash_to_field, Seed, Xof, XofTurboShake128},
 };
 use std::{borrow::Cow, marker::PhantomData};
 
@@ -62,13 +62,17 @@ pub enum SzkProofShare<F: FftFriendlyFieldElement, const SEED_SIZE: usize> {
     /// Leader's proof share is uncompressed. The first Seed is a blind, second
     /// is a joint randomness part.
     Leader {
+        /// The leader's share of the FLP proof.
         uncompressed_proof_share: Vec<F>,
+        /// The leader's blind and the helper's joint randomness part, if joint randomness is used.
         leader_blind_and_helper_joint_rand_part: Option<(Seed<SEED_SIZE>, Seed<SEED_SIZE>)>,
     },
     /// The Helper uses one seed for both its compressed proof share and as the blind for its joint
     /// randomness.
     Helper {
+        /// Seed used to derive the helper's proof share and its joint randomness blind.
         proof_share_seed_and_blind: Seed<SEED_SIZE>,
+        /// The leader's joint randomness part, if joint randomness is used.
         leader_joint_rand_part: Option<Seed<SEED_SIZE>>,
     },
 }
@@ -76,13 +80,13 @@ pub enum SzkProofShare<F: FftFriendlyFieldElement, const SEED_SIZE: usize> {
 /// A tuple containing the state and messages produced by an SZK query.
 #[derive(Clone)]
 pub(crate) struct SzkQueryShare<F, const SEED_SIZE: usize> {
-    joint_rand_part: Option<Seed<SEED_SIZE>>,
-    verifier: SzkVerifier<F>,
+    pub(crate) joint_rand_part: Option<Seed<SEED_SIZE>>,
+    pub(crate) verifier: SzkVerifier<F>,
 }
 
 /// The state that needs to be stored by an Szk verifier between query() and decide()
 pub(crate) struct SzkQueryState<const SEED_SIZE: usize> {
-    joint_rand_seed: Option<Seed<SEED_SIZE>>,
+    pub(crate) joint_rand_seed: Option<Seed<SEED_SIZE>>,
 }
 
 /// Verifier type for the SZK proof.
@@ -101,7 +105,6 @@ where
     phantom: PhantomData<P>,
 }
 
-#[cfg(test)]
 impl<T: Type> Szk<T, XofTurboShake128, 16> {
     /// Create an instance of [`Szk`] using [`XofTurboShake128`].
     pub fn new_turboshake128(typ: T, algorithm_id: u32) -> Self {
@@ -136,12 +139,12 @@ where
     /// Derive a vector of random field elements for consumption by the FLP
     /// prover.
     fn derive_prove_rand(&self, prove_rand_seed: &Seed<SEED_SIZE>) -> Vec<T::Field> {
-        P::seed_stream(
+        hash_to_field::<P, _, SEED_SIZE>(
             prove_rand_seed,
             &self.domain_separation_tag(DST_PROVE_RANDOMNESS),
             &[],
+            self.typ.prove_rand_len(),
         )
-        .into_field_vec(self.typ.prove_rand_len())
     }
 
     fn derive_joint_rand_part(
@@ -187,12 +190,12 @@ where
     ) -> (Seed<SEED_SIZE>, Vec<T::Field>) {
         let joint_rand_seed =
             self.derive_joint_rand_seed(leader_joint_rand_part, helper_joint_rand_part);
-        let joint_rand = P::seed_stream(
+        let joint_rand = hash_to_field::<P, _, SEED_SIZE>(
             &joint_rand_seed,
             &self.domain_separation_tag(DST_JOINT_RANDOMNESS),
             &[],
-        )
-        .into_field_vec(self.typ.joint_rand_len());
+            self.typ.joint_rand_len(),
+        );
 
         (joint_rand_seed, joint_rand)
     }
@@ -208,20 +211,19 @@ where
     }
 
     fn derive_query_rand(&self, verify_key: &[u8; SEED_SIZE], nonce: &[u8; 16]) -> Vec<T::Field> {
-        let mut xof = P::init(
-            verify_key,
+        hash_to_field::<P, _, SEED_SIZE>(
+            &Seed(*verify_key),
             &self.domain_separation_tag(DST_QUERY_RANDOMNESS),
-        );
-        xof.update(nonce);
-        xof.into_seed_stream()
-            .into_field_vec(self.typ.query_rand_len())
+            &[nonce],
+            self.typ.query_rand_len(),
+        )
     }
 
     pub(crate) fn has_joint_rand(&self) -> bool {
         self.typ.joint_rand_len() > 0
     }
 
-    fn prove(
+    pub(crate) fn prove(
         &self,
         leader_input_share: &[T::Field],
         helper_input_share: &[T::Field],
@@ -278,7 +280,7 @@ where
         Ok([leader_proof_share, helper_proof_share])
     }
 
-    fn query(
+    pub(crate) fn query(
         &self,
         input_share: &[T::Field],
         proof_share: SzkProofShare<T::Field, SEED_SIZE>,
@@ -372,7 +374,7 @@ where
 
     /// Returns true if the verifier message indicates that the input from which
     /// it was generated is valid.
-    fn decide(
+    pub(crate) fn decide(
         &self,
         verifier: &[T::Field],
         leader_joint_rand_part_opt: Option<Seed<SEED_SIZE>>,
@@ -404,6 +406,7 @@ where
     }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
     use crate::field::Field128 as TestField;
@@ -2,10 +2,27 @@
 
 //! A collection of [`Type`] implementations.
 
-use crate::field::{FftFriendlyFieldElement, FieldElementWithIntegerExt};
+use crate::field::{FftFriendlyFieldElement, FieldElementWithIntegerExt, FieldError};
 use crate::flp::gadgets::{Mul, ParallelSumGadget, PolyEval};
 use crate::flp::{FlpError, Gadget, Type};
 use crate::polynomial::poly_range_check;
+#[cfg(feature = "experimental")]
+use crate::{
+    dp::{
+        distributions::{PureDpDiscreteLaplace, ZCdpDiscreteGaussian},
+        DifferentialPrivacyStrategy,
+    },
+    flp::TypeWithNoise,
+    vdaf::xof::SeedStreamTurboShake128,
+};
+#[cfg(feature = "experimental")]
+use num_bigint::{BigInt, BigUint, Sign};
+#[cfg(feature = "experimental")]
+use num_rational::Ratio;
+#[cfg(feature = "experimental")]
+use num_traits::One;
+#[cfg(feature = "experimental")]
+use rand::{distributions::Distribution, SeedableRng};
 use std::convert::TryInto;
 use std::fmt::{self, Debug};
 use std::marker::PhantomData;
@@ -144,8 +161,7 @@ impl<F: FftFriendlyFieldElement> Type for Sum<F> {
     type Field = F;
 
     fn encode_measurement(&self, summand: &F::Integer) -> Result<Vec<F>, FlpError> {
-        let v = F::encode_as_bitvector(*summand, self.bits)?.collect();
-        Ok(v)
+        encode_summand(*summand, self.bits)
     }
 
     fn decode_result(&self, data: &[F], _num_measurements: usize) -> Result<F::Integer, FlpError> {
@@ -205,6 +221,127 @@ impl<F: FftFriendlyFieldElement> Type for Sum<F> {
     }
 }
 
+/// The signed sum type. Each measurement is an integer in `[-2^(bits-1), 2^(bits-1))` and the
+/// aggregate is the sum of the measurements, which may be negative.
+///
+/// Measurements are encoded in two's complement, with the same range check as [`Sum`]. The most
+/// significant bit is weighed by `-2^(bits-1)` instead of `2^(bits-1)`, so each output share is
+/// a share of the measurement itself, and a negative sum `-x` is the field element `p - x`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SignedSum<F: FftFriendlyFieldElement> {
+    sum: Sum<F>,
+}
+
+impl<F: FftFriendlyFieldElement> Debug for SignedSum<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedSum")
+            .field("bits", &self.sum.bits)
+            .finish()
+    }
+}
+
+impl<F: FftFriendlyFieldElement> SignedSum<F> {
+    /// Return a new [`SignedSum`] type parameter. Each value of this type is an integer in range
+    /// `[-2^(bits-1), 2^(bits-1))`, where `bits` is between 1 and 64.
+    pub fn new(bits: usize) -> Result<Self, FlpError> {
+        if bits == 0 || bits > 64 {
+            return Err(FlpError::InvalidParameter(
+                "bits must be between 1 and 64".to_string(),
+            ));
+        }
+        Ok(Self {
+            sum: Sum::new(bits)?,
+        })
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Type for SignedSum<F> {
+    type Measurement = i64;
+    type AggregateResult = i128;
+    type Field = F;
+
+    fn encode_measurement(&self, summand: &i64) -> Result<Vec<F>, FlpError> {
+        let bits = self.sum.bits;
+        if bits < 64 && !(-(1 << (bits - 1))..1 << (bits - 1)).contains(summand) {
+            return Err(FlpError::Encode(format!(
+                "summand {summand} is outside of range [-2^{}, 2^{})",
+                bits - 1,
+                bits - 1
+            )));
+        }
+        Ok((0..bits)
+            .map(|i| {
+                if (summand >> i) & 1 == 1 {
+                    F::one()
+                } else {
+                    F::zero()
+                }
+            })
+            .collect())
+    }
+
+    fn decode_result(&self, data: &[F], _num_measurements: usize) -> Result<i128, FlpError> {
+        if data.len() != 1 {
+            return Err(FlpError::Decode("unexpected input length".into()));
+        }
+        decode_signed(data[0])
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        self.sum.gadget()
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.sum.valid(g, input, joint_rand, num_shares)
+    }
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.truncate_call_check(&input)?;
+        let (msb, rest) = input.split_last().unwrap();
+        let msb_weight = F::from(F::valid_integer_try_from(1usize)? << rest.len());
+        let res = if rest.is_empty() {
+            F::zero()
+        } else {
+            F::decode_bitvector(rest)?
+        };
+        Ok(vec![res - msb_weight * *msb])
+    }
+
+    fn input_len(&self) -> usize {
+        self.sum.input_len()
+    }
+
+    fn proof_len(&self) -> usize {
+        self.sum.proof_len()
+    }
+
+    fn verifier_len(&self) -> usize {
+        self.sum.verifier_len()
+    }
+
+    fn output_len(&self) -> usize {
+        1
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        self.sum.joint_rand_len()
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.sum.prove_rand_len()
+    }
+
+    fn query_rand_len(&self) -> usize {
+        self.sum.query_rand_len()
+    }
+}
+
 /// The average type. Each measurement is an integer in `[0,2^bits)` for some `0 < bits < 64` and the
 /// aggregate is the arithmetic average.
 #[derive(Clone, PartialEq, Eq)]
@@ -242,8 +379,7 @@ impl<F: FftFriendlyFieldElement> Type for Average<F> {
     type Field = F;
 
     fn encode_measurement(&self, summand: &F::Integer) -> Result<Vec<F>, FlpError> {
-        let v = F::encode_as_bitvector(*summand, self.bits)?.collect();
-        Ok(v)
+        encode_summand(*summand, self.bits)
     }
 
     fn decode_result(&self, data: &[F], num_measurements: usize) -> Result<f64, FlpError> {
@@ -361,6 +497,29 @@ impl<F: FftFriendlyFieldElement, S: ParallelSumGadget<F, Mul<F>>> Histogram<F, S
     }
 }
 
+impl<F: FftFriendlyFieldElement, S> Histogram<F, S> {
+    /// Decodes an aggregate like [`Type::decode_result`], pairing the count of each bucket with
+    /// its label. Fails if there is not exactly one label per bucket.
+    pub fn decode_labeled_result<L: Clone>(
+        &self,
+        data: &[F],
+        labels: &[L],
+    ) -> Result<Vec<(L, F::Integer)>, FlpError> {
+        if labels.len() != self.length {
+            return Err(FlpError::Decode(format!(
+                "got {} labels for a histogram of length {}",
+                labels.len(),
+                self.length
+            )));
+        }
+        Ok(labels
+            .iter()
+            .cloned()
+            .zip(decode_result_vec(data, self.length)?)
+            .collect())
+    }
+}
+
 impl<F, S> Clone for Histogram<F, S> {
     fn clone(&self) -> Self {
         Self {
@@ -382,6 +541,12 @@ where
     type Field = F;
 
     fn encode_measurement(&self, measurement: &usize) -> Result<Vec<F>, FlpError> {
+        if *measurement >= self.length {
+            return Err(FlpError::Encode(format!(
+                "bucket index {measurement} out of range for histogram of length {}",
+                self.length
+            )));
+        }
         let mut data = vec![F::zero(); self.length];
 
         data[*measurement] = F::one();
@@ -668,165 +833,1554 @@ where
     }
 }
 
-/// Compute a random linear combination of the result of calls of `g` on each element of `input`.
-///
-/// # Arguments
-///
-/// * `g` - The gadget to be applied elementwise
-/// * `input` - The vector on whose elements to apply `g`
-/// * `rnd` - The randomness used for the linear combination
-pub(crate) fn call_gadget_on_vec_entries<F: FftFriendlyFieldElement>(
-    g: &mut Box<dyn Gadget<F>>,
-    input: &[F],
-    rnd: F,
-) -> Result<F, FlpError> {
-    let mut range_check = F::zero();
-    let mut r = rnd;
-    for chunk in input.chunks(1) {
-        range_check += r * g.call(chunk)?;
-        r *= rnd;
-    }
-    Ok(range_check)
-}
+/// A [`SumVec`] whose length `D` is known at compile time. Measurements and aggregate results are
+/// arrays, so a measurement of the wrong length is a type error rather than an [`FlpError`]. The
+/// encoding, proof, and wire format are the same as those of [`SumVec`] of length `D`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FixedSumVec<F: FftFriendlyFieldElement, S, const D: usize>(SumVec<F, S>);
 
-/// Given a vector `data` of field elements which should contain exactly one entry, return the
-/// integer representation of that entry.
-pub(crate) fn decode_result<F: FftFriendlyFieldElement>(
-    data: &[F],
-) -> Result<F::Integer, FlpError> {
-    if data.len() != 1 {
-        return Err(FlpError::Decode("unexpected input length".into()));
+impl<F: FftFriendlyFieldElement, S, const D: usize> Clone for FixedSumVec<F, S, D> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
     }
-    Ok(F::Integer::from(data[0]))
 }
 
-/// Given a vector `data` of field elements, return a vector containing the corresponding integer
-/// representations, if the number of entries matches `expected_len`.
-pub(crate) fn decode_result_vec<F: FftFriendlyFieldElement>(
-    data: &[F],
-    expected_len: usize,
-) -> Result<Vec<F::Integer>, FlpError> {
-    if data.len() != expected_len {
-        return Err(FlpError::Decode("unexpected input length".into()));
+impl<F: FftFriendlyFieldElement, S: ParallelSumGadget<F, Mul<F>>, const D: usize>
+    FixedSumVec<F, S, D>
+{
+    /// Returns a new [`FixedSumVec`] with the desired bit width. See [`SumVec::new`] for the
+    /// conditions under which this fails.
+    pub fn new(bits: usize, chunk_length: usize) -> Result<Self, FlpError> {
+        Ok(Self(SumVec::new(bits, D, chunk_length)?))
     }
-    Ok(data.iter().map(|elem| F::Integer::from(*elem)).collect())
 }
 
-/// This evaluates range checks on a slice of field elements, using a ParallelSum gadget evaluating
-/// many multiplication gates.
-///
-/// # Arguments
-///
-/// * `gadget`: A `ParallelSumGadget<F, Mul<F>>` gadget, or a shim wrapping the same.
-/// * `input`: A slice of inputs. This calculation will check that all inputs were zero or one
-///   before secret sharing.
-/// * `joint_randomness`: A joint randomness value, used to compute a random linear combination of
-///   individual range checks.
-/// * `chunk_length`: How many multiplication gates per ParallelSum gadget. This must match what the
-///   gadget was constructed with.
-/// * `num_shares`: The number of shares that the inputs were secret shared into. This is needed to
-///   correct constant terms in the circuit.
-///
-/// # Returns
-///
-/// This returns (additive shares of) zero if all inputs were zero or one, and otherwise returns a
-/// non-zero value with high probability.
-pub(crate) fn parallel_sum_range_checks<F: FftFriendlyFieldElement>(
-    gadget: &mut Box<dyn Gadget<F>>,
-    input: &[F],
-    joint_randomness: F,
-    chunk_length: usize,
-    num_shares: usize,
-) -> Result<F, FlpError> {
-    let f_num_shares = F::from(F::valid_integer_try_from::<usize>(num_shares)?);
-    let num_shares_inverse = f_num_shares.inv();
-
-    let mut output = F::zero();
-    let mut r_power = joint_randomness;
-    let mut padded_chunk = vec![F::zero(); 2 * chunk_length];
+impl<F, S, const D: usize> Type for FixedSumVec<F, S, D>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    type Measurement = [F::Integer; D];
+    type AggregateResult = [F::Integer; D];
+    type Field = F;
 
-    for chunk in input.chunks(chunk_length) {
-        // Construct arguments for the Mul subcircuits.
-        for (input, args) in chunk.iter().zip(padded_chunk.chunks_exact_mut(2)) {
-            args[0] = r_power * *input;
-            args[1] = *input - num_shares_inverse;
-            r_power *= joint_randomness;
-        }
-        // If the chunk of the input is smaller than chunk_length, use zeros instead of measurement
-        // inputs for the remaining calls.
-        for args in padded_chunk[chunk.len() * 2..].chunks_exact_mut(2) {
-            args[0] = F::zero();
-            args[1] = -num_shares_inverse;
-            // Skip updating r_power. This inner loop is only used during the last iteration of the
-            // outer loop, if the last input chunk is a partial chunk. Thus, r_power won't be
-            // accessed again before returning.
+    fn encode_measurement(&self, measurement: &[F::Integer; D]) -> Result<Vec<F>, FlpError> {
+        let mut flattened = Vec::with_capacity(self.0.flattened_len);
+        for summand in measurement.iter() {
+            if summand > &self.0.max {
+                return Err(FlpError::Encode(format!(
+                    "summand exceeds maximum of 2^{}-1",
+                    self.0.bits
+                )));
+            }
+            flattened.extend(F::encode_as_bitvector(*summand, self.0.bits)?);
         }
-
-        output += gadget.call(&padded_chunk)?;
+        Ok(flattened)
     }
 
-    Ok(output)
-}
+    fn decode_result(
+        &self,
+        data: &[F],
+        num_measurements: usize,
+    ) -> Result<[F::Integer; D], FlpError> {
+        self.0
+            .decode_result(data, num_measurements)?
+            .try_into()
+            .map_err(|_| FlpError::Decode("unexpected aggregate result length".into()))
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::field::{random_vector, Field64 as TestField, FieldElement};
-    use crate::flp::gadgets::ParallelSum;
-    #[cfg(feature = "multithreaded")]
-    use crate::flp::gadgets::ParallelSumMultithreaded;
-    use crate::flp::test_utils::FlpTest;
-    use std::cmp;
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        self.0.gadget()
+    }
 
-    #[test]
-    fn test_count() {
-        let count: Count<TestField> = Count::new();
-        let zero = TestField::zero();
-        let one = TestField::one();
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.0.valid(g, input, joint_rand, num_shares)
+    }
 
-        // Round trip
-        assert_eq!(
-            count
-                .decode_result(
-                    &count
-                        .truncate(count.encode_measurement(&true).unwrap())
-                        .unwrap(),
-                    1
-                )
-                .unwrap(),
-            1,
-        );
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.0.truncate(input)
+    }
 
-        // Test FLP on valid input.
-        FlpTest::expect_valid::<3>(&count, &count.encode_measurement(&true).unwrap(), &[one]);
-        FlpTest::expect_valid::<3>(&count, &count.encode_measurement(&false).unwrap(), &[zero]);
+    fn input_len(&self) -> usize {
+        self.0.input_len()
+    }
 
-        // Test FLP on invalid input.
-        FlpTest::expect_invalid::<3>(&count, &[TestField::from(1337)]);
+    fn proof_len(&self) -> usize {
+        self.0.proof_len()
+    }
 
-        // Try running the validity circuit on an input that's too short.
-        count.valid(&mut count.gadget(), &[], &[], 1).unwrap_err();
-        count
-            .valid(&mut count.gadget(), &[1.into(), 2.into()], &[], 1)
-            .unwrap_err();
+    fn verifier_len(&self) -> usize {
+        self.0.verifier_len()
     }
 
-    #[test]
-    fn test_sum() {
-        let sum = Sum::new(11).unwrap();
-        let zero = TestField::zero();
-        let one = TestField::one();
-        let nine = TestField::from(9);
+    fn output_len(&self) -> usize {
+        D
+    }
 
-        // Round trip
-        assert_eq!(
-            sum.decode_result(
-                &sum.truncate(sum.encode_measurement(&27).unwrap()).unwrap(),
-                1
-            )
-            .unwrap(),
-            27,
-        );
+    fn joint_rand_len(&self) -> usize {
+        self.0.joint_rand_len()
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.0.prove_rand_len()
+    }
+
+    fn query_rand_len(&self) -> usize {
+        self.0.query_rand_len()
+    }
+}
+
+/// A vector of bits with at most `max_weight` ones, for "select up to k" questions. The aggregate
+/// counts the number of ones in each position, like a [`Histogram`] in which each measurement may
+/// select several buckets.
+///
+/// The measurement is encoded as its bits followed by the bits of its weight (the number of ones),
+/// offset so that every weight above `max_weight` overflows the bit width. The circuit checks that
+/// every encoded entry is a bit and that the offset weight is the sum of the measurement bits plus
+/// the offset.
+///
+/// For vectors of wider integers with at most k non-zero entries, use [`SparseSumVec`].
+#[derive(PartialEq, Eq)]
+pub struct MultihotCountVec<F, S> {
+    length: usize,
+    max_weight: usize,
+    bits_for_weight: usize,
+    offset: usize,
+    chunk_length: usize,
+    gadget_calls: usize,
+    phantom: PhantomData<(F, S)>,
+}
+
+impl<F: FftFriendlyFieldElement, S> Debug for MultihotCountVec<F, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultihotCountVec")
+            .field("length", &self.length)
+            .field("max_weight", &self.max_weight)
+            .field("chunk_length", &self.chunk_length)
+            .finish()
+    }
+}
+
+impl<F: FftFriendlyFieldElement, S: ParallelSumGadget<F, Mul<F>>> MultihotCountVec<F, S> {
+    /// Returns a new [`MultihotCountVec`] of the given length, in which each measurement has at
+    /// most `max_weight` ones.
+    ///
+    /// # Errors
+    ///
+    /// * `length` or `chunk_length` is zero, or `length` is too large to be counted in the field.
+    /// * `max_weight` is zero or exceeds `length`.
+    pub fn new(length: usize, max_weight: usize, chunk_length: usize) -> Result<Self, FlpError> {
+        if length == 0 {
+            return Err(FlpError::InvalidParameter(
+                "length cannot be zero".to_string(),
+            ));
+        }
+        if max_weight == 0 || max_weight > length {
+            return Err(FlpError::InvalidParameter(format!(
+                "max_weight must be between 1 and the length {length}, got {max_weight}"
+            )));
+        }
+        if chunk_length == 0 {
+            return Err(FlpError::InvalidParameter(
+                "chunk_length cannot be zero".to_string(),
+            ));
+        }
+
+        // The offset weight, `weight + offset`, fits in `bits_for_weight` bits exactly when the
+        // weight is at most `max_weight`.
+        let bits_for_weight = max_weight.ilog2() as usize + 1;
+        let offset = (1 << bits_for_weight) - 1 - max_weight;
+        if F::valid_integer_try_from::<usize>(max_weight + offset).is_err() {
+            return Err(FlpError::InvalidParameter(
+                "length is too large for the field".to_string(),
+            ));
+        }
+
+        let encoded_len = length + bits_for_weight;
+        Ok(Self {
+            length,
+            max_weight,
+            bits_for_weight,
+            offset,
+            chunk_length,
+            gadget_calls: (encoded_len + chunk_length - 1) / chunk_length,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<F, S> Clone for MultihotCountVec<F, S> {
+    fn clone(&self) -> Self {
+        Self {
+            length: self.length,
+            max_weight: self.max_weight,
+            bits_for_weight: self.bits_for_weight,
+            offset: self.offset,
+            chunk_length: self.chunk_length,
+            gadget_calls: self.gadget_calls,
+            phantom: self.phantom,
+        }
+    }
+}
+
+impl<F, S> Type for MultihotCountVec<F, S>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    type Measurement = Vec<bool>;
+    type AggregateResult = Vec<F::Integer>;
+    type Field = F;
+
+    fn encode_measurement(&self, measurement: &Vec<bool>) -> Result<Vec<F>, FlpError> {
+        if measurement.len() != self.length {
+            return Err(FlpError::Encode(format!(
+                "unexpected measurement length: got {}; want {}",
+                measurement.len(),
+                self.length
+            )));
+        }
+        let weight = measurement.iter().filter(|bit| **bit).count();
+        if weight > self.max_weight {
+            return Err(FlpError::Encode(format!(
+                "measurement has weight {weight}, exceeding the maximum of {}",
+                self.max_weight
+            )));
+        }
+
+        let mut encoded = Vec::with_capacity(self.input_len());
+        encoded.extend(
+            measurement
+                .iter()
+                .map(|bit| if *bit { F::one() } else { F::zero() }),
+        );
+        encoded.extend(encode_summand::<F>(
+            F::valid_integer_try_from(weight + self.offset)?,
+            self.bits_for_weight,
+        )?);
+        Ok(encoded)
+    }
+
+    fn decode_result(
+        &self,
+        data: &[F],
+        _num_measurements: usize,
+    ) -> Result<Vec<F::Integer>, FlpError> {
+        decode_result_vec(data, self.length)
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        vec![Box::new(S::new(
+            Mul::new(self.gadget_calls),
+            self.chunk_length,
+        ))]
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.valid_call_check(input, joint_rand)?;
+
+        // Check that each element of `input` is a 0 or 1.
+        let range_check = parallel_sum_range_checks(
+            &mut g[0],
+            input,
+            joint_rand[0],
+            self.chunk_length,
+            num_shares,
+        )?;
+
+        // Check that the measurement bits plus the offset sum to the encoded weight. The offset is
+        // a constant, so each share carries its share of it.
+        let num_shares_inverse = F::from(F::valid_integer_try_from(num_shares)?).inv();
+        let mut weight_check =
+            F::from(F::valid_integer_try_from(self.offset)?) * num_shares_inverse;
+        for val in &input[..self.length] {
+            weight_check += *val;
+        }
+        weight_check -= F::decode_bitvector(&input[self.length..])?;
+
+        // Take a random linear combination of both checks.
+        let out = joint_rand[1] * range_check + (joint_rand[1] * joint_rand[1]) * weight_check;
+        Ok(out)
+    }
+
+    fn truncate(&self, mut input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.truncate_call_check(&input)?;
+        input.truncate(self.length);
+        Ok(input)
+    }
+
+    fn input_len(&self) -> usize {
+        self.length + self.bits_for_weight
+    }
+
+    fn proof_len(&self) -> usize {
+        (self.chunk_length * 2) + 2 * ((1 + self.gadget_calls).next_power_of_two() - 1) + 1
+    }
+
+    fn verifier_len(&self) -> usize {
+        2 + self.chunk_length * 2
+    }
+
+    fn output_len(&self) -> usize {
+        self.length
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        2
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.chunk_length * 2
+    }
+
+    fn query_rand_len(&self) -> usize {
+        1
+    }
+}
+
+/// A vector of integers in `[0, 2^bits)` with at most `max_nonzero` non-zero entries, for "select
+/// up to k" questions that carry a value with each selection, such as a rating of each chosen
+/// category. The aggregate is the sum of the measurements in each position. With `bits` equal to 1,
+/// this is the sparsity constraint of [`MultihotCountVec`], at a larger encoding.
+///
+/// The measurement is encoded as the bits of each entry, then an indicator bit `b_i` for each
+/// entry, then the bits of the number of indicators set, offset as in [`MultihotCountVec`] so that
+/// every count above `max_nonzero` overflows the bit width. The circuit checks that every encoded
+/// element is a bit, that `x_i * (1 - b_i) = 0` for each entry `x_i`, so that every non-zero entry
+/// has its indicator set, and that the offset count is the sum of the indicators plus the offset.
+#[derive(PartialEq, Eq)]
+pub struct SparseSumVec<F, S> {
+    length: usize,
+    bits: usize,
+    max_nonzero: usize,
+    bits_for_count: usize,
+    offset: usize,
+    chunk_length: usize,
+    range_gadget_calls: usize,
+    indicator_gadget_calls: usize,
+    phantom: PhantomData<(F, S)>,
+}
+
+impl<F: FftFriendlyFieldElement, S> Debug for SparseSumVec<F, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SparseSumVec")
+            .field("length", &self.length)
+            .field("bits", &self.bits)
+            .field("max_nonzero", &self.max_nonzero)
+            .field("chunk_length", &self.chunk_length)
+            .finish()
+    }
+}
+
+impl<F: FftFriendlyFieldElement, S: ParallelSumGadget<F, Mul<F>>> SparseSumVec<F, S> {
+    /// Returns a new [`SparseSumVec`] of `length` integers of `bits` bits, at most `max_nonzero`
+    /// of which are non-zero.
+    ///
+    /// # Errors
+    ///
+    /// * `length` or `chunk_length` is zero.
+    /// * `bits` is zero or too large for the field.
+    /// * `max_nonzero` is zero or exceeds `length`.
+    pub fn new(
+        length: usize,
+        bits: usize,
+        max_nonzero: usize,
+        chunk_length: usize,
+    ) -> Result<Self, FlpError> {
+        if length == 0 {
+            return Err(FlpError::InvalidParameter(
+                "length cannot be zero".to_string(),
+            ));
+        }
+        if bits == 0 || !F::valid_integer_bitlength(bits) {
+            return Err(FlpError::InvalidParameter(
+                "bits must be positive and fit in the field".to_string(),
+            ));
+        }
+        if max_nonzero == 0 || max_nonzero > length {
+            return Err(FlpError::InvalidParameter(format!(
+                "max_nonzero must be between 1 and the length {length}, got {max_nonzero}"
+            )));
+        }
+        if chunk_length == 0 {
+            return Err(FlpError::InvalidParameter(
+                "chunk_length cannot be zero".to_string(),
+            ));
+        }
+
+        // As in `MultihotCountVec`, the offset count fits in `bits_for_count` bits exactly when the
+        // count is at most `max_nonzero`.
+        let bits_for_count = max_nonzero.ilog2() as usize + 1;
+        let offset = (1 << bits_for_count) - 1 - max_nonzero;
+        if F::valid_integer_try_from::<usize>(max_nonzero + offset).is_err() {
+            return Err(FlpError::InvalidParameter(
+                "length is too large for the field".to_string(),
+            ));
+        }
+
+        let encoded_len = length * bits + length + bits_for_count;
+        Ok(Self {
+            length,
+            bits,
+            max_nonzero,
+            bits_for_count,
+            offset,
+            chunk_length,
+            range_gadget_calls: (encoded_len + chunk_length - 1) / chunk_length,
+            indicator_gadget_calls: (length + chunk_length - 1) / chunk_length,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<F, S> Clone for SparseSumVec<F, S> {
+    fn clone(&self) -> Self {
+        Self {
+            length: self.length,
+            bits: self.bits,
+            max_nonzero: self.max_nonzero,
+            bits_for_count: self.bits_for_count,
+            offset: self.offset,
+            chunk_length: self.chunk_length,
+            range_gadget_calls: self.range_gadget_calls,
+            indicator_gadget_calls: self.indicator_gadget_calls,
+            phantom: self.phantom,
+        }
+    }
+}
+
+impl<F, S> Type for SparseSumVec<F, S>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    type Measurement = Vec<F::Integer>;
+    type AggregateResult = Vec<F::Integer>;
+    type Field = F;
+
+    fn encode_measurement(&self, measurement: &Vec<F::Integer>) -> Result<Vec<F>, FlpError> {
+        if measurement.len() != self.length {
+            return Err(FlpError::Encode(format!(
+                "unexpected measurement length: got {}; want {}",
+                measurement.len(),
+                self.length
+            )));
+        }
+        let nonzero = measurement
+            .iter()
+            .filter(|entry| F::from(**entry) != F::zero())
+            .count();
+        if nonzero > self.max_nonzero {
+            return Err(FlpError::Encode(format!(
+                "measurement has {nonzero} non-zero entries, exceeding the maximum of {}",
+                self.max_nonzero
+            )));
+        }
+
+        let mut encoded = Vec::with_capacity(self.input_len());
+        for entry in measurement {
+            encoded.append(&mut encode_summand::<F>(*entry, self.bits)?);
+        }
+        encoded.extend(measurement.iter().map(|entry| {
+            if F::from(*entry) != F::zero() {
+                F::one()
+            } else {
+                F::zero()
+            }
+        }));
+        encoded.append(&mut encode_summand::<F>(
+            F::valid_integer_try_from(nonzero + self.offset)?,
+            self.bits_for_count,
+        )?);
+        Ok(encoded)
+    }
+
+    fn decode_result(
+        &self,
+        data: &[F],
+        _num_measurements: usize,
+    ) -> Result<Vec<F::Integer>, FlpError> {
+        decode_result_vec(data, self.length)
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        vec![
+            Box::new(S::new(Mul::new(self.range_gadget_calls), self.chunk_length)),
+            Box::new(S::new(
+                Mul::new(self.indicator_gadget_calls),
+                self.chunk_length,
+            )),
+        ]
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.valid_call_check(input, joint_rand)?;
+        let (entries, rest) = input.split_at(self.length * self.bits);
+        let (indicators, count) = rest.split_at(self.length);
+
+        // Check that each element of `input` is a 0 or 1.
+        let range_check = parallel_sum_range_checks(
+            &mut g[0],
+            input,
+            joint_rand[0],
+            self.chunk_length,
+            num_shares,
+        )?;
+
+        // Check that `x_i * (1 - b_i)` is zero for each entry, with a random linear combination of
+        // the products. Each share carries its share of the constant 1.
+        let num_shares_inverse = F::from(F::valid_integer_try_from(num_shares)?).inv();
+        let mut indicator_check = F::zero();
+        let mut r_power = joint_rand[1];
+        let mut padded_chunk = vec![F::zero(); 2 * self.chunk_length];
+        let entries: Vec<F> = entries
+            .chunks(self.bits)
+            .map(F::decode_bitvector)
+            .collect::<Result<_, _>>()?;
+        for (entries, indicators) in entries
+            .chunks(self.chunk_length)
+            .zip(indicators.chunks(self.chunk_length))
+        {
+            for ((entry, indicator), args) in entries
+                .iter()
+                .zip(indicators)
+                .zip(padded_chunk.chunks_exact_mut(2))
+            {
+                args[0] = r_power * *entry;
+                args[1] = num_shares_inverse - *indicator;
+                r_power *= joint_rand[1];
+            }
+            for args in padded_chunk[entries.len() * 2..].chunks_exact_mut(2) {
+                args[0] = F::zero();
+                args[1] = F::zero();
+            }
+            indicator_check += g[1].call(&padded_chunk)?;
+        }
+
+        // Check that the indicators plus the offset sum to the encoded count.
+        let mut count_check = F::from(F::valid_integer_try_from(self.offset)?) * num_shares_inverse;
+        for indicator in indicators {
+            count_check += *indicator;
+        }
+        count_check -= F::decode_bitvector(count)?;
+
+        // Take a random linear combination of the checks.
+        let r = joint_rand[2];
+        Ok(r * range_check + r * r * indicator_check + r * r * r * count_check)
+    }
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.truncate_call_check(&input)?;
+        input[..self.length * self.bits]
+            .chunks(self.bits)
+            .map(|bits| Ok(F::decode_bitvector(bits)?))
+            .collect()
+    }
+
+    fn input_len(&self) -> usize {
+        self.length * self.bits + self.length + self.bits_for_count
+    }
+
+    fn proof_len(&self) -> usize {
+        (self.chunk_length * 2) * 2
+            + 2 * ((1 + self.range_gadget_calls).next_power_of_two() - 1)
+            + 1
+            + 2 * ((1 + self.indicator_gadget_calls).next_power_of_two() - 1)
+            + 1
+    }
+
+    fn verifier_len(&self) -> usize {
+        1 + 2 * (self.chunk_length * 2 + 1)
+    }
+
+    fn output_len(&self) -> usize {
+        self.length
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        3
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.chunk_length * 2 * 2
+    }
+
+    fn query_rand_len(&self) -> usize {
+        2
+    }
+}
+
+/// A sequence of integers in an inclusive range `[min, max]`, such as the values of an enum-like
+/// metric. The aggregate result is the sum of the measurements in each position.
+///
+/// Each entry is encoded as a single field element and checked with
+/// [`PolyEval::range_check`], rather than decomposed into bits as in [`SumVec`]. The degree of
+/// the check is the number of integers in the range, so the proof is smaller than a bit
+/// decomposition's when the range is small, and the bounds need not be powers of two.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RangeSumVec<F: FftFriendlyFieldElement> {
+    min: usize,
+    max: usize,
+    len: usize,
+    phantom: PhantomData<F>,
+}
+
+impl<F: FftFriendlyFieldElement> Debug for RangeSumVec<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RangeSumVec")
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<F: FftFriendlyFieldElement> RangeSumVec<F> {
+    /// The largest number of integers in the range, i.e., `max - min + 1`. Beyond this, the degree
+    /// of the range check makes the proof larger than a bit decomposition's.
+    pub const MAX_RANGE_SIZE: usize = 64;
+
+    /// Returns a new [`RangeSumVec`] of `len` integers in range `[min, max]`.
+    ///
+    /// # Errors
+    ///
+    /// * `len` is zero.
+    /// * `min` is not smaller than `max`.
+    /// * The range contains more than [`Self::MAX_RANGE_SIZE`] integers.
+    /// * `max` is not smaller than the field modulus.
+    pub fn new(min: usize, max: usize, len: usize) -> Result<Self, FlpError> {
+        if len == 0 {
+            return Err(FlpError::InvalidParameter("len cannot be zero".to_string()));
+        }
+        if min >= max {
+            return Err(FlpError::InvalidParameter(
+                "min must be smaller than max".to_string(),
+            ));
+        }
+        if max - min >= Self::MAX_RANGE_SIZE {
+            return Err(FlpError::InvalidParameter(format!(
+                "range exceeds {} integers",
+                Self::MAX_RANGE_SIZE
+            )));
+        }
+        match F::Integer::try_from(max) {
+            Ok(max) if max < F::modulus() => (),
+            _ => {
+                return Err(FlpError::InvalidParameter(
+                    "max exceeds the field modulus".to_string(),
+                ))
+            }
+        }
+        Ok(Self {
+            min,
+            max,
+            len,
+            phantom: PhantomData,
+        })
+    }
+
+    fn range_size(&self) -> usize {
+        self.max - self.min + 1
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Type for RangeSumVec<F> {
+    type Measurement = Vec<usize>;
+    type AggregateResult = Vec<F::Integer>;
+    type Field = F;
+
+    fn encode_measurement(&self, measurement: &Vec<usize>) -> Result<Vec<F>, FlpError> {
+        if measurement.len() != self.len {
+            return Err(FlpError::Encode(format!(
+                "unexpected measurement length: got {}; want {}",
+                measurement.len(),
+                self.len
+            )));
+        }
+        measurement
+            .iter()
+            .map(|value| {
+                if !(self.min..=self.max).contains(value) {
+                    return Err(FlpError::Encode(format!(
+                        "value {value} is outside of range [{}, {}]",
+                        self.min, self.max
+                    )));
+                }
+                Ok(F::from(F::Integer::try_from(*value).unwrap()))
+            })
+            .collect()
+    }
+
+    fn decode_result(
+        &self,
+        data: &[F],
+        _num_measurements: usize,
+    ) -> Result<Vec<F::Integer>, FlpError> {
+        decode_result_vec(data, self.len)
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        vec![Box::new(PolyEval::range_check(
+            self.min, self.max, self.len,
+        ))]
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        _num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.valid_call_check(input, joint_rand)?;
+        call_gadget_on_vec_entries(&mut g[0], input, joint_rand[0])
+    }
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.truncate_call_check(&input)?;
+        Ok(input)
+    }
+
+    fn input_len(&self) -> usize {
+        self.len
+    }
+
+    fn proof_len(&self) -> usize {
+        self.range_size() * ((1 + self.len).next_power_of_two() - 1) + 2
+    }
+
+    fn verifier_len(&self) -> usize {
+        3
+    }
+
+    fn output_len(&self) -> usize {
+        self.len
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        1
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        1
+    }
+
+    fn query_rand_len(&self) -> usize {
+        1
+    }
+}
+
+/// A vector of counters and of counters of conjunctions. Each measurement is a vector of `len`
+/// booleans, and the result counts, for each position, the measurements that set it, followed by,
+/// for each configured pair of positions `(i, j)`, the measurements that set both `i` and `j`.
+///
+/// The Client encodes the conjunction of each pair as a cross term alongside the bits, and the
+/// circuit checks with a multiplication gate that each cross term is the product of its bits. This
+/// counts "feature A was used and the client crashed" without encoding one counter per
+/// combination of values.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AndCountVec<F> {
+    len: usize,
+    pairs: Vec<(usize, usize)>,
+    phantom: PhantomData<F>,
+}
+
+impl<F> Debug for AndCountVec<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AndCountVec")
+            .field("len", &self.len)
+            .field("pairs", &self.pairs)
+            .finish()
+    }
+}
+
+impl<F: FftFriendlyFieldElement> AndCountVec<F> {
+    /// Returns a new [`AndCountVec`] of `len` counters and a conjunction counter for each pair of
+    /// positions in `pairs`.
+    ///
+    /// # Errors
+    ///
+    /// * `len` is zero.
+    /// * A pair refers to a position out of range, or to the same position twice.
+    pub fn new(len: usize, pairs: Vec<(usize, usize)>) -> Result<Self, FlpError> {
+        if len == 0 {
+            return Err(FlpError::InvalidParameter("len cannot be zero".to_string()));
+        }
+        for (i, j) in pairs.iter() {
+            if *i >= len || *j >= len || i == j {
+                return Err(FlpError::InvalidParameter(format!(
+                    "invalid pair of positions ({i}, {j})"
+                )));
+            }
+        }
+        Ok(Self {
+            len,
+            pairs,
+            phantom: PhantomData,
+        })
+    }
+
+    fn gadget_calls(&self) -> usize {
+        self.len + self.pairs.len()
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Type for AndCountVec<F> {
+    type Measurement = Vec<bool>;
+    type AggregateResult = Vec<F::Integer>;
+    type Field = F;
+
+    fn encode_measurement(&self, measurement: &Vec<bool>) -> Result<Vec<F>, FlpError> {
+        if measurement.len() != self.len {
+            return Err(FlpError::Encode(format!(
+                "unexpected measurement length: got {}; want {}",
+                measurement.len(),
+                self.len
+            )));
+        }
+        let bit = |value: bool| {
+            F::conditional_select(&F::zero(), &F::one(), Choice::from(u8::from(value)))
+        };
+        let mut encoded: Vec<F> = measurement.iter().map(|value| bit(*value)).collect();
+        encoded.extend(
+            self.pairs
+                .iter()
+                .map(|(i, j)| bit(measurement[*i] & measurement[*j])),
+        );
+        Ok(encoded)
+    }
+
+    fn decode_result(
+        &self,
+        data: &[F],
+        _num_measurements: usize,
+    ) -> Result<Vec<F::Integer>, FlpError> {
+        decode_result_vec(data, self.output_len())
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        vec![Box::new(Mul::new(self.gadget_calls()))]
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        _num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.valid_call_check(input, joint_rand)?;
+        let (bits, cross_terms) = input.split_at(self.len);
+
+        // Each bit is zero or one, and each cross term is the product of its bits.
+        let mut result = F::zero();
+        let mut r = joint_rand[0];
+        for bit in bits {
+            result += r * (g[0].call(&[*bit, *bit])? - *bit);
+            r *= joint_rand[0];
+        }
+        for ((i, j), cross_term) in self.pairs.iter().zip(cross_terms) {
+            result += r * (g[0].call(&[bits[*i], bits[*j]])? - *cross_term);
+            r *= joint_rand[0];
+        }
+        Ok(result)
+    }
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.truncate_call_check(&input)?;
+        Ok(input)
+    }
+
+    fn input_len(&self) -> usize {
+        self.len + self.pairs.len()
+    }
+
+    fn proof_len(&self) -> usize {
+        2 * ((1 + self.gadget_calls()).next_power_of_two() - 1) + 3
+    }
+
+    fn verifier_len(&self) -> usize {
+        4
+    }
+
+    fn output_len(&self) -> usize {
+        self.input_len()
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        1
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        2
+    }
+
+    fn query_rand_len(&self) -> usize {
+        1
+    }
+}
+
+/// An approximate count of distinct identifiers, in the style of HyperLogLog. Each Client hashes
+/// its identifiers into a sketch of `2^bucket_bits` registers, where each register holds the
+/// largest rank, in `[0, max_rank]`, of the hashes that fall into its bucket (see
+/// [`DistinctCount::sketch`]). The aggregate result is an estimate of the number of distinct
+/// identifiers across all measurements.
+///
+/// The registers of the sketches must be combined by taking their maximum, which is not linear.
+/// Instead, each register is encoded as a one-hot vector over its possible values, so that the
+/// aggregate counts, for each bucket, the measurements with each register value; the largest value
+/// with a non-zero count is the register of the combined sketch. The circuit checks that each
+/// register is in range, i.e., that each one-hot vector is a vector of bits summing to one.
+///
+/// Estimates saturate once the hashes of some bucket have ranks exceeding `max_rank`, so
+/// `max_rank` should be chosen for the expected number of identifiers: each additional rank doubles
+/// the count at which this happens.
+#[derive(PartialEq, Eq)]
+pub struct DistinctCount<F, S> {
+    bucket_bits: usize,
+    max_rank: usize,
+    chunk_length: usize,
+    gadget_calls: usize,
+    phantom: PhantomData<(F, S)>,
+}
+
+impl<F, S> Debug for DistinctCount<F, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DistinctCount")
+            .field("bucket_bits", &self.bucket_bits)
+            .field("max_rank", &self.max_rank)
+            .field("chunk_length", &self.chunk_length)
+            .finish()
+    }
+}
+
+impl<F, S> Clone for DistinctCount<F, S> {
+    fn clone(&self) -> Self {
+        Self {
+            bucket_bits: self.bucket_bits,
+            max_rank: self.max_rank,
+            chunk_length: self.chunk_length,
+            gadget_calls: self.gadget_calls,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<F: FftFriendlyFieldElement, S: ParallelSumGadget<F, Mul<F>>> DistinctCount<F, S> {
+    /// Returns a new [`DistinctCount`] with `2^bucket_bits` registers of values in `[0,
+    /// max_rank]`.
+    ///
+    /// # Errors
+    ///
+    /// * `bucket_bits` is not in `[4, 16]`.
+    /// * `max_rank` is zero, or larger than the largest rank of a 64-bit hash, `65 - bucket_bits`.
+    /// * `chunk_length` is zero.
+    pub fn new(bucket_bits: usize, max_rank: usize, chunk_length: usize) -> Result<Self, FlpError> {
+        if !(4..=16).contains(&bucket_bits) {
+            return Err(FlpError::InvalidParameter(
+                "bucket_bits must be between 4 and 16".to_string(),
+            ));
+        }
+        if max_rank == 0 || max_rank > 65 - bucket_bits {
+            return Err(FlpError::InvalidParameter(format!(
+                "max_rank must be between 1 and {}",
+                65 - bucket_bits
+            )));
+        }
+        if chunk_length == 0 {
+            return Err(FlpError::InvalidParameter(
+                "chunk_length cannot be zero".to_string(),
+            ));
+        }
+
+        let input_len = (1 << bucket_bits) * (max_rank + 1);
+        let mut gadget_calls = input_len / chunk_length;
+        if input_len % chunk_length != 0 {
+            gadget_calls += 1;
+        }
+
+        Ok(Self {
+            bucket_bits,
+            max_rank,
+            chunk_length,
+            gadget_calls,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<F: FftFriendlyFieldElement, S> DistinctCount<F, S> {
+    /// Returns the number of registers in a sketch.
+    pub fn num_buckets(&self) -> usize {
+        1 << self.bucket_bits
+    }
+
+    /// Returns the sketch of a set of identifiers, given a 64-bit hash of each. The first
+    /// `bucket_bits` bits of a hash select its bucket, and its rank is one more than the number of
+    /// leading zeros of the remaining bits, capped at `max_rank`.
+    ///
+    /// The hash must be the same across all Clients, and should be keyed, so that the buckets and
+    /// ranks of an identifier are not public.
+    pub fn sketch(&self, hashes: impl IntoIterator<Item = u64>) -> Vec<usize> {
+        let mut registers = vec![0; self.num_buckets()];
+        for hash in hashes {
+            let bucket = (hash >> (64 - self.bucket_bits)) as usize;
+            let rank = ((hash << self.bucket_bits).leading_zeros() as usize + 1).min(self.max_rank);
+            registers[bucket] = registers[bucket].max(rank);
+        }
+        registers
+    }
+
+    /// Decodes an aggregate into the registers of the combined sketch of all measurements.
+    pub fn decode_registers(&self, data: &[F]) -> Result<Vec<usize>, FlpError> {
+        if data.len() != self.num_buckets() * (self.max_rank + 1) {
+            return Err(FlpError::Decode("unexpected input length".into()));
+        }
+        Ok(data
+            .chunks(self.max_rank + 1)
+            .map(|counts| {
+                counts
+                    .iter()
+                    .rposition(|count| *count != F::zero())
+                    .unwrap_or(0)
+            })
+            .collect())
+    }
+
+    /// Estimates the number of distinct identifiers in a sketch.
+    pub fn estimate(&self, registers: &[usize]) -> f64 {
+        let m = self.num_buckets() as f64;
+        let alpha = match self.bucket_bits {
+            4 => 0.673,
+            5 => 0.697,
+            6 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let harmonic_sum: f64 = registers
+            .iter()
+            .map(|register| 2f64.powi(-(*register as i32)))
+            .sum();
+        let estimate = alpha * m * m / harmonic_sum;
+
+        // Use linear counting for small cardinalities, for which the estimate above is biased.
+        let empty = registers.iter().filter(|register| **register == 0).count();
+        if estimate <= 2.5 * m && empty > 0 {
+            m * (m / empty as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
+
+impl<F, S> Type for DistinctCount<F, S>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    type Measurement = Vec<usize>;
+    type AggregateResult = f64;
+    type Field = F;
+
+    fn encode_measurement(&self, registers: &Vec<usize>) -> Result<Vec<F>, FlpError> {
+        if registers.len() != self.num_buckets() {
+            return Err(FlpError::Encode(format!(
+                "unexpected number of registers: got {}; want {}",
+                registers.len(),
+                self.num_buckets()
+            )));
+        }
+        let mut data = vec![F::zero(); self.input_len()];
+        for (bucket, register) in registers.iter().enumerate() {
+            if *register > self.max_rank {
+                return Err(FlpError::Encode(format!(
+                    "register {register} exceeds maximum rank {}",
+                    self.max_rank
+                )));
+            }
+            data[bucket * (self.max_rank + 1) + register] = F::one();
+        }
+        Ok(data)
+    }
+
+    fn decode_result(&self, data: &[F], _num_measurements: usize) -> Result<f64, FlpError> {
+        Ok(self.estimate(&self.decode_registers(data)?))
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        vec![Box::new(S::new(
+            Mul::new(self.gadget_calls),
+            self.chunk_length,
+        ))]
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.valid_call_check(input, joint_rand)?;
+
+        // Check that each element of `input` is a 0 or 1.
+        let range_check = parallel_sum_range_checks(
+            &mut g[0],
+            input,
+            joint_rand[0],
+            self.chunk_length,
+            num_shares,
+        )?;
+
+        // Check that the one-hot vector of each register sums to 1, and take a random linear
+        // combination of all checks.
+        let shares_inv = F::from(F::valid_integer_try_from(num_shares)?).inv();
+        let mut out = joint_rand[1] * range_check;
+        let mut r = joint_rand[1];
+        for one_hot in input.chunks(self.max_rank + 1) {
+            r *= joint_rand[1];
+            let mut sum_check = -shares_inv;
+            for val in one_hot {
+                sum_check += *val;
+            }
+            out += r * sum_check;
+        }
+        Ok(out)
+    }
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.truncate_call_check(&input)?;
+        Ok(input)
+    }
+
+    fn input_len(&self) -> usize {
+        self.num_buckets() * (self.max_rank + 1)
+    }
+
+    fn proof_len(&self) -> usize {
+        (self.chunk_length * 2) + 2 * ((1 + self.gadget_calls).next_power_of_two() - 1) + 1
+    }
+
+    fn verifier_len(&self) -> usize {
+        2 + self.chunk_length * 2
+    }
+
+    fn output_len(&self) -> usize {
+        self.input_len()
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        2
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.chunk_length * 2
+    }
+
+    fn query_rand_len(&self) -> usize {
+        1
+    }
+}
+
+/// Encodes `summand` as a vector of `bits` bits, or returns an error if it doesn't fit.
+fn encode_summand<F: FftFriendlyFieldElement>(
+    summand: F::Integer,
+    bits: usize,
+) -> Result<Vec<F>, FlpError> {
+    match F::encode_as_bitvector(summand, bits) {
+        Ok(v) => Ok(v.collect()),
+        Err(FieldError::InputSizeMismatch) => Err(FlpError::Encode(format!(
+            "summand exceeds maximum of 2^{bits}-1"
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Compute a random linear combination of the result of calls of `g` on each element of `input`.
+///
+/// # Arguments
+///
+/// * `g` - The gadget to be applied elementwise
+/// * `input` - The vector on whose elements to apply `g`
+/// * `rnd` - The randomness used for the linear combination
+pub(crate) fn call_gadget_on_vec_entries<F: FftFriendlyFieldElement>(
+    g: &mut Box<dyn Gadget<F>>,
+    input: &[F],
+    rnd: F,
+) -> Result<F, FlpError> {
+    let mut range_check = F::zero();
+    let mut r = rnd;
+    for chunk in input.chunks(1) {
+        range_check += r * g.call(chunk)?;
+        r *= rnd;
+    }
+    Ok(range_check)
+}
+
+/// Given a vector `data` of field elements which should contain exactly one entry, return the
+/// integer representation of that entry.
+pub(crate) fn decode_result<F: FftFriendlyFieldElement>(
+    data: &[F],
+) -> Result<F::Integer, FlpError> {
+    if data.len() != 1 {
+        return Err(FlpError::Decode("unexpected input length".into()));
+    }
+    Ok(F::Integer::from(data[0]))
+}
+
+/// Interprets a field element as a signed integer: elements above `p / 2` are the negative integers
+/// `x - p`. The moduli of all fields are below `2^128`, so the integer fits in an `i128`.
+pub(crate) fn decode_signed<F: FftFriendlyFieldElement>(x: F) -> Result<i128, FlpError> {
+    let (mut magnitude, negative) = if F::Integer::from(x) <= F::Integer::from(-x) {
+        (F::Integer::from(x), false)
+    } else {
+        (F::Integer::from(-x), true)
+    };
+
+    // `F::Integer` converts only to `u64`, so convert 16 bits at a time.
+    let zero = F::Integer::from(F::zero());
+    let mask = F::valid_integer_try_from(0xffffusize)?;
+    let mut res = 0i128;
+    let mut shift = 0;
+    while magnitude != zero {
+        let chunk: u64 = (magnitude & mask)
+            .try_into()
+            .map_err(|_| FlpError::Decode("integer conversion failed".into()))?;
+        res |= i128::from(chunk) << shift;
+        magnitude = magnitude >> 16;
+        shift += 16;
+    }
+    Ok(if negative { -res } else { res })
+}
+
+/// Given a vector `data` of field elements, return a vector containing the corresponding integer
+/// representations, if the number of entries matches `expected_len`.
+pub(crate) fn decode_result_vec<F: FftFriendlyFieldElement>(
+    data: &[F],
+    expected_len: usize,
+) -> Result<Vec<F::Integer>, FlpError> {
+    if data.len() != expected_len {
+        return Err(FlpError::Decode("unexpected input length".into()));
+    }
+    Ok(data.iter().map(|elem| F::Integer::from(*elem)).collect())
+}
+
+/// This evaluates range checks on a slice of field elements, using a ParallelSum gadget evaluating
+/// many multiplication gates.
+///
+/// # Arguments
+///
+/// * `gadget`: A `ParallelSumGadget<F, Mul<F>>` gadget, or a shim wrapping the same.
+/// * `input`: A slice of inputs. This calculation will check that all inputs were zero or one
+///   before secret sharing.
+/// * `joint_randomness`: A joint randomness value, used to compute a random linear combination of
+///   individual range checks.
+/// * `chunk_length`: How many multiplication gates per ParallelSum gadget. This must match what the
+///   gadget was constructed with.
+/// * `num_shares`: The number of shares that the inputs were secret shared into. This is needed to
+///   correct constant terms in the circuit.
+///
+/// # Returns
+///
+/// This returns (additive shares of) zero if all inputs were zero or one, and otherwise returns a
+/// non-zero value with high probability.
+pub(crate) fn parallel_sum_range_checks<F: FftFriendlyFieldElement>(
+    gadget: &mut Box<dyn Gadget<F>>,
+    input: &[F],
+    joint_randomness: F,
+    chunk_length: usize,
+    num_shares: usize,
+) -> Result<F, FlpError> {
+    let f_num_shares = F::from(F::valid_integer_try_from::<usize>(num_shares)?);
+    let num_shares_inverse = f_num_shares.inv();
+
+    let mut output = F::zero();
+    let mut r_power = joint_randomness;
+    let mut padded_chunk = vec![F::zero(); 2 * chunk_length];
+
+    for chunk in input.chunks(chunk_length) {
+        // Construct arguments for the Mul subcircuits.
+        for (input, args) in chunk.iter().zip(padded_chunk.chunks_exact_mut(2)) {
+            args[0] = r_power * *input;
+            args[1] = *input - num_shares_inverse;
+            r_power *= joint_randomness;
+        }
+        // If the chunk of the input is smaller than chunk_length, use zeros instead of measurement
+        // inputs for the remaining calls.
+        for args in padded_chunk[chunk.len() * 2..].chunks_exact_mut(2) {
+            args[0] = F::zero();
+            args[1] = -num_shares_inverse;
+            // Skip updating r_power. This inner loop is only used during the last iteration of the
+            // outer loop, if the last input chunk is a partial chunk. Thus, r_power won't be
+            // accessed again before returning.
+        }
+
+        output += gadget.call(&padded_chunk)?;
+    }
+
+    Ok(output)
+}
+
+// The sensitivities below are for batches that differ in the value of a single measurement. The
+// Laplace mechanism is calibrated to the L1 sensitivity of the aggregate and the Gaussian mechanism
+// to its L2 sensitivity.
+
+#[cfg(feature = "experimental")]
+impl<F: FftFriendlyFieldElement> TypeWithNoise<PureDpDiscreteLaplace> for Count<F> {
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &PureDpDiscreteLaplace,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        add_noise(dp_strategy, Ratio::one(), agg_result)
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<F: FftFriendlyFieldElement> TypeWithNoise<ZCdpDiscreteGaussian> for Count<F> {
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &ZCdpDiscreteGaussian,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        add_noise(dp_strategy, Ratio::one(), agg_result)
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<F: FftFriendlyFieldElement> TypeWithNoise<PureDpDiscreteLaplace> for Sum<F> {
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &PureDpDiscreteLaplace,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        add_noise(dp_strategy, max_summand(self.bits).into(), agg_result)
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<F: FftFriendlyFieldElement> TypeWithNoise<ZCdpDiscreteGaussian> for Sum<F> {
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &ZCdpDiscreteGaussian,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        add_noise(dp_strategy, max_summand(self.bits).into(), agg_result)
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<F, S> TypeWithNoise<PureDpDiscreteLaplace> for Histogram<F, S>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &PureDpDiscreteLaplace,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        // Moving a measurement to a different bucket changes two entries by one.
+        add_noise(
+            dp_strategy,
+            Ratio::from_integer(BigUint::from(2u8)),
+            agg_result,
+        )
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<F, S> TypeWithNoise<ZCdpDiscreteGaussian> for Histogram<F, S>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &ZCdpDiscreteGaussian,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        // The L2 sensitivity is sqrt(2), which is not rational. 3/2 is used as an upper bound.
+        add_noise(
+            dp_strategy,
+            Ratio::new(BigUint::from(3u8), BigUint::from(2u8)),
+            agg_result,
+        )
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<F, S> TypeWithNoise<PureDpDiscreteLaplace> for SumVec<F, S>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &PureDpDiscreteLaplace,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        add_noise(
+            dp_strategy,
+            (max_summand(self.bits) * BigUint::from(self.len)).into(),
+            agg_result,
+        )
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<F, S> TypeWithNoise<ZCdpDiscreteGaussian> for SumVec<F, S>
+where
+    F: FftFriendlyFieldElement,
+    S: ParallelSumGadget<F, Mul<F>> + Eq + 'static,
+{
+    fn add_noise_to_result(
+        &self,
+        dp_strategy: &ZCdpDiscreteGaussian,
+        agg_result: &mut [F],
+        _num_measurements: usize,
+    ) -> Result<(), FlpError> {
+        // The L2 sensitivity is sqrt(len) * (2^bits - 1). Round the square root up.
+        let len = BigUint::from(self.len);
+        let mut sqrt_len = len.sqrt();
+        if &sqrt_len * &sqrt_len < len {
+            sqrt_len += 1u8;
+        }
+        add_noise(
+            dp_strategy,
+            (max_summand(self.bits) * sqrt_len).into(),
+            agg_result,
+        )
+    }
+}
+
+/// Returns `2^bits - 1`, the largest value a summand with the given bit width can take.
+#[cfg(feature = "experimental")]
+fn max_summand(bits: usize) -> BigUint {
+    (BigUint::one() << bits) - 1u8
+}
+
+/// Adds independent noise, calibrated to the given sensitivity, to each entry of the aggregate.
+#[cfg(feature = "experimental")]
+fn add_noise<F, S>(
+    dp_strategy: &S,
+    sensitivity: Ratio<BigUint>,
+    agg_result: &mut [F],
+) -> Result<(), FlpError>
+where
+    F: FftFriendlyFieldElement,
+    S: DifferentialPrivacyStrategy<Sensitivity = Ratio<BigUint>>,
+    S::Distribution: Distribution<BigInt>,
+{
+    let sampler = dp_strategy.create_distribution(sensitivity)?;
+    let mut rng = SeedStreamTurboShake128::from_entropy();
+    let base = F::from(F::valid_integer_try_from(256usize)?);
+    for entry in agg_result.iter_mut() {
+        let noise: BigInt = sampler.sample(&mut rng);
+
+        // Map the noise into the field by evaluating its big-endian base-256 digits, then
+        // negating if necessary. This reduces the noise modulo the field modulus.
+        let (sign, digits) = noise.to_bytes_be();
+        let mut f_noise = F::zero();
+        for digit in digits {
+            f_noise = f_noise * base + F::from(F::valid_integer_try_from(usize::from(digit))?);
+        }
+        if sign == Sign::Minus {
+            f_noise = -f_noise;
+        }
+        *entry += f_noise;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{random_vector, Field128, Field64 as TestField, FieldElement};
+    use crate::flp::gadgets::ParallelSum;
+    #[cfg(feature = "multithreaded")]
+    use crate::flp::gadgets::ParallelSumMultithreaded;
+    use crate::flp::test_utils::FlpTest;
+    use assert_matches::assert_matches;
+    use std::cmp;
+
+    #[test]
+    fn test_count() {
+        let count: Count<TestField> = Count::new();
+        let zero = TestField::zero();
+        let one = TestField::one();
+
+        // Round trip
+        assert_eq!(
+            count
+                .decode_result(
+                    &count
+                        .truncate(count.encode_measurement(&true).unwrap())
+                        .unwrap(),
+                    1
+                )
+                .unwrap(),
+            1,
+        );
+
+        // Test FLP on valid input.
+        FlpTest::expect_valid::<3>(&count, &count.encode_measurement(&true).unwrap(), &[one]);
+        FlpTest::expect_valid::<3>(&count, &count.encode_measurement(&false).unwrap(), &[zero]);
+
+        // Test FLP on invalid input.
+        FlpTest::expect_invalid::<3>(&count, &[TestField::from(1337)]);
+
+        // Try running the validity circuit on an input that's too short.
+        count.valid(&mut count.gadget(), &[], &[], 1).unwrap_err();
+        count
+            .valid(&mut count.gadget(), &[1.into(), 2.into()], &[], 1)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_sum() {
+        let sum = Sum::new(11).unwrap();
+        let zero = TestField::zero();
+        let one = TestField::one();
+        let nine = TestField::from(9);
+
+        // Summands must fit in the bit width.
+        sum.encode_measurement(&((1 << 11) - 1)).unwrap();
+        assert_matches!(sum.encode_measurement(&(1 << 11)), Err(FlpError::Encode(_)));
+
+        // Round trip
+        assert_eq!(
+            sum.decode_result(
+                &sum.truncate(sum.encode_measurement(&27).unwrap()).unwrap(),
+                1
+            )
+            .unwrap(),
+            27,
+        );
 
         // Test FLP on valid input.
         FlpTest::expect_valid::<3>(
@@ -847,6 +2401,80 @@ mod tests {
         FlpTest::expect_invalid::<3>(&Sum::new(5).unwrap(), &[zero, zero, zero, zero, nine]);
     }
 
+    #[test]
+    fn test_signed_sum() {
+        let sum = SignedSum::new(8).unwrap();
+        let zero = TestField::zero();
+        let one = TestField::one();
+        let nine = TestField::from(9);
+
+        // Summands must fit in the bit width.
+        sum.encode_measurement(&127).unwrap();
+        sum.encode_measurement(&-128).unwrap();
+        assert_matches!(sum.encode_measurement(&128), Err(FlpError::Encode(_)));
+        assert_matches!(sum.encode_measurement(&-129), Err(FlpError::Encode(_)));
+
+        // Round trip
+        for summand in [-128, -27, -1, 0, 1, 27, 127] {
+            assert_eq!(
+                sum.decode_result(
+                    &sum.truncate(sum.encode_measurement(&summand).unwrap())
+                        .unwrap(),
+                    1
+                )
+                .unwrap(),
+                i128::from(summand),
+            );
+        }
+
+        // The output shares of measurements sum to the sum of the measurements.
+        let output = [-100, 20, -7, 3]
+            .iter()
+            .map(|x| sum.truncate(sum.encode_measurement(x).unwrap()).unwrap()[0])
+            .fold(zero, |acc, x| acc + x);
+        assert_eq!(sum.decode_result(&[output], 4).unwrap(), -84);
+
+        // Test FLP on valid input.
+        FlpTest::expect_valid::<3>(
+            &sum,
+            &sum.encode_measurement(&-5).unwrap(),
+            &[-TestField::from(5)],
+        );
+        FlpTest::expect_valid::<3>(&SignedSum::new(1).unwrap(), &[one], &[-one]);
+        FlpTest::expect_valid::<3>(&SignedSum::new(2).unwrap(), &[one, zero], &[one]);
+
+        // Test FLP on invalid input.
+        FlpTest::expect_invalid::<3>(&SignedSum::new(3).unwrap(), &[one, nine, zero]);
+        FlpTest::expect_invalid::<3>(&SignedSum::new(3).unwrap(), &[zero, zero, -one]);
+
+        assert_matches!(
+            SignedSum::<TestField>::new(0),
+            Err(FlpError::InvalidParameter(_))
+        );
+        assert!(SignedSum::<TestField>::new(64).is_err());
+        let wide = SignedSum::<Field128>::new(64).unwrap();
+        for summand in [i64::MIN, i64::MAX] {
+            assert_eq!(
+                wide.decode_result(
+                    &wide
+                        .truncate(wide.encode_measurement(&summand).unwrap())
+                        .unwrap(),
+                    1
+                )
+                .unwrap(),
+                i128::from(summand),
+            );
+        }
+        assert_eq!(
+            decode_signed(Field128::from(u128::MAX >> 2)).unwrap(),
+            i128::MAX >> 1
+        );
+        assert_eq!(
+            decode_signed(-Field128::from(u128::MAX >> 2)).unwrap(),
+            -(i128::MAX >> 1)
+        );
+    }
+
     #[test]
     fn test_average() {
         let average = Average::new(11).unwrap();
@@ -902,6 +2530,7 @@ mod tests {
         assert_eq!(&hist.encode_measurement(&0).unwrap(), &[one, zero, zero]);
         assert_eq!(&hist.encode_measurement(&1).unwrap(), &[zero, one, zero]);
         assert_eq!(&hist.encode_measurement(&2).unwrap(), &[zero, zero, one]);
+        assert_matches!(hist.encode_measurement(&3), Err(FlpError::Encode(_)));
 
         // Round trip
         assert_eq!(
@@ -912,6 +2541,15 @@ mod tests {
             .unwrap(),
             [0, 0, 1]
         );
+        assert_eq!(
+            hist.decode_labeled_result(&[one, nine, zero], &["a", "b", "c"])
+                .unwrap(),
+            [("a", 1), ("b", 9), ("c", 0)]
+        );
+        assert_matches!(
+            hist.decode_labeled_result(&[one, nine, zero], &["a", "b"]),
+            Err(FlpError::Decode(_))
+        );
 
         // Test valid inputs.
         FlpTest::expect_valid::<3>(
@@ -952,6 +2590,442 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_multihot_count_vec() {
+        let multihot =
+            MultihotCountVec::<TestField, ParallelSum<TestField, Mul<TestField>>>::new(4, 2, 2)
+                .unwrap();
+        let zero = TestField::zero();
+        let one = TestField::one();
+        let nine = TestField::from(9);
+
+        // The weight, offset by 1, is encoded in two bits.
+        assert_eq!(
+            multihot
+                .encode_measurement(&vec![true, false, true, false])
+                .unwrap(),
+            [one, zero, one, zero, one, one]
+        );
+        assert_eq!(
+            multihot.encode_measurement(&vec![false; 4]).unwrap(),
+            [zero, zero, zero, zero, one, zero]
+        );
+        assert_matches!(
+            multihot.encode_measurement(&vec![true, true, true, false]),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            multihot.encode_measurement(&vec![true]),
+            Err(FlpError::Encode(_))
+        );
+
+        // Round trip
+        assert_eq!(
+            multihot
+                .decode_result(
+                    &multihot
+                        .truncate(
+                            multihot
+                                .encode_measurement(&vec![false, true, false, true])
+                                .unwrap()
+                        )
+                        .unwrap(),
+                    1
+                )
+                .unwrap(),
+            [0, 1, 0, 1]
+        );
+
+        // Test valid inputs.
+        for measurement in [
+            vec![false; 4],
+            vec![true, false, false, false],
+            vec![false, true, true, false],
+        ] {
+            let expected: Vec<_> = measurement
+                .iter()
+                .map(|bit| if *bit { one } else { zero })
+                .collect();
+            FlpTest::expect_valid::<3>(
+                &multihot,
+                &multihot.encode_measurement(&measurement).unwrap(),
+                &expected,
+            );
+        }
+
+        // Test invalid inputs: too many ones, a wrong weight, and entries that are not bits.
+        FlpTest::expect_invalid::<3>(&multihot, &[one, one, one, zero, one, one]);
+        FlpTest::expect_invalid::<3>(&multihot, &[one, zero, zero, zero, one, one]);
+        FlpTest::expect_invalid::<3>(&multihot, &[nine, zero, zero, zero, one, one]);
+        FlpTest::expect_invalid::<3>(&multihot, &[one, one, zero, zero, nine, zero]);
+
+        // Test parameters.
+        for (length, max_weight) in [(4, 1), (4, 4), (10, 7), (1, 1)] {
+            let multihot =
+                MultihotCountVec::<TestField, ParallelSum<TestField, Mul<TestField>>>::new(
+                    length, max_weight, 3,
+                )
+                .unwrap();
+            FlpTest::expect_valid_no_output::<3>(
+                &multihot,
+                &multihot
+                    .encode_measurement(&(0..length).map(|i| i < max_weight).collect::<Vec<_>>())
+                    .unwrap(),
+            );
+        }
+        for (length, max_weight, chunk_length) in [(0, 1, 1), (4, 0, 1), (4, 5, 1), (4, 2, 0)] {
+            assert_matches!(
+                MultihotCountVec::<TestField, ParallelSum<TestField, Mul<TestField>>>::new(
+                    length,
+                    max_weight,
+                    chunk_length
+                ),
+                Err(FlpError::InvalidParameter(_))
+            );
+        }
+    }
+
+    #[test]
+    fn test_sparse_sum_vec() {
+        let sparse =
+            SparseSumVec::<TestField, ParallelSum<TestField, Mul<TestField>>>::new(3, 2, 2, 2)
+                .unwrap();
+        let zero = TestField::zero();
+        let one = TestField::one();
+        let two = TestField::from(2);
+        let three = TestField::from(3);
+
+        // The entries are encoded in two bits each, followed by the indicators and the count,
+        // offset by 1, in two bits.
+        assert_eq!(
+            sparse.encode_measurement(&vec![3, 0, 2]).unwrap(),
+            [one, one, zero, zero, zero, one, one, zero, one, one, one]
+        );
+        assert_eq!(
+            sparse.encode_measurement(&vec![0; 3]).unwrap(),
+            [zero, zero, zero, zero, zero, zero, zero, zero, zero, one, zero]
+        );
+        assert_matches!(
+            sparse.encode_measurement(&vec![1, 1, 1]),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            sparse.encode_measurement(&vec![4, 0, 0]),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            sparse.encode_measurement(&vec![1]),
+            Err(FlpError::Encode(_))
+        );
+
+        // Round trip
+        assert_eq!(
+            sparse
+                .decode_result(
+                    &sparse
+                        .truncate(sparse.encode_measurement(&vec![0, 3, 1]).unwrap())
+                        .unwrap(),
+                    1
+                )
+                .unwrap(),
+            [0, 3, 1]
+        );
+
+        // Test valid inputs.
+        for (measurement, expected) in [
+            (vec![0, 0, 0], [zero, zero, zero]),
+            (vec![2, 0, 0], [two, zero, zero]),
+            (vec![0, 3, 1], [zero, three, one]),
+        ] {
+            FlpTest::expect_valid::<3>(
+                &sparse,
+                &sparse.encode_measurement(&measurement).unwrap(),
+                &expected,
+            );
+        }
+
+        // A zero entry may have its indicator set, as long as the count is within the bound.
+        FlpTest::expect_valid::<3>(
+            &sparse,
+            &[one, zero, zero, zero, zero, zero, one, one, zero, one, one],
+            &[one, zero, zero],
+        );
+
+        // Test invalid inputs: a non-zero entry without its indicator, too many indicators, a
+        // wrong count, and elements that are not bits.
+        FlpTest::expect_invalid::<3>(
+            &sparse,
+            &[one, zero, one, zero, zero, zero, one, zero, zero, zero, one],
+        );
+        FlpTest::expect_invalid::<3>(
+            &sparse,
+            &[one, zero, one, zero, one, zero, one, one, one, one, one],
+        );
+        FlpTest::expect_invalid::<3>(
+            &sparse,
+            &[one, zero, zero, zero, zero, zero, one, zero, zero, one, one],
+        );
+        FlpTest::expect_invalid::<3>(
+            &sparse,
+            &[
+                three, zero, zero, zero, zero, zero, one, zero, zero, zero, one,
+            ],
+        );
+
+        // Test parameters.
+        for (length, bits, max_nonzero) in [(4, 1, 1), (4, 3, 4), (10, 8, 7), (1, 1, 1)] {
+            let sparse = SparseSumVec::<TestField, ParallelSum<TestField, Mul<TestField>>>::new(
+                length,
+                bits,
+                max_nonzero,
+                3,
+            )
+            .unwrap();
+            FlpTest::expect_valid_no_output::<3>(
+                &sparse,
+                &sparse
+                    .encode_measurement(
+                        &(0..length)
+                            .map(|i| if i < max_nonzero { (1 << bits) - 1 } else { 0 })
+                            .collect::<Vec<_>>(),
+                    )
+                    .unwrap(),
+            );
+        }
+        for (length, bits, max_nonzero, chunk_length) in [
+            (0, 1, 1, 1),
+            (4, 0, 1, 1),
+            (4, 128, 1, 1),
+            (4, 1, 0, 1),
+            (4, 1, 5, 1),
+            (4, 1, 2, 0),
+        ] {
+            assert_matches!(
+                SparseSumVec::<TestField, ParallelSum<TestField, Mul<TestField>>>::new(
+                    length,
+                    bits,
+                    max_nonzero,
+                    chunk_length
+                ),
+                Err(FlpError::InvalidParameter(_))
+            );
+        }
+    }
+
+    #[test]
+    fn test_range_sum_vec() {
+        let range = RangeSumVec::<TestField>::new(3, 7, 3).unwrap();
+        let zero = TestField::zero();
+        let two = TestField::from(2);
+        let three = TestField::from(3);
+        let seven = TestField::from(7);
+        let eight = TestField::from(8);
+
+        // Round trip
+        assert_eq!(
+            range
+                .decode_result(
+                    &range
+                        .truncate(range.encode_measurement(&vec![3, 5, 7]).unwrap())
+                        .unwrap(),
+                    1
+                )
+                .unwrap(),
+            [3, 5, 7]
+        );
+        assert_matches!(
+            range.encode_measurement(&vec![3, 8, 7]),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            range.encode_measurement(&vec![2, 3, 7]),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(range.encode_measurement(&vec![3]), Err(FlpError::Encode(_)));
+
+        // Test valid inputs.
+        for measurement in [vec![3, 3, 3], vec![4, 5, 6], vec![7, 3, 7]] {
+            let input = range.encode_measurement(&measurement).unwrap();
+            FlpTest::expect_valid::<3>(&range, &input, &input);
+        }
+
+        // Test invalid inputs.
+        FlpTest::expect_invalid::<3>(&range, &[three, eight, seven]);
+        FlpTest::expect_invalid::<3>(&range, &[two, three, seven]);
+        FlpTest::expect_invalid::<3>(&range, &[zero, zero, zero]);
+        FlpTest::expect_invalid::<3>(&range, &[-three, three, seven]);
+
+        // Test parameters.
+        for (min, max, len) in [(0, 1, 1), (0, 2, 1), (10, 73, 10), (100, 102, 33)] {
+            let range = RangeSumVec::<TestField>::new(min, max, len).unwrap();
+            FlpTest::expect_valid_no_output::<3>(
+                &range,
+                &range
+                    .encode_measurement(&(0..len).map(|i| min + i % (max - min + 1)).collect())
+                    .unwrap(),
+            );
+        }
+        for (min, max, len) in [(0, 1, 0), (3, 3, 1), (4, 3, 1), (0, 64, 1)] {
+            assert_matches!(
+                RangeSumVec::<TestField>::new(min, max, len),
+                Err(FlpError::InvalidParameter(_))
+            );
+        }
+        assert_matches!(
+            RangeSumVec::<TestField>::new(usize::MAX - 1, usize::MAX, 1),
+            Err(FlpError::InvalidParameter(_))
+        );
+    }
+
+    #[test]
+    fn test_and_count_vec() {
+        let and = AndCountVec::<TestField>::new(3, vec![(0, 1), (2, 0)]).unwrap();
+        let zero = TestField::zero();
+        let one = TestField::one();
+        let two = TestField::from(2);
+
+        // Round trip
+        assert_eq!(
+            and.decode_result(
+                &and.truncate(and.encode_measurement(&vec![true, true, false]).unwrap())
+                    .unwrap(),
+                1
+            )
+            .unwrap(),
+            [1, 1, 0, 1, 0]
+        );
+        assert_matches!(
+            and.encode_measurement(&vec![true]),
+            Err(FlpError::Encode(_))
+        );
+
+        // Test valid inputs.
+        for measurement in [
+            vec![false; 3],
+            vec![true; 3],
+            vec![true, false, true],
+            vec![false, true, true],
+        ] {
+            let input = and.encode_measurement(&measurement).unwrap();
+            FlpTest::expect_valid::<3>(&and, &input, &input);
+        }
+
+        // Test invalid inputs: a cross term that is not the product of its bits, and entries that
+        // are not bits.
+        FlpTest::expect_invalid::<3>(&and, &[one, one, zero, zero, zero]);
+        FlpTest::expect_invalid::<3>(&and, &[one, zero, zero, one, zero]);
+        FlpTest::expect_invalid::<3>(&and, &[two, zero, zero, zero, zero]);
+        FlpTest::expect_invalid::<3>(&and, &[one, one, one, one, two]);
+
+        // Test parameters.
+        let and = AndCountVec::<TestField>::new(1, Vec::new()).unwrap();
+        FlpTest::expect_valid_no_output::<3>(&and, &and.encode_measurement(&vec![true]).unwrap());
+        let pairs = (0..10)
+            .flat_map(|i| (i + 1..10).map(move |j| (i, j)))
+            .collect();
+        let and = AndCountVec::<TestField>::new(10, pairs).unwrap();
+        FlpTest::expect_valid_no_output::<3>(
+            &and,
+            &and.encode_measurement(&(0..10).map(|i| i % 3 == 0).collect())
+                .unwrap(),
+        );
+        for (len, pairs) in [(0, vec![]), (2, vec![(0, 2)]), (2, vec![(1, 1)])] {
+            assert_matches!(
+                AndCountVec::<TestField>::new(len, pairs),
+                Err(FlpError::InvalidParameter(_))
+            );
+        }
+    }
+
+    #[test]
+    fn test_distinct_count() {
+        type TestDistinctCount = DistinctCount<TestField, ParallelSum<TestField, Mul<TestField>>>;
+
+        // An arbitrary but well-mixed 64-bit hash.
+        fn hash(x: u64) -> u64 {
+            let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        }
+
+        let distinct = TestDistinctCount::new(4, 3, 8).unwrap();
+        let zero = TestField::zero();
+        let one = TestField::one();
+
+        // The bucket is selected by the first four bits and the rank is capped at three.
+        let registers = distinct.sketch([
+            0x0400_0000_0000_0000,
+            0x1000_0000_0000_0000,
+            0x1800_0000_0000_0000,
+            0x4800_0000_0000_0000,
+        ]);
+        assert_eq!(registers[..5], [2, 3, 0, 0, 1]);
+        let mut expected = vec![zero; 64];
+        for (bucket, register) in [(0, 2), (1, 3), (4, 1)] {
+            expected[bucket * 4 + register] = one;
+        }
+        for bucket in [2, 3].into_iter().chain(5..16) {
+            expected[bucket * 4] = one;
+        }
+        assert_eq!(distinct.encode_measurement(&registers).unwrap(), expected);
+        assert_eq!(distinct.decode_registers(&expected).unwrap(), registers);
+        assert_matches!(
+            distinct.encode_measurement(&vec![4; 16]),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            distinct.encode_measurement(&vec![0; 15]),
+            Err(FlpError::Encode(_))
+        );
+
+        // Test valid inputs.
+        for registers in [vec![0; 16], vec![3; 16], distinct.sketch((0..20).map(hash))] {
+            FlpTest::expect_valid_no_output::<3>(
+                &distinct,
+                &distinct.encode_measurement(&registers).unwrap(),
+            );
+        }
+
+        // Test invalid inputs: a register that is not one-hot, and one with a non-bit entry.
+        let mut input = distinct.encode_measurement(&vec![1; 16]).unwrap();
+        input[5] = zero;
+        FlpTest::expect_invalid::<3>(&distinct, &input);
+        input[6] = one;
+        input[7] = one;
+        FlpTest::expect_invalid::<3>(&distinct, &input);
+        let mut input = distinct.encode_measurement(&vec![1; 16]).unwrap();
+        input[0] = TestField::from(2);
+        input[1] = -one;
+        FlpTest::expect_invalid::<3>(&distinct, &input);
+
+        // Test the estimate on the sum of the encoded sketches of overlapping sets.
+        let distinct = TestDistinctCount::new(8, 20, 32).unwrap();
+        let mut aggregate = vec![zero; distinct.input_len()];
+        for client in 0..10 {
+            let sketch = distinct.sketch((client * 1000..client * 1000 + 2000).map(hash));
+            for (sum, x) in aggregate
+                .iter_mut()
+                .zip(distinct.encode_measurement(&sketch).unwrap())
+            {
+                *sum += x;
+            }
+        }
+        let estimate = distinct.decode_result(&aggregate, 10).unwrap();
+        assert!((estimate - 11000.0).abs() < 11000.0 * 0.2, "{estimate}");
+        let estimate = distinct.estimate(&distinct.sketch((0..100).map(hash)));
+        assert!((estimate - 100.0).abs() < 20.0, "{estimate}");
+
+        for (bucket_bits, max_rank, chunk_length) in
+            [(3, 3, 1), (17, 3, 1), (4, 0, 1), (4, 62, 1), (4, 3, 0)]
+        {
+            assert_matches!(
+                TestDistinctCount::new(bucket_bits, max_rank, chunk_length),
+                Err(FlpError::InvalidParameter(_))
+            );
+        }
+    }
+
     fn test_sum_vec<F, S>(f: F)
     where
         F: Fn(usize, usize, usize) -> Result<SumVec<TestField, S>, FlpError>,
@@ -1056,8 +3130,57 @@ mod tests {
         assert_eq!(verifier.len(), typ.verifier_len());
         assert!(typ.decide(&verifier).unwrap());
     }
+
+    #[test]
+    #[cfg(feature = "experimental")]
+    fn test_add_noise() {
+        use crate::dp::{
+            distributions::{PureDpDiscreteLaplace, ZCdpDiscreteGaussian},
+            PureDpBudget, Rational, ZCdpBudget,
+        };
+
+        let histogram = Histogram::<TestField, ParallelSum<TestField, _>>::new(100, 10).unwrap();
+        let agg_result = histogram.encode_measurement(&3).unwrap();
+
+        // With a very large epsilon, the noise is zero with overwhelming probability.
+        let laplace = PureDpDiscreteLaplace::from_budget(
+            PureDpBudget::new(Rational::from_unsigned(100_000u32, 1).unwrap()).unwrap(),
+        );
+        let mut noised = agg_result.clone();
+        histogram
+            .add_noise_to_result(&laplace, &mut noised, 1)
+            .unwrap();
+        assert_eq!(noised, agg_result);
+
+        // With a small epsilon, some entry is noised with overwhelming probability.
+        let laplace = PureDpDiscreteLaplace::from_budget(
+            PureDpBudget::new(Rational::from_unsigned(1u8, 10).unwrap()).unwrap(),
+        );
+        let gaussian = ZCdpDiscreteGaussian::from_budget(ZCdpBudget::new(
+            Rational::from_unsigned(1u8, 10).unwrap(),
+        ));
+        let mut noised = agg_result.clone();
+        histogram
+            .add_noise_to_result(&laplace, &mut noised, 1)
+            .unwrap();
+        assert_ne!(noised, agg_result);
+        let mut noised = agg_result.clone();
+        histogram
+            .add_noise_to_result(&gaussian, &mut noised, 1)
+            .unwrap();
+        assert_ne!(noised, agg_result);
+
+        // Noise is reduced modulo the field modulus: small negative values decode as large ones.
+        let count = Count::<TestField>::new();
+        let mut noised = vec![TestField::zero(); 20];
+        count.add_noise_to_result(&laplace, &mut noised, 1).unwrap();
+        assert!(noised
+            .iter()
+            .all(|x| u64::from(*x) < 1000 || u64::from(-*x) < 1000));
+    }
 }
 
+pub mod composite;
 #[cfg(feature = "experimental")]
 #[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
 pub mod fixedpoint_l2;
@@ -0,0 +1,781 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Types composed of other types.
+//!
+//! A [`Pair`] packs a measurement of each of two types into a single measurement, whose validity
+//! circuit checks both. A Client thus sends a single input share and a single proof for, say, a
+//! [`Count`](crate::flp::types::Count) and a [`Sum`](crate::flp::types::Sum), instead of a report
+//! for each in separate tasks. Pairs nest, so any number of types can be combined.
+//!
+//! ```
+//! use prio::{
+//!     field::Field64,
+//!     flp::types::{composite::Pair, Count, Sum},
+//!     flp::Type,
+//! };
+//!
+//! let typ = Pair::new(Count::<Field64>::new(), Pair::new(Count::new(), Sum::new(8).unwrap()));
+//! let encoded = typ.encode_measurement(&(true, (false, 42))).unwrap();
+//! assert_eq!(encoded.len(), 1 + 1 + 8);
+//! ```
+//!
+//! A [`Record`] does the same for named fields declared at runtime with a [`RecordBuilder`]. The
+//! encoding and the proof are those of the fields in order of declaration. Clients set the fields
+//! of a measurement by name, and the Collector decodes each field of the aggregate by name.
+//!
+//! ```
+//! use prio::{
+//!     field::Field64,
+//!     flp::types::{composite::RecordBuilder, Count, Sum},
+//!     flp::Type,
+//! };
+//!
+//! let record = RecordBuilder::new()
+//!     .field("crashed", Count::<Field64>::new())
+//!     .unwrap()
+//!     .field("latency", Sum::new(8).unwrap())
+//!     .unwrap()
+//!     .build()
+//!     .unwrap();
+//! let measurement = record
+//!     .measurement()
+//!     .set::<Count<Field64>>("crashed", &true)
+//!     .unwrap()
+//!     .set::<Sum<Field64>>("latency", &42)
+//!     .unwrap()
+//!     .finish()
+//!     .unwrap();
+//! let output = record
+//!     .truncate(record.encode_measurement(&measurement).unwrap())
+//!     .unwrap();
+//! let aggregate = record.decode_result(&output, 1).unwrap();
+//! assert_eq!(aggregate.get::<Sum<Field64>>("latency").unwrap(), 42);
+//! ```
+
+use crate::field::FftFriendlyFieldElement;
+use crate::flp::{FlpError, Gadget, Type};
+use std::any::Any;
+use std::fmt::Debug;
+
+/// A pair of types. The measurement is a measurement of each type, encoded one after the other,
+/// and the aggregate result is the aggregate result of each. See the
+/// [module documentation](self) for details.
+///
+/// The gadgets, the proof, and the randomness of the pair are those of `A` followed by those of
+/// `B`. The pair adds one joint randomness value, which weighs the validity of `B` against that of
+/// `A`, so that one cannot cancel out the other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pair<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A: Type, B: Type<Field = A::Field>> Pair<A, B> {
+    /// Returns the pair of `a` and `b`.
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+
+    /// Returns the first type of the pair.
+    pub fn first(&self) -> &A {
+        &self.a
+    }
+
+    /// Returns the second type of the pair.
+    pub fn second(&self) -> &B {
+        &self.b
+    }
+}
+
+impl<A: Type, B: Type<Field = A::Field>> Type for Pair<A, B> {
+    type Measurement = (A::Measurement, B::Measurement);
+    type AggregateResult = (A::AggregateResult, B::AggregateResult);
+    type Field = A::Field;
+
+    fn encode_measurement(
+        &self,
+        (a, b): &(A::Measurement, B::Measurement),
+    ) -> Result<Vec<A::Field>, FlpError> {
+        let mut encoded = self.a.encode_measurement(a)?;
+        encoded.append(&mut self.b.encode_measurement(b)?);
+        Ok(encoded)
+    }
+
+    fn decode_result(
+        &self,
+        data: &[A::Field],
+        num_measurements: usize,
+    ) -> Result<Self::AggregateResult, FlpError> {
+        if data.len() != self.output_len() {
+            return Err(FlpError::Decode("unexpected input length".into()));
+        }
+        let (a, b) = data.split_at(self.a.output_len());
+        Ok((
+            self.a.decode_result(a, num_measurements)?,
+            self.b.decode_result(b, num_measurements)?,
+        ))
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<A::Field>>> {
+        let mut gadgets = self.a.gadget();
+        gadgets.append(&mut self.b.gadget());
+        gadgets
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<A::Field>>>,
+        input: &[A::Field],
+        joint_rand: &[A::Field],
+        num_shares: usize,
+    ) -> Result<A::Field, FlpError> {
+        self.valid_call_check(input, joint_rand)?;
+        let (input_a, input_b) = input.split_at(self.a.input_len());
+        let (joint_rand_a, joint_rand) = joint_rand.split_at(self.a.joint_rand_len());
+        let (joint_rand_b, weight) = joint_rand.split_at(self.b.joint_rand_len());
+
+        // Each type expects its own gadgets, in order. The gadgets are put back together
+        // afterwards, as the caller inspects them after evaluating the circuit.
+        let mut g_b = g.split_off(g.len() - self.b.gadget().len());
+        let valid_a = self.a.valid(g, input_a, joint_rand_a, num_shares);
+        let valid_b = self.b.valid(&mut g_b, input_b, joint_rand_b, num_shares);
+        g.append(&mut g_b);
+        Ok(valid_a? + weight[0] * valid_b?)
+    }
+
+    fn truncate(&self, input: Vec<A::Field>) -> Result<Vec<A::Field>, FlpError> {
+        self.truncate_call_check(&input)?;
+        let (a, b) = input.split_at(self.a.input_len());
+        let mut output = self.a.truncate(a.to_vec())?;
+        output.append(&mut self.b.truncate(b.to_vec())?);
+        Ok(output)
+    }
+
+    fn input_len(&self) -> usize {
+        self.a.input_len() + self.b.input_len()
+    }
+
+    fn proof_len(&self) -> usize {
+        self.a.proof_len() + self.b.proof_len()
+    }
+
+    fn verifier_len(&self) -> usize {
+        // Both verifiers include the output of the validity circuit, but the pair has only one.
+        self.a.verifier_len() + self.b.verifier_len() - 1
+    }
+
+    fn output_len(&self) -> usize {
+        self.a.output_len() + self.b.output_len()
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        self.a.joint_rand_len() + self.b.joint_rand_len() + 1
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.a.prove_rand_len() + self.b.prove_rand_len()
+    }
+
+    fn query_rand_len(&self) -> usize {
+        self.a.query_rand_len() + self.b.query_rand_len()
+    }
+}
+
+/// The operations of a [`Type`] that do not depend on its measurement and aggregate result types,
+/// so that the fields of a [`Record`] can be of different types. `as_any` returns the type itself.
+trait ErasedType<F: FftFriendlyFieldElement>: Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn clone_box(&self) -> Box<dyn ErasedType<F>>;
+
+    fn eq_dyn(&self, other: &dyn ErasedType<F>) -> bool;
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>>;
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError>;
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError>;
+
+    fn input_len(&self) -> usize;
+
+    fn proof_len(&self) -> usize;
+
+    fn verifier_len(&self) -> usize;
+
+    fn output_len(&self) -> usize;
+
+    fn joint_rand_len(&self) -> usize;
+
+    fn prove_rand_len(&self) -> usize;
+
+    fn query_rand_len(&self) -> usize;
+}
+
+/// A [`Type`] whose measurement and aggregate result types are erased.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Erased<T>(T);
+
+impl<F, T> ErasedType<F> for Erased<T>
+where
+    F: FftFriendlyFieldElement,
+    T: Type<Field = F> + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
+
+    fn clone_box(&self) -> Box<dyn ErasedType<F>> {
+        Box::new(self.clone())
+    }
+
+    fn eq_dyn(&self, other: &dyn ErasedType<F>) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(&self.0)
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        self.0.gadget()
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.0.valid(g, input, joint_rand, num_shares)
+    }
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.0.truncate(input)
+    }
+
+    fn input_len(&self) -> usize {
+        self.0.input_len()
+    }
+
+    fn proof_len(&self) -> usize {
+        self.0.proof_len()
+    }
+
+    fn verifier_len(&self) -> usize {
+        self.0.verifier_len()
+    }
+
+    fn output_len(&self) -> usize {
+        self.0.output_len()
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        self.0.joint_rand_len()
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.0.prove_rand_len()
+    }
+
+    fn query_rand_len(&self) -> usize {
+        self.0.query_rand_len()
+    }
+}
+
+#[derive(Debug)]
+struct RecordField<F: FftFriendlyFieldElement> {
+    name: String,
+    typ: Box<dyn ErasedType<F>>,
+    num_gadgets: usize,
+}
+
+impl<F: FftFriendlyFieldElement> Clone for RecordField<F> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            typ: self.typ.clone_box(),
+            num_gadgets: self.num_gadgets,
+        }
+    }
+}
+
+impl<F: FftFriendlyFieldElement> PartialEq for RecordField<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.typ.eq_dyn(other.typ.as_ref())
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Eq for RecordField<F> {}
+
+/// A builder for a [`Record`].
+#[derive(Debug)]
+pub struct RecordBuilder<F: FftFriendlyFieldElement> {
+    fields: Vec<RecordField<F>>,
+}
+
+impl<F: FftFriendlyFieldElement> RecordBuilder<F> {
+    /// Returns a builder for a record with no fields.
+    pub fn new() -> Self {
+        Self { fields: Vec::new() }
+    }
+
+    /// Appends a field named `name`, with measurements of type `typ`. Returns an error if there
+    /// is already a field with this name.
+    pub fn field<T>(mut self, name: &str, typ: T) -> Result<Self, FlpError>
+    where
+        T: Type<Field = F> + Send + Sync + 'static,
+    {
+        if self.fields.iter().any(|field| field.name == name) {
+            return Err(FlpError::InvalidParameter(format!(
+                "duplicate field {name:?}"
+            )));
+        }
+        self.fields.push(RecordField {
+            name: name.into(),
+            num_gadgets: typ.gadget().len(),
+            typ: Box::new(Erased(typ)),
+        });
+        Ok(self)
+    }
+
+    /// Returns the record. Returns an error if it has no fields.
+    pub fn build(self) -> Result<Record<F>, FlpError> {
+        if self.fields.is_empty() {
+            return Err(FlpError::InvalidParameter(
+                "a record needs at least one field".to_string(),
+            ));
+        }
+        Ok(Record {
+            fields: self.fields,
+        })
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Default for RecordBuilder<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A record of named fields, each of which holds a measurement of some type. See the
+/// [module documentation](self) for details.
+///
+/// The validity circuit of the record is a random linear combination of the circuits of its
+/// fields, weighed by the powers of one joint randomness value added by the record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record<F: FftFriendlyFieldElement> {
+    fields: Vec<RecordField<F>>,
+}
+
+impl<F: FftFriendlyFieldElement> Record<F> {
+    /// Returns the names of the fields, in order.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.fields.iter().map(|field| field.name.as_str())
+    }
+
+    /// Returns a builder for a measurement of this record.
+    pub fn measurement(&self) -> RecordMeasurementBuilder<'_, F> {
+        RecordMeasurementBuilder {
+            record: self,
+            parts: vec![None; self.fields.len()],
+        }
+    }
+
+    /// Returns the field named `name` and its position, checking that it has type `T`.
+    fn field<T: 'static>(&self, name: &str) -> Result<(usize, &T), FlpError> {
+        let (index, field) = self
+            .fields
+            .iter()
+            .enumerate()
+            .find(|(_, field)| field.name == name)
+            .ok_or_else(|| FlpError::InvalidParameter(format!("no field {name:?}")))?;
+        let typ = field.typ.as_any().downcast_ref::<T>().ok_or_else(|| {
+            FlpError::InvalidParameter(format!("field {name:?} has a different type"))
+        })?;
+        Ok((index, typ))
+    }
+}
+
+/// A builder for a [`RecordMeasurement`], which sets each field of the record by name.
+#[derive(Debug)]
+pub struct RecordMeasurementBuilder<'a, F: FftFriendlyFieldElement> {
+    record: &'a Record<F>,
+    parts: Vec<Option<Vec<F>>>,
+}
+
+impl<F: FftFriendlyFieldElement> RecordMeasurementBuilder<'_, F> {
+    /// Sets the field named `name` to `measurement`. Returns an error if there is no such field,
+    /// if it does not have type `T`, or if the measurement cannot be encoded.
+    pub fn set<T: Type<Field = F> + 'static>(
+        mut self,
+        name: &str,
+        measurement: &T::Measurement,
+    ) -> Result<Self, FlpError> {
+        let (index, typ) = self.record.field::<T>(name)?;
+        self.parts[index] = Some(typ.encode_measurement(measurement)?);
+        Ok(self)
+    }
+
+    /// Returns the measurement. Returns an error if a field is not set.
+    pub fn finish(self) -> Result<RecordMeasurement<F>, FlpError> {
+        let parts = self
+            .parts
+            .into_iter()
+            .zip(self.record.field_names())
+            .map(|(part, name)| {
+                part.ok_or_else(|| FlpError::Encode(format!("field {name:?} is not set")))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(RecordMeasurement { parts })
+    }
+}
+
+/// A measurement of a [`Record`], built with [`Record::measurement`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordMeasurement<F> {
+    parts: Vec<Vec<F>>,
+}
+
+/// The aggregate result of a [`Record`], from which each field is decoded by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordAggregate<F: FftFriendlyFieldElement> {
+    record: Record<F>,
+    outputs: Vec<Vec<F>>,
+    num_measurements: usize,
+}
+
+impl<F: FftFriendlyFieldElement> RecordAggregate<F> {
+    /// Returns the aggregate result of the field named `name`. Returns an error if there is no
+    /// such field, if it does not have type `T`, or if its aggregate cannot be decoded.
+    pub fn get<T: Type<Field = F> + 'static>(
+        &self,
+        name: &str,
+    ) -> Result<T::AggregateResult, FlpError> {
+        let (index, typ) = self.record.field::<T>(name)?;
+        typ.decode_result(&self.outputs[index], self.num_measurements)
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Type for Record<F> {
+    type Measurement = RecordMeasurement<F>;
+    type AggregateResult = RecordAggregate<F>;
+    type Field = F;
+
+    fn encode_measurement(&self, measurement: &RecordMeasurement<F>) -> Result<Vec<F>, FlpError> {
+        if measurement.parts.len() != self.fields.len()
+            || measurement
+                .parts
+                .iter()
+                .zip(self.fields.iter())
+                .any(|(part, field)| part.len() != field.typ.input_len())
+        {
+            return Err(FlpError::Encode(
+                "measurement does not match the fields of the record".into(),
+            ));
+        }
+        Ok(measurement.parts.concat())
+    }
+
+    fn decode_result(
+        &self,
+        data: &[F],
+        num_measurements: usize,
+    ) -> Result<RecordAggregate<F>, FlpError> {
+        if data.len() != self.output_len() {
+            return Err(FlpError::Decode("unexpected input length".into()));
+        }
+        let mut outputs = Vec::with_capacity(self.fields.len());
+        let mut data = data;
+        for field in self.fields.iter() {
+            let (output, rest) = data.split_at(field.typ.output_len());
+            outputs.push(output.to_vec());
+            data = rest;
+        }
+        Ok(RecordAggregate {
+            record: self.clone(),
+            outputs,
+            num_measurements,
+        })
+    }
+
+    fn gadget(&self) -> Vec<Box<dyn Gadget<F>>> {
+        self.fields
+            .iter()
+            .flat_map(|field| field.typ.gadget())
+            .collect()
+    }
+
+    fn valid(
+        &self,
+        g: &mut Vec<Box<dyn Gadget<F>>>,
+        input: &[F],
+        joint_rand: &[F],
+        num_shares: usize,
+    ) -> Result<F, FlpError> {
+        self.valid_call_check(input, joint_rand)?;
+        let (mut joint_rand, weight) = joint_rand.split_at(joint_rand.len() - 1);
+        let mut input = input;
+
+        // Each field expects its own gadgets, in order. The gadgets are put back together as we
+        // go, as the caller inspects them after evaluating the circuit.
+        let mut gadgets = std::mem::take(g);
+        let mut out = F::zero();
+        let mut r = F::one();
+        for field in self.fields.iter() {
+            let (field_input, rest) = input.split_at(field.typ.input_len());
+            input = rest;
+            let (field_joint_rand, rest) = joint_rand.split_at(field.typ.joint_rand_len());
+            joint_rand = rest;
+            let mut field_gadgets: Vec<_> = gadgets.drain(..field.num_gadgets).collect();
+            let valid = field.typ.valid(
+                &mut field_gadgets,
+                field_input,
+                field_joint_rand,
+                num_shares,
+            );
+            g.append(&mut field_gadgets);
+            out += r * valid?;
+            r *= weight[0];
+        }
+        Ok(out)
+    }
+
+    fn truncate(&self, input: Vec<F>) -> Result<Vec<F>, FlpError> {
+        self.truncate_call_check(&input)?;
+        let mut output = Vec::with_capacity(self.output_len());
+        let mut input = input.as_slice();
+        for field in self.fields.iter() {
+            let (field_input, rest) = input.split_at(field.typ.input_len());
+            input = rest;
+            output.append(&mut field.typ.truncate(field_input.to_vec())?);
+        }
+        Ok(output)
+    }
+
+    fn input_len(&self) -> usize {
+        self.fields.iter().map(|field| field.typ.input_len()).sum()
+    }
+
+    fn proof_len(&self) -> usize {
+        self.fields.iter().map(|field| field.typ.proof_len()).sum()
+    }
+
+    fn verifier_len(&self) -> usize {
+        // Each verifier includes the output of the validity circuit, but the record has only one.
+        self.fields
+            .iter()
+            .map(|field| field.typ.verifier_len() - 1)
+            .sum::<usize>()
+            + 1
+    }
+
+    fn output_len(&self) -> usize {
+        self.fields.iter().map(|field| field.typ.output_len()).sum()
+    }
+
+    fn joint_rand_len(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| field.typ.joint_rand_len())
+            .sum::<usize>()
+            + 1
+    }
+
+    fn prove_rand_len(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| field.typ.prove_rand_len())
+            .sum()
+    }
+
+    fn query_rand_len(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| field.typ.query_rand_len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{Field64 as TestField, FieldElement};
+    use crate::flp::gadgets::{Mul, ParallelSum};
+    use crate::flp::test_utils::FlpTest;
+    use crate::flp::types::{Count, Histogram, Sum};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn test_pair() {
+        let typ = Pair::new(
+            Pair::new(Count::<TestField>::new(), Count::new()),
+            Pair::new(
+                Sum::new(4).unwrap(),
+                Histogram::<TestField, ParallelSum<TestField, Mul<TestField>>>::new(3, 2).unwrap(),
+            ),
+        );
+        let zero = TestField::zero();
+        let one = TestField::one();
+        let two = TestField::from(2);
+
+        // Round trip
+        let measurement = ((true, false), (9, 2));
+        let encoded = typ.encode_measurement(&measurement).unwrap();
+        assert_eq!(encoded, [one, zero, one, zero, zero, one, zero, zero, one]);
+        assert_eq!(
+            typ.decode_result(&typ.truncate(encoded.clone()).unwrap(), 1)
+                .unwrap(),
+            ((1, 0), (9, vec![0, 0, 1]))
+        );
+        assert_matches!(
+            typ.encode_measurement(&((true, false), (16, 2))),
+            Err(FlpError::Encode(_))
+        );
+
+        // Test valid inputs.
+        FlpTest::expect_valid::<3>(
+            &typ,
+            &encoded,
+            &[one, zero, TestField::from(9), zero, zero, one],
+        );
+        for measurement in [((false, false), (0, 0)), ((true, true), (15, 1))] {
+            FlpTest::expect_valid_no_output::<3>(
+                &typ,
+                &typ.encode_measurement(&measurement).unwrap(),
+            );
+        }
+
+        // Test invalid inputs: an invalid measurement of any one of the types.
+        for i in [0, 1, 3] {
+            let mut input = encoded.clone();
+            input[i] = two;
+            FlpTest::expect_invalid::<3>(&typ, &input);
+        }
+        let mut input = encoded.clone();
+        input[7] = one;
+        FlpTest::expect_invalid::<3>(&typ, &input);
+    }
+
+    #[test]
+    fn test_record() {
+        type TestHistogram = Histogram<TestField, ParallelSum<TestField, Mul<TestField>>>;
+        let record = RecordBuilder::new()
+            .field("crashed", Count::<TestField>::new())
+            .unwrap()
+            .field("latency", Sum::new(4).unwrap())
+            .unwrap()
+            .field("bucket", TestHistogram::new(3, 2).unwrap())
+            .unwrap()
+            .field("used_a", Count::new())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            record.field_names().collect::<Vec<_>>(),
+            ["crashed", "latency", "bucket", "used_a"]
+        );
+        let zero = TestField::zero();
+        let one = TestField::one();
+        let two = TestField::from(2);
+        let measurement = |crashed, latency, bucket, used_a| {
+            record
+                .measurement()
+                .set::<Count<TestField>>("crashed", &crashed)
+                .unwrap()
+                .set::<Sum<TestField>>("latency", &latency)
+                .unwrap()
+                .set::<TestHistogram>("bucket", &bucket)
+                .unwrap()
+                .set::<Count<TestField>>("used_a", &used_a)
+                .unwrap()
+                .finish()
+                .unwrap()
+        };
+
+        // The encoding is that of the fields in order of declaration.
+        let encoded = record
+            .encode_measurement(&measurement(true, 9, 2, false))
+            .unwrap();
+        assert_eq!(encoded, [one, one, zero, zero, one, zero, zero, one, zero]);
+
+        // Test valid and invalid inputs.
+        FlpTest::expect_valid::<3>(
+            &record,
+            &encoded,
+            &[one, TestField::from(9), zero, zero, one, zero],
+        );
+        for i in [0, 1, 8] {
+            let mut input = encoded.clone();
+            input[i] = two;
+            FlpTest::expect_invalid::<3>(&record, &input);
+        }
+        let mut input = encoded.clone();
+        input[5] = one;
+        FlpTest::expect_invalid::<3>(&record, &input);
+
+        // Decode the aggregate of a few measurements by field.
+        let mut aggregate = vec![zero; record.output_len()];
+        for m in [
+            measurement(true, 9, 2, false),
+            measurement(false, 3, 2, true),
+            measurement(true, 15, 0, true),
+        ] {
+            let output = record
+                .truncate(record.encode_measurement(&m).unwrap())
+                .unwrap();
+            for (sum, x) in aggregate.iter_mut().zip(output) {
+                *sum += x;
+            }
+        }
+        let aggregate = record.decode_result(&aggregate, 3).unwrap();
+        assert_eq!(aggregate.get::<Count<TestField>>("crashed").unwrap(), 2);
+        assert_eq!(aggregate.get::<Sum<TestField>>("latency").unwrap(), 27);
+        assert_eq!(aggregate.get::<TestHistogram>("bucket").unwrap(), [1, 0, 2]);
+        assert_eq!(aggregate.get::<Count<TestField>>("used_a").unwrap(), 2);
+        assert_matches!(
+            aggregate.get::<Sum<TestField>>("crashed"),
+            Err(FlpError::InvalidParameter(_))
+        );
+        assert_matches!(
+            aggregate.get::<Count<TestField>>("unknown"),
+            Err(FlpError::InvalidParameter(_))
+        );
+
+        // Errors building records and measurements.
+        assert_matches!(
+            record
+                .measurement()
+                .set::<Count<TestField>>("crashed", &true)
+                .unwrap()
+                .finish(),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            record.measurement().set::<Sum<TestField>>("latency", &16),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            RecordBuilder::new()
+                .field("crashed", Count::<TestField>::new())
+                .unwrap()
+                .field("crashed", Count::new()),
+            Err(FlpError::InvalidParameter(_))
+        );
+        assert_matches!(
+            RecordBuilder::<TestField>::new().build(),
+            Err(FlpError::InvalidParameter(_))
+        );
+
+        // Records compare equal if their fields have the same names and types.
+        let other = RecordBuilder::new()
+            .field("crashed", Count::<TestField>::new())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(other.clone(), other);
+        assert_ne!(other, record);
+    }
+}
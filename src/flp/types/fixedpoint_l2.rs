@@ -99,6 +99,7 @@
 //!    the square of the norm.
 //! 2. We want our norm computation result to be integral and in the range `[0, 2^(2n-2))`,
 //!    so we can represent it in our field integers. We achieve this by multiplying with `2^(2n-2)`.
+//!
 //! This means that what is actually computed in this type is the following:
 //! ```text
 //! our_norm(xs) = 2^(2n-2) * norm(xs)^2
@@ -136,6 +137,7 @@
 //!  - The result of `norm(xs)` should be in `[0,1)`.
 //!  - Thus, the result of `our_norm(xs)` should be in `[0,2^(2n-2))`.
 //!  - The result of `our_norm_on_encoded(ys)` should be in `[0,2^(2n-2))`.
+//!
 //! This means that the valid norms are exactly those representable with `2n-2`
 //! bits.
 //!
@@ -170,6 +172,7 @@
 //!
 
 pub mod compatible_float;
+pub mod quantize;
 
 use crate::dp::{distributions::ZCdpDiscreteGaussian, DifferentialPrivacyStrategy, DpError};
 use crate::field::{
@@ -396,6 +399,33 @@ where
     }
 }
 
+impl<T, SPoly, SMul> FixedPointBoundedL2VecSum<T, SPoly, SMul>
+where
+    T: Fixed + CompatibleFloat,
+    SPoly: ParallelSumGadget<Field128, PolyEval<Field128>> + Eq + Clone + 'static,
+    SMul: ParallelSumGadget<Field128, Mul<Field128>> + Eq + Clone + 'static,
+{
+    /// Decodes an aggregate like [`Type::decode_result`], then divides each entry of the sum by
+    /// the number of measurements, yielding the mean of the submitted vectors. Fails if there are
+    /// no measurements.
+    pub fn decode_mean(
+        &self,
+        data: &[Field128],
+        num_measurements: usize,
+    ) -> Result<Vec<f64>, FlpError> {
+        if num_measurements == 0 {
+            return Err(FlpError::Decode(
+                "cannot take the mean of zero measurements".into(),
+            ));
+        }
+        let mut res = self.decode_result(data, num_measurements)?;
+        for x in res.iter_mut() {
+            *x /= num_measurements as f64;
+        }
+        Ok(res)
+    }
+}
+
 impl<T, SPoly, SMul> Type for FixedPointBoundedL2VecSum<T, SPoly, SMul>
 where
     T: Fixed + CompatibleFloat,
@@ -759,6 +789,15 @@ mod tests {
             .unwrap(),
             vec!(0.25, 0.125, 0.0625)
         );
+        let encoded = vsum
+            .truncate(vsum.encode_measurement(&fp_vec).unwrap())
+            .unwrap();
+        let sum: Vec<Field128> = encoded.iter().map(|x| *x + *x).collect();
+        assert_eq!(
+            vsum.decode_mean(&sum, 2).unwrap(),
+            vec!(0.25, 0.125, 0.0625)
+        );
+        assert!(vsum.decode_mean(&sum, 0).is_err());
 
         // Noise
         let mut v = vsum
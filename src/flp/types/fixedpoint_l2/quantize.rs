@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Quantization of model updates for the [`FixedPointBoundedL2VecSum`] type.
+//!
+//! In federated learning, each Client submits an update of the model as a vector of floats, and
+//! the Collector obtains their sum. A [`Quantizer`] converts an update into a measurement of the
+//! norm-bounded type: the update is clipped to an L2 norm of at most `clip_norm`, scaled into the
+//! unit ball, and rounded stochastically to the fixed point type, so that the rounding is unbiased.
+//! The scale leaves enough room for the rounding error, so that every quantized update passes the
+//! norm check of the validity circuit. [`Quantizer::dequantize`] undoes the scaling on the
+//! aggregate.
+//!
+//! ```
+//! use fixed::{types::extra::U31, FixedI32};
+//! use prio::{
+//!     field::Field128,
+//!     flp::{
+//!         gadgets::{Mul, ParallelSum, PolyEval},
+//!         types::fixedpoint_l2::{quantize::Quantizer, FixedPointBoundedL2VecSum},
+//!         Type,
+//!     },
+//! };
+//!
+//! let typ: FixedPointBoundedL2VecSum<
+//!     FixedI32<U31>,
+//!     ParallelSum<Field128, PolyEval<Field128>>,
+//!     ParallelSum<Field128, Mul<Field128>>,
+//! > = FixedPointBoundedL2VecSum::new(3).unwrap();
+//! let quantizer = Quantizer::new(&typ, 10.0).unwrap();
+//!
+//! let measurement = quantizer
+//!     .quantize(&[3.0, -4.0, 1.5], &mut rand::thread_rng())
+//!     .unwrap();
+//! let output = typ.truncate(typ.encode_measurement(&measurement).unwrap()).unwrap();
+//! let update = quantizer.dequantize(&typ.decode_result(&output, 1).unwrap());
+//! assert!((update[1] + 4.0).abs() < 1e-6);
+//! ```
+
+use crate::field::Field128;
+use crate::flp::gadgets::{Mul, ParallelSumGadget, PolyEval};
+use crate::flp::types::fixedpoint_l2::{
+    compatible_float::CompatibleFloat, FixedPointBoundedL2VecSum,
+};
+use crate::flp::FlpError;
+use fixed::traits::Fixed;
+use rand::Rng;
+use std::marker::PhantomData;
+
+/// Converts model updates to and from measurements of a [`FixedPointBoundedL2VecSum`] with fixed
+/// point type `T`. See the [module documentation](self) for details.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantizer<T> {
+    entries: usize,
+    clip_norm: f64,
+    scale: f64,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Fixed + CompatibleFloat> Quantizer<T> {
+    /// Returns a quantizer for measurements of `typ`, which clips updates to an L2 norm of at most
+    /// `clip_norm`.
+    ///
+    /// # Errors
+    ///
+    /// * `clip_norm` is not a positive, finite number.
+    /// * The vectors of `typ` are so long that the rounding error can exceed the norm bound.
+    pub fn new<SPoly, SMul>(
+        typ: &FixedPointBoundedL2VecSum<T, SPoly, SMul>,
+        clip_norm: f32,
+    ) -> Result<Self, FlpError>
+    where
+        SPoly: ParallelSumGadget<Field128, PolyEval<Field128>> + Clone,
+        SMul: ParallelSumGadget<Field128, Mul<Field128>> + Clone,
+    {
+        if !clip_norm.is_finite() || clip_norm <= 0.0 {
+            return Err(FlpError::InvalidParameter(format!(
+                "invalid clipping norm {clip_norm}"
+            )));
+        }
+
+        // Rounding moves each entry by less than one unit in the last place, and hence the vector
+        // by less than `sqrt(entries)` of them, and saturating the largest entry may move it down
+        // by one more. The scaled update must be that far inside the unit ball.
+        let ulp = T::DELTA.to_num::<f64>();
+        let margin = ((typ.entries as f64).sqrt() + 1.0) * ulp;
+        if margin >= 0.5 {
+            return Err(FlpError::InvalidParameter(
+                "too many entries for the precision of the fixed point type".into(),
+            ));
+        }
+
+        Ok(Self {
+            entries: typ.entries,
+            clip_norm: clip_norm.into(),
+            scale: (1.0 - margin) / f64::from(clip_norm),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the clipping norm.
+    pub fn clip_norm(&self) -> f32 {
+        self.clip_norm as f32
+    }
+
+    /// Quantizes `update`, drawing the randomness for the stochastic rounding from `rng`. Returns
+    /// an error if the update has the wrong length or is not finite.
+    pub fn quantize<R: Rng + ?Sized>(
+        &self,
+        update: &[f32],
+        rng: &mut R,
+    ) -> Result<Vec<T>, FlpError> {
+        if update.len() != self.entries {
+            return Err(FlpError::Encode(format!(
+                "unexpected update length: got {}; want {}",
+                update.len(),
+                self.entries
+            )));
+        }
+        if update.iter().any(|x| !x.is_finite()) {
+            return Err(FlpError::Encode("update is not finite".into()));
+        }
+
+        let norm = update
+            .iter()
+            .map(|x| f64::from(*x) * f64::from(*x))
+            .sum::<f64>()
+            .sqrt();
+        let scale = if norm > self.clip_norm {
+            self.scale * self.clip_norm / norm
+        } else {
+            self.scale
+        };
+
+        let ulps = 2f64.powi(T::FRAC_NBITS as i32);
+        Ok(update
+            .iter()
+            .map(|x| {
+                let scaled = f64::from(*x) * scale * ulps;
+                let floor = scaled.floor();
+                let rounded = if rng.gen::<f64>() < scaled - floor {
+                    floor + 1.0
+                } else {
+                    floor
+                };
+                T::saturating_from_num(rounded / ulps)
+            })
+            .collect())
+    }
+
+    /// Converts an aggregate, as decoded by
+    /// [`Type::decode_result`](crate::flp::Type::decode_result) or
+    /// [`FixedPointBoundedL2VecSum::decode_mean`], back to the scale of the updates.
+    pub fn dequantize(&self, aggregate: &[f64]) -> Vec<f32> {
+        aggregate.iter().map(|x| (x / self.scale) as f32).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flp::gadgets::ParallelSum;
+    use crate::flp::test_utils::FlpTest;
+    use crate::flp::Type;
+    use assert_matches::assert_matches;
+    use fixed::types::extra::{U15, U31};
+    use fixed::{FixedI16, FixedI32};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    type TestType<T> = FixedPointBoundedL2VecSum<
+        T,
+        ParallelSum<Field128, PolyEval<Field128>>,
+        ParallelSum<Field128, Mul<Field128>>,
+    >;
+
+    #[test]
+    fn quantize_within_norm_bound() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let typ = TestType::<FixedI16<U15>>::new(4).unwrap();
+        let quantizer = Quantizer::new(&typ, 2.0).unwrap();
+
+        // Updates at and beyond the clipping norm, including ones whose entries round up, still
+        // pass the norm check.
+        for update in [
+            [2.0, 0.0, 0.0, 0.0],
+            [1.0, -1.0, 1.0, -1.0],
+            [100.0, 100.0, -100.0, 0.1],
+            [0.3, 0.0001, -0.00002, 0.7],
+        ] {
+            for _ in 0..10 {
+                let measurement = quantizer.quantize(&update, &mut rng).unwrap();
+                FlpTest::expect_valid_no_output::<2>(
+                    &typ,
+                    &typ.encode_measurement(&measurement).unwrap(),
+                );
+            }
+        }
+
+        let update = [1.0, -1.0, 0.5, 0.0];
+        let measurement = quantizer.quantize(&update, &mut rng).unwrap();
+        let output = typ
+            .truncate(typ.encode_measurement(&measurement).unwrap())
+            .unwrap();
+        let dequantized = quantizer.dequantize(&typ.decode_result(&output, 1).unwrap());
+        for (x, y) in update.iter().zip(dequantized) {
+            assert!((x - y).abs() < 1e-3, "{x} {y}");
+        }
+
+        // Clipping preserves the direction of the update.
+        let measurement = quantizer
+            .quantize(&[30.0, 40.0, 0.0, 0.0], &mut rng)
+            .unwrap();
+        let output = typ
+            .truncate(typ.encode_measurement(&measurement).unwrap())
+            .unwrap();
+        let dequantized = quantizer.dequantize(&typ.decode_result(&output, 1).unwrap());
+        assert!((dequantized[0] - 1.2).abs() < 1e-3, "{dequantized:?}");
+        assert!((dequantized[1] - 1.6).abs() < 1e-3, "{dequantized:?}");
+    }
+
+    #[test]
+    fn quantize_is_unbiased() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let typ = TestType::<FixedI16<U15>>::new(1).unwrap();
+        let quantizer = Quantizer::new(&typ, 1.0).unwrap();
+
+        // A value a third of the way between two fixed point numbers rounds up a third of the
+        // time.
+        let ulp = FixedI16::<U15>::DELTA.to_num::<f64>();
+        let update = [((10.0 + 1.0 / 3.0) * ulp / quantizer.scale) as f32];
+        let num_trials = 3000;
+        let mut sum = 0.0;
+        for _ in 0..num_trials {
+            sum += quantizer.quantize(&update, &mut rng).unwrap()[0].to_num::<f64>();
+        }
+        let mean = sum / f64::from(num_trials) / ulp;
+        assert!((mean - (10.0 + 1.0 / 3.0)).abs() < 0.05, "{mean}");
+    }
+
+    #[test]
+    fn quantize_errors() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let typ = TestType::<FixedI32<U31>>::new(2).unwrap();
+        for clip_norm in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_matches!(
+                Quantizer::new(&typ, clip_norm),
+                Err(FlpError::InvalidParameter(_))
+            );
+        }
+        let quantizer = Quantizer::new(&typ, 1.0).unwrap();
+        assert_eq!(quantizer.clip_norm(), 1.0);
+        assert_matches!(
+            quantizer.quantize(&[0.0], &mut rng),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            quantizer.quantize(&[0.0, f32::NAN], &mut rng),
+            Err(FlpError::Encode(_))
+        );
+        assert_matches!(
+            Quantizer::new(&TestType::<FixedI16<U15>>::new(1 << 28).unwrap(), 1.0),
+            Err(FlpError::InvalidParameter(_))
+        );
+    }
+}
@@ -30,6 +30,8 @@ use std::{
 };
 use subtle::{Choice, ConditionallyNegatable, ConditionallySelectable, ConstantTimeEq};
 
+pub mod encode;
+
 /// IDPF-related errors.
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -339,14 +341,56 @@ fn eval_next<V>(
 where
     V: IdpfValue,
 {
+    let (seeds, control_bits) =
+        extend_corrected(key, *control_bit, correction_word, extend_xof_fixed_key);
+    eval_child(
+        is_leader,
+        parameter,
+        key,
+        control_bit,
+        &seeds,
+        &control_bits,
+        correction_word,
+        input_bit,
+        convert_xof_fixed_key,
+    )
+}
+
+/// Expands the seed of a node into the corrected seeds and control bits of its two children.
+fn extend_corrected<V>(
+    key: &[u8; 16],
+    control_bit: Choice,
+    correction_word: &IdpfCorrectionWord<V>,
+    extend_xof_fixed_key: &XofFixedKeyAes128Key,
+) -> ([[u8; 16]; 2], [Choice; 2]) {
     let (mut seeds, mut control_bits) = extend(key, extend_xof_fixed_key);
 
-    seeds[0] = conditional_xor_seeds(&seeds[0], &correction_word.seed, *control_bit);
-    control_bits[0] ^= correction_word.control_bits[0] & *control_bit;
-    seeds[1] = conditional_xor_seeds(&seeds[1], &correction_word.seed, *control_bit);
-    control_bits[1] ^= correction_word.control_bits[1] & *control_bit;
+    seeds[0] = conditional_xor_seeds(&seeds[0], &correction_word.seed, control_bit);
+    control_bits[0] ^= correction_word.control_bits[0] & control_bit;
+    seeds[1] = conditional_xor_seeds(&seeds[1], &correction_word.seed, control_bit);
+    control_bits[1] ^= correction_word.control_bits[1] & control_bit;
+
+    (seeds, control_bits)
+}
 
-    let seed_corrected = conditional_select_seed(input_bit, &seeds);
+/// Computes the key, control bit and output share of the child selected by `input_bit`, from the
+/// output of [`extend_corrected`] for its parent.
+#[allow(clippy::too_many_arguments)]
+fn eval_child<V>(
+    is_leader: bool,
+    parameter: &V::ValueParameter,
+    key: &mut [u8; 16],
+    control_bit: &mut Choice,
+    seeds: &[[u8; 16]; 2],
+    control_bits: &[Choice; 2],
+    correction_word: &IdpfCorrectionWord<V>,
+    input_bit: Choice,
+    convert_xof_fixed_key: &XofFixedKeyAes128Key,
+) -> V
+where
+    V: IdpfValue,
+{
+    let seed_corrected = conditional_select_seed(input_bit, seeds);
     *control_bit = Choice::conditional_select(&control_bits[0], &control_bits[1], input_bit);
 
     let (new_key, elements) = convert::<V>(&seed_corrected, convert_xof_fixed_key, parameter);
@@ -358,6 +402,90 @@ where
     out
 }
 
+/// A node of the IDPF tree visited by [`Idpf::eval_batch`], with the indices of the prefixes that
+/// pass through it.
+struct BatchNode {
+    key: [u8; 16],
+    control_bit: Choice,
+    members: Vec<usize>,
+}
+
+/// Evaluates one level of the IDPF tree for [`Idpf::eval_batch`]: each node is expanded once, and
+/// the children that some prefix passes through are returned with their output shares.
+#[allow(clippy::too_many_arguments)]
+fn eval_batch_level<V>(
+    is_leader: bool,
+    parameter: &V::ValueParameter,
+    nodes: Vec<BatchNode>,
+    correction_word: &IdpfCorrectionWord<V>,
+    prefixes: &[IdpfInput],
+    level: usize,
+    extend_xof_fixed_key: &XofFixedKeyAes128Key,
+    convert_xof_fixed_key: &XofFixedKeyAes128Key,
+) -> Vec<(BatchNode, V)>
+where
+    V: IdpfValue,
+{
+    let mut children = Vec::with_capacity(nodes.len() * 2);
+    for node in nodes {
+        let (seeds, control_bits) = extend_corrected(
+            &node.key,
+            node.control_bit,
+            correction_word,
+            extend_xof_fixed_key,
+        );
+        let (left, right): (Vec<usize>, Vec<usize>) = node
+            .members
+            .into_iter()
+            .partition(|index| !prefixes[*index][level]);
+        for (bit, members) in [(false, left), (true, right)] {
+            if members.is_empty() {
+                continue;
+            }
+            let mut key = node.key;
+            let mut control_bit = node.control_bit;
+            let value = eval_child(
+                is_leader,
+                parameter,
+                &mut key,
+                &mut control_bit,
+                &seeds,
+                &control_bits,
+                correction_word,
+                Choice::from(bit as u8),
+                convert_xof_fixed_key,
+            );
+            children.push((
+                BatchNode {
+                    key,
+                    control_bit,
+                    members,
+                },
+                value,
+            ));
+        }
+    }
+    children
+}
+
+/// Returns the fixed-key XOFs used to extend and convert seeds.
+fn xof_fixed_keys(binder: &[u8]) -> (XofFixedKeyAes128Key, XofFixedKeyAes128Key) {
+    let extend_dst = [
+        VERSION, 1, /* algorithm class */
+        0, 0, 0, 0, /* algorithm ID */
+        0, 0, /* usage */
+    ];
+    let convert_dst = [
+        VERSION, 1, /* algorithm class */
+        0, 0, 0, 0, /* algorithm ID */
+        0, 1, /* usage */
+    ];
+    (
+        XofFixedKeyAes128Key::new(&extend_dst, binder),
+        XofFixedKeyAes128Key::new(&convert_dst, binder),
+    )
+}
+
 /// This defines a family of IDPFs (incremental distributed point functions) with certain types of
 /// values at inner tree nodes and at leaf tree nodes.
 ///
@@ -402,18 +530,7 @@ where
         let initial_keys: [Seed<16>; 2] =
             [Seed::from_bytes(random[0]), Seed::from_bytes(random[1])];
 
-        let extend_dst = [
-            VERSION, 1, /* algorithm class */
-            0, 0, 0, 0, /* algorithm ID */
-            0, 0, /* usage */
-        ];
-        let convert_dst = [
-            VERSION, 1, /* algorithm class */
-            0, 0, 0, 0, /* algorithm ID */
-            0, 1, /* usage */
-        ];
-        let extend_xof_fixed_key = XofFixedKeyAes128Key::new(&extend_dst, binder);
-        let convert_xof_fixed_key = XofFixedKeyAes128Key::new(&convert_dst, binder);
+        let (extend_xof_fixed_key, convert_xof_fixed_key) = xof_fixed_keys(binder);
 
         let mut keys = [initial_keys[0].0, initial_keys[1].0];
         let mut control_bits = [Choice::from(0u8), Choice::from(1u8)];
@@ -499,19 +616,7 @@ where
         cache: &mut dyn IdpfCache,
     ) -> Result<IdpfOutputShare<VI, VL>, IdpfError> {
         let bits = public_share.inner_correction_words.len() + 1;
-
-        let extend_dst = [
-            VERSION, 1, /* algorithm class */
-            0, 0, 0, 0, /* algorithm ID */
-            0, 0, /* usage */
-        ];
-        let convert_dst = [
-            VERSION, 1, /* algorithm class */
-            0, 0, 0, 0, /* algorithm ID */
-            0, 1, /* usage */
-        ];
-        let extend_xof_fixed_key = XofFixedKeyAes128Key::new(&extend_dst, binder);
-        let convert_xof_fixed_key = XofFixedKeyAes128Key::new(&convert_dst, binder);
+        let (extend_xof_fixed_key, convert_xof_fixed_key) = xof_fixed_keys(binder);
 
         let mut last_inner_output = None;
         for ((correction_word, input_bit), level) in public_share.inner_correction_words
@@ -621,6 +726,101 @@ where
             cache,
         )
     }
+
+    /// Evaluate an IDPF share on each of `prefixes`, which must all have the same length, and
+    /// return the output shares in the same order.
+    ///
+    /// This is equivalent to calling [`Idpf::eval`] on each prefix, but faster: each node of the
+    /// tree that some prefix passes through is visited once, and the seed of a node is expanded
+    /// once for both of its children, so sibling prefixes share the work.
+    pub fn eval_batch(
+        &self,
+        agg_id: usize,
+        public_share: &IdpfPublicShare<VI, VL>,
+        key: &Seed<16>,
+        prefixes: &[IdpfInput],
+        binder: &[u8],
+    ) -> Result<Vec<IdpfOutputShare<VI, VL>>, IdpfError>
+    where
+        VI: Clone,
+        VL: Clone,
+    {
+        let bits = public_share.inner_correction_words.len() + 1;
+        if agg_id > 1 {
+            return Err(IdpfError::InvalidParameter(format!(
+                "invalid aggregator ID {agg_id}"
+            )));
+        }
+        let is_leader = agg_id == 0;
+        let Some(len) = prefixes.first().map(IdpfInput::len) else {
+            return Ok(Vec::new());
+        };
+        if len == 0 {
+            return Err(IdpfError::InvalidParameter("empty prefix".to_string()));
+        }
+        if len > bits {
+            return Err(IdpfError::InvalidParameter(format!(
+                "prefix length ({len}) exceeds configured number of bits ({bits})",
+            )));
+        }
+        if prefixes.iter().any(|prefix| prefix.len() != len) {
+            return Err(IdpfError::InvalidParameter(
+                "prefixes have different lengths".to_string(),
+            ));
+        }
+        let (extend_xof_fixed_key, convert_xof_fixed_key) = xof_fixed_keys(binder);
+
+        let mut nodes = vec![BatchNode {
+            key: key.0,
+            control_bit: Choice::from((!is_leader) as u8),
+            members: (0..prefixes.len()).collect(),
+        }];
+        let mut inner_outputs = Vec::new();
+        for (level, correction_word) in public_share.inner_correction_words[..len.min(bits - 1)]
+            .iter()
+            .enumerate()
+        {
+            let children = eval_batch_level(
+                is_leader,
+                &self.inner_node_value_parameter,
+                nodes,
+                correction_word,
+                prefixes,
+                level,
+                &extend_xof_fixed_key,
+                &convert_xof_fixed_key,
+            );
+            (nodes, inner_outputs) = children.into_iter().unzip();
+        }
+
+        let mut outputs: Vec<Option<IdpfOutputShare<VI, VL>>> =
+            prefixes.iter().map(|_| None).collect();
+        if len == bits {
+            let children = eval_batch_level(
+                is_leader,
+                &self.leaf_node_value_parameter,
+                nodes,
+                &public_share.leaf_correction_word,
+                prefixes,
+                bits - 1,
+                &extend_xof_fixed_key,
+                &convert_xof_fixed_key,
+            );
+            for (node, value) in children {
+                for index in node.members {
+                    outputs[index] = Some(IdpfOutputShare::Leaf(value.clone()));
+                }
+            }
+        } else {
+            for (node, value) in nodes.into_iter().zip(inner_outputs) {
+                for index in node.members {
+                    outputs[index] = Some(IdpfOutputShare::Inner(value.clone()));
+                }
+            }
+        }
+        // Unwrap safety: every prefix is a member of exactly one node at each level.
+        Ok(outputs.into_iter().map(Option::unwrap).collect())
+    }
 }
 
 /// An IDPF public share. This contains the list of correction words used by all parties when
@@ -1087,7 +1287,7 @@ mod tests {
     use subtle::{Choice, ConditionallyNegatable, ConditionallySelectable};
 
     use super::{
-        HashMapCache, Idpf, IdpfCache, IdpfCorrectionWord, IdpfInput, IdpfOutputShare,
+        HashMapCache, Idpf, IdpfCache, IdpfCorrectionWord, IdpfError, IdpfInput, IdpfOutputShare,
         IdpfPublicShare, NoCache, RingBufferCache,
     };
     use crate::{
@@ -1390,6 +1590,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn idpf_poplar_eval_batch() {
+        const INPUT_LEN: usize = 10;
+        let bits: Vec<bool> = (0..INPUT_LEN).map(|_| random()).collect();
+        let input = IdpfInput::from_bools(&bits);
+        let mut prng = Prng::new().unwrap();
+        let inner_values = (0..INPUT_LEN - 1)
+            .map(|_| Poplar1IdpfValue::new([Field64::one(), prng.next().unwrap()]))
+            .collect::<Vec<_>>();
+        let leaf_value =
+            Poplar1IdpfValue::new([Field255::one(), Prng::new().unwrap().next().unwrap()]);
+
+        let nonce: [u8; 16] = random();
+        let idpf = Idpf::new((), ());
+        let (public_share, keys) = idpf.gen(&input, inner_values, leaf_value, &nonce).unwrap();
+
+        for len in [1, 4, INPUT_LEN] {
+            // The prefix on the path, its sibling, some random prefixes, and a repeat.
+            let mut prefixes = vec![input.prefix(len - 1)];
+            let mut sibling = bits[..len].to_vec();
+            sibling[len - 1] = !sibling[len - 1];
+            prefixes.push(IdpfInput::from_bools(&sibling));
+            for _ in 0..20 {
+                let random_bits: Vec<bool> = (0..len).map(|_| random()).collect();
+                prefixes.push(IdpfInput::from_bools(&random_bits));
+            }
+            prefixes.push(input.prefix(len - 1));
+
+            for (agg_id, key) in keys.iter().enumerate() {
+                let batch = idpf
+                    .eval_batch(agg_id, &public_share, key, &prefixes, &nonce)
+                    .unwrap();
+                assert_eq!(batch.len(), prefixes.len());
+                for (prefix, output) in prefixes.iter().zip(batch) {
+                    let expected = idpf
+                        .eval(
+                            agg_id,
+                            &public_share,
+                            key,
+                            prefix,
+                            &nonce,
+                            &mut NoCache::new(),
+                        )
+                        .unwrap();
+                    assert_eq!(output, expected);
+                }
+            }
+        }
+
+        assert!(idpf
+            .eval_batch(0, &public_share, &keys[0], &[], &nonce)
+            .unwrap()
+            .is_empty());
+        assert_matches!(
+            idpf.eval_batch(
+                0,
+                &public_share,
+                &keys[0],
+                &[input.prefix(0), input.prefix(1)],
+                &nonce
+            ),
+            Err(IdpfError::InvalidParameter(_))
+        );
+        assert!(idpf
+            .eval_batch(
+                2,
+                &public_share,
+                &keys[0],
+                std::slice::from_ref(&input),
+                &nonce
+            )
+            .is_err());
+        assert!(idpf
+            .eval_batch(
+                0,
+                &public_share,
+                &keys[0],
+                &[input.clone_with_suffix(&[true])],
+                &nonce
+            )
+            .is_err());
+    }
+
     #[test]
     fn idpf_poplar_cache_behavior() {
         let bits = bitbox![0, 1, 1, 1, 0, 1, 0, 0];
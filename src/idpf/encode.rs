@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Encodings of strings as IDPF inputs.
+//!
+//! Heavy-hitters VDAFs such as [`Poplar1`](crate::vdaf::poplar1::Poplar1) take inputs of a fixed
+//! number of bits. [`StringEncoder`] maps a UTF-8 string to a fixed number of bytes, truncating
+//! long strings at a character boundary and padding short ones with zero bytes.
+//! [`DomainNameEncoder`] does the same for DNS names after normalizing them and reversing their
+//! labels, so that `www.example.com` is encoded as `com.example.www`. The prefixes of an encoded
+//! name are then its parent domains, and a heavy-hitters search finds popular domains as well as
+//! popular names.
+//!
+//! The Collector decodes the heavy hitters, or prefixes of them that end on a byte boundary, with
+//! the same encoder. Trailing zero bytes are taken to be padding, so a string that ends with
+//! `'\0'` does not survive the round trip.
+
+use crate::idpf::{IdpfError, IdpfInput};
+
+/// The maximum length of a DNS name in its text form, without the trailing dot.
+const MAX_DOMAIN_NAME_LEN: usize = 253;
+
+/// The maximum length of a DNS label.
+const MAX_LABEL_LEN: usize = 63;
+
+/// Encodes UTF-8 strings as IDPF inputs of `8 * max_len` bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StringEncoder {
+    max_len: usize,
+}
+
+impl StringEncoder {
+    /// Constructs an encoder for strings of up to `max_len` bytes. The inputs are `8 * max_len`
+    /// bits long, which may be at most `u16::MAX`.
+    pub fn new(max_len: usize) -> Result<Self, IdpfError> {
+        if max_len == 0 || max_len > usize::from(u16::MAX) / 8 {
+            return Err(IdpfError::InvalidParameter(format!(
+                "maximum length must be between 1 and {} bytes",
+                u16::MAX / 8
+            )));
+        }
+        Ok(Self { max_len })
+    }
+
+    /// Returns the length of the encoded inputs in bits.
+    pub fn bits(&self) -> usize {
+        8 * self.max_len
+    }
+
+    /// Encodes `s`, keeping as many of its characters as fit in `max_len` bytes.
+    pub fn encode(&self, s: &str) -> IdpfInput {
+        let mut len = s.len().min(self.max_len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = vec![0; self.max_len];
+        bytes[..len].copy_from_slice(&s.as_bytes()[..len]);
+        IdpfInput::from_bytes(&bytes)
+    }
+
+    /// Decodes an encoded string, or a prefix of one whose length is a whole number of bytes.
+    /// Fails if the prefix ends in the middle of a character.
+    pub fn decode(&self, input: &IdpfInput) -> Result<String, IdpfError> {
+        if input.len() % 8 != 0 || input.len() > self.bits() {
+            return Err(IdpfError::InvalidParameter(format!(
+                "input of {} bits is not a whole number of bytes up to {}",
+                input.len(),
+                self.max_len
+            )));
+        }
+        let mut bytes = input.to_bytes();
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        String::from_utf8(bytes)
+            .map_err(|_| IdpfError::InvalidParameter("input is not valid UTF-8".into()))
+    }
+}
+
+/// Encodes DNS names as IDPF inputs of `8 * max_len` bits, with their labels reversed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DomainNameEncoder {
+    inner: StringEncoder,
+}
+
+impl DomainNameEncoder {
+    /// Constructs an encoder for names of up to `max_len` bytes. Longer names are truncated,
+    /// which keeps their top-level labels.
+    pub fn new(max_len: usize) -> Result<Self, IdpfError> {
+        Ok(Self {
+            inner: StringEncoder::new(max_len)?,
+        })
+    }
+
+    /// Returns the length of the encoded inputs in bits.
+    pub fn bits(&self) -> usize {
+        self.inner.bits()
+    }
+
+    /// Encodes `name` after lowercasing it and removing a trailing dot. Fails unless the name is
+    /// ASCII (internationalized names must be converted to their `xn--` form first) and its labels
+    /// are non-empty and within the length limits of DNS.
+    pub fn encode(&self, name: &str) -> Result<IdpfInput, IdpfError> {
+        let name = name.strip_suffix('.').unwrap_or(name);
+        if !name.is_ascii() {
+            return Err(IdpfError::InvalidParameter(
+                "domain name is not ASCII".into(),
+            ));
+        }
+        if name.len() > MAX_DOMAIN_NAME_LEN {
+            return Err(IdpfError::InvalidParameter(
+                "domain name is too long".into(),
+            ));
+        }
+        if name
+            .split('.')
+            .any(|label| label.is_empty() || label.len() > MAX_LABEL_LEN)
+        {
+            return Err(IdpfError::InvalidParameter(
+                "domain name has an empty or overlong label".into(),
+            ));
+        }
+        let reversed = name
+            .to_ascii_lowercase()
+            .rsplit('.')
+            .collect::<Vec<_>>()
+            .join(".");
+        Ok(self.inner.encode(&reversed))
+    }
+
+    /// Decodes an encoded name, or a prefix of one whose length is a whole number of bytes, back
+    /// to the usual label order. The first label of a truncated name may be incomplete.
+    pub fn decode(&self, input: &IdpfInput) -> Result<String, IdpfError> {
+        let reversed = self.inner.decode(input)?;
+        Ok(reversed.rsplit('.').collect::<Vec<_>>().join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::prefix_tree::PrefixTree;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn string_encoder() {
+        let encoder = StringEncoder::new(4).unwrap();
+        assert_eq!(encoder.bits(), 32);
+
+        let input = encoder.encode("ab");
+        assert_eq!(input.len(), 32);
+        assert_eq!(input.to_bytes(), b"ab\0\0");
+        assert_eq!(encoder.decode(&input).unwrap(), "ab");
+
+        // Long strings are truncated at a character boundary: "é" takes two bytes.
+        let input = encoder.encode("abcé");
+        assert_eq!(input.to_bytes(), b"abc\0");
+        assert_eq!(encoder.decode(&input).unwrap(), "abc");
+        assert_eq!(encoder.decode(&encoder.encode("aé")).unwrap(), "aé");
+
+        // Byte-aligned prefixes decode, unless they split a character.
+        let input = encoder.encode("aéb");
+        assert_eq!(encoder.decode(&input.prefix(7)).unwrap(), "a");
+        assert_matches!(
+            encoder.decode(&input.prefix(15)),
+            Err(IdpfError::InvalidParameter(_))
+        );
+        assert!(encoder.decode(&input.prefix(3)).is_err());
+        assert!(encoder.decode(&IdpfInput::from_bytes(b"abcde")).is_err());
+
+        assert!(StringEncoder::new(0).is_err());
+        assert!(StringEncoder::new(8192).is_err());
+    }
+
+    #[test]
+    fn domain_name_encoder() {
+        let encoder = DomainNameEncoder::new(16).unwrap();
+        let input = encoder.encode("WWW.Example.com.").unwrap();
+        assert_eq!(&input.to_bytes()[..15], b"com.example.www");
+        assert_eq!(encoder.decode(&input).unwrap(), "www.example.com");
+
+        // Prefixes of the encoding are parent domains.
+        assert_eq!(
+            encoder.decode(&input.prefix(8 * 11 - 1)).unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            encoder
+                .encode("mail.example.com")
+                .unwrap()
+                .prefix(8 * 11 - 1),
+            input.prefix(8 * 11 - 1)
+        );
+
+        // Long names keep their top-level labels.
+        let long = encoder.encode("a.very-long-subdomain.example.com").unwrap();
+        assert_eq!(encoder.decode(&long).unwrap(), "very.example.com");
+
+        for name in ["", "a..b", "bücher.example", &"a".repeat(64)] {
+            assert!(encoder.encode(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn encoded_heavy_hitters() {
+        let encoder = StringEncoder::new(1).unwrap();
+        let mut tree = PrefixTree::new(encoder.bits(), 2).unwrap();
+        let counts = |prefixes: &[IdpfInput]| -> Vec<u64> {
+            let inputs = [
+                encoder.encode("a"),
+                encoder.encode("a"),
+                encoder.encode("b"),
+            ];
+            prefixes
+                .iter()
+                .map(|prefix| {
+                    inputs
+                        .iter()
+                        .filter(|input| input.prefix(prefix.len() - 1) == *prefix)
+                        .count() as u64
+                })
+                .collect()
+        };
+        while !tree.is_done() {
+            let counts = counts(tree.prefixes());
+            tree.advance(&counts).unwrap();
+        }
+        let heavy_hitters: Vec<_> = tree
+            .heavy_hitters()
+            .iter()
+            .map(|(input, count)| (encoder.decode(input).unwrap(), *count))
+            .collect();
+        assert_eq!(heavy_hitters, [("a".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! **Insecure** stand-ins for the cryptographic primitives of this crate, for benchmarking only.
+//!
+//! Profiles of sharding and preparation mix the cost of the proof system (FFTs, polynomial
+//! evaluation, gadget calls) with the cost of the XOF that derives shares, joint randomness, and
+//! query randomness. [`XofInsecure`] replaces the XOF with a cheap, non-cryptographic mixing
+//! function, so that the cost of everything else can be measured on its own: build the same VDAF
+//! with [`XofInsecure`] as its XOF parameter and compare. The type aliases in this module do so
+//! for the common Prio3 instances.
+//!
+//! **Nothing in this module provides any security.** Shares derived with [`XofInsecure`] can be
+//! predicted from each other, so the privacy of measurements is lost, and joint randomness can be
+//! chosen by a Client, so proofs are unsound. The module is only compiled with the `insecure`
+//! feature, which must never be enabled in a deployment.
+//!
+//! This crate does not encrypt input shares, so there is no encryption layer to replace here. Prio2
+//! expands helper shares with AES-128 in CTR mode as specified by ENPA, not with an [`Xof`], and
+//! is unaffected.
+//!
+//! ```
+//! use prio::{flp::types::Count, insecure::Prio3CountInsecure, vdaf::Client};
+//!
+//! let vdaf = Prio3CountInsecure::new(2, 1, 0xFFFF_0000, Count::new()).unwrap();
+//! let (_public_share, input_shares) = vdaf.shard(&true, &[0; 16]).unwrap();
+//! assert_eq!(input_shares.len(), 2);
+//! ```
+
+use crate::{
+    field::{Field128, Field64},
+    flp::{
+        gadgets::{Mul, ParallelSum},
+        types::{Count, Histogram, Sum, SumVec},
+    },
+    vdaf::{prio3::Prio3, xof::Xof},
+};
+use rand_core::{impls, RngCore};
+
+/// Prio3Count with [`XofInsecure`] in place of TurboSHAKE128. **Insecure**; see the
+/// [module documentation](self).
+pub type Prio3CountInsecure = Prio3<Count<Field64>, XofInsecure, 16>;
+
+/// Prio3Sum with [`XofInsecure`] in place of TurboSHAKE128. **Insecure**; see the
+/// [module documentation](self).
+pub type Prio3SumInsecure = Prio3<Sum<Field128>, XofInsecure, 16>;
+
+/// Prio3SumVec with [`XofInsecure`] in place of TurboSHAKE128. **Insecure**; see the
+/// [module documentation](self).
+pub type Prio3SumVecInsecure =
+    Prio3<SumVec<Field128, ParallelSum<Field128, Mul<Field128>>>, XofInsecure, 16>;
+
+/// Prio3Histogram with [`XofInsecure`] in place of TurboSHAKE128. **Insecure**; see the
+/// [module documentation](self).
+pub type Prio3HistogramInsecure =
+    Prio3<Histogram<Field128, ParallelSum<Field128, Mul<Field128>>>, XofInsecure, 16>;
+
+/// An [`Xof`] that is fast and deterministic but offers **no security**: its state is a 64-bit
+/// FNV-1a hash of the seed, domain separation tag, and binder, and its output is a SplitMix64
+/// stream seeded with that hash. See the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct XofInsecure {
+    state: u64,
+}
+
+impl XofInsecure {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn absorb(&mut self, data: &[u8]) {
+        for byte in data {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(Self::FNV_PRIME);
+        }
+    }
+}
+
+impl<const SEED_SIZE: usize> Xof<SEED_SIZE> for XofInsecure {
+    type SeedStream = SeedStreamInsecure;
+
+    fn init(seed_bytes: &[u8; SEED_SIZE], dst: &[u8]) -> Self {
+        let mut xof = Self {
+            state: Self::FNV_OFFSET,
+        };
+        // Unlike the data passed to `update()`, the tag and seed have fixed boundaries.
+        xof.absorb(&(dst.len() as u64).to_le_bytes());
+        xof.absorb(dst);
+        xof.absorb(seed_bytes);
+        xof
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.absorb(data);
+    }
+
+    fn into_seed_stream(self) -> SeedStreamInsecure {
+        SeedStreamInsecure(self.state)
+    }
+}
+
+/// The SplitMix64 stream produced by [`XofInsecure`]. **Insecure**; see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct SeedStreamInsecure(u64);
+
+impl RngCore for SeedStreamInsecure {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{test_utils::run_vdaf, xof::Seed};
+
+    #[test]
+    fn xof_insecure() {
+        let seed = Seed::<16>::from_bytes([1; 16]);
+        let stream = |seed: &Seed<16>, dst: &[u8], binder: &[u8]| {
+            let mut bytes = [0; 40];
+            <XofInsecure as Xof<16>>::seed_stream(seed, dst, binder).fill_bytes(&mut bytes);
+            bytes
+        };
+        assert_eq!(
+            stream(&seed, b"dst", b"binder"),
+            stream(&seed, b"dst", b"binder")
+        );
+        assert_ne!(
+            stream(&seed, b"dst", b"binder"),
+            stream(&seed, b"dst", b"other")
+        );
+        assert_ne!(
+            stream(&seed, b"dst", b"binder"),
+            stream(&seed, b"ds", b"tbinder")
+        );
+        assert_ne!(
+            stream(&seed, b"dst", b"binder"),
+            stream(&Seed::from_bytes([2; 16]), b"dst", b"binder")
+        );
+    }
+
+    #[test]
+    fn run_prio3_insecure() {
+        let count = Prio3CountInsecure::new(2, 1, 0xFFFF_0000, Count::new()).unwrap();
+        assert_eq!(run_vdaf(&count, &(), [true, false, true]).unwrap(), 2);
+
+        let sum_vec =
+            Prio3SumVecInsecure::new(3, 1, 0xFFFF_0000, SumVec::new(1, 4, 2).unwrap()).unwrap();
+        assert_eq!(
+            run_vdaf(&sum_vec, &(), [vec![1, 0, 1, 1], vec![0, 0, 1, 0]]).unwrap(),
+            vec![1, 0, 2, 1]
+        );
+    }
+}
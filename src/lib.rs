@@ -17,11 +17,14 @@
 pub mod benchmarked;
 #[cfg(feature = "experimental")]
 mod bt;
+#[cfg(feature = "capi")]
+#[cfg_attr(docsrs, doc(cfg(feature = "capi")))]
+pub mod capi;
 pub mod codec;
 #[cfg(feature = "experimental")]
 #[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
 pub mod dp;
-mod fft;
+pub mod fft;
 pub mod field;
 pub mod flp;
 mod fp;
@@ -32,8 +35,21 @@ mod fp64;
     doc(cfg(all(feature = "crypto-dependencies", feature = "experimental")))
 )]
 pub mod idpf;
+#[cfg(feature = "insecure")]
+#[cfg_attr(docsrs, doc(cfg(feature = "insecure")))]
+pub mod insecure;
 mod polynomial;
 mod prng;
+#[cfg(feature = "secure-memory")]
+#[cfg_attr(docsrs, doc(cfg(feature = "secure-memory")))]
+pub mod secure_memory;
+mod self_test;
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod sim;
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod testdata;
 pub mod topology;
 pub mod vdaf;
 #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
@@ -42,3 +58,5 @@ pub mod vdaf;
     doc(cfg(all(feature = "crypto-dependencies", feature = "experimental")))
 )]
 pub mod vidpf;
+
+pub use self_test::self_test;
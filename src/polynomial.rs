@@ -4,10 +4,14 @@
 //! Functions for polynomial interpolation and evaluation
 
 #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
-use crate::fft::{discrete_fourier_transform, discrete_fourier_transform_inv_finish};
+use crate::fft::{discrete_fourier_transform_inv_finish, FftBackend, FftError};
 use crate::field::FftFriendlyFieldElement;
 
-use std::convert::TryFrom;
+use core::convert::TryFrom;
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+use std::ops::{Add, AddAssign, Mul};
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+use subtle::ConstantTimeEq;
 
 /// Temporary memory used for FFT
 #[derive(Clone, Debug)]
@@ -25,6 +29,14 @@ impl<F: FftFriendlyFieldElement> PolyFFTTempMemory<F> {
             fft_roots_sub: vec![F::zero(); length],
         }
     }
+
+    /// Zeroes the scratch space.
+    #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+    pub(crate) fn reset(&mut self) {
+        self.fft_tmp.fill(F::zero());
+        self.fft_y_sub.fill(F::zero());
+        self.fft_roots_sub.fill(F::zero());
+    }
 }
 
 #[cfg(test)]
@@ -199,17 +211,70 @@ pub fn poly_mul<F: FftFriendlyFieldElement>(p: &[F], q: &[F]) -> Vec<F> {
     out
 }
 
+/// A field containing `F`, either `F` itself or an extension of it. Polynomials with coefficients
+/// in `F` can be evaluated at its elements.
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+pub trait FieldOver<F>:
+    Copy + Add<Output = Self> + AddAssign + Mul<Output = Self> + ConstantTimeEq
+{
+    /// Evaluates the polynomial with coefficients `coeffs`, lowest degree first, at `x`.
+    fn eval_poly(coeffs: &[F], x: Self) -> Self;
+}
+
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+impl<F: FftFriendlyFieldElement> FieldOver<F> for F {
+    fn eval_poly(coeffs: &[F], x: F) -> F {
+        poly_eval(coeffs, x)
+    }
+}
+
+/// Interpolates a polynomial from its values at the roots of unity and evaluates it at `eval_at`,
+/// which may lie in an extension of the field of the points.
 #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
 #[inline]
-pub fn poly_interpret_eval<F: FftFriendlyFieldElement>(
+pub fn poly_interpret_eval<F, E, B>(
     points: &[F],
-    eval_at: F,
+    eval_at: E,
     tmp_coeffs: &mut [F],
-) -> F {
+    backend: &B,
+) -> Result<E, FftError>
+where
+    F: FftFriendlyFieldElement,
+    E: FieldOver<F>,
+    B: FftBackend<F> + ?Sized,
+{
     let size_inv = F::from(F::Integer::try_from(points.len()).unwrap()).inv();
-    discrete_fourier_transform(tmp_coeffs, points, points.len()).unwrap();
+    backend.fft(tmp_coeffs, points, points.len())?;
     discrete_fourier_transform_inv_finish(tmp_coeffs, points.len(), size_inv);
-    poly_eval(&tmp_coeffs[..points.len()], eval_at)
+    Ok(E::eval_poly(&tmp_coeffs[..points.len()], eval_at))
+}
+
+/// Interpolates each of a batch of polynomials from the same number of points and evaluates them
+/// all at `eval_at`. The transforms are passed to the backend together.
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+pub fn poly_interpret_eval_batch<F, E, B, const N: usize>(
+    points: [&[F]; N],
+    eval_at: E,
+    mut tmp_coeffs: [&mut [F]; N],
+    backend: &B,
+) -> Result<[E; N], FftError>
+where
+    F: FftFriendlyFieldElement,
+    E: FieldOver<F>,
+    B: FftBackend<F> + ?Sized,
+{
+    let size = points.first().map_or(0, |p| p.len());
+    if points.iter().any(|p| p.len() != size) {
+        return Err(FftError::SizeInvalid);
+    }
+    let size_inv = F::from(F::Integer::try_from(size).unwrap()).inv();
+    backend.fft_batch(&mut tmp_coeffs, &points, size)?;
+    let mut out = [eval_at; N];
+    for (out, coeffs) in out.iter_mut().zip(tmp_coeffs) {
+        discrete_fourier_transform_inv_finish(coeffs, size, size_inv);
+        *out = E::eval_poly(&coeffs[..size], eval_at);
+    }
+    Ok(out)
 }
 
 // Returns a polynomial that evaluates to `0` if the input is in range `[start, end)`. Otherwise,
@@ -6,13 +6,16 @@
 //! NOTE: The public API for this module is a work in progress.
 
 use crate::field::{FieldElement, FieldElementExt};
-#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
-use crate::vdaf::xof::SeedStreamAes128;
 use crate::vdaf::xof::{Seed, SeedStreamTurboShake128, Xof, XofTurboShake128};
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+use crate::vdaf::{
+    prio2::{SEED_EXPANSION_IV_LEN, SEED_EXPANSION_KEY_LEN},
+    xof::SeedStreamAes128,
+};
 use rand_core::RngCore;
 
-use std::marker::PhantomData;
-use std::ops::ControlFlow;
+use core::marker::PhantomData;
+use core::ops::ControlFlow;
 
 const BUFFER_SIZE_IN_ELEMENTS: usize = 32;
 
@@ -39,9 +42,11 @@ pub(crate) struct Prng<F, S> {
 impl<F: FieldElement> Prng<F, SeedStreamAes128> {
     /// Create a [`Prng`] from a seed for Prio 2. The first 16 bytes of the seed and the last 16
     /// bytes of the seed are used, respectively, for the key and initialization vector for AES128
-    /// in CTR mode.
+    /// in CTR mode. See [`SEED_EXPANSION_VERSION`](crate::vdaf::prio2::SEED_EXPANSION_VERSION).
     pub(crate) fn from_prio2_seed(seed: &[u8; 32]) -> Self {
-        let seed_stream = SeedStreamAes128::new(&seed[..16], &seed[16..]);
+        let (key, iv) = seed.split_at(SEED_EXPANSION_KEY_LEN);
+        debug_assert_eq!(iv.len(), SEED_EXPANSION_IV_LEN);
+        let seed_stream = SeedStreamAes128::new(key, iv);
         Self::from_seed_stream(seed_stream)
     }
 }
@@ -214,6 +219,37 @@ mod tests {
         );
     }
 
+    /// Shares expanded from a Prio2 seed in a field other than `FieldPrio2` are rejection sampled
+    /// against that field's modulus.
+    #[test]
+    #[cfg(feature = "experimental")]
+    fn extract_share_from_seed_other_fields() {
+        use crate::field::FieldElementWithInteger;
+
+        let seed = [7; 32];
+        let mut stream = SeedStreamAes128::new(&seed[..16], &seed[16..]);
+        let mut bytes = vec![0; 8 * 1000];
+        stream.fill_bytes(&mut bytes);
+        let want: Vec<Field64> = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .filter(|x| *x < Field64::modulus())
+            .map(Field64::from)
+            .collect();
+        assert_eq!(extract_share_from_seed::<Field64>(want.len(), &seed), want);
+
+        let mut stream = SeedStreamAes128::new(&seed[..16], &seed[16..]);
+        let mut bytes = vec![0; 16 * 1000];
+        stream.fill_bytes(&mut bytes);
+        let want: Vec<Field128> = bytes
+            .chunks_exact(16)
+            .map(|chunk| u128::from_le_bytes(chunk.try_into().unwrap()))
+            .filter(|x| *x < Field128::modulus())
+            .map(Field128::from)
+            .collect();
+        assert_eq!(extract_share_from_seed::<Field128>(want.len(), &seed), want);
+    }
+
     #[cfg(feature = "experimental")]
     fn extract_share_from_seed<F: FieldElement>(length: usize, seed: &[u8]) -> Vec<F> {
         assert_eq!(seed.len(), 32);
@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Memory protection for long-lived secrets.
+//!
+//! An Aggregator typically holds its VDAF verification key for as long as it runs, and a helper its
+//! `HelperStateKey`, which `HelperStateKey::new_locked` derives into locked memory. [`LockedBytes`]
+//! keeps such a secret in memory that is locked into RAM with `mlock` (or `VirtualLock` on
+//! Windows), so that it is never written to swap, and that is zeroed before it is freed. Each
+//! secret gets its own mapping, so that unlocking one secret never unlocks the page of another, and
+//! the mapping has an inaccessible guard page on either side of the secret. The secret ends where
+//! the trailing guard page begins, so a read or write past its end faults rather than reaching
+//! other memory.
+//!
+//! On platforms other than Unix and Windows, memory cannot be locked and [`LockedBytes`] cannot be
+//! constructed.
+
+use std::{
+    fmt::{self, Debug},
+    io,
+    ptr::{self, NonNull},
+};
+
+/// Errors returned by this module.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SecureMemoryError {
+    /// The allocation failed.
+    #[error("allocation failed")]
+    Alloc,
+
+    /// The operating system refused to lock the memory, e.g. because the process exceeded its
+    /// limit on locked memory.
+    #[error("failed to lock memory: {0}")]
+    Lock(#[source] io::Error),
+
+    /// Failure when calling getrandom().
+    #[error("getrandom: {0}")]
+    GetRandom(#[from] getrandom::Error),
+}
+
+/// A secret of `N` bytes held in locked memory, between two guard pages. The memory is zeroed,
+/// unlocked and unmapped when this is dropped.
+pub struct LockedBytes<const N: usize> {
+    /// The start of the mapping, which begins with a guard page.
+    base: NonNull<u8>,
+    /// The length of the mapping, including both guard pages.
+    len: usize,
+    /// The secret, which ends at the trailing guard page.
+    ptr: NonNull<[u8; N]>,
+}
+
+// Safety: `LockedBytes` uniquely owns its mapping, like a `Box<[u8; N]>`.
+unsafe impl<const N: usize> Send for LockedBytes<N> {}
+unsafe impl<const N: usize> Sync for LockedBytes<N> {}
+
+impl<const N: usize> LockedBytes<N> {
+    /// Allocates and locks `N` zero bytes.
+    pub fn new() -> Result<Self, SecureMemoryError> {
+        let page = sys::page_size();
+        let data_len = ((N + page - 1) / page).max(1) * page;
+        let len = data_len
+            .checked_add(2 * page)
+            .ok_or(SecureMemoryError::Alloc)?;
+
+        // Safety: `len` is a nonzero multiple of the page size. The mapping is inaccessible until
+        // the data pages are made readable and writable, and it is zeroed by the operating system.
+        let base = unsafe { sys::map(len) }.map_err(|_| SecureMemoryError::Alloc)?;
+        // Safety: the data pages lie within the mapping, after the leading guard page.
+        let data = unsafe { base.as_ptr().add(page) };
+        let unmap = |e| {
+            // Safety: `base` was mapped with length `len` and is not used again.
+            unsafe { sys::unmap(base.as_ptr(), len) };
+            e
+        };
+        // Safety: as above.
+        unsafe { sys::protect_read_write(data, data_len) }
+            .map_err(|_| unmap(SecureMemoryError::Alloc))?;
+        // Safety: as above.
+        unsafe { sys::lock(data, data_len) }.map_err(|e| unmap(SecureMemoryError::Lock(e)))?;
+
+        // Safety: the secret lies within the data pages, which are readable and writable, and
+        // arrays of bytes have no alignment requirement.
+        let ptr = unsafe { NonNull::new_unchecked(data.add(data_len - N)) };
+        Ok(Self {
+            base,
+            len,
+            ptr: ptr.cast(),
+        })
+    }
+
+    /// Generates a random secret directly in locked memory.
+    pub fn random() -> Result<Self, SecureMemoryError> {
+        let mut secret = Self::new()?;
+        getrandom::getrandom(secret.as_mut_bytes())?;
+        Ok(secret)
+    }
+
+    /// Moves `bytes` into locked memory, zeroing the original.
+    pub fn from_bytes(bytes: &mut [u8; N]) -> Result<Self, SecureMemoryError> {
+        let mut secret = Self::new()?;
+        secret.as_mut_bytes().copy_from_slice(bytes);
+        zero(bytes);
+        Ok(secret)
+    }
+
+    /// Returns the secret.
+    pub fn as_bytes(&self) -> &[u8; N] {
+        // Safety: `ptr` is valid, aligned and initialized for the lifetime of `self`.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Returns the secret, mutably.
+    pub fn as_mut_bytes(&mut self) -> &mut [u8; N] {
+        // Safety: `ptr` is valid, aligned and initialized for the lifetime of `self`, and `self`
+        // is borrowed mutably.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<const N: usize> Drop for LockedBytes<N> {
+    fn drop(&mut self) {
+        zero(self.as_mut_bytes());
+        let page = sys::page_size();
+        // Safety: the data pages were locked in `new()`, and the mapping is not used again. A
+        // failure to unlock is not actionable; the memory is unmapped either way.
+        unsafe {
+            let _ = sys::unlock(self.base.as_ptr().add(page), self.len - 2 * page);
+            sys::unmap(self.base.as_ptr(), self.len);
+        }
+    }
+}
+
+impl<const N: usize> Debug for LockedBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockedBytes").finish_non_exhaustive()
+    }
+}
+
+/// Zeroes `bytes` with volatile writes, so that the compiler cannot elide them.
+fn zero(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        // Safety: `byte` is a valid, aligned reference.
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{io, ptr::NonNull};
+
+    pub(super) fn page_size() -> usize {
+        // Safety: sysconf() has no preconditions.
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        usize::try_from(size).unwrap_or(1 << 12)
+    }
+
+    pub(super) unsafe fn map(len: usize) -> io::Result<NonNull<u8>> {
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANON,
+            -1,
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        NonNull::new(ptr.cast()).ok_or_else(io::Error::last_os_error)
+    }
+
+    pub(super) unsafe fn protect_read_write(ptr: *mut u8, len: usize) -> io::Result<()> {
+        if libc::mprotect(ptr.cast(), len, libc::PROT_READ | libc::PROT_WRITE) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) unsafe fn unmap(ptr: *mut u8, len: usize) {
+        libc::munmap(ptr.cast(), len);
+    }
+
+    pub(super) unsafe fn lock(ptr: *mut u8, len: usize) -> io::Result<()> {
+        if libc::mlock(ptr.cast(), len) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) unsafe fn unlock(ptr: *mut u8, len: usize) -> io::Result<()> {
+        if libc::munlock(ptr.cast(), len) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{ffi::c_void, io, ptr::NonNull};
+
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const PAGE_NOACCESS: u32 = 0x01;
+    const PAGE_READWRITE: u32 = 0x04;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn VirtualAlloc(addr: *mut c_void, size: usize, ty: u32, protect: u32) -> *mut c_void;
+        fn VirtualProtect(addr: *mut c_void, size: usize, protect: u32, old: *mut u32) -> i32;
+        fn VirtualFree(addr: *mut c_void, size: usize, ty: u32) -> i32;
+        fn VirtualLock(addr: *mut c_void, size: usize) -> i32;
+        fn VirtualUnlock(addr: *mut c_void, size: usize) -> i32;
+    }
+
+    /// The page size of every Windows platform.
+    pub(super) fn page_size() -> usize {
+        1 << 12
+    }
+
+    pub(super) unsafe fn map(len: usize) -> io::Result<NonNull<u8>> {
+        let ptr = VirtualAlloc(
+            std::ptr::null_mut(),
+            len,
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_NOACCESS,
+        );
+        NonNull::new(ptr.cast()).ok_or_else(io::Error::last_os_error)
+    }
+
+    pub(super) unsafe fn protect_read_write(ptr: *mut u8, len: usize) -> io::Result<()> {
+        let mut old = 0;
+        if VirtualProtect(ptr.cast(), len, PAGE_READWRITE, &mut old) != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) unsafe fn unmap(ptr: *mut u8, _len: usize) {
+        VirtualFree(ptr.cast(), 0, MEM_RELEASE);
+    }
+
+    pub(super) unsafe fn lock(ptr: *mut u8, len: usize) -> io::Result<()> {
+        if VirtualLock(ptr.cast(), len) != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) unsafe fn unlock(ptr: *mut u8, len: usize) -> io::Result<()> {
+        if VirtualUnlock(ptr.cast(), len) != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::{io, ptr::NonNull};
+
+    pub(super) fn page_size() -> usize {
+        1 << 12
+    }
+
+    pub(super) unsafe fn map(_len: usize) -> io::Result<NonNull<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory locking is not supported on this platform",
+        ))
+    }
+
+    pub(super) unsafe fn protect_read_write(_ptr: *mut u8, _len: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub(super) unsafe fn unmap(_ptr: *mut u8, _len: usize) {}
+
+    pub(super) unsafe fn lock(_ptr: *mut u8, _len: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub(super) unsafe fn unlock(_ptr: *mut u8, _len: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_bytes() {
+        let zeros = LockedBytes::<32>::new().unwrap();
+        assert_eq!(zeros.as_bytes(), &[0; 32]);
+
+        let mut key = [7; 32];
+        let locked = LockedBytes::from_bytes(&mut key).unwrap();
+        assert_eq!(key, [0; 32]);
+        assert_eq!(locked.as_bytes(), &[7; 32]);
+        assert_eq!(format!("{locked:?}"), "LockedBytes { .. }");
+
+        let a = LockedBytes::<16>::random().unwrap();
+        let b = LockedBytes::<16>::random().unwrap();
+        assert_ne!(a.as_bytes(), b.as_bytes());
+        assert_eq!(
+            (a.as_bytes().as_ptr() as usize + 16) % sys::page_size(),
+            0,
+            "secret does not end at the guard page"
+        );
+
+        // Secrets larger than a page, and empty ones, are supported.
+        const BIG: usize = 1 << 16;
+        let mut big = LockedBytes::<{ BIG + 1 }>::new().unwrap();
+        big.as_mut_bytes()[0] = 1;
+        big.as_mut_bytes()[BIG] = 2;
+        assert_eq!(big.as_bytes()[..2], [1, 0]);
+        assert_eq!(big.as_bytes()[BIG], 2);
+        LockedBytes::<0>::new().unwrap();
+    }
+}
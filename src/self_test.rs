@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Self-test of the VDAFs, to be run at startup.
+
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+use crate::vdaf::prio2::{self, Prio2};
+use crate::{
+    codec::{Encode, ParameterizedDecode},
+    vdaf::{
+        prio3::Prio3, Aggregatable, Aggregator, Client, Collector, PrepareTransition, VdafError,
+    },
+};
+use rand::prelude::*;
+use sha3::{Digest, Sha3_256};
+use std::fmt::Debug;
+
+/// The number of random measurements aggregated by each check.
+const NUM_MEASUREMENTS: usize = 10;
+
+/// The SHA3-256 digest of the messages computed by [`known_answer`], as produced by the reference
+/// platform.
+const KNOWN_ANSWER_DIGEST: [u8; 32] = [
+    0x98, 0x1d, 0xe1, 0x22, 0x29, 0x27, 0xd2, 0x35, 0xa2, 0x3f, 0x71, 0xed, 0x82, 0xb4, 0x43, 0xd2,
+    0x32, 0x43, 0xfa, 0xdc, 0xd7, 0x59, 0x19, 0xa7, 0x7d, 0x2c, 0x9a, 0x7c, 0x9c, 0x1a, 0xe4, 0x4a,
+];
+
+/// Runs each VDAF end-to-end on random measurements and checks that the aggregate result matches
+/// the sum of the measurements.
+///
+/// Each check shards the measurements, prepares the input shares, merges the output shares into
+/// aggregate shares, and unshards them, encoding and decoding every message along the way. It also
+/// checks that preparation fails if the Aggregators use different verification keys, so that a
+/// verifier that accepts everything is caught. With the `crypto-dependencies` and `experimental`
+/// features, Prio2 and its [seed expansion](crate::vdaf::prio2::seed_expansion_self_test) are
+/// checked too.
+///
+/// Before the random checks, a measurement is sharded and prepared with fixed randomness, and the
+/// messages are compared with those of the reference platform. Field arithmetic, the FFT, and the
+/// XOF are implemented in portable Rust without floating point, so every platform should produce
+/// the same messages; a mismatch means that this one cannot be relied upon to reach the same
+/// verification decisions as the others.
+///
+/// An application can run this at startup to demonstrate that the cryptography works on the
+/// hardware it is running on. It takes a few milliseconds. An error names the VDAF whose check
+/// failed.
+pub fn self_test() -> Result<(), VdafError> {
+    known_answer()?;
+    let mut rng = thread_rng();
+
+    let measurements: Vec<bool> = (0..NUM_MEASUREMENTS).map(|_| rng.gen()).collect();
+    let expected = measurements.iter().map(|m| u64::from(*m)).sum();
+    check("Prio3Count", &Prio3::new_count(2)?, &measurements, expected)?;
+
+    let measurements: Vec<u128> = (0..NUM_MEASUREMENTS)
+        .map(|_| rng.gen_range(0..256))
+        .collect();
+    let expected = measurements.iter().sum();
+    check("Prio3Sum", &Prio3::new_sum(3, 8)?, &measurements, expected)?;
+
+    let measurements: Vec<Vec<u128>> = (0..NUM_MEASUREMENTS)
+        .map(|_| (0..5).map(|_| rng.gen_range(0..4)).collect())
+        .collect();
+    let expected = (0..5)
+        .map(|i| measurements.iter().map(|m| m[i]).sum())
+        .collect();
+    check(
+        "Prio3SumVec",
+        &Prio3::new_sum_vec(2, 2, 5, 3)?,
+        &measurements,
+        expected,
+    )?;
+
+    let measurements: Vec<usize> = (0..NUM_MEASUREMENTS).map(|_| rng.gen_range(0..6)).collect();
+    let mut expected = vec![0; 6];
+    for m in &measurements {
+        expected[*m] += 1;
+    }
+    check(
+        "Prio3Histogram",
+        &Prio3::new_histogram(2, 6, 2)?,
+        &measurements,
+        expected,
+    )?;
+
+    #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+    {
+        prio2::seed_expansion_self_test()?;
+        let measurements: Vec<Vec<u32>> = (0..NUM_MEASUREMENTS)
+            .map(|_| (0..7).map(|_| rng.gen_range(0..2)).collect())
+            .collect();
+        let expected = (0..7)
+            .map(|i| measurements.iter().map(|m| m[i]).sum())
+            .collect();
+        check("Prio2", &Prio2::new(7)?, &measurements, expected)?;
+    }
+
+    Ok(())
+}
+
+/// Aggregates `measurements` with `vdaf` and compares the result with `expected`.
+fn check<V, const SEED_SIZE: usize>(
+    name: &str,
+    vdaf: &V,
+    measurements: &[V::Measurement],
+    expected: V::AggregateResult,
+) -> Result<(), VdafError>
+where
+    V: Client<16> + Aggregator<SEED_SIZE, 16, AggregationParam = ()> + Collector,
+    V::AggregateResult: PartialEq + Debug,
+{
+    let fail = |what: &str| VdafError::Uncategorized(format!("{name} self-test failed: {what}"));
+    let mut rng = thread_rng();
+    let mut verify_key = [0; SEED_SIZE];
+    rng.fill(&mut verify_key[..]);
+
+    let mut agg_shares: Vec<Option<V::AggregateShare>> = vec![None; vdaf.num_aggregators()];
+    for measurement in measurements {
+        let nonce = rng.gen();
+        let (public_share, input_shares) = vdaf.shard(measurement, &nonce)?;
+        let public_share =
+            V::PublicShare::get_decoded_with_param(vdaf, &public_share.get_encoded()?)?;
+        let input_shares = input_shares
+            .iter()
+            .enumerate()
+            .map(|(agg_id, input_share)| {
+                V::InputShare::get_decoded_with_param(&(vdaf, agg_id), &input_share.get_encoded()?)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let out_shares = prepare(vdaf, &[verify_key], &nonce, &public_share, &input_shares)?;
+        for (out_share, agg_share) in out_shares.into_iter().zip(agg_shares.iter_mut()) {
+            let out_share = V::AggregateShare::from(out_share);
+            match agg_share {
+                Some(agg_share) => agg_share.merge(&out_share)?,
+                None => *agg_share = Some(out_share),
+            }
+        }
+
+        // An Aggregator with a different verification key computes its verifier share at a
+        // different point, so the verifier shares do not agree.
+        let mut other_key = verify_key;
+        other_key[0] ^= 1;
+        if prepare(
+            vdaf,
+            &[verify_key, other_key],
+            &nonce,
+            &public_share,
+            &input_shares,
+        )
+        .is_ok()
+        {
+            return Err(fail("verification succeeded with mismatched keys"));
+        }
+    }
+
+    let agg_shares = agg_shares
+        .into_iter()
+        .map(|agg_share| {
+            // Unwrap safety: there is at least one measurement.
+            let encoded = agg_share.unwrap().get_encoded()?;
+            V::AggregateShare::get_decoded_with_param(&(vdaf, &()), &encoded)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let result = vdaf.unshard(&(), agg_shares, measurements.len())?;
+    if result != expected {
+        return Err(fail(&format!("got {result:?}, expected {expected:?}")));
+    }
+    Ok(())
+}
+
+/// Shards a fixed Prio3SumVec measurement with fixed randomness, prepares it, and compares the
+/// digest of every message with [`KNOWN_ANSWER_DIGEST`].
+fn known_answer() -> Result<(), VdafError> {
+    let vdaf = Prio3::new_sum_vec(2, 2, 5, 3)?;
+    let nonce = [1; 16];
+    let verify_key = [2; 16];
+    let random: Vec<u8> = (0..vdaf.random_size()).map(|i| i as u8).collect();
+    let (public_share, input_shares) =
+        vdaf.shard_with_random(&vec![0, 1, 2, 3, 0], &nonce, &random)?;
+
+    let mut hasher = Sha3_256::new();
+    hasher.update(public_share.get_encoded()?);
+    let mut states = Vec::new();
+    let mut prep_shares = Vec::new();
+    for (agg_id, input_share) in input_shares.iter().enumerate() {
+        hasher.update(input_share.get_encoded()?);
+        let (state, prep_share) =
+            vdaf.prepare_init(&verify_key, agg_id, &(), &nonce, &public_share, input_share)?;
+        hasher.update(prep_share.get_encoded()?);
+        states.push(state);
+        prep_shares.push(prep_share);
+    }
+    let prep_msg = vdaf.prepare_shares_to_prepare_message(&(), prep_shares)?;
+    hasher.update(prep_msg.get_encoded()?);
+    for state in states {
+        if let PrepareTransition::Finish(out_share) = vdaf.prepare_next(state, prep_msg.clone())? {
+            hasher.update(out_share.get_encoded()?);
+        }
+    }
+
+    let digest: [u8; 32] = hasher.finalize().into();
+    if digest != KNOWN_ANSWER_DIGEST {
+        return Err(VdafError::Uncategorized(format!(
+            "self-test failed for known answer: got digest {digest:02x?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Prepares one report, giving Aggregator `i` the verification key `verify_keys[i]`, or the last
+/// key if there are fewer keys than Aggregators.
+fn prepare<V, const SEED_SIZE: usize>(
+    vdaf: &V,
+    verify_keys: &[[u8; SEED_SIZE]],
+    nonce: &[u8; 16],
+    public_share: &V::PublicShare,
+    input_shares: &[V::InputShare],
+) -> Result<Vec<V::OutputShare>, VdafError>
+where
+    V: Aggregator<SEED_SIZE, 16, AggregationParam = ()>,
+{
+    let mut states = Vec::new();
+    let mut prep_shares = Vec::new();
+    for (agg_id, input_share) in input_shares.iter().enumerate() {
+        let verify_key = &verify_keys[agg_id.min(verify_keys.len() - 1)];
+        let (state, prep_share) =
+            vdaf.prepare_init(verify_key, agg_id, &(), nonce, public_share, input_share)?;
+        prep_shares.push(V::PrepareShare::get_decoded_with_param(
+            &state,
+            &prep_share.get_encoded()?,
+        )?);
+        states.push(state);
+    }
+    let prep_msg = vdaf.prepare_shares_to_prepare_message(&(), prep_shares)?;
+
+    let mut out_shares = Vec::new();
+    for state in states {
+        let prep_msg = V::PrepareMessage::get_decoded_with_param(&state, &prep_msg.get_encoded()?)?;
+        match vdaf.prepare_next(state, prep_msg)? {
+            PrepareTransition::Finish(out_share) => out_shares.push(out_share),
+            PrepareTransition::Continue(..) => {
+                return Err(VdafError::Uncategorized(
+                    "self-test only supports one-round VDAFs".into(),
+                ))
+            }
+        }
+    }
+    Ok(out_shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_test_passes() {
+        self_test().unwrap();
+    }
+}
@@ -0,0 +1,397 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! In-memory simulation of a VDAF deployment, with fault injection.
+//!
+//! [`Simulation`] plays the Client, every Aggregator, and the Collector in a single process. Each
+//! message is encoded and decoded as it would be on the wire, and [`Fault`]s may be injected into
+//! the encoded messages of individual reports. This allows applications to test how their code
+//! handles malformed and malicious reports without standing up any network plumbing.
+
+use crate::{
+    codec::{CodecError, Encode, ParameterizedDecode},
+    vdaf::{Aggregatable, Aggregator, Client, Collector, PrepareTransition, VdafError},
+};
+use rand::prelude::*;
+
+/// A modification applied to one of the encoded messages of a report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fault {
+    /// Flip a bit of the public share. The bit index is taken modulo the bit length of the
+    /// message.
+    FlipPublicShareBit {
+        /// Index of the bit to flip.
+        bit: usize,
+    },
+
+    /// Flip a bit of the input share sent to an Aggregator. Flipping a bit of the leader's share of
+    /// the proof simulates a Client submitting a malicious proof.
+    FlipInputShareBit {
+        /// The Aggregator whose share is modified.
+        aggregator: usize,
+        /// Index of the bit to flip.
+        bit: usize,
+    },
+
+    /// Truncate the input share sent to an Aggregator to the given number of bytes.
+    TruncateInputShare {
+        /// The Aggregator whose share is modified.
+        aggregator: usize,
+        /// The length to truncate to.
+        len: usize,
+    },
+
+    /// Flip a bit of the prepare share broadcast by an Aggregator in the given round.
+    FlipPrepareShareBit {
+        /// The Aggregator whose prepare share is modified.
+        aggregator: usize,
+        /// The round of preparation, starting from 0.
+        round: usize,
+        /// Index of the bit to flip.
+        bit: usize,
+    },
+}
+
+/// The reason a report was rejected during a simulation.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SimError {
+    /// The Client failed to shard the measurement.
+    #[error("sharding failed: {0}")]
+    Shard(#[source] VdafError),
+
+    /// A message could not be encoded.
+    #[error("failed to encode {message}: {error}")]
+    Encode {
+        /// The message that failed to encode.
+        message: &'static str,
+        /// The underlying error.
+        #[source]
+        error: CodecError,
+    },
+
+    /// A message could not be decoded.
+    #[error("failed to decode {message}: {error}")]
+    Decode {
+        /// The message that failed to decode.
+        message: &'static str,
+        /// The underlying error.
+        #[source]
+        error: CodecError,
+    },
+
+    /// An Aggregator failed to prepare its share of the report.
+    #[error("aggregator {aggregator} failed to prepare: {error}")]
+    Prepare {
+        /// The Aggregator that failed.
+        aggregator: usize,
+        /// The underlying error.
+        #[source]
+        error: VdafError,
+    },
+
+    /// The prepare shares could not be combined into a prepare message.
+    #[error("failed to combine prepare shares in round {round}: {error}")]
+    PrepareMessage {
+        /// The round of preparation, starting from 0.
+        round: usize,
+        /// The underlying error.
+        #[source]
+        error: VdafError,
+    },
+}
+
+/// The results of simulating a batch of reports.
+#[derive(Debug)]
+pub struct SimOutcome<V: Collector> {
+    /// For each report, in order, `Ok(())` if it was aggregated or the reason it was rejected.
+    pub reports: Vec<Result<(), SimError>>,
+
+    /// The aggregate result over the reports that were accepted, or `None` if there were none.
+    pub aggregate_result: Option<V::AggregateResult>,
+}
+
+/// Runs a VDAF end-to-end in memory. See the [module documentation](self) for details.
+#[derive(Clone, Debug)]
+pub struct Simulation<V: Collector, const SEED_SIZE: usize> {
+    vdaf: V,
+    verify_key: [u8; SEED_SIZE],
+    agg_param: V::AggregationParam,
+}
+
+impl<V, const SEED_SIZE: usize> Simulation<V, SEED_SIZE>
+where
+    V: Client<16> + Aggregator<SEED_SIZE, 16> + Collector,
+{
+    /// Creates a simulation of `vdaf` with the given aggregation parameter and a random
+    /// verification key.
+    pub fn new(vdaf: V, agg_param: V::AggregationParam) -> Self {
+        let mut verify_key = [0; SEED_SIZE];
+        thread_rng().fill(&mut verify_key[..]);
+        Self {
+            vdaf,
+            verify_key,
+            agg_param,
+        }
+    }
+
+    /// Shards `measurement`, applies `faults` to the encoded messages, and runs preparation with
+    /// every Aggregator. Returns each Aggregator's output share if the report is accepted.
+    pub fn run_report(
+        &self,
+        measurement: &V::Measurement,
+        faults: &[Fault],
+    ) -> Result<Vec<V::OutputShare>, SimError> {
+        let vdaf = &self.vdaf;
+        let nonce: [u8; 16] = random();
+        let (public_share, input_shares) =
+            vdaf.shard(measurement, &nonce).map_err(SimError::Shard)?;
+
+        let mut encoded_public_share = encode(&public_share, "public share")?;
+        for fault in faults {
+            if let Fault::FlipPublicShareBit { bit } = fault {
+                flip_bit(&mut encoded_public_share, *bit);
+            }
+        }
+        let public_share = V::PublicShare::get_decoded_with_param(vdaf, &encoded_public_share)
+            .map_err(|error| SimError::Decode {
+                message: "public share",
+                error,
+            })?;
+
+        let mut states = Vec::with_capacity(input_shares.len());
+        let mut outbound = Vec::with_capacity(input_shares.len());
+        for (agg_id, input_share) in input_shares.iter().enumerate() {
+            let mut encoded = encode(input_share, "input share")?;
+            for fault in faults {
+                match *fault {
+                    Fault::FlipInputShareBit { aggregator, bit } if aggregator == agg_id => {
+                        flip_bit(&mut encoded, bit)
+                    }
+                    Fault::TruncateInputShare { aggregator, len } if aggregator == agg_id => {
+                        encoded.truncate(len)
+                    }
+                    _ => (),
+                }
+            }
+            let input_share = V::InputShare::get_decoded_with_param(&(vdaf, agg_id), &encoded)
+                .map_err(|error| SimError::Decode {
+                    message: "input share",
+                    error,
+                })?;
+
+            let (state, prep_share) = vdaf
+                .prepare_init(
+                    &self.verify_key,
+                    agg_id,
+                    &self.agg_param,
+                    &nonce,
+                    &public_share,
+                    &input_share,
+                )
+                .map_err(|error| SimError::Prepare {
+                    aggregator: agg_id,
+                    error,
+                })?;
+            states.push(state);
+            outbound.push(prep_share);
+        }
+
+        let mut round = 0;
+        loop {
+            let mut inbound = Vec::with_capacity(outbound.len());
+            for (agg_id, prep_share) in outbound.iter().enumerate() {
+                let mut encoded = encode(prep_share, "prepare share")?;
+                for fault in faults {
+                    if let Fault::FlipPrepareShareBit {
+                        aggregator,
+                        round: fault_round,
+                        bit,
+                    } = *fault
+                    {
+                        if aggregator == agg_id && fault_round == round {
+                            flip_bit(&mut encoded, bit);
+                        }
+                    }
+                }
+                inbound.push(
+                    V::PrepareShare::get_decoded_with_param(&states[0], &encoded).map_err(
+                        |error| SimError::Decode {
+                            message: "prepare share",
+                            error,
+                        },
+                    )?,
+                );
+            }
+
+            let prep_msg = vdaf
+                .prepare_shares_to_prepare_message(&self.agg_param, inbound)
+                .map_err(|error| SimError::PrepareMessage { round, error })?;
+
+            let mut out_shares = Vec::new();
+            outbound.clear();
+            for (agg_id, state) in std::mem::take(&mut states).into_iter().enumerate() {
+                match vdaf
+                    .prepare_next(state, prep_msg.clone())
+                    .map_err(|error| SimError::Prepare {
+                        aggregator: agg_id,
+                        error,
+                    })? {
+                    PrepareTransition::Continue(state, prep_share) => {
+                        states.push(state);
+                        outbound.push(prep_share);
+                    }
+                    PrepareTransition::Finish(out_share) => out_shares.push(out_share),
+                }
+            }
+
+            if states.is_empty() {
+                return Ok(out_shares);
+            }
+            round += 1;
+        }
+    }
+
+    /// Runs each report with its faults, aggregates the reports that are accepted, and unshards
+    /// the result.
+    pub fn run<I>(&self, reports: I) -> Result<SimOutcome<V>, VdafError>
+    where
+        I: IntoIterator<Item = (V::Measurement, Vec<Fault>)>,
+    {
+        let mut agg_shares: Option<Vec<V::AggregateShare>> = None;
+        let mut num_measurements = 0;
+        let mut outcomes = Vec::new();
+        for (measurement, faults) in reports {
+            let out_shares = match self.run_report(&measurement, &faults) {
+                Ok(out_shares) => out_shares,
+                Err(e) => {
+                    outcomes.push(Err(e));
+                    continue;
+                }
+            };
+            num_measurements += 1;
+            outcomes.push(Ok(()));
+            match agg_shares {
+                Some(ref mut agg_shares) => {
+                    for (agg_share, out_share) in agg_shares.iter_mut().zip(out_shares.iter()) {
+                        agg_share.accumulate(out_share)?;
+                    }
+                }
+                None => {
+                    agg_shares = Some(out_shares.into_iter().map(Into::into).collect());
+                }
+            }
+        }
+
+        let aggregate_result = agg_shares
+            .map(|agg_shares| {
+                self.vdaf
+                    .unshard(&self.agg_param, agg_shares, num_measurements)
+            })
+            .transpose()?;
+        Ok(SimOutcome {
+            reports: outcomes,
+            aggregate_result,
+        })
+    }
+}
+
+fn encode<T: Encode>(message: &T, name: &'static str) -> Result<Vec<u8>, SimError> {
+    message.get_encoded().map_err(|error| SimError::Encode {
+        message: name,
+        error,
+    })
+}
+
+fn flip_bit(bytes: &mut [u8], bit: usize) {
+    if !bytes.is_empty() {
+        let bit = bit % (bytes.len() * 8);
+        bytes[bit / 8] ^= 1 << (bit % 8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::prio3::{Prio3Count, Prio3Histogram};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn honest_reports() {
+        let sim = Simulation::new(Prio3Histogram::new_histogram(3, 4, 2).unwrap(), ());
+        let outcome = sim.run([0, 1, 1, 3].map(|m| (m, Vec::new()))).unwrap();
+        assert!(outcome.reports.iter().all(Result::is_ok));
+        assert_eq!(outcome.aggregate_result, Some(vec![1, 2, 0, 1]));
+    }
+
+    #[test]
+    fn faulty_reports() {
+        let sim = Simulation::new(Prio3Count::new_count(2).unwrap(), ());
+        let outcome = sim
+            .run([
+                (true, Vec::new()),
+                (
+                    true,
+                    vec![Fault::TruncateInputShare {
+                        aggregator: 1,
+                        len: 3,
+                    }],
+                ),
+                (
+                    true,
+                    vec![Fault::FlipInputShareBit {
+                        aggregator: 0,
+                        bit: 0,
+                    }],
+                ),
+                (
+                    true,
+                    vec![Fault::FlipInputShareBit {
+                        aggregator: 1,
+                        bit: 100,
+                    }],
+                ),
+                (
+                    false,
+                    vec![Fault::FlipPrepareShareBit {
+                        aggregator: 0,
+                        round: 0,
+                        bit: 3,
+                    }],
+                ),
+                (false, vec![Fault::FlipPublicShareBit { bit: 0 }]),
+            ])
+            .unwrap();
+
+        assert_matches!(outcome.reports[0], Ok(()));
+        assert_matches!(
+            outcome.reports[1],
+            Err(SimError::Decode {
+                message: "input share",
+                ..
+            })
+        );
+        assert_matches!(outcome.reports[2], Err(SimError::PrepareMessage { .. }));
+        assert_matches!(outcome.reports[3], Err(SimError::PrepareMessage { .. }));
+        assert_matches!(outcome.reports[4], Err(SimError::PrepareMessage { .. }));
+        // Prio3Count has no joint randomness, so its public share is empty.
+        assert_matches!(outcome.reports[5], Ok(()));
+        assert_eq!(outcome.aggregate_result, Some(1));
+    }
+
+    #[test]
+    fn all_rejected() {
+        let sim = Simulation::new(Prio3Count::new_count(2).unwrap(), ());
+        let outcome = sim
+            .run([(
+                true,
+                vec![Fault::TruncateInputShare {
+                    aggregator: 0,
+                    len: 0,
+                }],
+            )])
+            .unwrap();
+        assert_eq!(outcome.reports.len(), 1);
+        assert_eq!(outcome.aggregate_result, None);
+    }
+}
@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deterministic generation of batches of reports, for load testing Aggregators.
+//!
+//! A [`BatchGenerator`] shards measurements drawn from a closure and encodes the shares as they
+//! would be uploaded. All of its randomness, including the nonces and the Clients' randomness,
+//! comes from a seeded RNG, so the same seed always yields the same batch and a load test can be
+//! replayed exactly. A configurable fraction of the reports is corrupted by flipping a random bit
+//! of the leader's input share, which an Aggregator should reject. The functions in this module
+//! return measurement closures for common shapes of measurement, of any dimension.
+//!
+//! ```
+//! use prio::{testdata::{bit_vectors, BatchGenerator}, vdaf::prio3::Prio3};
+//!
+//! let vdaf = Prio3::new_sum_vec(2, 1, 100, 10).unwrap();
+//! let mut generator = BatchGenerator::new(vdaf, 1234, bit_vectors(100))
+//!     .with_invalid_ratio(0.25)
+//!     .unwrap();
+//! let batch = generator.batch(8).unwrap();
+//! assert_eq!(batch.len(), 8);
+//! ```
+
+use crate::{
+    codec::Encode,
+    vdaf::{ClientWithRng, VdafError},
+};
+use rand::prelude::*;
+
+/// A generated report: the measurement, the nonce, and the encoded shares.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestReport<M> {
+    /// The measurement that was sharded.
+    pub measurement: M,
+
+    /// The nonce the measurement was sharded with.
+    pub nonce: [u8; 16],
+
+    /// The encoded public share.
+    pub public_share: Vec<u8>,
+
+    /// The encoded input share of each Aggregator, the leader's first.
+    pub input_shares: Vec<Vec<u8>>,
+
+    /// False if the leader's input share was corrupted, so that the report should be rejected.
+    pub valid: bool,
+}
+
+/// Generates reports deterministically from a seed. See the [module documentation](self) for
+/// details.
+pub struct BatchGenerator<V, G> {
+    vdaf: V,
+    measurement: G,
+    invalid_ratio: f64,
+    rng: StdRng,
+}
+
+impl<V, G> BatchGenerator<V, G>
+where
+    V: ClientWithRng<16>,
+    G: FnMut(&mut StdRng) -> V::Measurement,
+{
+    /// Creates a generator of reports for `vdaf` whose measurements are drawn with `measurement`.
+    /// Every report is valid until an [invalid ratio](Self::with_invalid_ratio) is set.
+    pub fn new(vdaf: V, seed: u64, measurement: G) -> Self {
+        Self {
+            vdaf,
+            measurement,
+            invalid_ratio: 0.0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Corrupts each report with probability `ratio`. Returns an error unless `ratio` is between
+    /// 0 and 1.
+    pub fn with_invalid_ratio(mut self, ratio: f64) -> Result<Self, VdafError> {
+        if !(0.0..=1.0).contains(&ratio) {
+            return Err(VdafError::Uncategorized(format!(
+                "invalid ratio must be between 0 and 1, got {ratio}"
+            )));
+        }
+        self.invalid_ratio = ratio;
+        Ok(self)
+    }
+
+    /// Returns the VDAF the reports are generated for.
+    pub fn vdaf(&self) -> &V {
+        &self.vdaf
+    }
+
+    /// Generates the next report.
+    pub fn next_report(&mut self) -> Result<TestReport<V::Measurement>, VdafError> {
+        let measurement = (self.measurement)(&mut self.rng);
+        let nonce = self.rng.gen();
+        let (public_share, input_shares) =
+            self.vdaf
+                .shard_with_rng(&measurement, &nonce, &mut self.rng)?;
+        let mut input_shares = input_shares
+            .iter()
+            .map(Encode::get_encoded)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let valid = !self.rng.gen_bool(self.invalid_ratio);
+        if !valid {
+            let leader = &mut input_shares[0];
+            let bit = self.rng.gen_range(0..leader.len() * 8);
+            leader[bit / 8] ^= 1 << (bit % 8);
+        }
+        Ok(TestReport {
+            measurement,
+            nonce,
+            public_share: public_share.get_encoded()?,
+            input_shares,
+            valid,
+        })
+    }
+
+    /// Generates the next `num_reports` reports.
+    pub fn batch(
+        &mut self,
+        num_reports: usize,
+    ) -> Result<Vec<TestReport<V::Measurement>>, VdafError> {
+        (0..num_reports).map(|_| self.next_report()).collect()
+    }
+}
+
+/// Returns measurements for [`Count`](crate::flp::types::Count): true with probability `p`.
+pub fn counts(p: f64) -> impl FnMut(&mut StdRng) -> bool {
+    move |rng| rng.gen_bool(p)
+}
+
+/// Returns measurements for [`Sum`](crate::flp::types::Sum): uniformly random `bits`-bit integers.
+/// `bits` must be between 1 and 128.
+pub fn sums(bits: usize) -> impl FnMut(&mut StdRng) -> u128 {
+    move |rng| rng.gen::<u128>() >> (128 - bits)
+}
+
+/// Returns measurements for [`SumVec`](crate::flp::types::SumVec) with `bits == 1`: vectors of
+/// `len` uniformly random bits.
+pub fn bit_vectors(len: usize) -> impl FnMut(&mut StdRng) -> Vec<u128> {
+    move |rng| (0..len).map(|_| u128::from(rng.gen::<bool>())).collect()
+}
+
+/// Returns measurements for [`Histogram`](crate::flp::types::Histogram): bucket indices in
+/// `0..len`, where bucket `i` is drawn with probability proportional to `1 / (i + 1)`, so that a
+/// few buckets are popular and most are rare.
+pub fn histogram_buckets(len: usize) -> impl FnMut(&mut StdRng) -> usize {
+    let weights: Vec<f64> = (1..=len).map(|i| 1.0 / i as f64).collect();
+    let total: f64 = weights.iter().sum();
+    move |rng| {
+        let mut x = rng.gen::<f64>() * total;
+        for (i, weight) in weights.iter().enumerate() {
+            if x < *weight {
+                return i;
+            }
+            x -= weight;
+        }
+        len - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::ParameterizedDecode,
+        vdaf::{
+            prio3::{Prio3, Prio3Count, Prio3Histogram},
+            test_utils::run_vdaf_prepare,
+            Aggregator, Client, Collector,
+        },
+    };
+
+    /// Decodes and prepares a generated report, returning true if it is accepted.
+    fn prepare<V>(vdaf: &V, report: &TestReport<V::Measurement>) -> bool
+    where
+        V: Client<16> + Aggregator<16, 16, AggregationParam = ()> + Collector,
+    {
+        let Ok(public_share) = V::PublicShare::get_decoded_with_param(vdaf, &report.public_share)
+        else {
+            return false;
+        };
+        let mut input_shares = Vec::new();
+        for (agg_id, encoded) in report.input_shares.iter().enumerate() {
+            let Ok(input_share) = V::InputShare::get_decoded_with_param(&(vdaf, agg_id), encoded)
+            else {
+                return false;
+            };
+            input_shares.push(input_share);
+        }
+        run_vdaf_prepare(
+            vdaf,
+            &[1; 16],
+            &(),
+            &report.nonce,
+            public_share,
+            input_shares,
+        )
+        .is_ok()
+    }
+
+    #[test]
+    fn deterministic_batches() {
+        let generate = |seed| {
+            BatchGenerator::new(Prio3Count::new_count(2).unwrap(), seed, counts(0.5))
+                .with_invalid_ratio(0.5)
+                .unwrap()
+                .batch(10)
+                .unwrap()
+        };
+        assert_eq!(generate(1), generate(1));
+        assert_ne!(generate(1), generate(2));
+    }
+
+    #[test]
+    fn invalid_reports_are_rejected() {
+        let mut generator = BatchGenerator::new(Prio3Count::new_count(2).unwrap(), 7, counts(0.3))
+            .with_invalid_ratio(0.5)
+            .unwrap();
+        let batch = generator.batch(40).unwrap();
+        assert!(batch.iter().any(|report| report.valid));
+        assert!(batch.iter().any(|report| !report.valid));
+        for report in &batch {
+            assert_eq!(prepare(generator.vdaf(), report), report.valid);
+        }
+
+        for len in [1, 10, 100] {
+            let vdaf = Prio3::new_sum_vec(2, 1, len, 4).unwrap();
+            let mut generator = BatchGenerator::new(vdaf, 7, bit_vectors(len))
+                .with_invalid_ratio(0.5)
+                .unwrap();
+            for report in generator.batch(10).unwrap() {
+                assert_eq!(report.measurement.len(), len);
+                assert_eq!(prepare(generator.vdaf(), &report), report.valid);
+            }
+        }
+
+        let vdaf = Prio3Histogram::new_histogram(2, 20, 4).unwrap();
+        let mut generator = BatchGenerator::new(vdaf, 7, histogram_buckets(20));
+        let batch = generator.batch(200).unwrap();
+        let first = batch
+            .iter()
+            .filter(|report| report.measurement == 0)
+            .count();
+        let last = batch
+            .iter()
+            .filter(|report| report.measurement == 19)
+            .count();
+        assert!(first > last);
+        assert!(batch
+            .iter()
+            .all(|report| report.valid && prepare(generator.vdaf(), report)));
+
+        let vdaf = Prio3::new_sum(2, 8).unwrap();
+        let mut generator = BatchGenerator::new(vdaf, 7, sums(8));
+        assert!(generator
+            .batch(10)
+            .unwrap()
+            .iter()
+            .all(|report| report.measurement < 256 && prepare(generator.vdaf(), report)));
+
+        assert!(
+            BatchGenerator::new(Prio3Count::new_count(2).unwrap(), 0, counts(0.5))
+                .with_invalid_ratio(1.5)
+                .is_err()
+        );
+    }
+}
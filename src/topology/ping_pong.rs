@@ -55,6 +55,10 @@ pub enum PingPongError {
         expected: &'static str,
     },
 
+    /// Error decoding a ping-pong message received from the peer, or encoding one for it
+    #[error("encode/decode ping-pong message {0}")]
+    CodecMessage(CodecError),
+
     /// Internal error
     #[error("internal error: {0}")]
     InternalError(&'static str),
@@ -297,8 +301,8 @@ where
 
 /// Corresponds to the `State` enumeration implicitly defined in [VDAF's Ping-Pong Topology][VDAF].
 /// VDAF describes `Start` and `Rejected` states, but the `Start` state is never instantiated in
-/// code, and the `Rejected` state is represented as `std::result::Result::Err`, so this enum does
-/// not include those variants.
+/// code, and the `Rejected` state is represented as `std::result::Result::Err` (or
+/// [`PingPongStep::Reject`]), so this enum does not include those variants.
 ///
 /// [VDAF]: https://datatracker.ietf.org/doc/html/draft-irtf-cfrg-vdaf-08#section-5.8
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -336,6 +340,80 @@ pub enum PingPongContinuedValue<
     },
 }
 
+/// The outcome of a step of preparation driven by [`PingPongTopology::leader_start`],
+/// [`PingPongTopology::helper_start`], [`PingPongTopology::leader_step`] or
+/// [`PingPongTopology::helper_step`]. Messages to and from the peer are opaque byte strings, so
+/// network code does not need to look inside them.
+#[derive(Debug)]
+pub enum PingPongStep<
+    const VERIFY_KEY_SIZE: usize,
+    const NONCE_SIZE: usize,
+    A: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+> {
+    /// Preparation continues. `outbound` should be transmitted to the peer, and the peer's reply
+    /// passed, along with `prep_state`, to the next step.
+    Continue {
+        /// The host's preparation state, which it stores until the peer replies.
+        prep_state: A::PrepareState,
+        /// The message to transmit to the peer.
+        outbound: Vec<u8>,
+    },
+    /// Preparation is finished. The output share may be accumulated once `outbound`, if any, has
+    /// been transmitted to the peer.
+    Finish {
+        /// The output share which may now be accumulated.
+        output_share: A::OutputShare,
+        /// The message to transmit to the peer, if the peer has not yet finished.
+        outbound: Option<Vec<u8>>,
+    },
+    /// The report was rejected and no message should be transmitted to the peer. The ping-pong
+    /// topology has no message for rejection, so the application must tell the peer out of band,
+    /// as DAP does with its report errors.
+    Reject(PingPongError),
+}
+
+impl<
+        const VERIFY_KEY_SIZE: usize,
+        const NONCE_SIZE: usize,
+        A: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+    > PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, A>
+{
+    fn new(
+        result: Result<
+            (
+                PingPongState<VERIFY_KEY_SIZE, NONCE_SIZE, A>,
+                Option<PingPongMessage>,
+            ),
+            PingPongError,
+        >,
+    ) -> Self {
+        let (state, outbound) = match result.and_then(|(state, message)| {
+            let outbound = message
+                .map(|message| message.get_encoded())
+                .transpose()
+                .map_err(PingPongError::CodecMessage)?;
+            Ok((state, outbound))
+        }) {
+            Ok(value) => value,
+            Err(err) => return Self::Reject(err),
+        };
+
+        match (state, outbound) {
+            (PingPongState::Continued(prep_state), Some(outbound)) => Self::Continue {
+                prep_state,
+                outbound,
+            },
+            (PingPongState::Continued(_), None) => Self::Reject(PingPongError::InternalError(
+                "preparation continues but there is no message for the peer",
+            )),
+            (PingPongState::Finished(output_share), outbound) => Self::Finish {
+                output_share,
+                outbound,
+            },
+        }
+    }
+}
+
 /// Extension trait on [`crate::vdaf::Aggregator`] which adds the [VDAF Ping-Pong Topology][VDAF].
 ///
 /// [VDAF]: https://datatracker.ietf.org/doc/html/draft-irtf-cfrg-vdaf-08#section-5.8
@@ -416,7 +494,7 @@ pub trait PingPongTopology<const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize
     ///   share may be accumulated.
     ///
     /// - `PingPongContinuedValue::FinishedNoMessage`: preparation is finished and the output share
-    ///    may be accumulated. No message needs to be sent to the helper.
+    ///   may be accumulated. No message needs to be sent to the helper.
     ///
     /// # Errors
     ///
@@ -466,6 +544,47 @@ pub trait PingPongTopology<const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize
         agg_param: &Self::AggregationParam,
         inbound: &PingPongMessage,
     ) -> Result<Self::ContinuedValue, PingPongError>;
+
+    /// Starts the leader's preparation of a report, like [`Self::leader_initialized`]. The
+    /// outcome is always `PingPongStep::Continue` or `PingPongStep::Reject`.
+    fn leader_start(
+        &self,
+        verify_key: &[u8; VERIFY_KEY_SIZE],
+        agg_param: &Self::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        public_share: &Self::PublicShare,
+        input_share: &Self::InputShare,
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self>;
+
+    /// Starts the helper's preparation of a report, like [`Self::helper_initialized`], given the
+    /// leader's first message as received from the network.
+    fn helper_start(
+        &self,
+        verify_key: &[u8; VERIFY_KEY_SIZE],
+        agg_param: &Self::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        public_share: &Self::PublicShare,
+        input_share: &Self::InputShare,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self>;
+
+    /// Advances the leader's preparation of a report, like [`Self::leader_continued`], given the
+    /// state from the previous step and the helper's reply as received from the network.
+    fn leader_step(
+        &self,
+        prep_state: Self::PrepareState,
+        agg_param: &Self::AggregationParam,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self>;
+
+    /// Advances the helper's preparation of a report, like [`Self::helper_continued`], given the
+    /// state from the previous step and the leader's reply as received from the network.
+    fn helper_step(
+        &self,
+        prep_state: Self::PrepareState,
+        agg_param: &Self::AggregationParam,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self>;
 }
 
 /// Private interfaces for implementing ping-pong
@@ -479,6 +598,14 @@ trait PingPongTopologyPrivate<const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: us
         agg_param: &Self::AggregationParam,
         inbound: &PingPongMessage,
     ) -> Result<Self::ContinuedValue, PingPongError>;
+
+    fn step(
+        &self,
+        is_leader: bool,
+        prep_state: Self::PrepareState,
+        agg_param: &Self::AggregationParam,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self>;
 }
 
 impl<const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize, A>
@@ -576,6 +703,65 @@ where
     ) -> Result<Self::ContinuedValue, PingPongError> {
         self.continued(false, helper_state, agg_param, inbound)
     }
+
+    fn leader_start(
+        &self,
+        verify_key: &[u8; VERIFY_KEY_SIZE],
+        agg_param: &Self::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        public_share: &Self::PublicShare,
+        input_share: &Self::InputShare,
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self> {
+        PingPongStep::new(
+            self.leader_initialized(verify_key, agg_param, nonce, public_share, input_share)
+                .map(|(state, message)| (state, Some(message))),
+        )
+    }
+
+    fn helper_start(
+        &self,
+        verify_key: &[u8; VERIFY_KEY_SIZE],
+        agg_param: &Self::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        public_share: &Self::PublicShare,
+        input_share: &Self::InputShare,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self> {
+        PingPongStep::new(
+            PingPongMessage::get_decoded(inbound)
+                .map_err(PingPongError::CodecMessage)
+                .and_then(|inbound| {
+                    self.helper_initialized(
+                        verify_key,
+                        agg_param,
+                        nonce,
+                        public_share,
+                        input_share,
+                        &inbound,
+                    )
+                })
+                .and_then(|transition| transition.evaluate(self))
+                .map(|(state, message)| (state, Some(message))),
+        )
+    }
+
+    fn leader_step(
+        &self,
+        prep_state: Self::PrepareState,
+        agg_param: &Self::AggregationParam,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self> {
+        self.step(true, prep_state, agg_param, inbound)
+    }
+
+    fn helper_step(
+        &self,
+        prep_state: Self::PrepareState,
+        agg_param: &Self::AggregationParam,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self> {
+        self.step(false, prep_state, agg_param, inbound)
+    }
 }
 
 impl<const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize, A>
@@ -657,6 +843,35 @@ where
             }),
         }
     }
+
+    fn step(
+        &self,
+        is_leader: bool,
+        prep_state: Self::PrepareState,
+        agg_param: &Self::AggregationParam,
+        inbound: &[u8],
+    ) -> PingPongStep<VERIFY_KEY_SIZE, NONCE_SIZE, Self> {
+        PingPongStep::new(
+            PingPongMessage::get_decoded(inbound)
+                .map_err(PingPongError::CodecMessage)
+                .and_then(|inbound| {
+                    self.continued(
+                        is_leader,
+                        PingPongState::Continued(prep_state),
+                        agg_param,
+                        &inbound,
+                    )
+                })
+                .and_then(|value| match value {
+                    PingPongContinuedValue::WithMessage { transition } => transition
+                        .evaluate(self)
+                        .map(|(state, message)| (state, Some(message))),
+                    PingPongContinuedValue::FinishedNoMessage { output_share } => {
+                        Ok((PingPongState::Finished(output_share), None))
+                    }
+                }),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -853,6 +1068,115 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ping_pong_byte_steps() {
+        let verify_key = [];
+        let aggregation_param = dummy::AggregationParam(0);
+        let nonce = [0; 16];
+        #[allow(clippy::let_unit_value)]
+        let public_share = ();
+        let input_share = dummy::InputShare(7);
+
+        for rounds in 1..=4 {
+            let vdaf = dummy::Vdaf::new(rounds);
+            let PingPongStep::Continue {
+                prep_state: mut leader_state,
+                outbound,
+            } = vdaf.leader_start(
+                &verify_key,
+                &aggregation_param,
+                &nonce,
+                &public_share,
+                &input_share,
+            )
+            else {
+                panic!("leader did not continue");
+            };
+
+            // Shuttle opaque messages back and forth until both aggregators finish.
+            let mut helper_state = None;
+            let mut inbound = Some(outbound);
+            let mut output_shares = Vec::new();
+            let mut is_leader = false;
+            while let Some(message) = inbound.take() {
+                let step = match (is_leader, helper_state.take()) {
+                    (false, None) => vdaf.helper_start(
+                        &verify_key,
+                        &aggregation_param,
+                        &nonce,
+                        &public_share,
+                        &input_share,
+                        &message,
+                    ),
+                    (false, Some(state)) => vdaf.helper_step(state, &aggregation_param, &message),
+                    (true, state) => {
+                        helper_state = state;
+                        vdaf.leader_step(leader_state, &aggregation_param, &message)
+                    }
+                };
+                match step {
+                    PingPongStep::Continue {
+                        prep_state,
+                        outbound,
+                    } => {
+                        if is_leader {
+                            leader_state = prep_state;
+                        } else {
+                            helper_state = Some(prep_state);
+                        }
+                        inbound = Some(outbound);
+                    }
+                    PingPongStep::Finish {
+                        output_share,
+                        outbound,
+                    } => {
+                        output_shares.push(output_share);
+                        inbound = outbound;
+                    }
+                    PingPongStep::Reject(err) => panic!("rejected: {err}"),
+                }
+                is_leader = !is_leader;
+            }
+            assert_eq!(output_shares, [dummy::OutputShare(7); 2], "{rounds} rounds");
+        }
+
+        // Malformed messages and failed preparation are rejections.
+        let vdaf = dummy::Vdaf::new(2);
+        assert_matches!(
+            vdaf.helper_start(
+                &verify_key,
+                &aggregation_param,
+                &nonce,
+                &public_share,
+                &input_share,
+                &[0xff],
+            ),
+            PingPongStep::Reject(PingPongError::CodecMessage(_))
+        );
+        let failing = dummy::Vdaf::new(2)
+            .with_prep_step_fn(|_| Err(VdafError::Uncategorized("failed".into())));
+        let PingPongStep::Continue { outbound, .. } = failing.leader_start(
+            &verify_key,
+            &aggregation_param,
+            &nonce,
+            &public_share,
+            &input_share,
+        ) else {
+            panic!("leader did not continue");
+        };
+        assert_matches!(
+            failing.helper_start(
+                &verify_key,
+                &aggregation_param,
+                &nonce,
+                &public_share,
+                &input_share,
+                &outbound,
+            ),
+            PingPongStep::Reject(PingPongError::VdafPrepareNext(_))
+        );
+    }
+
     #[test]
     fn roundtrip_message() {
         let messages = [
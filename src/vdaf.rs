@@ -6,11 +6,9 @@
 //! [draft-irtf-cfrg-vdaf-08]: https://datatracker.ietf.org/doc/draft-irtf-cfrg-vdaf/08/
 
 #[cfg(feature = "experimental")]
-use crate::dp::DifferentialPrivacyStrategy;
+use crate::dp::{DifferentialPrivacyStrategy, DpError};
 #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
 use crate::idpf::IdpfError;
-#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
-use crate::vidpf::VidpfError;
 use crate::{
     codec::{CodecError, Decode, Encode, ParameterizedDecode},
     field::{encode_fieldvec, merge_vector, FieldElement, FieldError},
@@ -18,6 +16,10 @@ use crate::{
     prng::PrngError,
     vdaf::xof::Seed,
 };
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+use crate::{flp::szk::SzkError, vidpf::VidpfError};
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use std::{error::Error, fmt::Debug, io::Cursor};
 use subtle::{Choice, ConstantTimeEq};
@@ -42,6 +44,10 @@ pub enum VdafError {
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
 
+    /// Codec error.
+    #[error("codec error: {0}")]
+    Codec(#[from] CodecError),
+
     /// FLP error.
     #[error("flp error: {0}")]
     Flp(#[from] FlpError),
@@ -54,6 +60,16 @@ pub enum VdafError {
     #[error("getrandom: {0}")]
     GetRandom(#[from] getrandom::Error),
 
+    /// Differential privacy error.
+    #[cfg(feature = "experimental")]
+    #[error("dp error: {0}")]
+    Dp(#[from] DpError),
+
+    /// A report was rejected because of its timestamp.
+    #[cfg(feature = "experimental")]
+    #[error("timestamp error: {0}")]
+    Timestamp(#[from] crate::vdaf::report::TimestampError),
+
     /// IDPF error.
     #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
     #[error("idpf error: {0}")]
@@ -64,11 +80,93 @@ pub enum VdafError {
     #[error("vidpf error: {0}")]
     Vidpf(#[from] VidpfError),
 
+    /// SZK error.
+    #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+    #[error("szk error: {0}")]
+    Szk(#[from] SzkError),
+
+    /// Persisted state failed its integrity check when it was loaded, e.g. because a file was
+    /// truncated or modified outside of this crate.
+    #[cfg(feature = "experimental")]
+    #[error("corrupted state: {0}")]
+    CorruptedState(String),
+
+    /// A report was rejected during preparation. The reason distinguishes a report that was
+    /// malformed from one whose proof did not verify.
+    #[error("report rejected ({reason}): {source}")]
+    Rejected {
+        /// Why the report was rejected.
+        reason: RejectionReason,
+
+        /// The underlying error.
+        source: Box<VdafError>,
+    },
+
     /// Errors from other VDAFs.
     #[error(transparent)]
     Other(Box<dyn Error + 'static + Send + Sync>),
 }
 
+impl VdafError {
+    /// Returns the reason the report was rejected, if this error is a rejection.
+    pub fn rejection_reason(&self) -> Option<RejectionReason> {
+        match self {
+            Self::Rejected { reason, .. } => Some(*reason),
+            _ => None,
+        }
+    }
+}
+
+/// The reason a report was rejected during preparation.
+///
+/// With the `metrics` feature, [`RejectionReason::as_str`] is the `reason` label of the
+/// `prio_reports_rejected_total` counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RejectionReason {
+    /// The input share could not be decrypted. The VDAFs in this crate never return this, as they
+    /// do not encrypt input shares; it is provided so that an application that does can report
+    /// decryption failures alongside the other reasons.
+    Decryption,
+
+    /// A share or message had the wrong length, was missing a part, or the wrong number of
+    /// prepare shares was received.
+    LengthMismatch,
+
+    /// The proof could not be split into its components.
+    ProofUnpack,
+
+    /// The proof (or sketch) did not verify, i.e. a polynomial identity did not hold.
+    InvalidProof,
+
+    /// The messages of the Aggregators did not agree, e.g. on the joint randomness.
+    PeerMismatch,
+
+    /// A peer's message was well-formed but violated an invariant every honest message satisfies,
+    /// e.g. it was all zeros.
+    MalformedMessage,
+}
+
+impl RejectionReason {
+    /// Returns a short, stable name for the reason, suitable as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Decryption => "decryption",
+            Self::LengthMismatch => "length_mismatch",
+            Self::ProofUnpack => "proof_unpack",
+            Self::InvalidProof => "invalid_proof",
+            Self::PeerMismatch => "peer_mismatch",
+            Self::MalformedMessage => "malformed_message",
+        }
+    }
+}
+
+impl std::fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// An additive share of a vector of field elements.
 #[derive(Clone, Debug)]
 pub enum Share<F, const SEED_SIZE: usize> {
@@ -99,6 +197,19 @@ impl<F: ConstantTimeEq, const SEED_SIZE: usize> ConstantTimeEq for Share<F, SEED
     }
 }
 
+#[cfg(feature = "test-util")]
+impl<'a, F: arbitrary::Arbitrary<'a>, const SEED_SIZE: usize> arbitrary::Arbitrary<'a>
+    for Share<F, SEED_SIZE>
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        if u.arbitrary()? {
+            Ok(Share::Leader(u.arbitrary()?))
+        } else {
+            Ok(Share::Helper(u.arbitrary()?))
+        }
+    }
+}
+
 /// Parameters needed to decode a [`Share`]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum ShareDecodingParameter<const SEED_SIZE: usize> {
@@ -218,6 +329,24 @@ pub trait Client<const NONCE_SIZE: usize>: Vdaf {
     ) -> Result<(Self::PublicShare, Vec<Self::InputShare>), VdafError>;
 }
 
+/// A [`Client`] that can draw its randomness from a caller-supplied RNG.
+///
+/// Sharding with a seeded RNG is deterministic, which lets applications write golden tests that
+/// pin the exact public and input shares for a given measurement. Production code should call
+/// [`Client::shard`], which uses the operating system's RNG, unless the RNG is keyed with a secret
+/// as in [`ReportBuilder::build_idempotent`](crate::vdaf::report::ReportBuilder::build_idempotent).
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "test-util", feature = "experimental"))))]
+pub trait ClientWithRng<const NONCE_SIZE: usize>: Client<NONCE_SIZE> {
+    /// Shards a measurement like [`Client::shard`], drawing all randomness from `rng`.
+    fn shard_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        measurement: &Self::Measurement,
+        nonce: &[u8; NONCE_SIZE],
+        rng: &mut R,
+    ) -> Result<(Self::PublicShare, Vec<Self::InputShare>), VdafError>;
+}
+
 /// The Aggregator's role in the execution of a VDAF.
 pub trait Aggregator<const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize>: Vdaf {
     /// State of the Aggregator during the Prepare process.
@@ -381,6 +510,13 @@ impl<F> From<Vec<F>> for OutputShare<F> {
     }
 }
 
+#[cfg(feature = "test-util")]
+impl<'a, F: arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for OutputShare<F> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary().map(Self)
+    }
+}
+
 impl<F: FieldElement> Encode for OutputShare<F> {
     fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
         encode_fieldvec(&self.0, bytes)
@@ -457,6 +593,13 @@ impl<F: FieldElement> AggregateShare<F> {
     }
 }
 
+#[cfg(feature = "test-util")]
+impl<'a, F: arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for AggregateShare<F> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary().map(Self)
+    }
+}
+
 impl<F: FieldElement> Encode for AggregateShare<F> {
     fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
         encode_fieldvec(&self.0, bytes)
@@ -734,11 +877,114 @@ mod tests {
             AggregateShare(Vec::from([3, 2, 1])),
         ])
     }
+
+    #[cfg(feature = "test-util")]
+    fn check_shard_with_rng<V, const SEED_SIZE: usize>(
+        vdaf: &V,
+        agg_param: &V::AggregationParam,
+        measurement: &V::Measurement,
+    ) where
+        V: super::ClientWithRng<16> + super::Aggregator<SEED_SIZE, 16> + super::Collector,
+    {
+        use crate::codec::Encode;
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let shard = |seed| {
+            let (public_share, input_shares) = vdaf
+                .shard_with_rng(measurement, &[0; 16], &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            let encoded = (
+                public_share.get_encoded().unwrap(),
+                input_shares
+                    .iter()
+                    .map(|share| share.get_encoded().unwrap())
+                    .collect::<Vec<_>>(),
+            );
+            (public_share, input_shares, encoded)
+        };
+
+        let (public_share, input_shares, encoded) = shard(1);
+        assert_eq!(shard(1).2, encoded);
+        assert_ne!(shard(2).2, encoded);
+        super::test_utils::run_vdaf_prepare(
+            vdaf,
+            &[1; SEED_SIZE],
+            agg_param,
+            &[0; 16],
+            public_share,
+            input_shares,
+        )
+        .unwrap();
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn shard_with_rng() {
+        use crate::vdaf::prio3::Prio3;
+
+        check_shard_with_rng(
+            &Prio3::new_sum_vec(3, 2, 4, 2).unwrap(),
+            &(),
+            &vec![0, 1, 2, 3],
+        );
+
+        #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+        {
+            use crate::{
+                idpf::IdpfInput,
+                vdaf::{
+                    poplar1::{Poplar1, Poplar1AggregationParam},
+                    prio2::Prio2,
+                },
+            };
+
+            let prio2 = Prio2::new(4).unwrap().with_num_aggregators(3).unwrap();
+            check_shard_with_rng(&prio2, &(), &vec![1, 0, 0, 1]);
+
+            let input = IdpfInput::from_bools(&[true, false]);
+            let agg_param =
+                Poplar1AggregationParam::try_from_prefixes(vec![input.clone()]).unwrap();
+            check_shard_with_rng(&Poplar1::new_turboshake128(2), &agg_param, &input);
+        }
+    }
 }
 
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod accumulator;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod audit;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod commitment;
 #[cfg(feature = "test-util")]
 #[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
 pub mod dummy;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod export;
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "crypto-dependencies", feature = "experimental")))
+)]
+pub mod helper_state;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod id;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod ingest;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod key_config;
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "crypto-dependencies", feature = "experimental")))
+)]
+pub mod mastic;
 #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
 #[cfg_attr(
     docsrs,
@@ -750,9 +996,34 @@ pub mod poplar1;
     docsrs,
     doc(cfg(all(feature = "crypto-dependencies", feature = "experimental")))
 )]
+pub mod prefix_tree;
+#[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "crypto-dependencies", feature = "experimental")))
+)]
 pub mod prio2;
 pub mod prio3;
 #[cfg(any(test, feature = "test-util"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
 pub mod prio3_test;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod registry;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod report;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod sampling;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod sanity;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod task;
+mod telemetry;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub mod version;
 pub mod xof;
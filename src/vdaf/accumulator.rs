@@ -0,0 +1,1590 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Accumulators for aggregate shares that are too large to hold in memory.
+//!
+//! [`FileAccumulator`] keeps a vector of field elements in a file and applies updates one chunk at
+//! a time, so the memory needed to aggregate a very wide measurement (e.g., a
+//! [`Prio3SumVec`](crate::vdaf::prio3::Prio3SumVec) with tens of millions of entries) is bounded
+//! by the chunk length rather than by the dimension. The file is accessed through ordinary reads
+//! and writes rather than a memory map: this avoids `unsafe` code and keeps the operating system's
+//! page cache in charge of what stays resident.
+//!
+//! [`WindowedAccumulator`] groups output shares into batches by report timestamp, one per fixed
+//! time window (e.g., one hour), and hands each batch to a callback once its window has closed and
+//! a grace period for late reports has passed. The [`store`] module persists per-batch state
+//! across restarts.
+//!
+//! State written to disk carries a SHA3-256 checksum, or a MAC if the store is given a key, which
+//! is checked when the state is loaded. State that fails the check is never aggregated into:
+//! loading it returns [`VdafError::CorruptedState`].
+//!
+//! [`IntervalAccumulator`] holds the aggregate share and replay window of one collection interval
+//! at a time, and [`IntervalAccumulator::finish_batch`] releases the interval and resets the state
+//! for the next in a single step.
+//!
+//! [`ArmAccumulator`] keeps a separate aggregate share for each arm of an A/B experiment, keyed by
+//! the label returned by [`ReportShare::experiment_arm`](crate::vdaf::report::ReportShare::experiment_arm),
+//! so that a single task yields per-arm aggregates.
+//!
+//! [`LaneAccumulator`] sums the elements of a small field, such as
+//! [`FieldPrio2`](crate::field::FieldPrio2), as plain `u64` integers, and reduces them modulo the
+//! field's prime only when the sums are read or merged, or when another addition could overflow.
+//!
+//! [`ShardedAccumulator`] splits the entries of wide shares into contiguous ranges and sums each
+//! range on its own thread. Each entry is only ever touched by one thread, which adds the shares
+//! in the order they were given, so the result does not depend on how the threads are scheduled
+//! and Aggregators can compare their accumulators bit for bit.
+//!
+//! [`DualAccumulator`] prepares a sample of reports a second time with an alternate
+//! implementation of the VDAF, such as Prio2 using [`NaiveFftBackend`](crate::fft::NaiveFftBackend),
+//! and records any disagreement with the primary implementation, so that regressions in optimized
+//! code paths are caught in production without affecting the results.
+
+use crate::{
+    codec::Encode,
+    field::{FieldElement, SmallFieldElement},
+    vdaf::{
+        id::ReportId, sampling::SamplingPolicy, Aggregatable, AggregateShare, Aggregator,
+        OutputShare, PrepareTransition, VdafError,
+    },
+};
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Debug},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    ops::Range,
+    path::Path,
+    thread,
+};
+use subtle::ConstantTimeEq;
+
+pub mod store;
+
+/// Domain separation tag for the checksums of persisted state.
+const CHECKSUM_DST: &[u8] = b"prio persisted state checksum";
+
+/// The length in bytes of a checksum.
+pub(crate) const CHECKSUM_LEN: usize = 32;
+
+/// Starts the checksum of a piece of persisted state. With a `key`, the checksum is a MAC: SHA3 is
+/// not subject to length extension, so hashing the key before the contents suffices. The
+/// `context` names the piece of state, so that a checksum cannot be moved from one to another.
+pub(crate) fn checksum_hasher(key: Option<&[u8; 32]>, context: &[u8]) -> Sha3_256 {
+    let mut hasher = Sha3_256::new();
+    hasher.update(CHECKSUM_DST);
+    match key {
+        Some(key) => {
+            hasher.update([1]);
+            hasher.update(key);
+        }
+        None => hasher.update([0]),
+    }
+    hasher.update((context.len() as u64).to_be_bytes());
+    hasher.update(context);
+    hasher
+}
+
+/// Returns an error naming `what` unless `stored` is the checksum in `hasher`.
+pub(crate) fn check_checksum(hasher: Sha3_256, stored: &[u8], what: &str) -> Result<(), VdafError> {
+    let computed: [u8; CHECKSUM_LEN] = hasher.finalize().into();
+    if bool::from(computed.ct_eq(stored)) {
+        Ok(())
+    } else {
+        Err(VdafError::CorruptedState(format!(
+            "checksum mismatch in {what}"
+        )))
+    }
+}
+
+/// An accumulator for a vector of field elements stored in a file.
+///
+/// The file holds the encoding of each field element, in order, with no header, followed by a
+/// checksum of the elements. Updates are applied in chunks of at most `chunk_len` elements.
+/// Changes are buffered by the operating system until [`FileAccumulator::flush`] is called, which
+/// also updates the checksum; a file that was changed but not flushed fails the check when it is
+/// reopened.
+#[derive(Debug)]
+pub struct FileAccumulator<F> {
+    file: File,
+    len: usize,
+    chunk_len: usize,
+    buf: Vec<u8>,
+    chunk: Vec<F>,
+}
+
+impl<F: FieldElement> FileAccumulator<F> {
+    /// Creates a new accumulator of `len` zeros at `path`, truncating the file if it exists.
+    /// Updates are applied `chunk_len` elements at a time.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        len: usize,
+        chunk_len: usize,
+    ) -> Result<Self, VdafError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let mut acc = Self::new(file, len, chunk_len)?;
+
+        acc.chunk.resize(acc.chunk_len, F::zero());
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(acc.chunk_len);
+            acc.write_chunk(n)?;
+            remaining -= n;
+        }
+        acc.flush()?;
+        Ok(acc)
+    }
+
+    /// Opens an existing accumulator of `len` elements at `path`. Returns an error if the size of
+    /// the file does not match `len`, and [`VdafError::CorruptedState`] if the elements do not
+    /// match the checksum.
+    pub fn open<P: AsRef<Path>>(path: P, len: usize, chunk_len: usize) -> Result<Self, VdafError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let file_len = file.metadata()?.len();
+        if Some(file_len)
+            != byte_len::<F>(len)
+                .and_then(|n| n.checked_add(CHECKSUM_LEN))
+                .and_then(|n| u64::try_from(n).ok())
+        {
+            return Err(VdafError::Uncategorized(format!(
+                "accumulator file has length {file_len}, expected {len} field elements"
+            )));
+        }
+        let mut acc = Self::new(file, len, chunk_len)?;
+        let hasher = acc.checksum()?;
+        let mut stored = [0; CHECKSUM_LEN];
+        acc.file.read_exact(&mut stored)?;
+        check_checksum(hasher, &stored, "accumulator file")?;
+        Ok(acc)
+    }
+
+    fn new(file: File, len: usize, chunk_len: usize) -> Result<Self, VdafError> {
+        if chunk_len == 0 {
+            return Err(VdafError::Uncategorized(
+                "chunk length must be positive".into(),
+            ));
+        }
+        if byte_len::<F>(len).is_none() {
+            return Err(VdafError::Uncategorized(
+                "accumulator length exceeds memory capacity".into(),
+            ));
+        }
+        let chunk_len = chunk_len.min(len.max(1));
+        Ok(Self {
+            file,
+            len,
+            chunk_len,
+            buf: Vec::with_capacity(chunk_len * F::ENCODED_SIZE),
+            chunk: Vec::with_capacity(chunk_len),
+        })
+    }
+
+    /// Returns the number of field elements in the accumulator.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the accumulator has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds `other` to the elements of the accumulator starting at index `offset`.
+    ///
+    /// This allows a caller to stream a wide output share into the accumulator piece by piece,
+    /// without ever holding the whole share in memory.
+    pub fn accumulate_at(&mut self, offset: usize, other: &[F]) -> Result<(), VdafError> {
+        if offset
+            .checked_add(other.len())
+            .map_or(true, |end| end > self.len)
+        {
+            return Err(VdafError::Uncategorized(
+                "update exceeds accumulator length".into(),
+            ));
+        }
+
+        for (i, update) in other.chunks(self.chunk_len).enumerate() {
+            self.seek(offset + i * self.chunk_len)?;
+            self.read_chunk(update.len())?;
+            for (x, y) in self.chunk.iter_mut().zip(update) {
+                *x += *y;
+            }
+            self.seek(offset + i * self.chunk_len)?;
+            self.write_chunk(update.len())?;
+        }
+        Ok(())
+    }
+
+    /// Adds an output share to the accumulator.
+    pub fn accumulate(&mut self, output_share: &OutputShare<F>) -> Result<(), VdafError> {
+        self.check_len(output_share.as_ref().len())?;
+        self.accumulate_at(0, output_share.as_ref())
+    }
+
+    /// Adds an aggregate share to the accumulator.
+    pub fn merge(&mut self, agg_share: &AggregateShare<F>) -> Result<(), VdafError> {
+        self.check_len(agg_share.as_ref().len())?;
+        self.accumulate_at(0, agg_share.as_ref())
+    }
+
+    /// Updates the checksum and writes any buffered changes to disk.
+    pub fn flush(&mut self) -> Result<(), VdafError> {
+        let checksum: [u8; CHECKSUM_LEN] = self.checksum()?.finalize().into();
+        self.file.write_all(&checksum)?;
+        self.file.flush()?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Hashes the elements in the file, leaving the position at the checksum that follows them.
+    fn checksum(&mut self) -> Result<Sha3_256, VdafError> {
+        let mut hasher = checksum_hasher(None, b"file accumulator");
+        self.seek(0)?;
+        let mut remaining = self.len;
+        while remaining > 0 {
+            let n = remaining.min(self.chunk_len);
+            self.buf.resize(n * F::ENCODED_SIZE, 0);
+            self.file.read_exact(&mut self.buf)?;
+            hasher.update(&self.buf);
+            remaining -= n;
+        }
+        Ok(hasher)
+    }
+
+    /// Reads `out.len()` elements of the accumulator starting at index `offset`.
+    pub fn read_at(&mut self, offset: usize, out: &mut [F]) -> Result<(), VdafError> {
+        if offset
+            .checked_add(out.len())
+            .map_or(true, |end| end > self.len)
+        {
+            return Err(VdafError::Uncategorized(
+                "read exceeds accumulator length".into(),
+            ));
+        }
+
+        for (i, out) in out.chunks_mut(self.chunk_len).enumerate() {
+            self.seek(offset + i * self.chunk_len)?;
+            self.read_chunk(out.len())?;
+            out.copy_from_slice(&self.chunk);
+        }
+        Ok(())
+    }
+
+    /// Reads the entire accumulator into memory as an aggregate share.
+    pub fn to_aggregate_share(&mut self) -> Result<AggregateShare<F>, VdafError> {
+        let mut out = vec![F::zero(); self.len];
+        self.read_at(0, &mut out)?;
+        Ok(AggregateShare::from(out))
+    }
+
+    fn check_len(&self, len: usize) -> Result<(), VdafError> {
+        if len != self.len {
+            return Err(VdafError::Uncategorized(format!(
+                "share has length {len}, expected {}",
+                self.len
+            )));
+        }
+        Ok(())
+    }
+
+    fn seek(&mut self, index: usize) -> Result<(), VdafError> {
+        // Cannot overflow: the file length was checked when the accumulator was constructed.
+        let pos = (index * F::ENCODED_SIZE) as u64;
+        self.file.seek(SeekFrom::Start(pos))?;
+        Ok(())
+    }
+
+    /// Reads `n` elements from the current position into `self.chunk`.
+    fn read_chunk(&mut self, n: usize) -> Result<(), VdafError> {
+        self.buf.resize(n * F::ENCODED_SIZE, 0);
+        self.file.read_exact(&mut self.buf)?;
+        self.chunk.clear();
+        for encoded in self.buf.chunks_exact(F::ENCODED_SIZE) {
+            self.chunk.push(F::try_from(encoded)?);
+        }
+        Ok(())
+    }
+
+    /// Writes the first `n` elements of `self.chunk` at the current position.
+    fn write_chunk(&mut self, n: usize) -> Result<(), VdafError> {
+        self.buf.clear();
+        for x in &self.chunk[..n] {
+            x.encode(&mut self.buf)?;
+        }
+        self.file.write_all(&self.buf)?;
+        Ok(())
+    }
+}
+
+/// A batch of output shares whose time window has closed, passed to the callback of a
+/// [`WindowedAccumulator`].
+#[derive(Clone, Debug)]
+pub struct FinalizedBatch<F> {
+    /// The start of the batch's time window. This is a multiple of the window length.
+    pub start: u64,
+
+    /// The length of the batch's time window.
+    pub duration: u64,
+
+    /// The number of output shares in the batch.
+    pub report_count: u64,
+
+    /// The sum of the output shares in the batch.
+    pub aggregate_share: AggregateShare<F>,
+}
+
+/// An accumulator that sorts output shares into batches by time window.
+///
+/// Each output share is accumulated into the batch for the window of length `window` containing
+/// its report's timestamp; windows start at multiples of `window`. The accumulator does not read
+/// the clock: the caller reports the current time with [`WindowedAccumulator::advance`], which
+/// finalizes every batch whose window ended at least `grace_period` ago by passing it to the
+/// callback. Reports for finalized windows, or for any window that ends before the last window
+/// finalized, are rejected. Times are in the same unit as report timestamps, typically seconds.
+pub struct WindowedAccumulator<F, C> {
+    len: usize,
+    window: u64,
+    grace_period: u64,
+    now: u64,
+    /// The end of the latest window finalized so far.
+    released: u64,
+    batches: BTreeMap<u64, (u64, AggregateShare<F>)>,
+    on_finalize: C,
+}
+
+impl<F, C> WindowedAccumulator<F, C>
+where
+    F: FieldElement,
+    C: FnMut(FinalizedBatch<F>),
+{
+    /// Creates an accumulator for output shares of length `len`. Each finalized batch is passed to
+    /// `on_finalize`. Returns an error if `window` is zero.
+    pub fn new(
+        len: usize,
+        window: u64,
+        grace_period: u64,
+        on_finalize: C,
+    ) -> Result<Self, VdafError> {
+        if window == 0 {
+            return Err(VdafError::Uncategorized(
+                "window length must be positive".into(),
+            ));
+        }
+        Ok(Self {
+            len,
+            window,
+            grace_period,
+            now: 0,
+            released: 0,
+            batches: BTreeMap::new(),
+            on_finalize,
+        })
+    }
+
+    /// Adds the output share of a report with the given timestamp to its batch. Returns an error
+    /// if the share has the wrong length or if the batch has already been finalized.
+    pub fn accumulate(
+        &mut self,
+        timestamp: u64,
+        output_share: &OutputShare<F>,
+    ) -> Result<(), VdafError> {
+        if output_share.as_ref().len() != self.len {
+            return Err(VdafError::Uncategorized(format!(
+                "share has length {}, expected {}",
+                output_share.as_ref().len(),
+                self.len
+            )));
+        }
+        let start = timestamp - timestamp % self.window;
+        if self.is_closed(start) || timestamp < self.released {
+            return Err(VdafError::Uncategorized(format!(
+                "report with timestamp {timestamp} arrived after its batch was finalized"
+            )));
+        }
+        let (report_count, aggregate_share) = self
+            .batches
+            .entry(start)
+            .or_insert_with(|| (0, AggregateShare::from(vec![F::zero(); self.len])));
+        aggregate_share.accumulate(output_share)?;
+        *report_count += 1;
+        Ok(())
+    }
+
+    /// Advances the current time to `now`, finalizing every batch that is due, in order of start
+    /// time. Time never moves backwards: an earlier `now` is ignored.
+    pub fn advance(&mut self, now: u64) {
+        self.now = self.now.max(now);
+        while let Some(start) = self.batches.keys().next().copied() {
+            if !self.is_closed(start) {
+                break;
+            }
+            let Some((report_count, aggregate_share)) = self.batches.remove(&start) else {
+                break;
+            };
+            self.finalize(start, report_count, aggregate_share);
+        }
+    }
+
+    /// Finalizes every open batch, regardless of the current time, e.g. during shutdown. Reports
+    /// for these windows, and earlier ones, are rejected from then on.
+    pub fn finalize_all(&mut self) {
+        for (start, (report_count, aggregate_share)) in std::mem::take(&mut self.batches) {
+            self.finalize(start, report_count, aggregate_share);
+        }
+    }
+
+    /// Returns the start times of the batches that are still open.
+    pub fn open_batches(&self) -> impl Iterator<Item = u64> + '_ {
+        self.batches.keys().copied()
+    }
+
+    /// Passes the batch starting at `start` to the callback and records the end of its window.
+    fn finalize(&mut self, start: u64, report_count: u64, aggregate_share: AggregateShare<F>) {
+        self.released = self.released.max(start.saturating_add(self.window));
+        (self.on_finalize)(FinalizedBatch {
+            start,
+            duration: self.window,
+            report_count,
+            aggregate_share,
+        });
+    }
+
+    /// Returns true if the batch starting at `start` is due to be finalized.
+    fn is_closed(&self, start: u64) -> bool {
+        start
+            .saturating_add(self.window)
+            .saturating_add(self.grace_period)
+            <= self.now
+    }
+}
+
+impl<F: Debug, C> Debug for WindowedAccumulator<F, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WindowedAccumulator")
+            .field("len", &self.len)
+            .field("window", &self.window)
+            .field("grace_period", &self.grace_period)
+            .field("now", &self.now)
+            .field("released", &self.released)
+            .field("batches", &self.batches)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The state released by [`IntervalAccumulator::finish_batch`].
+#[derive(Clone, Debug)]
+pub struct FinishedBatch<F> {
+    /// The index of the collection interval, counting from zero.
+    pub interval: u64,
+
+    /// The number of reports accumulated during the interval.
+    pub report_count: u64,
+
+    /// The sum of the output shares of those reports.
+    pub aggregate_share: AggregateShare<F>,
+}
+
+/// An accumulator for one collection interval at a time, with the replay protection for it.
+///
+/// Output shares are accumulated along with the IDs of their reports; a report whose ID was seen
+/// during the current or the previous interval is ignored. [`IntervalAccumulator::finish_batch`]
+/// releases the aggregate share and report count of the interval, overwrites the accumulator with
+/// zeros in place, and rotates the replay window, forgetting the IDs of the interval before. As it
+/// is a single call that takes the state, a batch can neither be released twice nor leak into the
+/// next interval. Aggregators must reject reports more than one interval old by timestamp, with a
+/// [`TimestampPolicy`](crate::vdaf::report::TimestampPolicy), for the replay window to suffice.
+#[derive(Clone, Debug)]
+pub struct IntervalAccumulator<F> {
+    interval: u64,
+    report_count: u64,
+    accumulator: Vec<F>,
+    current: HashSet<ReportId>,
+    previous: HashSet<ReportId>,
+}
+
+impl<F: FieldElement> IntervalAccumulator<F> {
+    /// Creates an accumulator for output shares of length `len`, at interval 0.
+    pub fn new(len: usize) -> Self {
+        Self {
+            interval: 0,
+            report_count: 0,
+            accumulator: vec![F::zero(); len],
+            current: HashSet::new(),
+            previous: HashSet::new(),
+        }
+    }
+
+    /// Returns the index of the current interval.
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Returns the number of reports accumulated during the current interval.
+    pub fn report_count(&self) -> u64 {
+        self.report_count
+    }
+
+    /// Adds the output share of report `report_id`. Returns `false`, and leaves the accumulator
+    /// unchanged, if the report is a replay. Returns an error if the share has the wrong length.
+    pub fn accumulate(
+        &mut self,
+        report_id: &ReportId,
+        output_share: &OutputShare<F>,
+    ) -> Result<bool, VdafError> {
+        let share = output_share.as_ref();
+        if share.len() != self.accumulator.len() {
+            return Err(VdafError::Uncategorized(format!(
+                "share has length {}, expected {}",
+                share.len(),
+                self.accumulator.len()
+            )));
+        }
+        if self.previous.contains(report_id) || !self.current.insert(*report_id) {
+            return Ok(false);
+        }
+        for (x, y) in self.accumulator.iter_mut().zip(share) {
+            *x += *y;
+        }
+        self.report_count += 1;
+        Ok(true)
+    }
+
+    /// Ends the current interval: returns its aggregate share and report count, zeroes the
+    /// accumulator and moves on to the next interval.
+    pub fn finish_batch(&mut self) -> FinishedBatch<F> {
+        let batch = FinishedBatch {
+            interval: self.interval,
+            report_count: self.report_count,
+            aggregate_share: AggregateShare::from(self.accumulator.clone()),
+        };
+        self.accumulator.fill(F::zero());
+        self.previous = std::mem::take(&mut self.current);
+        self.report_count = 0;
+        self.interval += 1;
+        batch
+    }
+}
+
+/// The aggregate share of one arm of an experiment, released by [`ArmAccumulator::finish`].
+#[derive(Clone, Debug)]
+pub struct ArmBatch<F> {
+    /// The label of the arm, or `None` for reports without a label.
+    pub arm: Option<String>,
+
+    /// The number of reports accumulated for the arm.
+    pub report_count: u64,
+
+    /// The sum of the output shares of those reports.
+    pub aggregate_share: AggregateShare<F>,
+}
+
+/// An accumulator with one aggregate share per experiment arm.
+///
+/// Each arm is a batch in its own right: the Collector learns its aggregate, so an Aggregator
+/// must hold every arm to the task's minimum batch size, and can use
+/// [`ArmAccumulator::report_count`] to decide which arms to release. Arms are created as reports
+/// arrive, and returned in order of label, with unlabeled reports first.
+#[derive(Clone, Debug)]
+pub struct ArmAccumulator<F> {
+    len: usize,
+    arms: BTreeMap<Option<String>, (u64, Vec<F>)>,
+}
+
+impl<F: FieldElement> ArmAccumulator<F> {
+    /// Creates an accumulator for output shares of length `len`.
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            arms: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the labels of the arms that have reports.
+    pub fn arms(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        self.arms.keys().map(Option::as_deref)
+    }
+
+    /// Returns the number of reports accumulated for `arm`.
+    pub fn report_count(&self, arm: Option<&str>) -> u64 {
+        self.arms
+            .get(&arm.map(String::from))
+            .map_or(0, |(report_count, _)| *report_count)
+    }
+
+    /// Adds an output share to the aggregate of `arm`. Returns an error if the share has the wrong
+    /// length.
+    pub fn accumulate(
+        &mut self,
+        arm: Option<&str>,
+        output_share: &OutputShare<F>,
+    ) -> Result<(), VdafError> {
+        let share = output_share.as_ref();
+        if share.len() != self.len {
+            return Err(VdafError::Uncategorized(format!(
+                "share has length {}, expected {}",
+                share.len(),
+                self.len
+            )));
+        }
+        let (report_count, accumulator) = self
+            .arms
+            .entry(arm.map(String::from))
+            .or_insert_with(|| (0, vec![F::zero(); self.len]));
+        for (x, y) in accumulator.iter_mut().zip(share) {
+            *x += *y;
+        }
+        *report_count += 1;
+        Ok(())
+    }
+
+    /// Returns the aggregate share of `arm`, or `None` if it has no reports.
+    pub fn aggregate_share(&self, arm: Option<&str>) -> Option<AggregateShare<F>> {
+        self.arms
+            .get(&arm.map(String::from))
+            .map(|(_, accumulator)| AggregateShare::from(accumulator.clone()))
+    }
+
+    /// Releases the aggregate shares of all arms and empties the accumulator.
+    pub fn finish(&mut self) -> Vec<ArmBatch<F>> {
+        std::mem::take(&mut self.arms)
+            .into_iter()
+            .map(|(arm, (report_count, accumulator))| ArmBatch {
+                arm,
+                report_count,
+                aggregate_share: AggregateShare::from(accumulator),
+            })
+            .collect()
+    }
+}
+
+/// An accumulator that sums field elements in `u64` lanes and defers the modular reduction.
+///
+/// Each lane holds the integer sum of the elements added to it, in the field's internal
+/// representation, so adding an element costs a single integer addition. Since every element is
+/// less than the modulus `p`, a lane that starts out reduced can take `u64::MAX / (p - 1) - 1`
+/// more additions before it might overflow; for [`FieldPrio2`](crate::field::FieldPrio2) this is
+/// about four billion. The accumulator counts the additions, so that it can reduce every lane
+/// before the limit is reached, and otherwise reduces only when the sums are read or merged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LaneAccumulator<F> {
+    lanes: Vec<u64>,
+    remaining: u64,
+    phantom: PhantomData<F>,
+}
+
+impl<F: SmallFieldElement> LaneAccumulator<F> {
+    /// The number of additions a reduced lane can take.
+    const MAX_ADDITIONS: u64 = u64::MAX / (F::LANE_MODULUS - 1) - 1;
+
+    /// Creates an accumulator of `len` zeros.
+    pub fn new(len: usize) -> Self {
+        Self {
+            lanes: vec![0; len],
+            remaining: Self::MAX_ADDITIONS,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the number of lanes.
+    pub fn len(&self) -> usize {
+        self.lanes.len()
+    }
+
+    /// Returns true if there are no lanes.
+    pub fn is_empty(&self) -> bool {
+        self.lanes.is_empty()
+    }
+
+    /// Returns the number of additions that can be made before the lanes must be reduced.
+    pub fn remaining_additions(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Adds `output_share` into the lanes. Returns an error if it has the wrong length.
+    pub fn accumulate(&mut self, output_share: &OutputShare<F>) -> Result<(), VdafError> {
+        let share = output_share.as_ref();
+        if share.len() != self.lanes.len() {
+            return Err(VdafError::Uncategorized(format!(
+                "share has length {}, expected {}",
+                share.len(),
+                self.lanes.len()
+            )));
+        }
+        self.reserve_addition();
+        for (lane, x) in self.lanes.iter_mut().zip(share) {
+            *lane += x.to_lane();
+        }
+        Ok(())
+    }
+
+    /// Adds the sums of `other` into the lanes, as one more addition. Returns an error if the
+    /// accumulators have different lengths.
+    pub fn merge(&mut self, other: &Self) -> Result<(), VdafError> {
+        if other.lanes.len() != self.lanes.len() {
+            return Err(VdafError::Uncategorized(format!(
+                "accumulator has length {}, expected {}",
+                other.lanes.len(),
+                self.lanes.len()
+            )));
+        }
+        self.reserve_addition();
+        for (lane, x) in self.lanes.iter_mut().zip(&other.lanes) {
+            *lane += x % F::LANE_MODULUS;
+        }
+        Ok(())
+    }
+
+    /// Reduces every lane modulo the field's prime.
+    pub fn reduce(&mut self) {
+        for lane in self.lanes.iter_mut() {
+            *lane %= F::LANE_MODULUS;
+        }
+        self.remaining = Self::MAX_ADDITIONS;
+    }
+
+    /// Returns the sums as an aggregate share.
+    pub fn to_aggregate_share(&self) -> AggregateShare<F> {
+        AggregateShare::from(
+            self.lanes
+                .iter()
+                .map(|lane| F::from_lane(lane % F::LANE_MODULUS))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn reserve_addition(&mut self) {
+        if self.remaining == 0 {
+            self.reduce();
+        }
+        self.remaining -= 1;
+    }
+}
+
+/// An accumulator whose entries are split into shards that are updated in parallel.
+///
+/// The shards are contiguous ranges of entries, of equal length except possibly the last. Batches
+/// of output shares passed to [`ShardedAccumulator::accumulate_batch`] are summed with one scoped
+/// thread per shard, each adding its range of every share in order. Merging two accumulators
+/// merges them shard by shard. The shard boundaries depend only on the length and the number of
+/// shards, never on scheduling, so the same inputs always produce the same accumulator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardedAccumulator<F> {
+    len: usize,
+    shards: Vec<Vec<F>>,
+    report_count: u64,
+}
+
+impl<F: FieldElement + Send + Sync> ShardedAccumulator<F> {
+    /// Creates an accumulator of `len` zeros split into `num_shards` shards. There are never more
+    /// shards than entries, except that an empty accumulator has one empty shard. Returns an
+    /// error if `num_shards` is zero.
+    pub fn new(len: usize, num_shards: usize) -> Result<Self, VdafError> {
+        if num_shards == 0 {
+            return Err(VdafError::Uncategorized(
+                "number of shards must be positive".into(),
+            ));
+        }
+        let num_shards = num_shards.min(len.max(1));
+        let shard_len = (len + num_shards - 1) / num_shards;
+        let mut shards = Vec::with_capacity(num_shards);
+        let mut start = 0;
+        while shards.is_empty() || start < len {
+            let end = len.min(start + shard_len);
+            shards.push(vec![F::zero(); end - start]);
+            start = end;
+        }
+        Ok(Self {
+            len,
+            shards,
+            report_count: 0,
+        })
+    }
+
+    /// Returns the number of entries in the accumulator.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the accumulator has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the ranges of entries held by each shard, in order.
+    pub fn shard_ranges(&self) -> Vec<Range<usize>> {
+        let mut start = 0;
+        self.shards
+            .iter()
+            .map(|shard| {
+                let range = start..start + shard.len();
+                start = range.end;
+                range
+            })
+            .collect()
+    }
+
+    /// Returns the number of output shares added with [`ShardedAccumulator::accumulate_batch`],
+    /// including those of merged accumulators.
+    pub fn report_count(&self) -> u64 {
+        self.report_count
+    }
+
+    /// Adds a batch of output shares, summing each shard on its own thread. Returns an error,
+    /// without changing the accumulator, if any share has the wrong length.
+    pub fn accumulate_batch(&mut self, output_shares: &[OutputShare<F>]) -> Result<(), VdafError> {
+        if let Some(share) = output_shares
+            .iter()
+            .find(|share| share.as_ref().len() != self.len)
+        {
+            return Err(VdafError::Uncategorized(format!(
+                "share has length {}, expected {}",
+                share.as_ref().len(),
+                self.len
+            )));
+        }
+        let ranges = self.shard_ranges();
+        thread::scope(|scope| {
+            for (shard, range) in self.shards.iter_mut().zip(ranges) {
+                scope.spawn(move || {
+                    for output_share in output_shares {
+                        for (x, y) in shard.iter_mut().zip(&output_share.as_ref()[range.clone()]) {
+                            *x += *y;
+                        }
+                    }
+                });
+            }
+        });
+        self.report_count += output_shares.len() as u64;
+        Ok(())
+    }
+
+    /// Adds `other` into the accumulator, shard by shard. Returns an error if the accumulators
+    /// have different lengths or numbers of shards.
+    pub fn merge(&mut self, other: &Self) -> Result<(), VdafError> {
+        if other.len != self.len || other.shards.len() != self.shards.len() {
+            return Err(VdafError::Uncategorized(format!(
+                "accumulator has length {} in {} shards, expected {} in {}",
+                other.len,
+                other.shards.len(),
+                self.len,
+                self.shards.len()
+            )));
+        }
+        for (shard, other) in self.shards.iter_mut().zip(&other.shards) {
+            for (x, y) in shard.iter_mut().zip(other) {
+                *x += *y;
+            }
+        }
+        self.report_count += other.report_count;
+        Ok(())
+    }
+
+    /// Returns the sums as an aggregate share, with the shards concatenated in order.
+    pub fn to_aggregate_share(&self) -> AggregateShare<F> {
+        AggregateShare::from(
+            self.shards
+                .iter()
+                .flat_map(|shard| shard.iter().copied())
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+/// A comparison of the two implementations of a [`DualAccumulator`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShadowReport<const NONCE_SIZE: usize> {
+    /// The number of reports that were prepared with both implementations.
+    pub sampled: usize,
+
+    /// The nonces of the sampled reports for which the implementations disagreed: one accepted
+    /// the report and the other rejected it, or they produced different prepare shares or output
+    /// shares.
+    pub mismatches: Vec<[u8; NONCE_SIZE]>,
+
+    /// Whether the two implementations produced the same aggregate share over the sampled reports
+    /// they both finished.
+    pub aggregates_match: bool,
+}
+
+/// Prepares reports with a primary implementation of a VDAF and, for a sample of them, also with a
+/// shadow implementation, and compares the two.
+///
+/// The shadow is the same VDAF configured with an alternate code path, for instance
+/// [`Prio2`](crate::vdaf::prio2::Prio2) with the
+/// [`NaiveFftBackend`](crate::fft::NaiveFftBackend) in place of an optimized backend. Only the
+/// primary's prepare shares and output shares are returned to the caller, so the shadow never
+/// affects the outcome of preparation; it only records disagreements, which point to a
+/// regression in one of the implementations. Reports are sampled by a
+/// [`SamplingPolicy`], so all Aggregators sample the same reports.
+///
+/// Besides the [aggregate share](DualAccumulator::aggregate_share) of all finished reports, the
+/// accumulator keeps one aggregate share per implementation over the sampled reports, compared in
+/// the [`ShadowReport`].
+#[derive(Debug)]
+pub struct DualAccumulator<V, const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize>
+where
+    V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+{
+    primary: V,
+    shadow: V,
+    policy: SamplingPolicy,
+    pending: HashMap<[u8; NONCE_SIZE], V::PrepareState>,
+    aggregate: Option<V::AggregateShare>,
+    sampled_aggregate: Option<V::AggregateShare>,
+    shadow_aggregate: Option<V::AggregateShare>,
+    sampled: usize,
+    mismatches: Vec<[u8; NONCE_SIZE]>,
+}
+
+impl<V, const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize>
+    DualAccumulator<V, VERIFY_KEY_SIZE, NONCE_SIZE>
+where
+    V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+{
+    /// Creates an accumulator that prepares every report with `primary`, and around `sample_rate`
+    /// of them with `shadow` as well. Returns an error unless `sample_rate` is between 0 and 1.
+    pub fn new(primary: V, shadow: V, sample_rate: f64) -> Result<Self, VdafError> {
+        Ok(Self {
+            primary,
+            shadow,
+            policy: SamplingPolicy::rate(sample_rate)?.with_salt(b"shadow"),
+            pending: HashMap::new(),
+            aggregate: None,
+            sampled_aggregate: None,
+            shadow_aggregate: None,
+            sampled: 0,
+            mismatches: Vec::new(),
+        })
+    }
+
+    /// Returns true if the report with `nonce` is prepared with the shadow implementation too.
+    pub fn is_sampled(&self, nonce: &[u8; NONCE_SIZE]) -> bool {
+        self.policy.is_sampled(nonce)
+    }
+
+    /// Starts preparing a report, as in [`Aggregator::prepare_init`] with the primary
+    /// implementation. If the report is sampled, it is also started with the shadow, whose prepare
+    /// share is compared with the primary's.
+    pub fn prepare_init(
+        &mut self,
+        verify_key: &[u8; VERIFY_KEY_SIZE],
+        agg_id: usize,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        public_share: &V::PublicShare,
+        input_share: &V::InputShare,
+    ) -> Result<(V::PrepareState, V::PrepareShare), VdafError> {
+        let result = self.primary.prepare_init(
+            verify_key,
+            agg_id,
+            agg_param,
+            nonce,
+            public_share,
+            input_share,
+        );
+        if self.is_sampled(nonce) {
+            self.sampled += 1;
+            let shadow = self.shadow.prepare_init(
+                verify_key,
+                agg_id,
+                agg_param,
+                nonce,
+                public_share,
+                input_share,
+            );
+            match (&result, shadow) {
+                (Ok((_, share)), Ok((state, shadow_share))) => {
+                    if encodings_match(share, &shadow_share) {
+                        self.pending.insert(*nonce, state);
+                    } else {
+                        self.mismatches.push(*nonce);
+                    }
+                }
+                (Err(_), Err(_)) => (),
+                _ => self.mismatches.push(*nonce),
+            }
+        }
+        result
+    }
+
+    /// Continues preparing the report with `nonce`, as in [`Aggregator::prepare_next`] with the
+    /// primary implementation, and with the shadow if the report is sampled and the
+    /// implementations have agreed so far. The output share of a finished report is added to the
+    /// aggregate share.
+    pub fn prepare_next(
+        &mut self,
+        nonce: &[u8; NONCE_SIZE],
+        state: V::PrepareState,
+        input: V::PrepareMessage,
+    ) -> Result<PrepareTransition<V, VERIFY_KEY_SIZE, NONCE_SIZE>, VdafError> {
+        let shadow = self
+            .pending
+            .remove(nonce)
+            .map(|shadow_state| self.shadow.prepare_next(shadow_state, input.clone()));
+        let result = self.primary.prepare_next(state, input);
+
+        match (&result, shadow) {
+            (_, None) => (),
+            (Err(_), Some(Err(_))) => (),
+            (
+                Ok(PrepareTransition::Continue(_, share)),
+                Some(Ok(PrepareTransition::Continue(shadow_state, shadow_share))),
+            ) if encodings_match(share, &shadow_share) => {
+                self.pending.insert(*nonce, shadow_state);
+            }
+            (
+                Ok(PrepareTransition::Finish(output_share)),
+                Some(Ok(PrepareTransition::Finish(shadow_output_share))),
+            ) if encodings_match(output_share, &shadow_output_share) => {
+                accumulate_into(&mut self.sampled_aggregate, output_share)?;
+                accumulate_into(&mut self.shadow_aggregate, &shadow_output_share)?;
+            }
+            _ => self.mismatches.push(*nonce),
+        }
+
+        if let Ok(PrepareTransition::Finish(output_share)) = &result {
+            accumulate_into(&mut self.aggregate, output_share)?;
+        }
+        result
+    }
+
+    /// Returns the aggregate share of the output shares of all finished reports, as computed by
+    /// the primary implementation, or `None` if no report has finished.
+    pub fn aggregate_share(&self) -> Option<&V::AggregateShare> {
+        self.aggregate.as_ref()
+    }
+
+    /// Compares the two implementations over the reports sampled so far.
+    pub fn report(&self) -> Result<ShadowReport<NONCE_SIZE>, VdafError> {
+        let aggregates_match = match (&self.sampled_aggregate, &self.shadow_aggregate) {
+            (Some(sampled), Some(shadow)) => sampled.get_encoded()? == shadow.get_encoded()?,
+            (None, None) => true,
+            _ => false,
+        };
+        Ok(ShadowReport {
+            sampled: self.sampled,
+            mismatches: self.mismatches.clone(),
+            aggregates_match,
+        })
+    }
+}
+
+fn encodings_match<T: Encode>(a: &T, b: &T) -> bool {
+    matches!((a.get_encoded(), b.get_encoded()), (Ok(a), Ok(b)) if a == b)
+}
+
+fn accumulate_into<A: Aggregatable>(
+    aggregate: &mut Option<A>,
+    output_share: &A::OutputShare,
+) -> Result<(), VdafError>
+where
+    A::OutputShare: Clone,
+{
+    match aggregate {
+        Some(aggregate) => aggregate.accumulate(output_share),
+        None => {
+            *aggregate = Some(A::from(output_share.clone()));
+            Ok(())
+        }
+    }
+}
+
+fn byte_len<F: FieldElement>(len: usize) -> Option<usize> {
+    len.checked_mul(F::ENCODED_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{
+        lanes::Sealed, random_vector, Field128, Field64, FieldElementWithInteger, FieldPrio2,
+    };
+    use assert_matches::assert_matches;
+    use std::{fs, path::PathBuf};
+
+    /// A file in the temporary directory that is removed when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!(
+                "prio-accumulator-{}-{:016x}",
+                std::process::id(),
+                rand::random::<u64>()
+            )))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn file_accumulator() {
+        let path = TempPath::new();
+        let len = 1000;
+
+        for chunk_len in [1, 7, 64, 1000, 5000] {
+            let mut want = AggregateShare::from(vec![Field128::zero(); len]);
+            let mut acc = FileAccumulator::<Field128>::create(&path.0, len, chunk_len).unwrap();
+            assert_eq!(acc.len(), len);
+            assert_eq!(acc.to_aggregate_share().unwrap(), want);
+
+            for _ in 0..5 {
+                let output_share = OutputShare::from(random_vector::<Field128>(len).unwrap());
+                acc.accumulate(&output_share).unwrap();
+                Aggregatable::accumulate(&mut want, &output_share).unwrap();
+            }
+            let agg_share = AggregateShare::from(random_vector::<Field128>(len).unwrap());
+            acc.merge(&agg_share).unwrap();
+            Aggregatable::merge(&mut want, &agg_share).unwrap();
+            acc.flush().unwrap();
+            assert_eq!(acc.to_aggregate_share().unwrap(), want);
+
+            // Reopening the file yields the same contents.
+            drop(acc);
+            let mut acc = FileAccumulator::<Field128>::open(&path.0, len, chunk_len).unwrap();
+            assert_eq!(acc.to_aggregate_share().unwrap(), want);
+        }
+    }
+
+    #[test]
+    fn file_accumulator_partial_update() {
+        let path = TempPath::new();
+        let mut acc = FileAccumulator::<Field64>::create(&path.0, 10, 3).unwrap();
+
+        acc.accumulate_at(4, &[Field64::from(1), Field64::from(2), Field64::from(3)])
+            .unwrap();
+        acc.accumulate_at(5, &[Field64::from(10)]).unwrap();
+
+        let mut out = [Field64::zero(); 5];
+        acc.read_at(3, &mut out).unwrap();
+        assert_eq!(out, [0, 1, 12, 3, 0].map(Field64::from),);
+
+        assert_matches!(
+            acc.accumulate_at(8, &[Field64::one(); 3]),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert_matches!(
+            acc.read_at(usize::MAX, &mut out),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert_matches!(
+            acc.accumulate(&OutputShare::from(vec![Field64::one(); 9])),
+            Err(VdafError::Uncategorized(_))
+        );
+    }
+
+    #[test]
+    fn file_accumulator_corrupted() {
+        let path = TempPath::new();
+        let mut acc = FileAccumulator::<Field64>::create(&path.0, 10, 4).unwrap();
+        acc.accumulate_at(2, &[Field64::from(7)]).unwrap();
+        acc.flush().unwrap();
+        drop(acc);
+        FileAccumulator::<Field64>::open(&path.0, 10, 3).unwrap();
+
+        let mut contents = fs::read(&path.0).unwrap();
+        contents[2 * Field64::ENCODED_SIZE] ^= 1;
+        fs::write(&path.0, &contents).unwrap();
+        assert_matches!(
+            FileAccumulator::<Field64>::open(&path.0, 10, 4),
+            Err(VdafError::CorruptedState(_))
+        );
+
+        // Changes that were never flushed fail the check too.
+        let mut acc = FileAccumulator::<Field64>::create(&path.0, 10, 4).unwrap();
+        acc.accumulate_at(0, &[Field64::one()]).unwrap();
+        drop(acc);
+        assert_matches!(
+            FileAccumulator::<Field64>::open(&path.0, 10, 4),
+            Err(VdafError::CorruptedState(_))
+        );
+    }
+
+    #[test]
+    fn file_accumulator_open_wrong_length() {
+        let path = TempPath::new();
+        FileAccumulator::<Field64>::create(&path.0, 10, 4).unwrap();
+        assert_matches!(
+            FileAccumulator::<Field64>::open(&path.0, 11, 4),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert_matches!(
+            FileAccumulator::<Field64>::open(&path.0, 10, 0),
+            Err(VdafError::Uncategorized(_))
+        );
+    }
+
+    #[test]
+    fn lane_accumulator() {
+        let len = 100;
+        let mut acc = LaneAccumulator::<FieldPrio2>::new(len);
+        assert_eq!(acc.len(), len);
+        assert_eq!(
+            acc.remaining_additions(),
+            u64::MAX / (u64::from(FieldPrio2::modulus()) - 1) - 1
+        );
+        assert!(acc.remaining_additions() > 1 << 32);
+        let mut want = AggregateShare::from(vec![FieldPrio2::zero(); len]);
+        let mut other = acc.clone();
+        for i in 0..10 {
+            // Force a reduction partway through.
+            if i == 5 {
+                acc.remaining = 0;
+            }
+            let output_share = OutputShare::from(vec![-FieldPrio2::one(); len]);
+            acc.accumulate(&output_share).unwrap();
+            other
+                .accumulate(&OutputShare::from(random_vector(len).unwrap()))
+                .unwrap();
+            Aggregatable::accumulate(&mut want, &output_share).unwrap();
+        }
+        assert_eq!(
+            acc.remaining_additions(),
+            LaneAccumulator::<FieldPrio2>::MAX_ADDITIONS - 5
+        );
+        assert_eq!(acc.to_aggregate_share(), want);
+
+        acc.merge(&other).unwrap();
+        Aggregatable::merge(&mut want, &other.to_aggregate_share()).unwrap();
+        assert_eq!(acc.to_aggregate_share(), want);
+        acc.reduce();
+        assert_eq!(acc.to_aggregate_share(), want);
+        assert!(acc
+            .lanes
+            .iter()
+            .all(|lane| *lane < FieldPrio2::LANE_MODULUS));
+
+        assert_matches!(
+            acc.accumulate(&OutputShare::from(vec![FieldPrio2::one(); len + 1])),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert!(acc.merge(&LaneAccumulator::new(1)).is_err());
+    }
+
+    #[test]
+    fn sharded_accumulator() {
+        let len = 10;
+        let mut acc = ShardedAccumulator::<Field64>::new(len, 4).unwrap();
+        assert_eq!(acc.len(), len);
+        assert_eq!(acc.shard_ranges(), [0..3, 3..6, 6..9, 9..10]);
+        assert_eq!(
+            ShardedAccumulator::<Field64>::new(2, 4)
+                .unwrap()
+                .shard_ranges(),
+            [0..1, 1..2]
+        );
+        let empty = ShardedAccumulator::<Field64>::new(0, 4).unwrap();
+        assert_eq!(empty.shard_ranges().len(), 1);
+        assert!(empty.shard_ranges()[0].is_empty());
+
+        let output_shares: Vec<_> = (0..20)
+            .map(|_| OutputShare::from(random_vector::<Field64>(len).unwrap()))
+            .collect();
+        let mut want = AggregateShare::from(vec![Field64::zero(); len]);
+        for output_share in &output_shares {
+            Aggregatable::accumulate(&mut want, output_share).unwrap();
+        }
+        acc.accumulate_batch(&output_shares[..15]).unwrap();
+        let mut other = ShardedAccumulator::new(len, 4).unwrap();
+        other.accumulate_batch(&output_shares[15..]).unwrap();
+        acc.merge(&other).unwrap();
+        assert_eq!(acc.report_count(), 20);
+        assert_eq!(acc.to_aggregate_share(), want);
+
+        // The result is the same however the shares are batched.
+        let mut again = ShardedAccumulator::new(len, 4).unwrap();
+        for batch in output_shares.chunks(3) {
+            again.accumulate_batch(batch).unwrap();
+        }
+        assert_eq!(again, acc);
+
+        assert_matches!(
+            acc.accumulate_batch(&[
+                OutputShare::from(vec![Field64::one(); len]),
+                OutputShare::from(vec![Field64::one(); len + 1]),
+            ]),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert_eq!(acc.to_aggregate_share(), want);
+        assert!(acc
+            .merge(&ShardedAccumulator::new(len, 3).unwrap())
+            .is_err());
+        assert!(ShardedAccumulator::<Field64>::new(len, 0).is_err());
+    }
+
+    #[test]
+    fn windowed_accumulator() {
+        let finalized = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = finalized.clone();
+        let mut acc =
+            WindowedAccumulator::new(2, 3600, 600, |batch| sink.borrow_mut().push(batch)).unwrap();
+        let share = |x: u64| OutputShare::from(vec![Field64::from(x), Field64::one()]);
+
+        acc.accumulate(0, &share(1)).unwrap();
+        acc.accumulate(3599, &share(2)).unwrap();
+        acc.accumulate(3600, &share(4)).unwrap();
+        acc.accumulate(7300, &share(8)).unwrap();
+        assert_eq!(acc.open_batches().collect::<Vec<_>>(), [0, 3600, 7200]);
+
+        // The first window closes at 3600, but late reports are accepted until 4200.
+        acc.advance(4199);
+        assert!(finalized.borrow().is_empty());
+        acc.accumulate(10, &share(16)).unwrap();
+        acc.advance(4200);
+        {
+            let finalized = finalized.borrow();
+            assert_eq!(finalized.len(), 1);
+            assert_eq!(finalized[0].start, 0);
+            assert_eq!(finalized[0].duration, 3600);
+            assert_eq!(finalized[0].report_count, 3);
+            assert_eq!(
+                finalized[0].aggregate_share,
+                AggregateShare::from(vec![Field64::from(19), Field64::from(3)])
+            );
+        }
+        assert_matches!(
+            acc.accumulate(20, &share(1)),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert_matches!(
+            acc.accumulate(3600, &OutputShare::from(vec![Field64::one()])),
+            Err(VdafError::Uncategorized(_))
+        );
+
+        // Time doesn't move backwards.
+        acc.advance(0);
+        assert_matches!(
+            acc.accumulate(20, &share(1)),
+            Err(VdafError::Uncategorized(_))
+        );
+
+        acc.advance(u64::MAX);
+        assert_eq!(
+            finalized
+                .borrow()
+                .iter()
+                .map(|batch| (batch.start, batch.report_count))
+                .collect::<Vec<_>>(),
+            [(0, 3), (3600, 1), (7200, 1)]
+        );
+
+        let mut acc =
+            WindowedAccumulator::new(2, 60, 0, |batch| finalized.borrow_mut().push(batch)).unwrap();
+        acc.accumulate(0, &share(1)).unwrap();
+        acc.accumulate(100, &share(2)).unwrap();
+        acc.finalize_all();
+        assert_eq!(acc.open_batches().count(), 0);
+        assert_eq!(finalized.borrow().len(), 5);
+
+        // Windows ending at or before the last one finalized stay closed, though time hasn't
+        // moved.
+        for timestamp in [0, 30, 119] {
+            assert_matches!(
+                acc.accumulate(timestamp, &share(1)),
+                Err(VdafError::Uncategorized(_))
+            );
+        }
+        acc.accumulate(120, &share(4)).unwrap();
+        assert_eq!(acc.open_batches().collect::<Vec<_>>(), [120]);
+
+        assert!(WindowedAccumulator::<Field64, _>::new(2, 0, 0, |_| ()).is_err());
+    }
+
+    #[test]
+    fn interval_accumulator() {
+        let mut acc = IntervalAccumulator::new(2);
+        let share = |x: u64| OutputShare::from(vec![Field64::from(x), Field64::one()]);
+        let id = |i: u8| ReportId::from([i; 16]);
+
+        assert!(acc.accumulate(&id(1), &share(1)).unwrap());
+        assert!(acc.accumulate(&id(2), &share(2)).unwrap());
+        assert!(!acc.accumulate(&id(1), &share(4)).unwrap());
+        assert!(acc
+            .accumulate(&id(3), &OutputShare::from(vec![Field64::one()]))
+            .is_err());
+        assert_eq!(acc.report_count(), 2);
+
+        let batch = acc.finish_batch();
+        assert_eq!(batch.interval, 0);
+        assert_eq!(batch.report_count, 2);
+        assert_eq!(
+            batch.aggregate_share,
+            AggregateShare::from(vec![Field64::from(3), Field64::from(2)])
+        );
+        assert_eq!(acc.interval(), 1);
+        assert_eq!(acc.report_count(), 0);
+        assert!(acc.accumulator.iter().all(|x| *x == Field64::zero()));
+
+        // Reports from the previous interval are still recognized as replays.
+        assert!(!acc.accumulate(&id(2), &share(8)).unwrap());
+        assert!(acc.accumulate(&id(3), &share(8)).unwrap());
+        let batch = acc.finish_batch();
+        assert_eq!(batch.interval, 1);
+        assert_eq!(batch.report_count, 1);
+        assert_eq!(
+            batch.aggregate_share,
+            AggregateShare::from(vec![Field64::from(8), Field64::one()])
+        );
+
+        // An empty interval releases zeros. The window only reaches back one interval, so the
+        // reports of the earlier ones are forgotten.
+        let batch = acc.finish_batch();
+        assert_eq!(batch.report_count, 0);
+        assert_eq!(
+            batch.aggregate_share,
+            AggregateShare::from(vec![Field64::zero(); 2])
+        );
+        assert!(acc.accumulate(&id(1), &share(1)).unwrap());
+        assert!(acc.accumulate(&id(3), &share(1)).unwrap());
+    }
+
+    #[test]
+    fn arm_accumulator() {
+        let mut acc = ArmAccumulator::new(2);
+        let share = |x: u64| OutputShare::from(vec![Field64::from(x), Field64::one()]);
+        acc.accumulate(Some("treatment"), &share(1)).unwrap();
+        acc.accumulate(Some("control"), &share(2)).unwrap();
+        acc.accumulate(Some("treatment"), &share(4)).unwrap();
+        acc.accumulate(None, &share(8)).unwrap();
+        assert_eq!(
+            acc.arms().collect::<Vec<_>>(),
+            [None, Some("control"), Some("treatment")]
+        );
+        assert_eq!(acc.report_count(Some("treatment")), 2);
+        assert_eq!(acc.report_count(Some("other")), 0);
+        assert_eq!(
+            acc.aggregate_share(Some("treatment")).unwrap(),
+            AggregateShare::from(vec![Field64::from(5), Field64::from(2)])
+        );
+        assert!(acc.aggregate_share(Some("other")).is_none());
+        assert_matches!(
+            acc.accumulate(Some("control"), &OutputShare::from(vec![Field64::one()])),
+            Err(VdafError::Uncategorized(_))
+        );
+
+        let batches = acc.finish();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[1].arm.as_deref(), Some("control"));
+        assert_eq!(batches[1].report_count, 1);
+        assert_eq!(
+            batches[1].aggregate_share,
+            AggregateShare::from(vec![Field64::from(2), Field64::one()])
+        );
+        assert_eq!(acc.arms().count(), 0);
+    }
+
+    #[test]
+    fn dual_accumulator() {
+        use crate::{
+            fft::{CpuFftBackend, FftBackend, FftError, NaiveFftBackend},
+            vdaf::{prio2::Prio2, Client, Collector},
+        };
+        use std::sync::Arc;
+
+        /// A backend with a bug in transforms of size 16.
+        #[derive(Debug)]
+        struct BuggyBackend;
+
+        impl FftBackend<FieldPrio2> for BuggyBackend {
+            fn fft(
+                &self,
+                outp: &mut [FieldPrio2],
+                inp: &[FieldPrio2],
+                size: usize,
+            ) -> Result<(), FftError> {
+                CpuFftBackend.fft(outp, inp, size)?;
+                if size == 16 {
+                    outp[3] += FieldPrio2::one();
+                }
+                Ok(())
+            }
+        }
+
+        let run = |primary: Prio2, shadow: Prio2, sample_rate| {
+            let mut accs: Vec<_> = (0..2)
+                .map(|_| {
+                    DualAccumulator::new(primary.clone(), shadow.clone(), sample_rate).unwrap()
+                })
+                .collect();
+            let verify_key = [7; 32];
+            for i in 0..20u8 {
+                let nonce = [i; 16];
+                let measurement: Vec<u32> = (0..6).map(|j| u32::from((i + j) % 2)).collect();
+                let (public_share, input_shares) = primary.shard(&measurement, &nonce).unwrap();
+                let (states, shares): (Vec<_>, Vec<_>) = accs
+                    .iter_mut()
+                    .zip(&input_shares)
+                    .enumerate()
+                    .map(|(agg_id, (acc, input_share))| {
+                        acc.prepare_init(
+                            &verify_key,
+                            agg_id,
+                            &(),
+                            &nonce,
+                            &public_share,
+                            input_share,
+                        )
+                        .unwrap()
+                    })
+                    .unzip();
+                // Reports that the primary rejects are dropped.
+                let Ok(message) = primary.prepare_shares_to_prepare_message(&(), shares) else {
+                    continue;
+                };
+                for (acc, state) in accs.iter_mut().zip(states) {
+                    acc.prepare_next(&nonce, state, message).unwrap();
+                }
+            }
+            if accs[0].aggregate_share().is_some() {
+                let aggregate = primary
+                    .unshard(
+                        &(),
+                        accs.iter()
+                            .map(|acc| acc.aggregate_share().unwrap().clone()),
+                        20,
+                    )
+                    .unwrap();
+                assert_eq!(aggregate, [10; 6]);
+            }
+            accs[0].report().unwrap()
+        };
+
+        let cpu = Prio2::new(6).unwrap();
+        let naive = cpu.clone().with_fft_backend(Arc::new(NaiveFftBackend));
+        let report = run(cpu.clone(), naive.clone(), 1.0);
+        assert_eq!(report.sampled, 20);
+        assert!(report.mismatches.is_empty());
+        assert!(report.aggregates_match);
+
+        let report = run(cpu.clone(), naive.clone(), 0.5);
+        assert!(report.sampled > 0 && report.sampled < 20);
+        assert!(report.mismatches.is_empty());
+
+        // A regression in the primary's code path, which makes it reject valid reports, is
+        // caught by the shadow.
+        let buggy = cpu.clone().with_fft_backend(Arc::new(BuggyBackend));
+        let report = run(buggy.clone(), naive.clone(), 1.0);
+        assert_eq!(report.mismatches.len(), 20);
+        assert_eq!(report.mismatches[3], [3; 16]);
+
+        // So is a regression in the shadow's.
+        let report = run(cpu.clone(), buggy, 1.0);
+        assert_eq!(report.mismatches.len(), 20);
+
+        let report = run(cpu.clone(), cpu.clone(), 0.0);
+        assert_eq!(report.sampled, 0);
+        assert!(report.aggregates_match);
+        assert!(DualAccumulator::new(cpu.clone(), cpu, 1.5).is_err());
+    }
+}
@@ -0,0 +1,650 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Storage for aggregation state that outlives a single process.
+//!
+//! An [`AccumulatorStore`] holds, for each batch, the sum of the output shares accumulated into it
+//! and the number of reports, along with the set of report IDs it has seen, so that replayed
+//! reports are not counted twice. [`MemoryStore`] keeps this state in memory, and
+//! [`DirectoryStore`] keeps it in files in a directory, so that it survives a restart. Other
+//! backends, such as an embedded key-value store or a database shared by a fleet of Aggregators,
+//! can be provided by implementing the trait.
+//!
+//! Every record [`DirectoryStore`] writes is followed by a checksum, which is checked when the
+//! record is read back, so that a truncated or modified file is reported as
+//! [`VdafError::CorruptedState`] rather than aggregated into. Opened with a key, the store uses a
+//! MAC instead, which also detects deliberate tampering by anyone who does not hold the key.
+//!
+//! Before collecting a batch, the Aggregators should confirm that they accumulated the same
+//! reports: a report dropped by one of them yields an aggregate share that silently corrupts the
+//! result. Each Aggregator computes a [`ReportSetDigest`] of the report IDs in the batch and sends
+//! it to the other, and [`AccumulatorStore::remove_batch_checked`] removes the batch only if the
+//! digests match.
+
+use crate::{
+    codec::{decode_u32_items, encode_u32_items, encode_u8_items, CodecError, Decode, Encode},
+    field::FieldElement,
+    vdaf::{
+        accumulator::{check_checksum, checksum_hasher, CHECKSUM_LEN},
+        Aggregatable, AggregateShare, OutputShare, VdafError,
+    },
+};
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Debug},
+    fs::{self, File, OpenOptions},
+    io::{Cursor, ErrorKind, Read, Write},
+    path::PathBuf,
+};
+use subtle::ConstantTimeEq;
+
+/// Domain separation tag for [`ReportSetDigest`].
+const DIGEST_DST: &[u8] = b"prio report set digest";
+
+/// The state of one batch in an [`AccumulatorStore`].
+#[derive(Clone, Debug)]
+pub struct StoredBatch<F> {
+    /// The number of reports accumulated into the batch.
+    pub report_count: u64,
+
+    /// The sum of the output shares of those reports.
+    pub aggregate_share: AggregateShare<F>,
+}
+
+/// Storage for per-batch aggregate shares and the set of reports already accumulated.
+///
+/// Batches and reports are identified by opaque byte strings chosen by the application. A report
+/// ID is remembered after its batch is removed, so a report cannot be replayed into a later batch.
+pub trait AccumulatorStore<F: FieldElement> {
+    /// Adds the output share of report `report_id` to batch `batch_id`, creating the batch if
+    /// needed. Returns `false`, and leaves the store unchanged, if the report was accumulated
+    /// before.
+    fn accumulate(
+        &mut self,
+        batch_id: &[u8],
+        report_id: &[u8],
+        output_share: &OutputShare<F>,
+    ) -> Result<bool, VdafError>;
+
+    /// Returns the state of batch `batch_id`, or `None` if nothing has been accumulated into it.
+    fn batch(&self, batch_id: &[u8]) -> Result<Option<StoredBatch<F>>, VdafError>;
+
+    /// Removes batch `batch_id`, e.g. once it has been collected, and returns its state.
+    fn remove_batch(&mut self, batch_id: &[u8]) -> Result<Option<StoredBatch<F>>, VdafError>;
+
+    /// Removes batch `batch_id` like [`AccumulatorStore::remove_batch`], but only if the digest
+    /// `peer` received from the other Aggregator matches `local`, the digest of the reports this
+    /// Aggregator accumulated into the batch, and `local` covers as many reports as the batch
+    /// holds. Otherwise returns an error and leaves the batch in place, so that the Aggregators
+    /// can reconcile their report sets.
+    fn remove_batch_checked(
+        &mut self,
+        batch_id: &[u8],
+        local: &ReportSetDigest,
+        peer: &ReportSetDigest,
+    ) -> Result<Option<StoredBatch<F>>, VdafError> {
+        local.check(peer)?;
+        if let Some(batch) = self.batch(batch_id)? {
+            if batch.report_count != local.report_count() {
+                return Err(VdafError::Uncategorized(format!(
+                    "batch holds {} reports, but the digest covers {}",
+                    batch.report_count,
+                    local.report_count()
+                )));
+            }
+        }
+        self.remove_batch(batch_id)
+    }
+}
+
+/// A compact digest of a set of report IDs, exchanged by the Aggregators to check that they are
+/// about to aggregate the same reports.
+///
+/// The digest is the number of distinct reports along with the SHA3-256 hash of their IDs, sorted
+/// and length-prefixed, so it does not depend on the order in which the reports arrived.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReportSetDigest {
+    report_count: u64,
+    hash: [u8; 32],
+}
+
+impl ReportSetDigest {
+    /// Computes the digest of a set of report IDs. Duplicate IDs are counted once.
+    pub fn new<I>(report_ids: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let report_ids: Vec<I::Item> = report_ids.into_iter().collect();
+        let report_ids: BTreeSet<&[u8]> = report_ids.iter().map(AsRef::as_ref).collect();
+        let report_count = report_ids.len() as u64;
+        let mut hasher = Sha3_256::new();
+        hasher.update(DIGEST_DST);
+        hasher.update(report_count.to_be_bytes());
+        for report_id in report_ids {
+            hasher.update((report_id.len() as u64).to_be_bytes());
+            hasher.update(report_id);
+        }
+        Self {
+            report_count,
+            hash: hasher.finalize().into(),
+        }
+    }
+
+    /// Returns the number of distinct reports in the set.
+    pub fn report_count(&self) -> u64 {
+        self.report_count
+    }
+
+    /// Returns an error if `peer` is not the digest of the same set of reports.
+    pub fn check(&self, peer: &Self) -> Result<(), VdafError> {
+        if self.report_count != peer.report_count {
+            return Err(VdafError::Uncategorized(format!(
+                "report sets differ: {} reports, but the peer has {}",
+                self.report_count, peer.report_count
+            )));
+        }
+        if !bool::from(self.hash.ct_eq(&peer.hash)) {
+            return Err(VdafError::Uncategorized(
+                "report sets differ: digest mismatch".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Encode for ReportSetDigest {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.report_count.encode(bytes)?;
+        bytes.extend_from_slice(&self.hash);
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(8 + 32)
+    }
+}
+
+impl Decode for ReportSetDigest {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let report_count = u64::decode(bytes)?;
+        let mut hash = [0; 32];
+        bytes.read_exact(&mut hash)?;
+        Ok(Self { report_count, hash })
+    }
+}
+
+impl<F: FieldElement> StoredBatch<F> {
+    fn new(output_share: &OutputShare<F>) -> Self {
+        Self {
+            report_count: 1,
+            aggregate_share: AggregateShare::from(output_share.as_ref().to_vec()),
+        }
+    }
+
+    fn accumulate(&mut self, output_share: &OutputShare<F>) -> Result<(), VdafError> {
+        self.aggregate_share.accumulate(output_share)?;
+        self.report_count += 1;
+        Ok(())
+    }
+}
+
+/// An [`AccumulatorStore`] held in memory. Its state is lost when it is dropped.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore<F> {
+    batches: HashMap<Vec<u8>, StoredBatch<F>>,
+    reports: HashSet<Vec<u8>>,
+}
+
+impl<F> MemoryStore<F> {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self {
+            batches: HashMap::new(),
+            reports: HashSet::new(),
+        }
+    }
+}
+
+impl<F: FieldElement> AccumulatorStore<F> for MemoryStore<F> {
+    fn accumulate(
+        &mut self,
+        batch_id: &[u8],
+        report_id: &[u8],
+        output_share: &OutputShare<F>,
+    ) -> Result<bool, VdafError> {
+        if self.reports.contains(report_id) {
+            return Ok(false);
+        }
+        match self.batches.get_mut(batch_id) {
+            Some(batch) => batch.accumulate(output_share)?,
+            None => {
+                self.batches
+                    .insert(batch_id.to_vec(), StoredBatch::new(output_share));
+            }
+        }
+        self.reports.insert(report_id.to_vec());
+        Ok(true)
+    }
+
+    fn batch(&self, batch_id: &[u8]) -> Result<Option<StoredBatch<F>>, VdafError> {
+        Ok(self.batches.get(batch_id).cloned())
+    }
+
+    fn remove_batch(&mut self, batch_id: &[u8]) -> Result<Option<StoredBatch<F>>, VdafError> {
+        Ok(self.batches.remove(batch_id))
+    }
+}
+
+/// An [`AccumulatorStore`] kept in a directory.
+///
+/// Each batch is stored in its own file, named after the hex encoding of the batch ID, and
+/// replaced atomically (by writing a temporary file and renaming it) on every update. Report IDs
+/// are appended to a file named `reports`, which is read back when the store is opened. A report
+/// ID is recorded before its batch is updated, so a crash in between loses the report rather than
+/// counting it twice. A report ID whose record was cut short by a crash is discarded, and the
+/// record removed, when the store is opened.
+///
+/// Each report ID and each batch file is followed by a checksum, computed with the key if the
+/// store was opened with [`DirectoryStore::open_with_key`]. The checksum of a batch covers its
+/// ID, so a batch file renamed to another batch's name fails the check. A store must always be
+/// opened with the same key, or always without one.
+///
+/// Rewriting the batch file for every report suits batches of modest length. Only one process
+/// may use a directory at a time.
+pub struct DirectoryStore<F> {
+    dir: PathBuf,
+    key: Option<[u8; 32]>,
+    reports: HashSet<Vec<u8>>,
+    reports_file: File,
+    phantom: std::marker::PhantomData<F>,
+}
+
+impl<F: FieldElement> DirectoryStore<F> {
+    /// Opens the store in `dir`, creating the directory if it does not exist. Returns
+    /// [`VdafError::CorruptedState`] if a recorded report ID fails its checksum.
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self, VdafError> {
+        Self::open_inner(dir.into(), None)
+    }
+
+    /// Opens the store in `dir` like [`DirectoryStore::open`], but protects its files with a MAC
+    /// under `key` rather than a checksum.
+    pub fn open_with_key<P: Into<PathBuf>>(dir: P, key: [u8; 32]) -> Result<Self, VdafError> {
+        Self::open_inner(dir.into(), Some(key))
+    }
+
+    fn open_inner(dir: PathBuf, key: Option<[u8; 32]>) -> Result<Self, VdafError> {
+        fs::create_dir_all(&dir)?;
+        let mut reports_file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join("reports"))?;
+
+        let mut encoded = Vec::new();
+        reports_file.read_to_end(&mut encoded)?;
+        let mut rest = encoded.as_slice();
+        let mut reports = HashSet::new();
+        while let Some((&len, record)) = rest.split_first() {
+            // A record that runs past the end of the file was being written when the process
+            // stopped. Its report was not counted, so the record is discarded.
+            if record.len() < usize::from(len) + CHECKSUM_LEN {
+                reports_file.set_len((encoded.len() - rest.len()) as u64)?;
+                reports_file.sync_data()?;
+                break;
+            }
+            let (report_id, record) = record.split_at(usize::from(len));
+            let (stored, record) = record.split_at(CHECKSUM_LEN);
+            let mut hasher = checksum_hasher(key.as_ref(), b"report");
+            hasher.update(report_id);
+            check_checksum(hasher, stored, "reports file")?;
+            reports.insert(report_id.to_vec());
+            rest = record;
+        }
+
+        Ok(Self {
+            dir,
+            key,
+            reports,
+            reports_file,
+            phantom: std::marker::PhantomData,
+        })
+    }
+
+    fn batch_path(&self, batch_id: &[u8]) -> PathBuf {
+        let name: String = batch_id.iter().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(format!("batch-{name}"))
+    }
+
+    fn batch_hasher(&self, batch_id: &[u8]) -> Sha3_256 {
+        let mut hasher = checksum_hasher(self.key.as_ref(), b"batch");
+        hasher.update((batch_id.len() as u64).to_be_bytes());
+        hasher.update(batch_id);
+        hasher
+    }
+}
+
+impl<F> Debug for DirectoryStore<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DirectoryStore")
+            .field("dir", &self.dir)
+            .field("keyed", &self.key.is_some())
+            .field("reports", &self.reports.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FieldElement> AccumulatorStore<F> for DirectoryStore<F> {
+    fn accumulate(
+        &mut self,
+        batch_id: &[u8],
+        report_id: &[u8],
+        output_share: &OutputShare<F>,
+    ) -> Result<bool, VdafError> {
+        if self.reports.contains(report_id) {
+            return Ok(false);
+        }
+        let batch = match self.batch(batch_id)? {
+            Some(mut batch) => {
+                batch.accumulate(output_share)?;
+                batch
+            }
+            None => StoredBatch::new(output_share),
+        };
+
+        let mut encoded = Vec::new();
+        encode_u8_items(&mut encoded, &(), report_id)?;
+        let mut hasher = checksum_hasher(self.key.as_ref(), b"report");
+        hasher.update(report_id);
+        encoded.extend_from_slice(&hasher.finalize());
+        self.reports_file.write_all(&encoded)?;
+        self.reports_file.sync_data()?;
+        self.reports.insert(report_id.to_vec());
+
+        let mut encoded = Vec::new();
+        batch.report_count.encode(&mut encoded)?;
+        encode_u32_items(&mut encoded, &(), batch.aggregate_share.as_ref())?;
+        let mut hasher = self.batch_hasher(batch_id);
+        hasher.update(&encoded);
+        encoded.extend_from_slice(&hasher.finalize());
+        let path = self.batch_path(batch_id);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&encoded)?;
+        file.sync_data()?;
+        fs::rename(tmp, path)?;
+        Ok(true)
+    }
+
+    fn batch(&self, batch_id: &[u8]) -> Result<Option<StoredBatch<F>>, VdafError> {
+        let encoded = match fs::read(self.batch_path(batch_id)) {
+            Ok(encoded) => encoded,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(split) = encoded.len().checked_sub(CHECKSUM_LEN) else {
+            return Err(VdafError::CorruptedState("truncated batch file".into()));
+        };
+        let (encoded, stored) = encoded.split_at(split);
+        let mut hasher = self.batch_hasher(batch_id);
+        hasher.update(encoded);
+        check_checksum(hasher, stored, "batch file")?;
+        let mut bytes = std::io::Cursor::new(encoded);
+        let report_count = u64::decode(&mut bytes)?;
+        let aggregate_share = decode_u32_items::<_, F>(&(), &mut bytes)?;
+        Ok(Some(StoredBatch {
+            report_count,
+            aggregate_share: AggregateShare::from(aggregate_share),
+        }))
+    }
+
+    fn remove_batch(&mut self, batch_id: &[u8]) -> Result<Option<StoredBatch<F>>, VdafError> {
+        let batch = self.batch(batch_id)?;
+        if batch.is_some() {
+            fs::remove_file(self.batch_path(batch_id))?;
+        }
+        Ok(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Field64;
+    use assert_matches::assert_matches;
+
+    fn check_store<S: AccumulatorStore<Field64>>(store: &mut S) {
+        let share = |x: u64| OutputShare::from(vec![Field64::from(x), Field64::from(1)]);
+        assert!(store
+            .accumulate(b"batch 1", b"report 1", &share(1))
+            .unwrap());
+        assert!(store
+            .accumulate(b"batch 1", b"report 2", &share(2))
+            .unwrap());
+        assert!(store
+            .accumulate(b"batch 2", b"report 3", &share(4))
+            .unwrap());
+
+        // Replays are ignored, even into another batch.
+        assert!(!store
+            .accumulate(b"batch 1", b"report 1", &share(8))
+            .unwrap());
+        assert!(!store
+            .accumulate(b"batch 2", b"report 2", &share(8))
+            .unwrap());
+
+        let batch = store.batch(b"batch 1").unwrap().unwrap();
+        assert_eq!(batch.report_count, 2);
+        assert_eq!(
+            batch.aggregate_share,
+            AggregateShare::from(vec![Field64::from(3), Field64::from(2)])
+        );
+        assert!(store.batch(b"batch 3").unwrap().is_none());
+
+        // Output shares of the wrong length are rejected.
+        assert!(store
+            .accumulate(
+                b"batch 1",
+                b"report 4",
+                &OutputShare::from(vec![Field64::one()])
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn memory_store() {
+        let mut store = MemoryStore::new();
+        check_store(&mut store);
+        assert_eq!(
+            store
+                .remove_batch(b"batch 2")
+                .unwrap()
+                .unwrap()
+                .report_count,
+            1
+        );
+        assert!(store.batch(b"batch 2").unwrap().is_none());
+        assert!(!store
+            .accumulate(
+                b"batch 2",
+                b"report 3",
+                &OutputShare::from(vec![Field64::one(); 2])
+            )
+            .unwrap());
+    }
+
+    #[test]
+    fn report_set_digest() {
+        let digest = ReportSetDigest::new([&b"report 1"[..], b"report 2", b"report 3"]);
+        assert_eq!(digest.report_count(), 3);
+
+        // The digest ignores order and duplicates.
+        let same = ReportSetDigest::new(vec![
+            b"report 3".to_vec(),
+            b"report 1".to_vec(),
+            b"report 2".to_vec(),
+            b"report 1".to_vec(),
+        ]);
+        assert_eq!(digest, same);
+        digest.check(&same).unwrap();
+
+        let missing = ReportSetDigest::new([b"report 1", b"report 3"]);
+        assert!(digest.check(&missing).is_err());
+        let different = ReportSetDigest::new([&b"report 1"[..], b"report 2", b"report 4"]);
+        assert!(digest.check(&different).is_err());
+        // Length prefixes keep the boundaries between IDs.
+        assert_ne!(
+            ReportSetDigest::new([&b"ab"[..], b"c"]),
+            ReportSetDigest::new([&b"a"[..], b"bc"])
+        );
+
+        let encoded = digest.get_encoded().unwrap();
+        assert_eq!(Some(encoded.len()), digest.encoded_len());
+        assert_eq!(ReportSetDigest::get_decoded(&encoded).unwrap(), digest);
+
+        let mut store = MemoryStore::new();
+        check_store(&mut store);
+        let batch_1 = ReportSetDigest::new([b"report 1", b"report 2"]);
+        let one = ReportSetDigest::new([b"report 1"]);
+        assert!(store
+            .remove_batch_checked(b"batch 1", &batch_1, &missing)
+            .is_err());
+        assert!(store.remove_batch_checked(b"batch 1", &one, &one).is_err());
+        assert!(store.batch(b"batch 1").unwrap().is_some());
+        assert_eq!(
+            store
+                .remove_batch_checked(b"batch 1", &batch_1, &batch_1)
+                .unwrap()
+                .unwrap()
+                .report_count,
+            2
+        );
+        assert!(store.batch(b"batch 1").unwrap().is_none());
+    }
+
+    #[test]
+    fn directory_store() {
+        let dir = std::env::temp_dir().join(format!(
+            "prio-store-{}-{:016x}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let mut store = DirectoryStore::open(&dir).unwrap();
+        check_store(&mut store);
+
+        // The state survives reopening the store.
+        drop(store);
+        let mut store = DirectoryStore::<Field64>::open(&dir).unwrap();
+        assert_eq!(store.batch(b"batch 1").unwrap().unwrap().report_count, 2);
+        assert!(!store
+            .accumulate(
+                b"batch 1",
+                b"report 2",
+                &OutputShare::from(vec![Field64::one(); 2])
+            )
+            .unwrap());
+        assert_eq!(
+            store
+                .remove_batch(b"batch 2")
+                .unwrap()
+                .unwrap()
+                .aggregate_share,
+            AggregateShare::from(vec![Field64::from(4), Field64::from(1)])
+        );
+        assert!(store.remove_batch(b"batch 2").unwrap().is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn directory_store_corrupted() {
+        let dir = std::env::temp_dir().join(format!(
+            "prio-store-{}-{:016x}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let key = [7; 32];
+        let mut store = DirectoryStore::open_with_key(&dir, key).unwrap();
+        check_store(&mut store);
+        let batch_path = store.batch_path(b"batch 1");
+        drop(store);
+        DirectoryStore::<Field64>::open_with_key(&dir, key).unwrap();
+
+        // The wrong key, or none, fails the check.
+        assert_matches!(
+            DirectoryStore::<Field64>::open_with_key(&dir, [8; 32]),
+            Err(VdafError::CorruptedState(_))
+        );
+        assert_matches!(
+            DirectoryStore::<Field64>::open(&dir),
+            Err(VdafError::CorruptedState(_))
+        );
+
+        // A modified batch, or one moved to another batch's name, is not loaded.
+        let contents = fs::read(&batch_path).unwrap();
+        let mut modified = contents.clone();
+        modified[8] ^= 1;
+        fs::write(&batch_path, &modified).unwrap();
+        let mut store = DirectoryStore::<Field64>::open_with_key(&dir, key).unwrap();
+        assert_matches!(store.batch(b"batch 1"), Err(VdafError::CorruptedState(_)));
+        assert_matches!(
+            store.accumulate(
+                b"batch 1",
+                b"report 5",
+                &OutputShare::from(vec![Field64::one(); 2])
+            ),
+            Err(VdafError::CorruptedState(_))
+        );
+        fs::write(store.batch_path(b"batch 2"), &contents).unwrap();
+        assert_matches!(store.batch(b"batch 2"), Err(VdafError::CorruptedState(_)));
+        fs::write(&batch_path, &contents[..10]).unwrap();
+        assert_matches!(store.batch(b"batch 1"), Err(VdafError::CorruptedState(_)));
+        drop(store);
+
+        // So is a modified record of a report ID.
+        let reports_path = dir.join("reports");
+        let reports = fs::read(&reports_path).unwrap();
+        let mut modified = reports.clone();
+        modified[1] ^= 1;
+        fs::write(&reports_path, &modified).unwrap();
+        assert_matches!(
+            DirectoryStore::<Field64>::open_with_key(&dir, key),
+            Err(VdafError::CorruptedState(_))
+        );
+
+        // A record cut short by a crash while it was appended is discarded, and removed from the
+        // file, so that the store opens and later records are appended after the complete ones.
+        let mut torn = reports.clone();
+        torn.extend_from_slice(&[8, b'r', b'e', b'p']);
+        fs::write(&reports_path, &torn).unwrap();
+        let mut store = DirectoryStore::<Field64>::open_with_key(&dir, key).unwrap();
+        assert_eq!(store.reports.len(), 3);
+        assert_eq!(fs::read(&reports_path).unwrap(), reports);
+        assert!(store
+            .accumulate(
+                b"batch 3",
+                b"report 9",
+                &OutputShare::from(vec![Field64::one(); 2])
+            )
+            .unwrap());
+        drop(store);
+        let store = DirectoryStore::<Field64>::open_with_key(&dir, key).unwrap();
+        assert_eq!(store.reports.len(), 4);
+        drop(store);
+
+        // The same holds for a record missing only part of its checksum.
+        let reports = fs::read(&reports_path).unwrap();
+        fs::write(&reports_path, &reports[..reports.len() - 1]).unwrap();
+        let store = DirectoryStore::<Field64>::open_with_key(&dir, key).unwrap();
+        assert!(!store.reports.contains(&b"report 9"[..]));
+        assert_eq!(store.reports.len(), 3);
+        drop(store);
+        assert!(format!(
+            "{:?}",
+            DirectoryStore::<Field64>::open(dir.join("other")).unwrap()
+        )
+        .contains("keyed: false"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Audit logs of VDAF preparation.
+//!
+//! An Aggregator may record, for every report it prepares, the report nonce, the prepare shares
+//! broadcast by all Aggregators, and whether the report was accepted. [`AuditLog`] chains the
+//! entries together with SHA3-256, so that an Aggregator which publishes (or signs) the head of the
+//! chain commits to the entire log. A third party can later decode an exported log, check that it
+//! matches the published head, and inspect each decision.
+//!
+//! Prepare shares of the VDAFs in this crate consist of verifier shares and joint randomness parts;
+//! none of them reveal the measurement. The verification key is not recorded.
+
+use crate::{
+    codec::{
+        decode_u32_items, decode_u8_items, encode_u32_items, encode_u8_items, CodecError, Decode,
+        Encode,
+    },
+    vdaf::{Aggregator, VdafError},
+};
+use sha3::{Digest, Sha3_256};
+use std::io::{Cursor, Read};
+
+/// The length of the hash that heads an [`AuditLog`].
+pub const AUDIT_HASH_LEN: usize = 32;
+
+/// One round of preparation of one report, recorded in an [`AuditLog`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// The report nonce.
+    pub nonce: Vec<u8>,
+
+    /// The encoded prepare share of each Aggregator, in order of Aggregator ID.
+    pub prep_shares: Vec<Vec<u8>>,
+
+    /// Whether the prepare shares were successfully combined into a prepare message.
+    pub accepted: bool,
+}
+
+impl Encode for AuditEntry {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_u8_items(bytes, &(), &self.nonce)?;
+        let prep_shares = self
+            .prep_shares
+            .iter()
+            .map(|share| Opaque(share.clone()))
+            .collect::<Vec<_>>();
+        encode_u32_items(bytes, &(), &prep_shares)?;
+        u8::from(self.accepted).encode(bytes)
+    }
+}
+
+impl Decode for AuditEntry {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let nonce = decode_u8_items(&(), bytes)?;
+        let prep_shares = decode_u32_items::<_, Opaque>(&(), bytes)?
+            .into_iter()
+            .map(|share| share.0)
+            .collect();
+        let accepted = match u8::decode(bytes)? {
+            0 => false,
+            1 => true,
+            _ => return Err(CodecError::UnexpectedValue),
+        };
+        Ok(Self {
+            nonce,
+            prep_shares,
+            accepted,
+        })
+    }
+}
+
+/// A byte string with a 32-bit length prefix.
+struct Opaque(Vec<u8>);
+
+impl Encode for Opaque {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_u32_items(bytes, &(), &self.0)
+    }
+}
+
+impl Decode for Opaque {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self(decode_u32_items(&(), bytes)?))
+    }
+}
+
+/// A hash-chained log of [`AuditEntry`]s.
+///
+/// The head of an empty log is all zeros. Recording an entry replaces the head with the SHA3-256
+/// hash of the previous head followed by the encoded entry. The encoding of the log is the head
+/// followed by the entries; decoding recomputes the chain and fails if the head does not match.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+    head: [u8; AUDIT_HASH_LEN],
+}
+
+impl AuditLog {
+    /// Creates an empty log.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            head: [0; AUDIT_HASH_LEN],
+        }
+    }
+
+    /// Appends an entry to the log.
+    pub fn record(&mut self, entry: AuditEntry) -> Result<(), CodecError> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(self.head);
+        hasher.update(entry.get_encoded()?);
+        self.head = hasher.finalize().into();
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Combines prepare shares into a prepare message with
+    /// [`Aggregator::prepare_shares_to_prepare_message`], and records the prepare shares and the
+    /// outcome in the log.
+    pub fn prepare_shares_to_prepare_message<
+        V,
+        const VERIFY_KEY_SIZE: usize,
+        const NONCE_SIZE: usize,
+    >(
+        &mut self,
+        vdaf: &V,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        prep_shares: Vec<V::PrepareShare>,
+    ) -> Result<V::PrepareMessage, VdafError>
+    where
+        V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+    {
+        let encoded_prep_shares = prep_shares
+            .iter()
+            .map(Encode::get_encoded)
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = vdaf.prepare_shares_to_prepare_message(agg_param, prep_shares);
+        self.record(AuditEntry {
+            nonce: nonce.to_vec(),
+            prep_shares: encoded_prep_shares,
+            accepted: result.is_ok(),
+        })?;
+        result
+    }
+
+    /// Returns the entries of the log, in the order they were recorded.
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Returns the head of the hash chain, which commits to every entry of the log.
+    pub fn head(&self) -> [u8; AUDIT_HASH_LEN] {
+        self.head
+    }
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encode for AuditLog {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        bytes.extend_from_slice(&self.head);
+        encode_u32_items(bytes, &(), &self.entries)
+    }
+}
+
+impl Decode for AuditLog {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let mut head = [0; AUDIT_HASH_LEN];
+        bytes.read_exact(&mut head)?;
+
+        let mut log = Self::new();
+        for entry in decode_u32_items(&(), bytes)? {
+            log.record(entry)?;
+        }
+        if log.head != head {
+            return Err(CodecError::UnexpectedValue);
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{prio3::Prio3, Client};
+
+    #[test]
+    fn audit_log() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let verify_key = [0; 16];
+        let mut log = AuditLog::new();
+
+        for (i, measurement) in [true, false].into_iter().enumerate() {
+            let nonce = [i as u8; 16];
+            let (public_share, input_shares) = vdaf.shard(&measurement, &nonce).unwrap();
+            let prep_shares = input_shares
+                .iter()
+                .enumerate()
+                .map(|(agg_id, input_share)| {
+                    vdaf.prepare_init(&verify_key, agg_id, &(), &nonce, &public_share, input_share)
+                        .unwrap()
+                        .1
+                })
+                .collect();
+            log.prepare_shares_to_prepare_message(&vdaf, &(), &nonce, prep_shares)
+                .unwrap();
+        }
+
+        // A report whose prepare shares don't verify is recorded as rejected.
+        let nonce = [2; 16];
+        let (public_share, input_shares) = vdaf.shard(&true, &nonce).unwrap();
+        let prep_shares = input_shares
+            .iter()
+            .enumerate()
+            .map(|(agg_id, input_share)| {
+                vdaf.prepare_init(
+                    &verify_key,
+                    agg_id,
+                    &(),
+                    &[agg_id as u8; 16],
+                    &public_share,
+                    input_share,
+                )
+                .unwrap()
+                .1
+            })
+            .collect();
+        assert!(log
+            .prepare_shares_to_prepare_message(&vdaf, &(), &nonce, prep_shares)
+            .is_err());
+
+        assert_eq!(log.entries().len(), 3);
+        assert_eq!(
+            log.entries()
+                .iter()
+                .map(|entry| entry.accepted)
+                .collect::<Vec<_>>(),
+            [true, true, false]
+        );
+        assert_eq!(log.entries()[2].nonce, nonce);
+        assert_ne!(log.head(), [0; AUDIT_HASH_LEN]);
+
+        // The log round-trips, and tampering with any entry is detected.
+        let encoded = log.get_encoded().unwrap();
+        assert_eq!(AuditLog::get_decoded(&encoded).unwrap(), log);
+        for i in AUDIT_HASH_LEN..encoded.len() {
+            let mut tampered = encoded.clone();
+            tampered[i] ^= 1;
+            assert!(AuditLog::get_decoded(&tampered).is_err(), "byte {i}");
+        }
+    }
+}
@@ -0,0 +1,391 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Commitments to aggregate shares.
+//!
+//! The Collector cannot tell whether an aggregate share it receives was computed honestly: a
+//! buggy or malicious Aggregator could send a share that omits reports, or change its share after
+//! seeing the other Aggregators'. With this module, each Aggregator commits to its aggregate share
+//! and the number of reports it aggregated with [`commit`], and sends the
+//! [`AggregateShareCommitment`] to the Collector (or publishes it) before it releases the share.
+//! [`unshard_committed`] then checks each share against its commitment, and that every Aggregator
+//! claims the number of reports the Collector expects, before unsharding.
+//!
+//! A commitment only prevents an Aggregator from choosing its share adaptively if the Collector
+//! holds every commitment before any share is opened. [`CommitmentRound`] enforces that order when
+//! the shares arrive one at a time: it accepts openings only once every Aggregator has committed,
+//! and checks each opening as it arrives.
+//!
+//! The commitment is the SHA3-256 hash of the encoded aggregate share, the report count and a
+//! random blind, so it reveals nothing about the share until it is opened. It binds an Aggregator
+//! to one share; it cannot show that the share is the sum of output shares of valid reports.
+
+use crate::{
+    codec::{CodecError, Decode, Encode},
+    vdaf::{Collector, VdafError},
+};
+use sha3::{Digest, Sha3_256};
+use std::io::{Cursor, Read};
+use subtle::ConstantTimeEq;
+
+/// Domain separation tag for [`AggregateShareCommitment`].
+const COMMITMENT_DST: &[u8] = b"prio aggregate share commitment";
+
+/// A commitment to an aggregate share and the number of reports it aggregates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AggregateShareCommitment([u8; 32]);
+
+/// The values needed to open an [`AggregateShareCommitment`], sent with the aggregate share.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AggregateShareOpening {
+    /// The number of reports in the aggregate share.
+    pub report_count: u64,
+
+    blind: [u8; 32],
+}
+
+/// Commits to `agg_share`, which aggregates `report_count` reports. The commitment is sent before
+/// the aggregate share, and the opening along with it.
+pub fn commit<A: Encode>(
+    agg_share: &A,
+    report_count: u64,
+) -> Result<(AggregateShareCommitment, AggregateShareOpening), VdafError> {
+    let mut blind = [0; 32];
+    getrandom::getrandom(&mut blind)?;
+    let opening = AggregateShareOpening {
+        report_count,
+        blind,
+    };
+    Ok((
+        AggregateShareCommitment::compute(agg_share, &opening)?,
+        opening,
+    ))
+}
+
+impl AggregateShareCommitment {
+    fn compute<A: Encode>(
+        agg_share: &A,
+        opening: &AggregateShareOpening,
+    ) -> Result<Self, VdafError> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(COMMITMENT_DST);
+        hasher.update(opening.blind);
+        hasher.update(opening.report_count.to_be_bytes());
+        hasher.update(agg_share.get_encoded()?);
+        Ok(Self(hasher.finalize().into()))
+    }
+
+    /// Returns an error unless this commits to `agg_share` with `opening`.
+    pub fn verify<A: Encode>(
+        &self,
+        agg_share: &A,
+        opening: &AggregateShareOpening,
+    ) -> Result<(), VdafError> {
+        if !bool::from(self.0.ct_eq(&Self::compute(agg_share, opening)?.0)) {
+            return Err(VdafError::Uncategorized(
+                "aggregate share does not match its commitment".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Encode for AggregateShareCommitment {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        bytes.extend_from_slice(&self.0);
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(32)
+    }
+}
+
+impl Decode for AggregateShareCommitment {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let mut commitment = [0; 32];
+        bytes.read_exact(&mut commitment)?;
+        Ok(Self(commitment))
+    }
+}
+
+impl Encode for AggregateShareOpening {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.report_count.encode(bytes)?;
+        bytes.extend_from_slice(&self.blind);
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(8 + 32)
+    }
+}
+
+impl Decode for AggregateShareOpening {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let report_count = u64::decode(bytes)?;
+        let mut blind = [0; 32];
+        bytes.read_exact(&mut blind)?;
+        Ok(Self {
+            report_count,
+            blind,
+        })
+    }
+}
+
+/// Unshards the aggregate shares like [`Collector::unshard`], after checking each share against
+/// the commitment its Aggregator sent earlier, and that each Aggregator aggregated
+/// `num_measurements` reports. `shares` yields a commitment, opening and aggregate share for each
+/// Aggregator.
+pub fn unshard_committed<V, I>(
+    vdaf: &V,
+    agg_param: &V::AggregationParam,
+    shares: I,
+    num_measurements: usize,
+) -> Result<V::AggregateResult, VdafError>
+where
+    V: Collector,
+    I: IntoIterator<
+        Item = (
+            AggregateShareCommitment,
+            AggregateShareOpening,
+            V::AggregateShare,
+        ),
+    >,
+{
+    let mut agg_shares = Vec::new();
+    for (agg_id, (commitment, opening, agg_share)) in shares.into_iter().enumerate() {
+        commitment.verify(&agg_share, &opening)?;
+        if opening.report_count != num_measurements as u64 {
+            return Err(VdafError::Uncategorized(format!(
+                "aggregator {agg_id} aggregated {} reports, expected {num_measurements}",
+                opening.report_count
+            )));
+        }
+        agg_shares.push(agg_share);
+    }
+    vdaf.unshard(agg_param, agg_shares, num_measurements)
+}
+
+/// The Collector's side of a commit-then-open exchange of aggregate shares `A`. See the
+/// [module documentation](self) for details.
+#[derive(Clone, Debug)]
+pub struct CommitmentRound<A> {
+    commitments: Vec<Option<AggregateShareCommitment>>,
+    openings: Vec<Option<(AggregateShareOpening, A)>>,
+}
+
+impl<A: Encode> CommitmentRound<A> {
+    /// Starts a round with `num_aggregators` Aggregators.
+    pub fn new(num_aggregators: usize) -> Self {
+        Self {
+            commitments: vec![None; num_aggregators],
+            openings: (0..num_aggregators).map(|_| None).collect(),
+        }
+    }
+
+    /// Records the commitment of Aggregator `agg_id`. Returns an error if the Aggregator has
+    /// already committed, if another Aggregator sent the same commitment, or if shares are already
+    /// being opened.
+    pub fn commit(
+        &mut self,
+        agg_id: usize,
+        commitment: AggregateShareCommitment,
+    ) -> Result<(), VdafError> {
+        if self.is_committed() {
+            return Err(VdafError::Uncategorized(
+                "commitment received after all aggregators committed".into(),
+            ));
+        }
+        if self.commitments.contains(&Some(commitment)) {
+            return Err(VdafError::Uncategorized("commitment received twice".into()));
+        }
+        let slot = self.commitments.get_mut(agg_id).ok_or_else(|| {
+            VdafError::Uncategorized(format!("unexpected aggregator ID {agg_id}"))
+        })?;
+        if slot.is_some() {
+            return Err(VdafError::Uncategorized(format!(
+                "aggregator {agg_id} already committed"
+            )));
+        }
+        *slot = Some(commitment);
+        Ok(())
+    }
+
+    /// Returns true once every Aggregator has committed, and shares may be opened.
+    pub fn is_committed(&self) -> bool {
+        self.commitments.iter().all(Option::is_some)
+    }
+
+    /// Records the aggregate share of Aggregator `agg_id` after checking it against the
+    /// Aggregator's commitment. Returns an error if some Aggregator has not yet committed, or if
+    /// this Aggregator has already opened its share.
+    pub fn open(
+        &mut self,
+        agg_id: usize,
+        opening: AggregateShareOpening,
+        agg_share: A,
+    ) -> Result<(), VdafError> {
+        if !self.is_committed() {
+            return Err(VdafError::Uncategorized(
+                "aggregate share opened before all aggregators committed".into(),
+            ));
+        }
+        let (Some(Some(commitment)), Some(slot)) =
+            (self.commitments.get(agg_id), self.openings.get_mut(agg_id))
+        else {
+            return Err(VdafError::Uncategorized(format!(
+                "unexpected aggregator ID {agg_id}"
+            )));
+        };
+        if slot.is_some() {
+            return Err(VdafError::Uncategorized(format!(
+                "aggregator {agg_id} already opened its aggregate share"
+            )));
+        }
+        commitment.verify(&agg_share, &opening)?;
+        *slot = Some((opening, agg_share));
+        Ok(())
+    }
+
+    /// Unshards the opened aggregate shares like [`unshard_committed`]. Returns an error if some
+    /// Aggregator has not opened its share.
+    pub fn finish<V>(
+        self,
+        vdaf: &V,
+        agg_param: &V::AggregationParam,
+        num_measurements: usize,
+    ) -> Result<V::AggregateResult, VdafError>
+    where
+        V: Collector<AggregateShare = A>,
+    {
+        let shares = self
+            .commitments
+            .into_iter()
+            .zip(self.openings)
+            .enumerate()
+            .map(
+                |(agg_id, (commitment, opened))| match (commitment, opened) {
+                    (Some(commitment), Some((opening, agg_share))) => {
+                        Ok((commitment, opening, agg_share))
+                    }
+                    _ => Err(VdafError::Uncategorized(format!(
+                        "aggregator {agg_id} did not open its aggregate share"
+                    ))),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        unshard_committed(vdaf, agg_param, shares, num_measurements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        field::{Field128, FieldElement},
+        vdaf::{prio3::Prio3, test_utils::run_vdaf_prepare, Aggregatable, AggregateShare, Client},
+    };
+
+    #[test]
+    fn committed_aggregate_shares() {
+        let vdaf = Prio3::new_sum(2, 8).unwrap();
+        let measurements = [1, 2, 3];
+        let mut agg_shares: Vec<AggregateShare<Field128>> = Vec::new();
+        for (i, measurement) in measurements.iter().enumerate() {
+            let nonce = [i as u8; 16];
+            let (public_share, input_shares) = vdaf.shard(measurement, &nonce).unwrap();
+            let out_shares =
+                run_vdaf_prepare(&vdaf, &[0; 16], &(), &nonce, public_share, input_shares).unwrap();
+            if agg_shares.is_empty() {
+                agg_shares = out_shares.into_iter().map(AggregateShare::from).collect();
+            } else {
+                for (agg_share, out_share) in agg_shares.iter_mut().zip(out_shares) {
+                    agg_share.accumulate(&out_share).unwrap();
+                }
+            }
+        }
+
+        let committed: Vec<_> = agg_shares
+            .iter()
+            .map(|agg_share| {
+                let (commitment, opening) = commit(agg_share, 3).unwrap();
+                let commitment =
+                    AggregateShareCommitment::get_decoded(&commitment.get_encoded().unwrap())
+                        .unwrap();
+                let opening =
+                    AggregateShareOpening::get_decoded(&opening.get_encoded().unwrap()).unwrap();
+                (commitment, opening, agg_share.clone())
+            })
+            .collect();
+        assert_eq!(
+            unshard_committed(&vdaf, &(), committed.clone(), 3).unwrap(),
+            6
+        );
+
+        // The Collector expects another report.
+        assert!(unshard_committed(&vdaf, &(), committed.clone(), 4).is_err());
+
+        // An Aggregator changes its share after committing.
+        let mut tampered = committed.clone();
+        tampered[1].2 = AggregateShare::from(vec![Field128::one()]);
+        assert!(unshard_committed(&vdaf, &(), tampered, 3).is_err());
+
+        // An Aggregator opens its commitment with the wrong count.
+        let mut tampered = committed;
+        tampered[0].1.report_count = 4;
+        assert!(unshard_committed(&vdaf, &(), tampered, 4).is_err());
+
+        // Each commitment uses a fresh blind, so commitments to the same share differ.
+        assert_ne!(
+            commit(&agg_shares[0], 3).unwrap().0,
+            commit(&agg_shares[0], 3).unwrap().0
+        );
+    }
+
+    #[test]
+    fn commitment_round() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let nonce = [0; 16];
+        let (public_share, input_shares) = vdaf.shard(&true, &nonce).unwrap();
+        let agg_shares: Vec<AggregateShare<_>> =
+            run_vdaf_prepare(&vdaf, &[0; 16], &(), &nonce, public_share, input_shares)
+                .unwrap()
+                .into_iter()
+                .map(AggregateShare::from)
+                .collect();
+        let (commitments, openings): (Vec<_>, Vec<_>) = agg_shares
+            .iter()
+            .map(|agg_share| commit(agg_share, 1).unwrap())
+            .unzip();
+
+        let mut round = CommitmentRound::new(2);
+        round.commit(1, commitments[1]).unwrap();
+        assert!(!round.is_committed());
+
+        // No share is opened until every Aggregator commits, and commitments cannot be copied.
+        assert!(round
+            .open(1, openings[1].clone(), agg_shares[1].clone())
+            .is_err());
+        assert!(round.commit(0, commitments[1]).is_err());
+        assert!(round.commit(2, commitments[0]).is_err());
+        round.commit(0, commitments[0]).unwrap();
+        assert!(round.is_committed());
+        assert!(round.commit(0, commitments[0]).is_err());
+
+        // Shares are checked as they are opened.
+        assert!(round
+            .open(0, openings[0].clone(), agg_shares[1].clone())
+            .is_err());
+        round
+            .open(0, openings[0].clone(), agg_shares[0].clone())
+            .unwrap();
+        assert!(round
+            .open(0, openings[0].clone(), agg_shares[0].clone())
+            .is_err());
+        assert!(round.clone().finish(&vdaf, &(), 1).is_err());
+        round
+            .open(1, openings[1].clone(), agg_shares[1].clone())
+            .unwrap();
+        assert_eq!(round.finish(&vdaf, &(), 1).unwrap(), 1);
+    }
+}
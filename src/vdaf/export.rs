@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Export of finalized aggregates.
+//!
+//! Once a Collector has unsharded an aggregate result, it usually stores or publishes it along with
+//! the metadata needed to interpret it: the task it belongs to, the batch interval, the number of
+//! reports, and the differential privacy parameters, if noise was added. [`ExportedAggregate`]
+//! holds all of these, and [`write_csv`] and [`write_json`] write a list of them in a fixed format.
+//!
+//! In CSV, each entry of an aggregate is a separate row, headed by [`CSV_HEADER`]. A scalar
+//! aggregate is a single row with index 0. In JSON, the aggregates are written as an array of
+//! objects with the same fields as [`ExportedAggregate`].
+
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, io::Write};
+
+/// The header row written by [`write_csv`].
+pub const CSV_HEADER: &str = "task_id,batch_start,batch_duration,report_count,dp_mechanism,\
+                              dp_epsilon,dp_delta,index,label,value";
+
+/// Errors returned by this module.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ExportError {
+    /// The number of labels does not match the number of entries in an aggregate.
+    #[error("got {labels} labels for an aggregate with {values} entries")]
+    LabelMismatch {
+        /// The number of labels.
+        labels: usize,
+        /// The number of entries.
+        values: usize,
+    },
+
+    /// Writing the output failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Serializing to JSON failed.
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The time interval covered by a batch, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchInterval {
+    /// The start of the interval.
+    pub start: u64,
+
+    /// The length of the interval.
+    pub duration: u64,
+}
+
+/// The differential privacy guarantee of an aggregate.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DpParameters {
+    /// The noise mechanism, e.g. `discrete_gaussian`.
+    pub mechanism: String,
+
+    /// The privacy parameter epsilon.
+    pub epsilon: f64,
+
+    /// The privacy parameter delta, if the guarantee is approximate.
+    pub delta: Option<f64>,
+}
+
+/// A finalized aggregate and its metadata.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedAggregate<T> {
+    /// The identifier of the task, as used by the application.
+    pub task_id: String,
+
+    /// The batch interval of the aggregate.
+    pub batch_interval: BatchInterval,
+
+    /// The number of reports in the aggregate.
+    pub report_count: u64,
+
+    /// The differential privacy parameters, or `None` if no noise was added.
+    pub dp: Option<DpParameters>,
+
+    /// The entries of the aggregate.
+    pub values: Vec<T>,
+
+    /// A label for each entry of the aggregate, e.g. the bucket labels of a histogram.
+    pub labels: Option<Vec<String>>,
+}
+
+impl<T> ExportedAggregate<T> {
+    fn check_labels(&self) -> Result<(), ExportError> {
+        match &self.labels {
+            Some(labels) if labels.len() != self.values.len() => Err(ExportError::LabelMismatch {
+                labels: labels.len(),
+                values: self.values.len(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Writes `aggregates` as CSV, starting with [`CSV_HEADER`]. Fields that are not set are left
+/// empty.
+pub fn write_csv<T: Display, W: Write>(
+    writer: &mut W,
+    aggregates: &[ExportedAggregate<T>],
+) -> Result<(), ExportError> {
+    writeln!(writer, "{CSV_HEADER}")?;
+    for aggregate in aggregates {
+        aggregate.check_labels()?;
+        let (mechanism, epsilon, delta) = match &aggregate.dp {
+            Some(dp) => (
+                csv_field(&dp.mechanism),
+                dp.epsilon.to_string(),
+                dp.delta.map(|delta| delta.to_string()).unwrap_or_default(),
+            ),
+            None => Default::default(),
+        };
+        for (index, value) in aggregate.values.iter().enumerate() {
+            let label = aggregate
+                .labels
+                .as_ref()
+                .map(|labels| csv_field(&labels[index]))
+                .unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{},{},{},{mechanism},{epsilon},{delta},{index},{label},{value}",
+                csv_field(&aggregate.task_id),
+                aggregate.batch_interval.start,
+                aggregate.batch_interval.duration,
+                aggregate.report_count,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `aggregates` as a JSON array.
+pub fn write_json<T: Serialize, W: Write>(
+    writer: &mut W,
+    aggregates: &[ExportedAggregate<T>],
+) -> Result<(), ExportError> {
+    for aggregate in aggregates {
+        aggregate.check_labels()?;
+    }
+    serde_json::to_writer(writer, aggregates)?;
+    Ok(())
+}
+
+/// Quotes a CSV field if it contains a delimiter, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{prio3::Prio3, test_utils::run_vdaf};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn export() {
+        let vdaf = Prio3::new_histogram(2, 3, 2).unwrap();
+        let values = run_vdaf(&vdaf, &(), [0, 2, 2]).unwrap();
+        let mut aggregates = vec![
+            ExportedAggregate {
+                task_id: "task, 1".into(),
+                batch_interval: BatchInterval {
+                    start: 1_700_000_000,
+                    duration: 3600,
+                },
+                report_count: 3,
+                dp: None,
+                values,
+                labels: Some(vec!["a".into(), "b".into(), "c \"d\"".into()]),
+            },
+            ExportedAggregate {
+                task_id: "task2".into(),
+                batch_interval: BatchInterval {
+                    start: 1_700_003_600,
+                    duration: 3600,
+                },
+                report_count: 5,
+                dp: Some(DpParameters {
+                    mechanism: "discrete_gaussian".into(),
+                    epsilon: 0.5,
+                    delta: Some(1e-9),
+                }),
+                values: vec![5],
+                labels: None,
+            },
+        ];
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &aggregates).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            [
+                CSV_HEADER,
+                "\"task, 1\",1700000000,3600,3,,,,0,a,1",
+                "\"task, 1\",1700000000,3600,3,,,,1,b,0",
+                "\"task, 1\",1700000000,3600,3,,,,2,\"c \"\"d\"\"\",2",
+                "task2,1700003600,3600,5,discrete_gaussian,0.5,0.000000001,0,,5",
+                "",
+            ]
+            .join("\n")
+        );
+
+        let mut json = Vec::new();
+        write_json(&mut json, &aggregates).unwrap();
+        let decoded: Vec<ExportedAggregate<u128>> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(decoded, aggregates);
+
+        aggregates[1].labels = Some(Vec::new());
+        assert_matches!(
+            write_csv(&mut Vec::new(), &aggregates),
+            Err(ExportError::LabelMismatch {
+                labels: 0,
+                values: 1
+            })
+        );
+        assert_matches!(
+            write_json(&mut Vec::new(), &aggregates),
+            Err(ExportError::LabelMismatch { .. })
+        );
+    }
+}
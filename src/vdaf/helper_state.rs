@@ -0,0 +1,589 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Stateless helpers, which hand their preparation state to the leader between rounds.
+//!
+//! A helper that keeps the state of each report it prepares must route every round of a report to
+//! the same server, or share the state between its servers. [`StatelessHelper`] instead seals the
+//! state under a key known only to the helper and returns the [`OpaqueState`] to the leader along
+//! with its prepare share. The leader stores the blob with the rest of the report's state and sends
+//! it back with the next prepare message, so that any server of the helper can continue.
+//!
+//! Sealing encrypts the state with AES-128 in counter mode and authenticates it with HMAC-SHA256,
+//! keyed by a [`HelperStateKey`]. The tag binds the task ID, the verify key, the Aggregator ID,
+//! the report ID, the aggregation parameter and the round of preparation, so that the leader can
+//! neither read the state nor modify it, nor return the state of one report, task or round in
+//! place of another, even if the helper uses the same key for several tasks. A blob that fails these checks is rejected with [`VdafError::CorruptedState`]. Every
+//! server of the helper must share the key, and the key must be rotated like any other secret; a
+//! blob sealed under an old key cannot be opened. With the `secure-memory` feature,
+//! `HelperStateKey::new_locked` keeps the key in locked memory.
+//!
+//! Without state of its own, the helper cannot tell whether a blob has been used before, so a
+//! leader could have a round prepared twice with different prepare messages. A helper that must
+//! rule this out still has to remember, by report ID, which reports it has finished.
+
+#[cfg(feature = "secure-memory")]
+use crate::secure_memory::{LockedBytes, SecureMemoryError};
+use crate::{
+    codec::{decode_u32_items, encode_u32_items, CodecError, Decode, Encode, ParameterizedDecode},
+    vdaf::{id::TaskId, xof::SeedStreamAes128, Aggregator, PrepareTransition, VdafError},
+};
+use hmac::{Hmac, Mac};
+use rand::prelude::*;
+use sha2::Sha256;
+use std::{
+    fmt::{self, Debug},
+    io::Cursor,
+    ptr,
+};
+use subtle::ConstantTimeEq;
+
+/// Domain separation tag for the keys derived from a [`HelperStateKey`].
+const KEY_DST: &[u8] = b"prio helper state key";
+
+/// The length in bytes of the nonce of a sealed state.
+const NONCE_LEN: usize = 16;
+
+/// The length in bytes of the tag of a sealed state.
+const TAG_LEN: usize = 32;
+
+/// The length in bytes of the encryption key.
+const ENCRYPTION_KEY_LEN: usize = 16;
+
+/// The length in bytes of the encryption key followed by the MAC key.
+const KEYS_LEN: usize = ENCRYPTION_KEY_LEN + 32;
+
+/// The key under which a helper seals its preparation state. See the
+/// [module documentation](self) for details.
+#[derive(Clone)]
+pub struct HelperStateKey(KeyBytes);
+
+/// The encryption key followed by the MAC key.
+#[derive(Clone)]
+enum KeyBytes {
+    Plain([u8; KEYS_LEN]),
+    #[cfg(feature = "secure-memory")]
+    Locked(std::sync::Arc<LockedBytes<KEYS_LEN>>),
+}
+
+impl HelperStateKey {
+    /// Derives the key from `secret`, which must be uniformly random and known only to the
+    /// helper.
+    pub fn new(secret: &[u8; 32]) -> Self {
+        let mut keys = [0; KEYS_LEN];
+        Self::derive(secret, &mut keys);
+        Self(KeyBytes::Plain(keys))
+    }
+
+    /// Like [`HelperStateKey::new`], but derives the key into locked memory, and zeroes `secret`.
+    #[cfg(feature = "secure-memory")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "secure-memory")))]
+    pub fn new_locked(secret: &mut [u8; 32]) -> Result<Self, SecureMemoryError> {
+        let secret = LockedBytes::from_bytes(secret)?;
+        let mut keys = LockedBytes::new()?;
+        Self::derive(secret.as_bytes(), keys.as_mut_bytes());
+        Ok(Self(KeyBytes::Locked(std::sync::Arc::new(keys))))
+    }
+
+    fn derive(secret: &[u8; 32], keys: &mut [u8; KEYS_LEN]) {
+        let derive = |usage: u8| {
+            // Unwrap safety: new_from_slice() is infallible for Hmac.
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+            mac.update(KEY_DST);
+            mac.update(&[usage]);
+            mac.finalize().into_bytes()
+        };
+        keys[..ENCRYPTION_KEY_LEN].copy_from_slice(&derive(0)[..ENCRYPTION_KEY_LEN]);
+        keys[ENCRYPTION_KEY_LEN..].copy_from_slice(&derive(1));
+    }
+
+    fn keys(&self) -> &[u8; KEYS_LEN] {
+        match &self.0 {
+            KeyBytes::Plain(keys) => keys,
+            #[cfg(feature = "secure-memory")]
+            KeyBytes::Locked(keys) => keys.as_bytes(),
+        }
+    }
+
+    /// Encrypts and authenticates `plaintext`, binding it to `context`.
+    pub fn seal(&self, context: &[u8], plaintext: &[u8]) -> OpaqueState {
+        let mut nonce = [0; NONCE_LEN];
+        thread_rng().fill(&mut nonce[..]);
+        let mut blob = Vec::with_capacity(NONCE_LEN + plaintext.len() + TAG_LEN);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(plaintext);
+        self.apply_keystream(&nonce, &mut blob[NONCE_LEN..]);
+        let tag = self.tag(context, &blob);
+        blob.extend_from_slice(&tag);
+        OpaqueState(blob)
+    }
+
+    /// Checks that `state` was sealed under this key with `context` and returns the plaintext.
+    pub fn open(&self, context: &[u8], state: &OpaqueState) -> Result<Vec<u8>, VdafError> {
+        let blob = &state.0;
+        if blob.len() < NONCE_LEN + TAG_LEN {
+            return Err(VdafError::CorruptedState("sealed state too short".into()));
+        }
+        let (sealed, tag) = blob.split_at(blob.len() - TAG_LEN);
+        if !bool::from(self.tag(context, sealed).ct_eq(tag)) {
+            return Err(VdafError::CorruptedState(
+                "sealed state failed authentication".into(),
+            ));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let mut plaintext = ciphertext.to_vec();
+        self.apply_keystream(nonce, &mut plaintext);
+        Ok(plaintext)
+    }
+
+    fn apply_keystream(&self, nonce: &[u8], buf: &mut [u8]) {
+        let mut keystream = vec![0; buf.len()];
+        SeedStreamAes128::new(&self.keys()[..ENCRYPTION_KEY_LEN], nonce).fill_bytes(&mut keystream);
+        for (x, y) in buf.iter_mut().zip(keystream) {
+            *x ^= y;
+        }
+    }
+
+    fn tag(&self, context: &[u8], sealed: &[u8]) -> [u8; TAG_LEN] {
+        // Unwrap safety: new_from_slice() is infallible for Hmac.
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.keys()[ENCRYPTION_KEY_LEN..]).unwrap();
+        mac.update(&(context.len() as u64).to_be_bytes());
+        mac.update(context);
+        mac.update(sealed);
+        mac.finalize().into_bytes().into()
+    }
+}
+
+impl Drop for KeyBytes {
+    fn drop(&mut self) {
+        match self {
+            Self::Plain(keys) => {
+                for byte in keys.iter_mut() {
+                    // Safety: `byte` is a valid, aligned reference. The write is volatile so that
+                    // the compiler cannot elide it.
+                    unsafe { ptr::write_volatile(byte, 0) };
+                }
+            }
+            // Locked keys are zeroed by `LockedBytes` when the last reference is dropped.
+            #[cfg(feature = "secure-memory")]
+            Self::Locked(_) => {}
+        }
+    }
+}
+
+impl Debug for HelperStateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HelperStateKey").finish_non_exhaustive()
+    }
+}
+
+/// Preparation state sealed by a helper, which the leader stores and returns unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpaqueState(Vec<u8>);
+
+impl OpaqueState {
+    /// Wraps a blob received from the leader.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl AsRef<[u8]> for OpaqueState {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Encode for OpaqueState {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_u32_items(bytes, &(), &self.0)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(4 + self.0.len())
+    }
+}
+
+impl Decode for OpaqueState {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        decode_u32_items(&(), bytes).map(Self)
+    }
+}
+
+/// The result of a round of preparation by a [`StatelessHelper`].
+#[derive(Clone, Debug)]
+pub enum StatelessTransition<
+    V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+    const VERIFY_KEY_SIZE: usize,
+    const NONCE_SIZE: usize,
+> {
+    /// Continue processing. The state is sent to the leader along with the prepare share.
+    Continue(OpaqueState, V::PrepareShare),
+
+    /// Finish processing and return the output share.
+    Finish(V::OutputShare),
+}
+
+/// A helper that keeps no preparation state between rounds. See the
+/// [module documentation](self) for details.
+pub struct StatelessHelper<'a, V, const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize> {
+    vdaf: &'a V,
+    task_id: TaskId,
+    verify_key: [u8; VERIFY_KEY_SIZE],
+    agg_id: usize,
+    state_key: HelperStateKey,
+}
+
+impl<'a, V, const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize>
+    StatelessHelper<'a, V, VERIFY_KEY_SIZE, NONCE_SIZE>
+where
+    V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+    V::PrepareState: Encode + for<'b> ParameterizedDecode<(&'b V, usize)>,
+{
+    /// Creates a helper for `vdaf` in task `task_id` with Aggregator ID `agg_id`, which seals its
+    /// state under `state_key`.
+    pub fn new(
+        vdaf: &'a V,
+        task_id: TaskId,
+        verify_key: [u8; VERIFY_KEY_SIZE],
+        agg_id: usize,
+        state_key: HelperStateKey,
+    ) -> Self {
+        Self {
+            vdaf,
+            task_id,
+            verify_key,
+            agg_id,
+            state_key,
+        }
+    }
+
+    /// Starts preparing a report, as in [`Aggregator::prepare_init`], and returns the sealed
+    /// state along with the prepare share.
+    pub fn prepare_init(
+        &self,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        public_share: &V::PublicShare,
+        input_share: &V::InputShare,
+    ) -> Result<(OpaqueState, V::PrepareShare), VdafError> {
+        let (state, share) = self.vdaf.prepare_init(
+            &self.verify_key,
+            self.agg_id,
+            agg_param,
+            nonce,
+            public_share,
+            input_share,
+        )?;
+        Ok((self.seal(agg_param, nonce, 0, &state)?, share))
+    }
+
+    /// Continues preparing the report with ID `nonce`, as in [`Aggregator::prepare_next`], from
+    /// the state returned by the previous round. `round` counts the rounds before this one: it is
+    /// 0 for the state returned by [`StatelessHelper::prepare_init`], 1 for the state returned by
+    /// the first call to this method, and so on. The leader's request determines it, as the DAP
+    /// aggregation step does.
+    pub fn prepare_next(
+        &self,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        round: u64,
+        state: &OpaqueState,
+        input: V::PrepareMessage,
+    ) -> Result<StatelessTransition<V, VERIFY_KEY_SIZE, NONCE_SIZE>, VdafError> {
+        let state = self.open(agg_param, nonce, round, state)?;
+        match self.vdaf.prepare_next(state, input)? {
+            PrepareTransition::Continue(state, share) => {
+                let next_round = round
+                    .checked_add(1)
+                    .ok_or_else(|| VdafError::Uncategorized("too many rounds".into()))?;
+                Ok(StatelessTransition::Continue(
+                    self.seal(agg_param, nonce, next_round, &state)?,
+                    share,
+                ))
+            }
+            PrepareTransition::Finish(output_share) => {
+                Ok(StatelessTransition::Finish(output_share))
+            }
+        }
+    }
+
+    /// Returns the context a state is sealed with. Every part but the aggregation parameter has
+    /// a fixed length, so the encoding is unambiguous.
+    fn context(
+        &self,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        round: u64,
+    ) -> Result<Vec<u8>, VdafError> {
+        let mut context = self.task_id.as_bytes().to_vec();
+        context.extend_from_slice(&self.verify_key);
+        context.extend_from_slice(&(self.agg_id as u64).to_be_bytes());
+        context.extend_from_slice(nonce);
+        context.extend_from_slice(&round.to_be_bytes());
+        agg_param.encode(&mut context)?;
+        Ok(context)
+    }
+
+    fn seal(
+        &self,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        round: u64,
+        state: &V::PrepareState,
+    ) -> Result<OpaqueState, VdafError> {
+        Ok(self.state_key.seal(
+            &self.context(agg_param, nonce, round)?,
+            &state.get_encoded()?,
+        ))
+    }
+
+    fn open(
+        &self,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        round: u64,
+        state: &OpaqueState,
+    ) -> Result<V::PrepareState, VdafError> {
+        let plaintext = self
+            .state_key
+            .open(&self.context(agg_param, nonce, round)?, state)?;
+        Ok(V::PrepareState::get_decoded_with_param(
+            &(self.vdaf, self.agg_id),
+            &plaintext,
+        )?)
+    }
+}
+
+impl<'a, V: Debug, const VERIFY_KEY_SIZE: usize, const NONCE_SIZE: usize> Debug
+    for StatelessHelper<'a, V, VERIFY_KEY_SIZE, NONCE_SIZE>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatelessHelper")
+            .field("vdaf", &self.vdaf)
+            .field("task_id", &self.task_id)
+            .field("agg_id", &self.agg_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{poplar1::Poplar1, prio3::Prio3, Client, Collector};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn seal_and_open() {
+        let key = HelperStateKey::new(&[1; 32]);
+        let sealed = key.seal(b"context", b"prepare state");
+        assert_eq!(key.open(b"context", &sealed).unwrap(), b"prepare state");
+
+        // The nonce is random, so sealing the same state twice gives different blobs.
+        assert_ne!(key.seal(b"context", b"prepare state"), sealed);
+
+        let encoded = sealed.get_encoded().unwrap();
+        assert_eq!(OpaqueState::get_decoded(&encoded).unwrap(), sealed);
+
+        assert_matches!(
+            key.open(b"other context", &sealed),
+            Err(VdafError::CorruptedState(_))
+        );
+        assert_matches!(
+            HelperStateKey::new(&[2; 32]).open(b"context", &sealed),
+            Err(VdafError::CorruptedState(_))
+        );
+        let mut tampered = sealed.as_ref().to_vec();
+        tampered[NONCE_LEN] ^= 1;
+        assert_matches!(
+            key.open(b"context", &OpaqueState::from_bytes(tampered)),
+            Err(VdafError::CorruptedState(_))
+        );
+        assert_matches!(
+            key.open(b"context", &OpaqueState::from_bytes(vec![0; 40])),
+            Err(VdafError::CorruptedState(_))
+        );
+    }
+
+    #[cfg(feature = "secure-memory")]
+    #[test]
+    fn seal_and_open_locked() {
+        let mut secret = [1; 32];
+        let locked = HelperStateKey::new_locked(&mut secret).unwrap();
+        assert_eq!(secret, [0; 32]);
+
+        // The locked key is the same as the one derived in ordinary memory.
+        let key = HelperStateKey::new(&[1; 32]);
+        let sealed = key.seal(b"context", b"prepare state");
+        assert_eq!(
+            locked.clone().open(b"context", &sealed).unwrap(),
+            b"prepare state"
+        );
+        let sealed = locked.seal(b"context", b"prepare state");
+        assert_eq!(key.open(b"context", &sealed).unwrap(), b"prepare state");
+    }
+
+    #[test]
+    fn stateless_helper_prio3() {
+        let vdaf = Prio3::new_sum_vec(2, 2, 3, 1).unwrap();
+        let verify_key = [7; 16];
+        let nonce = [3; 16];
+        let (public_share, input_shares) = vdaf.shard(&vec![1, 0, 3], &nonce).unwrap();
+
+        let (leader_state, leader_share) = vdaf
+            .prepare_init(&verify_key, 0, &(), &nonce, &public_share, &input_shares[0])
+            .unwrap();
+        let task_id = TaskId::from([5; 32]);
+        let helper =
+            StatelessHelper::new(&vdaf, task_id, verify_key, 1, HelperStateKey::new(&[1; 32]));
+        let (helper_state, helper_share) = helper
+            .prepare_init(&(), &nonce, &public_share, &input_shares[1])
+            .unwrap();
+        let message = vdaf
+            .prepare_shares_to_prepare_message(&(), [leader_share, helper_share])
+            .unwrap();
+
+        // A different server of the helper, with the same key, continues from the blob. The blob
+        // is bound to the report, the round, the task and the verify key.
+        let other_server =
+            StatelessHelper::new(&vdaf, task_id, verify_key, 1, HelperStateKey::new(&[1; 32]));
+        assert_matches!(
+            other_server.prepare_next(&(), &[4; 16], 0, &helper_state, message.clone()),
+            Err(VdafError::CorruptedState(_))
+        );
+        assert_matches!(
+            other_server.prepare_next(&(), &nonce, 1, &helper_state, message.clone()),
+            Err(VdafError::CorruptedState(_))
+        );
+        let other_task = StatelessHelper::new(
+            &vdaf,
+            TaskId::from([6; 32]),
+            verify_key,
+            1,
+            HelperStateKey::new(&[1; 32]),
+        );
+        assert_matches!(
+            other_task.prepare_next(&(), &nonce, 0, &helper_state, message.clone()),
+            Err(VdafError::CorruptedState(_))
+        );
+        let other_verify_key =
+            StatelessHelper::new(&vdaf, task_id, [8; 16], 1, HelperStateKey::new(&[1; 32]));
+        assert_matches!(
+            other_verify_key.prepare_next(&(), &nonce, 0, &helper_state, message.clone()),
+            Err(VdafError::CorruptedState(_))
+        );
+        let helper_out = match other_server
+            .prepare_next(&(), &nonce, 0, &helper_state, message.clone())
+            .unwrap()
+        {
+            StatelessTransition::Finish(out) => out,
+            StatelessTransition::Continue(..) => panic!("unexpected transition"),
+        };
+        let leader_out = match vdaf.prepare_next(leader_state, message).unwrap() {
+            PrepareTransition::Finish(out) => out,
+            PrepareTransition::Continue(..) => panic!("unexpected transition"),
+        };
+
+        let agg_shares = [leader_out, helper_out]
+            .into_iter()
+            .map(|out| vdaf.aggregate(&(), [out]).unwrap());
+        assert_eq!(vdaf.unshard(&(), agg_shares, 1).unwrap(), vec![1, 0, 3]);
+    }
+
+    #[test]
+    fn stateless_helper_multiple_rounds() {
+        let vdaf = Poplar1::new_turboshake128(4);
+        let verify_key = [7; 16];
+        let nonce = [3; 16];
+        let agg_param = crate::vdaf::poplar1::Poplar1AggregationParam::try_from_prefixes(vec![
+            crate::idpf::IdpfInput::from_bools(&[false, true]),
+            crate::idpf::IdpfInput::from_bools(&[true, true]),
+        ])
+        .unwrap();
+        let (public_share, input_shares) = vdaf
+            .shard(
+                &crate::idpf::IdpfInput::from_bools(&[false, true, true, false]),
+                &nonce,
+            )
+            .unwrap();
+
+        let (mut leader_state, leader_share) = vdaf
+            .prepare_init(
+                &verify_key,
+                0,
+                &agg_param,
+                &nonce,
+                &public_share,
+                &input_shares[0],
+            )
+            .unwrap();
+        let helper = StatelessHelper::new(
+            &vdaf,
+            TaskId::from([5; 32]),
+            verify_key,
+            1,
+            HelperStateKey::new(&[1; 32]),
+        );
+        let (mut helper_state, helper_share) = helper
+            .prepare_init(&agg_param, &nonce, &public_share, &input_shares[1])
+            .unwrap();
+        let mut shares = [leader_share, helper_share];
+        let other_agg_param =
+            crate::vdaf::poplar1::Poplar1AggregationParam::try_from_prefixes(vec![
+                crate::idpf::IdpfInput::from_bools(&[false, true]),
+            ])
+            .unwrap();
+
+        let mut round = 0;
+        let mut previous_state = None;
+        let (leader_out, helper_out) = loop {
+            let message = vdaf
+                .prepare_shares_to_prepare_message(&agg_param, shares)
+                .unwrap();
+
+            // The blob is bound to the aggregation parameter, and the blob of an earlier round
+            // can't be replayed in this one.
+            assert_matches!(
+                helper.prepare_next(
+                    &other_agg_param,
+                    &nonce,
+                    round,
+                    &helper_state,
+                    message.clone()
+                ),
+                Err(VdafError::CorruptedState(_))
+            );
+            if let Some(previous_state) = &previous_state {
+                assert_matches!(
+                    helper.prepare_next(&agg_param, &nonce, round, previous_state, message.clone()),
+                    Err(VdafError::CorruptedState(_))
+                );
+            }
+
+            let leader = vdaf.prepare_next(leader_state, message.clone()).unwrap();
+            let helper = helper
+                .prepare_next(&agg_param, &nonce, round, &helper_state, message)
+                .unwrap();
+            round += 1;
+            match (leader, helper) {
+                (
+                    PrepareTransition::Continue(state, leader_share),
+                    StatelessTransition::Continue(sealed, helper_share),
+                ) => {
+                    leader_state = state;
+                    previous_state = Some(std::mem::replace(&mut helper_state, sealed));
+                    shares = [leader_share, helper_share];
+                }
+                (PrepareTransition::Finish(leader), StatelessTransition::Finish(helper)) => {
+                    break (leader, helper)
+                }
+                _ => panic!("aggregators disagree on the number of rounds"),
+            }
+        };
+
+        let agg_shares = [leader_out, helper_out]
+            .into_iter()
+            .map(|out| vdaf.aggregate(&agg_param, [out]).unwrap());
+        assert_eq!(vdaf.unshard(&agg_param, agg_shares, 1).unwrap(), vec![1, 0]);
+        assert!(round > 1);
+    }
+}
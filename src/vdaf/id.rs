@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Identifiers of tasks and reports.
+//!
+//! A [`TaskId`] names a task and a [`ReportId`] names a report, which it also serves as the VDAF
+//! nonce. Both are fixed-length strings of random-looking bytes, so they are easy to confuse with
+//! each other and with keys and seeds when passed around as byte slices; the newtypes keep them
+//! apart. They are encoded as their bytes, displayed in unpadded base64url, as in DAP, and parsed
+//! from either base64url or hex. Comparisons run in constant time, as IDs are sometimes derived
+//! from secrets, for example by [`TaskConfig::task_id`](crate::vdaf::task::TaskConfig::task_id).
+//!
+//! ```
+//! use prio::vdaf::id::TaskId;
+//!
+//! let task_id = TaskId::from([0xab; 32]);
+//! let encoded = task_id.to_string();
+//! assert_eq!(encoded.len(), 43);
+//! assert_eq!(encoded.parse::<TaskId>().unwrap(), task_id);
+//! assert_eq!(format!("{task_id:x}").parse::<TaskId>().unwrap(), task_id);
+//! ```
+
+use crate::codec::{CodecError, Decode, Encode};
+use std::{
+    fmt::{self, Debug, Display, Formatter, LowerHex},
+    hash::{Hash, Hasher},
+    io::{Cursor, Read},
+    str::FromStr,
+};
+use subtle::{Choice, ConstantTimeEq};
+
+/// Errors parsing an identifier from a string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ParseIdError {
+    /// The string is neither the hex nor the base64url encoding of an identifier.
+    #[error("identifier has length {0}, which is neither hex nor base64url")]
+    Length(usize),
+
+    /// The string contains a character that is not valid in its encoding.
+    #[error("invalid character {0:?} in identifier")]
+    Character(char),
+}
+
+macro_rules! id_type {
+    ($(#[$attr:meta])* $name:ident, $len:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy)]
+        pub struct $name([u8; $len]);
+
+        impl $name {
+            /// The length of the identifier, in bytes.
+            pub const LEN: usize = $len;
+
+            /// Returns the bytes of the identifier.
+            pub fn as_bytes(&self) -> &[u8; $len] {
+                &self.0
+            }
+        }
+
+        impl From<[u8; $len]> for $name {
+            fn from(bytes: [u8; $len]) -> Self {
+                Self(bytes)
+            }
+        }
+
+        impl From<$name> for [u8; $len] {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl TryFrom<&[u8]> for $name {
+            type Error = CodecError;
+
+            fn try_from(bytes: &[u8]) -> Result<Self, CodecError> {
+                Ok(Self(
+                    bytes.try_into().map_err(|_| CodecError::UnexpectedValue)?,
+                ))
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl ConstantTimeEq for $name {
+            fn ct_eq(&self, other: &Self) -> Choice {
+                self.0.ct_eq(&other.0)
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.ct_eq(other).into()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str(&base64url_encode(&self.0))
+            }
+        }
+
+        impl Debug for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({self})", stringify!($name))
+            }
+        }
+
+        impl LowerHex for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIdError;
+
+            fn from_str(s: &str) -> Result<Self, ParseIdError> {
+                let mut bytes = [0; $len];
+                parse_id(s, &mut bytes)?;
+                Ok(Self(bytes))
+            }
+        }
+
+        impl Encode for $name {
+            fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+                bytes.extend_from_slice(&self.0);
+                Ok(())
+            }
+
+            fn encoded_len(&self) -> Option<usize> {
+                Some($len)
+            }
+        }
+
+        impl Decode for $name {
+            fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+                let mut id = [0; $len];
+                bytes.read_exact(&mut id)?;
+                Ok(Self(id))
+            }
+        }
+    };
+}
+
+id_type!(
+    /// The identifier of a task.
+    TaskId,
+    32
+);
+
+id_type!(
+    /// The identifier of a report, which is also its VDAF nonce.
+    ReportId,
+    16
+);
+
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes `bytes` in base64url, without padding.
+fn base64url_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 4 + 2) / 3);
+    for chunk in bytes.chunks(3) {
+        let mut block = [0; 3];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from_be_bytes([0, block[0], block[1], block[2]]);
+        for i in 0..chunk.len() + 1 {
+            let sextet = (bits >> (18 - 6 * i)) & 0x3f;
+            encoded.push(char::from(BASE64URL_ALPHABET[sextet as usize]));
+        }
+    }
+    encoded
+}
+
+/// Parses `s` into `out`, as hex if it is twice as long as `out` and as unpadded base64url if it
+/// has the length of the base64url encoding of `out`.
+fn parse_id(s: &str, out: &mut [u8]) -> Result<(), ParseIdError> {
+    if s.len() == 2 * out.len() {
+        for (byte, pair) in out.iter_mut().zip(s.as_bytes().chunks(2)) {
+            *byte = (hex_digit(pair[0])? << 4) | hex_digit(pair[1])?;
+        }
+        return Ok(());
+    }
+    if s.len() != (out.len() * 4 + 2) / 3 {
+        return Err(ParseIdError::Length(s.len()));
+    }
+    let mut bits = 0u32;
+    let mut num_bits = 0;
+    let mut i = 0;
+    for c in s.bytes() {
+        let sextet = BASE64URL_ALPHABET
+            .iter()
+            .position(|a| *a == c)
+            .ok_or(ParseIdError::Character(char::from(c)))?;
+        bits = (bits << 6) | sextet as u32;
+        num_bits += 6;
+        if num_bits >= 8 {
+            num_bits -= 8;
+            out[i] = (bits >> num_bits) as u8;
+            bits &= (1 << num_bits) - 1;
+            i += 1;
+        }
+    }
+    // The leftover bits of the last character must be zero, so that the encoding is canonical.
+    if bits != 0 {
+        return Err(ParseIdError::Character(char::from(
+            s.as_bytes()[s.len() - 1],
+        )));
+    }
+    Ok(())
+}
+
+fn hex_digit(c: u8) -> Result<u8, ParseIdError> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(ParseIdError::Character(char::from(c))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+    #[test]
+    fn id_round_trip() {
+        for seed in 0..=255u8 {
+            let mut bytes = [0; 32];
+            bytes
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, b)| *b = seed ^ (i as u8).wrapping_mul(37));
+            let task_id = TaskId::from(bytes);
+            let encoded = task_id.to_string();
+            assert_eq!(encoded, URL_SAFE_NO_PAD.encode(bytes));
+            assert_eq!(encoded.parse::<TaskId>().unwrap(), task_id);
+            assert_eq!(format!("{task_id:x}").parse::<TaskId>().unwrap(), task_id);
+
+            let report_id = ReportId::try_from(&bytes[..16]).unwrap();
+            assert_eq!(report_id.to_string(), URL_SAFE_NO_PAD.encode(&bytes[..16]));
+            assert_eq!(
+                report_id.to_string().parse::<ReportId>().unwrap(),
+                report_id
+            );
+            assert_eq!(
+                ReportId::get_decoded(&report_id.get_encoded().unwrap()).unwrap(),
+                report_id
+            );
+        }
+
+        let report_id = ReportId::from([0xff; 16]);
+        assert_eq!(format!("{report_id:?}"), "ReportId(_____________________w)");
+        assert_eq!(
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+                .parse::<ReportId>()
+                .unwrap(),
+            report_id
+        );
+    }
+
+    #[test]
+    fn id_parse_errors() {
+        assert_eq!("".parse::<ReportId>(), Err(ParseIdError::Length(0)));
+        assert_eq!(
+            "AAAAAAAAAAAAAAAAAAAAA".parse::<ReportId>(),
+            Err(ParseIdError::Length(21))
+        );
+        assert_eq!(
+            "AAAAAAAAAAAAAAAAAAAA+A".parse::<ReportId>(),
+            Err(ParseIdError::Character('+'))
+        );
+        // The last character carries four unused bits, which must be zero.
+        assert_eq!(
+            "AAAAAAAAAAAAAAAAAAAAAB".parse::<ReportId>(),
+            Err(ParseIdError::Character('B'))
+        );
+        assert_eq!(
+            "0000000000000000000000000000000g".parse::<ReportId>(),
+            Err(ParseIdError::Character('g'))
+        );
+        assert!(ReportId::try_from(&[0; 15][..]).is_err());
+        assert!(ReportId::get_decoded(&[0; 15]).is_err());
+    }
+}
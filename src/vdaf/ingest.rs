@@ -0,0 +1,360 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A bounded queue for incoming reports.
+//!
+//! An Aggregator service receives reports faster than it can verify them one at a time, and needs
+//! to push back on clients when it falls behind. [`Ingestor`] buffers submitted items (typically
+//! [`ReportShare`](crate::vdaf::report::ReportShare)s) in a queue of fixed capacity, and a worker
+//! thread hands them to a handler in batches, which the handler is free to verify in parallel.
+//! Submission fails rather than buffering without bound: [`Ingestor::try_submit`] returns
+//! [`IngestError::Full`] when the queue is full, and both submission methods return
+//! [`IngestError::RateLimited`] when a configured rate limit is exceeded. The item is handed back
+//! with the error, so the caller can retry or reject it.
+//!
+//! The queue uses a standard library channel and thread rather than an async runtime. An async
+//! service can call [`Ingestor::try_submit`] from a task without blocking it. With the `metrics`
+//! feature, the queue depth is reported as the gauge `prio_ingest_queue_depth`.
+
+use crate::vdaf::{telemetry, VdafError};
+use std::{
+    fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+/// Errors returned when submitting an item to an [`Ingestor`]. Each carries the item back.
+#[derive(thiserror::Error)]
+#[non_exhaustive]
+pub enum IngestError<T> {
+    /// The queue is full.
+    #[error("ingestion queue is full")]
+    Full(T),
+
+    /// The rate limit has been exceeded.
+    #[error("ingestion rate limit exceeded")]
+    RateLimited(T),
+
+    /// The worker has stopped, because the handler panicked.
+    #[error("ingestion worker has stopped")]
+    Closed(T),
+}
+
+impl<T> IngestError<T> {
+    /// Returns the item that could not be submitted.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Full(item) | Self::RateLimited(item) | Self::Closed(item) => item,
+        }
+    }
+}
+
+impl<T> Debug for IngestError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Full(_) => "Full",
+            Self::RateLimited(_) => "RateLimited",
+            Self::Closed(_) => "Closed",
+        };
+        write!(f, "{name}(..)")
+    }
+}
+
+/// Configuration of an [`Ingestor`].
+#[derive(Clone, Debug)]
+pub struct IngestorConfig {
+    /// The maximum number of items waiting in the queue.
+    pub capacity: usize,
+
+    /// The maximum number of items passed to the handler at once.
+    pub batch_size: usize,
+
+    /// The maximum number of items accepted per second, or `None` for no limit. Up to this many
+    /// items may be accepted in a burst.
+    pub rate_limit: Option<u32>,
+}
+
+impl Default for IngestorConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            batch_size: 64,
+            rate_limit: None,
+        }
+    }
+}
+
+/// A token bucket holding up to `rate` tokens, refilled at `rate` tokens per second.
+#[derive(Debug)]
+struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: f64::from(rate),
+            tokens: f64::from(rate),
+            last: Instant::now(),
+        }
+    }
+
+    fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A bounded queue whose items are passed in batches to a handler on a worker thread. See the
+/// [module documentation](self) for details.
+pub struct Ingestor<T> {
+    sender: Option<SyncSender<T>>,
+    depth: Arc<AtomicUsize>,
+    rate_limiter: Option<Mutex<RateLimiter>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> Ingestor<T> {
+    /// Starts a worker thread that passes batches of submitted items to `handler`. A batch holds
+    /// the items that are waiting when the worker becomes free, up to `config.batch_size`.
+    pub fn spawn<H>(config: IngestorConfig, mut handler: H) -> Result<Self, VdafError>
+    where
+        H: FnMut(Vec<T>) + Send + 'static,
+    {
+        if config.capacity == 0 || config.batch_size == 0 || config.rate_limit == Some(0) {
+            return Err(VdafError::Uncategorized(
+                "capacity, batch size and rate limit must be positive".into(),
+            ));
+        }
+        let (sender, receiver) = mpsc::sync_channel::<T>(config.capacity);
+        let depth = Arc::new(AtomicUsize::new(0));
+        let worker_depth = Arc::clone(&depth);
+        let batch_size = config.batch_size;
+        let worker = thread::Builder::new()
+            .name("prio-ingestor".into())
+            .spawn(move || {
+                while let Ok(item) = receiver.recv() {
+                    let mut batch = vec![item];
+                    while batch.len() < batch_size {
+                        match receiver.try_recv() {
+                            Ok(item) => batch.push(item),
+                            Err(_) => break,
+                        }
+                    }
+                    let depth = worker_depth.fetch_sub(batch.len(), Ordering::SeqCst) - batch.len();
+                    telemetry::queue_depth(depth);
+                    handler(batch);
+                }
+            })?;
+        Ok(Self {
+            sender: Some(sender),
+            depth,
+            rate_limiter: config
+                .rate_limit
+                .map(|rate| Mutex::new(RateLimiter::new(rate))),
+            worker: Some(worker),
+        })
+    }
+
+    /// Submits an item, waiting for room in the queue if it is full.
+    pub fn submit(&self, item: T) -> Result<(), IngestError<T>> {
+        let item = self.check_rate(item)?;
+        self.increment_depth();
+        match self.sender().send(item) {
+            Ok(()) => Ok(()),
+            Err(mpsc::SendError(item)) => {
+                self.decrement_depth();
+                Err(IngestError::Closed(item))
+            }
+        }
+    }
+
+    /// Submits an item if there is room in the queue.
+    pub fn try_submit(&self, item: T) -> Result<(), IngestError<T>> {
+        let item = self.check_rate(item)?;
+        self.increment_depth();
+        match self.sender().try_send(item) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.decrement_depth();
+                Err(match e {
+                    TrySendError::Full(item) => IngestError::Full(item),
+                    TrySendError::Disconnected(item) => IngestError::Closed(item),
+                })
+            }
+        }
+    }
+
+    /// Returns the number of items waiting in the queue.
+    pub fn queue_depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+
+    /// Stops accepting items and waits for the worker to handle the items already queued.
+    /// Returns an error if the handler panicked.
+    pub fn shutdown(mut self) -> Result<(), VdafError> {
+        self.stop()
+    }
+
+    fn sender(&self) -> &SyncSender<T> {
+        // The sender is only taken by `stop()`, which consumes or drops `self`.
+        self.sender.as_ref().expect("ingestor is shut down")
+    }
+
+    fn check_rate(&self, item: T) -> Result<T, IngestError<T>> {
+        match &self.rate_limiter {
+            Some(limiter) if !limiter.lock().unwrap().try_acquire() => {
+                Err(IngestError::RateLimited(item))
+            }
+            _ => Ok(item),
+        }
+    }
+
+    fn increment_depth(&self) {
+        telemetry::queue_depth(self.depth.fetch_add(1, Ordering::SeqCst) + 1);
+    }
+
+    fn decrement_depth(&self) {
+        telemetry::queue_depth(self.depth.fetch_sub(1, Ordering::SeqCst) - 1);
+    }
+
+    fn stop(&mut self) -> Result<(), VdafError> {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            worker
+                .join()
+                .map_err(|_| VdafError::Uncategorized("ingestion handler panicked".into()))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Debug for Ingestor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ingestor")
+            .field("queue_depth", &self.depth.load(Ordering::SeqCst))
+            .field("rate_limiter", &self.rate_limiter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for Ingestor<T> {
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use std::{sync::mpsc::channel, time::Duration};
+
+    /// Waits up to `timeout` for `condition` to hold.
+    fn wait_for(timeout: Duration, condition: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while !condition() {
+            if start.elapsed() > timeout {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    #[test]
+    fn ingestor_batches() {
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let batch_sizes = Arc::new(Mutex::new(Vec::new()));
+        let (unblock, blocked) = channel::<()>();
+        let ingestor = {
+            let handled = Arc::clone(&handled);
+            let batch_sizes = Arc::clone(&batch_sizes);
+            let blocked = Mutex::new(blocked);
+            Ingestor::spawn(
+                IngestorConfig {
+                    capacity: 8,
+                    batch_size: 3,
+                    rate_limit: None,
+                },
+                move |batch: Vec<u32>| {
+                    blocked.lock().unwrap().recv().unwrap();
+                    batch_sizes.lock().unwrap().push(batch.len());
+                    handled.lock().unwrap().extend(batch);
+                },
+            )
+            .unwrap()
+        };
+
+        // The worker takes the first item and blocks in the handler, so the rest queue up.
+        ingestor.submit(0).unwrap();
+        assert!(wait_for(Duration::from_secs(10), || ingestor.queue_depth() == 0));
+        for i in 1..9 {
+            ingestor.try_submit(i).unwrap();
+        }
+        assert_eq!(ingestor.queue_depth(), 8);
+        assert_matches!(ingestor.try_submit(9), Err(IngestError::Full(9)));
+
+        for _ in 0..4 {
+            unblock.send(()).unwrap();
+        }
+        ingestor.shutdown().unwrap();
+        assert_eq!(*handled.lock().unwrap(), (0..9).collect::<Vec<_>>());
+        assert_eq!(*batch_sizes.lock().unwrap(), [1, 3, 3, 2]);
+    }
+
+    #[test]
+    fn ingestor_rate_limit() {
+        let ingestor = Ingestor::spawn(
+            IngestorConfig {
+                rate_limit: Some(2),
+                ..Default::default()
+            },
+            |_: Vec<u32>| (),
+        )
+        .unwrap();
+        ingestor.submit(0).unwrap();
+        ingestor.try_submit(1).unwrap();
+        let err = ingestor.submit(2).unwrap_err();
+        assert_matches!(err, IngestError::RateLimited(_));
+        assert_eq!(err.into_inner(), 2);
+        ingestor.shutdown().unwrap();
+
+        assert!(Ingestor::<u32>::spawn(
+            IngestorConfig {
+                capacity: 0,
+                ..Default::default()
+            },
+            |_| ()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn ingestor_handler_panics() {
+        let ingestor = Ingestor::spawn(IngestorConfig::default(), |_: Vec<u32>| panic!()).unwrap();
+        ingestor.submit(0).unwrap();
+        assert!(wait_for(Duration::from_secs(10), || matches!(
+            ingestor.try_submit(1),
+            Err(IngestError::Closed(1))
+        )));
+        assert_matches!(ingestor.shutdown(), Err(VdafError::Uncategorized(_)));
+    }
+}
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Discovery of the Aggregators' encryption keys.
+//!
+//! Aggregators rotate the keys that Clients encrypt input shares to. During a rotation, an
+//! Aggregator publishes both the old and the new key, each with the interval of time in which it
+//! may be used, so that Clients with slightly skewed clocks or stale caches still pick a key the
+//! Aggregator can decrypt with. [`PublicKeyConfig`] is the list an Aggregator publishes. It can be
+//! served as JSON, through its `serde` implementations, or in the binary encoding used elsewhere in
+//! the crate.
+//!
+//! A Client fetches the config of each Aggregator and either picks a key itself with
+//! [`PublicKeyConfig::select`], or lets [`ReportBuilder::build_with_keys`] pick, for each
+//! Aggregator, the newest key of a supported algorithm that is valid at the report's timestamp.
+//! This crate does not encrypt input shares; the selected key's [`PublicKey::id`] is meant to be
+//! sent alongside the ciphertext so that the Aggregator knows which private key to use.
+//!
+//! [`ReportBuilder::build_with_keys`]: crate::vdaf::report::ReportBuilder::build_with_keys
+
+use crate::{
+    codec::{decode_u16_items, encode_u16_items, CodecError, Decode, Encode},
+    vdaf::VdafError,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// The algorithm of an encryption key, given as the HPKE cipher suite it is used with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeyAlgorithm {
+    /// The HPKE KEM identifier, e.g. `0x0020` for DHKEM(X25519, HKDF-SHA256).
+    pub kem_id: u16,
+
+    /// The HPKE KDF identifier, e.g. `0x0001` for HKDF-SHA256.
+    pub kdf_id: u16,
+
+    /// The HPKE AEAD identifier, e.g. `0x0001` for AES-128-GCM.
+    pub aead_id: u16,
+}
+
+impl Encode for KeyAlgorithm {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.kem_id.encode(bytes)?;
+        self.kdf_id.encode(bytes)?;
+        self.aead_id.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(6)
+    }
+}
+
+impl Decode for KeyAlgorithm {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            kem_id: u16::decode(bytes)?,
+            kdf_id: u16::decode(bytes)?,
+            aead_id: u16::decode(bytes)?,
+        })
+    }
+}
+
+/// A public encryption key and the interval of time, in seconds since the Unix epoch, in which
+/// Clients may use it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKey {
+    /// The identifier of the key, unique within its [`PublicKeyConfig`].
+    pub id: u8,
+
+    /// The algorithm of the key.
+    pub algorithm: KeyAlgorithm,
+
+    /// The encoded public key.
+    pub public_key: Vec<u8>,
+
+    /// The first time at which the key may be used.
+    pub not_before: u64,
+
+    /// The time from which the key may no longer be used.
+    pub not_after: u64,
+}
+
+impl PublicKey {
+    /// Returns true if the key may be used at time `now`.
+    pub fn is_valid_at(&self, now: u64) -> bool {
+        self.not_before <= now && now < self.not_after
+    }
+}
+
+impl Encode for PublicKey {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.id.encode(bytes)?;
+        self.algorithm.encode(bytes)?;
+        encode_u16_items(bytes, &(), &self.public_key)?;
+        self.not_before.encode(bytes)?;
+        self.not_after.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(1 + 6 + 2 + self.public_key.len() + 8 + 8)
+    }
+}
+
+impl Decode for PublicKey {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            id: u8::decode(bytes)?,
+            algorithm: KeyAlgorithm::decode(bytes)?,
+            public_key: decode_u16_items(&(), bytes)?,
+            not_before: u64::decode(bytes)?,
+            not_after: u64::decode(bytes)?,
+        })
+    }
+}
+
+/// The list of public keys published by an Aggregator. See the [module documentation](self) for
+/// details.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<PublicKey>", into = "Vec<PublicKey>")]
+pub struct PublicKeyConfig {
+    keys: Vec<PublicKey>,
+}
+
+impl PublicKeyConfig {
+    /// Constructs a config from a list of keys. Fails if the list is empty, if two keys have the
+    /// same ID, or if a key's validity interval is empty.
+    pub fn new(keys: Vec<PublicKey>) -> Result<Self, VdafError> {
+        if keys.is_empty() {
+            return Err(VdafError::Uncategorized(
+                "public key config has no keys".into(),
+            ));
+        }
+        for (i, key) in keys.iter().enumerate() {
+            if key.not_before >= key.not_after {
+                return Err(VdafError::Uncategorized(format!(
+                    "public key {} has an empty validity interval",
+                    key.id
+                )));
+            }
+            if keys[..i].iter().any(|other| other.id == key.id) {
+                return Err(VdafError::Uncategorized(format!(
+                    "duplicate public key ID {}",
+                    key.id
+                )));
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    /// Returns the keys, in the order they were published.
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    /// Returns the key with identifier `id`, if any. An Aggregator uses this to find the key a
+    /// Client encrypted to.
+    pub fn get(&self, id: u8) -> Option<&PublicKey> {
+        self.keys.iter().find(|key| key.id == id)
+    }
+
+    /// Picks the key to encrypt to at time `now`: among the keys that are valid at `now` and whose
+    /// algorithm is in `supported`, the one that became valid most recently. Ties go to the key
+    /// published first. Returns `None` if there is no such key.
+    pub fn select(&self, now: u64, supported: &[KeyAlgorithm]) -> Option<&PublicKey> {
+        self.keys
+            .iter()
+            .filter(|key| key.is_valid_at(now) && supported.contains(&key.algorithm))
+            .fold(None, |best: Option<&PublicKey>, key| match best {
+                Some(best) if best.not_before >= key.not_before => Some(best),
+                _ => Some(key),
+            })
+    }
+}
+
+impl TryFrom<Vec<PublicKey>> for PublicKeyConfig {
+    type Error = VdafError;
+
+    fn try_from(keys: Vec<PublicKey>) -> Result<Self, VdafError> {
+        Self::new(keys)
+    }
+}
+
+impl From<PublicKeyConfig> for Vec<PublicKey> {
+    fn from(config: PublicKeyConfig) -> Self {
+        config.keys
+    }
+}
+
+impl Encode for PublicKeyConfig {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_u16_items(bytes, &(), &self.keys)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        let mut len = 2;
+        for key in &self.keys {
+            len += key.encoded_len()?;
+        }
+        Some(len)
+    }
+}
+
+impl Decode for PublicKeyConfig {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Self::new(decode_u16_items(&(), bytes)?).map_err(|e| CodecError::Other(Box::new(e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X25519_AES128: KeyAlgorithm = KeyAlgorithm {
+        kem_id: 0x0020,
+        kdf_id: 0x0001,
+        aead_id: 0x0001,
+    };
+
+    const P256_AES128: KeyAlgorithm = KeyAlgorithm {
+        kem_id: 0x0010,
+        kdf_id: 0x0001,
+        aead_id: 0x0001,
+    };
+
+    fn key(id: u8, algorithm: KeyAlgorithm, not_before: u64, not_after: u64) -> PublicKey {
+        PublicKey {
+            id,
+            algorithm,
+            public_key: vec![id; 32],
+            not_before,
+            not_after,
+        }
+    }
+
+    #[test]
+    fn select_public_key() {
+        let config = PublicKeyConfig::new(vec![
+            key(1, X25519_AES128, 0, 200),
+            key(2, X25519_AES128, 100, 300),
+            key(3, P256_AES128, 150, 400),
+        ])
+        .unwrap();
+
+        assert_eq!(config.select(50, &[X25519_AES128]).unwrap().id, 1);
+        // During the rotation the newer key is preferred.
+        assert_eq!(config.select(150, &[X25519_AES128]).unwrap().id, 2);
+        assert_eq!(
+            config
+                .select(150, &[X25519_AES128, P256_AES128])
+                .unwrap()
+                .id,
+            3
+        );
+        assert_eq!(config.select(250, &[X25519_AES128]).unwrap().id, 2);
+        assert!(config.select(300, &[X25519_AES128]).is_none());
+        assert!(config.select(50, &[P256_AES128]).is_none());
+        assert_eq!(config.get(3).unwrap().algorithm, P256_AES128);
+        assert!(config.get(4).is_none());
+    }
+
+    #[test]
+    fn public_key_config_encoding() {
+        let config = PublicKeyConfig::new(vec![
+            key(1, X25519_AES128, 0, 200),
+            key(2, P256_AES128, 5, 6),
+        ])
+        .unwrap();
+        let encoded = config.get_encoded().unwrap();
+        assert_eq!(Some(encoded.len()), config.encoded_len());
+        assert_eq!(PublicKeyConfig::get_decoded(&encoded).unwrap(), config);
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PublicKeyConfig>(&json).unwrap(),
+            config
+        );
+
+        // Invalid configs are rejected when decoding too.
+        let duplicate = vec![key(1, X25519_AES128, 0, 200), key(1, P256_AES128, 5, 6)];
+        assert!(PublicKeyConfig::new(duplicate.clone()).is_err());
+        assert!(serde_json::from_value::<PublicKeyConfig>(
+            serde_json::to_value(&duplicate).unwrap()
+        )
+        .is_err());
+        let mut encoded = Vec::new();
+        encode_u16_items(&mut encoded, &(), &duplicate).unwrap();
+        assert!(PublicKeyConfig::get_decoded(&encoded).is_err());
+        assert!(PublicKeyConfig::new(Vec::new()).is_err());
+        assert!(PublicKeyConfig::new(vec![key(1, X25519_AES128, 10, 10)]).is_err());
+    }
+}
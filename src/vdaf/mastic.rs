@@ -0,0 +1,978 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Weighted heavy hitters, in the style of Mastic.
+//!
+//! Each Client holds an input string of `bits` bits and a weight, such as the number of bytes
+//! transferred, that is a measurement of an FLP [`Type`]. It splits the point function that maps
+//! every prefix of its input to the encoded weight with the [VIDPF](crate::vidpf), and proves with
+//! [SZK](crate::flp::szk) that the weight at the root of the prefix tree is valid for the type.
+//! The Aggregators evaluate their keys at a set of candidate prefixes, all of the same length,
+//! given by the [aggregation parameter](MasticAggregationParam); unsharding yields, for each
+//! prefix, the total weight of the inputs that begin with it. With
+//! [`Count`](crate::flp::types::Count), which only accepts a weight of 1, the result is a count of
+//! inputs, as with [`Poplar1`](crate::vdaf::poplar1::Poplar1). With
+//! [`Sum`](crate::flp::types::Sum), each input contributes a bounded integer weight.
+//!
+//! Only the weight at the root is range-checked, so preparation also checks that the Client's
+//! VIDPF keys carry that weight, and nothing else, down to each candidate prefix. Each Aggregator
+//! evaluates every prefix of the candidates together with its sibling, and the Aggregators compare
+//! a hash of
+//!
+//! - the VIDPF proof at each of these nodes, which match only if the keys are one-hot: at each
+//!   level, at most one of the evaluated nodes has a non-zero weight; and
+//! - their shares of the difference between the weight at each evaluated node and the sum of the
+//!   weights at its two children, which cancel only if the weights are consistent along the path.
+//!
+//! Together, these ensure that each candidate prefix has either weight zero or the validated root
+//! weight. The cost of preparation grows with the number of candidates times their length.
+//!
+//! [`sparse::SparseVec`] builds sums of sparse vectors on Mastic, with one report per non-zero
+//! entry.
+
+use crate::{
+    codec::{CodecError, Decode, Encode, ParameterizedDecode},
+    field::{decode_fieldvec, FftFriendlyFieldElement, FieldElement},
+    flp::{
+        szk::{Szk, SzkProofShare, SzkVerifier},
+        Type,
+    },
+    vdaf::{
+        poplar1::Poplar1AggregationParam,
+        telemetry,
+        xof::{Seed, XofTurboShake128},
+        Aggregatable, AggregateShare, Aggregator, Client, Collector, OutputShare,
+        PrepareTransition, RejectionReason, Vdaf, VdafError,
+    },
+    vidpf::{Vidpf, VidpfInput, VidpfKey, VidpfPublicShare, VidpfWeight},
+};
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Debug},
+    io::{Cursor, Read},
+};
+use subtle::{Choice, ConditionallyNegatable, ConstantTimeEq};
+
+pub mod sparse;
+
+/// The algorithm identifier used to derive the SZK randomness. This is in the range reserved for
+/// private use, since Mastic has no assigned codepoint.
+const MASTIC_ALGORITHM_ID: u32 = 0xFFFF_0001;
+
+/// Domain separation tag for the hash of the VIDPF proofs and path checks.
+const EVAL_PROOF_DST: &[u8] = b"mastic eval proof";
+
+/// The public share of a Mastic report.
+pub type MasticPublicShare<F> = VidpfPublicShare<VidpfWeight<F>>;
+
+/// The aggregation parameter of [`Mastic`]: the candidate prefixes, all of the same length and in
+/// lexicographic order. It is the same as that of [`Poplar1`](crate::vdaf::poplar1::Poplar1).
+pub type MasticAggregationParam = Poplar1AggregationParam;
+
+/// An Aggregator's share of a Mastic report.
+#[derive(Clone)]
+pub struct MasticInputShare<F: FftFriendlyFieldElement> {
+    vidpf_key: VidpfKey,
+    proof_share: SzkProofShare<F, 16>,
+}
+
+impl<F: FftFriendlyFieldElement> Debug for MasticInputShare<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MasticInputShare")
+            .field("vidpf_key", &self.vidpf_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Encode for MasticInputShare<F> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.vidpf_key.encode(bytes)?;
+        match &self.proof_share {
+            SzkProofShare::Leader {
+                uncompressed_proof_share,
+                leader_blind_and_helper_joint_rand_part,
+            } => {
+                for x in uncompressed_proof_share {
+                    x.encode(bytes)?;
+                }
+                if let Some((blind, helper_joint_rand_part)) =
+                    leader_blind_and_helper_joint_rand_part
+                {
+                    blind.encode(bytes)?;
+                    helper_joint_rand_part.encode(bytes)?;
+                }
+            }
+            SzkProofShare::Helper {
+                proof_share_seed_and_blind,
+                leader_joint_rand_part,
+            } => {
+                proof_share_seed_and_blind.encode(bytes)?;
+                if let Some(leader_joint_rand_part) = leader_joint_rand_part {
+                    leader_joint_rand_part.encode(bytes)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(
+            self.vidpf_key.encoded_len()?
+                + match &self.proof_share {
+                    SzkProofShare::Leader {
+                        uncompressed_proof_share,
+                        leader_blind_and_helper_joint_rand_part,
+                    } => {
+                        F::ENCODED_SIZE * uncompressed_proof_share.len()
+                            + leader_blind_and_helper_joint_rand_part
+                                .as_ref()
+                                .map_or(0, |_| 32)
+                    }
+                    SzkProofShare::Helper {
+                        leader_joint_rand_part,
+                        ..
+                    } => 16 + leader_joint_rand_part.as_ref().map_or(0, |_| 16),
+                },
+        )
+    }
+}
+
+impl<'a, T: Type> ParameterizedDecode<(&'a Mastic<T>, usize)> for MasticInputShare<T::Field> {
+    fn decode_with_param(
+        (mastic, agg_id): &(&'a Mastic<T>, usize),
+        bytes: &mut Cursor<&[u8]>,
+    ) -> Result<Self, CodecError> {
+        let vidpf_key = VidpfKey::decode_with_param(agg_id, bytes)?;
+        let has_joint_rand = mastic.szk.has_joint_rand();
+        let proof_share = if *agg_id == 0 {
+            SzkProofShare::Leader {
+                uncompressed_proof_share: decode_fieldvec(mastic.typ.proof_len(), bytes)?,
+                leader_blind_and_helper_joint_rand_part: if has_joint_rand {
+                    Some((Seed::decode(bytes)?, Seed::decode(bytes)?))
+                } else {
+                    None
+                },
+            }
+        } else {
+            SzkProofShare::Helper {
+                proof_share_seed_and_blind: Seed::decode(bytes)?,
+                leader_joint_rand_part: if has_joint_rand {
+                    Some(Seed::decode(bytes)?)
+                } else {
+                    None
+                },
+            }
+        };
+        Ok(Self {
+            vidpf_key,
+            proof_share,
+        })
+    }
+}
+
+impl<T: Type> ParameterizedDecode<Mastic<T>> for MasticPublicShare<T::Field> {
+    fn decode_with_param(
+        mastic: &Mastic<T>,
+        bytes: &mut Cursor<&[u8]>,
+    ) -> Result<Self, CodecError> {
+        Self::decode_with_param(&(mastic.bits, mastic.typ.input_len()), bytes)
+    }
+}
+
+/// The state an Aggregator keeps between [`Mastic::prepare_init`] and [`Mastic::prepare_next`].
+#[derive(Clone)]
+pub struct MasticPrepareState<F> {
+    joint_rand_seed: Option<Seed<16>>,
+    verifier_len: usize,
+    output_share: OutputShare<F>,
+}
+
+impl<F: ConstantTimeEq> PartialEq for MasticPrepareState<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<F: ConstantTimeEq> Eq for MasticPrepareState<F> {}
+
+impl<F: ConstantTimeEq> ConstantTimeEq for MasticPrepareState<F> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // As with Prio3, we allow short-circuiting on the presence or absence of the joint
+        // randomness seed and on the verifier length.
+        if self.verifier_len != other.verifier_len {
+            return Choice::from(0);
+        }
+        let joint_rand_seed_eq = match (&self.joint_rand_seed, &other.joint_rand_seed) {
+            (Some(left), Some(right)) => left.ct_eq(right),
+            (None, None) => Choice::from(1),
+            _ => return Choice::from(0),
+        };
+        joint_rand_seed_eq & self.output_share.ct_eq(&other.output_share)
+    }
+}
+
+impl<F> Debug for MasticPrepareState<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MasticPrepareState")
+            .field("verifier_len", &self.verifier_len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The message an Aggregator sends to its peer after [`Mastic::prepare_init`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MasticPrepareShare<F> {
+    verifier: SzkVerifier<F>,
+    joint_rand_part: Option<Seed<16>>,
+    eval_proof: [u8; 32],
+}
+
+impl<F: FieldElement> Encode for MasticPrepareShare<F> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        for x in &self.verifier {
+            x.encode(bytes)?;
+        }
+        if let Some(joint_rand_part) = &self.joint_rand_part {
+            joint_rand_part.encode(bytes)?;
+        }
+        bytes.extend_from_slice(&self.eval_proof);
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(
+            F::ENCODED_SIZE * self.verifier.len()
+                + self.joint_rand_part.as_ref().map_or(0, |_| 16)
+                + 32,
+        )
+    }
+}
+
+impl<F: FieldElement> ParameterizedDecode<MasticPrepareState<F>> for MasticPrepareShare<F> {
+    fn decode_with_param(
+        state: &MasticPrepareState<F>,
+        bytes: &mut Cursor<&[u8]>,
+    ) -> Result<Self, CodecError> {
+        let verifier = decode_fieldvec(state.verifier_len, bytes)?;
+        let joint_rand_part = state
+            .joint_rand_seed
+            .as_ref()
+            .map(|_| Seed::decode(bytes))
+            .transpose()?;
+        let mut eval_proof = [0; 32];
+        bytes.read_exact(&mut eval_proof)?;
+        Ok(Self {
+            verifier,
+            joint_rand_part,
+            eval_proof,
+        })
+    }
+}
+
+/// The message both Aggregators pass to [`Mastic::prepare_next`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MasticPrepareMessage<F> {
+    verifier: SzkVerifier<F>,
+    leader_joint_rand_part: Option<Seed<16>>,
+    helper_joint_rand_part: Option<Seed<16>>,
+}
+
+impl<F: FieldElement> Encode for MasticPrepareMessage<F> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        for x in &self.verifier {
+            x.encode(bytes)?;
+        }
+        if let (Some(leader), Some(helper)) =
+            (&self.leader_joint_rand_part, &self.helper_joint_rand_part)
+        {
+            leader.encode(bytes)?;
+            helper.encode(bytes)?;
+        }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(
+            F::ENCODED_SIZE * self.verifier.len()
+                + self.leader_joint_rand_part.as_ref().map_or(0, |_| 32),
+        )
+    }
+}
+
+impl<F: FieldElement> ParameterizedDecode<MasticPrepareState<F>> for MasticPrepareMessage<F> {
+    fn decode_with_param(
+        state: &MasticPrepareState<F>,
+        bytes: &mut Cursor<&[u8]>,
+    ) -> Result<Self, CodecError> {
+        let verifier = decode_fieldvec(state.verifier_len, bytes)?;
+        let (leader_joint_rand_part, helper_joint_rand_part) = if state.joint_rand_seed.is_some() {
+            (Some(Seed::decode(bytes)?), Some(Seed::decode(bytes)?))
+        } else {
+            (None, None)
+        };
+        Ok(Self {
+            verifier,
+            leader_joint_rand_part,
+            helper_joint_rand_part,
+        })
+    }
+}
+
+impl<'a, T: Type> ParameterizedDecode<(&'a Mastic<T>, &'a MasticAggregationParam)>
+    for OutputShare<T::Field>
+{
+    fn decode_with_param(
+        (mastic, agg_param): &(&'a Mastic<T>, &'a MasticAggregationParam),
+        bytes: &mut Cursor<&[u8]>,
+    ) -> Result<Self, CodecError> {
+        decode_fieldvec(mastic.output_len(agg_param), bytes).map(Self)
+    }
+}
+
+impl<'a, T: Type> ParameterizedDecode<(&'a Mastic<T>, &'a MasticAggregationParam)>
+    for AggregateShare<T::Field>
+{
+    fn decode_with_param(
+        (mastic, agg_param): &(&'a Mastic<T>, &'a MasticAggregationParam),
+        bytes: &mut Cursor<&[u8]>,
+    ) -> Result<Self, CodecError> {
+        decode_fieldvec(mastic.output_len(agg_param), bytes).map(Self)
+    }
+}
+
+/// Weighted heavy hitters over `bits`-bit inputs, with weights validated by the FLP type `T`. See
+/// the [module documentation](self) for details.
+pub struct Mastic<T: Type> {
+    bits: usize,
+    typ: T,
+    szk: Szk<T, XofTurboShake128, 16>,
+    vidpf: Vidpf<VidpfWeight<T::Field>, 16>,
+}
+
+impl<T: Type> Mastic<T> {
+    /// Constructs an instance for `bits`-bit inputs whose weights are measurements of `typ`.
+    pub fn new(bits: usize, typ: T) -> Result<Self, VdafError> {
+        if bits == 0 || bits > usize::from(u16::MAX) {
+            return Err(VdafError::Uncategorized(format!(
+                "bits must be between 1 and {}",
+                u16::MAX
+            )));
+        }
+        Ok(Self {
+            bits,
+            szk: Szk::new_turboshake128(typ.clone(), MASTIC_ALGORITHM_ID),
+            vidpf: Vidpf::new(typ.input_len()),
+            typ,
+        })
+    }
+
+    /// Returns the length of the inputs in bits.
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Returns the length of an output share for `agg_param`.
+    fn output_len(&self, agg_param: &MasticAggregationParam) -> usize {
+        self.typ.output_len() * agg_param.prefixes().len()
+    }
+
+    /// Returns the share of the weight at the root of the prefix tree, the sum of the shares at
+    /// its two children.
+    fn root_share(
+        &self,
+        key: &VidpfKey,
+        public_share: &MasticPublicShare<T::Field>,
+        nonce: &[u8; 16],
+    ) -> Result<VidpfWeight<T::Field>, VdafError> {
+        let left = self
+            .vidpf
+            .eval(key, public_share, &VidpfInput::from_bools(&[false]), nonce)?;
+        let right = self
+            .vidpf
+            .eval(key, public_share, &VidpfInput::from_bools(&[true]), nonce)?;
+        Ok(left.share + right.share)
+    }
+}
+
+impl<T: Type> Clone for Mastic<T> {
+    fn clone(&self) -> Self {
+        Self {
+            bits: self.bits,
+            typ: self.typ.clone(),
+            szk: Szk::new_turboshake128(self.typ.clone(), MASTIC_ALGORITHM_ID),
+            vidpf: Vidpf::new(self.typ.input_len()),
+        }
+    }
+}
+
+impl<T: Type> Debug for Mastic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mastic")
+            .field("bits", &self.bits)
+            .field("typ", &self.typ)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Type> Vdaf for Mastic<T> {
+    /// An input string and its weight.
+    type Measurement = (VidpfInput, T::Measurement);
+    /// The total weight of the inputs beginning with each candidate prefix.
+    type AggregateResult = Vec<T::AggregateResult>;
+    type AggregationParam = MasticAggregationParam;
+    type PublicShare = MasticPublicShare<T::Field>;
+    type InputShare = MasticInputShare<T::Field>;
+    type OutputShare = OutputShare<T::Field>;
+    type AggregateShare = AggregateShare<T::Field>;
+
+    fn algorithm_id(&self) -> u32 {
+        MASTIC_ALGORITHM_ID
+    }
+
+    fn num_aggregators(&self) -> usize {
+        2
+    }
+}
+
+impl<T: Type> Client<16> for Mastic<T> {
+    fn shard(
+        &self,
+        (input, weight): &(VidpfInput, T::Measurement),
+        nonce: &[u8; 16],
+    ) -> Result<(Self::PublicShare, Vec<Self::InputShare>), VdafError> {
+        if input.len() != self.bits {
+            return Err(VdafError::Uncategorized(format!(
+                "input has {} bits, expected {}",
+                input.len(),
+                self.bits
+            )));
+        }
+        let beta = self.typ.encode_measurement(weight)?;
+        let (public_share, [leader_key, helper_key]) =
+            self.vidpf
+                .gen(input, &VidpfWeight::from(beta.clone()), nonce)?;
+
+        let leader_beta_share = self.root_share(&leader_key, &public_share, nonce)?;
+        let helper_beta_share = self.root_share(&helper_key, &public_share, nonce)?;
+        let leader_seed = if self.szk.has_joint_rand() {
+            Some(Seed::generate()?)
+        } else {
+            None
+        };
+        let [leader_proof_share, helper_proof_share] = self.szk.prove(
+            leader_beta_share.as_ref(),
+            helper_beta_share.as_ref(),
+            &beta,
+            [Seed::generate()?, Seed::generate()?],
+            leader_seed,
+            nonce,
+        )?;
+
+        Ok((
+            public_share,
+            vec![
+                MasticInputShare {
+                    vidpf_key: leader_key,
+                    proof_share: leader_proof_share,
+                },
+                MasticInputShare {
+                    vidpf_key: helper_key,
+                    proof_share: helper_proof_share,
+                },
+            ],
+        ))
+    }
+}
+
+impl<T: Type> Aggregator<16, 16> for Mastic<T> {
+    type PrepareState = MasticPrepareState<T::Field>;
+    type PrepareShare = MasticPrepareShare<T::Field>;
+    type PrepareMessage = MasticPrepareMessage<T::Field>;
+
+    /// Begins preparation of Aggregator `agg_id`'s input share: evaluates the VIDPF at each of the
+    /// candidate prefixes, their prefixes and the siblings of these, hashes the proofs and path
+    /// checks of these evaluations, and queries the proof of the weight at the root.
+    fn prepare_init(
+        &self,
+        verify_key: &[u8; 16],
+        agg_id: usize,
+        agg_param: &MasticAggregationParam,
+        nonce: &[u8; 16],
+        public_share: &MasticPublicShare<T::Field>,
+        input_share: &MasticInputShare<T::Field>,
+    ) -> Result<(MasticPrepareState<T::Field>, MasticPrepareShare<T::Field>), VdafError> {
+        match (agg_id, &input_share.proof_share) {
+            (0, SzkProofShare::Leader { .. }) | (1, SzkProofShare::Helper { .. }) => (),
+            _ => {
+                return Err(VdafError::Uncategorized(format!(
+                    "input share is not for aggregator {agg_id}"
+                )))
+            }
+        }
+        if agg_param.level() >= self.bits {
+            return Err(VdafError::Uncategorized(format!(
+                "prefixes must have at most {} bits",
+                self.bits
+            )));
+        }
+
+        // Every node on the path to a candidate prefix, and its sibling, so that both children of
+        // each evaluated inner node are evaluated.
+        let mut nodes = BTreeSet::new();
+        for prefix in agg_param.prefixes() {
+            let mut path = Vec::with_capacity(prefix.len());
+            for bit in prefix.iter() {
+                path.push(!bit);
+                nodes.insert(path.clone());
+                *path.last_mut().unwrap() = bit;
+                nodes.insert(path.clone());
+            }
+        }
+
+        // The VIDPF proofs match only if the keys are one-hot on the evaluated nodes.
+        let mut hasher = Sha3_256::new();
+        hasher.update(EVAL_PROOF_DST);
+        let mut shares = BTreeMap::new();
+        for node in nodes {
+            let value_share = self.vidpf.eval(
+                &input_share.vidpf_key,
+                public_share,
+                &VidpfInput::from_bools(&node),
+                nonce,
+            )?;
+            hasher.update(value_share.proof);
+            shares.insert(node, value_share.share);
+        }
+
+        // The shares of the weight at each inner node, minus those at its children, cancel only if
+        // the weights are consistent along the path. The helper negates its share, so that the
+        // hashes match.
+        for (node, share) in &shares {
+            let mut left = node.clone();
+            left.push(false);
+            let mut right = node.clone();
+            right.push(true);
+            let (Some(left), Some(right)) = (shares.get(&left), shares.get(&right)) else {
+                continue;
+            };
+            let mut diff = share.clone() - left.clone() - right.clone();
+            diff.conditional_negate(Choice::from(u8::from(agg_id == 1)));
+            hasher.update(diff.get_encoded()?);
+        }
+
+        // Unwrap safety: every path starts with both children of the root.
+        let root_share = shares[&vec![false]].clone() + shares[&vec![true]].clone();
+        let (query_share, query_state) = self.szk.query(
+            root_share.as_ref(),
+            input_share.proof_share.clone(),
+            verify_key,
+            nonce,
+        )?;
+
+        let mut output_share = Vec::with_capacity(self.output_len(agg_param));
+        for prefix in agg_param.prefixes() {
+            let share = &shares[&prefix.iter().collect::<Vec<_>>()];
+            output_share.extend(self.typ.truncate(share.as_ref().to_vec())?);
+        }
+
+        Ok((
+            MasticPrepareState {
+                joint_rand_seed: query_state.joint_rand_seed,
+                verifier_len: self.typ.verifier_len(),
+                output_share: output_share.into(),
+            },
+            MasticPrepareShare {
+                verifier: query_share.verifier,
+                joint_rand_part: query_share.joint_rand_part,
+                eval_proof: hasher.finalize().into(),
+            },
+        ))
+    }
+
+    /// Combines the prepare shares of the two Aggregators, rejecting the report if the VIDPF
+    /// evaluations fail the one-hot or path checks, or the weight is invalid.
+    fn prepare_shares_to_prepare_message<M: IntoIterator<Item = MasticPrepareShare<T::Field>>>(
+        &self,
+        _agg_param: &MasticAggregationParam,
+        inputs: M,
+    ) -> Result<MasticPrepareMessage<T::Field>, VdafError> {
+        let _timer = telemetry::VerificationTimer::start("mastic");
+        let mut inputs = inputs.into_iter();
+        let (Some(leader), Some(helper), None) = (inputs.next(), inputs.next(), inputs.next())
+        else {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::LengthMismatch,
+                VdafError::Uncategorized("expected two prepare shares".into()),
+            ));
+        };
+        if !bool::from(leader.eval_proof.ct_eq(&helper.eval_proof)) {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::PeerMismatch,
+                VdafError::Uncategorized(
+                    "VIDPF evaluations failed the one-hot or path check".into(),
+                ),
+            ));
+        }
+        if leader.verifier.len() != helper.verifier.len() {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::LengthMismatch,
+                VdafError::Uncategorized("verifier shares have different lengths".into()),
+            ));
+        }
+        let verifier: SzkVerifier<T::Field> = leader
+            .verifier
+            .iter()
+            .zip(&helper.verifier)
+            .map(|(x, y)| *x + *y)
+            .collect();
+        let valid = self.typ.decide(&verifier).map_err(|e| {
+            telemetry::rejected("mastic", RejectionReason::LengthMismatch, e.into())
+        })?;
+        if !valid {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::InvalidProof,
+                VdafError::Uncategorized("proof of the weight did not verify".into()),
+            ));
+        }
+        telemetry::verified("mastic");
+        Ok(MasticPrepareMessage {
+            verifier,
+            leader_joint_rand_part: leader.joint_rand_part,
+            helper_joint_rand_part: helper.joint_rand_part,
+        })
+    }
+
+    /// Finishes preparation, returning the Aggregator's output share, which holds its share of the
+    /// weight at each prefix.
+    fn prepare_next(
+        &self,
+        state: MasticPrepareState<T::Field>,
+        msg: MasticPrepareMessage<T::Field>,
+    ) -> Result<PrepareTransition<Self, 16, 16>, VdafError> {
+        let consistent = self
+            .szk
+            .decide(
+                &msg.verifier,
+                msg.leader_joint_rand_part,
+                msg.helper_joint_rand_part,
+                state.joint_rand_seed,
+            )
+            .map_err(|e| {
+                telemetry::rejected("mastic", RejectionReason::LengthMismatch, e.into())
+            })?;
+        if !consistent {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::PeerMismatch,
+                VdafError::Uncategorized("joint randomness mismatch".into()),
+            ));
+        }
+        Ok(PrepareTransition::Finish(state.output_share))
+    }
+
+    fn aggregate<M: IntoIterator<Item = OutputShare<T::Field>>>(
+        &self,
+        agg_param: &MasticAggregationParam,
+        output_shares: M,
+    ) -> Result<AggregateShare<T::Field>, VdafError> {
+        let mut agg_share =
+            AggregateShare::from(vec![T::Field::zero(); self.output_len(agg_param)]);
+        let mut count = 0;
+        for output_share in output_shares {
+            agg_share.accumulate(&output_share)?;
+            count += 1;
+        }
+        telemetry::aggregated("mastic", count);
+        Ok(agg_share)
+    }
+}
+
+impl<T: Type> Collector for Mastic<T> {
+    /// Unshards the aggregate shares into the total weight of the inputs beginning with each
+    /// candidate prefix.
+    fn unshard<M: IntoIterator<Item = AggregateShare<T::Field>>>(
+        &self,
+        agg_param: &MasticAggregationParam,
+        agg_shares: M,
+        num_measurements: usize,
+    ) -> Result<Vec<T::AggregateResult>, VdafError> {
+        let mut agg_shares = agg_shares.into_iter();
+        let (Some(mut agg), Some(helper), None) =
+            (agg_shares.next(), agg_shares.next(), agg_shares.next())
+        else {
+            return Err(VdafError::Uncategorized(
+                "expected two aggregate shares".into(),
+            ));
+        };
+        agg.merge(&helper)?;
+        let output_len = self.typ.output_len();
+        if agg.as_ref().len() != self.output_len(agg_param) {
+            return Err(VdafError::Uncategorized(
+                "aggregate share has the wrong length for the prefixes".into(),
+            ));
+        }
+        agg.as_ref()
+            .chunks(output_len)
+            .map(|chunk| Ok(self.typ.decode_result(chunk, num_measurements)?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        field::Field64,
+        flp::types::{Count, Sum},
+        vdaf::test_utils::{run_vdaf, run_vdaf_prepare},
+    };
+    use assert_matches::assert_matches;
+
+    fn input(bits: &[bool]) -> VidpfInput {
+        VidpfInput::from_bools(bits)
+    }
+
+    fn agg_param(prefixes: &[&[bool]]) -> MasticAggregationParam {
+        MasticAggregationParam::try_from_prefixes(prefixes.iter().map(|p| input(p)).collect())
+            .unwrap()
+    }
+
+    /// Prepares one report with each Aggregator evaluating its own set of prefixes.
+    fn prepare<T: Type>(
+        mastic: &Mastic<T>,
+        nonce: &[u8; 16],
+        public_share: &MasticPublicShare<T::Field>,
+        input_shares: &[MasticInputShare<T::Field>],
+        agg_params: [&MasticAggregationParam; 2],
+    ) -> Result<(), VdafError> {
+        let verify_key = [7; 16];
+        let (leader_state, leader_share) = mastic.prepare_init(
+            &verify_key,
+            0,
+            agg_params[0],
+            nonce,
+            public_share,
+            &input_shares[0],
+        )?;
+        let (helper_state, helper_share) = mastic.prepare_init(
+            &verify_key,
+            1,
+            agg_params[1],
+            nonce,
+            public_share,
+            &input_shares[1],
+        )?;
+        let msg = mastic
+            .prepare_shares_to_prepare_message(agg_params[0], [leader_share, helper_share])?;
+        mastic.prepare_next(leader_state, msg.clone())?;
+        mastic.prepare_next(helper_state, msg)?;
+        Ok(())
+    }
+
+    #[test]
+    fn weighted_heavy_hitters() {
+        let mastic = Mastic::new(4, Sum::<Field64>::new(4).unwrap()).unwrap();
+        let measurements = [
+            (input(&[true, false, true, false]), 5),
+            (input(&[true, false, true, true]), 3),
+            (input(&[false, true, true, false]), 7),
+        ];
+        assert_eq!(
+            run_vdaf(
+                &mastic,
+                &agg_param(&[
+                    &[false, false],
+                    &[false, true],
+                    &[true, false],
+                    &[true, true]
+                ]),
+                measurements.clone()
+            )
+            .unwrap(),
+            [0, 7, 8, 0]
+        );
+        assert_eq!(
+            run_vdaf(
+                &mastic,
+                &agg_param(&[&[true, false, true, false], &[true, false, true, true]]),
+                measurements
+            )
+            .unwrap(),
+            [5, 3]
+        );
+
+        // Count weighs every input equally.
+        let mastic = Mastic::new(4, Count::<Field64>::new()).unwrap();
+        assert_eq!(
+            run_vdaf(
+                &mastic,
+                &agg_param(&[&[true]]),
+                [
+                    (input(&[true, false, true, false]), true),
+                    (input(&[true, true, true, false]), true),
+                ]
+            )
+            .unwrap(),
+            [2]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_reports() {
+        let mastic = Mastic::new(4, Sum::<Field64>::new(4).unwrap()).unwrap();
+        let nonce = [1; 16];
+        let prefixes = agg_param(&[&[false, true], &[true, false]]);
+        let (public_share, mut input_shares) = mastic
+            .shard(&(input(&[true, false, true, false]), 5), &nonce)
+            .unwrap();
+
+        // The Aggregators evaluate different prefixes.
+        assert_matches!(
+            prepare(
+                &mastic,
+                &nonce,
+                &public_share,
+                &input_shares,
+                [&prefixes, &agg_param(&[&[false, true]])],
+            ),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::PeerMismatch,
+                ..
+            })
+        );
+
+        // An input share for the wrong Aggregator.
+        assert_matches!(
+            mastic
+                .prepare_init(
+                    &[0; 16],
+                    1,
+                    &prefixes,
+                    &nonce,
+                    &public_share,
+                    &input_shares[0],
+                )
+                .err(),
+            Some(VdafError::Uncategorized(_))
+        );
+
+        // Prefixes longer than the inputs.
+        assert!(mastic
+            .prepare_init(
+                &[0; 16],
+                0,
+                &agg_param(&[&[true, false, true, false, true]]),
+                &nonce,
+                &public_share,
+                &input_shares[0],
+            )
+            .is_err());
+
+        // An input that is too long.
+        assert!(mastic
+            .shard(&(input(&[true, false, true, false, true]), 5), &nonce)
+            .is_err());
+
+        // A weight too large for the type.
+        assert!(mastic
+            .shard(&(input(&[true, false, true, false]), 16), &nonce)
+            .is_err());
+
+        // The proof share is tampered with.
+        if let SzkProofShare::Leader {
+            uncompressed_proof_share,
+            ..
+        } = &mut input_shares[0].proof_share
+        {
+            uncompressed_proof_share[0] += Field64::one();
+        }
+        assert_matches!(
+            prepare(
+                &mastic,
+                &nonce,
+                &public_share,
+                &input_shares,
+                [&prefixes, &prefixes],
+            ),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::InvalidProof,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_inconsistent_keys() {
+        let mastic = Mastic::new(4, Sum::<Field64>::new(4).unwrap()).unwrap();
+        let nonce = [2; 16];
+        let prefixes = agg_param(&[&[true, false, true]]);
+        let (public_share, input_shares) = mastic
+            .shard(&(input(&[true, false, true, false]), 5), &nonce)
+            .unwrap();
+        let verify_key = [0; 16];
+        let out_shares = run_vdaf_prepare(
+            &mastic,
+            &verify_key,
+            &prefixes,
+            &nonce,
+            public_share.clone(),
+            input_shares.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            mastic
+                .unshard(
+                    &prefixes,
+                    out_shares.into_iter().map(AggregateShare::from),
+                    1
+                )
+                .unwrap(),
+            [5]
+        );
+
+        // The public share holds, for each level, a correction word with a seed, a byte of control
+        // bits and the weight, then each level's proof correction.
+        let encoded = public_share.get_encoded().unwrap();
+        let stride = 16 + 1 + mastic.typ.input_len() * Field64::ENCODED_SIZE;
+        let tampered = |offset: usize| {
+            let mut bytes = encoded.clone();
+            bytes[offset] ^= 1;
+            MasticPublicShare::get_decoded_with_param(&mastic, &bytes).unwrap()
+        };
+
+        // A Client that attaches a valid weight at the root and a different one deeper down fails
+        // the path check.
+        assert_matches!(
+            prepare(
+                &mastic,
+                &nonce,
+                &tampered(2 * stride + 17),
+                &input_shares,
+                [&prefixes, &prefixes],
+            ),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::PeerMismatch,
+                ..
+            })
+        );
+
+        // A Client whose keys are not one-hot fails the proof check.
+        assert_matches!(
+            prepare(
+                &mastic,
+                &nonce,
+                &tampered(4 * stride + 32),
+                &input_shares,
+                [&prefixes, &prefixes],
+            ),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::PeerMismatch,
+                ..
+            })
+        );
+    }
+}
@@ -0,0 +1,603 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Sums of sparse vectors.
+//!
+//! Each Client holds a vector of `dimension` weights, at most `num_entries` of which are non-zero,
+//! and submits it as a list of (index, weight) pairs, padded to exactly `num_entries` pairs so that
+//! the number of non-zero entries is not revealed. Each pair is sharded as a [`Mastic`] report for
+//! the index, written in `ceil(log2(dimension))` bits, so the report grows with `num_entries *
+//! log2(dimension)` rather than with `dimension`. The Aggregators evaluate every pair at every
+//! index, so each output share is a share of the dense vector.
+//!
+//! The weight of each pair is validated with the FLP type, and the VIDPF guarantees that each pair
+//! contributes at a single index. To bound the contribution at each index, the Client also proves
+//! that its indices are distinct: it sorts the pairs by index, pads them with unused indices, and
+//! the VIDPF payload of each pair carries, besides the weight,
+//!
+//! - a counter, which the Aggregators check sums to one over all indices, so that each pair
+//!   contributes at exactly one index below `dimension`; and
+//! - the gap between its index and that of the next pair, minus one, whose bits are range-checked
+//!   by the FLP along with the weight.
+//!
+//! Weighing each index by its counter, the Aggregators compute shares of the index of each pair,
+//! and check that each index plus one plus its gap is the next index. The indices are thus
+//! strictly increasing. Like the VIDPF checks, the Aggregators compare a hash of their shares of
+//! these differences, which are zero for an honest Client.
+
+use crate::{
+    codec::Encode,
+    field::{FftFriendlyFieldElement, FieldElement, FieldElementWithIntegerExt},
+    flp::{
+        types::{composite::Pair, Count, Sum},
+        Type,
+    },
+    vdaf::{
+        mastic::{
+            Mastic, MasticAggregationParam, MasticInputShare, MasticPrepareMessage,
+            MasticPrepareShare, MasticPrepareState, MasticPublicShare,
+        },
+        telemetry,
+        xof::{Seed, Xof, XofTurboShake128},
+        Aggregatable, AggregateShare, Aggregator, Client, OutputShare, PrepareTransition,
+        RejectionReason, VdafError,
+    },
+    vidpf::VidpfInput,
+};
+use rand_core::RngCore;
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Debug},
+};
+use subtle::{Choice, ConditionallyNegatable, ConstantTimeEq};
+
+/// Domain separation tag for deriving the nonce of each pair from the nonce of the report.
+const ENTRY_NONCE_DST: &[u8] = b"mastic sparse entry";
+
+/// Domain separation tag for the hash of the index checks.
+const INDEX_CHECK_DST: &[u8] = b"mastic sparse index check";
+
+/// The FLP type of the payload of each pair: the counter, the weight, and the gap to the next
+/// index.
+type EntryType<T> = Pair<Count<<T as Type>::Field>, Pair<T, Sum<<T as Type>::Field>>>;
+
+/// The public share of a sparse vector report, with that of each pair.
+pub struct SparsePublicShare<F: FieldElement>(Vec<MasticPublicShare<F>>);
+
+/// An Aggregator's share of a sparse vector report, with its share of each pair.
+pub struct SparseInputShare<F: FftFriendlyFieldElement>(Vec<MasticInputShare<F>>);
+
+/// The state an Aggregator keeps between [`SparseVec::prepare_init`] and
+/// [`SparseVec::prepare_next`].
+pub struct SparsePrepareState<F>(Vec<MasticPrepareState<F>>);
+
+/// The message an Aggregator sends to its peer after [`SparseVec::prepare_init`].
+#[derive(Clone)]
+pub struct SparsePrepareShare<F> {
+    entries: Vec<MasticPrepareShare<F>>,
+    index_check: [u8; 32],
+}
+
+/// The message both Aggregators pass to [`SparseVec::prepare_next`].
+#[derive(Clone)]
+pub struct SparsePrepareMessage<F>(Vec<MasticPrepareMessage<F>>);
+
+/// Sums of vectors of `dimension` weights with at most `num_entries` non-zero entries, which are
+/// measurements of the FLP type `T`. See the [module documentation](self) for details.
+pub struct SparseVec<T: Type> {
+    dimension: usize,
+    num_entries: usize,
+    padding: T::Measurement,
+    indices: Vec<VidpfInput>,
+    agg_param: MasticAggregationParam,
+    typ: T,
+    mastic: Mastic<EntryType<T>>,
+}
+
+impl<T: Type> SparseVec<T> {
+    /// Constructs an instance for vectors of `dimension` weights, submitted as `num_entries`
+    /// pairs, at most `dimension`. Clients pad their lists of pairs with `padding`, which must be a
+    /// weight that does not change the aggregate, such as 0 for [`Sum`].
+    pub fn new(
+        dimension: usize,
+        num_entries: usize,
+        typ: T,
+        padding: T::Measurement,
+    ) -> Result<Self, VdafError> {
+        if dimension == 0 || num_entries == 0 {
+            return Err(VdafError::Uncategorized(
+                "dimension and num_entries must be positive".into(),
+            ));
+        }
+        if num_entries > dimension {
+            return Err(VdafError::Uncategorized(
+                "num_entries must be at most the dimension".into(),
+            ));
+        }
+        if typ
+            .truncate(typ.encode_measurement(&padding)?)?
+            .iter()
+            .any(|x| *x != T::Field::zero())
+        {
+            return Err(VdafError::Uncategorized(
+                "padding weight contributes to the aggregate".into(),
+            ));
+        }
+
+        let bits = ((usize::BITS - (dimension - 1).leading_zeros()) as usize).max(1);
+        // An index plus its gap must not wrap around the field, so that the indices are
+        // increasing as integers.
+        if !T::Field::valid_integer_bitlength(bits + 1) {
+            return Err(VdafError::Uncategorized(
+                "dimension is too large for the field".into(),
+            ));
+        }
+        let indices: Vec<_> = (0..dimension)
+            .map(|index| {
+                VidpfInput::from_bools(
+                    &(0..bits)
+                        .rev()
+                        .map(|i| (index >> i) & 1 == 1)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let agg_param = MasticAggregationParam::try_from_prefixes(indices.clone())?;
+        let entry_type = Pair::new(Count::new(), Pair::new(typ.clone(), Sum::new(bits)?));
+        Ok(Self {
+            dimension,
+            num_entries,
+            padding,
+            indices,
+            agg_param,
+            typ,
+            mastic: Mastic::new(bits, entry_type)?,
+        })
+    }
+
+    /// Returns the length of the vectors.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Returns the number of pairs in each report.
+    pub fn num_entries(&self) -> usize {
+        self.num_entries
+    }
+
+    /// Shards the vector whose non-zero entries are `entries`, given as (index, weight) pairs, into
+    /// a public share and two input shares.
+    #[allow(clippy::type_complexity)]
+    pub fn shard(
+        &self,
+        entries: &[(usize, T::Measurement)],
+        nonce: &[u8; 16],
+    ) -> Result<(SparsePublicShare<T::Field>, [SparseInputShare<T::Field>; 2]), VdafError> {
+        if entries.len() > self.num_entries {
+            return Err(VdafError::Uncategorized(format!(
+                "got {} entries, expected at most {}",
+                entries.len(),
+                self.num_entries
+            )));
+        }
+        let mut used = BTreeSet::new();
+        for (index, _) in entries {
+            if *index >= self.dimension {
+                return Err(VdafError::Uncategorized(format!(
+                    "index {index} is out of range for dimension {}",
+                    self.dimension
+                )));
+            }
+            if !used.insert(*index) {
+                return Err(VdafError::Uncategorized(format!(
+                    "index {index} appears more than once"
+                )));
+            }
+        }
+
+        // Pad with the smallest unused indices, and sort the pairs by index.
+        let mut pairs: Vec<_> = entries
+            .iter()
+            .map(|(index, weight)| (*index, weight))
+            .collect();
+        pairs.extend(
+            (0..self.dimension)
+                .filter(|index| !used.contains(index))
+                .take(self.num_entries - entries.len())
+                .map(|index| (index, &self.padding)),
+        );
+        pairs.sort_by_key(|(index, _)| *index);
+
+        let mut public_shares = Vec::with_capacity(self.num_entries);
+        let mut leader_shares = Vec::with_capacity(self.num_entries);
+        let mut helper_shares = Vec::with_capacity(self.num_entries);
+        for (i, (index, weight)) in pairs.iter().enumerate() {
+            let gap = pairs.get(i + 1).map_or(0, |(next, _)| next - index - 1);
+            let gap = T::Field::valid_integer_try_from(gap)?;
+            let (public_share, input_shares) = self.mastic.shard(
+                &(
+                    self.indices[*index].clone(),
+                    (true, ((*weight).clone(), gap)),
+                ),
+                &entry_nonce(nonce, i),
+            )?;
+            let [leader_share, helper_share]: [_; 2] = input_shares
+                .try_into()
+                .map_err(|_| VdafError::Uncategorized("expected two input shares".into()))?;
+            public_shares.push(public_share);
+            leader_shares.push(leader_share);
+            helper_shares.push(helper_share);
+        }
+        Ok((
+            SparsePublicShare(public_shares),
+            [
+                SparseInputShare(leader_shares),
+                SparseInputShare(helper_shares),
+            ],
+        ))
+    }
+
+    /// Begins preparation of Aggregator `agg_id`'s input share, evaluating each pair at every
+    /// index and computing the Aggregator's share of the index checks.
+    #[allow(clippy::type_complexity)]
+    pub fn prepare_init(
+        &self,
+        verify_key: &[u8; 16],
+        agg_id: usize,
+        nonce: &[u8; 16],
+        public_share: &SparsePublicShare<T::Field>,
+        input_share: &SparseInputShare<T::Field>,
+    ) -> Result<(SparsePrepareState<T::Field>, SparsePrepareShare<T::Field>), VdafError> {
+        if public_share.0.len() != self.num_entries || input_share.0.len() != self.num_entries {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::LengthMismatch,
+                VdafError::Uncategorized("report has the wrong number of entries".into()),
+            ));
+        }
+        let mut states = Vec::with_capacity(self.num_entries);
+        let mut shares = Vec::with_capacity(self.num_entries);
+        for (i, (public_share, input_share)) in
+            public_share.0.iter().zip(&input_share.0).enumerate()
+        {
+            let (state, share) = self.mastic.prepare_init(
+                verify_key,
+                agg_id,
+                &self.agg_param,
+                &entry_nonce(nonce, i),
+                public_share,
+                input_share,
+            )?;
+            states.push(state);
+            shares.push(share);
+        }
+
+        // The shares of the counter of each pair, summed over all indices, minus one, and of each
+        // index plus one plus its gap, minus the next index. The leader adds the constants, and the
+        // helper negates its shares, so that the hashes match exactly when all of these are zero.
+        let one = if agg_id == 0 {
+            T::Field::one()
+        } else {
+            T::Field::zero()
+        };
+        let entry_len = self.mastic.typ.output_len();
+        let mut hasher = Sha3_256::new();
+        hasher.update(INDEX_CHECK_DST);
+        let mut previous: Option<(T::Field, T::Field)> = None;
+        for state in &states {
+            let (mut count, mut index, mut gap) =
+                (T::Field::zero(), T::Field::zero(), T::Field::zero());
+            let mut position = T::Field::zero();
+            for chunk in state.output_share.as_ref().chunks(entry_len) {
+                count += chunk[0];
+                index += position * chunk[0];
+                gap += chunk[entry_len - 1];
+                position += T::Field::one();
+            }
+            let mut checks = vec![count - one];
+            if let Some((previous_index, previous_gap)) = previous {
+                checks.push(previous_index + one + previous_gap - index);
+            }
+            for mut check in checks {
+                check.conditional_negate(Choice::from(u8::from(agg_id == 1)));
+                hasher.update(check.get_encoded()?);
+            }
+            previous = Some((index, gap));
+        }
+
+        Ok((
+            SparsePrepareState(states),
+            SparsePrepareShare {
+                entries: shares,
+                index_check: hasher.finalize().into(),
+            },
+        ))
+    }
+
+    /// Combines the prepare shares of the two Aggregators, rejecting the report if any of its
+    /// pairs is rejected, or if the indices of the pairs fail the checks.
+    pub fn prepare_shares_to_prepare_message(
+        &self,
+        prep_shares: [SparsePrepareShare<T::Field>; 2],
+    ) -> Result<SparsePrepareMessage<T::Field>, VdafError> {
+        let [leader, helper] = prep_shares;
+        if leader.entries.len() != self.num_entries || helper.entries.len() != self.num_entries {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::LengthMismatch,
+                VdafError::Uncategorized("prepare shares have the wrong number of entries".into()),
+            ));
+        }
+        if !bool::from(leader.index_check.ct_eq(&helper.index_check)) {
+            return Err(telemetry::rejected(
+                "mastic",
+                RejectionReason::PeerMismatch,
+                VdafError::Uncategorized(
+                    "indices of the pairs are not distinct and in range".into(),
+                ),
+            ));
+        }
+        leader
+            .entries
+            .into_iter()
+            .zip(helper.entries)
+            .map(|(leader, helper)| {
+                self.mastic
+                    .prepare_shares_to_prepare_message(&self.agg_param, [leader, helper])
+            })
+            .collect::<Result<_, _>>()
+            .map(SparsePrepareMessage)
+    }
+
+    /// Finishes preparation, returning the Aggregator's output share, which holds its share of
+    /// the dense vector.
+    pub fn prepare_next(
+        &self,
+        state: SparsePrepareState<T::Field>,
+        msg: SparsePrepareMessage<T::Field>,
+    ) -> Result<OutputShare<T::Field>, VdafError> {
+        if state.0.len() != msg.0.len() {
+            return Err(VdafError::Uncategorized(
+                "prepare message has the wrong number of entries".into(),
+            ));
+        }
+        // Each pair's output share holds, at each index, the counter, the weight and the gap. Only
+        // the weights are summed.
+        let weight_len = self.typ.output_len();
+        let mut output_share = vec![T::Field::zero(); self.output_len()];
+        for (state, msg) in state.0.into_iter().zip(msg.0) {
+            let PrepareTransition::Finish(entry_share) = self.mastic.prepare_next(state, msg)?
+            else {
+                return Err(VdafError::Uncategorized(
+                    "preparation of an entry did not finish".into(),
+                ));
+            };
+            for (weights, entry) in output_share
+                .chunks_mut(weight_len)
+                .zip(entry_share.as_ref().chunks(weight_len + 2))
+            {
+                for (x, y) in weights.iter_mut().zip(&entry[1..=weight_len]) {
+                    *x += *y;
+                }
+            }
+        }
+        Ok(output_share.into())
+    }
+
+    /// Sums output shares into an aggregate share.
+    pub fn aggregate<M: IntoIterator<Item = OutputShare<T::Field>>>(
+        &self,
+        output_shares: M,
+    ) -> Result<AggregateShare<T::Field>, VdafError> {
+        let mut agg_share = AggregateShare::from(vec![T::Field::zero(); self.output_len()]);
+        let mut count = 0;
+        for output_share in output_shares {
+            agg_share.accumulate(&output_share)?;
+            count += 1;
+        }
+        telemetry::aggregated("mastic", count);
+        Ok(agg_share)
+    }
+
+    /// Unshards the aggregate shares into the sum of the vectors, one aggregate result per index.
+    pub fn unshard(
+        &self,
+        agg_shares: [AggregateShare<T::Field>; 2],
+        num_measurements: usize,
+    ) -> Result<Vec<T::AggregateResult>, VdafError> {
+        let [mut agg, helper] = agg_shares;
+        agg.merge(&helper)?;
+        if agg.as_ref().len() != self.output_len() {
+            return Err(VdafError::Uncategorized(
+                "aggregate share has the wrong length".into(),
+            ));
+        }
+        agg.as_ref()
+            .chunks(self.typ.output_len())
+            .map(|chunk| Ok(self.typ.decode_result(chunk, num_measurements)?))
+            .collect()
+    }
+
+    fn output_len(&self) -> usize {
+        self.typ.output_len() * self.dimension
+    }
+}
+
+impl<T: Type> Debug for SparseVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SparseVec")
+            .field("dimension", &self.dimension)
+            .field("num_entries", &self.num_entries)
+            .field("mastic", &self.mastic)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Derives the nonce of the `i`-th pair of a report, so that the pairs are bound to their
+/// position.
+fn entry_nonce(nonce: &[u8; 16], i: usize) -> [u8; 16] {
+    let mut entry_nonce = [0; 16];
+    XofTurboShake128::seed_stream(
+        &Seed::from_bytes(*nonce),
+        ENTRY_NONCE_DST,
+        &(i as u64).to_be_bytes(),
+    )
+    .fill_bytes(&mut entry_nonce);
+    entry_nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        field::Field64,
+        flp::types::{Count, Sum},
+    };
+    use assert_matches::assert_matches;
+
+    /// Prepares one report.
+    fn prepare<T: Type>(
+        sparse: &SparseVec<T>,
+        nonce: &[u8; 16],
+        public_share: &SparsePublicShare<T::Field>,
+        input_shares: &[SparseInputShare<T::Field>; 2],
+    ) -> Result<[OutputShare<T::Field>; 2], VdafError> {
+        let verify_key = [7; 16];
+        let (leader_state, leader_share) =
+            sparse.prepare_init(&verify_key, 0, nonce, public_share, &input_shares[0])?;
+        let (helper_state, helper_share) =
+            sparse.prepare_init(&verify_key, 1, nonce, public_share, &input_shares[1])?;
+        let msg = sparse.prepare_shares_to_prepare_message([leader_share, helper_share])?;
+        Ok([
+            sparse.prepare_next(leader_state, msg.clone())?,
+            sparse.prepare_next(helper_state, msg)?,
+        ])
+    }
+
+    #[test]
+    fn sparse_vec_sum() {
+        let sparse = SparseVec::new(10, 3, Sum::<Field64>::new(4).unwrap(), 0).unwrap();
+        let measurements: [&[(usize, u64)]; 3] = [&[(0, 5), (9, 3), (4, 1)], &[(9, 15)], &[]];
+
+        let mut output_shares = [Vec::new(), Vec::new()];
+        for (i, entries) in measurements.iter().enumerate() {
+            let nonce = [i as u8; 16];
+            let (public_share, input_shares) = sparse.shard(entries, &nonce).unwrap();
+            let [leader, helper] = prepare(&sparse, &nonce, &public_share, &input_shares).unwrap();
+            output_shares[0].push(leader);
+            output_shares[1].push(helper);
+        }
+        let [leader, helper] = output_shares;
+        let agg_shares = [
+            sparse.aggregate(leader).unwrap(),
+            sparse.aggregate(helper).unwrap(),
+        ];
+        assert_eq!(
+            sparse.unshard(agg_shares, 3).unwrap(),
+            [5, 0, 0, 0, 1, 0, 0, 0, 0, 18]
+        );
+    }
+
+    #[test]
+    fn sparse_vec_errors() {
+        let sparse = SparseVec::new(5, 2, Sum::<Field64>::new(4).unwrap(), 0).unwrap();
+        let nonce = [0; 16];
+        assert!(sparse.shard(&[(5, 1)], &nonce).is_err());
+        assert!(sparse.shard(&[(0, 16)], &nonce).is_err());
+        assert!(sparse.shard(&[(0, 1), (1, 1), (2, 1)], &nonce).is_err());
+
+        // The padding must not change the aggregate.
+        assert!(SparseVec::new(5, 2, Sum::<Field64>::new(4).unwrap(), 1).is_err());
+        assert!(SparseVec::new(5, 2, Count::<Field64>::new(), false).is_ok());
+        assert!(SparseVec::new(0, 2, Count::<Field64>::new(), false).is_err());
+        assert!(SparseVec::new(5, 0, Count::<Field64>::new(), false).is_err());
+        assert!(SparseVec::new(5, 6, Count::<Field64>::new(), false).is_err());
+
+        // An honest Client's indices are distinct.
+        assert!(sparse.shard(&[(1, 1), (1, 2)], &nonce).is_err());
+
+        // The pairs of a report are bound to their position.
+        let (public_share, [mut leader, mut helper]) =
+            sparse.shard(&[(1, 2), (3, 4)], &nonce).unwrap();
+        leader.0.swap(0, 1);
+        helper.0.swap(0, 1);
+        assert!(prepare(&sparse, &nonce, &public_share, &[leader, helper]).is_err());
+
+        // A report with a missing pair.
+        let (public_share, [leader, mut helper]) = sparse.shard(&[(1, 2), (3, 4)], &nonce).unwrap();
+        helper.0.pop();
+        assert_matches!(
+            prepare(&sparse, &nonce, &public_share, &[leader, helper]),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::LengthMismatch,
+                ..
+            })
+        );
+    }
+
+    /// Shards a report whose pairs are given as (index bits, counter, weight, gap), without the
+    /// checks of [`SparseVec::shard`].
+    fn shard_malicious(
+        sparse: &SparseVec<Sum<Field64>>,
+        pairs: &[(Vec<bool>, bool, u64, u64)],
+        nonce: &[u8; 16],
+    ) -> (SparsePublicShare<Field64>, [SparseInputShare<Field64>; 2]) {
+        let mut public_shares = Vec::new();
+        let mut input_shares = [Vec::new(), Vec::new()];
+        for (i, (index, counter, weight, gap)) in pairs.iter().enumerate() {
+            let (public_share, [leader, helper]): (_, [_; 2]) = sparse
+                .mastic
+                .shard(
+                    &(VidpfInput::from_bools(index), (*counter, (*weight, *gap))),
+                    &entry_nonce(nonce, i),
+                )
+                .map(|(public_share, shares)| (public_share, shares.try_into().unwrap()))
+                .unwrap();
+            public_shares.push(public_share);
+            input_shares[0].push(leader);
+            input_shares[1].push(helper);
+        }
+        let [leader, helper] = input_shares;
+        (
+            SparsePublicShare(public_shares),
+            [SparseInputShare(leader), SparseInputShare(helper)],
+        )
+    }
+
+    #[test]
+    fn sparse_vec_rejects_repeated_indices() {
+        let sparse = SparseVec::new(5, 2, Sum::<Field64>::new(4).unwrap(), 0).unwrap();
+        let nonce = [1; 16];
+        let (t, f) = (true, false);
+
+        // The shards of an honest Client pass the checks.
+        let (public_share, input_shares) = shard_malicious(
+            &sparse,
+            &[(vec![f, f, t], t, 3, 2), (vec![t, f, f], t, 2, 0)],
+            &nonce,
+        );
+        prepare(&sparse, &nonce, &public_share, &input_shares).unwrap();
+
+        for pairs in [
+            // Two pairs with the same index, whatever the gap.
+            vec![(vec![f, f, t], t, 15, 0), (vec![f, f, t], t, 15, 0)],
+            vec![(vec![f, f, t], t, 15, 3), (vec![f, f, t], t, 15, 0)],
+            // Indices out of order.
+            vec![(vec![t, f, f], t, 15, 0), (vec![f, f, t], t, 15, 0)],
+            // A pair without its counter, which would not count towards the order.
+            vec![(vec![f, f, t], f, 15, 0), (vec![f, f, t], t, 15, 0)],
+            // An index beyond the dimension.
+            vec![(vec![f, f, t], t, 15, 4), (vec![t, t, f], t, 15, 0)],
+        ] {
+            let (public_share, input_shares) = shard_malicious(&sparse, &pairs, &nonce);
+            assert_matches!(
+                prepare(&sparse, &nonce, &public_share, &input_shares),
+                Err(VdafError::Rejected {
+                    reason: RejectionReason::PeerMismatch,
+                    ..
+                }),
+                "{pairs:?}"
+            );
+        }
+    }
+}
@@ -4,17 +4,23 @@
 //!
 //! [draft-irtf-cfrg-vdaf-08]: https://datatracker.ietf.org/doc/draft-irtf-cfrg-vdaf/08/
 
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+use crate::vdaf::ClientWithRng;
 use crate::{
     codec::{CodecError, Decode, Encode, ParameterizedDecode},
     field::{decode_fieldvec, merge_vector, Field255, Field64, FieldElement},
-    idpf::{Idpf, IdpfInput, IdpfOutputShare, IdpfPublicShare, IdpfValue, RingBufferCache},
+    idpf::{Idpf, IdpfInput, IdpfOutputShare, IdpfPublicShare, IdpfValue},
     prng::Prng,
     vdaf::{
+        telemetry,
         xof::{Seed, Xof, XofTurboShake128},
-        Aggregatable, Aggregator, Client, Collector, PrepareTransition, Vdaf, VdafError,
+        Aggregatable, Aggregator, Client, Collector, PrepareTransition, RejectionReason, Vdaf,
+        VdafError,
     },
 };
 use bitvec::{prelude::Lsb0, vec::BitVec};
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+use rand_core::CryptoRng;
 use rand_core::RngCore;
 use std::{
     convert::TryFrom,
@@ -820,8 +826,17 @@ impl Decode for Poplar1AggregationParam {
         let prefix_count =
             usize::try_from(u32::decode(bytes)?).map_err(|e| CodecError::Other(e.into()))?;
 
-        let packed_bit_count = (usize::from(level) + 1) * prefix_count;
-        let mut packed = vec![0u8; (packed_bit_count + 7) / 8];
+        // Check the claimed length against the input before allocating, so that a malicious
+        // encoding can't cause a large allocation.
+        let packed_bit_count = (usize::from(level) + 1)
+            .checked_mul(prefix_count)
+            .ok_or(CodecError::LengthPrefixTooBig(usize::MAX))?;
+        let packed_len = packed_bit_count / 8 + usize::from(packed_bit_count % 8 != 0);
+        let remaining = bytes.get_ref().len() - bytes.position() as usize;
+        if packed_len > remaining {
+            return Err(CodecError::LengthPrefixTooBig(packed_len));
+        }
+        let mut packed = vec![0u8; packed_len];
         bytes.read_exact(&mut packed)?;
         if packed_bit_count % 8 != 0 {
             let unused_bits = packed[0] >> (packed_bit_count % 8);
@@ -992,17 +1007,10 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Poplar1<P, SEED_SIZE> {
             corr_prng.get(), // c_share
         ];
 
-        let mut idpf_eval_cache = RingBufferCache::new(agg_param.prefixes.len());
         let idpf = Idpf::<Poplar1IdpfValue<Field64>, Poplar1IdpfValue<Field255>>::new((), ());
-        for prefix in agg_param.prefixes.iter() {
-            let share = Poplar1IdpfValue::<F>::from(idpf.eval(
-                agg_id,
-                public_share,
-                idpf_key,
-                prefix,
-                nonce,
-                &mut idpf_eval_cache,
-            )?);
+        let shares = idpf.eval_batch(agg_id, public_share, idpf_key, &agg_param.prefixes, nonce)?;
+        for share in shares {
+            let share = Poplar1IdpfValue::<F>::from(share);
 
             let r = verify_prng.get();
             let checked_data_share = share.0[0] * r;
@@ -1017,6 +1025,10 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Poplar1<P, SEED_SIZE> {
 }
 
 impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Client<16> for Poplar1<P, SEED_SIZE> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bits = self.bits))
+    )]
     fn shard(
         &self,
         input: &IdpfInput,
@@ -1034,6 +1046,26 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Client<16> for Poplar1<P, SEED_S
     }
 }
 
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> ClientWithRng<16> for Poplar1<P, SEED_SIZE> {
+    fn shard_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        input: &IdpfInput,
+        nonce: &[u8; 16],
+        rng: &mut R,
+    ) -> Result<(Self::PublicShare, Vec<Poplar1InputShare<SEED_SIZE>>), VdafError> {
+        let mut idpf_random = [[0u8; 16]; 2];
+        let mut poplar_random = [[0u8; SEED_SIZE]; 3];
+        for random_seed in idpf_random.iter_mut() {
+            rng.fill_bytes(random_seed);
+        }
+        for random_seed in poplar_random.iter_mut() {
+            rng.fill_bytes(random_seed);
+        }
+        self.shard_with_random(input, nonce, &idpf_random, &poplar_random)
+    }
+}
+
 impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Aggregator<SEED_SIZE, 16>
     for Poplar1<P, SEED_SIZE>
 {
@@ -1042,6 +1074,10 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Aggregator<SEED_SIZE, 16>
     type PrepareMessage = Poplar1PrepareMessage;
 
     #[allow(clippy::type_complexity)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bits = self.bits, agg_id))
+    )]
     fn prepare_init(
         &self,
         verify_key: &[u8; SEED_SIZE],
@@ -1061,6 +1097,13 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Aggregator<SEED_SIZE, 16>
             }
         };
 
+        if usize::from(agg_param.level) >= self.bits {
+            return Err(VdafError::Uncategorized(format!(
+                "aggregation parameter level ({}) exceeds the IDPF depth ({})",
+                agg_param.level, self.bits
+            )));
+        }
+
         if usize::from(agg_param.level) < self.bits - 1 {
             let mut corr_prng = self.init_prng::<_, _, Field64>(
                 input_share.corr_seed.as_ref(),
@@ -1125,25 +1168,35 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Aggregator<SEED_SIZE, 16>
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bits = self.bits))
+    )]
     fn prepare_shares_to_prepare_message<M: IntoIterator<Item = Poplar1FieldVec>>(
         &self,
         _: &Poplar1AggregationParam,
         inputs: M,
     ) -> Result<Poplar1PrepareMessage, VdafError> {
+        let _timer = telemetry::VerificationTimer::start("poplar1");
+        let malformed = |msg: &str| {
+            telemetry::rejected(
+                "poplar1",
+                RejectionReason::LengthMismatch,
+                VdafError::Uncategorized(msg.into()),
+            )
+        };
         let mut inputs = inputs.into_iter();
         let prep_share_0 = inputs
             .next()
-            .ok_or_else(|| VdafError::Uncategorized("insufficient number of prep shares".into()))?;
+            .ok_or_else(|| malformed("insufficient number of prep shares"))?;
         let prep_share_1 = inputs
             .next()
-            .ok_or_else(|| VdafError::Uncategorized("insufficient number of prep shares".into()))?;
+            .ok_or_else(|| malformed("insufficient number of prep shares"))?;
         if inputs.next().is_some() {
-            return Err(VdafError::Uncategorized(
-                "more prep shares than expected".into(),
-            ));
+            return Err(malformed("more prep shares than expected"));
         }
 
-        match (prep_share_0, prep_share_1) {
+        let prep_msg = match (prep_share_0, prep_share_1) {
             (Poplar1FieldVec::Inner(share_0), Poplar1FieldVec::Inner(share_1)) => {
                 Ok(Poplar1PrepareMessage(
                     next_message(share_0, share_1)?.map_or(PrepareMessageVariant::Done, |sketch| {
@@ -1158,12 +1211,21 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Aggregator<SEED_SIZE, 16>
                     }),
                 ))
             }
-            _ => Err(VdafError::Uncategorized(
-                "received prep shares with mismatched field types".into(),
+            _ => Err(malformed(
+                "received prep shares with mismatched field types",
             )),
+        }?;
+
+        if let PrepareMessageVariant::Done = prep_msg.0 {
+            telemetry::verified("poplar1");
         }
+        Ok(prep_msg)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bits = self.bits))
+    )]
     fn prepare_next(
         &self,
         state: Poplar1PrepareState,
@@ -1234,20 +1296,31 @@ impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Aggregator<SEED_SIZE, 16>
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bits = self.bits))
+    )]
     fn aggregate<M: IntoIterator<Item = Poplar1FieldVec>>(
         &self,
         agg_param: &Poplar1AggregationParam,
         output_shares: M,
     ) -> Result<Poplar1FieldVec, VdafError> {
-        aggregate(
+        let mut count = 0;
+        let agg_share = aggregate(
             usize::from(agg_param.level) == self.bits - 1,
             agg_param.prefixes.len(),
-            output_shares,
-        )
+            output_shares.into_iter().inspect(|_| count += 1),
+        )?;
+        telemetry::aggregated("poplar1", count);
+        Ok(agg_share)
     }
 }
 
 impl<P: Xof<SEED_SIZE>, const SEED_SIZE: usize> Collector for Poplar1<P, SEED_SIZE> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bits = self.bits))
+    )]
     fn unshard<M: IntoIterator<Item = Poplar1FieldVec>>(
         &self,
         agg_param: &Poplar1AggregationParam,
@@ -1338,12 +1411,15 @@ fn next_message<F: FieldElement>(
     mut share_0: Vec<F>,
     share_1: Vec<F>,
 ) -> Result<Option<[F; 3]>, VdafError> {
-    merge_vector(&mut share_0, &share_1)?;
+    merge_vector(&mut share_0, &share_1)
+        .map_err(|e| telemetry::rejected("poplar1", RejectionReason::LengthMismatch, e.into()))?;
 
     if share_0.len() == 1 {
-        if share_0[0] != F::zero() {
-            Err(VdafError::Uncategorized(
-                "sketch verification failed".into(),
+        if !bool::from(share_0[0].ct_eq(&F::zero())) {
+            Err(telemetry::rejected(
+                "poplar1",
+                RejectionReason::InvalidProof,
+                VdafError::Uncategorized("sketch verification failed".into()),
             )) // Invalid sketch
         } else {
             Ok(None) // Sketch verification succeeded
@@ -1351,10 +1427,11 @@ fn next_message<F: FieldElement>(
     } else if share_0.len() == 3 {
         Ok(Some([share_0[0], share_0[1], share_0[2]])) // Sketch verification continues
     } else {
-        Err(VdafError::Uncategorized(format!(
-            "unexpected sketch length ({})",
-            share_0.len()
-        )))
+        Err(telemetry::rejected(
+            "poplar1",
+            RejectionReason::LengthMismatch,
+            VdafError::Uncategorized(format!("unexpected sketch length ({})", share_0.len())),
+        ))
     }
 }
 
@@ -1969,6 +2046,16 @@ mod tests {
         assert_matches!(err, CodecError::UnexpectedValue);
     }
 
+    #[test]
+    fn agg_param_length_too_big() {
+        // The maximum level and prefix count claim far more data than is present.
+        let err = Poplar1AggregationParam::get_decoded(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0])
+            .unwrap_err();
+        assert_matches!(err, CodecError::LengthPrefixTooBig(_));
+        let err = Poplar1AggregationParam::get_decoded(&[0, 0, 0, 0, 0, 9, 0]).unwrap_err();
+        assert_matches!(err, CodecError::LengthPrefixTooBig(2));
+    }
+
     #[test]
     fn agg_param_ordering() {
         let err = Poplar1AggregationParam::get_decoded(&[0, 0, 0, 0, 0, 2, 1]).unwrap_err();
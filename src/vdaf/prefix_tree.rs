@@ -0,0 +1,336 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Collector's side of a heavy-hitters search.
+//!
+//! Heavy hitters are found by walking down the prefix tree one level per round: the Collector
+//! asks the Aggregators for the count of each candidate prefix, keeps those whose count meets the
+//! threshold, and makes the children of each survivor the next round's candidates. A prefix whose
+//! count is below the threshold cannot have a heavy hitter below it, so its subtree is pruned.
+//! [`PrefixTree`] keeps the state of this loop. It works with
+//! [`Poplar1`](crate::vdaf::poplar1::Poplar1) and with [`Mastic`](crate::vdaf::mastic::Mastic),
+//! which take the same aggregation parameter, through [`PrefixTree::aggregation_param`].
+//!
+//! Each round reveals the counts of the candidates to the Collector, so operators may want to bound
+//! the number of rounds and of candidates. A [`PrefixTreePolicy`] sets the threshold for each
+//! level, the maximum number of candidates per round, and the depth at which the search stops.
+//!
+//! ```
+//! use prio::vdaf::prefix_tree::PrefixTree;
+//!
+//! let mut tree = PrefixTree::new(2, 10).unwrap();
+//! // Round 1: counts for prefixes 0 and 1.
+//! tree.advance(&[3, 12]).unwrap();
+//! // Round 2: counts for prefixes 10 and 11.
+//! tree.advance(&[11, 1]).unwrap();
+//! assert!(tree.is_done());
+//! assert_eq!(tree.heavy_hitters().len(), 1);
+//! ```
+
+use crate::{
+    idpf::IdpfInput,
+    vdaf::{poplar1::Poplar1AggregationParam, VdafError},
+};
+
+/// Limits on a heavy-hitters search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefixTreePolicy {
+    /// The minimum count of a prefix at each level: a prefix of length `l` survives if its count
+    /// is at least `thresholds[l - 1]`. The last threshold applies to the levels beyond the end.
+    pub thresholds: Vec<u64>,
+
+    /// The maximum number of candidates in a round, or `None` for no limit. If the survivors of a
+    /// round have too many children, only the survivors with the highest counts are expanded;
+    /// among equal counts, the lexicographically smaller prefix is preferred. Must be at least 2.
+    pub max_candidates: Option<usize>,
+
+    /// The prefix length at which the search stops, or `None` to search the whole tree. The
+    /// survivors at this length are reported as the heavy hitters.
+    pub max_depth: Option<usize>,
+}
+
+impl PrefixTreePolicy {
+    /// Returns a policy with the same `threshold` at every level and no other limits.
+    pub fn with_threshold(threshold: u64) -> Self {
+        Self {
+            thresholds: vec![threshold],
+            max_candidates: None,
+            max_depth: None,
+        }
+    }
+
+    fn threshold(&self, prefix_len: usize) -> u64 {
+        // Unwrap safety: `PrefixTree::with_policy` checks there is at least one threshold.
+        let last = *self.thresholds.last().unwrap();
+        self.thresholds.get(prefix_len - 1).copied().unwrap_or(last)
+    }
+}
+
+/// Drives the search for the `bits`-bit inputs whose count is at least a threshold. See the
+/// [module documentation](self) for details.
+#[derive(Clone, Debug)]
+pub struct PrefixTree {
+    bits: usize,
+    policy: PrefixTreePolicy,
+    candidates: Vec<IdpfInput>,
+    heavy_hitters: Vec<(IdpfInput, u64)>,
+}
+
+impl PrefixTree {
+    /// Starts a search for `bits`-bit heavy hitters whose count is at least `threshold`. The first
+    /// round's candidates are the two prefixes of length 1.
+    pub fn new(bits: usize, threshold: u64) -> Result<Self, VdafError> {
+        Self::with_policy(bits, PrefixTreePolicy::with_threshold(threshold))
+    }
+
+    /// Starts a search for `bits`-bit heavy hitters within the limits of `policy`.
+    pub fn with_policy(bits: usize, policy: PrefixTreePolicy) -> Result<Self, VdafError> {
+        if bits == 0 || bits > usize::from(u16::MAX) {
+            return Err(VdafError::Uncategorized(format!(
+                "bits must be between 1 and {}",
+                u16::MAX
+            )));
+        }
+        if policy.thresholds.is_empty() {
+            return Err(VdafError::Uncategorized(
+                "at least one threshold is required".into(),
+            ));
+        }
+        if policy.max_candidates.is_some_and(|max| max < 2) {
+            return Err(VdafError::Uncategorized(
+                "maximum number of candidates must be at least 2".into(),
+            ));
+        }
+        if policy
+            .max_depth
+            .is_some_and(|depth| depth == 0 || depth > bits)
+        {
+            return Err(VdafError::Uncategorized(format!(
+                "maximum depth must be between 1 and {bits}"
+            )));
+        }
+        Ok(Self {
+            bits,
+            policy,
+            candidates: vec![
+                IdpfInput::from_bools(&[false]),
+                IdpfInput::from_bools(&[true]),
+            ],
+            heavy_hitters: Vec::new(),
+        })
+    }
+
+    /// Returns the policy of this search.
+    pub fn policy(&self) -> &PrefixTreePolicy {
+        &self.policy
+    }
+
+    /// Returns the length of the candidate prefixes of the current round, or 0 when the search is
+    /// done.
+    pub fn prefix_len(&self) -> usize {
+        self.candidates.first().map_or(0, IdpfInput::len)
+    }
+
+    /// Returns the candidate prefixes of the current round, in lexicographic order.
+    pub fn prefixes(&self) -> &[IdpfInput] {
+        &self.candidates
+    }
+
+    /// Returns the Poplar1 aggregation parameter for the current round. Fails if the search is
+    /// done.
+    pub fn aggregation_param(&self) -> Result<Poplar1AggregationParam, VdafError> {
+        if self.is_done() {
+            return Err(VdafError::Uncategorized(
+                "heavy-hitters search is done".into(),
+            ));
+        }
+        Poplar1AggregationParam::try_from_prefixes(self.candidates.clone())
+    }
+
+    /// Takes the aggregate result of the current round, one count per candidate prefix, and picks
+    /// the candidates for the next round. At the last level, or the policy's maximum depth, the
+    /// candidates that meet the threshold are the heavy hitters and the search is done.
+    pub fn advance(&mut self, counts: &[u64]) -> Result<(), VdafError> {
+        if self.is_done() {
+            return Err(VdafError::Uncategorized(
+                "heavy-hitters search is done".into(),
+            ));
+        }
+        if counts.len() != self.candidates.len() {
+            return Err(VdafError::Uncategorized(format!(
+                "got {} counts for {} prefixes",
+                counts.len(),
+                self.candidates.len()
+            )));
+        }
+        let prefix_len = self.prefix_len();
+        let last_level = prefix_len == self.policy.max_depth.unwrap_or(self.bits);
+        let threshold = self.policy.threshold(prefix_len);
+        let mut survivors: Vec<(IdpfInput, u64)> = std::mem::take(&mut self.candidates)
+            .into_iter()
+            .zip(counts.iter().copied())
+            .filter(|(_, count)| *count >= threshold)
+            .collect();
+        if last_level {
+            self.heavy_hitters = survivors;
+            return Ok(());
+        }
+
+        if let Some(max_candidates) = self.policy.max_candidates {
+            let max_survivors = max_candidates / 2;
+            if survivors.len() > max_survivors {
+                // The sort is stable and the survivors are in lexicographic order, so ties go to
+                // the smaller prefix.
+                survivors.sort_by(|(_, a), (_, b)| b.cmp(a));
+                survivors.truncate(max_survivors);
+                survivors.sort_by(|(a, _), (b, _)| a.cmp(b));
+            }
+        }
+        self.candidates = survivors
+            .into_iter()
+            .flat_map(|(prefix, _)| {
+                [
+                    prefix.clone_with_suffix(&[false]),
+                    prefix.clone_with_suffix(&[true]),
+                ]
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Returns true if there are no more rounds: the last level has been counted, or no candidate
+    /// met the threshold.
+    pub fn is_done(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Returns the heavy hitters and their counts, in lexicographic order. This is empty until the
+    /// search is done.
+    pub fn heavy_hitters(&self) -> &[(IdpfInput, u64)] {
+        &self.heavy_hitters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{poplar1::Poplar1, test_utils::run_vdaf};
+
+    #[test]
+    fn poplar1_heavy_hitters() {
+        let bits = 4;
+        let inputs = [
+            [false, true, true, false],
+            [false, true, true, false],
+            [false, true, true, false],
+            [false, true, true, true],
+            [true, false, false, true],
+            [true, false, false, true],
+            [true, false, false, true],
+            [true, true, true, true],
+        ];
+        let measurements: Vec<IdpfInput> = inputs
+            .iter()
+            .map(|bits| IdpfInput::from_bools(bits))
+            .collect();
+        let vdaf = Poplar1::new_turboshake128(bits);
+
+        let mut tree = PrefixTree::new(bits, 3).unwrap();
+        let mut rounds = 0;
+        while !tree.is_done() {
+            let agg_param = tree.aggregation_param().unwrap();
+            let counts = run_vdaf(&vdaf, &agg_param, measurements.clone()).unwrap();
+            tree.advance(&counts).unwrap();
+            rounds += 1;
+        }
+        assert_eq!(rounds, bits);
+        assert_eq!(
+            tree.heavy_hitters(),
+            [
+                (IdpfInput::from_bools(&inputs[0]), 3),
+                (IdpfInput::from_bools(&inputs[4]), 3),
+            ]
+        );
+        assert!(tree.aggregation_param().is_err());
+        assert!(tree.advance(&[]).is_err());
+    }
+
+    #[test]
+    fn prefix_tree_pruning() {
+        let mut tree = PrefixTree::new(3, 5).unwrap();
+        assert_eq!(tree.prefix_len(), 1);
+        assert!(tree.advance(&[1]).is_err());
+
+        tree.advance(&[5, 4]).unwrap();
+        assert_eq!(
+            tree.prefixes(),
+            [
+                IdpfInput::from_bools(&[false, false]),
+                IdpfInput::from_bools(&[false, true]),
+            ]
+        );
+
+        // No prefix meets the threshold, so the search ends early.
+        tree.advance(&[2, 3]).unwrap();
+        assert!(tree.is_done());
+        assert_eq!(tree.prefix_len(), 0);
+        assert!(tree.heavy_hitters().is_empty());
+
+        assert!(PrefixTree::new(0, 1).is_err());
+    }
+
+    #[test]
+    fn prefix_tree_policy() {
+        let policy = PrefixTreePolicy {
+            thresholds: vec![1, 4],
+            max_candidates: Some(5),
+            max_depth: Some(3),
+        };
+        let mut tree = PrefixTree::with_policy(8, policy.clone()).unwrap();
+        assert_eq!(tree.policy(), &policy);
+
+        // Both prefixes meet the first threshold.
+        tree.advance(&[1, 2]).unwrap();
+        assert_eq!(tree.prefixes().len(), 4);
+
+        // Three prefixes meet the second threshold, but only two may be expanded. The highest
+        // count wins, and the tie between 00 and 11 goes to 00.
+        tree.advance(&[4, 3, 6, 4]).unwrap();
+        assert_eq!(
+            tree.prefixes(),
+            [
+                IdpfInput::from_bools(&[false, false, false]),
+                IdpfInput::from_bools(&[false, false, true]),
+                IdpfInput::from_bools(&[true, false, false]),
+                IdpfInput::from_bools(&[true, false, true]),
+            ]
+        );
+
+        // The search stops at the maximum depth, and the last threshold still applies.
+        tree.advance(&[4, 0, 3, 5]).unwrap();
+        assert!(tree.is_done());
+        assert_eq!(
+            tree.heavy_hitters(),
+            [
+                (IdpfInput::from_bools(&[false, false, false]), 4),
+                (IdpfInput::from_bools(&[true, false, true]), 5),
+            ]
+        );
+
+        for policy in [
+            PrefixTreePolicy {
+                thresholds: Vec::new(),
+                ..policy.clone()
+            },
+            PrefixTreePolicy {
+                max_candidates: Some(1),
+                ..policy.clone()
+            },
+            PrefixTreePolicy {
+                max_depth: Some(9),
+                ..policy
+            },
+        ] {
+            assert!(PrefixTree::with_policy(8, policy).is_err());
+        }
+    }
+}
@@ -1,47 +1,147 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! Backwards-compatible port of the ENPA Prio system to a VDAF.
+//!
+//! # Share sizes
+//!
+//! The Helper's input share is a 32-byte seed. The Leader's input share holds one [`FieldPrio2`]
+//! element, 4 bytes, for each entry of the measurement and of the proof. The Leader's shares of
+//! the measurement bits cannot be packed into fewer bytes: each is the bit minus a value the
+//! Helper derives from its seed, and so is uniformly distributed over the field. An encoding of
+//! about one bit per entry would reveal the measurement to the Leader, or would need masked
+//! (XOR) shares that the Aggregators could only convert to additive shares with another round of
+//! interaction.
 
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+use crate::vdaf::ClientWithRng;
 use crate::{
     codec::{CodecError, Decode, Encode, ParameterizedDecode},
+    fft::{CpuFftBackend, FftBackend},
     field::{
         decode_fieldvec, FftFriendlyFieldElement, FieldElement, FieldElementWithInteger, FieldPrio2,
     },
+    flp::soundness::SoundnessError,
+    polynomial::FieldOver,
     prng::Prng,
     vdaf::{
+        accumulator::LaneAccumulator,
         prio2::{
-            client::{self as v2_client, proof_length},
-            server as v2_server,
+            client::{self as v2_client, proof_length, ProofLayout},
+            ext::FieldPrio2Ext,
+            server::{self as v2_server, VerificationMessage},
         },
+        telemetry,
         xof::Seed,
         Aggregatable, AggregateShare, Aggregator, Client, Collector, OutputShare,
-        PrepareTransition, Share, ShareDecodingParameter, Vdaf, VdafError,
+        PrepareTransition, RejectionReason, Share, ShareDecodingParameter, Vdaf, VdafError,
     },
 };
 use hmac::{Hmac, Mac};
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+use rand_core::CryptoRng;
 use rand_core::RngCore;
 use sha2::Sha256;
-use std::{convert::TryFrom, io::Cursor};
+use std::{convert::TryFrom, io::Cursor, sync::Arc};
 use subtle::{Choice, ConstantTimeEq};
 
 mod client;
+mod ext;
+pub mod layout;
+pub mod proof;
 mod server;
 #[cfg(test)]
 mod test_vector;
 
+/// The version of the construction that expands a helper's seed into its share.
+///
+/// Version 1 is the construction deployed in ENPA, and is frozen:
+///
+/// * The 32-byte seed is split into an AES-128 key ([`SEED_EXPANSION_KEY_LEN`] bytes) followed by
+///   a CTR-mode initialization vector ([`SEED_EXPANSION_IV_LEN`] bytes).
+/// * The key stream is AES-128 in CTR mode, where the last 8 bytes of the IV are a big-endian
+///   counter, incremented once per 16-byte block, and the first 8 bytes are fixed.
+/// * The key stream is read in consecutive 4-byte little-endian chunks. A chunk is the next element
+///   of the share if it is less than the modulus of [`FieldPrio2`], and is otherwise discarded.
+///
+/// [`seed_expansion_self_test`] checks this construction against a fixed test vector.
+pub const SEED_EXPANSION_VERSION: u32 = 1;
+
+/// The number of bytes of the seed used as the AES-128 key. See [`SEED_EXPANSION_VERSION`].
+pub const SEED_EXPANSION_KEY_LEN: usize = 16;
+
+/// The number of bytes of the seed used as the CTR-mode IV. See [`SEED_EXPANSION_VERSION`].
+pub const SEED_EXPANSION_IV_LEN: usize = 16;
+
+/// Checks that seeds are expanded as specified by [`SEED_EXPANSION_VERSION`], by expanding a fixed
+/// seed and comparing the result with a known answer. An application can run this at startup to
+/// guard against a miscompiled or substituted cipher implementation.
+pub fn seed_expansion_self_test() -> Result<(), VdafError> {
+    const SEED: [u8; 32] = [
+        0xcd, 0x85, 0x5b, 0xd4, 0x86, 0x48, 0xa4, 0xce, 0x52, 0x5c, 0x36, 0xee, 0x5a, 0x71, 0xf3,
+        0x0f, 0x66, 0x80, 0xd3, 0x67, 0x53, 0x9a, 0x39, 0x6f, 0x12, 0x2f, 0xad, 0x94, 0x4d, 0x34,
+        0xcb, 0x58,
+    ];
+    const EXPECTED: [u32; 8] = [
+        0xd0056ec5, 0xe23f9c52, 0x47e4ddb4, 0xbe5dacf6, 0x4b130aba, 0x530c7a90, 0xe8fc4ee5,
+        0xb0569cb7,
+    ];
+    let share = Prng::<FieldPrio2, _>::from_prio2_seed(&SEED).take(EXPECTED.len());
+    if share.map(u32::from).eq(EXPECTED) {
+        Ok(())
+    } else {
+        Err(VdafError::Uncategorized(
+            "seed expansion does not match the test vector".into(),
+        ))
+    }
+}
+
+/// Scratch memory for preparing Prio2 input shares. An Aggregator can keep one and pass it to
+/// [`Prio2::prepare_init_with_memory`] for each report, so that the memory is allocated once. It
+/// holds the [validation memory](Prio2::validation_memory_len) for each chunk length it has been
+/// used with, allocated when first needed.
+#[derive(Debug, Default)]
+pub struct Prio2ValidationMemory(Vec<(usize, v2_server::ValidationMemory<FieldPrio2>)>);
+
+impl Prio2ValidationMemory {
+    /// Returns the memory for chunks of length `dimension`, allocating it if this is the first
+    /// chunk of this length, as long as it fits in `limit` bytes.
+    fn get(
+        &mut self,
+        dimension: usize,
+        limit: usize,
+    ) -> Result<&mut v2_server::ValidationMemory<FieldPrio2>, v2_server::ServerError> {
+        let index = match self.0.iter().position(|(len, _)| *len == dimension) {
+            Some(index) => index,
+            None => {
+                let mem = v2_server::ValidationMemory::new(dimension, limit)?;
+                self.0.push((dimension, mem));
+                self.0.len() - 1
+            }
+        };
+        Ok(&mut self.0[index].1)
+    }
+}
+
 /// The Prio2 VDAF. It supports the same measurement type as
 /// [`Prio3SumVec`](crate::vdaf::prio3::Prio3SumVec) with `bits == 1` but uses the proof system and
 /// finite field deployed in ENPA.
 #[derive(Clone, Debug)]
 pub struct Prio2 {
     input_len: usize,
+    num_aggregators: u8,
+    fft_backend: Arc<dyn FftBackend<FieldPrio2>>,
+    extended_verification: bool,
+    chunk_len: usize,
+    validation_memory_limit: usize,
 }
 
 impl Prio2 {
     /// Returns an instance of the VDAF for the given input length.
     pub fn new(input_len: usize) -> Result<Self, VdafError> {
-        let n = (input_len + 1).next_power_of_two();
-        if let Ok(size) = u32::try_from(2 * n) {
+        let fft_len = ProofLayout::new(input_len)
+            .ok_or_else(|| VdafError::Uncategorized("input size exceeds memory capacity".into()))?
+            .fft_len();
+        if let Ok(size) = u32::try_from(fft_len) {
             if size > FieldPrio2::generator_order() {
                 return Err(VdafError::Uncategorized(
                     "input size exceeds field capacity".into(),
@@ -53,7 +153,173 @@ impl Prio2 {
             ));
         }
 
-        Ok(Prio2 { input_len })
+        let prio2 = Prio2 {
+            input_len,
+            num_aggregators: 2,
+            fft_backend: Arc::new(CpuFftBackend),
+            extended_verification: false,
+            chunk_len: input_len,
+            validation_memory_limit: v2_server::DEFAULT_VALIDATION_MEMORY_LIMIT,
+        };
+        prio2.check_validation_memory()?;
+        Ok(prio2)
+    }
+
+    /// Use `fft_backend` to compute the DFTs needed to prepare input shares. By default, these are
+    /// computed with [`CpuFftBackend`].
+    pub fn with_fft_backend(mut self, fft_backend: Arc<dyn FftBackend<FieldPrio2>>) -> Self {
+        self.fft_backend = fft_backend;
+        self
+    }
+
+    /// Verify proofs at a random point of the quadratic extension of [`FieldPrio2`] rather than of
+    /// [`FieldPrio2`] itself.
+    ///
+    /// Measurements and proofs are still shared in [`FieldPrio2`], so Clients and input shares are
+    /// unchanged; only the polynomial identity check moves to the larger field. This squares the
+    /// field size in the [soundness error](Self::soundness_error), at the cost of doubling the
+    /// size of prepare shares. All Aggregators of a task must enable it or none.
+    pub fn with_extended_verification(mut self) -> Self {
+        self.extended_verification = true;
+        self
+    }
+
+    /// Split measurements into `num_aggregators` shares instead of two. As with two Aggregators, the
+    /// leader's share holds the masked measurement and proof, and every other share is a seed that
+    /// the Aggregator expands with a PRNG, so each additional Aggregator adds only 32 bytes to a
+    /// report.
+    ///
+    /// This is an extension of ENPA Prio, which was deployed with exactly two Aggregators.
+    pub fn with_num_aggregators(mut self, num_aggregators: u8) -> Result<Self, VdafError> {
+        if num_aggregators < 2 {
+            return Err(VdafError::Uncategorized(format!(
+                "invalid number of aggregators: {num_aggregators}"
+            )));
+        }
+        self.num_aggregators = num_aggregators;
+        Ok(self)
+    }
+
+    /// Split measurements into chunks of `chunk_len` entries, the last of which may be shorter,
+    /// and prove each chunk separately.
+    ///
+    /// The leader's share is the concatenation of the chunks' shares, each laid out like the share
+    /// of a measurement of the chunk's length, and every prepare share holds one verifier share
+    /// per chunk. Aggregators can compute the verifier share of a chunk as soon as they have its
+    /// part of the input share, and a report that fails verification can be traced to the chunks
+    /// whose proofs are invalid with [`Prio2::invalid_chunks`]. Each chunk adds a few field
+    /// elements to the leader's share and three to every prepare share.
+    ///
+    /// This is an extension of ENPA Prio, which proved the whole measurement at once. All
+    /// Aggregators of a task must use the same chunk length.
+    pub fn with_chunk_length(mut self, chunk_len: usize) -> Result<Self, VdafError> {
+        if chunk_len == 0 || chunk_len > self.input_len {
+            return Err(VdafError::Uncategorized(format!(
+                "chunk length must be between 1 and the input length, got {chunk_len}"
+            )));
+        }
+        self.chunk_len = chunk_len;
+        Ok(self)
+    }
+
+    /// Limit the scratch memory an Aggregator allocates to prepare an input share to `limit`
+    /// bytes. The default limit is 256 MiB. Returns an error if the
+    /// [validation memory](Self::validation_memory_len) for the input length, or for the
+    /// [chunk length](Self::with_chunk_length) if one is set, exceeds the limit.
+    pub fn with_validation_memory_limit(mut self, limit: usize) -> Result<Self, VdafError> {
+        self.validation_memory_limit = limit;
+        self.check_validation_memory()?;
+        Ok(self)
+    }
+
+    /// The length in bytes of the scratch memory an Aggregator allocates to prepare an input
+    /// share. It is reused across chunks, and across reports with
+    /// [`Prio2::prepare_init_with_memory`].
+    pub fn validation_memory_len(&self) -> usize {
+        // Unwrap safety: the constructor checks that this does not overflow.
+        v2_server::ValidationMemory::<FieldPrio2>::size(self.chunk_len.min(self.input_len)).unwrap()
+    }
+
+    fn check_validation_memory(&self) -> Result<(), VdafError> {
+        let required =
+            v2_server::ValidationMemory::<FieldPrio2>::size(self.chunk_len.min(self.input_len));
+        if required.map_or(true, |required| required > self.validation_memory_limit) {
+            return Err(VdafError::Uncategorized(
+                v2_server::ServerError::MemoryLimit {
+                    required,
+                    limit: self.validation_memory_limit,
+                }
+                .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of chunks a measurement is split into. This is 1 unless a
+    /// [chunk length](Self::with_chunk_length) shorter than the input length is set.
+    pub fn num_chunks(&self) -> usize {
+        self.chunk_lens().len()
+    }
+
+    /// Returns the length of each chunk of a measurement.
+    fn chunk_lens(&self) -> Vec<usize> {
+        if self.chunk_len >= self.input_len {
+            return vec![self.input_len];
+        }
+        (0..self.input_len)
+            .step_by(self.chunk_len)
+            .map(|start| self.chunk_len.min(self.input_len - start))
+            .collect()
+    }
+
+    /// Returns the number of field elements in the leader's input share.
+    fn leader_share_len(&self) -> usize {
+        self.chunk_lens().into_iter().map(proof_length).sum()
+    }
+
+    /// Returns an upper bound on the probability that the Aggregators accept an invalid
+    /// measurement. The proof calls a degree-2 gadget once per entry of the measurement, and the
+    /// field has only 32 bits, so the bound grows quickly with the input length.
+    pub fn soundness_error(&self) -> Result<SoundnessError, VdafError> {
+        let mut field_size = u128::from(FieldPrio2::modulus());
+        if self.extended_verification {
+            field_size *= field_size;
+        }
+        Ok(SoundnessError::new(field_size, self.input_len, 2, 1)?)
+    }
+
+    /// Fails if the [soundness error](Self::soundness_error) exceeds `2^max_log2`.
+    pub fn check_soundness(&self, max_log2: f64) -> Result<(), VdafError> {
+        Ok(self.soundness_error()?.check(max_log2)?)
+    }
+
+    /// The length in bytes of the encoded input share of Aggregator `agg_id`. The leader's share
+    /// (`agg_id == 0`) holds the measurement and proof in full; every other Aggregator's share is
+    /// a seed.
+    pub fn input_share_len(&self, agg_id: usize) -> usize {
+        if agg_id == 0 {
+            FieldPrio2::ENCODED_SIZE * self.leader_share_len()
+        } else {
+            32
+        }
+    }
+
+    /// The length in bytes of an encoded prepare share, which is three field elements, or three
+    /// elements of the extension field with [extended verification](Self::with_extended_verification),
+    /// per chunk.
+    pub fn prepare_share_len(&self) -> usize {
+        let len = if self.extended_verification {
+            6 * FieldPrio2::ENCODED_SIZE
+        } else {
+            3 * FieldPrio2::ENCODED_SIZE
+        };
+        len * self.num_chunks()
+    }
+
+    /// The length in bytes of an encoded aggregate share. This is also the size of the field
+    /// elements an Aggregator holds in memory for each batch.
+    pub fn aggregate_share_len(&self) -> usize {
+        FieldPrio2::ENCODED_SIZE * self.input_len
     }
 
     /// Prepare an input share for aggregation using the given field element `query_rand` to
@@ -68,37 +334,342 @@ impl Prio2 {
         input_share: &Share<FieldPrio2, 32>,
         is_leader: bool,
     ) -> Result<(Prio2PrepareState, Prio2PrepareShare), VdafError> {
-        let expanded_data: Option<Vec<FieldPrio2>> = match input_share {
-            Share::Leader(_) => None,
-            Share::Helper(ref seed) => {
-                let prng = Prng::from_prio2_seed(seed.as_ref());
-                Some(prng.take(proof_length(self.input_len)).collect())
-            }
-        };
-        let data = match input_share {
-            Share::Leader(ref data) => data,
-            Share::Helper(_) => expanded_data.as_ref().unwrap(),
-        };
-
-        let verifier_share = v2_server::generate_verification_message(
-            self.input_len,
+        let (state, verifier_shares) = self.prepare_init_at(
             query_rand,
-            data, // Combined input and proof shares
+            input_share,
             is_leader,
-        )
-        .map_err(|e| VdafError::Uncategorized(e.to_string()))?;
+            &mut Prio2ValidationMemory::default(),
+        )?;
+        Ok((
+            state,
+            Prio2PrepareShare(VerifierShare::Base(verifier_shares)),
+        ))
+    }
+
+    /// Like [`Aggregator::prepare_init`], but computes the verifier shares in `memory`. An
+    /// Aggregator that keeps one [`Prio2ValidationMemory`] per thread and passes it for every
+    /// report allocates the scratch memory for preparation only once, rather than once per report.
+    pub fn prepare_init_with_memory(
+        &self,
+        agg_key: &[u8; 32],
+        agg_id: usize,
+        nonce: &[u8; 16],
+        input_share: &Share<FieldPrio2, 32>,
+        memory: &mut Prio2ValidationMemory,
+    ) -> Result<(Prio2PrepareState, Prio2PrepareShare), VdafError> {
+        let is_leader = self.role_try_from(agg_id)?;
+
+        // In the ENPA Prio system, the query randomness is generated by a third party and
+        // distributed to the Aggregators after they receive their input shares. In a VDAF, shared
+        // randomness is derived from a nonce selected by the client. For Prio2 we compute the
+        // query using HMAC-SHA256 evaluated over the nonce.
+        //
+        // Unwrap safety: new_from_slice() is infallible for Hmac.
+        let mut mac = Hmac::<Sha256>::new_from_slice(agg_key).unwrap();
+        mac.update(nonce);
+        let hmac_tag = mac.finalize();
+        let mut prng = Prng::from_prio2_seed(&hmac_tag.into_bytes().into());
+        if self.extended_verification {
+            let query_rand = self.choose_eval_at_ext(&mut prng);
+            let (state, verifier_shares) =
+                self.prepare_init_at(query_rand, input_share, is_leader, memory)?;
+            Ok((
+                state,
+                Prio2PrepareShare(VerifierShare::Extended(verifier_shares)),
+            ))
+        } else {
+            let query_rand = self.choose_eval_at(&mut prng);
+            let (state, verifier_shares) =
+                self.prepare_init_at(query_rand, input_share, is_leader, memory)?;
+            Ok((
+                state,
+                Prio2PrepareShare(VerifierShare::Base(verifier_shares)),
+            ))
+        }
+    }
+
+    /// Computes the verifier share of each chunk at `eval_at`, which may lie in the extension
+    /// field.
+    fn prepare_init_at<E: FieldOver<FieldPrio2>>(
+        &self,
+        eval_at: E,
+        input_share: &Share<FieldPrio2, 32>,
+        is_leader: bool,
+        memory: &mut Prio2ValidationMemory,
+    ) -> Result<(Prio2PrepareState, Vec<VerificationMessage<E>>), VdafError> {
+        let chunk_lens = self.chunk_lens();
+        let to_rejection = |e: v2_server::ServerError| {
+            let reason = match e {
+                v2_server::ServerError::Serialize(_) => RejectionReason::ProofUnpack,
+                v2_server::ServerError::ShareLength => RejectionReason::LengthMismatch,
+                _ => return VdafError::Uncategorized(e.to_string()),
+            };
+            telemetry::rejected("prio2", reason, VdafError::Uncategorized(e.to_string()))
+        };
+        if let Share::Leader(data) = input_share {
+            if chunk_lens.len() > 1 && data.len() != self.leader_share_len() {
+                return Err(to_rejection(v2_server::ServerError::Serialize(
+                    v2_client::SerializeError::UnpackInputSizeMismatch,
+                )));
+            }
+        }
+
+        let mut verifier_shares = Vec::with_capacity(chunk_lens.len());
+        let mut truncated_data = Vec::new();
+        // The helper's share is read from its PRNG as it is needed, without expanding it.
+        let mut helper_prng = match input_share {
+            Share::Leader(_) => None,
+            Share::Helper(seed) => Some(Prng::<FieldPrio2, _>::from_prio2_seed(seed.as_ref())),
+        };
+        let mut offset = 0;
+        for chunk_len in chunk_lens {
+            let mem = memory
+                .get(chunk_len, self.validation_memory_limit)
+                .map_err(to_rejection)?;
+            let verifier_share = match (input_share, helper_prng.as_mut()) {
+                (Share::Leader(data), _) => {
+                    // With a single chunk, the whole share is passed on so that its length is
+                    // checked when the proof is unpacked.
+                    let proof = if offset == 0 && chunk_len == self.input_len {
+                        &data[..]
+                    } else {
+                        &data[offset..offset + proof_length(chunk_len)]
+                    };
+                    truncated_data.extend_from_slice(proof.get(..chunk_len).unwrap_or(proof));
+                    v2_server::generate_verification_message(
+                        chunk_len,
+                        eval_at,
+                        proof, // Combined input and proof shares
+                        is_leader,
+                        mem,
+                        self.fft_backend.as_ref(),
+                    )
+                }
+                (Share::Helper(_), Some(prng)) => {
+                    v2_server::generate_verification_message_streamed(
+                        chunk_len,
+                        eval_at,
+                        prng.by_ref(),
+                        is_leader,
+                        mem,
+                        self.fft_backend.as_ref(),
+                    )
+                }
+                (Share::Helper(_), None) => unreachable!("helper shares have a PRNG"),
+            }
+            .map_err(to_rejection)?;
+            verifier_shares.push(verifier_share);
+            offset += proof_length(chunk_len);
+        }
 
         let truncated_share = match input_share {
-            Share::Leader(data) => Share::Leader(data[..self.input_len].to_vec()),
+            Share::Leader(_) => Share::Leader(truncated_data),
             Share::Helper(seed) => Share::Helper(seed.clone()),
         };
 
         Ok((
-            Prio2PrepareState(truncated_share),
-            Prio2PrepareShare(verifier_share),
+            Prio2PrepareState(
+                truncated_share,
+                self.extended_verification,
+                verifier_shares.len(),
+            ),
+            verifier_shares,
         ))
     }
 
+    /// Returns the indices of the chunks whose proofs the prepare shares, the leader's first, do
+    /// not verify. An empty list means that the report is valid. With a single
+    /// [chunk](Self::with_chunk_length), this only says whether the report is valid; with several,
+    /// it localizes the corruption of an invalid report.
+    ///
+    /// Returns an error with [`RejectionReason::MalformedMessage`] if a prepare share violates an
+    /// invariant of honest messages, such as being all zeros, rather than checking the proofs with
+    /// it.
+    pub fn invalid_chunks(
+        &self,
+        prep_shares: &[Prio2PrepareShare],
+    ) -> Result<Vec<usize>, VdafError> {
+        let mut base = Vec::new();
+        let mut extended = Vec::new();
+        for share in prep_shares {
+            match &share.0 {
+                VerifierShare::Base(share) => base.push(share.as_slice()),
+                VerifierShare::Extended(share) => extended.push(share.as_slice()),
+            }
+        }
+        let num_shares = if self.extended_verification {
+            extended.len()
+        } else {
+            base.len()
+        };
+        if num_shares != self.num_aggregators()
+            || base.len() + extended.len() != num_shares
+            || base
+                .iter()
+                .map(|share| share.len())
+                .chain(extended.iter().map(|share| share.len()))
+                .any(|len| len != self.num_chunks())
+        {
+            return Err(telemetry::rejected(
+                "prio2",
+                RejectionReason::LengthMismatch,
+                VdafError::Uncategorized("wrong number of verifier shares".into()),
+            ));
+        }
+
+        let malformed = if self.extended_verification {
+            malformed_verifier_share(&extended)
+        } else {
+            malformed_verifier_share(&base)
+        };
+        if let Some(msg) = malformed {
+            return Err(telemetry::rejected(
+                "prio2",
+                RejectionReason::MalformedMessage,
+                VdafError::Uncategorized(msg),
+            ));
+        }
+
+        Ok(if self.extended_verification {
+            invalid_chunks(&extended)
+        } else {
+            invalid_chunks(&base)
+        })
+    }
+
+    /// Encodes a measurement as field elements, checking that it has the expected length and that
+    /// every entry is 0 or 1.
+    fn encode_measurement(&self, measurement: &[u32]) -> Result<Vec<FieldPrio2>, VdafError> {
+        if measurement.len() != self.input_len {
+            return Err(VdafError::Uncategorized("incorrect input length".into()));
+        }
+        measurement
+            .iter()
+            .enumerate()
+            .map(|(i, int)| {
+                if *int > 1 {
+                    return Err(VdafError::Uncategorized(format!(
+                        "measurement entry {i} is {int}, but entries must be 0 or 1"
+                    )));
+                }
+                Ok((*int).into())
+            })
+            .collect()
+    }
+
+    /// Allocates scratch memory for sharding measurements with [`Prio2::shard_with_memory`].
+    pub fn client_memory(&self) -> Result<Prio2ClientMemory, VdafError> {
+        let mut mems = Vec::new();
+        for chunk_len in self.chunk_lens() {
+            if mems
+                .iter()
+                .all(|mem: &v2_client::ClientMemory<_>| mem.dimension() != chunk_len)
+            {
+                mems.push(v2_client::ClientMemory::new(chunk_len)?);
+            }
+        }
+        Ok(Prio2ClientMemory(mems))
+    }
+
+    /// Shards a measurement like [`Client::shard`], but returns the leader's share split into its
+    /// [`Proof`](proof::Proof) components, along with the seed of each helper's share. This is
+    /// useful to applications that transport the shares in their own format. Fails if
+    /// measurements are split into several [chunks](Self::with_chunk_length).
+    pub fn prove(
+        &self,
+        measurement: &[u32],
+    ) -> Result<(proof::Proof<FieldPrio2>, Vec<Seed<32>>), VdafError> {
+        if self.num_chunks() > 1 {
+            return Err(VdafError::Uncategorized(
+                "chunked proofs cannot be split into their components".into(),
+            ));
+        }
+        let (_, input_shares) =
+            self.shard_with_memory(&mut self.client_memory()?, measurement, &[0; 16])?;
+        let mut input_shares = input_shares.into_iter();
+        let Some(Share::Leader(leader)) = input_shares.next() else {
+            unreachable!("the first input share is the leader's");
+        };
+        let leader = proof::Proof::from_flat(&leader, self.input_len)
+            .map_err(|e| VdafError::Uncategorized(e.to_string()))?;
+        let helper_seeds = input_shares
+            .map(|share| match share {
+                Share::Helper(seed) => seed,
+                Share::Leader(_) => unreachable!("only the first input share is the leader's"),
+            })
+            .collect();
+        Ok((leader, helper_seeds))
+    }
+
+    /// Shards a measurement like [`Client::shard`], but constructs the proof in `mem` instead of
+    /// freshly allocated memory. Clients that shard many measurements can allocate `mem` once with
+    /// [`Prio2::client_memory`] and reuse it for each of them.
+    pub fn shard_with_memory(
+        &self,
+        mem: &mut Prio2ClientMemory,
+        measurement: &[u32],
+        _nonce: &[u8; 16],
+    ) -> Result<((), Vec<Share<FieldPrio2, 32>>), VdafError> {
+        let prove_seed = Seed::generate()?;
+        let helper_seeds = (1..self.num_aggregators)
+            .map(|_| Seed::generate())
+            .collect::<Result<_, _>>()?;
+        let input = self.encode_measurement(measurement)?;
+        self.shard_with_seeds(mem, &input, &prove_seed, helper_seeds)
+    }
+
+    /// Shards an encoded measurement, deriving the proof's randomness from `prove_seed` and giving
+    /// each helper one of `helper_seeds`.
+    fn shard_with_seeds(
+        &self,
+        mem: &mut Prio2ClientMemory,
+        input: &[FieldPrio2],
+        prove_seed: &Seed<32>,
+        helper_seeds: Vec<Seed<32>>,
+    ) -> Result<((), Vec<Share<FieldPrio2, 32>>), VdafError> {
+        let chunk_lens = self.chunk_lens();
+        let mut leader_data = Vec::with_capacity(self.leader_share_len());
+        let mut chunks = input;
+        for (i, chunk_len) in chunk_lens.iter().copied().enumerate() {
+            let Some(chunk_mem) = mem.0.iter_mut().find(|mem| mem.dimension() == chunk_len) else {
+                return Err(VdafError::Uncategorized(
+                    "client memory was allocated for a different input length".into(),
+                ));
+            };
+            let (chunk, rest) = chunks.split_at(chunk_len);
+            chunks = rest;
+            let copy_data = |share_data: &mut [FieldPrio2]| {
+                share_data[..].clone_from_slice(chunk);
+            };
+            // A single chunk is proved with `prove_seed` itself, as in ENPA Prio.
+            let chunk_seed = if chunk_lens.len() == 1 {
+                prove_seed.clone()
+            } else {
+                chunk_prove_seed(prove_seed, i)
+            };
+            leader_data.extend(chunk_mem.prove_with(&chunk_seed, copy_data));
+        }
+
+        for helper_seed in &helper_seeds {
+            let helper_prng = Prng::from_prio2_seed(helper_seed.as_ref());
+            for (s1, d) in leader_data.iter_mut().zip(helper_prng) {
+                *s1 -= d;
+            }
+        }
+
+        let mut input_shares = vec![Share::Leader(leader_data)];
+        input_shares.extend(helper_seeds.into_iter().map(Share::Helper));
+        Ok(((), input_shares))
+    }
+
+    /// Returns whether `agg_id` is the leader, or an error if there is no such Aggregator.
+    fn role_try_from(&self, agg_id: usize) -> Result<bool, VdafError> {
+        if agg_id >= self.num_aggregators() {
+            return Err(VdafError::Uncategorized("unexpected aggregator id".into()));
+        }
+        Ok(agg_id == 0)
+    }
+
     /// Choose a random point for polynomial evaluation.
     ///
     /// The point returned is not one of the roots used for polynomial interpolation.
@@ -109,12 +680,25 @@ impl Prio2 {
         // Make sure the query randomness isn't a root of unity. Evaluating the proof at any of
         // these points would be a privacy violation, since these points were used by the prover to
         // construct the wire polynomials.
-        let n = (self.input_len + 1).next_power_of_two();
-        let proof_length = 2 * n;
+        // Unwrap safety: the constructor checks that the layout and this conversion succeed.
+        let fft_len = u32::try_from(ProofLayout::new(self.input_len).unwrap().fft_len()).unwrap();
         loop {
             let eval_at: FieldPrio2 = prng.get();
-            // Unwrap safety: the constructor checks that this conversion succeeds.
-            if eval_at.pow(u32::try_from(proof_length).unwrap()) != FieldPrio2::one() {
+            if eval_at.pow(fft_len) != FieldPrio2::one() {
+                return eval_at;
+            }
+        }
+    }
+
+    /// Choose a random point of the extension field for polynomial evaluation. The point is not
+    /// in [`FieldPrio2`], so it is not one of the roots used for polynomial interpolation.
+    fn choose_eval_at_ext<S>(&self, prng: &mut Prng<FieldPrio2, S>) -> FieldPrio2Ext
+    where
+        S: RngCore,
+    {
+        loop {
+            let eval_at = FieldPrio2Ext::new(prng.get(), prng.get());
+            if !eval_at.is_base() {
                 return eval_at;
             }
         }
@@ -135,47 +719,146 @@ impl Vdaf for Prio2 {
     }
 
     fn num_aggregators(&self) -> usize {
-        // Prio2 can easily be extended to support more than two Aggregators.
-        2
+        self.num_aggregators.into()
     }
 }
 
 impl Client<16> for Prio2 {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.input_len))
+    )]
     fn shard(
         &self,
         measurement: &Vec<u32>,
-        _nonce: &[u8; 16],
+        nonce: &[u8; 16],
     ) -> Result<(Self::PublicShare, Vec<Share<FieldPrio2, 32>>), VdafError> {
-        if measurement.len() != self.input_len {
-            return Err(VdafError::Uncategorized("incorrect input length".into()));
-        }
-        let mut input: Vec<FieldPrio2> = Vec::with_capacity(measurement.len());
-        for int in measurement {
-            input.push((*int).into());
-        }
+        self.shard_with_memory(&mut self.client_memory()?, measurement, nonce)
+    }
+}
 
-        let mut mem = v2_client::ClientMemory::new(self.input_len)?;
-        let copy_data = |share_data: &mut [FieldPrio2]| {
-            share_data[..].clone_from_slice(&input);
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+impl ClientWithRng<16> for Prio2 {
+    fn shard_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        measurement: &Vec<u32>,
+        _nonce: &[u8; 16],
+        rng: &mut R,
+    ) -> Result<(Self::PublicShare, Vec<Share<FieldPrio2, 32>>), VdafError> {
+        let mut seed = || {
+            let mut bytes = [0; 32];
+            rng.fill_bytes(&mut bytes);
+            Seed::from_bytes(bytes)
         };
-        let mut leader_data = mem.prove_with(self.input_len, copy_data);
+        let prove_seed = seed();
+        let helper_seeds = (1..self.num_aggregators).map(|_| seed()).collect();
+        let input = self.encode_measurement(measurement)?;
+        self.shard_with_seeds(
+            &mut self.client_memory()?,
+            &input,
+            &prove_seed,
+            helper_seeds,
+        )
+    }
+}
+
+/// Scratch memory for constructing Prio2 proofs, allocated by [`Prio2::client_memory`] and used
+/// by [`Prio2::shard_with_memory`].
+///
+/// The memory holds values derived from the last measurement sharded with it until
+/// [`Prio2ClientMemory::reset`] is called or it is dropped.
+#[derive(Debug)]
+pub struct Prio2ClientMemory(Vec<v2_client::ClientMemory<FieldPrio2>>);
+
+impl Prio2ClientMemory {
+    /// Zeroes the memory, so that nothing derived from previous measurements remains in it. It
+    /// can still be used afterwards.
+    pub fn reset(&mut self) {
+        self.0.iter_mut().for_each(v2_client::ClientMemory::reset)
+    }
+}
+
+/// Derives the seed for the proof of chunk `index` from the report's `prove_seed`.
+fn chunk_prove_seed(prove_seed: &Seed<32>, index: usize) -> Seed<32> {
+    // Unwrap safety: new_from_slice() is infallible for Hmac.
+    let mut mac = Hmac::<Sha256>::new_from_slice(prove_seed.as_ref()).unwrap();
+    mac.update(b"chunk");
+    // Unwrap safety: the number of chunks is at most the input length, which fits in a u32.
+    mac.update(&u32::try_from(index).unwrap().to_be_bytes());
+    Seed::from_bytes(mac.finalize().into_bytes().into())
+}
 
-        let helper_seed = Seed::generate()?;
-        let helper_prng = Prng::from_prio2_seed(helper_seed.as_ref());
-        for (s1, d) in leader_data.iter_mut().zip(helper_prng.into_iter()) {
-            *s1 -= d;
+/// Returns a description of the first verifier share, given per Aggregator with the leader's
+/// first, that no honest Aggregator sends but with negligible probability: one that is all zeros,
+/// as sent by a peer that skipped the computation, or one that equals another Aggregator's share
+/// for the same chunk, as sent by a peer that echoes the messages it receives. Field elements are
+/// canonical by construction, as decoding rejects encodings of integers at least the modulus.
+fn malformed_verifier_share<E: FieldOver<FieldPrio2> + From<FieldPrio2>>(
+    verifier_shares: &[&[VerificationMessage<E>]],
+) -> Option<String> {
+    let zero = E::from(FieldPrio2::zero());
+    for chunk in 0..verifier_shares[0].len() {
+        for (agg_id, shares) in verifier_shares.iter().enumerate() {
+            let share = &shares[chunk];
+            if bool::from(share.f_r.ct_eq(&zero) & share.g_r.ct_eq(&zero) & share.h_r.ct_eq(&zero))
+            {
+                return Some(format!(
+                    "verifier share of aggregator {agg_id} for chunk {chunk} is zero"
+                ));
+            }
+            for (other_id, other) in verifier_shares[..agg_id].iter().enumerate() {
+                let other = &other[chunk];
+                if bool::from(
+                    share.f_r.ct_eq(&other.f_r)
+                        & share.g_r.ct_eq(&other.g_r)
+                        & share.h_r.ct_eq(&other.h_r),
+                ) {
+                    return Some(format!(
+                        "verifier shares of aggregators {other_id} and {agg_id} for chunk {chunk} \
+                         are equal"
+                    ));
+                }
+            }
         }
+    }
+    None
+}
 
-        Ok((
-            (),
-            vec![Share::Leader(leader_data), Share::Helper(helper_seed)],
-        ))
+/// Returns the indices of the chunks whose verifier shares, given per Aggregator with the
+/// leader's first, do not sum to a valid verification message.
+fn invalid_chunks<E: FieldOver<FieldPrio2>>(
+    verifier_shares: &[&[VerificationMessage<E>]],
+) -> Vec<usize> {
+    (0..verifier_shares[0].len())
+        .filter(|chunk| {
+            let shares: Vec<_> = verifier_shares
+                .iter()
+                .map(|shares| shares[*chunk].clone())
+                .collect();
+            !verifier_shares_valid(&shares)
+        })
+        .collect()
+}
+
+/// Checks that the verifier shares, the leader's first, sum to a valid verification message.
+fn verifier_shares_valid<E: FieldOver<FieldPrio2>>(
+    verifier_shares: &[VerificationMessage<E>],
+) -> bool {
+    // The verifier is linear, so the helpers' shares can be combined before the check.
+    let mut helper_share = verifier_shares[1].clone();
+    for share in &verifier_shares[2..] {
+        helper_share.f_r += share.f_r;
+        helper_share.g_r += share.g_r;
+        helper_share.h_r += share.h_r;
     }
+    v2_server::is_valid_share(&verifier_shares[0], &helper_share)
 }
 
-/// State of each [`Aggregator`] during the Preparation phase.
+/// State of each [`Aggregator`] during the Preparation phase. Besides the share, it records
+/// whether [extended verification](Prio2::with_extended_verification) is enabled and the number of
+/// [chunks](Prio2::with_chunk_length), which determine how the prepare shares are decoded.
 #[derive(Clone, Debug)]
-pub struct Prio2PrepareState(Share<FieldPrio2, 32>);
+pub struct Prio2PrepareState(Share<FieldPrio2, 32>, bool, usize);
 
 impl PartialEq for Prio2PrepareState {
     fn eq(&self, other: &Self) -> bool {
@@ -188,6 +871,8 @@ impl Eq for Prio2PrepareState {}
 impl ConstantTimeEq for Prio2PrepareState {
     fn ct_eq(&self, other: &Self) -> Choice {
         self.0.ct_eq(&other.0)
+            & u8::from(self.1).ct_eq(&u8::from(other.1))
+            & (self.2 as u64).ct_eq(&(other.2 as u64))
     }
 }
 
@@ -212,44 +897,94 @@ impl<'a> ParameterizedDecode<(&'a Prio2, usize)> for Prio2PrepareState {
             ShareDecodingParameter::Helper
         };
         let out_share = Share::decode_with_param(&share_decoder, bytes)?;
-        Ok(Self(out_share))
+        Ok(Self(
+            out_share,
+            prio2.extended_verification,
+            prio2.num_chunks(),
+        ))
     }
 }
 
 /// Message emitted by each [`Aggregator`] during the Preparation phase.
-#[derive(Clone, Debug)]
-pub struct Prio2PrepareShare(v2_server::VerificationMessage<FieldPrio2>);
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prio2PrepareShare(VerifierShare);
+
+/// The verifier shares of each chunk, computed in [`FieldPrio2`] or, with extended verification,
+/// in its extension.
+#[derive(Clone, Debug, PartialEq)]
+enum VerifierShare {
+    Base(Vec<VerificationMessage<FieldPrio2>>),
+    Extended(Vec<VerificationMessage<FieldPrio2Ext>>),
+}
 
 impl Encode for Prio2PrepareShare {
     fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
-        self.0.f_r.encode(bytes)?;
-        self.0.g_r.encode(bytes)?;
-        self.0.h_r.encode(bytes)
+        match &self.0 {
+            VerifierShare::Base(shares) => encode_verifier_shares(shares, bytes),
+            VerifierShare::Extended(shares) => encode_verifier_shares(shares, bytes),
+        }
     }
 
     fn encoded_len(&self) -> Option<usize> {
-        Some(FieldPrio2::ENCODED_SIZE * 3)
+        match &self.0 {
+            VerifierShare::Base(shares) => Some(FieldPrio2::ENCODED_SIZE * 3 * shares.len()),
+            VerifierShare::Extended(shares) => Some(FieldPrio2::ENCODED_SIZE * 6 * shares.len()),
+        }
     }
 }
 
 impl ParameterizedDecode<Prio2PrepareState> for Prio2PrepareShare {
     fn decode_with_param(
-        _state: &Prio2PrepareState,
+        state: &Prio2PrepareState,
         bytes: &mut Cursor<&[u8]>,
     ) -> Result<Self, CodecError> {
-        Ok(Self(v2_server::VerificationMessage {
-            f_r: FieldPrio2::decode(bytes)?,
-            g_r: FieldPrio2::decode(bytes)?,
-            h_r: FieldPrio2::decode(bytes)?,
+        Ok(Self(if state.1 {
+            VerifierShare::Extended(
+                (0..state.2)
+                    .map(|_| decode_verifier_share(bytes))
+                    .collect::<Result<_, _>>()?,
+            )
+        } else {
+            VerifierShare::Base(
+                (0..state.2)
+                    .map(|_| decode_verifier_share(bytes))
+                    .collect::<Result<_, _>>()?,
+            )
         }))
     }
 }
 
+fn encode_verifier_shares<E: Encode>(
+    shares: &[VerificationMessage<E>],
+    bytes: &mut Vec<u8>,
+) -> Result<(), CodecError> {
+    for share in shares {
+        share.f_r.encode(bytes)?;
+        share.g_r.encode(bytes)?;
+        share.h_r.encode(bytes)?;
+    }
+    Ok(())
+}
+
+fn decode_verifier_share<E: Decode>(
+    bytes: &mut Cursor<&[u8]>,
+) -> Result<VerificationMessage<E>, CodecError> {
+    Ok(VerificationMessage {
+        f_r: E::decode(bytes)?,
+        g_r: E::decode(bytes)?,
+        h_r: E::decode(bytes)?,
+    })
+}
+
 impl Aggregator<32, 16> for Prio2 {
     type PrepareState = Prio2PrepareState;
     type PrepareShare = Prio2PrepareShare;
     type PrepareMessage = ();
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.input_len, agg_id))
+    )]
     fn prepare_init(
         &self,
         agg_key: &[u8; 32],
@@ -259,45 +994,48 @@ impl Aggregator<32, 16> for Prio2 {
         _public_share: &Self::PublicShare,
         input_share: &Share<FieldPrio2, 32>,
     ) -> Result<(Prio2PrepareState, Prio2PrepareShare), VdafError> {
-        let is_leader = role_try_from(agg_id)?;
-
-        // In the ENPA Prio system, the query randomness is generated by a third party and
-        // distributed to the Aggregators after they receive their input shares. In a VDAF, shared
-        // randomness is derived from a nonce selected by the client. For Prio2 we compute the
-        // query using HMAC-SHA256 evaluated over the nonce.
-        //
-        // Unwrap safety: new_from_slice() is infallible for Hmac.
-        let mut mac = Hmac::<Sha256>::new_from_slice(agg_key).unwrap();
-        mac.update(nonce);
-        let hmac_tag = mac.finalize();
-        let mut prng = Prng::from_prio2_seed(&hmac_tag.into_bytes().into());
-        let query_rand = self.choose_eval_at(&mut prng);
-
-        self.prepare_init_with_query_rand(query_rand, input_share, is_leader)
+        self.prepare_init_with_memory(
+            agg_key,
+            agg_id,
+            nonce,
+            input_share,
+            &mut Prio2ValidationMemory::default(),
+        )
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.input_len))
+    )]
     fn prepare_shares_to_prepare_message<M: IntoIterator<Item = Prio2PrepareShare>>(
         &self,
         _: &Self::AggregationParam,
         inputs: M,
     ) -> Result<(), VdafError> {
-        let verifier_shares: Vec<v2_server::VerificationMessage<FieldPrio2>> =
-            inputs.into_iter().map(|msg| msg.0).collect();
-        if verifier_shares.len() != 2 {
-            return Err(VdafError::Uncategorized(
-                "wrong number of verifier shares".into(),
-            ));
-        }
-
-        if !v2_server::is_valid_share(&verifier_shares[0], &verifier_shares[1]) {
-            return Err(VdafError::Uncategorized(
-                "proof verifier check failed".into(),
+        let _timer = telemetry::VerificationTimer::start("prio2");
+        let prep_shares: Vec<_> = inputs.into_iter().collect();
+        let invalid_chunks = self.invalid_chunks(&prep_shares)?;
+        if !invalid_chunks.is_empty() {
+            let msg = if self.num_chunks() == 1 {
+                "proof verifier check failed".to_string()
+            } else {
+                format!("proof verifier check failed for chunks {invalid_chunks:?}")
+            };
+            return Err(telemetry::rejected(
+                "prio2",
+                RejectionReason::InvalidProof,
+                VdafError::Uncategorized(msg),
             ));
         }
 
+        telemetry::verified("prio2");
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.input_len))
+    )]
     fn prepare_next(
         &self,
         state: Prio2PrepareState,
@@ -306,28 +1044,49 @@ impl Aggregator<32, 16> for Prio2 {
         let data = match state.0 {
             Share::Leader(data) => data,
             Share::Helper(seed) => {
-                let prng = Prng::from_prio2_seed(seed.as_ref());
-                prng.take(self.input_len).collect()
+                let mut prng = Prng::<FieldPrio2, _>::from_prio2_seed(seed.as_ref());
+                let mut data = Vec::with_capacity(self.input_len);
+                for chunk_len in self.chunk_lens() {
+                    data.extend(prng.by_ref().take(chunk_len));
+                    // Skip the chunk's proof.
+                    prng.by_ref()
+                        .take(proof_length(chunk_len) - chunk_len)
+                        .for_each(drop);
+                }
+                data
             }
         };
         Ok(PrepareTransition::Finish(OutputShare::from(data)))
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.input_len))
+    )]
     fn aggregate<M: IntoIterator<Item = OutputShare<FieldPrio2>>>(
         &self,
         _agg_param: &Self::AggregationParam,
         out_shares: M,
     ) -> Result<AggregateShare<FieldPrio2>, VdafError> {
-        let mut agg_share = AggregateShare(vec![FieldPrio2::zero(); self.input_len]);
+        // Sum the output shares in integer lanes, so that the modular reduction is deferred until
+        // the end rather than done for every element of every output share.
+        let mut lanes = LaneAccumulator::new(self.input_len);
+        let mut count = 0;
         for out_share in out_shares.into_iter() {
-            agg_share.accumulate(&out_share)?;
+            lanes.accumulate(&out_share)?;
+            count += 1;
         }
 
-        Ok(agg_share)
+        telemetry::aggregated("prio2", count);
+        Ok(lanes.to_aggregate_share())
     }
 }
 
 impl Collector for Prio2 {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.input_len))
+    )]
     fn unshard<M: IntoIterator<Item = AggregateShare<FieldPrio2>>>(
         &self,
         _agg_param: &Self::AggregationParam,
@@ -348,9 +1107,11 @@ impl<'a> ParameterizedDecode<(&'a Prio2, usize)> for Share<FieldPrio2, 32> {
         (prio2, agg_id): &(&'a Prio2, usize),
         bytes: &mut Cursor<&[u8]>,
     ) -> Result<Self, CodecError> {
-        let is_leader = role_try_from(*agg_id).map_err(|e| CodecError::Other(Box::new(e)))?;
+        let is_leader = prio2
+            .role_try_from(*agg_id)
+            .map_err(|e| CodecError::Other(Box::new(e)))?;
         let decoder = if is_leader {
-            ShareDecodingParameter::Leader(proof_length(prio2.input_len))
+            ShareDecodingParameter::Leader(prio2.leader_share_len())
         } else {
             ShareDecodingParameter::Helper
         };
@@ -383,20 +1144,13 @@ where
     }
 }
 
-fn role_try_from(agg_id: usize) -> Result<bool, VdafError> {
-    match agg_id {
-        0 => Ok(true),
-        1 => Ok(false),
-        _ => Err(VdafError::Uncategorized("unexpected aggregator id".into())),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::vdaf::{
-        equality_comparison_test, fieldvec_roundtrip_test, prio2::test_vector::Priov2TestVector,
-        test_utils::run_vdaf,
+        equality_comparison_test, fieldvec_roundtrip_test,
+        prio2::test_vector::Priov2TestVector,
+        test_utils::{run_vdaf, run_vdaf_prepare},
     };
     use assert_matches::assert_matches;
     use rand::prelude::*;
@@ -422,6 +1176,491 @@ mod tests {
         );
     }
 
+    #[test]
+    fn run_prio2_multiple_aggregators() {
+        let prio2 = Prio2::new(4).unwrap().with_num_aggregators(4).unwrap();
+        assert_eq!(prio2.num_aggregators(), 4);
+
+        let (_, input_shares) = prio2.shard(&vec![1, 0, 1, 1], &[0; 16]).unwrap();
+        assert_matches!(input_shares[0], Share::Leader(_));
+        assert!(input_shares[1..]
+            .iter()
+            .all(|share| matches!(share, Share::Helper(_))));
+
+        assert_eq!(
+            run_vdaf(&prio2, &(), [vec![1, 0, 1, 1], vec![0, 0, 1, 0]]).unwrap(),
+            vec![1, 0, 2, 1],
+        );
+        assert!(run_vdaf(&prio2, &(), [vec![2, 0, 0, 0]]).is_err());
+
+        assert_matches!(
+            Prio2::new(4).unwrap().with_num_aggregators(1),
+            Err(VdafError::Uncategorized(_))
+        );
+    }
+
+    #[test]
+    fn prio2_seed_expansion_self_test() {
+        seed_expansion_self_test().unwrap();
+    }
+
+    #[test]
+    fn prio2_prove() {
+        let prio2 = Prio2::new(4).unwrap().with_num_aggregators(3).unwrap();
+        let (leader, helper_seeds) = prio2.prove(&[1, 0, 1, 1]).unwrap();
+        assert_eq!(leader.dimension(), 4);
+        assert_eq!(helper_seeds.len(), 2);
+
+        let mut proof = leader.to_flat().unwrap();
+        for seed in &helper_seeds {
+            let helper = Prng::from_prio2_seed(seed.as_ref());
+            for (x, y) in proof.iter_mut().zip(helper) {
+                *x += y;
+            }
+        }
+        let proof = proof::Proof::from_flat(&proof, 4).unwrap();
+        assert_eq!(proof.data, [1, 0, 1, 1].map(FieldPrio2::from));
+        assert_eq!(proof.f0 * proof.g0, proof.h0);
+    }
+
+    #[test]
+    fn prio2_encoded_lens() {
+        let prio2 = Prio2::new(10).unwrap().with_num_aggregators(3).unwrap();
+        let (_, input_shares) = prio2.shard(&vec![1; 10], &[0; 16]).unwrap();
+        for (agg_id, input_share) in input_shares.iter().enumerate() {
+            assert_eq!(
+                input_share.get_encoded().unwrap().len(),
+                prio2.input_share_len(agg_id)
+            );
+            let (_, prep_share) = prio2
+                .prepare_init(&[0; 32], agg_id, &(), &[0; 16], &(), input_share)
+                .unwrap();
+            assert_eq!(
+                prep_share.get_encoded().unwrap().len(),
+                prio2.prepare_share_len()
+            );
+        }
+        let agg_share = prio2
+            .aggregate(&(), [OutputShare::from(vec![FieldPrio2::zero(); 10])])
+            .unwrap();
+        assert_eq!(
+            agg_share.get_encoded().unwrap().len(),
+            prio2.aggregate_share_len()
+        );
+    }
+
+    #[test]
+    fn prio2_soundness() {
+        let small = Prio2::new(10).unwrap().soundness_error().unwrap();
+        let large = Prio2::new(100_000).unwrap().soundness_error().unwrap();
+        assert!(small.log2() < large.log2());
+        assert!(Prio2::new(10).unwrap().check_soundness(-25.0).is_ok());
+        assert!(Prio2::new(100_000).unwrap().check_soundness(-25.0).is_err());
+    }
+
+    #[test]
+    fn run_prio2_extended_verification() {
+        let prio2 = Prio2::new(6).unwrap().with_extended_verification();
+        assert_eq!(
+            run_vdaf(
+                &prio2,
+                &(),
+                [
+                    vec![0, 0, 0, 0, 1, 0],
+                    vec![0, 1, 1, 0, 0, 0],
+                    vec![1, 1, 1, 0, 0, 1],
+                ]
+            )
+            .unwrap(),
+            vec![1, 2, 2, 0, 1, 1],
+        );
+        let three = prio2.clone().with_num_aggregators(3).unwrap();
+        assert_eq!(
+            run_vdaf(&three, &(), [vec![1, 0, 0, 1, 1, 0]]).unwrap(),
+            vec![1, 0, 0, 1, 1, 0]
+        );
+
+        // Input shares are the same as without extended verification.
+        let nonce = random();
+        let verify_key = random();
+        let (public_share, mut input_shares) = Prio2::new(6)
+            .unwrap()
+            .shard(&vec![1, 0, 1, 0, 1, 0], &nonce)
+            .unwrap();
+        let (_, prep_share) = prio2
+            .prepare_init(&verify_key, 0, &(), &nonce, &public_share, &input_shares[0])
+            .unwrap();
+        assert_eq!(
+            prep_share.get_encoded().unwrap().len(),
+            prio2.prepare_share_len()
+        );
+        assert_eq!(
+            prio2.prepare_share_len(),
+            2 * Prio2::new(6).unwrap().prepare_share_len()
+        );
+        run_vdaf_prepare(
+            &prio2,
+            &verify_key,
+            &(),
+            &nonce,
+            public_share,
+            input_shares.clone(),
+        )
+        .unwrap();
+
+        // An invalid measurement is rejected.
+        let Share::Leader(ref mut leader) = input_shares[0] else {
+            panic!("unexpected input share");
+        };
+        leader[0] += FieldPrio2::one();
+        assert!(
+            run_vdaf_prepare(&prio2, &verify_key, &(), &nonce, (), input_shares.clone()).is_err()
+        );
+
+        // Verifier shares computed in different fields are not combined.
+        let (_, base_share) = Prio2::new(6)
+            .unwrap()
+            .prepare_init(&verify_key, 1, &(), &nonce, &(), &input_shares[1])
+            .unwrap();
+        assert_matches!(
+            prio2.prepare_shares_to_prepare_message(&(), [prep_share, base_share]),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::LengthMismatch,
+                ..
+            })
+        );
+
+        let base = Prio2::new(1000).unwrap().soundness_error().unwrap();
+        let extended = Prio2::new(1000)
+            .unwrap()
+            .with_extended_verification()
+            .soundness_error()
+            .unwrap();
+        assert!(extended.log2() < base.log2() - 30.0);
+    }
+
+    #[test]
+    fn run_prio2_chunked() {
+        let prio2 = Prio2::new(10).unwrap().with_chunk_length(4).unwrap();
+        assert_eq!(prio2.num_chunks(), 3);
+        assert_eq!(prio2.chunk_lens(), [4, 4, 2]);
+        let measurements = [
+            vec![1, 0, 0, 1, 1, 0, 0, 0, 1, 1],
+            vec![0, 1, 1, 1, 0, 0, 0, 1, 1, 0],
+        ];
+        assert_eq!(
+            run_vdaf(&prio2, &(), measurements.clone()).unwrap(),
+            vec![1, 1, 1, 2, 1, 0, 0, 1, 2, 1],
+        );
+        let extended = prio2
+            .clone()
+            .with_extended_verification()
+            .with_num_aggregators(3)
+            .unwrap();
+        assert_eq!(
+            run_vdaf(&extended, &(), measurements.clone()).unwrap(),
+            vec![1, 1, 1, 2, 1, 0, 0, 1, 2, 1],
+        );
+
+        // A chunk length of the input length is the same as no chunking.
+        let whole = Prio2::new(10).unwrap().with_chunk_length(10).unwrap();
+        assert_eq!(whole.num_chunks(), 1);
+        assert_eq!(
+            whole.input_share_len(0),
+            Prio2::new(10).unwrap().input_share_len(0)
+        );
+
+        let (public_share, mut input_shares) = prio2.shard(&measurements[0], &[0; 16]).unwrap();
+        let prep_shares = |input_shares: &[Share<FieldPrio2, 32>]| -> Vec<_> {
+            input_shares
+                .iter()
+                .enumerate()
+                .map(|(agg_id, input_share)| {
+                    let (state, share) = prio2
+                        .prepare_init(&[0; 32], agg_id, &(), &[0; 16], &public_share, input_share)
+                        .unwrap();
+                    assert_eq!(
+                        Prio2PrepareShare::get_decoded_with_param(
+                            &state,
+                            &share.get_encoded().unwrap()
+                        )
+                        .unwrap(),
+                        share
+                    );
+                    share
+                })
+                .collect()
+        };
+        for (agg_id, input_share) in input_shares.iter().enumerate() {
+            assert_eq!(
+                input_share.get_encoded().unwrap().len(),
+                prio2.input_share_len(agg_id)
+            );
+        }
+        let valid = prep_shares(&input_shares);
+        assert_eq!(
+            valid[0].get_encoded().unwrap().len(),
+            prio2.prepare_share_len()
+        );
+        assert_eq!(prio2.prepare_share_len(), 3 * whole.prepare_share_len());
+        assert!(prio2.invalid_chunks(&valid).unwrap().is_empty());
+
+        // Corrupting the data of the second chunk is traced to that chunk.
+        let Share::Leader(ref mut leader) = input_shares[0] else {
+            panic!("unexpected input share");
+        };
+        leader[proof_length(4) + 1] += FieldPrio2::one();
+        let invalid = prep_shares(&input_shares);
+        assert_eq!(prio2.invalid_chunks(&invalid).unwrap(), [1]);
+        let err = prio2
+            .prepare_shares_to_prepare_message(&(), invalid)
+            .unwrap_err();
+        assert_eq!(err.rejection_reason(), Some(RejectionReason::InvalidProof));
+        assert_eq!(
+            err.to_string(),
+            "report rejected (invalid_proof): vdaf error: proof verifier check failed for chunks [1]"
+        );
+
+        // Prepare shares of another chunk length are rejected.
+        let (_, other) = whole
+            .prepare_init(&[0; 32], 1, &(), &[0; 16], &(), &input_shares[1])
+            .unwrap();
+        assert_matches!(
+            prio2.prepare_shares_to_prepare_message(&(), [valid[0].clone(), other]),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::LengthMismatch,
+                ..
+            })
+        );
+
+        assert!(prio2.prove(&measurements[0]).is_err());
+        assert!(Prio2::new(10).unwrap().with_chunk_length(0).is_err());
+        assert!(Prio2::new(10).unwrap().with_chunk_length(11).is_err());
+    }
+
+    #[test]
+    fn prio2_client_memory_reuse() {
+        let prio2 = Prio2::new(5).unwrap();
+        let mut mem = prio2.client_memory().unwrap();
+        for measurement in [vec![1, 0, 0, 1, 1], vec![0, 1, 1, 0, 0]] {
+            let (public_share, input_shares) = prio2
+                .shard_with_memory(&mut mem, &measurement, &[0; 16])
+                .unwrap();
+            assert!(
+                run_vdaf_prepare(&prio2, &[0; 32], &(), &[0; 16], public_share, input_shares)
+                    .is_ok()
+            );
+        }
+        mem.reset();
+        let (public_share, input_shares) = prio2
+            .shard_with_memory(&mut mem, &[1, 1, 1, 1, 1], &[0; 16])
+            .unwrap();
+        assert!(
+            run_vdaf_prepare(&prio2, &[0; 32], &(), &[0; 16], public_share, input_shares).is_ok()
+        );
+
+        // Memory allocated for a different input length is rejected.
+        let mut other = Prio2::new(6).unwrap().client_memory().unwrap();
+        assert_matches!(
+            prio2.shard_with_memory(&mut other, &[1, 0, 0, 1, 1], &[0; 16]),
+            Err(VdafError::Uncategorized(_))
+        );
+    }
+
+    #[test]
+    fn prio2_rejection_reasons() {
+        let prio2 = Prio2::new(4).unwrap();
+        let (_, mut input_shares) = prio2.shard(&vec![0, 1, 1, 0], &[0; 16]).unwrap();
+        let Share::Leader(ref mut leader) = input_shares[0] else {
+            panic!("unexpected input share");
+        };
+        leader.pop();
+        let err = prio2
+            .prepare_init(&[0; 32], 0, &(), &[0; 16], &(), &input_shares[0])
+            .unwrap_err();
+        assert_eq!(err.rejection_reason(), Some(RejectionReason::ProofUnpack));
+
+        let (_, mut input_shares) = prio2.shard(&vec![0, 1, 1, 0], &[0; 16]).unwrap();
+        let Share::Leader(ref mut leader) = input_shares[0] else {
+            panic!("unexpected input share");
+        };
+        leader[0] += FieldPrio2::one();
+        let err = run_vdaf_prepare(&prio2, &[0; 32], &(), &[0; 16], (), input_shares).unwrap_err();
+        assert_eq!(err.rejection_reason(), Some(RejectionReason::InvalidProof));
+        assert_eq!(
+            err.to_string(),
+            "report rejected (invalid_proof): vdaf error: proof verifier check failed"
+        );
+
+        assert_eq!(
+            VdafError::Uncategorized("other".into()).rejection_reason(),
+            None
+        );
+    }
+
+    #[test]
+    fn prio2_malformed_peer_message() {
+        let prio2 = Prio2::new(4).unwrap();
+        let (_, input_shares) = prio2.shard(&vec![0, 1, 1, 0], &[0; 16]).unwrap();
+        let (states, prep_shares): (Vec<_>, Vec<_>) = input_shares
+            .iter()
+            .enumerate()
+            .map(|(agg_id, input_share)| {
+                prio2
+                    .prepare_init(&[0; 32], agg_id, &(), &[0; 16], &(), input_share)
+                    .unwrap()
+            })
+            .unzip();
+        prio2
+            .prepare_shares_to_prepare_message(&(), prep_shares.clone())
+            .unwrap();
+
+        let zero = Prio2PrepareShare(VerifierShare::Base(vec![VerificationMessage {
+            f_r: FieldPrio2::zero(),
+            g_r: FieldPrio2::zero(),
+            h_r: FieldPrio2::zero(),
+        }]));
+        for bad in [
+            vec![prep_shares[0].clone(), zero],
+            vec![prep_shares[0].clone(), prep_shares[0].clone()],
+        ] {
+            let err = prio2
+                .prepare_shares_to_prepare_message(&(), bad)
+                .unwrap_err();
+            assert_eq!(
+                err.rejection_reason(),
+                Some(RejectionReason::MalformedMessage)
+            );
+        }
+
+        // Non-canonical field elements do not decode.
+        let mut encoded = prep_shares[1].get_encoded().unwrap();
+        encoded[..4].copy_from_slice(&[0xff; 4]);
+        assert!(Prio2PrepareShare::get_decoded_with_param(&states[1], &encoded).is_err());
+    }
+
+    #[test]
+    fn prio2_invalid_measurement() {
+        let prio2 = Prio2::new(3).unwrap();
+        assert_matches!(
+            prio2.shard(&vec![0, 2, 1], &[0; 16]),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert_matches!(
+            prio2.shard(&vec![0, 1], &[0; 16]),
+            Err(VdafError::Uncategorized(_))
+        );
+    }
+
+    #[test]
+    fn prio2_input_too_large() {
+        assert_matches!(Prio2::new(usize::MAX), Err(VdafError::Uncategorized(_)));
+        assert_matches!(Prio2::new(usize::MAX / 2), Err(VdafError::Uncategorized(_)));
+    }
+
+    #[test]
+    fn prio2_validation_memory_limit() {
+        let prio2 = Prio2::new(1000).unwrap();
+        let len = prio2.validation_memory_len();
+        assert_eq!(len, 2 * 2048 * std::mem::size_of::<FieldPrio2>());
+        assert_matches!(
+            prio2.clone().with_validation_memory_limit(len - 1),
+            Err(VdafError::Uncategorized(_))
+        );
+
+        // Chunking reduces the memory needed to prepare a share.
+        let prio2 = prio2
+            .with_chunk_length(100)
+            .unwrap()
+            .with_validation_memory_limit(len / 2)
+            .unwrap();
+        assert!(prio2.validation_memory_len() <= len / 2);
+        let measurement = vec![1; 1000];
+        let nonce = [0; 16];
+        let (public_share, input_shares) = prio2.shard(&measurement, &nonce).unwrap();
+        let out_shares =
+            run_vdaf_prepare(&prio2, &[0; 32], &(), &nonce, public_share, input_shares).unwrap();
+        assert_eq!(
+            prio2
+                .unshard(&(), out_shares.into_iter().map(AggregateShare::from), 1)
+                .unwrap(),
+            measurement
+        );
+    }
+
+    #[test]
+    fn prio2_prepare_init_with_memory() {
+        let prio2 = Prio2::new(1000).unwrap().with_chunk_length(300).unwrap();
+        let verify_key = [1; 32];
+        let mut memory = [
+            Prio2ValidationMemory::default(),
+            Prio2ValidationMemory::default(),
+        ];
+        for i in 0..3 {
+            let measurement = vec![u32::from(i % 2 == 0); 1000];
+            let nonce = [i; 16];
+            let (public_share, input_shares) = prio2.shard(&measurement, &nonce).unwrap();
+            for (agg_id, (input_share, memory)) in input_shares.iter().zip(&mut memory).enumerate()
+            {
+                let expected = prio2
+                    .prepare_init(&verify_key, agg_id, &(), &nonce, &public_share, input_share)
+                    .unwrap();
+                let got = prio2
+                    .prepare_init_with_memory(&verify_key, agg_id, &nonce, input_share, memory)
+                    .unwrap();
+                assert_eq!(got, expected);
+            }
+        }
+        // The memory holds one allocation for the chunks of 300 entries and one for the last.
+        assert_eq!(memory[0].0.len(), 2);
+    }
+
+    #[test]
+    fn run_prio2_with_fft_backend() {
+        use crate::fft::FftError;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Debug, Default)]
+        struct CountingBackend {
+            batches: AtomicUsize,
+        }
+
+        impl FftBackend<FieldPrio2> for CountingBackend {
+            fn fft(
+                &self,
+                outp: &mut [FieldPrio2],
+                inp: &[FieldPrio2],
+                size: usize,
+            ) -> Result<(), FftError> {
+                CpuFftBackend.fft(outp, inp, size)
+            }
+
+            fn fft_batch(
+                &self,
+                outps: &mut [&mut [FieldPrio2]],
+                inps: &[&[FieldPrio2]],
+                size: usize,
+            ) -> Result<(), FftError> {
+                self.batches.fetch_add(1, Ordering::Relaxed);
+                CpuFftBackend.fft_batch(outps, inps, size)
+            }
+        }
+
+        let backend = Arc::new(CountingBackend::default());
+        let prio2 = Prio2::new(6).unwrap().with_fft_backend(backend.clone());
+        assert_eq!(
+            run_vdaf(
+                &prio2,
+                &(),
+                [vec![0, 1, 0, 0, 1, 0], vec![1, 1, 0, 0, 0, 0]]
+            )
+            .unwrap(),
+            vec![1, 2, 0, 0, 1, 0],
+        );
+        // One batch per report per aggregator.
+        assert_eq!(backend.batches.load(Ordering::Relaxed), 4);
+    }
+
     #[test]
     fn prepare_state_serialization() {
         let mut rng = thread_rng();
@@ -458,9 +1697,7 @@ mod tests {
             let decoded_prepare_share =
                 Prio2PrepareShare::get_decoded_with_param(&prepare_state, &encoded_prepare_share)
                     .expect("failed to decode prepare share");
-            assert_eq!(decoded_prepare_share.0.f_r, prepare_share.0.f_r);
-            assert_eq!(decoded_prepare_share.0.g_r, prepare_share.0.g_r);
-            assert_eq!(decoded_prepare_share.0.h_r, prepare_share.0.h_r);
+            assert_eq!(decoded_prepare_share, prepare_share);
             assert_eq!(
                 prepare_share.encoded_len().unwrap(),
                 encoded_prepare_share.len()
@@ -532,20 +1769,26 @@ mod tests {
     #[test]
     fn prepare_state_equality_test() {
         equality_comparison_test(&[
-            Prio2PrepareState(Share::Leader(Vec::from([
-                FieldPrio2::from(0),
-                FieldPrio2::from(1),
-            ]))),
-            Prio2PrepareState(Share::Leader(Vec::from([
-                FieldPrio2::from(1),
-                FieldPrio2::from(0),
-            ]))),
-            Prio2PrepareState(Share::Helper(Seed(
-                (0..32).collect::<Vec<_>>().try_into().unwrap(),
-            ))),
-            Prio2PrepareState(Share::Helper(Seed(
-                (1..33).collect::<Vec<_>>().try_into().unwrap(),
-            ))),
+            Prio2PrepareState(
+                Share::Leader(Vec::from([FieldPrio2::from(0), FieldPrio2::from(1)])),
+                false,
+                1,
+            ),
+            Prio2PrepareState(
+                Share::Leader(Vec::from([FieldPrio2::from(1), FieldPrio2::from(0)])),
+                false,
+                1,
+            ),
+            Prio2PrepareState(
+                Share::Helper(Seed((0..32).collect::<Vec<_>>().try_into().unwrap())),
+                false,
+                1,
+            ),
+            Prio2PrepareState(
+                Share::Helper(Seed((1..33).collect::<Vec<_>>().try_into().unwrap())),
+                false,
+                1,
+            ),
         ])
     }
 }
@@ -14,10 +14,11 @@ use crate::{
     },
 };
 
-use std::convert::TryFrom;
+use core::convert::TryFrom;
 
 /// Errors that might be emitted by the client.
 #[derive(Debug, thiserror::Error)]
+#[allow(dead_code)]
 pub(crate) enum ClientError {
     /// PRNG error
     #[error("prng error: {0}")]
@@ -41,9 +42,11 @@ pub enum SerializeError {
     Codec(#[from] CodecError),
 }
 
+/// Scratch memory used to construct a proof. A single instance can be reused across reports, so
+/// that only the proof itself is allocated for each one.
 #[derive(Debug)]
 pub(crate) struct ClientMemory<F> {
-    prng: Prng<F, SeedStreamAes128>,
+    dimension: usize,
     points_f: Vec<F>,
     points_g: Vec<F>,
     evals_f: Vec<F>,
@@ -55,9 +58,12 @@ pub(crate) struct ClientMemory<F> {
 }
 
 impl<F: FftFriendlyFieldElement> ClientMemory<F> {
+    /// Allocates memory for proving measurements of the given dimension.
     pub(crate) fn new(dimension: usize) -> Result<Self, VdafError> {
-        let n = (dimension + 1).next_power_of_two();
-        if let Ok(size) = F::Integer::try_from(2 * n) {
+        let layout = ProofLayout::new(dimension)
+            .ok_or_else(|| VdafError::Uncategorized("input size exceeds memory capacity".into()))?;
+        let (n, fft_len) = (layout.n(), layout.fft_len());
+        if let Ok(size) = F::Integer::try_from(fft_len) {
             if size > F::generator_order() {
                 return Err(VdafError::Uncategorized(
                     "input size exceeds field capacity".into(),
@@ -70,24 +76,48 @@ impl<F: FftFriendlyFieldElement> ClientMemory<F> {
         }
 
         Ok(Self {
-            prng: Prng::from_prio2_seed(Seed::<32>::generate()?.as_ref()),
+            dimension,
             points_f: vec![F::zero(); n],
             points_g: vec![F::zero(); n],
-            evals_f: vec![F::zero(); 2 * n],
-            evals_g: vec![F::zero(); 2 * n],
-            roots_2n: fft_get_roots(2 * n, false),
+            evals_f: vec![F::zero(); fft_len],
+            evals_g: vec![F::zero(); fft_len],
+            roots_2n: fft_get_roots(fft_len, false),
             roots_n_inverted: fft_get_roots(n, true),
-            fft_memory: PolyFFTTempMemory::new(2 * n),
-            coeffs: vec![F::zero(); 2 * n],
+            fft_memory: PolyFFTTempMemory::new(fft_len),
+            coeffs: vec![F::zero(); fft_len],
         })
     }
+
+    /// Returns the dimension this memory was allocated for.
+    pub(crate) fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Zeroes the scratch space, which holds values derived from the last measurement proved.
+    /// The precomputed roots of unity are kept.
+    pub(crate) fn reset(&mut self) {
+        for buf in [
+            &mut self.points_f,
+            &mut self.points_g,
+            &mut self.evals_f,
+            &mut self.evals_g,
+            &mut self.coeffs,
+        ] {
+            buf.fill(F::zero());
+        }
+        self.fft_memory.reset();
+    }
 }
 
 impl<F: FftFriendlyFieldElement> ClientMemory<F> {
-    pub(crate) fn prove_with<G>(&mut self, dimension: usize, init_function: G) -> Vec<F>
+    /// Constructs a proof for a measurement of the dimension this memory was allocated for,
+    /// deriving the proof's randomness from `prove_seed`.
+    pub(crate) fn prove_with<G>(&mut self, prove_seed: &Seed<32>, init_function: G) -> Vec<F>
     where
         G: FnOnce(&mut [F]),
     {
+        let dimension = self.dimension;
+        let mut prng = Prng::from_prio2_seed(prove_seed.as_ref());
         let mut proof = vec![F::zero(); proof_length(dimension)];
         // unpack one long vector to different subparts
         let unpacked = unpack_proof_mut(&mut proof, dimension).unwrap();
@@ -102,21 +132,70 @@ impl<F: FftFriendlyFieldElement> ClientMemory<F> {
             unpacked.h0,
             unpacked.points_h_packed,
             self,
+            &mut prng,
         );
 
         proof
     }
 }
 
-/// Returns the number of field elements in the proof for given dimension of
-/// data elements
+/// The offsets of the components of a proof for a given dimension of data elements.
 ///
 /// Proof is a vector, where the first `dimension` elements are the data
 /// elements, the next 3 elements are the zero terms for polynomials f, g and h
-/// and the remaining elements are non-zero points of h(x).
+/// and the remaining N elements are non-zero points of h(x), where N is the
+/// smallest power of two larger than `dimension`. Polynomials f and g are
+/// interpolated through N points and h is evaluated at 2N points.
+///
+/// All lengths are computed once, with checked arithmetic, in [`ProofLayout::new`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ProofLayout {
+    dimension: usize,
+    n: usize,
+    len: usize,
+}
+
+impl ProofLayout {
+    /// Number of zero terms, one each for f, g and h.
+    const ZERO_TERMS: usize = 3;
+
+    /// Computes the layout for the given dimension, or returns `None` if any length overflows.
+    pub(crate) fn new(dimension: usize) -> Option<Self> {
+        let n = dimension.checked_add(1)?.checked_next_power_of_two()?;
+        // Make sure 2N is representable, so that `fft_len()` cannot overflow.
+        n.checked_mul(2)?;
+        // number of data items + number of zero terms + N
+        let len = dimension.checked_add(Self::ZERO_TERMS)?.checked_add(n)?;
+        Some(Self { dimension, n, len })
+    }
+
+    /// Number of data elements.
+    pub(crate) fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Number of points N that f and g are interpolated through.
+    pub(crate) fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Number of points 2N that the polynomials are evaluated at.
+    pub(crate) fn fft_len(&self) -> usize {
+        2 * self.n
+    }
+
+    /// Total number of field elements in the proof.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Returns the number of field elements in the proof for given dimension of
+/// data elements. See [`ProofLayout`].
+///
+/// Returns `usize::MAX`, which no proof can match, if the length overflows.
 pub(crate) fn proof_length(dimension: usize) -> usize {
-    // number of data items + number of zero terms + N
-    dimension + 3 + (dimension + 1).next_power_of_two()
+    ProofLayout::new(dimension).map_or(usize::MAX, |layout| layout.len())
 }
 
 /// Unpacked proof with subcomponents
@@ -153,14 +232,15 @@ pub(crate) struct UnpackedProofMut<'a, F: FftFriendlyFieldElement> {
 pub(crate) fn unpack_proof<F: FftFriendlyFieldElement>(
     proof: &[F],
     dimension: usize,
-) -> Result<UnpackedProof<F>, SerializeError> {
+) -> Result<UnpackedProof<'_, F>, SerializeError> {
     // check the proof length
-    if proof.len() != proof_length(dimension) {
+    let layout = ProofLayout::new(dimension).ok_or(SerializeError::UnpackInputSizeMismatch)?;
+    if proof.len() != layout.len() {
         return Err(SerializeError::UnpackInputSizeMismatch);
     }
     // split share into components
-    let (data, rest) = proof.split_at(dimension);
-    if let ([f0, g0, h0], points_h_packed) = rest.split_at(3) {
+    let (data, rest) = proof.split_at(layout.dimension());
+    if let ([f0, g0, h0], points_h_packed) = rest.split_at(ProofLayout::ZERO_TERMS) {
         Ok(UnpackedProof {
             data,
             f0,
@@ -177,14 +257,15 @@ pub(crate) fn unpack_proof<F: FftFriendlyFieldElement>(
 pub(crate) fn unpack_proof_mut<F: FftFriendlyFieldElement>(
     proof: &mut [F],
     dimension: usize,
-) -> Result<UnpackedProofMut<F>, SerializeError> {
+) -> Result<UnpackedProofMut<'_, F>, SerializeError> {
     // check the share length
-    if proof.len() != proof_length(dimension) {
+    let layout = ProofLayout::new(dimension).ok_or(SerializeError::UnpackInputSizeMismatch)?;
+    if proof.len() != layout.len() {
         return Err(SerializeError::UnpackInputSizeMismatch);
     }
     // split share into components
-    let (data, rest) = proof.split_at_mut(dimension);
-    if let ([f0, g0, h0], points_h_packed) = rest.split_at_mut(3) {
+    let (data, rest) = proof.split_at_mut(layout.dimension());
+    if let ([f0, g0, h0], points_h_packed) = rest.split_at_mut(ProofLayout::ZERO_TERMS) {
         Ok(UnpackedProofMut {
             data,
             f0,
@@ -230,6 +311,7 @@ fn interpolate_and_evaluate_at_2n<F: FftFriendlyFieldElement>(
 ///
 /// Based on Theorem 2.3.3 from Henry Corrigan-Gibbs' dissertation
 /// This constructs the output \pi by doing the necessesary calculations
+#[allow(clippy::too_many_arguments)]
 fn construct_proof<F: FftFriendlyFieldElement>(
     data: &[F],
     dimension: usize,
@@ -238,12 +320,14 @@ fn construct_proof<F: FftFriendlyFieldElement>(
     h0: &mut F,
     points_h_packed: &mut [F],
     mem: &mut ClientMemory<F>,
+    prng: &mut Prng<F, SeedStreamAes128>,
 ) {
-    let n = (dimension + 1).next_power_of_two();
+    // the memory was allocated for this dimension's layout, with N points per polynomial
+    let n = mem.points_f.len();
 
     // set zero terms to random
-    *f0 = mem.prng.get();
-    *g0 = mem.prng.get();
+    *f0 = prng.get();
+    *g0 = prng.get();
     mem.points_f[0] = *f0;
     mem.points_g[0] = *g0;
 
@@ -282,13 +366,13 @@ fn construct_proof<F: FftFriendlyFieldElement>(
     );
 
     // calculate the proof polynomial as evals_f(r) * evals_g(r)
-    // only add non-zero points
-    let mut j: usize = 0;
-    let mut i: usize = 1;
-    while i < 2 * n {
-        points_h_packed[j] = mem.evals_f[i] * mem.evals_g[i];
-        j += 1;
-        i += 2;
+    // only add non-zero points, which are at the odd indices
+    for ((h, f), g) in points_h_packed
+        .iter_mut()
+        .zip(mem.evals_f.iter().skip(1).step_by(2))
+        .zip(mem.evals_g.iter().skip(1).step_by(2))
+    {
+        *h = *f * *g;
     }
 }
 
@@ -298,7 +382,9 @@ mod tests {
 
     use crate::{
         field::{Field64, FieldPrio2},
-        vdaf::prio2::client::{proof_length, unpack_proof, unpack_proof_mut, SerializeError},
+        vdaf::prio2::client::{
+            proof_length, unpack_proof, unpack_proof_mut, ProofLayout, SerializeError,
+        },
     };
 
     #[test]
@@ -332,4 +418,31 @@ mod tests {
             Err(SerializeError::UnpackInputSizeMismatch)
         );
     }
+
+    #[test]
+    fn test_proof_layout() {
+        for (dim, n) in [(0, 1), (1, 2), (3, 4), (4, 8), (15, 16), (16, 32)] {
+            let layout = ProofLayout::new(dim).unwrap();
+            assert_eq!(layout.dimension(), dim);
+            assert_eq!(layout.n(), n);
+            assert_eq!(layout.fft_len(), 2 * n);
+            assert_eq!(layout.len(), dim + 3 + n);
+            assert_eq!(proof_length(dim), layout.len());
+        }
+
+        // Lengths that overflow are rejected rather than wrapping around.
+        for dim in [
+            usize::MAX,
+            usize::MAX - 3,
+            usize::MAX / 2,
+            usize::MAX / 4 + 1,
+        ] {
+            assert_eq!(ProofLayout::new(dim), None);
+            assert_eq!(proof_length(dim), usize::MAX);
+            assert_matches!(
+                unpack_proof(&[FieldPrio2::from(0); 4], dim),
+                Err(SerializeError::UnpackInputSizeMismatch)
+            );
+        }
+    }
 }
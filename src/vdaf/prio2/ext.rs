@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The quadratic extension of [`FieldPrio2`], used to verify proofs at a point outside the field
+//! the shares live in.
+//!
+//! Elements are written `a + b * u`, where `u^2 = 17`. Since 17 is not a square modulo the
+//! modulus of [`FieldPrio2`], this is a field of size `p^2`.
+
+use crate::{
+    codec::{CodecError, Decode, Encode},
+    field::{FieldElement, FieldPrio2},
+    polynomial::FieldOver,
+};
+use std::{
+    io::Cursor,
+    ops::{Add, AddAssign, Mul},
+};
+use subtle::{Choice, ConstantTimeEq};
+
+/// The square of the generator `u` of the extension.
+const NON_RESIDUE: u32 = 17;
+
+/// An element `a + b * u` of the quadratic extension of [`FieldPrio2`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct FieldPrio2Ext {
+    a: FieldPrio2,
+    b: FieldPrio2,
+}
+
+impl FieldPrio2Ext {
+    pub(crate) fn new(a: FieldPrio2, b: FieldPrio2) -> Self {
+        Self { a, b }
+    }
+
+    /// Returns true if this element lies in [`FieldPrio2`]. Such elements include the roots of
+    /// unity used to interpolate the proof polynomials.
+    pub(crate) fn is_base(&self) -> bool {
+        self.b == FieldPrio2::zero()
+    }
+}
+
+impl From<FieldPrio2> for FieldPrio2Ext {
+    fn from(a: FieldPrio2) -> Self {
+        Self::new(a, FieldPrio2::zero())
+    }
+}
+
+impl Add for FieldPrio2Ext {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.a + rhs.a, self.b + rhs.b)
+    }
+}
+
+impl AddAssign for FieldPrio2Ext {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Mul for FieldPrio2Ext {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        // (a + bu)(c + du) = (ac + 17bd) + (ad + bc)u
+        Self::new(
+            self.a * rhs.a + FieldPrio2::from(NON_RESIDUE) * self.b * rhs.b,
+            self.a * rhs.b + self.b * rhs.a,
+        )
+    }
+}
+
+impl ConstantTimeEq for FieldPrio2Ext {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.a.ct_eq(&other.a) & self.b.ct_eq(&other.b)
+    }
+}
+
+impl FieldOver<FieldPrio2> for FieldPrio2Ext {
+    fn eval_poly(coeffs: &[FieldPrio2], x: Self) -> Self {
+        // Horner's method. Multiplying by x is the only operation in the extension.
+        let mut result = Self::from(FieldPrio2::zero());
+        for coeff in coeffs.iter().rev() {
+            result = result * x;
+            result.a += *coeff;
+        }
+        result
+    }
+}
+
+impl Encode for FieldPrio2Ext {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.a.encode(bytes)?;
+        self.b.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(2 * FieldPrio2::ENCODED_SIZE)
+    }
+}
+
+impl Decode for FieldPrio2Ext {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self::new(
+            FieldPrio2::decode(bytes)?,
+            FieldPrio2::decode(bytes)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{field::random_vector, polynomial::poly_eval};
+
+    #[test]
+    fn ext_arithmetic() {
+        let u = FieldPrio2Ext::new(FieldPrio2::zero(), FieldPrio2::one());
+        assert_eq!(u * u, FieldPrio2Ext::from(FieldPrio2::from(NON_RESIDUE)));
+        assert!(!u.is_base());
+
+        // Extension arithmetic agrees with the base field on base field elements.
+        let v: Vec<FieldPrio2> = random_vector(3).unwrap();
+        let (x, y) = (FieldPrio2Ext::from(v[0]), FieldPrio2Ext::from(v[1]));
+        assert_eq!(x * y, FieldPrio2Ext::from(v[0] * v[1]));
+        assert_eq!(x + y, FieldPrio2Ext::from(v[0] + v[1]));
+        assert!((x * y).is_base());
+
+        // Polynomial evaluation agrees with `poly_eval` at base field points.
+        let coeffs: Vec<FieldPrio2> = random_vector(10).unwrap();
+        assert_eq!(
+            FieldPrio2Ext::eval_poly(&coeffs, FieldPrio2Ext::from(v[2])),
+            FieldPrio2Ext::from(poly_eval(&coeffs, v[2]))
+        );
+
+        // Multiplication distributes over addition.
+        let z = FieldPrio2Ext::new(v[2], v[0]);
+        assert_eq!((x + u) * z, x * z + u * z);
+
+        let encoded = z.get_encoded().unwrap();
+        assert_eq!(Some(encoded.len()), z.encoded_len());
+        assert_eq!(FieldPrio2Ext::get_decoded(&encoded).unwrap(), z);
+    }
+}
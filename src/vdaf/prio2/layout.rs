@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Byte layout of the messages of a [`Prio2`] configuration.
+//!
+//! [`Prio2Layout`] gives the byte ranges of the components of the leader's encoded input share
+//! (the data, `f(0)`, `g(0)`, `h(0)` and the packed points of `h`, for each
+//! [chunk](Prio2::with_chunk_length)) and of the verifier shares in an encoded prepare share,
+//! along with the length of every message. It is meant for debugging tools and packet dissectors,
+//! which can locate a field of a captured message without reimplementing the proof system.
+//!
+//! A helper's input share is a seed of [`HELPER_INPUT_SHARE_LEN`] bytes. Expanded, it has the same
+//! layout as the leader's share, counted in field elements of [`ELEMENT_LEN`] bytes rather than in
+//! bytes.
+//!
+//! ```
+//! use prio::vdaf::prio2::{layout::{Component, Prio2Layout}, Prio2};
+//!
+//! let layout = Prio2Layout::new(&Prio2::new(10).unwrap());
+//! assert_eq!(layout.chunks[0].f0, 40..44);
+//! assert_eq!(layout.component_at(41), Some((0, Component::F0)));
+//! ```
+
+use crate::{
+    field::{FieldElement, FieldPrio2},
+    vdaf::prio2::{client::ProofLayout, Prio2},
+};
+use std::ops::Range;
+
+/// The length in bytes of an encoded [`FieldPrio2`] element.
+pub const ELEMENT_LEN: usize = FieldPrio2::ENCODED_SIZE;
+
+/// The length in bytes of a helper's input share, which is a seed.
+pub const HELPER_INPUT_SHARE_LEN: usize = 32;
+
+/// The length in bytes of the verification key.
+pub const VERIFY_KEY_LEN: usize = 32;
+
+/// The length in bytes of a nonce.
+pub const NONCE_LEN: usize = 16;
+
+/// The byte ranges of the components of one chunk's proof in the leader's input share.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkLayout {
+    /// The number of measurement entries in the chunk.
+    pub dimension: usize,
+
+    /// The share of the chunk's entries.
+    pub data: Range<usize>,
+
+    /// The share of `f(0)`.
+    pub f0: Range<usize>,
+
+    /// The share of `g(0)`.
+    pub g0: Range<usize>,
+
+    /// The share of `h(0)`.
+    pub h0: Range<usize>,
+
+    /// The shares of the values of `h` at the odd powers of the `2N`th root of unity.
+    pub points_h: Range<usize>,
+}
+
+impl ChunkLayout {
+    /// Returns the byte range of the whole chunk.
+    pub fn range(&self) -> Range<usize> {
+        self.data.start..self.points_h.end
+    }
+}
+
+/// The byte ranges of one chunk's verifier share in a prepare share.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifierLayout {
+    /// The share of `f(r)`.
+    pub f_r: Range<usize>,
+
+    /// The share of `g(r)`.
+    pub g_r: Range<usize>,
+
+    /// The share of `h(r)`.
+    pub h_r: Range<usize>,
+}
+
+/// A component of the leader's input share.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Component {
+    /// The share of the measurement entry at this index within the chunk.
+    Data(usize),
+
+    /// The share of `f(0)`.
+    F0,
+
+    /// The share of `g(0)`.
+    G0,
+
+    /// The share of `h(0)`.
+    H0,
+
+    /// The share of the packed point of `h` at this index.
+    PointsH(usize),
+}
+
+/// The layout of the messages of a [`Prio2`] configuration. See the
+/// [module documentation](self) for details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Prio2Layout {
+    /// The layout of each chunk of the leader's input share, in order.
+    pub chunks: Vec<ChunkLayout>,
+
+    /// The layout of each chunk's verifier share in a prepare share, in order.
+    pub verifier_shares: Vec<VerifierLayout>,
+
+    /// The length in bytes of the leader's input share.
+    pub leader_input_share_len: usize,
+
+    /// The length in bytes of a prepare share.
+    pub prepare_share_len: usize,
+
+    /// The length in bytes of an output share or aggregate share.
+    pub aggregate_share_len: usize,
+}
+
+impl Prio2Layout {
+    /// Computes the layout of the messages of `prio2`.
+    pub fn new(prio2: &Prio2) -> Self {
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        let mut range = |elements: usize| {
+            let range = offset..offset + elements * ELEMENT_LEN;
+            offset = range.end;
+            range
+        };
+        for dimension in prio2.chunk_lens() {
+            // Unwrap safety: the constructor of `Prio2` checks that the layout exists.
+            let proof = ProofLayout::new(dimension).unwrap();
+            chunks.push(ChunkLayout {
+                dimension,
+                data: range(dimension),
+                f0: range(1),
+                g0: range(1),
+                h0: range(1),
+                points_h: range(proof.n()),
+            });
+        }
+        let leader_input_share_len = offset;
+
+        let verifier_element_len = if prio2.extended_verification {
+            2 * ELEMENT_LEN
+        } else {
+            ELEMENT_LEN
+        };
+        let mut offset = 0;
+        let mut range = || {
+            let range = offset..offset + verifier_element_len;
+            offset = range.end;
+            range
+        };
+        let verifier_shares = chunks
+            .iter()
+            .map(|_| VerifierLayout {
+                f_r: range(),
+                g_r: range(),
+                h_r: range(),
+            })
+            .collect();
+
+        Self {
+            chunks,
+            verifier_shares,
+            leader_input_share_len,
+            prepare_share_len: offset,
+            aggregate_share_len: prio2.input_len * ELEMENT_LEN,
+        }
+    }
+
+    /// Returns the length in bytes of the encoded input share of Aggregator `agg_id`.
+    pub fn input_share_len(&self, agg_id: usize) -> usize {
+        if agg_id == 0 {
+            self.leader_input_share_len
+        } else {
+            HELPER_INPUT_SHARE_LEN
+        }
+    }
+
+    /// Returns the index of the chunk and the component of the leader's input share that the
+    /// byte at `offset` belongs to, or `None` if the offset is past the end of the share.
+    pub fn component_at(&self, offset: usize) -> Option<(usize, Component)> {
+        let (index, chunk) = self
+            .chunks
+            .iter()
+            .enumerate()
+            .find(|(_, chunk)| chunk.range().contains(&offset))?;
+        let component = if chunk.data.contains(&offset) {
+            Component::Data((offset - chunk.data.start) / ELEMENT_LEN)
+        } else if chunk.f0.contains(&offset) {
+            Component::F0
+        } else if chunk.g0.contains(&offset) {
+            Component::G0
+        } else if chunk.h0.contains(&offset) {
+            Component::H0
+        } else {
+            Component::PointsH((offset - chunk.points_h.start) / ELEMENT_LEN)
+        };
+        Some((index, component))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::Encode,
+        vdaf::{prio2::proof::Proof, Aggregator, Client, Share},
+    };
+
+    #[test]
+    fn prio2_layout() {
+        let prio2 = Prio2::new(10).unwrap();
+        let layout = Prio2Layout::new(&prio2);
+        assert_eq!(layout.chunks.len(), 1);
+        let chunk = &layout.chunks[0];
+        assert_eq!(chunk.data, 0..40);
+        assert_eq!(chunk.h0, 48..52);
+        assert_eq!(chunk.points_h, 52..116);
+        assert_eq!(layout.leader_input_share_len, prio2.input_share_len(0));
+        assert_eq!(layout.input_share_len(1), prio2.input_share_len(1));
+        assert_eq!(layout.prepare_share_len, prio2.prepare_share_len());
+        assert_eq!(layout.aggregate_share_len, prio2.aggregate_share_len());
+        assert_eq!(layout.verifier_shares[0].h_r, 8..12);
+
+        // The ranges locate the components of an actual share.
+        let (_, input_shares) = prio2.shard(&vec![1; 10], &[0; 16]).unwrap();
+        let encoded = input_shares[0].get_encoded().unwrap();
+        let Share::Leader(ref leader) = input_shares[0] else {
+            panic!("unexpected input share");
+        };
+        let proof = Proof::from_flat(leader, 10).unwrap();
+        assert_eq!(encoded[chunk.g0.clone()], proof.g0.get_encoded().unwrap());
+        assert_eq!(
+            encoded[chunk.points_h.clone()][..4],
+            proof.points_h_packed[0].get_encoded().unwrap()
+        );
+
+        assert_eq!(layout.component_at(5), Some((0, Component::Data(1))));
+        assert_eq!(layout.component_at(44), Some((0, Component::G0)));
+        assert_eq!(layout.component_at(115), Some((0, Component::PointsH(15))));
+        assert_eq!(layout.component_at(116), None);
+    }
+
+    #[test]
+    fn prio2_chunked_layout() {
+        let prio2 = Prio2::new(10)
+            .unwrap()
+            .with_chunk_length(4)
+            .unwrap()
+            .with_extended_verification();
+        let layout = Prio2Layout::new(&prio2);
+        assert_eq!(
+            layout
+                .chunks
+                .iter()
+                .map(|chunk| chunk.dimension)
+                .collect::<Vec<_>>(),
+            [4, 4, 2]
+        );
+        assert_eq!(layout.chunks[1].range().start, layout.chunks[0].range().end);
+        assert_eq!(layout.chunks[2].points_h.end, layout.leader_input_share_len);
+        assert_eq!(layout.leader_input_share_len, prio2.input_share_len(0));
+        assert_eq!(layout.prepare_share_len, prio2.prepare_share_len());
+        assert_eq!(layout.verifier_shares[2].f_r, 48..56);
+        assert_eq!(
+            layout.component_at(layout.chunks[2].f0.start),
+            Some((2, Component::F0))
+        );
+
+        let (_, input_shares) = prio2.shard(&vec![1; 10], &[0; 16]).unwrap();
+        let (_, prep_share) = prio2
+            .prepare_init(&[0; 32], 0, &(), &[0; 16], &(), &input_shares[0])
+            .unwrap();
+        assert_eq!(
+            prep_share.get_encoded().unwrap().len(),
+            layout.prepare_share_len
+        );
+    }
+}
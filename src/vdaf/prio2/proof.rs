@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Prio2 proof layout.
+//!
+//! A Prio2 input share, once expanded, is a single vector of field elements: the `dimension` data
+//! elements, the zero terms `f(0)`, `g(0)` and `h(0)` of the polynomials `f`, `g` and `h`, and the
+//! values of `h` at the odd powers of the `2N`th root of unity, where `N` is the smallest power of
+//! two larger than `dimension`. [`Proof`] holds these components as separate fields and converts
+//! to and from the flat layout. Since the layout is linear, the components of a full proof are the
+//! sums of the components of its shares.
+
+use crate::{
+    codec::{CodecError, Encode, ParameterizedDecode},
+    field::{decode_fieldvec, FftFriendlyFieldElement},
+    vdaf::prio2::client::{proof_length, unpack_proof, ProofLayout},
+};
+use std::io::Cursor;
+
+pub use crate::vdaf::prio2::client::SerializeError;
+
+/// A Prio2 proof, or a share of one, split into its components.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proof<F> {
+    /// The encoded measurement.
+    pub data: Vec<F>,
+    /// Zeroth coefficient of polynomial f.
+    pub f0: F,
+    /// Zeroth coefficient of polynomial g.
+    pub g0: F,
+    /// Zeroth coefficient of polynomial h.
+    pub h0: F,
+    /// Non-zero points of polynomial h.
+    pub points_h_packed: Vec<F>,
+}
+
+impl<F: FftFriendlyFieldElement> Proof<F> {
+    /// Returns the number of field elements in the flat layout of a proof for a measurement of
+    /// length `dimension`.
+    pub fn flat_len(dimension: usize) -> usize {
+        proof_length(dimension)
+    }
+
+    /// Splits a proof in the flat layout into its components.
+    pub fn from_flat(proof: &[F], dimension: usize) -> Result<Self, SerializeError> {
+        let unpacked = unpack_proof(proof, dimension)?;
+        Ok(Self {
+            data: unpacked.data.to_vec(),
+            f0: *unpacked.f0,
+            g0: *unpacked.g0,
+            h0: *unpacked.h0,
+            points_h_packed: unpacked.points_h_packed.to_vec(),
+        })
+    }
+
+    /// Returns the length of the measurement this proof is for.
+    pub fn dimension(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Concatenates the components into the flat layout. Fails if `points_h_packed` has the wrong
+    /// length for the dimension.
+    pub fn to_flat(&self) -> Result<Vec<F>, SerializeError> {
+        let layout =
+            ProofLayout::new(self.dimension()).ok_or(SerializeError::UnpackInputSizeMismatch)?;
+        if self.points_h_packed.len() != layout.n() {
+            return Err(SerializeError::UnpackInputSizeMismatch);
+        }
+        let mut proof = Vec::with_capacity(layout.len());
+        proof.extend_from_slice(&self.data);
+        proof.extend([self.f0, self.g0, self.h0]);
+        proof.extend_from_slice(&self.points_h_packed);
+        Ok(proof)
+    }
+}
+
+impl<F: FftFriendlyFieldElement> Encode for Proof<F> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        for x in self.to_flat().map_err(|e| CodecError::Other(e.into()))? {
+            x.encode(bytes)?;
+        }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(F::ENCODED_SIZE * Self::flat_len(self.dimension()))
+    }
+}
+
+/// Decodes a proof in the flat layout. The decoding parameter is the dimension.
+impl<F: FftFriendlyFieldElement> ParameterizedDecode<usize> for Proof<F> {
+    fn decode_with_param(dimension: &usize, bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let proof = decode_fieldvec(Self::flat_len(*dimension), bytes)?;
+        Self::from_flat(&proof, *dimension).map_err(|e| CodecError::Other(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        field::FieldPrio2,
+        prng::Prng,
+        vdaf::{prio2::Prio2, Client, Share},
+    };
+
+    #[test]
+    fn proof_from_shares() {
+        let dimension = 5;
+        let vdaf = Prio2::new(dimension).unwrap();
+        let (_, input_shares) = vdaf.shard(&vec![1, 0, 0, 1, 1], &[0; 16]).unwrap();
+        let (Share::Leader(leader), Share::Helper(seed)) = (&input_shares[0], &input_shares[1])
+        else {
+            panic!("unexpected input shares");
+        };
+        let helper: Vec<FieldPrio2> = Prng::from_prio2_seed(seed.as_ref())
+            .take(leader.len())
+            .collect();
+
+        let leader = Proof::from_flat(leader, dimension).unwrap();
+        let helper = Proof::from_flat(&helper, dimension).unwrap();
+        assert_eq!(
+            Proof::from_flat(&leader.to_flat().unwrap(), dimension).unwrap(),
+            leader
+        );
+
+        // The proof is the sum of the shares, componentwise.
+        let data: Vec<FieldPrio2> = leader
+            .data
+            .iter()
+            .zip(&helper.data)
+            .map(|(x, y)| *x + *y)
+            .collect();
+        assert_eq!(data, [1, 0, 0, 1, 1].map(FieldPrio2::from));
+        assert_eq!(
+            (leader.f0 + helper.f0) * (leader.g0 + helper.g0),
+            leader.h0 + helper.h0
+        );
+
+        let encoded = leader.get_encoded().unwrap();
+        assert_eq!(Some(encoded.len()), leader.encoded_len());
+        assert_eq!(
+            Proof::<FieldPrio2>::get_decoded_with_param(&dimension, &encoded).unwrap(),
+            leader
+        );
+        assert!(Proof::<FieldPrio2>::get_decoded_with_param(&(dimension + 1), &encoded).is_err());
+
+        assert!(Proof::<FieldPrio2>::from_flat(&leader.data, dimension).is_err());
+        let mut truncated = leader;
+        truncated.points_h_packed.pop();
+        assert!(truncated.to_flat().is_err());
+    }
+}
@@ -3,18 +3,24 @@
 
 //! Primitives for the Prio2 server.
 use crate::{
+    fft::{FftBackend, FftError},
     field::{FftFriendlyFieldElement, FieldError},
-    polynomial::poly_interpret_eval,
+    polynomial::{poly_interpret_eval, poly_interpret_eval_batch, FieldOver},
     prng::PrngError,
-    vdaf::prio2::client::{unpack_proof, SerializeError},
+    vdaf::prio2::client::{unpack_proof, ProofLayout, SerializeError},
 };
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::TryReserveError,
+    mem::size_of,
+    ops::{Add, Mul},
+};
+use subtle::ConstantTimeEq;
 
 /// Possible errors from server operations
 #[derive(Debug, thiserror::Error)]
 pub enum ServerError {
     /// Unexpected Share Length
-    #[allow(unused)]
     #[error("unexpected share length")]
     ShareLength,
     /// Finite field operation error
@@ -29,10 +35,31 @@ pub enum ServerError {
     /// PRNG error.
     #[error("prng error: {0}")]
     Prng(#[from] PrngError),
+    /// FFT error.
+    #[error("fft error: {0}")]
+    Fft(#[from] FftError),
+    /// The validation memory for the dimension would exceed the limit.
+    #[error("validation memory of {required:?} bytes exceeds the limit of {limit} bytes")]
+    MemoryLimit {
+        /// The number of bytes required, or `None` if this overflows a `usize`.
+        required: Option<usize>,
+        /// The limit.
+        limit: usize,
+    },
+    /// The validation memory could not be allocated.
+    #[error("allocation failed: {0}")]
+    Allocation(#[from] TryReserveError),
 }
 
 /// Verification message for proof validation
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// A message holds the evaluations at a single point. Evaluating a proof at several points would
+/// not be zero-knowledge: the proof masks `f` and `g` with one random value each, `f(0)` and
+/// `g(0)`, so two evaluations of `f` could be combined to cancel the mask and reveal a linear
+/// function of the measurement. To reduce the soundness error, use
+/// [extended verification](crate::vdaf::prio2::Prio2::with_extended_verification), which moves the
+/// evaluation point to a larger field instead.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct VerificationMessage<F> {
     /// f evaluated at random point
     pub f_r: F,
@@ -42,75 +69,172 @@ pub struct VerificationMessage<F> {
     pub h_r: F,
 }
 
+/// Scratch memory used to evaluate a proof share. A single instance can be reused across reports,
+/// so that constructing verification messages does not allocate.
+#[derive(Debug)]
+pub(crate) struct ValidationMemory<F> {
+    /// Points of the polynomial currently being evaluated.
+    fft_in: Vec<F>,
+    /// Scratch space for [`poly_interpret_eval`].
+    fft_mem: Vec<F>,
+}
+
+/// The default limit on the size of a [`ValidationMemory`], in bytes.
+pub(crate) const DEFAULT_VALIDATION_MEMORY_LIMIT: usize = 1 << 28;
+
+impl<F: FftFriendlyFieldElement> ValidationMemory<F> {
+    /// Returns the number of bytes allocated to validate proofs of the given dimension, or `None`
+    /// if the proof length overflows.
+    pub(crate) fn size(dimension: usize) -> Option<usize> {
+        ProofLayout::new(dimension)?
+            .fft_len()
+            .checked_mul(2 * size_of::<F>())
+    }
+
+    /// Allocate memory for validating proofs of the given dimension. The dimension may come from
+    /// an untrusted task configuration, so this returns [`ServerError::MemoryLimit`] rather than
+    /// allocating more than `limit` bytes, and [`ServerError::Allocation`] if the allocation fails.
+    pub(crate) fn new(dimension: usize, limit: usize) -> Result<Self, ServerError> {
+        let required = Self::size(dimension);
+        if required.map_or(true, |required| required > limit) {
+            return Err(ServerError::MemoryLimit { required, limit });
+        }
+        // Unwrap safety: the size is only defined if the layout is.
+        let fft_len = ProofLayout::new(dimension).unwrap().fft_len();
+        let zeros = || -> Result<Vec<F>, ServerError> {
+            let mut v = Vec::new();
+            v.try_reserve_exact(fft_len)?;
+            v.resize(fft_len, F::zero());
+            Ok(v)
+        };
+        Ok(Self {
+            fft_in: zeros()?,
+            fft_mem: zeros()?,
+        })
+    }
+}
+
 /// Given a proof and evaluation point, this constructs the verification
-/// message.
-pub(crate) fn generate_verification_message<F: FftFriendlyFieldElement>(
+/// message. The interpolations of `f` and `g` are passed to `fft` as a single batch.
+///
+/// The evaluation point may lie in an extension `E` of the field of the proof. The polynomials are
+/// interpolated in the base field and only evaluated in the extension.
+pub(crate) fn generate_verification_message<F: FftFriendlyFieldElement, E: FieldOver<F>>(
     dimension: usize,
-    eval_at: F,
+    eval_at: E,
     proof: &[F],
     is_first_server: bool,
-) -> Result<VerificationMessage<F>, ServerError> {
-    let unpacked = unpack_proof(proof, dimension)?;
-    let n: usize = (dimension + 1).next_power_of_two();
-    let proof_length = 2 * n;
-    let mut fft_in = vec![F::zero(); proof_length];
-    let mut fft_mem = vec![F::zero(); proof_length];
-
-    // construct and evaluate polynomial f at the random point
-    fft_in[0] = *unpacked.f0;
-    fft_in[1..unpacked.data.len() + 1].copy_from_slice(unpacked.data);
-    let f_r = poly_interpret_eval(&fft_in[..n], eval_at, &mut fft_mem);
-
-    // construct and evaluate polynomial g at the random point
-    fft_in[0] = *unpacked.g0;
-    if is_first_server {
-        for x in fft_in[1..unpacked.data.len() + 1].iter_mut() {
-            *x -= F::one();
-        }
+    mem: &mut ValidationMemory<F>,
+    fft: &dyn FftBackend<F>,
+) -> Result<VerificationMessage<E>, ServerError> {
+    // check the proof length
+    unpack_proof(proof, dimension)?;
+    generate_verification_message_streamed(
+        dimension,
+        eval_at,
+        proof.iter().copied(),
+        is_first_server,
+        mem,
+        fft,
+    )
+}
+
+/// Like [`generate_verification_message`], but reads the proof from an iterator as it is needed,
+/// so that a helper can pass the output of its PRNG without expanding its share into a vector
+/// first. The components of the proof are written directly into `mem`.
+///
+/// Returns [`ServerError::ShareLength`] if `proof` yields fewer elements than the proof length.
+/// Any further elements are not read.
+pub(crate) fn generate_verification_message_streamed<F, E, I>(
+    dimension: usize,
+    eval_at: E,
+    mut proof: I,
+    is_first_server: bool,
+    mem: &mut ValidationMemory<F>,
+    fft: &dyn FftBackend<F>,
+) -> Result<VerificationMessage<E>, ServerError>
+where
+    F: FftFriendlyFieldElement,
+    E: FieldOver<F>,
+    I: Iterator<Item = F>,
+{
+    let layout = ProofLayout::new(dimension).ok_or(SerializeError::UnpackInputSizeMismatch)?;
+    let (n, fft_len) = (layout.n(), layout.fft_len());
+    if mem.fft_in.len() != fft_len || mem.fft_mem.len() != fft_len {
+        return Err(ServerError::ShareLength);
     }
-    let g_r = poly_interpret_eval(&fft_in[..n], eval_at, &mut fft_mem);
-
-    // construct and evaluate polynomial h at the random point
-    fft_in[0] = *unpacked.h0;
-    fft_in[1] = unpacked.points_h_packed[0];
-    for (x, chunk) in unpacked.points_h_packed[1..]
-        .iter()
-        .zip(fft_in[2..proof_length].chunks_exact_mut(2))
+    let mut next = || proof.next().ok_or(ServerError::ShareLength);
+    let (f_in, g_in) = mem.fft_in.split_at_mut(n);
+
+    // construct polynomials f and g from the data, which comes first in the proof
+    for (f, g) in f_in[1..dimension + 1]
+        .iter_mut()
+        .zip(g_in[1..dimension + 1].iter_mut())
+    {
+        let x = next()?;
+        *f = x;
+        *g = if is_first_server { x - F::one() } else { x };
+    }
+    f_in[0] = next()?;
+    g_in[0] = next()?;
+
+    // The memory may hold points from a previous evaluation of h, so clear the padding of f and g.
+    for x in f_in[dimension + 1..]
+        .iter_mut()
+        .chain(g_in[dimension + 1..].iter_mut())
     {
+        *x = F::zero();
+    }
+
+    // evaluate f and g at the random point
+    let (f_mem, g_mem) = mem.fft_mem.split_at_mut(n);
+    let [f_r, g_r] = poly_interpret_eval_batch([f_in, g_in], eval_at, [f_mem, g_mem], fft)?;
+
+    // construct and evaluate polynomial h at the random point, reusing the memory of f and g
+    let fft_in = &mut mem.fft_in;
+    fft_in[0] = next()?;
+    fft_in[1] = next()?;
+    for chunk in fft_in[2..fft_len].chunks_exact_mut(2) {
         chunk[0] = F::zero();
-        chunk[1] = *x;
+        chunk[1] = next()?;
     }
-    let h_r = poly_interpret_eval(&fft_in, eval_at, &mut fft_mem);
+    let h_r = poly_interpret_eval(fft_in, eval_at, &mut mem.fft_mem, fft)?;
 
     Ok(VerificationMessage { f_r, g_r, h_r })
 }
 
 /// Decides if the distributed proof is valid
-pub(crate) fn is_valid_share<F: FftFriendlyFieldElement>(
-    v1: &VerificationMessage<F>,
-    v2: &VerificationMessage<F>,
-) -> bool {
+pub(crate) fn is_valid_share<E>(v1: &VerificationMessage<E>, v2: &VerificationMessage<E>) -> bool
+where
+    E: Copy + Add<Output = E> + Mul<Output = E> + ConstantTimeEq,
+{
     // reconstruct f_r, g_r, h_r
     let f_r = v1.f_r + v2.f_r;
     let g_r = v1.g_r + v2.g_r;
     let h_r = v1.h_r + v2.h_r;
-    // validity check
-    f_r * g_r == h_r
+    // validity check. The comparison is constant-time so that its timing does not depend on the
+    // reconstructed values.
+    (f_r * g_r).ct_eq(&h_r).into()
 }
 
 #[cfg(test)]
 mod test_util {
     use crate::{
-        codec::ParameterizedDecode,
+        codec::Decode,
+        fft::CpuFftBackend,
         field::{merge_vector, FftFriendlyFieldElement},
         prng::Prng,
         vdaf::{
             prio2::client::{proof_length, SerializeError},
-            Share, ShareDecodingParameter,
+            xof::Seed,
         },
     };
+    use std::io::Cursor;
 
-    use super::{generate_verification_message, is_valid_share, ServerError, VerificationMessage};
+    use super::{
+        generate_verification_message, is_valid_share, ServerError, ValidationMemory,
+        VerificationMessage, DEFAULT_VALIDATION_MEMORY_LIMIT,
+    };
 
     /// Main workhorse of the server.
     #[derive(Debug)]
@@ -118,6 +242,8 @@ mod test_util {
         dimension: usize,
         is_first_server: bool,
         accumulator: Vec<F>,
+        share: Vec<F>,
+        validation_mem: ValidationMemory<F>,
     }
 
     impl<F: FftFriendlyFieldElement> Server<F> {
@@ -131,23 +257,30 @@ mod test_util {
                 dimension,
                 is_first_server,
                 accumulator: vec![F::zero(); dimension],
+                share: Vec::with_capacity(proof_length(dimension)),
+                validation_mem: ValidationMemory::new(dimension, DEFAULT_VALIDATION_MEMORY_LIMIT)?,
             })
         }
 
-        /// Deserialize
-        fn deserialize_share(&self, share: &[u8]) -> Result<Vec<F>, ServerError> {
+        /// Deserialize a share into the server's share buffer, reusing its allocation.
+        fn deserialize_share(&mut self, share: &[u8]) -> Result<(), ServerError> {
             let len = proof_length(self.dimension);
-            let decoding_parameter = if self.is_first_server {
-                ShareDecodingParameter::Leader(len)
+            self.share.clear();
+            if self.is_first_server {
+                let mut cursor = Cursor::new(share);
+                for _ in 0..len {
+                    self.share
+                        .push(F::decode(&mut cursor).map_err(SerializeError::from)?);
+                }
+                if cursor.position() as usize != share.len() {
+                    return Err(ServerError::ShareLength);
+                }
             } else {
-                ShareDecodingParameter::Helper
-            };
-            let decoded_share = Share::get_decoded_with_param(&decoding_parameter, share)
-                .map_err(SerializeError::from)?;
-            match decoded_share {
-                Share::Leader(vec) => Ok(vec),
-                Share::Helper(seed) => Ok(Prng::from_prio2_seed(&seed.0).take(len).collect()),
+                let seed = Seed::<32>::get_decoded(share).map_err(SerializeError::from)?;
+                self.share
+                    .extend(Prng::<F, _>::from_prio2_seed(seed.as_ref()).take(len));
             }
+            Ok(())
         }
 
         /// Generate verification message from an encrypted share
@@ -161,12 +294,14 @@ mod test_util {
             eval_at: F,
             share: &[u8],
         ) -> Result<VerificationMessage<F>, ServerError> {
-            let share_field = self.deserialize_share(share)?;
+            self.deserialize_share(share)?;
             generate_verification_message(
                 self.dimension,
                 eval_at,
-                &share_field,
+                &self.share,
                 self.is_first_server,
+                &mut self.validation_mem,
+                &CpuFftBackend,
             )
         }
 
@@ -180,13 +315,12 @@ mod test_util {
             v1: &VerificationMessage<F>,
             v2: &VerificationMessage<F>,
         ) -> Result<bool, ServerError> {
-            let share_field = self.deserialize_share(share)?;
+            self.deserialize_share(share)?;
             let is_valid = is_valid_share(v1, v2);
             if is_valid {
-                // Add to the accumulator. share_field also includes the proof
-                // encoding, so we slice off the first dimension fields, which are
-                // the actual data share.
-                merge_vector(&mut self.accumulator, &share_field[..self.dimension])?;
+                // Add to the accumulator. The share also includes the proof encoding, so we slice
+                // off the first dimension fields, which are the actual data share.
+                merge_vector(&mut self.accumulator, &self.share[..self.dimension])?;
             }
 
             Ok(is_valid)
@@ -199,6 +333,7 @@ mod tests {
     use super::*;
     use crate::{
         codec::{Encode, ParameterizedDecode},
+        fft::CpuFftBackend,
         field::{FieldElement, FieldPrio2},
         prng::Prng,
         vdaf::{
@@ -207,7 +342,8 @@ mod tests {
                 server::test_util::Server,
                 Prio2,
             },
-            Client, Share, ShareDecodingParameter,
+            xof::Seed,
+            Share, ShareDecodingParameter,
         },
     };
     use assert_matches::assert_matches;
@@ -237,9 +373,130 @@ mod tests {
         let share2 = secret_share(&mut proof);
         let eval_at = FieldPrio2::from(12313);
 
-        let v1 = generate_verification_message(dim, eval_at, &proof, true).unwrap();
-        let v2 = generate_verification_message(dim, eval_at, &share2, false).unwrap();
+        let mut mem = ValidationMemory::new(dim, DEFAULT_VALIDATION_MEMORY_LIMIT).unwrap();
+        let v1 =
+            generate_verification_message(dim, eval_at, &proof, true, &mut mem, &CpuFftBackend)
+                .unwrap();
+        let v2 =
+            generate_verification_message(dim, eval_at, &share2, false, &mut mem, &CpuFftBackend)
+                .unwrap();
+        assert!(is_valid_share(&v1, &v2));
+    }
+
+    #[test]
+    fn test_validation_streamed() {
+        let dim = 8;
+        let seed = Seed::<32>::generate().unwrap();
+        let share: Vec<FieldPrio2> = Prng::from_prio2_seed(seed.as_ref())
+            .take(proof_length(dim))
+            .collect();
+        let eval_at = FieldPrio2::from(12313);
+
+        let mut mem = ValidationMemory::new(dim, DEFAULT_VALIDATION_MEMORY_LIMIT).unwrap();
+        let want =
+            generate_verification_message(dim, eval_at, &share, false, &mut mem, &CpuFftBackend)
+                .unwrap();
+        let got = generate_verification_message_streamed(
+            dim,
+            eval_at,
+            Prng::<FieldPrio2, _>::from_prio2_seed(seed.as_ref()),
+            false,
+            &mut mem,
+            &CpuFftBackend,
+        )
+        .unwrap();
+        assert_eq!(got, want);
+
+        assert_matches!(
+            generate_verification_message_streamed(
+                dim,
+                eval_at,
+                share[..share.len() - 1].iter().copied(),
+                false,
+                &mut mem,
+                &CpuFftBackend,
+            ),
+            Err(ServerError::ShareLength)
+        );
+    }
+
+    #[test]
+    fn test_validation_memory_reuse() {
+        let dim = 8;
+        let proof_u32: Vec<u32> = vec![
+            1, 0, 0, 0, 0, 0, 0, 0, 2052337230, 3217065186, 1886032198, 2533724497, 397524722,
+            3820138372, 1535223968, 4291254640, 3565670552, 2447741959, 163741941, 335831680,
+            2567182742, 3542857140, 124017604, 4201373647, 431621210, 1618555683, 267689149,
+        ];
+
+        let mut proof: Vec<FieldPrio2> = proof_u32.iter().map(|x| FieldPrio2::from(*x)).collect();
+        let share2 = secret_share(&mut proof);
+        let eval_at = FieldPrio2::from(12313);
+
+        // Dirty the memory by evaluating an unrelated proof share first.
+        let mut reused = ValidationMemory::new(dim, DEFAULT_VALIDATION_MEMORY_LIMIT).unwrap();
+        let garbage: Vec<FieldPrio2> = (0..proof_length(dim))
+            .map(|_| FieldPrio2::from(random::<u32>()))
+            .collect();
+        generate_verification_message(dim, eval_at, &garbage, true, &mut reused, &CpuFftBackend)
+            .unwrap();
+
+        let v1 =
+            generate_verification_message(dim, eval_at, &proof, true, &mut reused, &CpuFftBackend)
+                .unwrap();
+        let want = generate_verification_message(
+            dim,
+            eval_at,
+            &proof,
+            true,
+            &mut ValidationMemory::new(dim, DEFAULT_VALIDATION_MEMORY_LIMIT).unwrap(),
+            &CpuFftBackend,
+        )
+        .unwrap();
+        assert_eq!(v1.f_r, want.f_r);
+        assert_eq!(v1.g_r, want.g_r);
+        assert_eq!(v1.h_r, want.h_r);
+
+        let v2 = generate_verification_message(
+            dim,
+            eval_at,
+            &share2,
+            false,
+            &mut reused,
+            &CpuFftBackend,
+        )
+        .unwrap();
         assert!(is_valid_share(&v1, &v2));
+
+        // Memory sized for a different dimension is rejected.
+        assert_matches!(
+            generate_verification_message(
+                dim,
+                eval_at,
+                &proof,
+                true,
+                &mut ValidationMemory::new(2 * dim, DEFAULT_VALIDATION_MEMORY_LIMIT).unwrap(),
+                &CpuFftBackend,
+            ),
+            Err(ServerError::ShareLength)
+        );
+    }
+
+    #[test]
+    fn test_validation_memory_limit() {
+        let dim = 1000;
+        let size = ValidationMemory::<FieldPrio2>::size(dim).unwrap();
+        assert_eq!(size, 2 * 2048 * size_of::<FieldPrio2>());
+        ValidationMemory::<FieldPrio2>::new(dim, size).unwrap();
+        assert_matches!(
+            ValidationMemory::<FieldPrio2>::new(dim, size - 1),
+            Err(ServerError::MemoryLimit { required: Some(required), limit })
+                if required == size && limit == size - 1
+        );
+        assert_matches!(
+            ValidationMemory::<FieldPrio2>::new(usize::MAX / 2, usize::MAX),
+            Err(ServerError::MemoryLimit { required: None, .. })
+        );
     }
 
     #[test]
@@ -255,8 +512,13 @@ mod tests {
         let share2 = secret_share(&mut proof);
         let eval_at = FieldPrio2::from(12313);
 
-        let v1 = generate_verification_message(dim, eval_at, &proof, true).unwrap();
-        let v2 = generate_verification_message(dim, eval_at, &share2, false).unwrap();
+        let mut mem = ValidationMemory::new(dim, DEFAULT_VALIDATION_MEMORY_LIMIT).unwrap();
+        let v1 =
+            generate_verification_message(dim, eval_at, &proof, true, &mut mem, &CpuFftBackend)
+                .unwrap();
+        let v2 =
+            generate_verification_message(dim, eval_at, &share2, false, &mut mem, &CpuFftBackend)
+                .unwrap();
 
         // serialize and deserialize the first verification message
         let serialized = serde_json::to_string(&v1).unwrap();
@@ -287,14 +549,22 @@ mod tests {
         let mut server2 = Server::new(dim, false).unwrap();
 
         // all zero data
-        let mut data = vec![0; dim];
+        let mut data = vec![FieldPrio2::zero(); dim];
 
         if let Tweak::WrongInput = tweak {
-            data[0] = 2;
+            data[0] = FieldPrio2::from(2);
         }
 
+        // Shard the data directly, since `shard()` rejects invalid measurements.
         let vdaf = Prio2::new(dim).unwrap();
-        let (_, shares) = vdaf.shard(&data, &[0; 16]).unwrap();
+        let (_, shares) = vdaf
+            .shard_with_seeds(
+                &mut vdaf.client_memory().unwrap(),
+                &data,
+                &Seed::generate().unwrap(),
+                vec![Seed::generate().unwrap()],
+            )
+            .unwrap();
         let share1_original = shares[0].get_encoded().unwrap();
         let share2 = shares[1].get_encoded().unwrap();
 
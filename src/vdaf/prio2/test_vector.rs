@@ -8,6 +8,7 @@ use std::fmt::Debug;
 
 /// Errors propagated by functions in this module.
 #[derive(Debug, thiserror::Error)]
+#[allow(dead_code)]
 pub(crate) enum TestVectorError {
     /// Error from Prio client
     #[error("Prio client error {0}")]
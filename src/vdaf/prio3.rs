@@ -16,6 +16,8 @@
 //! - [`Prio3Count`] for aggregating a counter (*)
 //! - [`Prio3Sum`] for copmputing the sum of integers (*)
 //! - [`Prio3SumVec`] for aggregating a vector of integers
+//! - [`Prio3FixedSumVec`] for aggregating a vector of integers whose length is known at compile
+//!   time
 //! - [`Prio3Histogram`] for estimating a distribution via a histogram (*)
 //!
 //! Additional types can be constructed from [`Prio3`] as needed.
@@ -40,28 +42,38 @@ use crate::flp::gadgets::ParallelSumMultithreaded;
 #[cfg(feature = "experimental")]
 use crate::flp::gadgets::PolyEval;
 use crate::flp::gadgets::{Mul, ParallelSum};
+use crate::flp::soundness::SoundnessError;
 #[cfg(feature = "experimental")]
 use crate::flp::types::fixedpoint_l2::{
     compatible_float::CompatibleFloat, FixedPointBoundedL2VecSum,
 };
-use crate::flp::types::{Average, Count, Histogram, Sum, SumVec};
-use crate::flp::Type;
+use crate::flp::types::{
+    AndCountVec, Average, Count, DistinctCount, FixedSumVec, Histogram, MultihotCountVec,
+    RangeSumVec, SignedSum, SparseSumVec, Sum, SumVec,
+};
 #[cfg(feature = "experimental")]
 use crate::flp::TypeWithNoise;
+use crate::flp::{Type, WireRoots};
 use crate::prng::Prng;
-use crate::vdaf::xof::{IntoFieldVec, Seed, Xof};
+use crate::vdaf::telemetry;
+use crate::vdaf::xof::{hash_to_field, IntoFieldVec, Seed, Xof};
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+use crate::vdaf::ClientWithRng;
 use crate::vdaf::{
     Aggregatable, AggregateShare, Aggregator, Client, Collector, OutputShare, PrepareTransition,
-    Share, ShareDecodingParameter, Vdaf, VdafError,
+    RejectionReason, Share, ShareDecodingParameter, Vdaf, VdafError,
 };
 #[cfg(feature = "experimental")]
 use fixed::traits::Fixed;
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+use rand_core::{CryptoRng, RngCore};
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::io::Cursor;
 use std::iter::{self, IntoIterator};
 use std::marker::PhantomData;
+use std::sync::Arc;
 use subtle::{Choice, ConstantTimeEq};
 
 const DST_MEASUREMENT_SHARE: u16 = 1;
@@ -106,6 +118,29 @@ impl Prio3SumVec {
     }
 }
 
+/// Like [`Prio3SumVec`] except the length `D` of the measurement is fixed at compile time.
+/// Measurements and aggregate results are arrays of length `D`. Messages are the same as those of
+/// a [`Prio3SumVec`] of length `D`.
+pub type Prio3FixedSumVec<const D: usize> =
+    Prio3<FixedSumVec<Field128, ParallelSum<Field128, Mul<Field128>>, D>, XofTurboShake128, 16>;
+
+impl<const D: usize> Prio3FixedSumVec<D> {
+    /// Construct an instance of Prio3FixedSumVec with the given number of aggregators. `bits`
+    /// defines the bit width of each summand of the measurement.
+    pub fn new_fixed_sum_vec(
+        num_aggregators: u8,
+        bits: usize,
+        chunk_length: usize,
+    ) -> Result<Self, VdafError> {
+        Prio3::new(
+            num_aggregators,
+            1,
+            0x00000002,
+            FixedSumVec::new(bits, chunk_length)?,
+        )
+    }
+}
+
 /// Like [`Prio3SumVec`] except this type uses multithreading to improve sharding and preparation
 /// time. Note that the improvement is only noticeable for very large input lengths.
 #[cfg(feature = "multithreaded")]
@@ -154,6 +189,18 @@ impl Prio3Sum {
     }
 }
 
+/// The signed sum type. Each measurement is an integer in `[-2^(bits-1), 2^(bits-1))` and the
+/// aggregate is the sum, which may be negative.
+pub type Prio3SignedSum = Prio3<SignedSum<Field128>, XofTurboShake128, 16>;
+
+impl Prio3SignedSum {
+    /// Construct an instance of Prio3SignedSum with the given number of aggregators and required
+    /// bit length. The bit length must be between 1 and 64, inclusive.
+    pub fn new_signed_sum(num_aggregators: u8, bits: usize) -> Result<Self, VdafError> {
+        Prio3::new(num_aggregators, 1, 0xFFFF0000, SignedSum::new(bits)?)
+    }
+}
+
 /// The fixed point vector sum type. Each measurement is a vector of fixed point numbers
 /// and the aggregate is the sum represented as 64-bit floats. The preparation phase
 /// ensures the L2 norm of the input vector is < 1.
@@ -254,6 +301,120 @@ impl Prio3Histogram {
     }
 }
 
+/// The multihot counter vector type. Each measurement is a vector of `length` bits with at most
+/// `max_weight` ones, and the result counts the ones in each position.
+pub type Prio3MultihotCountVec =
+    Prio3<MultihotCountVec<Field128, ParallelSum<Field128, Mul<Field128>>>, XofTurboShake128, 16>;
+
+impl Prio3MultihotCountVec {
+    /// Constructs an instance of Prio3MultihotCountVec with the given number of aggregators,
+    /// vector length, maximum weight, and parallel sum gadget chunk length.
+    pub fn new_multihot_count_vec(
+        num_aggregators: u8,
+        length: usize,
+        max_weight: usize,
+        chunk_length: usize,
+    ) -> Result<Self, VdafError> {
+        Prio3::new(
+            num_aggregators,
+            1,
+            0x00000004,
+            MultihotCountVec::new(length, max_weight, chunk_length)?,
+        )
+    }
+}
+
+/// The sparse sum vector type. Each measurement is a vector of `length` integers of `bits` bits
+/// with at most `max_nonzero` non-zero entries, and the result is the sum of the integers in each
+/// position.
+pub type Prio3SparseSumVec =
+    Prio3<SparseSumVec<Field128, ParallelSum<Field128, Mul<Field128>>>, XofTurboShake128, 16>;
+
+impl Prio3SparseSumVec {
+    /// Constructs an instance of Prio3SparseSumVec with the given number of aggregators, vector
+    /// length, bit width, maximum number of non-zero entries, and parallel sum gadget chunk length.
+    pub fn new_sparse_sum_vec(
+        num_aggregators: u8,
+        length: usize,
+        bits: usize,
+        max_nonzero: usize,
+        chunk_length: usize,
+    ) -> Result<Self, VdafError> {
+        Prio3::new(
+            num_aggregators,
+            1,
+            0xFFFF0000,
+            SparseSumVec::new(length, bits, max_nonzero, chunk_length)?,
+        )
+    }
+}
+
+/// The range sum vector type. Each measurement is a vector of integers in an inclusive range
+/// `[min, max]`, and the result is the sum of the integers in each position.
+pub type Prio3RangeSumVec = Prio3<RangeSumVec<Field128>, XofTurboShake128, 16>;
+
+impl Prio3RangeSumVec {
+    /// Constructs an instance of Prio3RangeSumVec with the given number of aggregators, range
+    /// bounds, and vector length.
+    pub fn new_range_sum_vec(
+        num_aggregators: u8,
+        min: usize,
+        max: usize,
+        len: usize,
+    ) -> Result<Self, VdafError> {
+        Prio3::new(
+            num_aggregators,
+            1,
+            0xFFFF0000,
+            RangeSumVec::new(min, max, len)?,
+        )
+    }
+}
+
+/// The conjunction counter vector type. Each measurement is a vector of booleans, and the result
+/// counts the measurements that set each position and each configured pair of positions.
+pub type Prio3AndCountVec = Prio3<AndCountVec<Field128>, XofTurboShake128, 16>;
+
+impl Prio3AndCountVec {
+    /// Constructs an instance of Prio3AndCountVec with the given number of aggregators, vector
+    /// length, and pairs of positions whose conjunctions are counted.
+    pub fn new_and_count_vec(
+        num_aggregators: u8,
+        len: usize,
+        pairs: Vec<(usize, usize)>,
+    ) -> Result<Self, VdafError> {
+        Prio3::new(
+            num_aggregators,
+            1,
+            0xFFFF0000,
+            AndCountVec::new(len, pairs)?,
+        )
+    }
+}
+
+/// The distinct count type. Each measurement is a sketch of a set of identifiers, and the result
+/// is an estimate of the number of distinct identifiers across all measurements.
+pub type Prio3DistinctCount =
+    Prio3<DistinctCount<Field128, ParallelSum<Field128, Mul<Field128>>>, XofTurboShake128, 16>;
+
+impl Prio3DistinctCount {
+    /// Constructs an instance of Prio3DistinctCount with the given number of aggregators, number
+    /// of bucket bits, maximum rank, and parallel sum gadget chunk length.
+    pub fn new_distinct_count(
+        num_aggregators: u8,
+        bucket_bits: usize,
+        max_rank: usize,
+        chunk_length: usize,
+    ) -> Result<Self, VdafError> {
+        Prio3::new(
+            num_aggregators,
+            1,
+            0xFFFF0000,
+            DistinctCount::new(bucket_bits, max_rank, chunk_length)?,
+        )
+    }
+}
+
 /// Like [`Prio3Histogram`] except this type uses multithreading to improve sharding and preparation
 /// time. Note that this improvement is only noticeable for very large input lengths.
 #[cfg(feature = "multithreaded")]
@@ -298,13 +459,7 @@ impl Prio3Average {
             )));
         }
 
-        Ok(Prio3 {
-            num_aggregators,
-            num_proofs: 1,
-            algorithm_id: 0xFFFF0000,
-            typ: Average::new(bits)?,
-            phantom: PhantomData,
-        })
+        Prio3::new(num_aggregators, 1, 0xFFFF0000, Average::new(bits)?)
     }
 }
 
@@ -381,6 +536,7 @@ where
     num_proofs: u8,
     algorithm_id: u32,
     typ: T,
+    wire_roots: Arc<WireRoots<T::Field>>,
     phantom: PhantomData<P>,
 }
 
@@ -408,6 +564,7 @@ where
             num_aggregators,
             num_proofs,
             algorithm_id,
+            wire_roots: Arc::new(WireRoots::new(&typ)?),
             typ,
             phantom: PhantomData,
         })
@@ -423,18 +580,79 @@ where
         self.typ.verifier_len()
     }
 
+    /// The length in bytes of the encoded public share.
+    pub fn public_share_len(&self) -> usize {
+        if self.typ.joint_rand_len() > 0 {
+            SEED_SIZE * self.num_aggregators()
+        } else {
+            0
+        }
+    }
+
+    /// The length in bytes of the encoded input share of Aggregator `agg_id`. The leader's share
+    /// (`agg_id == 0`) holds the measurement and proof shares in full; every other Aggregator's
+    /// share is expanded from seeds.
+    pub fn input_share_len(&self, agg_id: usize) -> usize {
+        let mut len = if agg_id == 0 {
+            T::Field::ENCODED_SIZE
+                * (self.typ.input_len() + self.typ.proof_len() * self.num_proofs())
+        } else {
+            2 * SEED_SIZE
+        };
+        if self.typ.joint_rand_len() > 0 {
+            len += SEED_SIZE;
+        }
+        len
+    }
+
+    /// The length in bytes of an encoded prepare share.
+    pub fn prepare_share_len(&self) -> usize {
+        let mut len = T::Field::ENCODED_SIZE * self.typ.verifier_len() * self.num_proofs();
+        if self.typ.joint_rand_len() > 0 {
+            len += SEED_SIZE;
+        }
+        len
+    }
+
+    /// The length in bytes of an encoded prepare message.
+    pub fn prepare_message_len(&self) -> usize {
+        if self.typ.joint_rand_len() > 0 {
+            SEED_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// The length in bytes of an encoded aggregate share. This is also the size of the field
+    /// elements an Aggregator holds in memory for each batch.
+    pub fn aggregate_share_len(&self) -> usize {
+        T::Field::ENCODED_SIZE * self.typ.output_len()
+    }
+
+    /// Returns an upper bound on the probability that the Aggregators accept an invalid
+    /// measurement, which depends on the field, the validity circuit and the number of proofs.
+    pub fn soundness_error(&self) -> Result<SoundnessError, VdafError> {
+        Ok(SoundnessError::for_type(&self.typ, self.num_proofs)?)
+    }
+
+    /// Fails if the [soundness error](Self::soundness_error) exceeds `2^max_log2`. An application
+    /// can call this when configuring a task, to refuse parameters that are too weak.
+    pub fn check_soundness(&self, max_log2: f64) -> Result<(), VdafError> {
+        Ok(self.soundness_error()?.check(max_log2)?)
+    }
+
     #[inline]
     fn num_proofs(&self) -> usize {
         self.num_proofs.into()
     }
 
     fn derive_prove_rands(&self, prove_rand_seed: &Seed<SEED_SIZE>) -> Vec<T::Field> {
-        P::seed_stream(
+        hash_to_field::<P, _, SEED_SIZE>(
             prove_rand_seed,
             &self.domain_separation_tag(DST_PROVE_RANDOMNESS),
-            &[self.num_proofs],
+            &[&[self.num_proofs]],
+            self.typ.prove_rand_len() * self.num_proofs(),
         )
-        .into_field_vec(self.typ.prove_rand_len() * self.num_proofs())
     }
 
     fn derive_joint_rand_seed<'a>(
@@ -456,12 +674,12 @@ where
         joint_rand_parts: impl Iterator<Item = &'a Seed<SEED_SIZE>>,
     ) -> (Seed<SEED_SIZE>, Vec<T::Field>) {
         let joint_rand_seed = self.derive_joint_rand_seed(joint_rand_parts);
-        let joint_rands = P::seed_stream(
+        let joint_rands = hash_to_field::<P, _, SEED_SIZE>(
             &joint_rand_seed,
             &self.domain_separation_tag(DST_JOINT_RANDOMNESS),
-            &[self.num_proofs],
-        )
-        .into_field_vec(self.typ.joint_rand_len() * self.num_proofs());
+            &[&[self.num_proofs]],
+            self.typ.joint_rand_len() * self.num_proofs(),
+        );
 
         (joint_rand_seed, joint_rands)
     }
@@ -479,17 +697,22 @@ where
     }
 
     fn derive_query_rands(&self, verify_key: &[u8; SEED_SIZE], nonce: &[u8; 16]) -> Vec<T::Field> {
-        let mut xof = P::init(
-            verify_key,
+        hash_to_field::<P, _, SEED_SIZE>(
+            &Seed(*verify_key),
             &self.domain_separation_tag(DST_QUERY_RANDOMNESS),
-        );
-        xof.update(&[self.num_proofs]);
-        xof.update(nonce);
-        xof.into_seed_stream()
-            .into_field_vec(self.typ.query_rand_len() * self.num_proofs())
+            &[&[self.num_proofs], nonce],
+            self.typ.query_rand_len() * self.num_proofs(),
+        )
+    }
+
+    /// The number of field elements the seeds of a helper's input share are expanded into: a
+    /// measurement share and a share of each proof.
+    #[cfg(all(feature = "crypto-dependencies", feature = "experimental"))]
+    pub(crate) fn expanded_share_len(&self) -> usize {
+        self.typ.input_len() + self.typ.proof_len() * self.num_proofs()
     }
 
-    fn random_size(&self) -> usize {
+    pub(crate) fn random_size(&self) -> usize {
         if self.typ.joint_rand_len() == 0 {
             // Two seeds per helper for measurement and proof shares, plus one seed for proving
             // randomness.
@@ -645,10 +868,11 @@ where
             let joint_rand =
                 &joint_rands[p * self.typ.joint_rand_len()..(p + 1) * self.typ.joint_rand_len()];
 
-            leader_proofs_share.append(&mut self.typ.prove(
+            leader_proofs_share.append(&mut self.typ.prove_with_roots(
                 &encoded_measurement,
                 prove_rand,
                 joint_rand,
+                &self.wire_roots,
             )?);
         }
 
@@ -743,6 +967,15 @@ impl<const SEED_SIZE: usize> Encode for Prio3PublicShare<SEED_SIZE> {
     }
 }
 
+#[cfg(feature = "test-util")]
+impl<'a, const SEED_SIZE: usize> arbitrary::Arbitrary<'a> for Prio3PublicShare<SEED_SIZE> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self {
+            joint_rand_parts: u.arbitrary()?,
+        })
+    }
+}
+
 impl<const SEED_SIZE: usize> PartialEq for Prio3PublicShare<SEED_SIZE> {
     fn eq(&self, other: &Self) -> bool {
         self.ct_eq(other).into()
@@ -800,6 +1033,19 @@ pub struct Prio3InputShare<F, const SEED_SIZE: usize> {
     joint_rand_blind: Option<Seed<SEED_SIZE>>,
 }
 
+#[cfg(feature = "test-util")]
+impl<'a, F: arbitrary::Arbitrary<'a>, const SEED_SIZE: usize> arbitrary::Arbitrary<'a>
+    for Prio3InputShare<F, SEED_SIZE>
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self {
+            measurement_share: u.arbitrary()?,
+            proofs_share: u.arbitrary()?,
+            joint_rand_blind: u.arbitrary()?,
+        })
+    }
+}
+
 impl<F: ConstantTimeEq, const SEED_SIZE: usize> PartialEq for Prio3InputShare<F, SEED_SIZE> {
     fn eq(&self, other: &Self) -> bool {
         self.ct_eq(other).into()
@@ -1028,6 +1274,10 @@ where
     P: Xof<SEED_SIZE>,
 {
     #[allow(clippy::type_complexity)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.typ.input_len()))
+    )]
     fn shard(
         &self,
         measurement: &T::Measurement,
@@ -1039,6 +1289,24 @@ where
     }
 }
 
+#[cfg(any(feature = "test-util", feature = "experimental"))]
+impl<T, P, const SEED_SIZE: usize> ClientWithRng<16> for Prio3<T, P, SEED_SIZE>
+where
+    T: Type,
+    P: Xof<SEED_SIZE>,
+{
+    fn shard_with_rng<R: CryptoRng + RngCore>(
+        &self,
+        measurement: &T::Measurement,
+        nonce: &[u8; 16],
+        rng: &mut R,
+    ) -> Result<(Self::PublicShare, Vec<Prio3InputShare<T::Field, SEED_SIZE>>), VdafError> {
+        let mut random = vec![0u8; self.random_size()];
+        rng.fill_bytes(&mut random);
+        self.shard_with_random(measurement, nonce, &random)
+    }
+}
+
 /// State of each [`Aggregator`] during the Preparation phase.
 #[derive(Clone)]
 pub struct Prio3PrepareState<F, const SEED_SIZE: usize> {
@@ -1157,6 +1425,14 @@ where
     /// Begins the Prep process with the other aggregators. The result of this process is
     /// the aggregator's output share.
     #[allow(clippy::type_complexity)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(input_len = self.typ.input_len(), agg_id)
+        )
+    )]
     fn prepare_init(
         &self,
         verify_key: &[u8; SEED_SIZE],
@@ -1197,8 +1473,11 @@ where
 
         // Compute the joint randomness.
         let (joint_rand_seed, joint_rand_part, joint_rands) = if self.typ.joint_rand_len() > 0 {
+            let joint_rand_blind = msg.joint_rand_blind.as_ref().ok_or_else(|| {
+                VdafError::Uncategorized("input share is missing joint randomness blind".into())
+            })?;
             let mut joint_rand_part_xof = P::init(
-                msg.joint_rand_blind.as_ref().unwrap().as_ref(),
+                joint_rand_blind.as_ref(),
                 &self.domain_separation_tag(DST_JOINT_RAND_PART),
             );
             joint_rand_part_xof.update(&[agg_id]);
@@ -1280,6 +1559,10 @@ where
         ))
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.typ.input_len()))
+    )]
     fn prepare_shares_to_prepare_message<
         M: IntoIterator<Item = Prio3PrepareShare<T::Field, SEED_SIZE>>,
     >(
@@ -1287,6 +1570,7 @@ where
         _: &Self::AggregationParam,
         inputs: M,
     ) -> Result<Prio3PrepareMessage<SEED_SIZE>, VdafError> {
+        let _timer = telemetry::VerificationTimer::start("prio3");
         let mut verifiers = vec![T::Field::zero(); self.typ.verifier_len() * self.num_proofs()];
         let mut joint_rand_parts = Vec::with_capacity(self.num_aggregators());
         let mut count = 0;
@@ -1294,15 +1578,27 @@ where
             count += 1;
 
             if share.verifiers.len() != verifiers.len() {
-                return Err(VdafError::Uncategorized(format!(
-                    "unexpected verifier share length: got {}; want {}",
-                    share.verifiers.len(),
-                    verifiers.len(),
-                )));
+                return Err(telemetry::rejected(
+                    "prio3",
+                    RejectionReason::LengthMismatch,
+                    VdafError::Uncategorized(format!(
+                        "unexpected verifier share length: got {}; want {}",
+                        share.verifiers.len(),
+                        verifiers.len(),
+                    )),
+                ));
             }
 
             if self.typ.joint_rand_len() > 0 {
-                let joint_rand_seed_part = share.joint_rand_part.unwrap();
+                let joint_rand_seed_part = share.joint_rand_part.ok_or_else(|| {
+                    telemetry::rejected(
+                        "prio3",
+                        RejectionReason::LengthMismatch,
+                        VdafError::Uncategorized(
+                            "prepare share is missing joint randomness part".into(),
+                        ),
+                    )
+                })?;
                 joint_rand_parts.push(joint_rand_seed_part);
             }
 
@@ -1312,19 +1608,28 @@ where
         }
 
         if count != self.num_aggregators {
-            return Err(VdafError::Uncategorized(format!(
-                "unexpected message count: got {}; want {}",
-                count, self.num_aggregators,
-            )));
+            return Err(telemetry::rejected(
+                "prio3",
+                RejectionReason::LengthMismatch,
+                VdafError::Uncategorized(format!(
+                    "unexpected message count: got {}; want {}",
+                    count, self.num_aggregators,
+                )),
+            ));
         }
 
-        // Check the proof verifiers.
+        // Check the proof verifiers. Every proof is checked even if an earlier one fails, so that
+        // the time taken doesn't reveal which proof failed.
+        let mut valid = Choice::from(1);
         for verifier in verifiers.chunks(self.typ.verifier_len()) {
-            if !self.typ.decide(verifier)? {
-                return Err(VdafError::Uncategorized(
-                    "proof verifier check failed".into(),
-                ));
-            }
+            valid &= Choice::from(u8::from(self.typ.decide(verifier)?));
+        }
+        if !bool::from(valid) {
+            return Err(telemetry::rejected(
+                "prio3",
+                RejectionReason::InvalidProof,
+                VdafError::Uncategorized("proof verifier check failed".into()),
+            ));
         }
 
         let joint_rand_seed = if self.typ.joint_rand_len() > 0 {
@@ -1333,9 +1638,14 @@ where
             None
         };
 
+        telemetry::verified("prio3");
         Ok(Prio3PrepareMessage { joint_rand_seed })
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.typ.input_len()))
+    )]
     fn prepare_next(
         &self,
         step: Prio3PrepareState<T::Field, SEED_SIZE>,
@@ -1343,15 +1653,20 @@ where
     ) -> Result<PrepareTransition<Self, SEED_SIZE, 16>, VdafError> {
         if self.typ.joint_rand_len() > 0 {
             // Check that the joint randomness was correct.
-            if step
-                .joint_rand_seed
-                .as_ref()
-                .unwrap()
-                .ct_ne(msg.joint_rand_seed.as_ref().unwrap())
-                .into()
-            {
-                return Err(VdafError::Uncategorized(
-                    "joint randomness mismatch".to_string(),
+            let (Some(joint_rand_seed), Some(msg_joint_rand_seed)) =
+                (step.joint_rand_seed.as_ref(), msg.joint_rand_seed.as_ref())
+            else {
+                return Err(telemetry::rejected(
+                    "prio3",
+                    RejectionReason::PeerMismatch,
+                    VdafError::Uncategorized("missing joint randomness seed".to_string()),
+                ));
+            };
+            if joint_rand_seed.ct_ne(msg_joint_rand_seed).into() {
+                return Err(telemetry::rejected(
+                    "prio3",
+                    RejectionReason::PeerMismatch,
+                    VdafError::Uncategorized("joint randomness mismatch".to_string()),
                 ));
             }
         }
@@ -1376,16 +1691,23 @@ where
     }
 
     /// Aggregates a sequence of output shares into an aggregate share.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.typ.input_len()))
+    )]
     fn aggregate<It: IntoIterator<Item = OutputShare<T::Field>>>(
         &self,
         _agg_param: &(),
         output_shares: It,
     ) -> Result<AggregateShare<T::Field>, VdafError> {
         let mut agg_share = AggregateShare(vec![T::Field::zero(); self.typ.output_len()]);
+        let mut count = 0;
         for output_share in output_shares.into_iter() {
             agg_share.accumulate(&output_share)?;
+            count += 1;
         }
 
+        telemetry::aggregated("prio3", count);
         Ok(agg_share)
     }
 }
@@ -1417,6 +1739,10 @@ where
     P: Xof<SEED_SIZE>,
 {
     /// Combines aggregate shares into the aggregate result.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_len = self.typ.input_len()))
+    )]
     fn unshard<It: IntoIterator<Item = AggregateShare<T::Field>>>(
         &self,
         _agg_param: &Self::AggregationParam,
@@ -1582,6 +1908,85 @@ mod tests {
     use fixed_macro::fixed;
     use rand::prelude::*;
 
+    fn check_encoded_lens<T, P, const SEED_SIZE: usize>(
+        prio3: &Prio3<T, P, SEED_SIZE>,
+        measurement: &T::Measurement,
+    ) where
+        T: Type,
+        P: Xof<SEED_SIZE>,
+    {
+        let verify_key = [0; SEED_SIZE];
+        let nonce = [0; 16];
+        let (public_share, input_shares) = prio3.shard(measurement, &nonce).unwrap();
+        assert_eq!(
+            public_share.get_encoded().unwrap().len(),
+            prio3.public_share_len()
+        );
+
+        let mut states = Vec::new();
+        let mut prep_shares = Vec::new();
+        for (agg_id, input_share) in input_shares.iter().enumerate() {
+            assert_eq!(
+                input_share.get_encoded().unwrap().len(),
+                prio3.input_share_len(agg_id)
+            );
+            let (state, prep_share) = prio3
+                .prepare_init(&verify_key, agg_id, &(), &nonce, &public_share, input_share)
+                .unwrap();
+            assert_eq!(
+                prep_share.get_encoded().unwrap().len(),
+                prio3.prepare_share_len()
+            );
+            states.push(state);
+            prep_shares.push(prep_share);
+        }
+        let prep_msg = prio3
+            .prepare_shares_to_prepare_message(&(), prep_shares)
+            .unwrap();
+        assert_eq!(
+            prep_msg.get_encoded().unwrap().len(),
+            prio3.prepare_message_len()
+        );
+
+        let PrepareTransition::Finish(out_share) =
+            prio3.prepare_next(states.remove(0), prep_msg).unwrap()
+        else {
+            panic!("unexpected transition");
+        };
+        let agg_share = prio3.aggregate(&(), [out_share]).unwrap();
+        assert_eq!(
+            agg_share.get_encoded().unwrap().len(),
+            prio3.aggregate_share_len()
+        );
+    }
+
+    #[test]
+    fn test_prio3_encoded_lens() {
+        check_encoded_lens(&Prio3::new_count(2).unwrap(), &true);
+        check_encoded_lens(&Prio3::new_sum(3, 16).unwrap(), &1337);
+        check_encoded_lens(&Prio3::new_sum_vec(2, 2, 20, 4).unwrap(), &vec![1; 20]);
+        check_encoded_lens(&Prio3::new_histogram(4, 10, 3).unwrap(), &7);
+    }
+
+    #[test]
+    fn test_prio3_soundness() {
+        let count = Prio3::new_count(2).unwrap();
+        assert!(count.soundness_error().unwrap().log2() < -62.0);
+        count.check_soundness(-60.0).unwrap();
+        assert_matches!(count.check_soundness(-64.0), Err(VdafError::Flp(_)));
+
+        // A second proof squares the bound.
+        let two_proofs =
+            Prio3::<Count<Field64>, XofTurboShake128, 16>::new(2, 2, 0xFFFF0000, Count::new())
+                .unwrap();
+        let (one, two) = (
+            count.soundness_error().unwrap().log2(),
+            two_proofs.soundness_error().unwrap().log2(),
+        );
+        assert!((two - 2.0 * one).abs() < 1e-6);
+        two_proofs.check_soundness(-64.0).unwrap();
+    }
+
     #[test]
     fn test_prio3_count() {
         let prio3 = Prio3::new_count(2).unwrap();
@@ -1627,21 +2032,39 @@ mod tests {
         let (public_share, mut input_shares) = prio3.shard(&1, &nonce).unwrap();
         input_shares[0].joint_rand_blind.as_mut().unwrap().0[0] ^= 255;
         let result = run_vdaf_prepare(&prio3, &verify_key, &(), &nonce, public_share, input_shares);
-        assert_matches!(result, Err(VdafError::Uncategorized(_)));
+        assert_matches!(
+            result,
+            Err(VdafError::Rejected {
+                reason: RejectionReason::InvalidProof,
+                ..
+            })
+        );
 
         let (public_share, mut input_shares) = prio3.shard(&1, &nonce).unwrap();
         assert_matches!(input_shares[0].measurement_share, Share::Leader(ref mut data) => {
             data[0] += Field128::one();
         });
         let result = run_vdaf_prepare(&prio3, &verify_key, &(), &nonce, public_share, input_shares);
-        assert_matches!(result, Err(VdafError::Uncategorized(_)));
+        assert_matches!(
+            result,
+            Err(VdafError::Rejected {
+                reason: RejectionReason::InvalidProof,
+                ..
+            })
+        );
 
         let (public_share, mut input_shares) = prio3.shard(&1, &nonce).unwrap();
         assert_matches!(input_shares[0].proofs_share, Share::Leader(ref mut data) => {
                 data[0] += Field128::one();
         });
         let result = run_vdaf_prepare(&prio3, &verify_key, &(), &nonce, public_share, input_shares);
-        assert_matches!(result, Err(VdafError::Uncategorized(_)));
+        assert_matches!(
+            result,
+            Err(VdafError::Rejected {
+                reason: RejectionReason::InvalidProof,
+                ..
+            })
+        );
 
         test_serialization(&prio3, &1, &nonce).unwrap();
     }
@@ -1664,6 +2087,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_prio3_fixed_sum_vec() {
+        let prio3 = Prio3::new_fixed_sum_vec(2, 2, 4).unwrap();
+        assert_eq!(
+            run_vdaf(&prio3, &(), [[0, 1, 2, 3, 0], [3, 3, 0, 1, 0]]).unwrap(),
+            [3, 4, 2, 4, 0],
+        );
+
+        // Reports are interchangeable with those of a Prio3SumVec of the same length.
+        let sum_vec = Prio3::new_sum_vec(2, 2, 5, 4).unwrap();
+        let nonce = [0; 16];
+        let (public_share, input_shares) = prio3.shard(&[1, 0, 2, 0, 3], &nonce).unwrap();
+        let public_share = Prio3PublicShare::get_decoded_with_param(
+            &sum_vec,
+            &public_share.get_encoded().unwrap(),
+        )
+        .unwrap();
+        let input_shares = input_shares
+            .iter()
+            .enumerate()
+            .map(|(agg_id, input_share)| {
+                Prio3InputShare::get_decoded_with_param(
+                    &(&sum_vec, agg_id),
+                    &input_share.get_encoded().unwrap(),
+                )
+                .unwrap()
+            });
+        let out_shares =
+            run_vdaf_prepare(&sum_vec, &[1; 16], &(), &nonce, public_share, input_shares).unwrap();
+        let agg_shares = out_shares.into_iter().map(AggregateShare::from);
+        assert_eq!(
+            sum_vec.unshard(&(), agg_shares, 1).unwrap(),
+            vec![1, 0, 2, 0, 3]
+        );
+    }
+
     #[test]
     fn test_prio3_sum_vec_multiproof() {
         let prio3 = Prio3::<
@@ -1876,7 +2335,13 @@ mod tests {
             input_shares[0].joint_rand_blind.as_mut().unwrap().0[0] ^= 255;
             let result =
                 run_vdaf_prepare(&prio3, &verify_key, &(), &nonce, public_share, input_shares);
-            assert_matches!(result, Err(VdafError::Uncategorized(_)));
+            assert_matches!(
+                result,
+                Err(VdafError::Rejected {
+                    reason: RejectionReason::InvalidProof,
+                    ..
+                })
+            );
 
             let (public_share, mut input_shares) = prio3
                 .shard(&vec![fp_4_inv, fp_8_inv, fp_16_inv], &nonce)
@@ -1886,7 +2351,13 @@ mod tests {
             });
             let result =
                 run_vdaf_prepare(&prio3, &verify_key, &(), &nonce, public_share, input_shares);
-            assert_matches!(result, Err(VdafError::Uncategorized(_)));
+            assert_matches!(
+                result,
+                Err(VdafError::Rejected {
+                    reason: RejectionReason::InvalidProof,
+                    ..
+                })
+            );
 
             let (public_share, mut input_shares) = prio3
                 .shard(&vec![fp_4_inv, fp_8_inv, fp_16_inv], &nonce)
@@ -1896,7 +2367,13 @@ mod tests {
             });
             let result =
                 run_vdaf_prepare(&prio3, &verify_key, &(), &nonce, public_share, input_shares);
-            assert_matches!(result, Err(VdafError::Uncategorized(_)));
+            assert_matches!(
+                result,
+                Err(VdafError::Rejected {
+                    reason: RejectionReason::InvalidProof,
+                    ..
+                })
+            );
 
             test_serialization(&prio3, &vec![fp_4_inv, fp_8_inv, fp_16_inv], &nonce).unwrap();
         }
@@ -1917,6 +2394,262 @@ mod tests {
         test_serialization(&prio3, &3, &[0; 16]).unwrap();
     }
 
+    #[test]
+    fn test_prio3_multihot_count_vec() {
+        let prio3 = Prio3::new_multihot_count_vec(2, 4, 2, 2).unwrap();
+
+        assert_eq!(
+            run_vdaf(
+                &prio3,
+                &(),
+                [
+                    vec![true, false, true, false],
+                    vec![false, false, true, true],
+                    vec![false; 4],
+                ]
+            )
+            .unwrap(),
+            vec![1, 0, 2, 1]
+        );
+        assert!(prio3
+            .shard(&vec![true, true, true, false], &[0; 16])
+            .is_err());
+        test_serialization(&prio3, &vec![false, true, false, false], &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_sparse_sum_vec() {
+        let prio3 = Prio3::new_sparse_sum_vec(2, 4, 8, 2, 3).unwrap();
+
+        assert_eq!(
+            run_vdaf(
+                &prio3,
+                &(),
+                [vec![255, 0, 3, 0], vec![0, 0, 7, 1], vec![0; 4]]
+            )
+            .unwrap(),
+            vec![255, 0, 10, 1]
+        );
+        assert!(prio3.shard(&vec![1, 1, 1, 0], &[0; 16]).is_err());
+        test_serialization(&prio3, &vec![0, 100, 0, 0], &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_range_sum_vec() {
+        let prio3 = Prio3::new_range_sum_vec(2, 10, 14, 3).unwrap();
+
+        assert_eq!(
+            run_vdaf(&prio3, &(), [vec![10, 14, 12], vec![11, 11, 14]]).unwrap(),
+            vec![21, 25, 26]
+        );
+        assert!(prio3.shard(&vec![10, 15, 12], &[0; 16]).is_err());
+        test_serialization(&prio3, &vec![13, 10, 14], &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_and_count_vec() {
+        let prio3 = Prio3::new_and_count_vec(2, 2, vec![(0, 1)]).unwrap();
+
+        assert_eq!(
+            run_vdaf(
+                &prio3,
+                &(),
+                [
+                    vec![true, true],
+                    vec![true, false],
+                    vec![false, true],
+                    vec![true, true],
+                ]
+            )
+            .unwrap(),
+            vec![3, 3, 2]
+        );
+        test_serialization(&prio3, &vec![true, false], &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_distinct_count() {
+        let prio3 = Prio3::new_distinct_count(2, 4, 6, 8).unwrap();
+        let sketch = |hashes: &[u64]| prio3.typ.sketch(hashes.iter().copied());
+
+        // Each bucket of the combined sketch holds the largest rank of any measurement.
+        let estimate = run_vdaf(
+            &prio3,
+            &(),
+            [
+                sketch(&[1 << 58, 1 << 57]),
+                sketch(&[1 << 57, 1 << 60, 0xff << 56]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            estimate,
+            prio3
+                .typ
+                .estimate(&sketch(&[1 << 58, 1 << 57, 1 << 60, 0xff << 56]))
+        );
+        assert!((estimate - 4.0).abs() < 1.0, "{estimate}");
+        assert!(prio3.shard(&vec![7; 16], &[0; 16]).is_err());
+        test_serialization(&prio3, &sketch(&[1, 2, 3]), &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_pair() {
+        use crate::flp::types::composite::Pair;
+
+        // Five booleans and an 8-bit sum in a single report.
+        let typ = Pair::new(
+            Pair::new(
+                Pair::new(Count::<Field128>::new(), Count::new()),
+                Pair::new(Count::new(), Count::new()),
+            ),
+            Pair::new(Count::new(), Sum::new(8).unwrap()),
+        );
+        let prio3 = Prio3::<_, XofTurboShake128, 16>::new(2, 1, 0xFFFF0000, typ).unwrap();
+        let measurement =
+            |bits: [bool; 5], sum| (((bits[0], bits[1]), (bits[2], bits[3])), (bits[4], sum));
+
+        assert_eq!(
+            run_vdaf(
+                &prio3,
+                &(),
+                [
+                    measurement([true, false, true, false, true], 200),
+                    measurement([true, true, false, false, true], 55),
+                ]
+            )
+            .unwrap(),
+            (((2, 1), (1, 0)), (2, 255))
+        );
+        assert!(prio3
+            .shard(&measurement([false; 5], 256), &[0; 16])
+            .is_err());
+        test_serialization(&prio3, &measurement([true; 5], 1), &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_record() {
+        use crate::flp::types::composite::RecordBuilder;
+
+        let record = RecordBuilder::new()
+            .field("crashed", Count::<Field128>::new())
+            .unwrap()
+            .field("latency", Sum::new(8).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let prio3 = Prio3::<_, XofTurboShake128, 16>::new(2, 1, 0xFFFF0000, record).unwrap();
+        let measurement = |crashed, latency| {
+            prio3
+                .typ
+                .measurement()
+                .set::<Count<Field128>>("crashed", &crashed)
+                .unwrap()
+                .set::<Sum<Field128>>("latency", &latency)
+                .unwrap()
+                .finish()
+                .unwrap()
+        };
+
+        let aggregate = run_vdaf(
+            &prio3,
+            &(),
+            [
+                measurement(true, 100),
+                measurement(false, 20),
+                measurement(true, 3),
+            ],
+        )
+        .unwrap();
+        assert_eq!(aggregate.get::<Count<Field128>>("crashed").unwrap(), 2);
+        assert_eq!(aggregate.get::<Sum<Field128>>("latency").unwrap(), 123);
+        test_serialization(&prio3, &measurement(false, 255), &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_signed_sum() {
+        let prio3 = Prio3::new_signed_sum(3, 16).unwrap();
+
+        assert_eq!(
+            run_vdaf(&prio3, &(), [-1000, 250, -32768, 7]).unwrap(),
+            -33511
+        );
+        assert_eq!(run_vdaf(&prio3, &(), [32767, 32767]).unwrap(), 65534);
+        assert!(prio3.shard(&32768, &[0; 16]).is_err());
+        test_serialization(&prio3, &-1, &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_prio3_reports_cannot_be_mixed() {
+        // Query and joint randomness are derived from the nonce, so an Aggregator cannot combine
+        // shares of two reports, even if they encode the same measurement.
+        let prio3 = Prio3::new_histogram(2, 4, 2).unwrap();
+        let verify_key = [0; 16];
+        let (nonce_a, nonce_b) = ([0; 16], [1; 16]);
+        let (public_share_a, input_shares_a) = prio3.shard(&1, &nonce_a).unwrap();
+        let (public_share_b, input_shares_b) = prio3.shard(&1, &nonce_b).unwrap();
+
+        for (nonce_1, public_share_1) in [(&nonce_a, &public_share_a), (&nonce_b, &public_share_b)]
+        {
+            let (state_0, prep_share_0) = prio3
+                .prepare_init(
+                    &verify_key,
+                    0,
+                    &(),
+                    &nonce_a,
+                    &public_share_a,
+                    &input_shares_a[0],
+                )
+                .unwrap();
+            let (_, prep_share_1) = prio3
+                .prepare_init(
+                    &verify_key,
+                    1,
+                    &(),
+                    nonce_1,
+                    public_share_1,
+                    &input_shares_b[1],
+                )
+                .unwrap();
+            let result = prio3
+                .prepare_shares_to_prepare_message(&(), [prep_share_0, prep_share_1])
+                .and_then(|prep_msg| prio3.prepare_next(state_0, prep_msg));
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_prio3_missing_joint_rand() {
+        let prio3 = Prio3::new_histogram(2, 4, 2).unwrap();
+        let verify_key = [0; 16];
+        let nonce = [0; 16];
+        let (public_share, mut input_shares) = prio3.shard(&1, &nonce).unwrap();
+
+        let (_, prep_share_0) = prio3
+            .prepare_init(&verify_key, 0, &(), &nonce, &public_share, &input_shares[0])
+            .unwrap();
+        let (_, mut prep_share_1) = prio3
+            .prepare_init(&verify_key, 1, &(), &nonce, &public_share, &input_shares[1])
+            .unwrap();
+
+        // An input share without a joint randomness blind is rejected, not a panic.
+        input_shares[0].joint_rand_blind = None;
+        assert_matches!(
+            prio3.prepare_init(&verify_key, 0, &(), &nonce, &public_share, &input_shares[0]),
+            Err(VdafError::Uncategorized(_))
+        );
+
+        // Likewise a prepare share without a joint randomness part.
+        prep_share_1.joint_rand_part = None;
+        assert_matches!(
+            prio3.prepare_shares_to_prepare_message(&(), [prep_share_0, prep_share_1]),
+            Err(VdafError::Rejected {
+                reason: RejectionReason::LengthMismatch,
+                ..
+            })
+        );
+    }
+
     #[test]
     #[cfg(feature = "multithreaded")]
     fn test_prio3_histogram_multithreaded() {
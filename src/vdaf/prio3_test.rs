@@ -6,9 +6,9 @@ use crate::{
     codec::{Encode, ParameterizedDecode},
     flp::Type,
     vdaf::{
-        prio3::{Prio3, Prio3InputShare, Prio3PrepareShare, Prio3PublicShare},
+        prio3::{Prio3, Prio3InputShare, Prio3PrepareMessage, Prio3PrepareShare, Prio3PublicShare},
         xof::Xof,
-        Aggregator, Collector, OutputShare, PrepareTransition, Vdaf,
+        AggregateShare, Aggregator, Collector, OutputShare, PrepareTransition, Vdaf,
     },
 };
 use serde::{Deserialize, Serialize};
@@ -169,7 +169,7 @@ where
     for (test_num, p) in t.prep.iter().enumerate() {
         let output_shares = check_prep_test_vec(prio3, verify_key, test_num, p);
         for (aggregator_output_shares, output_share) in
-            all_output_shares.iter_mut().zip(output_shares.into_iter())
+            all_output_shares.iter_mut().zip(output_shares)
         {
             aggregator_output_shares.push(output_share);
         }
@@ -227,6 +227,130 @@ pub fn check_test_vec<M, A, T, P, const SEED_SIZE: usize>(
     check_test_vec_custom_de::<M, M, _, _, _, SEED_SIZE>(test_vec_json_str, new_vdaf)
 }
 
+/// Prepares one report of a test vector as Aggregator `agg_id` alone: everything this Aggregator
+/// would receive from the Client and the other Aggregators is decoded from the test vector, and
+/// everything it would send is checked against it.
+fn check_prep_test_vec_as_aggregator<M, T, P, const SEED_SIZE: usize>(
+    prio3: &Prio3<T, P, SEED_SIZE>,
+    verify_key: &[u8; SEED_SIZE],
+    agg_id: usize,
+    test_num: usize,
+    t: &TPrio3Prep<M>,
+) -> OutputShare<T::Field>
+where
+    T: Type,
+    P: Xof<SEED_SIZE>,
+{
+    let nonce = <[u8; 16]>::try_from(t.nonce.clone()).unwrap();
+    let public_share = Prio3PublicShare::get_decoded_with_param(prio3, t.public_share.as_ref())
+        .unwrap_or_else(|e| err!(test_num, e, "decode test vector (public share)"));
+    let input_share =
+        Prio3InputShare::get_decoded_with_param(&(prio3, agg_id), t.input_shares[agg_id].as_ref())
+            .unwrap_or_else(|e| err!(test_num, e, "decode test vector (input share)"));
+
+    let (state, prep_share) = prio3
+        .prepare_init(verify_key, agg_id, &(), &nonce, &public_share, &input_share)
+        .unwrap_or_else(|e| err!(test_num, e, "prep state init"));
+    assert_eq!(1, t.prep_shares.len(), "#{test_num}");
+    assert_eq!(
+        prep_share.get_encoded().unwrap(),
+        t.prep_shares[0][agg_id].as_ref(),
+        "#{test_num}"
+    );
+
+    let prep_shares = t.prep_shares[0]
+        .iter()
+        .enumerate()
+        .map(|(i, encoded)| {
+            if i == agg_id {
+                prep_share.clone()
+            } else {
+                Prio3PrepareShare::get_decoded_with_param(&state, encoded.as_ref())
+                    .unwrap_or_else(|e| err!(test_num, e, "decode test vector (prep share)"))
+            }
+        })
+        .collect::<Vec<_>>();
+    let prep_msg = prio3
+        .prepare_shares_to_prepare_message(&(), prep_shares)
+        .unwrap_or_else(|e| err!(test_num, e, "prep preprocess"));
+    assert_eq!(t.prep_messages.len(), 1);
+    assert_eq!(
+        prep_msg.get_encoded().unwrap(),
+        t.prep_messages[0].as_ref(),
+        "#{test_num}"
+    );
+
+    // An Aggregator that does not combine the prep shares receives the prep message instead.
+    let prep_msg = Prio3PrepareMessage::get_decoded_with_param(&state, t.prep_messages[0].as_ref())
+        .unwrap_or_else(|e| err!(test_num, e, "decode test vector (prep message)"));
+    let out_share = match prio3.prepare_next(state, prep_msg).unwrap() {
+        PrepareTransition::Finish(out_share) => out_share,
+        _ => panic!("unexpected transition"),
+    };
+    let got: Vec<Vec<u8>> = out_share
+        .as_ref()
+        .iter()
+        .map(|x| x.get_encoded().unwrap())
+        .collect();
+    let want: Vec<&[u8]> = t.out_shares[agg_id].iter().map(AsRef::as_ref).collect();
+    assert_eq!(got, want, "#{test_num}");
+    out_share
+}
+
+/// Evaluate a Prio3 test vector from the point of view of Aggregator `agg_id` alone, as when this
+/// crate runs one Aggregator of a deployment whose other Aggregators and Clients are other
+/// implementations of the same draft. The Aggregator's inputs are decoded from the encoded messages
+/// in the test vector rather than computed locally, and its outputs, as well as the unsharded
+/// aggregate result, are checked against the test vector.
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub fn check_test_vec_as_aggregator<A, T, P, const SEED_SIZE: usize>(
+    test_vec_json_str: &str,
+    agg_id: usize,
+    new_vdaf: impl Fn(&HashMap<String, serde_json::Value>, u8) -> Prio3<T, P, SEED_SIZE>,
+) where
+    A: for<'de> Deserialize<'de> + Debug + Eq,
+    T: Type<AggregateResult = A>,
+    P: Xof<SEED_SIZE>,
+{
+    let t: TPrio3<serde_json::Value> = serde_json::from_str(test_vec_json_str).unwrap();
+    let vdaf = new_vdaf(&t.other_params, t.shares);
+    let verify_key = t.verify_key.as_ref().try_into().unwrap();
+    assert!(agg_id < usize::from(t.shares));
+
+    let out_shares = t
+        .prep
+        .iter()
+        .enumerate()
+        .map(|(test_num, p)| {
+            check_prep_test_vec_as_aggregator(&vdaf, verify_key, agg_id, test_num, p)
+        })
+        .collect::<Vec<_>>();
+    let num_measurements = out_shares.len();
+    let agg_share = vdaf.aggregate(&(), out_shares).unwrap();
+    assert_eq!(
+        agg_share.get_encoded().unwrap(),
+        t.agg_shares[agg_id].as_ref()
+    );
+
+    let agg_shares = t
+        .agg_shares
+        .iter()
+        .enumerate()
+        .map(|(i, encoded)| {
+            if i == agg_id {
+                agg_share.clone()
+            } else {
+                AggregateShare::get_decoded_with_param(&(&vdaf, &()), encoded.as_ref()).unwrap()
+            }
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vdaf.unshard(&(), agg_shares, num_measurements).unwrap(),
+        serde_json::from_value::<A>(t.agg_result).unwrap()
+    );
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(transparent)]
 struct Prio3CountMeasurement(u8);
@@ -248,7 +372,10 @@ mod tests {
         vdaf::{prio3::Prio3, xof::XofTurboShake128},
     };
 
-    use super::{check_test_vec, check_test_vec_custom_de, Prio3CountMeasurement};
+    use super::{
+        check_test_vec, check_test_vec_as_aggregator, check_test_vec_custom_de,
+        Prio3CountMeasurement,
+    };
 
     #[test]
     fn test_vec_prio3_count() {
@@ -327,4 +454,43 @@ mod tests {
             });
         }
     }
+
+    #[test]
+    fn test_vec_prio3_as_aggregator() {
+        for test_vector_str in [
+            include_str!("test_vec/08/Prio3Count_0.json"),
+            include_str!("test_vec/08/Prio3Count_1.json"),
+        ] {
+            for agg_id in 0..2 {
+                check_test_vec_as_aggregator(
+                    test_vector_str,
+                    agg_id,
+                    |_json_params, num_shares| Prio3::new_count(num_shares).unwrap(),
+                );
+            }
+        }
+        for test_vector_str in [
+            include_str!("test_vec/08/Prio3Sum_0.json"),
+            include_str!("test_vec/08/Prio3Sum_1.json"),
+        ] {
+            for agg_id in 0..2 {
+                check_test_vec_as_aggregator(test_vector_str, agg_id, |json_params, num_shares| {
+                    let bits = json_params["bits"].as_u64().unwrap() as usize;
+                    Prio3::new_sum(num_shares, bits).unwrap()
+                });
+            }
+        }
+        for test_vector_str in [
+            include_str!("test_vec/08/Prio3Histogram_0.json"),
+            include_str!("test_vec/08/Prio3Histogram_1.json"),
+        ] {
+            for agg_id in 0..2 {
+                check_test_vec_as_aggregator(test_vector_str, agg_id, |json_params, num_shares| {
+                    let length = json_params["length"].as_u64().unwrap() as usize;
+                    let chunk_length = json_params["chunk_length"].as_u64().unwrap() as usize;
+                    Prio3::new_histogram(num_shares, length, chunk_length).unwrap()
+                });
+            }
+        }
+    }
 }
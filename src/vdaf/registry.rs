@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A registry of measurement types.
+//!
+//! Aggregators and Clients in a fleet built from different versions of this crate, or from
+//! different implementations of the same VDAFs, must agree on what a measurement type means before
+//! they exchange shares of it. [`MeasurementTypeRegistry`] gives each type a stable name, such as
+//! `"histogram"`, and a stable numeric code, which is encoded on the wire as a variable-length
+//! integer (LEB128), so that the common types take a single byte. The types built into this crate
+//! have codes below [`FIRST_CUSTOM_CODE`]; an application registers its own types, such as custom
+//! validity circuits, with codes from there on.
+//!
+//! The code of a [`MeasurementType`] is the first field of the encoding of a
+//! [`TaskConfig`](crate::vdaf::task::TaskConfig), and a wire header can carry one with
+//! [`MeasurementTypeRegistry::encode_code`]. Decoding with
+//! [`MeasurementTypeRegistry::decode_code`] rejects codes that are not registered.
+//!
+//! ```
+//! use prio::vdaf::registry::{MeasurementTypeRegistry, FIRST_CUSTOM_CODE};
+//!
+//! let mut registry = MeasurementTypeRegistry::with_builtins();
+//! registry.register("example.com/hll", FIRST_CUSTOM_CODE).unwrap();
+//! assert_eq!(registry.code("histogram"), Some(3));
+//! assert_eq!(registry.name(FIRST_CUSTOM_CODE), Some("example.com/hll"));
+//! ```
+
+use crate::{
+    codec::CodecError,
+    vdaf::{task::MeasurementType, VdafError},
+};
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read},
+};
+
+/// The lowest code measurement types registered by applications may use.
+pub const FIRST_CUSTOM_CODE: u64 = 256;
+
+/// The names and codes of the measurement types built into this crate, one for each variant of
+/// [`MeasurementType`] but [`MeasurementType::Custom`].
+const BUILTIN_TYPES: &[(&str, u64)] = &[
+    ("count", 0),
+    ("sum", 1),
+    ("sumvec", 2),
+    ("histogram", 3),
+    ("fixedpoint-boundedl2-vec-sum", 4),
+    ("average", 5),
+    ("multihot-count-vec", 6),
+    ("range-sum-vec", 7),
+    ("and-count-vec", 8),
+    ("distinct-count", 9),
+    ("signed-sum", 10),
+];
+
+/// A two-way mapping between the names and codes of measurement types. See the
+/// [module documentation](self) for details.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeasurementTypeRegistry {
+    by_name: BTreeMap<String, u64>,
+    by_code: BTreeMap<u64, String>,
+}
+
+impl MeasurementTypeRegistry {
+    /// Returns an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a registry of the measurement types built into this crate.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        for (name, code) in BUILTIN_TYPES {
+            registry.insert(name, *code);
+        }
+        registry
+    }
+
+    /// Registers a measurement type. Names may contain lowercase ASCII letters, digits, and the
+    /// characters `-`, `.` and `/`, so that they can be namespaced by domain. Returns an error if
+    /// the name is invalid, if the code is below [`FIRST_CUSTOM_CODE`], or if the name or code is
+    /// already registered.
+    pub fn register(&mut self, name: &str, code: u64) -> Result<(), VdafError> {
+        if name.is_empty()
+            || !name
+                .bytes()
+                .all(|c| matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'/'))
+        {
+            return Err(VdafError::Uncategorized(format!(
+                "invalid measurement type name {name:?}"
+            )));
+        }
+        if code < FIRST_CUSTOM_CODE {
+            return Err(VdafError::Uncategorized(format!(
+                "measurement type code {code} is reserved"
+            )));
+        }
+        if self.by_name.contains_key(name) || self.by_code.contains_key(&code) {
+            return Err(VdafError::Uncategorized(format!(
+                "measurement type {name:?} or code {code} is already registered"
+            )));
+        }
+        self.insert(name, code);
+        Ok(())
+    }
+
+    /// Returns the code of the type named `name`, if it is registered.
+    pub fn code(&self, name: &str) -> Option<u64> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Returns the name of the type with code `code`, if it is registered.
+    pub fn name(&self, code: u64) -> Option<&str> {
+        self.by_code.get(&code).map(String::as_str)
+    }
+
+    /// Returns the registered names and codes, in order of code.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> + '_ {
+        self.by_code
+            .iter()
+            .map(|(code, name)| (name.as_str(), *code))
+    }
+
+    /// Returns the name of `measurement_type`, if its code is registered.
+    pub fn name_of(&self, measurement_type: &MeasurementType) -> Option<&str> {
+        self.name(measurement_type.code())
+    }
+
+    /// Appends the code of the type named `name` to `bytes`. Returns an error if the name is not
+    /// registered.
+    pub fn encode_code(&self, name: &str, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        let code = self.code(name).ok_or(CodecError::UnexpectedValue)?;
+        encode_varint(code, bytes);
+        Ok(())
+    }
+
+    /// Reads a code from `bytes` and returns the name of its type. Returns an error if the code is
+    /// not registered.
+    pub fn decode_code(&self, bytes: &mut Cursor<&[u8]>) -> Result<&str, CodecError> {
+        self.name(decode_varint(bytes)?)
+            .ok_or(CodecError::UnexpectedValue)
+    }
+
+    fn insert(&mut self, name: &str, code: u64) {
+        self.by_name.insert(name.into(), code);
+        self.by_code.insert(code, name.into());
+    }
+}
+
+/// Appends the LEB128 encoding of `value` to `bytes`.
+pub(crate) fn encode_varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Returns the length of the LEB128 encoding of `value`.
+pub(crate) fn varint_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize + 6) / 7
+}
+
+/// Reads a LEB128-encoded integer. Encodings that are longer than necessary, or that overflow a
+/// `u64`, are rejected, so that every value has a single encoding.
+pub(crate) fn decode_varint(bytes: &mut Cursor<&[u8]>) -> Result<u64, CodecError> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0];
+        bytes.read_exact(&mut byte)?;
+        let byte = byte[0];
+        let bits = u64::from(byte & 0x7f);
+        if i == 9 && bits > 1 {
+            return Err(CodecError::UnexpectedValue);
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            if i > 0 && byte == 0 {
+                return Err(CodecError::UnexpectedValue);
+            }
+            return Ok(value);
+        }
+    }
+    Err(CodecError::UnexpectedValue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        let mut registry = MeasurementTypeRegistry::with_builtins();
+        assert_eq!(registry.code("count"), Some(0));
+        assert_eq!(
+            registry.name_of(&MeasurementType::Histogram { chunk_length: 4 }),
+            Some("histogram")
+        );
+        assert_eq!(registry.code("hll"), None);
+
+        registry.register("example.com/hll", 300).unwrap();
+        assert_eq!(registry.name(300), Some("example.com/hll"));
+        assert_eq!(
+            registry.name_of(&MeasurementType::Custom { code: 300 }),
+            Some("example.com/hll")
+        );
+        assert!(registry.register("example.com/hll", 301).is_err());
+        assert!(registry.register("other", 300).is_err());
+        assert!(registry.register("other", 5).is_err());
+        assert!(registry.register("Other", 301).is_err());
+        assert!(registry.register("", 301).is_err());
+        assert_eq!(registry.iter().count(), BUILTIN_TYPES.len() + 1);
+
+        let mut bytes = Vec::new();
+        registry.encode_code("histogram", &mut bytes).unwrap();
+        registry.encode_code("example.com/hll", &mut bytes).unwrap();
+        assert_eq!(bytes, [3, 0xac, 0x02]);
+        let mut cursor = Cursor::new(bytes.as_slice());
+        assert_eq!(registry.decode_code(&mut cursor).unwrap(), "histogram");
+        assert_eq!(
+            registry.decode_code(&mut cursor).unwrap(),
+            "example.com/hll"
+        );
+        assert!(registry.encode_code("unknown", &mut bytes).is_err());
+        assert!(MeasurementTypeRegistry::new()
+            .decode_code(&mut Cursor::new(&[3][..]))
+            .is_err());
+    }
+
+    #[test]
+    fn varint() {
+        for value in [0, 1, 127, 128, 255, 300, 1 << 32, u64::MAX - 1, u64::MAX] {
+            let mut bytes = Vec::new();
+            encode_varint(value, &mut bytes);
+            assert_eq!(bytes.len(), varint_len(value));
+            let mut cursor = Cursor::new(bytes.as_slice());
+            assert_eq!(decode_varint(&mut cursor).unwrap(), value);
+            assert_eq!(cursor.position() as usize, bytes.len());
+        }
+
+        // Non-minimal, overflowing and truncated encodings are rejected.
+        for bad in [
+            &[0x80, 0x00][..],
+            &[0xff; 10][..],
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02][..],
+            &[0x80][..],
+        ] {
+            assert!(decode_varint(&mut Cursor::new(bad)).is_err(), "{bad:?}");
+        }
+    }
+}
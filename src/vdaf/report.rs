@@ -0,0 +1,1533 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reports and report shares.
+//!
+//! A [`Report`] is everything a Client uploads for one measurement: an identifier, which doubles as
+//! the VDAF nonce, a timestamp, the public share, and one input share per Aggregator. Each
+//! Aggregator receives a [`ReportShare`], which carries its own input share along with the
+//! Aggregator ID it belongs to. The ID is part of the encoding and is used both to decode the input
+//! share and to prepare it, so a leader share cannot be prepared as a helper share or vice versa.
+//!
+//! A report may carry [`Extension`]s, such as the Client's software version or a coarse location
+//! used to route the report. Extensions are visible to every Aggregator and are not secret-shared.
+//! Input shares are not encrypted here; an application that sends report shares over an untrusted
+//! channel must encrypt each one to its Aggregator, and should use [`ReportShare::aad`] as the
+//! associated data so that the extensions and the rest of the report metadata are authenticated.
+//!
+//! Aggregators usually accept reports only within a window of time, both to bound the batches a
+//! report can land in and to bound how long report IDs must be remembered to detect replays.
+//! [`ReportShare::prepare_init_checked`] enforces a [`TimestampPolicy`] before preparing a share,
+//! and rejects reports that are too old or too far in the future with distinct [`TimestampError`]s.
+//!
+//! Reports with the 16-byte nonces of the VDAFs in this crate also expose their ID as a
+//! [`ReportId`], which is what an Aggregator should key its replay store and logs by.
+//!
+//! Decoding a report allocates its shares, so an Aggregator flooded with junk should filter
+//! requests first with [`Report::validate_header`] or [`ReportShare::validate_header`]. These check
+//! the framing of the encoding in place, without allocating or doing any cryptography, and return
+//! the [`ReportHeader`] so the timestamp and the [`ProtocolVersion`] can be checked too. The
+//! encoding has no key ID of its own; an application that encrypts input shares should check the
+//! key ID in its own envelope before calling `validate_header`.
+//!
+//! [`ReportBuilder::build_with_keys`] also picks the key to encrypt each input share to, from the
+//! [`PublicKeyConfig`] each Aggregator publishes.
+//!
+//! A Client that may have to retry an upload can build its reports with
+//! [`ReportBuilder::build_idempotent`], which derives all of the report's randomness from a
+//! Client secret and the report ID, so that a retransmitted report is byte-identical to the
+//! original and the Aggregators can deduplicate it by ID.
+//!
+//! A report can be labeled with the arm of an A/B experiment it belongs to, with
+//! [`ReportBuilder::experiment_arm`]. The label is an extension, so it is public but authenticated
+//! along with the rest of the metadata; an Aggregator reads it with [`ReportShare::experiment_arm`]
+//! and sums the output shares of each arm separately, e.g. with an
+//! [`ArmAccumulator`](crate::vdaf::accumulator::ArmAccumulator), so that one task yields a private
+//! aggregate per arm.
+//!
+//! An Aggregator that verifies and aggregates a report in separate requests can keep the decrypted
+//! report share in between in a [`cache::ReportShareCache`].
+//!
+//! A vector measurement that is too wide to prove in one report can be split across several tasks
+//! with [`ReportBuilder::build_chunked`], and the aggregate results of those tasks reassembled with
+//! [`reassemble_chunks`].
+
+use crate::{
+    codec::{
+        decode_u16_items, decode_u32_items, encode_u16_items, encode_u32_items, CodecError, Decode,
+        Encode, ParameterizedDecode,
+    },
+    vdaf::{
+        id::ReportId,
+        key_config::{KeyAlgorithm, PublicKey, PublicKeyConfig},
+        version::{ProtocolVersion, SupportedVersions, VersionError},
+        Aggregator, Client, ClientWithRng, Vdaf, VdafError, VERSION,
+    },
+};
+use rand::prelude::*;
+use rand_core::impls::{next_u32_via_fill, next_u64_via_fill};
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256, Shake256Reader,
+};
+use std::{
+    fmt::{self, Debug},
+    io::{Cursor, Read},
+};
+
+pub mod cache;
+
+/// Reasons a report is rejected by a [`TimestampPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum TimestampError {
+    /// The report is older than the policy allows.
+    #[error("report timestamp {timestamp} is before the earliest accepted time {earliest}")]
+    TooOld {
+        /// The timestamp of the report.
+        timestamp: u64,
+        /// The earliest timestamp accepted.
+        earliest: u64,
+    },
+
+    /// The report is further in the future than the allowed clock skew.
+    #[error("report timestamp {timestamp} is after the latest accepted time {latest}")]
+    TooFarInFuture {
+        /// The timestamp of the report.
+        timestamp: u64,
+        /// The latest timestamp accepted.
+        latest: u64,
+    },
+}
+
+/// Bounds on the timestamps of reports an Aggregator accepts, relative to its current time. All
+/// times are in seconds, in the same units as the report timestamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimestampPolicy {
+    /// The maximum age of a report, e.g. the length of the window in which replays are detected.
+    /// `None` accepts reports of any age.
+    pub max_age: Option<u64>,
+
+    /// How far in the future a report's timestamp may be, to tolerate clock skew between Clients
+    /// and the Aggregator.
+    pub max_skew: u64,
+}
+
+impl TimestampPolicy {
+    /// Checks `timestamp` against the policy at time `now`.
+    pub fn check(&self, timestamp: u64, now: u64) -> Result<(), TimestampError> {
+        if let Some(max_age) = self.max_age {
+            let earliest = now.saturating_sub(max_age);
+            if timestamp < earliest {
+                return Err(TimestampError::TooOld {
+                    timestamp,
+                    earliest,
+                });
+            }
+        }
+        let latest = now.saturating_add(self.max_skew);
+        if timestamp > latest {
+            return Err(TimestampError::TooFarInFuture { timestamp, latest });
+        }
+        Ok(())
+    }
+}
+
+/// The type of the extension that carries the experiment arm of a report. See
+/// [`ReportBuilder::experiment_arm`].
+pub const EXPERIMENT_ARM_EXTENSION_TYPE: u16 = 0xff00;
+
+/// The maximum length in bytes of an experiment arm label.
+pub const MAX_EXPERIMENT_ARM_LEN: usize = 255;
+
+/// Metadata attached to a report. A report has at most one extension of each type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extension {
+    extension_type: u16,
+    data: Vec<u8>,
+}
+
+impl Extension {
+    /// Creates an extension. The meaning of `extension_type` is up to the application.
+    pub fn new(extension_type: u16, data: Vec<u8>) -> Self {
+        Self {
+            extension_type,
+            data,
+        }
+    }
+
+    /// Returns the type of the extension.
+    pub fn extension_type(&self) -> u16 {
+        self.extension_type
+    }
+
+    /// Returns the contents of the extension.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Encode for Extension {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.extension_type.encode(bytes)?;
+        encode_u16_items(bytes, &(), &self.data)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(4 + self.data.len())
+    }
+}
+
+impl Decode for Extension {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            extension_type: u16::decode(bytes)?,
+            data: decode_u16_items(&(), bytes)?,
+        })
+    }
+}
+
+/// Builds a [`Report`] with optional timestamp and extensions.
+#[derive(Clone, Debug)]
+pub struct ReportBuilder<'a, V> {
+    vdaf: &'a V,
+    timestamp: u64,
+    version: ProtocolVersion,
+    extensions: Vec<Extension>,
+}
+
+impl<'a, V> ReportBuilder<'a, V> {
+    /// Creates a builder for reports of `vdaf`, with timestamp 0, the current protocol version
+    /// and no extensions.
+    pub fn new(vdaf: &'a V) -> Self {
+        Self {
+            vdaf,
+            timestamp: 0,
+            version: ProtocolVersion::CURRENT,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Sets the protocol version of the report, e.g. the version the Client negotiated with the
+    /// Aggregators or found in the task config.
+    pub fn version(mut self, version: ProtocolVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the time at which the report was generated.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Attaches an extension to the report.
+    pub fn extension(mut self, extension: Extension) -> Self {
+        self.extensions.push(extension);
+        self
+    }
+
+    /// Labels the report with the arm of an experiment, such as `"control"` or `"treatment"`, by
+    /// attaching an extension of type [`EXPERIMENT_ARM_EXTENSION_TYPE`]. Building the report fails
+    /// if the label is empty or longer than [`MAX_EXPERIMENT_ARM_LEN`] bytes.
+    ///
+    /// The label is visible to every Aggregator, so it must not depend on the measurement.
+    pub fn experiment_arm(self, label: &str) -> Self {
+        self.extension(Extension::new(
+            EXPERIMENT_ARM_EXTENSION_TYPE,
+            label.as_bytes().to_vec(),
+        ))
+    }
+
+    /// Shards `measurement` into a new report with a random ID. Fails if two extensions have the
+    /// same type.
+    pub fn build<const NONCE_SIZE: usize>(
+        self,
+        measurement: &V::Measurement,
+    ) -> Result<Report<V, NONCE_SIZE>, VdafError>
+    where
+        V: Client<NONCE_SIZE>,
+    {
+        check_extensions(&self.extensions)?;
+        let mut id = [0; NONCE_SIZE];
+        thread_rng().fill(&mut id[..]);
+        let (public_share, input_shares) = self.vdaf.shard(measurement, &id)?;
+        Ok(Report {
+            id,
+            timestamp: self.timestamp,
+            version: self.version,
+            extensions: self.extensions,
+            public_share,
+            input_shares,
+        })
+    }
+
+    /// Shards `measurement` into a report whose ID is `report_id` and whose randomness is derived
+    /// from `master_secret` and the report metadata (the ID, timestamp, protocol version and
+    /// extensions), so that building the same report again yields the same bytes. A Client that
+    /// retries an upload can rebuild the report instead of storing it, and the Aggregators' replay
+    /// checks treat the retransmission as the same report rather than as a second measurement.
+    ///
+    /// `master_secret` must be uniformly random and known only to the Client. A report ID must
+    /// never be reused for a different measurement: two reports with the same secret and metadata
+    /// have the same shares, so their difference would leak the difference of the measurements.
+    /// Fails if two extensions have the same type.
+    pub fn build_idempotent<const NONCE_SIZE: usize>(
+        self,
+        measurement: &V::Measurement,
+        master_secret: &[u8; 32],
+        report_id: &[u8; NONCE_SIZE],
+    ) -> Result<Report<V, NONCE_SIZE>, VdafError>
+    where
+        V: ClientWithRng<NONCE_SIZE>,
+    {
+        check_extensions(&self.extensions)?;
+        let mut extensions = Vec::new();
+        encode_u16_items(&mut extensions, &(), &self.extensions)?;
+        let mut rng = IdempotentRng::new(
+            master_secret,
+            self.version,
+            report_id,
+            self.timestamp,
+            &extensions,
+        );
+        let (public_share, input_shares) =
+            self.vdaf.shard_with_rng(measurement, report_id, &mut rng)?;
+        Ok(Report {
+            id: *report_id,
+            timestamp: self.timestamp,
+            version: self.version,
+            extensions: self.extensions,
+            public_share,
+            input_shares,
+        })
+    }
+
+    /// Shards `measurement` like [`Self::build`], and picks the key to encrypt each input share to:
+    /// for each Aggregator, the key chosen by [`PublicKeyConfig::select`] from its config at the
+    /// report's timestamp. `key_configs` holds one config per Aggregator, in order of Aggregator
+    /// ID. Fails if there are not as many configs as Aggregators, or if an Aggregator has no key
+    /// of a `supported` algorithm that is valid at the report's timestamp.
+    pub fn build_with_keys<const NONCE_SIZE: usize>(
+        self,
+        measurement: &V::Measurement,
+        key_configs: &[PublicKeyConfig],
+        supported: &[KeyAlgorithm],
+    ) -> Result<(Report<V, NONCE_SIZE>, Vec<PublicKey>), VdafError>
+    where
+        V: Client<NONCE_SIZE>,
+    {
+        if key_configs.len() != self.vdaf.num_aggregators() {
+            return Err(VdafError::Uncategorized(format!(
+                "got {} public key configs for {} aggregators",
+                key_configs.len(),
+                self.vdaf.num_aggregators()
+            )));
+        }
+        let keys = key_configs
+            .iter()
+            .enumerate()
+            .map(|(agg_id, config)| {
+                config
+                    .select(self.timestamp, supported)
+                    .cloned()
+                    .ok_or_else(|| {
+                        VdafError::Uncategorized(format!(
+                            "aggregator {agg_id} has no usable public key at time {}",
+                            self.timestamp
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((self.build(measurement)?, keys))
+    }
+
+    /// Splits a vector measurement that is too wide for a single report into chunks of
+    /// `chunk_len` entries, and shards each chunk into its own report. `vdaf` must take
+    /// measurements of length `chunk_len`; the last chunk is padded with zeros (i.e.,
+    /// `T::default()`).
+    ///
+    /// Chunk `i` is meant to be uploaded to the `i`th of a sequence of tasks, whose aggregate
+    /// results are put back together with [`reassemble_chunks`]. Every chunk has the same ID,
+    /// timestamp and extensions, so that the parts of a measurement can be matched up across
+    /// tasks. Fails if `measurement` is empty, if `chunk_len` is zero, or if two extensions have
+    /// the same type.
+    pub fn build_chunked<T, const NONCE_SIZE: usize>(
+        self,
+        measurement: &[T],
+        chunk_len: usize,
+    ) -> Result<Vec<Report<V, NONCE_SIZE>>, VdafError>
+    where
+        V: Client<NONCE_SIZE, Measurement = Vec<T>>,
+        T: Clone + Default,
+    {
+        if measurement.is_empty() || chunk_len == 0 {
+            return Err(VdafError::Uncategorized(
+                "measurement and chunk length must be non-zero".into(),
+            ));
+        }
+        check_extensions(&self.extensions)?;
+        let mut id = [0; NONCE_SIZE];
+        thread_rng().fill(&mut id[..]);
+        measurement
+            .chunks(chunk_len)
+            .map(|chunk| {
+                let mut chunk = chunk.to_vec();
+                chunk.resize(chunk_len, T::default());
+                let (public_share, input_shares) = self.vdaf.shard(&chunk, &id)?;
+                Ok(Report {
+                    id,
+                    timestamp: self.timestamp,
+                    version: self.version,
+                    extensions: self.extensions.clone(),
+                    public_share,
+                    input_shares,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Domain separation tag for the randomness of [`ReportBuilder::build_idempotent`].
+const IDEMPOTENT_REPORT_DST: &[u8] = b"prio idempotent report";
+
+/// The RNG used by [`ReportBuilder::build_idempotent`]: the output of SHAKE256 on the domain
+/// separation tag, the master secret and the report metadata. The tag is prefixed with the VDAF
+/// draft version and the report's protocol version, so that a change to either derives unrelated
+/// randomness from the same secret.
+struct IdempotentRng(Shake256Reader);
+
+impl IdempotentRng {
+    fn new(
+        master_secret: &[u8; 32],
+        version: ProtocolVersion,
+        report_id: &[u8],
+        timestamp: u64,
+        extensions: &[u8],
+    ) -> Self {
+        let mut shake = Shake256::default();
+        shake.update(&[VERSION]);
+        shake.update(&version.get().to_be_bytes());
+        shake.update(&[IDEMPOTENT_REPORT_DST.len() as u8]);
+        shake.update(IDEMPOTENT_REPORT_DST);
+        shake.update(master_secret);
+        shake.update(&(report_id.len() as u64).to_be_bytes());
+        shake.update(report_id);
+        shake.update(&timestamp.to_be_bytes());
+        shake.update(extensions);
+        Self(shake.finalize_xof())
+    }
+}
+
+impl RngCore for IdempotentRng {
+    fn next_u32(&mut self) -> u32 {
+        next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        XofReader::read(&mut self.0, dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for IdempotentRng {}
+
+/// Concatenates the aggregate results of the tasks that measurements were split across by
+/// [`ReportBuilder::build_chunked`], in order, and removes the padding, leaving the first `len`
+/// entries. Fails if the chunks are too short in total.
+pub fn reassemble_chunks<T>(
+    chunks: impl IntoIterator<Item = Vec<T>>,
+    len: usize,
+) -> Result<Vec<T>, VdafError> {
+    let mut result = Vec::with_capacity(len);
+    for chunk in chunks {
+        result.extend(chunk);
+    }
+    if result.len() < len {
+        return Err(VdafError::Uncategorized(format!(
+            "chunks have {} entries in total, expected at least {len}",
+            result.len()
+        )));
+    }
+    result.truncate(len);
+    Ok(result)
+}
+
+/// A Client's report. See the [module documentation](self) for details.
+pub struct Report<V: Vdaf, const NONCE_SIZE: usize> {
+    id: [u8; NONCE_SIZE],
+    timestamp: u64,
+    version: ProtocolVersion,
+    extensions: Vec<Extension>,
+    public_share: V::PublicShare,
+    input_shares: Vec<V::InputShare>,
+}
+
+/// The report ID, timestamp and protocol version of an encoded report, read by
+/// [`Report::validate_header`] or [`ReportShare::validate_header`] without decoding the shares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReportHeader<const NONCE_SIZE: usize> {
+    id: [u8; NONCE_SIZE],
+    timestamp: u64,
+    version: ProtocolVersion,
+}
+
+impl<const NONCE_SIZE: usize> ReportHeader<NONCE_SIZE> {
+    /// Returns the report ID.
+    pub fn id(&self) -> &[u8; NONCE_SIZE] {
+        &self.id
+    }
+
+    /// Returns the time at which the report was generated.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the protocol version of the report.
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Returns an error unless the protocol version of the report is `supported`.
+    pub fn check_version(&self, supported: &SupportedVersions) -> Result<(), VersionError> {
+        supported.check(self.version)
+    }
+}
+
+impl ReportHeader<16> {
+    /// Returns the report ID as a [`ReportId`].
+    pub fn report_id(&self) -> ReportId {
+        ReportId::from(self.id)
+    }
+}
+
+impl<V: Client<NONCE_SIZE>, const NONCE_SIZE: usize> Report<V, NONCE_SIZE> {
+    /// Shards `measurement` into a new report with a random ID and no extensions. Use
+    /// [`ReportBuilder`] to attach extensions.
+    pub fn shard(
+        vdaf: &V,
+        measurement: &V::Measurement,
+        timestamp: u64,
+    ) -> Result<Self, VdafError> {
+        ReportBuilder::new(vdaf)
+            .timestamp(timestamp)
+            .build(measurement)
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> Report<V, NONCE_SIZE> {
+    /// Checks that `encoded` is framed like a report for `vdaf`: that every length prefix fits,
+    /// that the extensions are well formed with no duplicate types, that there is one input share
+    /// per Aggregator, and that nothing follows them. Neither allocates nor decodes the shares,
+    /// which may still fail to decode.
+    pub fn validate_header(
+        vdaf: &V,
+        encoded: &[u8],
+    ) -> Result<ReportHeader<NONCE_SIZE>, CodecError> {
+        validate_framing(encoded, vdaf.num_aggregators(), None)
+    }
+
+    /// Returns the report ID. This is the nonce passed to the VDAF.
+    pub fn id(&self) -> &[u8; NONCE_SIZE] {
+        &self.id
+    }
+
+    /// Returns the time at which the report was generated.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the protocol version the report was produced with.
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Returns the extensions attached to the report.
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Returns the experiment arm label of the report, if it has one. See
+    /// [`ReportBuilder::experiment_arm`].
+    pub fn experiment_arm(&self) -> Option<&str> {
+        // The label was checked when the report was built or decoded.
+        experiment_arm(&self.extensions).ok().flatten()
+    }
+
+    /// Returns the public share.
+    pub fn public_share(&self) -> &V::PublicShare {
+        &self.public_share
+    }
+
+    /// Returns the associated data with which to encrypt the input shares. This is the same for
+    /// every Aggregator, and is equal to [`ReportShare::aad`] of each report share.
+    pub fn aad(&self) -> Result<Vec<u8>, CodecError> {
+        aad(
+            &self.id,
+            self.timestamp,
+            self.version,
+            &self.extensions,
+            &self.public_share,
+        )
+    }
+
+    /// Returns the report share for Aggregator `agg_id`, or `None` if there is no such Aggregator.
+    pub fn report_share(&self, agg_id: usize) -> Option<ReportShare<V, NONCE_SIZE>> {
+        Some(ReportShare {
+            id: self.id,
+            timestamp: self.timestamp,
+            agg_id,
+            version: self.version,
+            extensions: self.extensions.clone(),
+            public_share: self.public_share.clone(),
+            input_share: self.input_shares.get(agg_id)?.clone(),
+        })
+    }
+
+    /// Splits the report into one report share per Aggregator, in order of Aggregator ID.
+    pub fn into_report_shares(self) -> Vec<ReportShare<V, NONCE_SIZE>> {
+        let Self {
+            id,
+            timestamp,
+            version,
+            extensions,
+            public_share,
+            input_shares,
+        } = self;
+        input_shares
+            .into_iter()
+            .enumerate()
+            .map(|(agg_id, input_share)| ReportShare {
+                id,
+                timestamp,
+                agg_id,
+                version,
+                extensions: extensions.clone(),
+                public_share: public_share.clone(),
+                input_share,
+            })
+            .collect()
+    }
+}
+
+impl<V: Vdaf> Report<V, 16> {
+    /// Returns the report ID as a [`ReportId`].
+    pub fn report_id(&self) -> ReportId {
+        ReportId::from(self.id)
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> Clone for Report<V, NONCE_SIZE> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            timestamp: self.timestamp,
+            version: self.version,
+            extensions: self.extensions.clone(),
+            public_share: self.public_share.clone(),
+            input_shares: self.input_shares.clone(),
+        }
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> Debug for Report<V, NONCE_SIZE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Report")
+            .field("id", &self.id)
+            .field("timestamp", &self.timestamp)
+            .field("version", &self.version)
+            .field("extensions", &self.extensions)
+            .field("public_share", &self.public_share)
+            .field("input_shares", &self.input_shares)
+            .finish()
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> Encode for Report<V, NONCE_SIZE> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        bytes.extend_from_slice(&self.id);
+        self.timestamp.encode(bytes)?;
+        self.version.encode(bytes)?;
+        encode_u16_items(bytes, &(), &self.extensions)?;
+        encode_opaque(bytes, &self.public_share)?;
+        for input_share in &self.input_shares {
+            encode_opaque(bytes, input_share)?;
+        }
+        Ok(())
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> ParameterizedDecode<V> for Report<V, NONCE_SIZE> {
+    fn decode_with_param(vdaf: &V, bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let mut id = [0; NONCE_SIZE];
+        bytes.read_exact(&mut id)?;
+        let timestamp = u64::decode(bytes)?;
+        let version = ProtocolVersion::decode(bytes)?;
+        let extensions = decode_extensions(bytes)?;
+        let public_share = decode_opaque(vdaf, bytes)?;
+        let input_shares = (0..vdaf.num_aggregators())
+            .map(|agg_id| decode_opaque(&(vdaf, agg_id), bytes))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            id,
+            timestamp,
+            version,
+            extensions,
+            public_share,
+            input_shares,
+        })
+    }
+}
+
+/// One Aggregator's share of a [`Report`].
+pub struct ReportShare<V: Vdaf, const NONCE_SIZE: usize> {
+    id: [u8; NONCE_SIZE],
+    timestamp: u64,
+    agg_id: usize,
+    version: ProtocolVersion,
+    extensions: Vec<Extension>,
+    public_share: V::PublicShare,
+    input_share: V::InputShare,
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> ReportShare<V, NONCE_SIZE> {
+    /// Like [`Report::validate_header`], but for an encoded report share. Also checks that the
+    /// Aggregator ID is in range for `vdaf`.
+    pub fn validate_header(
+        vdaf: &V,
+        encoded: &[u8],
+    ) -> Result<ReportHeader<NONCE_SIZE>, CodecError> {
+        validate_framing(encoded, 1, Some(vdaf.num_aggregators()))
+    }
+
+    /// Returns the report ID. This is the nonce passed to the VDAF.
+    pub fn id(&self) -> &[u8; NONCE_SIZE] {
+        &self.id
+    }
+
+    /// Returns the time at which the report was generated.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the ID of the Aggregator this share is for.
+    pub fn agg_id(&self) -> usize {
+        self.agg_id
+    }
+
+    /// Returns the protocol version the report was produced with.
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Returns the extensions attached to the report.
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Returns the experiment arm label of the report, if it has one. See
+    /// [`ReportBuilder::experiment_arm`].
+    pub fn experiment_arm(&self) -> Option<&str> {
+        // The label was checked when the report was built or decoded.
+        experiment_arm(&self.extensions).ok().flatten()
+    }
+
+    /// Returns the public share.
+    pub fn public_share(&self) -> &V::PublicShare {
+        &self.public_share
+    }
+
+    /// Returns the associated data with which the input share should be encrypted. It binds the
+    /// report ID, timestamp, extensions, and public share.
+    pub fn aad(&self) -> Result<Vec<u8>, CodecError> {
+        aad(
+            &self.id,
+            self.timestamp,
+            self.version,
+            &self.extensions,
+            &self.public_share,
+        )
+    }
+
+    /// Returns the input share.
+    pub fn input_share(&self) -> &V::InputShare {
+        &self.input_share
+    }
+
+    /// Begins preparation of this report share with [`Aggregator::prepare_init`], as the
+    /// Aggregator the share is for.
+    pub fn prepare_init<const VERIFY_KEY_SIZE: usize>(
+        &self,
+        vdaf: &V,
+        verify_key: &[u8; VERIFY_KEY_SIZE],
+        agg_param: &V::AggregationParam,
+    ) -> Result<(V::PrepareState, V::PrepareShare), VdafError>
+    where
+        V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+    {
+        vdaf.prepare_init(
+            verify_key,
+            self.agg_id,
+            agg_param,
+            &self.id,
+            &self.public_share,
+            &self.input_share,
+        )
+    }
+
+    /// Like [`Self::prepare_init`], but first checks the report timestamp against `policy` at time
+    /// `now`, and returns [`VdafError::Timestamp`] if it is rejected.
+    pub fn prepare_init_checked<const VERIFY_KEY_SIZE: usize>(
+        &self,
+        vdaf: &V,
+        verify_key: &[u8; VERIFY_KEY_SIZE],
+        agg_param: &V::AggregationParam,
+        policy: &TimestampPolicy,
+        now: u64,
+    ) -> Result<(V::PrepareState, V::PrepareShare), VdafError>
+    where
+        V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+    {
+        policy.check(self.timestamp, now)?;
+        self.prepare_init(vdaf, verify_key, agg_param)
+    }
+}
+
+impl<V: Vdaf> ReportShare<V, 16> {
+    /// Returns the report ID as a [`ReportId`].
+    pub fn report_id(&self) -> ReportId {
+        ReportId::from(self.id)
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> Clone for ReportShare<V, NONCE_SIZE> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            timestamp: self.timestamp,
+            agg_id: self.agg_id,
+            version: self.version,
+            extensions: self.extensions.clone(),
+            public_share: self.public_share.clone(),
+            input_share: self.input_share.clone(),
+        }
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> Debug for ReportShare<V, NONCE_SIZE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReportShare")
+            .field("id", &self.id)
+            .field("timestamp", &self.timestamp)
+            .field("agg_id", &self.agg_id)
+            .field("version", &self.version)
+            .field("extensions", &self.extensions)
+            .field("public_share", &self.public_share)
+            .field("input_share", &self.input_share)
+            .finish()
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> Encode for ReportShare<V, NONCE_SIZE> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        bytes.extend_from_slice(&self.id);
+        self.timestamp.encode(bytes)?;
+        u8::try_from(self.agg_id)
+            .map_err(|e| CodecError::Other(e.into()))?
+            .encode(bytes)?;
+        self.version.encode(bytes)?;
+        encode_u16_items(bytes, &(), &self.extensions)?;
+        encode_opaque(bytes, &self.public_share)?;
+        encode_opaque(bytes, &self.input_share)
+    }
+}
+
+impl<V: Vdaf, const NONCE_SIZE: usize> ParameterizedDecode<V> for ReportShare<V, NONCE_SIZE> {
+    fn decode_with_param(vdaf: &V, bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let mut id = [0; NONCE_SIZE];
+        bytes.read_exact(&mut id)?;
+        let timestamp = u64::decode(bytes)?;
+        let agg_id = usize::from(u8::decode(bytes)?);
+        if agg_id >= vdaf.num_aggregators() {
+            return Err(CodecError::UnexpectedValue);
+        }
+        let version = ProtocolVersion::decode(bytes)?;
+        let extensions = decode_extensions(bytes)?;
+        let public_share = decode_opaque(vdaf, bytes)?;
+        let input_share = decode_opaque(&(vdaf, agg_id), bytes)?;
+        Ok(Self {
+            id,
+            timestamp,
+            agg_id,
+            version,
+            extensions,
+            public_share,
+            input_share,
+        })
+    }
+}
+
+/// Checks the framing of an encoded report, or of a report share if `num_aggregators` is set, with
+/// `num_input_shares` input shares.
+fn validate_framing<const NONCE_SIZE: usize>(
+    mut bytes: &[u8],
+    num_input_shares: usize,
+    num_aggregators: Option<usize>,
+) -> Result<ReportHeader<NONCE_SIZE>, CodecError> {
+    let mut id = [0; NONCE_SIZE];
+    id.copy_from_slice(take(&mut bytes, NONCE_SIZE)?);
+    let mut timestamp = [0; 8];
+    timestamp.copy_from_slice(take(&mut bytes, 8)?);
+    if let Some(num_aggregators) = num_aggregators {
+        if usize::from(take(&mut bytes, 1)?[0]) >= num_aggregators {
+            return Err(CodecError::UnexpectedValue);
+        }
+    }
+    let mut version = [0; 2];
+    version.copy_from_slice(take(&mut bytes, 2)?);
+
+    let mut extensions = take_prefixed(&mut bytes, 2)?;
+    let mut seen = ExtensionTypeSet::new();
+    while !extensions.is_empty() {
+        let extension_type = take(&mut extensions, 2)?;
+        take_prefixed(&mut extensions, 2)?;
+        if !seen.insert(u16::from_be_bytes([extension_type[0], extension_type[1]])) {
+            return Err(CodecError::UnexpectedValue);
+        }
+    }
+
+    for _ in 0..1 + num_input_shares {
+        take_prefixed(&mut bytes, 4)?;
+    }
+    if !bytes.is_empty() {
+        return Err(CodecError::BytesLeftOver(bytes.len()));
+    }
+    Ok(ReportHeader {
+        id,
+        timestamp: u64::from_be_bytes(timestamp),
+        version: ProtocolVersion::new(u16::from_be_bytes(version)),
+    })
+}
+
+/// Splits the first `len` bytes off `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], CodecError> {
+    if bytes.len() < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    let (head, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(head)
+}
+
+/// Splits a vector with a big-endian length prefix of `prefix_len` bytes off `bytes`.
+fn take_prefixed<'a>(bytes: &mut &'a [u8], prefix_len: usize) -> Result<&'a [u8], CodecError> {
+    let len = take(bytes, prefix_len)?
+        .iter()
+        .fold(0, |len, byte| (len << 8) | usize::from(*byte));
+    if bytes.len() < len {
+        return Err(CodecError::LengthPrefixTooBig(len));
+    }
+    take(bytes, len)
+}
+
+/// Encodes the report metadata and public share, which are authenticated with each input share.
+fn aad<E: Encode>(
+    id: &[u8],
+    timestamp: u64,
+    version: ProtocolVersion,
+    extensions: &[Extension],
+    public_share: &E,
+) -> Result<Vec<u8>, CodecError> {
+    let mut bytes = id.to_vec();
+    timestamp.encode(&mut bytes)?;
+    version.encode(&mut bytes)?;
+    encode_u16_items(&mut bytes, &(), extensions)?;
+    encode_opaque(&mut bytes, public_share)?;
+    Ok(bytes)
+}
+
+/// Checks that no two extensions have the same type and that the experiment arm label, if any, is
+/// valid.
+fn check_extensions(extensions: &[Extension]) -> Result<(), VdafError> {
+    if has_duplicate_types(extensions) {
+        return Err(VdafError::Uncategorized(
+            "duplicate report extension type".into(),
+        ));
+    }
+    if experiment_arm(extensions).is_err() {
+        return Err(VdafError::Uncategorized(
+            "invalid experiment arm label".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the experiment arm label among `extensions`, if there is one. Labels must be non-empty
+/// UTF-8 strings of at most [`MAX_EXPERIMENT_ARM_LEN`] bytes.
+fn experiment_arm(extensions: &[Extension]) -> Result<Option<&str>, CodecError> {
+    let Some(extension) = extensions
+        .iter()
+        .find(|extension| extension.extension_type == EXPERIMENT_ARM_EXTENSION_TYPE)
+    else {
+        return Ok(None);
+    };
+    if extension.data.is_empty() || extension.data.len() > MAX_EXPERIMENT_ARM_LEN {
+        return Err(CodecError::UnexpectedValue);
+    }
+    std::str::from_utf8(&extension.data)
+        .map(Some)
+        .map_err(|_| CodecError::UnexpectedValue)
+}
+
+/// A set of extension types, as a bitset over the whole `u16` space, so that checking a list of
+/// extensions for duplicates takes linear time.
+struct ExtensionTypeSet([u64; 1024]);
+
+impl ExtensionTypeSet {
+    fn new() -> Self {
+        Self([0; 1024])
+    }
+
+    /// Adds `extension_type` to the set, and returns whether it was not already present.
+    fn insert(&mut self, extension_type: u16) -> bool {
+        let word = &mut self.0[usize::from(extension_type >> 6)];
+        let bit = 1 << (extension_type & 63);
+        let inserted = *word & bit == 0;
+        *word |= bit;
+        inserted
+    }
+}
+
+fn has_duplicate_types(extensions: &[Extension]) -> bool {
+    let mut seen = ExtensionTypeSet::new();
+    extensions
+        .iter()
+        .any(|extension| !seen.insert(extension.extension_type))
+}
+
+/// Decodes a list of extensions, rejecting duplicate types and invalid experiment arm labels.
+fn decode_extensions(bytes: &mut Cursor<&[u8]>) -> Result<Vec<Extension>, CodecError> {
+    let extensions: Vec<Extension> = decode_u16_items(&(), bytes)?;
+    if has_duplicate_types(&extensions) {
+        return Err(CodecError::UnexpectedValue);
+    }
+    experiment_arm(&extensions)?;
+    Ok(extensions)
+}
+
+/// Encodes `message` with a 32-bit length prefix.
+fn encode_opaque<E: Encode>(bytes: &mut Vec<u8>, message: &E) -> Result<(), CodecError> {
+    encode_u32_items(bytes, &(), &message.get_encoded()?)
+}
+
+/// Decodes a message with a 32-bit length prefix, which must be consumed entirely.
+fn decode_opaque<P, D: ParameterizedDecode<P>>(
+    decoding_parameter: &P,
+    bytes: &mut Cursor<&[u8]>,
+) -> Result<D, CodecError> {
+    let encoded: Vec<u8> = decode_u32_items(&(), bytes)?;
+    D::get_decoded_with_param(decoding_parameter, &encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{prio3::Prio3, test_utils::run_vdaf_prepare, Collector, PrepareTransition};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn report_round_trip() {
+        let vdaf = Prio3::new_sum_vec(2, 4, 3, 1).unwrap();
+        let report = Report::<_, 16>::shard(&vdaf, &vec![1, 2, 3], 1_700_000_000).unwrap();
+        assert_eq!(report.timestamp(), 1_700_000_000);
+
+        let encoded = report.get_encoded().unwrap();
+        let decoded = Report::<_, 16>::get_decoded_with_param(&vdaf, &encoded).unwrap();
+        assert_eq!(decoded.get_encoded().unwrap(), encoded);
+        assert!(
+            Report::<_, 16>::get_decoded_with_param(&vdaf, &encoded[..encoded.len() - 1]).is_err()
+        );
+
+        let verify_key = [1; 16];
+        let report_shares = decoded.into_report_shares();
+        let mut states = Vec::new();
+        let mut prep_shares = Vec::new();
+        for (agg_id, report_share) in report_shares.iter().enumerate() {
+            assert_eq!(report_share.agg_id(), agg_id);
+            let encoded = report_share.get_encoded().unwrap();
+            let report_share =
+                ReportShare::<_, 16>::get_decoded_with_param(&vdaf, &encoded).unwrap();
+            assert_eq!(report_share.id(), report.id());
+            assert_eq!(report_share.report_id(), report.report_id());
+            let (state, prep_share) = report_share.prepare_init(&vdaf, &verify_key, &()).unwrap();
+            states.push(state);
+            prep_shares.push(prep_share);
+        }
+
+        let prep_msg = vdaf
+            .prepare_shares_to_prepare_message(&(), prep_shares)
+            .unwrap();
+        let agg_shares = states
+            .into_iter()
+            .map(
+                |state| match vdaf.prepare_next(state, prep_msg.clone()).unwrap() {
+                    PrepareTransition::Finish(out_share) => {
+                        vdaf.aggregate(&(), [out_share]).unwrap()
+                    }
+                    _ => panic!("unexpected transition"),
+                },
+            )
+            .collect::<Vec<_>>();
+        assert_eq!(vdaf.unshard(&(), agg_shares, 1).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn report_extensions() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let version = Extension::new(1, b"1.2.3".to_vec());
+        let geo = Extension::new(2, b"EU".to_vec());
+        let report: Report<_, 16> = ReportBuilder::new(&vdaf)
+            .timestamp(1_700_000_000)
+            .extension(version.clone())
+            .extension(geo.clone())
+            .build(&true)
+            .unwrap();
+        assert_eq!(report.extensions(), [version.clone(), geo]);
+
+        let decoded =
+            Report::<_, 16>::get_decoded_with_param(&vdaf, &report.get_encoded().unwrap()).unwrap();
+        assert_eq!(decoded.extensions(), report.extensions());
+
+        // Every report share carries the extensions and authenticates the same data.
+        let aad = report.aad().unwrap();
+        for report_share in report.clone().into_report_shares() {
+            let encoded = report_share.get_encoded().unwrap();
+            let report_share =
+                ReportShare::<_, 16>::get_decoded_with_param(&vdaf, &encoded).unwrap();
+            assert_eq!(report_share.extensions(), report.extensions());
+            assert_eq!(report_share.aad().unwrap(), aad);
+        }
+
+        // Changing an extension changes the associated data.
+        let mut other = report.clone();
+        other.extensions[1] = Extension::new(2, b"US".to_vec());
+        assert_ne!(other.aad().unwrap(), aad);
+
+        // Duplicate extension types are rejected.
+        assert_matches!(
+            ReportBuilder::new(&vdaf)
+                .extension(version.clone())
+                .extension(version.clone())
+                .build::<16>(&true),
+            Err(VdafError::Uncategorized(_))
+        );
+        let mut duplicate = report;
+        duplicate.extensions = vec![version.clone(), version];
+        let encoded = duplicate.get_encoded().unwrap();
+        assert_matches!(
+            Report::<_, 16>::get_decoded_with_param(&vdaf, &encoded),
+            Err(CodecError::UnexpectedValue)
+        );
+    }
+
+    #[test]
+    fn report_experiment_arm() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let report: Report<_, 16> = ReportBuilder::new(&vdaf)
+            .extension(Extension::new(1, b"1.2.3".to_vec()))
+            .experiment_arm("treatment")
+            .build(&true)
+            .unwrap();
+        assert_eq!(report.experiment_arm(), Some("treatment"));
+        let aad = report.aad().unwrap();
+        for report_share in report.clone().into_report_shares() {
+            let encoded = report_share.get_encoded().unwrap();
+            let report_share =
+                ReportShare::<_, 16>::get_decoded_with_param(&vdaf, &encoded).unwrap();
+            assert_eq!(report_share.experiment_arm(), Some("treatment"));
+            assert_eq!(report_share.aad().unwrap(), aad);
+        }
+
+        // The label is authenticated.
+        let mut other = report.clone();
+        other.extensions[1] = Extension::new(EXPERIMENT_ARM_EXTENSION_TYPE, b"control".to_vec());
+        assert_eq!(other.experiment_arm(), Some("control"));
+        assert_ne!(other.aad().unwrap(), aad);
+
+        let unlabeled: Report<_, 16> = ReportBuilder::new(&vdaf).build(&true).unwrap();
+        assert_eq!(unlabeled.experiment_arm(), None);
+
+        // Empty, overlong and non-UTF-8 labels are rejected.
+        for label in ["", &"x".repeat(MAX_EXPERIMENT_ARM_LEN + 1)] {
+            assert_matches!(
+                ReportBuilder::new(&vdaf)
+                    .experiment_arm(label)
+                    .build::<16>(&true),
+                Err(VdafError::Uncategorized(_))
+            );
+        }
+        let mut invalid = report;
+        invalid.extensions[1] = Extension::new(EXPERIMENT_ARM_EXTENSION_TYPE, vec![0xff]);
+        let encoded = invalid.get_encoded().unwrap();
+        assert_matches!(
+            Report::<_, 16>::get_decoded_with_param(&vdaf, &encoded),
+            Err(CodecError::UnexpectedValue)
+        );
+    }
+
+    #[test]
+    fn report_version() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let v = ProtocolVersion::new;
+        let report: Report<_, 16> = ReportBuilder::new(&vdaf)
+            .version(v(2))
+            .build(&true)
+            .unwrap();
+        assert_eq!(report.version(), v(2));
+        let encoded = report.get_encoded().unwrap();
+        let header = Report::<_, 16>::validate_header(&vdaf, &encoded).unwrap();
+        assert_eq!(header.version(), v(2));
+        assert_matches!(
+            header.check_version(&SupportedVersions::default()),
+            Err(VersionError::Unsupported { .. })
+        );
+        header
+            .check_version(&SupportedVersions::new([v(1), v(2)]).unwrap())
+            .unwrap();
+
+        let aad = report.aad().unwrap();
+        for report_share in report.clone().into_report_shares() {
+            let encoded = report_share.get_encoded().unwrap();
+            assert_eq!(
+                ReportShare::<_, 16>::validate_header(&vdaf, &encoded)
+                    .unwrap()
+                    .version(),
+                v(2)
+            );
+            let report_share =
+                ReportShare::<_, 16>::get_decoded_with_param(&vdaf, &encoded).unwrap();
+            assert_eq!(report_share.version(), v(2));
+            assert_eq!(report_share.aad().unwrap(), aad);
+        }
+
+        // The version is authenticated.
+        let mut other = report;
+        other.version = v(1);
+        assert_ne!(other.aad().unwrap(), aad);
+        assert_eq!(
+            ReportBuilder::new(&vdaf)
+                .build::<16>(&true)
+                .unwrap()
+                .version(),
+            ProtocolVersion::CURRENT
+        );
+    }
+
+    #[test]
+    fn report_build_idempotent() {
+        let vdaf = Prio3::new_sum_vec(2, 2, 3, 1).unwrap();
+        let measurement = vec![1, 0, 3];
+        let secret = [7; 32];
+        let builder = ReportBuilder::new(&vdaf)
+            .timestamp(1_700_000_000)
+            .extension(Extension::new(1, b"1.2.3".to_vec()));
+        let report: Report<_, 16> = builder
+            .clone()
+            .build_idempotent(&measurement, &secret, &[1; 16])
+            .unwrap();
+        assert_eq!(report.id(), &[1; 16]);
+
+        // A retransmission is byte-identical.
+        let encoded = report.get_encoded().unwrap();
+        let retry = builder
+            .clone()
+            .build_idempotent(&measurement, &secret, &[1; 16])
+            .unwrap();
+        assert_eq!(retry.get_encoded().unwrap(), encoded);
+
+        // Another report ID, secret, timestamp or protocol version gives fresh randomness.
+        for other in [
+            builder
+                .clone()
+                .build_idempotent(&measurement, &secret, &[2; 16]),
+            builder
+                .clone()
+                .build_idempotent(&measurement, &[8; 32], &[1; 16]),
+            builder.clone().timestamp(1_700_000_001).build_idempotent(
+                &measurement,
+                &secret,
+                &[1; 16],
+            ),
+            builder
+                .clone()
+                .version(ProtocolVersion::new(2))
+                .build_idempotent(&measurement, &secret, &[1; 16]),
+        ] {
+            let other = other.unwrap();
+            assert_ne!(other.input_shares[1], report.input_shares[1]);
+        }
+
+        let out_shares = run_vdaf_prepare(
+            &vdaf,
+            &[0; 16],
+            &(),
+            &report.id,
+            report.public_share,
+            report.input_shares,
+        )
+        .unwrap();
+        let agg_shares = out_shares
+            .into_iter()
+            .map(|out_share| vdaf.aggregate(&(), [out_share]).unwrap());
+        assert_eq!(vdaf.unshard(&(), agg_shares, 1).unwrap(), measurement);
+
+        assert_matches!(
+            ReportBuilder::new(&vdaf)
+                .extension(Extension::new(1, Vec::new()))
+                .extension(Extension::new(1, Vec::new()))
+                .build_idempotent::<16>(&measurement, &secret, &[1; 16]),
+            Err(VdafError::Uncategorized(_))
+        );
+    }
+
+    #[test]
+    fn report_validate_header() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let report: Report<_, 16> = ReportBuilder::new(&vdaf)
+            .timestamp(1_700_000_000)
+            .extension(Extension::new(1, b"1.2.3".to_vec()))
+            .extension(Extension::new(2, b"EU".to_vec()))
+            .build(&true)
+            .unwrap();
+        let encoded = report.get_encoded().unwrap();
+        let header = Report::<_, 16>::validate_header(&vdaf, &encoded).unwrap();
+        assert_eq!(header.id(), report.id());
+        assert_eq!(header.report_id(), report.report_id());
+        assert_eq!(header.timestamp(), 1_700_000_000);
+
+        // Every truncation is rejected, as is trailing data.
+        for len in 0..encoded.len() {
+            assert!(Report::<_, 16>::validate_header(&vdaf, &encoded[..len]).is_err());
+        }
+        let mut long = encoded.clone();
+        long.push(0);
+        assert_matches!(
+            Report::<_, 16>::validate_header(&vdaf, &long),
+            Err(CodecError::BytesLeftOver(1))
+        );
+
+        // A report share is not a report, and vice versa.
+        let report_share = report.report_share(1).unwrap();
+        let encoded_share = report_share.get_encoded().unwrap();
+        assert_eq!(
+            ReportShare::<_, 16>::validate_header(&vdaf, &encoded_share).unwrap(),
+            header
+        );
+        assert!(Report::<_, 16>::validate_header(&vdaf, &encoded_share).is_err());
+        assert!(ReportShare::<_, 16>::validate_header(&vdaf, &encoded).is_err());
+
+        // The Aggregator ID must be in range.
+        let mut bad_agg_id = encoded_share;
+        bad_agg_id[24] = 2;
+        assert_matches!(
+            ReportShare::<_, 16>::validate_header(&vdaf, &bad_agg_id),
+            Err(CodecError::UnexpectedValue)
+        );
+
+        // Duplicate extension types are rejected, as when decoding.
+        let mut duplicate = report;
+        duplicate.extensions = vec![
+            Extension::new(1, Vec::new()),
+            Extension::new(2, Vec::new()),
+            Extension::new(1, b"x".to_vec()),
+        ];
+        assert_matches!(
+            Report::<_, 16>::validate_header(&vdaf, &duplicate.get_encoded().unwrap()),
+            Err(CodecError::UnexpectedValue)
+        );
+
+        // The check covers the whole type space, and a long list of distinct types is accepted.
+        duplicate.extensions = (0..4096)
+            .map(|i| Extension::new(i * 16 + 15, Vec::new()))
+            .collect();
+        assert_matches!(
+            Report::<_, 16>::validate_header(&vdaf, &duplicate.get_encoded().unwrap()),
+            Ok(_)
+        );
+        duplicate
+            .extensions
+            .push(Extension::new(u16::MAX, Vec::new()));
+        assert_matches!(
+            Report::<_, 16>::validate_header(&vdaf, &duplicate.get_encoded().unwrap()),
+            Err(CodecError::UnexpectedValue)
+        );
+    }
+
+    #[test]
+    fn report_chunked() {
+        let vdaf = Prio3::new_sum_vec(2, 1, 4, 2).unwrap();
+        let measurements = [vec![1, 0, 1, 1, 0, 1, 1, 1, 0, 1], vec![0; 10]];
+        let mut out_shares = vec![Vec::new(); 3];
+        for measurement in &measurements {
+            let reports = ReportBuilder::new(&vdaf)
+                .timestamp(1_700_000_000)
+                .build_chunked::<_, 16>(measurement, 4)
+                .unwrap();
+            assert_eq!(reports.len(), 3);
+            assert!(reports.iter().all(|report| report.id() == reports[0].id()));
+            for (chunk, report) in reports.into_iter().enumerate() {
+                out_shares[chunk].push(
+                    run_vdaf_prepare(
+                        &vdaf,
+                        &[0; 16],
+                        &(),
+                        &report.id,
+                        report.public_share,
+                        report.input_shares,
+                    )
+                    .unwrap(),
+                );
+            }
+        }
+
+        let results = out_shares.into_iter().map(|out_shares| {
+            let agg_shares = (0..2).map(|agg_id| {
+                vdaf.aggregate(&(), out_shares.iter().map(|shares| shares[agg_id].clone()))
+                    .unwrap()
+            });
+            vdaf.unshard(&(), agg_shares, measurements.len()).unwrap()
+        });
+        assert_eq!(reassemble_chunks(results, 10).unwrap(), measurements[0]);
+
+        assert!(reassemble_chunks(vec![vec![1, 2], vec![3]], 4).is_err());
+        assert_matches!(
+            ReportBuilder::new(&vdaf).build_chunked::<u128, 16>(&[], 4),
+            Err(VdafError::Uncategorized(_))
+        );
+    }
+
+    #[test]
+    fn report_with_keys() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let algorithm = KeyAlgorithm {
+            kem_id: 0x0020,
+            kdf_id: 0x0001,
+            aead_id: 0x0001,
+        };
+        let key = |id, not_before, not_after| PublicKey {
+            id,
+            algorithm,
+            public_key: vec![id; 32],
+            not_before,
+            not_after,
+        };
+        let configs = [
+            PublicKeyConfig::new(vec![key(1, 0, 200), key(2, 100, 300)]).unwrap(),
+            PublicKeyConfig::new(vec![key(7, 0, 1000)]).unwrap(),
+        ];
+
+        let (report, keys) = ReportBuilder::new(&vdaf)
+            .timestamp(150)
+            .build_with_keys::<16>(&true, &configs, &[algorithm])
+            .unwrap();
+        assert_eq!(report.timestamp(), 150);
+        assert_eq!(keys.iter().map(|key| key.id).collect::<Vec<_>>(), [2, 7]);
+
+        // The leader has no key valid at this time.
+        assert_matches!(
+            ReportBuilder::new(&vdaf)
+                .timestamp(300)
+                .build_with_keys::<16>(&true, &configs, &[algorithm]),
+            Err(VdafError::Uncategorized(_))
+        );
+        assert!(ReportBuilder::new(&vdaf)
+            .build_with_keys::<16>(&true, &configs[..1], &[algorithm])
+            .is_err());
+    }
+
+    #[test]
+    fn report_share_agg_id() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let report = Report::<_, 16>::shard(&vdaf, &true, 0).unwrap();
+        assert!(report.report_share(2).is_none());
+
+        // The leader's share is decoded as the leader's, whichever Aggregator receives it.
+        let encoded = report.report_share(0).unwrap().get_encoded().unwrap();
+        let report_share = ReportShare::<_, 16>::get_decoded_with_param(&vdaf, &encoded).unwrap();
+        assert_eq!(report_share.agg_id(), 0);
+
+        // Aggregator IDs out of range are rejected.
+        let mut bad = encoded.clone();
+        bad[16 + 8] = 2;
+        assert!(ReportShare::<_, 16>::get_decoded_with_param(&vdaf, &bad).is_err());
+    }
+
+    #[test]
+    fn report_timestamp_policy() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let policy = TimestampPolicy {
+            max_age: Some(3600),
+            max_skew: 60,
+        };
+        let now = 1_700_000_000;
+        let verify_key = [0; 16];
+        for (timestamp, ok) in [
+            (now, true),
+            (now - 3600, true),
+            (now + 60, true),
+            (now - 3601, false),
+            (now + 61, false),
+        ] {
+            let report_share = Report::<_, 16>::shard(&vdaf, &true, timestamp)
+                .unwrap()
+                .report_share(1)
+                .unwrap();
+            let result = report_share.prepare_init_checked(&vdaf, &verify_key, &(), &policy, now);
+            assert_eq!(result.is_ok(), ok, "timestamp {timestamp}");
+        }
+
+        assert_eq!(
+            policy.check(now - 3601, now),
+            Err(TimestampError::TooOld {
+                timestamp: now - 3601,
+                earliest: now - 3600
+            })
+        );
+        assert_eq!(
+            policy.check(now + 61, now),
+            Err(TimestampError::TooFarInFuture {
+                timestamp: now + 61,
+                latest: now + 60
+            })
+        );
+        assert_matches!(
+            Report::<_, 16>::shard(&vdaf, &true, 0)
+                .unwrap()
+                .report_share(0)
+                .unwrap()
+                .prepare_init_checked(&vdaf, &verify_key, &(), &policy, now),
+            Err(VdafError::Timestamp(TimestampError::TooOld { .. }))
+        );
+
+        // Without a maximum age, and near the ends of the range of timestamps, nothing overflows.
+        let policy = TimestampPolicy {
+            max_age: None,
+            max_skew: u64::MAX,
+        };
+        policy.check(0, u64::MAX).unwrap();
+        policy.check(u64::MAX, 1).unwrap();
+        let policy = TimestampPolicy {
+            max_age: Some(u64::MAX),
+            max_skew: 0,
+        };
+        policy.check(0, 1).unwrap();
+        assert!(policy.check(2, 1).is_err());
+    }
+}
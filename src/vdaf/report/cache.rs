@@ -0,0 +1,402 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A cache of report shares, for Aggregators that prepare and aggregate in separate requests.
+//!
+//! An Aggregator behind an HTTP API often verifies a report in one request and aggregates it in a
+//! later one, possibly in another process. A [`ReportShareCache`] keeps the decrypted
+//! [`ReportShare`] in between, keyed by its [`ReportId`], so that the share need not be uploaded
+//! or decrypted again. The cache stores the encoded shares in a [`CacheStore`]: [`MemoryCache`]
+//! keeps them in memory, and [`DirectoryCache`] in files in a directory shared by the processes.
+//! Other backends can be provided by implementing the trait.
+//!
+//! Like an [`AccumulatorStore`](crate::vdaf::accumulator::store::AccumulatorStore), a
+//! [`DirectoryCache`] follows each file with a checksum, or a MAC if it is opened with a key, so
+//! that a modified share is reported as [`VdafError::CorruptedState`] rather than prepared. The
+//! cached shares are secret: an application should keep the directory as protected as the keys
+//! that decrypt the shares, and remove each share once it has been aggregated.
+
+use crate::{
+    codec::{Encode, ParameterizedDecode},
+    vdaf::{
+        accumulator::{check_checksum, checksum_hasher, CHECKSUM_LEN},
+        id::ReportId,
+        report::ReportShare,
+        Vdaf, VdafError,
+    },
+};
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+
+/// Storage for encoded report shares, keyed by report ID.
+pub trait CacheStore {
+    /// Stores `encoded` under `report_id`. Returns `false`, and leaves the store unchanged, if a
+    /// share is already stored under the ID.
+    fn insert(&mut self, report_id: &ReportId, encoded: &[u8]) -> Result<bool, VdafError>;
+
+    /// Returns the share stored under `report_id`, if any.
+    fn get(&self, report_id: &ReportId) -> Result<Option<Vec<u8>>, VdafError>;
+
+    /// Removes the share stored under `report_id` and returns it.
+    fn remove(&mut self, report_id: &ReportId) -> Result<Option<Vec<u8>>, VdafError>;
+}
+
+/// A [`CacheStore`] held in memory. Its contents are lost when it is dropped.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCache {
+    shares: HashMap<ReportId, Vec<u8>>,
+}
+
+impl MemoryCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheStore for MemoryCache {
+    fn insert(&mut self, report_id: &ReportId, encoded: &[u8]) -> Result<bool, VdafError> {
+        if self.shares.contains_key(report_id) {
+            return Ok(false);
+        }
+        self.shares.insert(*report_id, encoded.to_vec());
+        Ok(true)
+    }
+
+    fn get(&self, report_id: &ReportId) -> Result<Option<Vec<u8>>, VdafError> {
+        Ok(self.shares.get(report_id).cloned())
+    }
+
+    fn remove(&mut self, report_id: &ReportId) -> Result<Option<Vec<u8>>, VdafError> {
+        Ok(self.shares.remove(report_id))
+    }
+}
+
+/// A [`CacheStore`] kept in a directory, with one file per share, named after the hex encoding of
+/// the report ID.
+///
+/// Each file is written to a temporary file with a unique name and then hard-linked into place,
+/// which fails if the share already exists. A reader thus never sees a partly written share, and
+/// when several processes cache the same report at once, exactly one of them succeeds. The
+/// directory must be on a file system that supports hard links. The checksum of a file covers the report ID, so a file renamed to
+/// another report's name fails the check. A directory must always be opened with the same key, or
+/// always without one.
+pub struct DirectoryCache {
+    dir: PathBuf,
+    key: Option<[u8; 32]>,
+}
+
+impl DirectoryCache {
+    /// Opens the cache in `dir`, creating the directory if it does not exist.
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self, VdafError> {
+        Self::open_inner(dir.into(), None)
+    }
+
+    /// Opens the cache in `dir` like [`DirectoryCache::open`], but protects its files with a MAC
+    /// under `key` rather than a checksum.
+    pub fn open_with_key<P: Into<PathBuf>>(dir: P, key: [u8; 32]) -> Result<Self, VdafError> {
+        Self::open_inner(dir.into(), Some(key))
+    }
+
+    fn open_inner(dir: PathBuf, key: Option<[u8; 32]>) -> Result<Self, VdafError> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, key })
+    }
+
+    fn path(&self, report_id: &ReportId) -> PathBuf {
+        self.dir.join(format!("share-{report_id:x}"))
+    }
+
+    fn hasher(&self, report_id: &ReportId) -> Sha3_256 {
+        let mut hasher = checksum_hasher(self.key.as_ref(), b"report share");
+        hasher.update(report_id.as_bytes());
+        hasher
+    }
+}
+
+impl Debug for DirectoryCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DirectoryCache")
+            .field("dir", &self.dir)
+            .field("keyed", &self.key.is_some())
+            .finish()
+    }
+}
+
+impl CacheStore for DirectoryCache {
+    fn insert(&mut self, report_id: &ReportId, encoded: &[u8]) -> Result<bool, VdafError> {
+        let path = self.path(report_id);
+        if path.exists() {
+            return Ok(false);
+        }
+        let mut hasher = self.hasher(report_id);
+        hasher.update(encoded);
+        let tmp = self.dir.join(format!(
+            "tmp-{report_id:x}-{}-{:016x}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        let result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+            .and_then(|mut file| {
+                file.write_all(encoded)?;
+                file.write_all(&hasher.finalize())?;
+                file.sync_data()
+            })
+            .and_then(|()| fs::hard_link(&tmp, &path));
+        // The share is published or not by now, and a stray temporary file is harmless, so the
+        // result of removing it is ignored.
+        let _ = fs::remove_file(&tmp);
+        match result {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get(&self, report_id: &ReportId) -> Result<Option<Vec<u8>>, VdafError> {
+        let mut encoded = match fs::read(self.path(report_id)) {
+            Ok(encoded) => encoded,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(split) = encoded.len().checked_sub(CHECKSUM_LEN) else {
+            return Err(VdafError::CorruptedState("truncated share file".into()));
+        };
+        let mut hasher = self.hasher(report_id);
+        hasher.update(&encoded[..split]);
+        check_checksum(hasher, &encoded[split..], "share file")?;
+        encoded.truncate(split);
+        Ok(Some(encoded))
+    }
+
+    fn remove(&mut self, report_id: &ReportId) -> Result<Option<Vec<u8>>, VdafError> {
+        let encoded = self.get(report_id)?;
+        if encoded.is_some() {
+            fs::remove_file(self.path(report_id))?;
+        }
+        Ok(encoded)
+    }
+}
+
+/// A cache of report shares for one VDAF, backed by a [`CacheStore`]. See the
+/// [module documentation](self) for details.
+#[derive(Clone, Debug)]
+pub struct ReportShareCache<S> {
+    store: S,
+}
+
+impl<S: CacheStore> ReportShareCache<S> {
+    /// Creates a cache backed by `store`.
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    /// Caches `report_share`. Returns `false`, and leaves the cache unchanged, if a share of a
+    /// report with the same ID is already cached.
+    pub fn insert<V: Vdaf>(
+        &mut self,
+        report_share: &ReportShare<V, 16>,
+    ) -> Result<bool, VdafError> {
+        self.store
+            .insert(&report_share.report_id(), &report_share.get_encoded()?)
+    }
+
+    /// Returns the cached share of report `report_id`, if any. Returns
+    /// [`VdafError::CorruptedState`] if the stored share does not decode to a share of that
+    /// report.
+    pub fn get<V: Vdaf>(
+        &self,
+        vdaf: &V,
+        report_id: &ReportId,
+    ) -> Result<Option<ReportShare<V, 16>>, VdafError> {
+        self.store
+            .get(report_id)?
+            .map(|encoded| decode_share(vdaf, report_id, &encoded))
+            .transpose()
+    }
+
+    /// Removes the cached share of report `report_id` and returns it, e.g. once it has been
+    /// aggregated.
+    pub fn remove<V: Vdaf>(
+        &mut self,
+        vdaf: &V,
+        report_id: &ReportId,
+    ) -> Result<Option<ReportShare<V, 16>>, VdafError> {
+        self.store
+            .remove(report_id)?
+            .map(|encoded| decode_share(vdaf, report_id, &encoded))
+            .transpose()
+    }
+
+    /// Returns the underlying store.
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+}
+
+fn decode_share<V: Vdaf>(
+    vdaf: &V,
+    report_id: &ReportId,
+    encoded: &[u8],
+) -> Result<ReportShare<V, 16>, VdafError> {
+    let report_share = ReportShare::get_decoded_with_param(vdaf, encoded)
+        .map_err(|e| VdafError::CorruptedState(format!("invalid cached share: {e}")))?;
+    if report_share.report_id() != *report_id {
+        return Err(VdafError::CorruptedState(
+            "cached share belongs to another report".into(),
+        ));
+    }
+    Ok(report_share)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{prio3::Prio3, report::ReportBuilder, Aggregator, PrepareTransition};
+    use assert_matches::assert_matches;
+
+    /// A directory in the temporary directory that is removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!(
+                "prio-share-cache-{}-{:016x}",
+                std::process::id(),
+                rand::random::<u64>()
+            )))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn check_cache<S: CacheStore>(store: S) -> S {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let verify_key = [1; 16];
+        let report = ReportBuilder::new(&vdaf).build::<16>(&true).unwrap();
+        let [leader, helper]: [_; 2] = report.into_report_shares().try_into().unwrap();
+        let report_id = helper.report_id();
+
+        // The verification round caches the share ...
+        let mut cache = ReportShareCache::new(store);
+        assert!(cache.insert(&helper).unwrap());
+        assert!(!cache.insert(&helper).unwrap());
+        assert!(cache.get(&vdaf, &[9; 16].into()).unwrap().is_none());
+
+        // ... and the aggregation round picks it up.
+        let cached = cache.get(&vdaf, &report_id).unwrap().unwrap();
+        assert_eq!(cached.get_encoded().unwrap(), helper.get_encoded().unwrap());
+        let (leader_state, leader_share) = leader.prepare_init(&vdaf, &verify_key, &()).unwrap();
+        let (helper_state, helper_share) = cached.prepare_init(&vdaf, &verify_key, &()).unwrap();
+        let message = vdaf
+            .prepare_shares_to_prepare_message(&(), [leader_share, helper_share])
+            .unwrap();
+        for state in [leader_state, helper_state] {
+            assert_matches!(
+                vdaf.prepare_next(state, message.clone()).unwrap(),
+                PrepareTransition::Finish(_)
+            );
+        }
+
+        assert!(cache.remove(&vdaf, &report_id).unwrap().is_some());
+        assert!(cache.get(&vdaf, &report_id).unwrap().is_none());
+        assert!(cache.remove(&vdaf, &report_id).unwrap().is_none());
+        cache.into_inner()
+    }
+
+    #[test]
+    fn memory_cache() {
+        let mut store = check_cache(MemoryCache::new());
+
+        // A share stored under the wrong ID is rejected.
+        let vdaf = Prio3::new_count(2).unwrap();
+        let report = ReportBuilder::new(&vdaf).build::<16>(&true).unwrap();
+        let encoded = report.report_share(1).unwrap().get_encoded().unwrap();
+        let other_id = ReportId::from([9; 16]);
+        store.insert(&other_id, &encoded).unwrap();
+        store.insert(&report.report_id(), b"garbage").unwrap();
+        let cache = ReportShareCache::new(store);
+        assert_matches!(
+            cache.get(&vdaf, &other_id),
+            Err(VdafError::CorruptedState(_))
+        );
+        assert_matches!(
+            cache.get(&vdaf, &report.report_id()),
+            Err(VdafError::CorruptedState(_))
+        );
+    }
+
+    #[test]
+    fn directory_cache() {
+        let dir = TempDir::new();
+        check_cache(DirectoryCache::open(&dir.0).unwrap());
+        check_cache(DirectoryCache::open_with_key(&dir.0, [3; 32]).unwrap());
+
+        // Another process sees shares the first one cached, and detects modified files.
+        let report_id = ReportId::from([5; 16]);
+        DirectoryCache::open(&dir.0)
+            .unwrap()
+            .insert(&report_id, b"encoded share")
+            .unwrap();
+        let store = DirectoryCache::open(&dir.0).unwrap();
+        assert_eq!(
+            store.get(&report_id).unwrap().unwrap(),
+            b"encoded share".to_vec()
+        );
+        let path = store.path(&report_id);
+        let mut contents = fs::read(&path).unwrap();
+        contents[0] ^= 1;
+        fs::write(&path, &contents).unwrap();
+        assert_matches!(store.get(&report_id), Err(VdafError::CorruptedState(_)));
+        fs::write(&path, [0; 3]).unwrap();
+        assert_matches!(store.get(&report_id), Err(VdafError::CorruptedState(_)));
+        assert_matches!(
+            DirectoryCache::open_with_key(&dir.0, [3; 32])
+                .unwrap()
+                .remove(&report_id),
+            Err(VdafError::CorruptedState(_))
+        );
+    }
+
+    #[test]
+    fn directory_cache_concurrent_insert() {
+        let dir = TempDir::new();
+        let report_id = ReportId::from([6; 16]);
+        let inserted = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..8u8)
+                .map(|i| {
+                    let dir = &dir.0;
+                    scope.spawn(move || {
+                        DirectoryCache::open(dir)
+                            .unwrap()
+                            .insert(&report_id, &[i; 100])
+                            .unwrap()
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // Exactly one insertion wins, its share is intact, and no temporary files are left.
+        assert_eq!(inserted.iter().filter(|inserted| **inserted).count(), 1);
+        let store = DirectoryCache::open(&dir.0).unwrap();
+        let winner = inserted.iter().position(|inserted| *inserted).unwrap() as u8;
+        assert_eq!(store.get(&report_id).unwrap().unwrap(), [winner; 100]);
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 1);
+    }
+}
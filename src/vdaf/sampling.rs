@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deterministic sampling of reports for debugging and quality monitoring.
+//!
+//! Logging the prepare shares of every report is too expensive for a busy Aggregator, but a small
+//! sample of them is enough to monitor how well Clients encode their measurements: a sudden rise
+//! in rejections with a particular reason, or in the share of a Client version among them, shows
+//! up in a sample of one report in a thousand. [`SamplingPolicy`] decides whether a report is
+//! sampled from a hash of its nonce (the report ID), so the decision does not depend on the order
+//! in which reports arrive, all Aggregators with the same policy sample the same reports, and a
+//! report that is retried is sampled again. A salt selects a different sample with the same rate.
+//!
+//! [`ReportSampler`] combines prepare shares into a prepare message, like
+//! [`Aggregator::prepare_shares_to_prepare_message`], and hands each sampled report to a callback
+//! with the encoded prepare shares of every Aggregator and the outcome of verification.
+//!
+//! ```
+//! use prio::vdaf::sampling::SamplingPolicy;
+//!
+//! let policy = SamplingPolicy::one_in(1000).unwrap();
+//! let sampled = (0..10_000u32)
+//!     .filter(|i| policy.is_sampled(&i.to_be_bytes()))
+//!     .count();
+//! assert!(sampled > 0 && sampled < 100);
+//! ```
+
+use crate::{
+    codec::Encode,
+    vdaf::{Aggregator, RejectionReason, VdafError},
+};
+use sha3::{Digest, Sha3_256};
+use std::fmt::{self, Debug};
+
+/// Domain separation tag for the hash that sampling decisions are made from.
+const SAMPLING_DST: &[u8] = b"prio report sampling";
+
+/// Decides which reports are sampled. See the [module documentation](self) for details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SamplingPolicy {
+    /// A report is sampled if the first eight bytes of its hash, as a big-endian integer, are less
+    /// than the threshold. `u64::MAX` samples every report.
+    threshold: u64,
+    salt: Vec<u8>,
+}
+
+impl SamplingPolicy {
+    /// Samples one report in `n`, on average. Returns an error if `n` is zero.
+    pub fn one_in(n: u64) -> Result<Self, VdafError> {
+        if n == 0 {
+            return Err(VdafError::Uncategorized(
+                "sampling interval must be positive".into(),
+            ));
+        }
+        Ok(Self {
+            threshold: if n == 1 { u64::MAX } else { u64::MAX / n },
+            salt: Vec::new(),
+        })
+    }
+
+    /// Samples a fraction `rate` of the reports, on average. Returns an error unless `rate` is
+    /// between 0 and 1.
+    pub fn rate(rate: f64) -> Result<Self, VdafError> {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(VdafError::Uncategorized(format!(
+                "sample rate must be between 0 and 1, got {rate}"
+            )));
+        }
+        Ok(Self {
+            // A rate of 1 saturates to `u64::MAX`.
+            threshold: (rate * u64::MAX as f64) as u64,
+            salt: Vec::new(),
+        })
+    }
+
+    /// Samples no report.
+    pub fn never() -> Self {
+        Self {
+            threshold: 0,
+            salt: Vec::new(),
+        }
+    }
+
+    /// Selects the sample with `salt`. Policies with the same rate and different salts sample
+    /// independent sets of reports.
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = salt.to_vec();
+        self
+    }
+
+    /// Returns true if the report with ID `report_id` is sampled.
+    pub fn is_sampled(&self, report_id: &[u8]) -> bool {
+        if self.threshold == u64::MAX {
+            return true;
+        }
+        let digest = Sha3_256::new()
+            .chain_update(SAMPLING_DST)
+            .chain_update((self.salt.len() as u64).to_be_bytes())
+            .chain_update(&self.salt)
+            .chain_update(report_id)
+            .finalize();
+        u64::from_be_bytes(digest[..8].try_into().unwrap()) < self.threshold
+    }
+}
+
+/// The outcome of verifying a sampled report.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SampleOutcome {
+    /// The prepare shares were combined into a prepare message.
+    Accepted,
+
+    /// The prepare shares were not combined.
+    Rejected {
+        /// The reason for the rejection, if the VDAF reported one.
+        reason: Option<RejectionReason>,
+
+        /// The error returned by the VDAF.
+        error: String,
+    },
+}
+
+/// A sampled report, handed to the callback of a [`ReportSampler`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SampledReport {
+    /// The report nonce.
+    pub nonce: Vec<u8>,
+
+    /// The encoded prepare share of each Aggregator, in order of Aggregator ID.
+    pub prep_shares: Vec<Vec<u8>>,
+
+    /// The outcome of verification.
+    pub outcome: SampleOutcome,
+}
+
+/// Routes sampled reports to a callback. See the [module documentation](self) for details.
+pub struct ReportSampler<C> {
+    policy: SamplingPolicy,
+    callback: C,
+}
+
+impl<C: FnMut(SampledReport)> ReportSampler<C> {
+    /// Creates a sampler that hands the reports sampled by `policy` to `callback`.
+    pub fn new(policy: SamplingPolicy, callback: C) -> Self {
+        Self { policy, callback }
+    }
+
+    /// Returns the sampling policy.
+    pub fn policy(&self) -> &SamplingPolicy {
+        &self.policy
+    }
+
+    /// Combines prepare shares into a prepare message with
+    /// [`Aggregator::prepare_shares_to_prepare_message`]. If the report with `nonce` is sampled,
+    /// the prepare shares and the outcome are handed to the callback before returning.
+    pub fn prepare_shares_to_prepare_message<
+        V,
+        const VERIFY_KEY_SIZE: usize,
+        const NONCE_SIZE: usize,
+    >(
+        &mut self,
+        vdaf: &V,
+        agg_param: &V::AggregationParam,
+        nonce: &[u8; NONCE_SIZE],
+        prep_shares: Vec<V::PrepareShare>,
+    ) -> Result<V::PrepareMessage, VdafError>
+    where
+        V: Aggregator<VERIFY_KEY_SIZE, NONCE_SIZE>,
+    {
+        if !self.policy.is_sampled(nonce) {
+            return vdaf.prepare_shares_to_prepare_message(agg_param, prep_shares);
+        }
+        let encoded_prep_shares = prep_shares
+            .iter()
+            .map(Encode::get_encoded)
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = vdaf.prepare_shares_to_prepare_message(agg_param, prep_shares);
+        let outcome = match &result {
+            Ok(_) => SampleOutcome::Accepted,
+            Err(error) => SampleOutcome::Rejected {
+                reason: error.rejection_reason(),
+                error: error.to_string(),
+            },
+        };
+        (self.callback)(SampledReport {
+            nonce: nonce.to_vec(),
+            prep_shares: encoded_prep_shares,
+            outcome,
+        });
+        result
+    }
+}
+
+impl<C> Debug for ReportSampler<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReportSampler")
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{prio3::Prio3, Client};
+    use std::cell::RefCell;
+
+    #[test]
+    fn sampling_policy() {
+        let ids: Vec<[u8; 16]> = (0..2000u32)
+            .map(|i| {
+                let mut id = [0; 16];
+                id[..4].copy_from_slice(&i.to_be_bytes());
+                id
+            })
+            .collect();
+        let sample = |policy: &SamplingPolicy| {
+            ids.iter()
+                .filter(|id| policy.is_sampled(&id[..]))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sample(&SamplingPolicy::one_in(1).unwrap()).len(), 2000);
+        assert_eq!(sample(&SamplingPolicy::rate(1.0).unwrap()).len(), 2000);
+        assert!(sample(&SamplingPolicy::never()).is_empty());
+        assert!(sample(&SamplingPolicy::rate(0.0).unwrap()).is_empty());
+
+        let policy = SamplingPolicy::one_in(10).unwrap();
+        let sampled = sample(&policy);
+        assert!((100..300).contains(&sampled.len()), "{}", sampled.len());
+        assert_eq!(sample(&policy.clone()), sampled);
+        let salted = sample(&policy.with_salt(b"other"));
+        assert!((100..300).contains(&salted.len()));
+        assert_ne!(salted, sampled);
+
+        assert!(SamplingPolicy::one_in(0).is_err());
+        assert!(SamplingPolicy::rate(-0.1).is_err());
+    }
+
+    #[test]
+    fn report_sampler() {
+        let vdaf = Prio3::new_count(2).unwrap();
+        let verify_key = [0; 16];
+        let sampled = RefCell::new(Vec::new());
+        let mut sampler = ReportSampler::new(SamplingPolicy::one_in(2).unwrap(), |report| {
+            sampled.borrow_mut().push(report)
+        });
+
+        let mut expected = Vec::new();
+        for i in 0..20u8 {
+            let nonce = [i; 16];
+            let (public_share, input_shares) = vdaf.shard(&true, &nonce).unwrap();
+            let prep_shares: Vec<_> = input_shares
+                .iter()
+                .enumerate()
+                .map(|(agg_id, input_share)| {
+                    // Every third report fails verification, as the Aggregators disagree on the
+                    // verification key.
+                    let verify_key = if i % 3 == 0 && agg_id == 1 {
+                        [1; 16]
+                    } else {
+                        verify_key
+                    };
+                    vdaf.prepare_init(&verify_key, agg_id, &(), &nonce, &public_share, input_share)
+                        .unwrap()
+                        .1
+                })
+                .collect();
+            let result =
+                sampler.prepare_shares_to_prepare_message(&vdaf, &(), &nonce, prep_shares.clone());
+            assert_eq!(result.is_ok(), i % 3 != 0);
+            if sampler.policy().is_sampled(&nonce) {
+                expected.push((nonce, prep_shares, result.is_ok()));
+            }
+        }
+
+        let sampled = sampled.into_inner();
+        assert!(!sampled.is_empty() && sampled.len() < 20);
+        assert_eq!(sampled.len(), expected.len());
+        for (report, (nonce, prep_shares, accepted)) in sampled.iter().zip(expected) {
+            assert_eq!(report.nonce, nonce);
+            assert_eq!(report.prep_shares.len(), 2);
+            assert_eq!(report.prep_shares[1], prep_shares[1].get_encoded().unwrap());
+            match &report.outcome {
+                SampleOutcome::Accepted => assert!(accepted),
+                SampleOutcome::Rejected { error, .. } => {
+                    assert!(!accepted);
+                    assert!(!error.is_empty());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Sanity checks of aggregate results, run by the Collector.
+//!
+//! The proof system guarantees that each accepted measurement is valid, but an aggregate can still
+//! be inconsistent if an Aggregator is buggy, if it merged the wrong aggregate shares, or if it
+//! accepted a malformed report it should have rejected. Such errors usually surface as values that
+//! no batch of valid measurements could produce: a histogram whose buckets do not add up to the
+//! number of reports, or a sum that exceeds the number of reports times the largest valid
+//! measurement, typically because a negative value wrapped around the field modulus.
+//!
+//! [`AggregateChecks`] is a configurable list of such checks. The constructors named after the
+//! Prio3 types return the checks that hold for every batch of valid measurements of that type,
+//! and further [`AggregateCheck`]s, such as an outlier bound on the share of a single bucket, can
+//! be added with [`AggregateChecks::with`]. The checks are meant for aggregates computed without
+//! differential privacy noise, which may break all of them.
+//!
+//! ```
+//! use prio::vdaf::sanity::{AggregateCheck, AggregateChecks};
+//!
+//! let checks = AggregateChecks::histogram();
+//! assert!(checks.verify(&[3, 0, 7], 10).is_ok());
+//! assert!(checks.verify(&[3, 0, 6], 10).is_err());
+//!
+//! let checks = AggregateChecks::sum(255).with(AggregateCheck::EntryFractionAtMost(0.5));
+//! assert_eq!(checks.check(&[3000], 10).len(), 2);
+//! ```
+
+use crate::vdaf::VdafError;
+use std::fmt::{self, Display, Formatter};
+
+/// A property an aggregate result must have. The aggregate is a vector of entries, each the sum
+/// of one entry of the measurements, computed over `num_measurements` reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AggregateCheck {
+    /// The entries add up to exactly the number of measurements, as for a histogram, where each
+    /// measurement contributes to a single bucket.
+    TotalEqualsCount,
+
+    /// The entries add up to at most the number of measurements times this value, the largest
+    /// sum of the entries of a single measurement.
+    TotalAtMost(u128),
+
+    /// Each entry is at most the number of measurements times this value, the largest valid
+    /// entry of a measurement.
+    EntryAtMost(u128),
+
+    /// Each entry is at most this fraction of the number of measurements. This is an outlier
+    /// check rather than a validity check: it flags a bucket that is more popular than expected.
+    EntryFractionAtMost(f64),
+}
+
+impl Display for AggregateCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TotalEqualsCount => write!(f, "total equals count"),
+            Self::TotalAtMost(max) => write!(f, "total at most count * {max}"),
+            Self::EntryAtMost(max) => write!(f, "entry at most count * {max}"),
+            Self::EntryFractionAtMost(fraction) => {
+                write!(f, "entry at most count * {fraction}")
+            }
+        }
+    }
+}
+
+/// A failed [`AggregateCheck`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inconsistency {
+    /// The check that failed.
+    pub check: AggregateCheck,
+
+    /// The index of the offending entry, for the checks of single entries.
+    pub index: Option<usize>,
+
+    /// The offending value: the entry or the total.
+    pub value: u128,
+
+    /// The bound the value was checked against, or `None` if the bound overflows.
+    pub bound: Option<u128>,
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "entry {index} is {}", self.value)?,
+            None => write!(f, "total is {}", self.value)?,
+        }
+        match self.bound {
+            Some(bound) => write!(f, ", bound {bound} ({})", self.check),
+            None => write!(f, " ({})", self.check),
+        }
+    }
+}
+
+/// A list of checks of aggregate results. See the [module documentation](self) for details.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AggregateChecks {
+    checks: Vec<AggregateCheck>,
+}
+
+impl AggregateChecks {
+    /// Returns an empty list of checks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the checks for [`Count`](crate::flp::types::Count).
+    pub fn count() -> Self {
+        Self::new().with(AggregateCheck::EntryAtMost(1))
+    }
+
+    /// Returns the checks for [`Sum`](crate::flp::types::Sum) with the given largest measurement.
+    pub fn sum(max_measurement: u128) -> Self {
+        Self::new().with(AggregateCheck::EntryAtMost(max_measurement))
+    }
+
+    /// Returns the checks for [`SumVec`](crate::flp::types::SumVec) with entries of `bits` bits.
+    pub fn sum_vec(bits: usize) -> Self {
+        let max = if bits >= 128 {
+            u128::MAX
+        } else {
+            (1 << bits) - 1
+        };
+        Self::new().with(AggregateCheck::EntryAtMost(max))
+    }
+
+    /// Returns the checks for [`Histogram`](crate::flp::types::Histogram).
+    pub fn histogram() -> Self {
+        Self::new()
+            .with(AggregateCheck::TotalEqualsCount)
+            .with(AggregateCheck::EntryAtMost(1))
+    }
+
+    /// Adds a check.
+    pub fn with(mut self, check: AggregateCheck) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// Returns the checks, in the order they are run.
+    pub fn checks(&self) -> &[AggregateCheck] {
+        &self.checks
+    }
+
+    /// Runs the checks on `aggregate`, computed over `num_measurements` reports, and returns the
+    /// inconsistencies found, in the order of the checks and then of the entries. A scalar
+    /// aggregate, such as that of [`Count`](crate::flp::types::Count), is passed as a vector of
+    /// one entry.
+    pub fn check(&self, aggregate: &[u128], num_measurements: usize) -> Vec<Inconsistency> {
+        // The total is `None` if it overflows, which no valid batch can cause.
+        let total = aggregate
+            .iter()
+            .try_fold(0u128, |total, entry| total.checked_add(*entry));
+        let count = num_measurements as u128;
+        let mut found = Vec::new();
+        for check in &self.checks {
+            let check = *check;
+            let total_inconsistency = |bound: Option<u128>, holds: bool| {
+                (!holds).then(|| Inconsistency {
+                    check,
+                    index: None,
+                    value: total.unwrap_or(u128::MAX),
+                    bound,
+                })
+            };
+            match check {
+                AggregateCheck::TotalEqualsCount => {
+                    found.extend(total_inconsistency(Some(count), total == Some(count)));
+                }
+                AggregateCheck::TotalAtMost(max) => {
+                    let bound = count.checked_mul(max);
+                    let holds = match (total, bound) {
+                        (Some(total), Some(bound)) => total <= bound,
+                        (_, None) => true,
+                        (None, Some(_)) => false,
+                    };
+                    found.extend(total_inconsistency(bound, holds));
+                }
+                AggregateCheck::EntryAtMost(max) => {
+                    if let Some(bound) = count.checked_mul(max) {
+                        found.extend(entries_above(check, aggregate, bound));
+                    }
+                }
+                AggregateCheck::EntryFractionAtMost(fraction) => {
+                    // Round up, so that an entry is only flagged if it exceeds the exact bound.
+                    let bound = (fraction * count as f64).ceil();
+                    if bound < u128::MAX as f64 {
+                        found.extend(entries_above(check, aggregate, bound.max(0.0) as u128));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Runs the checks like [`Self::check`], and returns an error describing the inconsistencies
+    /// if there are any.
+    pub fn verify(&self, aggregate: &[u128], num_measurements: usize) -> Result<(), VdafError> {
+        let found = self.check(aggregate, num_measurements);
+        if found.is_empty() {
+            return Ok(());
+        }
+        Err(VdafError::Uncategorized(format!(
+            "inconsistent aggregate over {num_measurements} measurements: {}",
+            found
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        )))
+    }
+}
+
+fn entries_above(
+    check: AggregateCheck,
+    aggregate: &[u128],
+    bound: u128,
+) -> impl Iterator<Item = Inconsistency> + '_ {
+    aggregate
+        .iter()
+        .enumerate()
+        .filter(move |(_, entry)| **entry > bound)
+        .map(move |(index, entry)| Inconsistency {
+            check,
+            index: Some(index),
+            value: *entry,
+            bound: Some(bound),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{prio3::Prio3, test_utils::run_vdaf};
+
+    #[test]
+    fn prio3_aggregates_pass() {
+        let histogram = Prio3::new_histogram(2, 4, 2).unwrap();
+        let aggregate = run_vdaf(&histogram, &(), [0, 3, 3, 1, 3]).unwrap();
+        assert_eq!(AggregateChecks::histogram().check(&aggregate, 5), []);
+
+        let sum = Prio3::new_sum(2, 8).unwrap();
+        let aggregate = run_vdaf(&sum, &(), [255, 255, 0]).unwrap();
+        assert_eq!(AggregateChecks::sum(255).check(&[aggregate], 3), []);
+
+        let sum_vec = Prio3::new_sum_vec(2, 2, 3, 1).unwrap();
+        let aggregate = run_vdaf(&sum_vec, &(), [vec![3, 0, 1], vec![3, 2, 1]]).unwrap();
+        assert_eq!(AggregateChecks::sum_vec(2).check(&aggregate, 2), []);
+
+        let count = Prio3::new_count(2).unwrap();
+        let aggregate = run_vdaf(&count, &(), [true, true]).unwrap();
+        assert_eq!(AggregateChecks::count().check(&[aggregate.into()], 2), []);
+    }
+
+    #[test]
+    fn inconsistent_aggregates() {
+        let found = AggregateChecks::histogram().check(&[2, 0, 5], 4);
+        assert_eq!(
+            found,
+            [
+                Inconsistency {
+                    check: AggregateCheck::TotalEqualsCount,
+                    index: None,
+                    value: 7,
+                    bound: Some(4),
+                },
+                Inconsistency {
+                    check: AggregateCheck::EntryAtMost(1),
+                    index: Some(2),
+                    value: 5,
+                    bound: Some(4),
+                },
+            ]
+        );
+        let err = AggregateChecks::histogram()
+            .verify(&[2, 0, 5], 4)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("entry 2 is 5, bound 4"), "{err}");
+
+        // A negative value that wrapped around the field modulus.
+        let wrapped = u128::from(u64::MAX) * 3;
+        assert_eq!(AggregateChecks::sum(255).check(&[wrapped], 100).len(), 1);
+        assert_eq!(
+            AggregateChecks::new()
+                .with(AggregateCheck::TotalAtMost(1))
+                .check(&[u128::MAX, 1], 3)[0]
+                .value,
+            u128::MAX
+        );
+
+        // Outliers.
+        let checks = AggregateChecks::new().with(AggregateCheck::EntryFractionAtMost(0.25));
+        assert_eq!(checks.check(&[25, 25, 25, 25], 100), []);
+        let found = checks.check(&[10, 60, 30], 100);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].index, Some(1));
+        assert_eq!(found[1].bound, Some(25));
+
+        // Bounds that overflow never fail.
+        assert_eq!(AggregateChecks::sum_vec(128).check(&[u128::MAX], 2), []);
+    }
+}
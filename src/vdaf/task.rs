@@ -0,0 +1,1294 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Task provisioning.
+//!
+//! Before the Aggregators accept reports for a task, they must agree on its parameters: the
+//! measurement type and its length, the field, the differential privacy parameters, the keys in
+//! use and the batch policy. If they do not, they will prepare shares under different VDAFs and
+//! the reports will be rejected, or worse, aggregated into a meaningless result.
+//!
+//! [`TaskConfig`] holds these parameters and has a canonical encoding: encoding is deterministic
+//! and decoding rejects anything that would not re-encode to the same bytes. Its
+//! [`TaskConfig::task_id`] is a hash of that encoding, so the task ID commits to every parameter.
+//! Provisioning is then a handshake: the leader sends the encoded config to the helper, the helper
+//! decodes and [validates](TaskConfig::validate) it and replies with a [`TaskAcknowledgement`], and
+//! the leader checks the acknowledgement with [`TaskConfig::check_acknowledgement`]. An
+//! acknowledgement for a different config carries a different task ID, so once the check passes
+//! both Aggregators have provably agreed on the same parameters.
+//!
+//! The config carries the [`ProtocolVersion`] the Aggregators
+//! [negotiated](SupportedVersions::negotiate) beforehand, and a helper refuses a version it does
+//! not support with [`TaskConfig::check_version`] before it acknowledges the config.
+//!
+//! The measurement type is identified by its code in a
+//! [`MeasurementTypeRegistry`], so that an application can define its own types, and a helper can
+//! refuse a task whose type it does not know with [`TaskConfig::check_registered`].
+//!
+//! The config includes the shape of the helpers' input shares, a [`HelperShareConfig`]: how many
+//! seeds a share holds, how long they are, how they are expanded, and into how many field
+//! elements. A helper checks that its VDAF agrees with it when the task is provisioned, with
+//! [`HelperShareConfig::check_vdaf`], and checks every decrypted share against it with
+//! [`HelperShareConfig::check_share`] before expanding it. A Client built for another version of
+//! the task then has its reports rejected, rather than having its seeds expanded into shares of
+//! the wrong length.
+
+use crate::{
+    codec::{decode_u16_items, encode_u16_items, CodecError, Decode, Encode},
+    vdaf::{
+        id::TaskId,
+        registry::{
+            decode_varint, encode_varint, varint_len, MeasurementTypeRegistry, FIRST_CUSTOM_CODE,
+        },
+        version::{ProtocolVersion, SupportedVersions, VersionError},
+    },
+};
+#[cfg(feature = "crypto-dependencies")]
+use crate::{
+    flp::Type,
+    vdaf::{
+        prio2::{layout::ELEMENT_LEN, Prio2},
+        prio3::Prio3,
+        xof::XofTurboShake128,
+    },
+};
+use sha3::{Digest, Sha3_256};
+use std::io::{Cursor, Read};
+use subtle::ConstantTimeEq;
+
+/// Domain separation tag for [`TaskConfig::task_id`].
+const TASK_ID_DST: &[u8] = b"prio task id";
+
+/// Domain separation tag for [`KeyFingerprint`].
+const KEY_FINGERPRINT_DST: &[u8] = b"prio key fingerprint";
+
+/// Errors returned by this module.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TaskError {
+    /// The task configuration is invalid.
+    #[error("invalid task config: {0}")]
+    InvalidConfig(&'static str),
+
+    /// The peer acknowledged a different task configuration.
+    #[error("peer acknowledged a different task config")]
+    Mismatch,
+
+    /// A helper's input share, or the VDAF that expands it, does not have the shape the task
+    /// config specifies.
+    #[error("helper share mismatch: {0}")]
+    HelperShare(String),
+
+    /// The measurement type is not in the registry.
+    #[error("unknown measurement type code {0}")]
+    UnknownMeasurementType(u64),
+
+    /// The protocol version of the task is not supported.
+    #[error("version error: {0}")]
+    Version(#[from] VersionError),
+
+    /// Encoding or decoding a message failed.
+    #[error("codec error: {0}")]
+    Codec(#[from] CodecError),
+}
+
+/// The type of measurement a task collects, and its parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MeasurementType {
+    /// A boolean counted across reports.
+    Count,
+    /// An integer in `[0, 2^bits)`.
+    Sum {
+        /// The number of bits of the measurement.
+        bits: u8,
+    },
+    /// A vector of integers in `[0, 2^bits)`.
+    SumVec {
+        /// The number of bits of each entry.
+        bits: u8,
+        /// The length of the chunks the validity circuit is split into.
+        chunk_length: u32,
+    },
+    /// A one-hot vector selecting one bucket of a histogram.
+    Histogram {
+        /// The length of the chunks the validity circuit is split into.
+        chunk_length: u32,
+    },
+    /// A vector of fixed-point numbers in `[-1, 1)` with bounded L2 norm.
+    FixedPointBoundedL2VecSum {
+        /// The number of bits of each entry: 16, 32 or 64.
+        bits: u8,
+    },
+    /// An integer in `[0, 2^bits)`, averaged across reports.
+    Average {
+        /// The number of bits of the measurement.
+        bits: u8,
+    },
+    /// A vector of bits with at most `max_weight` ones.
+    MultihotCountVec {
+        /// The largest number of ones in a measurement.
+        max_weight: u32,
+        /// The length of the chunks the validity circuit is split into.
+        chunk_length: u32,
+    },
+    /// A vector of integers in `[min, max]`.
+    RangeSumVec {
+        /// The smallest value of an entry.
+        min: u64,
+        /// The largest value of an entry.
+        max: u64,
+    },
+    /// A vector of bits, with a counter for each bit and for the conjunction of each pair of
+    /// positions in `pairs`.
+    AndCountVec {
+        /// The pairs of positions whose conjunctions are counted.
+        pairs: Vec<(u32, u32)>,
+    },
+    /// A sketch of `2^bucket_bits` registers, for approximately counting distinct identifiers.
+    DistinctCount {
+        /// The logarithm of the number of registers.
+        bucket_bits: u8,
+        /// The largest value of a register.
+        max_rank: u8,
+        /// The length of the chunks the validity circuit is split into.
+        chunk_length: u32,
+    },
+    /// An integer in `[-2^(bits-1), 2^(bits-1))`.
+    SignedSum {
+        /// The number of bits of the measurement.
+        bits: u8,
+    },
+    /// A type defined by the application, such as a custom validity circuit, identified by its
+    /// code in a [`MeasurementTypeRegistry`]. Its parameters are fixed by the application.
+    Custom {
+        /// The code of the type, at least [`FIRST_CUSTOM_CODE`].
+        code: u64,
+    },
+}
+
+impl MeasurementType {
+    /// Returns the code of the type in a [`MeasurementTypeRegistry`]. This is the first field of
+    /// the type's encoding.
+    pub fn code(&self) -> u64 {
+        match self {
+            Self::Count => 0,
+            Self::Sum { .. } => 1,
+            Self::SumVec { .. } => 2,
+            Self::Histogram { .. } => 3,
+            Self::FixedPointBoundedL2VecSum { .. } => 4,
+            Self::Average { .. } => 5,
+            Self::MultihotCountVec { .. } => 6,
+            Self::RangeSumVec { .. } => 7,
+            Self::AndCountVec { .. } => 8,
+            Self::DistinctCount { .. } => 9,
+            Self::SignedSum { .. } => 10,
+            Self::Custom { code } => *code,
+        }
+    }
+}
+
+impl Encode for MeasurementType {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_varint(self.code(), bytes);
+        match self {
+            Self::Count | Self::Custom { .. } => Ok(()),
+            Self::Sum { bits }
+            | Self::FixedPointBoundedL2VecSum { bits }
+            | Self::Average { bits }
+            | Self::SignedSum { bits } => bits.encode(bytes),
+            Self::SumVec { bits, chunk_length } => {
+                bits.encode(bytes)?;
+                chunk_length.encode(bytes)
+            }
+            Self::Histogram { chunk_length } => chunk_length.encode(bytes),
+            Self::MultihotCountVec {
+                max_weight,
+                chunk_length,
+            } => {
+                max_weight.encode(bytes)?;
+                chunk_length.encode(bytes)
+            }
+            Self::RangeSumVec { min, max } => {
+                min.encode(bytes)?;
+                max.encode(bytes)
+            }
+            Self::AndCountVec { pairs } => {
+                u16::try_from(pairs.len())
+                    .map_err(|_| CodecError::LengthPrefixTooBig(pairs.len()))?
+                    .encode(bytes)?;
+                for (i, j) in pairs {
+                    i.encode(bytes)?;
+                    j.encode(bytes)?;
+                }
+                Ok(())
+            }
+            Self::DistinctCount {
+                bucket_bits,
+                max_rank,
+                chunk_length,
+            } => {
+                bucket_bits.encode(bytes)?;
+                max_rank.encode(bytes)?;
+                chunk_length.encode(bytes)
+            }
+        }
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(match self {
+            Self::Count => 1,
+            Self::Sum { .. }
+            | Self::FixedPointBoundedL2VecSum { .. }
+            | Self::Average { .. }
+            | Self::SignedSum { .. } => 2,
+            Self::SumVec { .. } => 6,
+            Self::Histogram { .. } => 5,
+            Self::MultihotCountVec { .. } => 9,
+            Self::RangeSumVec { .. } => 17,
+            Self::AndCountVec { pairs } => 3 + 8 * pairs.len(),
+            Self::DistinctCount { .. } => 7,
+            Self::Custom { code } => varint_len(*code),
+        })
+    }
+}
+
+impl Decode for MeasurementType {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        match decode_varint(bytes)? {
+            0 => Ok(Self::Count),
+            1 => Ok(Self::Sum {
+                bits: u8::decode(bytes)?,
+            }),
+            2 => Ok(Self::SumVec {
+                bits: u8::decode(bytes)?,
+                chunk_length: u32::decode(bytes)?,
+            }),
+            3 => Ok(Self::Histogram {
+                chunk_length: u32::decode(bytes)?,
+            }),
+            4 => Ok(Self::FixedPointBoundedL2VecSum {
+                bits: u8::decode(bytes)?,
+            }),
+            5 => Ok(Self::Average {
+                bits: u8::decode(bytes)?,
+            }),
+            6 => Ok(Self::MultihotCountVec {
+                max_weight: u32::decode(bytes)?,
+                chunk_length: u32::decode(bytes)?,
+            }),
+            7 => Ok(Self::RangeSumVec {
+                min: u64::decode(bytes)?,
+                max: u64::decode(bytes)?,
+            }),
+            8 => {
+                let num_pairs = u16::decode(bytes)?;
+                let pairs = (0..num_pairs)
+                    .map(|_| Ok((u32::decode(bytes)?, u32::decode(bytes)?)))
+                    .collect::<Result<_, CodecError>>()?;
+                Ok(Self::AndCountVec { pairs })
+            }
+            9 => Ok(Self::DistinctCount {
+                bucket_bits: u8::decode(bytes)?,
+                max_rank: u8::decode(bytes)?,
+                chunk_length: u32::decode(bytes)?,
+            }),
+            10 => Ok(Self::SignedSum {
+                bits: u8::decode(bytes)?,
+            }),
+            code if code >= FIRST_CUSTOM_CODE => Ok(Self::Custom { code }),
+            _ => Err(CodecError::UnexpectedValue),
+        }
+    }
+}
+
+/// The field a task's shares live in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FieldId {
+    /// [`Field64`](crate::field::Field64).
+    Field64,
+    /// [`Field128`](crate::field::Field128).
+    Field128,
+    /// [`FieldPrio2`](crate::field::FieldPrio2).
+    FieldPrio2,
+}
+
+impl Encode for FieldId {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        match self {
+            Self::Field64 => 0u8,
+            Self::Field128 => 1u8,
+            Self::FieldPrio2 => 2u8,
+        }
+        .encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+impl Decode for FieldId {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        match u8::decode(bytes)? {
+            0 => Ok(Self::Field64),
+            1 => Ok(Self::Field128),
+            2 => Ok(Self::FieldPrio2),
+            _ => Err(CodecError::UnexpectedValue),
+        }
+    }
+}
+
+/// The noise mechanism used to make aggregates differentially private.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DpMechanism {
+    /// Discrete Gaussian noise, for zero-concentrated differential privacy.
+    DiscreteGaussian,
+    /// Discrete Laplace noise, for pure differential privacy.
+    DiscreteLaplace,
+}
+
+/// The differential privacy parameters of a task. The budget `epsilon` is the fraction
+/// `epsilon_numerator / epsilon_denominator`, which must be in lowest terms so that it has a
+/// single encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DpConfig {
+    /// The noise mechanism.
+    pub mechanism: DpMechanism,
+
+    /// The numerator of epsilon.
+    pub epsilon_numerator: u64,
+
+    /// The denominator of epsilon.
+    pub epsilon_denominator: u64,
+}
+
+impl Encode for DpConfig {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        match self.mechanism {
+            DpMechanism::DiscreteGaussian => 0u8,
+            DpMechanism::DiscreteLaplace => 1u8,
+        }
+        .encode(bytes)?;
+        self.epsilon_numerator.encode(bytes)?;
+        self.epsilon_denominator.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(1 + 8 + 8)
+    }
+}
+
+impl Decode for DpConfig {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let mechanism = match u8::decode(bytes)? {
+            0 => DpMechanism::DiscreteGaussian,
+            1 => DpMechanism::DiscreteLaplace,
+            _ => return Err(CodecError::UnexpectedValue),
+        };
+        Ok(Self {
+            mechanism,
+            epsilon_numerator: u64::decode(bytes)?,
+            epsilon_denominator: u64::decode(bytes)?,
+        })
+    }
+}
+
+/// The SHA3-256 fingerprint of a key, such as the VDAF verification key or an Aggregator's public
+/// encryption key. The fingerprint lets the Aggregators check that they hold the same keys
+/// without sending the keys themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KeyFingerprint([u8; 32]);
+
+impl KeyFingerprint {
+    /// Computes the fingerprint of `key`.
+    pub fn new(key: &[u8]) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(KEY_FINGERPRINT_DST);
+        hasher.update(key);
+        Self(hasher.finalize().into())
+    }
+}
+
+impl AsRef<[u8; 32]> for KeyFingerprint {
+    fn as_ref(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Encode for KeyFingerprint {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        bytes.extend_from_slice(&self.0);
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(32)
+    }
+}
+
+impl Decode for KeyFingerprint {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let mut fingerprint = [0; 32];
+        bytes.read_exact(&mut fingerprint)?;
+        Ok(Self(fingerprint))
+    }
+}
+
+/// When a batch of reports may be collected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchPolicy {
+    /// The minimum number of reports in a batch.
+    pub min_batch_size: u64,
+
+    /// The maximum number of reports in a batch, or `None` for no limit.
+    pub max_batch_size: Option<u64>,
+
+    /// The granularity of report timestamps and batch intervals, in seconds.
+    pub time_precision: u64,
+}
+
+impl Encode for BatchPolicy {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.min_batch_size.encode(bytes)?;
+        encode_option(bytes, &self.max_batch_size)?;
+        self.time_precision.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(8 + 1 + self.max_batch_size.map_or(0, |_| 8) + 8)
+    }
+}
+
+impl Decode for BatchPolicy {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            min_batch_size: u64::decode(bytes)?,
+            max_batch_size: decode_option(bytes)?,
+            time_precision: u64::decode(bytes)?,
+        })
+    }
+}
+
+/// The algorithm that expands the seeds of a helper's input share into field elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SeedExpansion {
+    /// [`XofTurboShake128`], as used by Prio3.
+    TurboShake128,
+    /// [`XofHmacSha256Aes128`](crate::vdaf::xof::XofHmacSha256Aes128).
+    HmacSha256Aes128,
+    /// AES-128 in CTR mode, as used by Prio2.
+    Aes128Ctr,
+}
+
+impl SeedExpansion {
+    /// Returns the length in bytes of the seeds of this algorithm.
+    pub fn seed_len(&self) -> usize {
+        match self {
+            Self::TurboShake128 => 16,
+            Self::HmacSha256Aes128 | Self::Aes128Ctr => 32,
+        }
+    }
+}
+
+impl Encode for SeedExpansion {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        match self {
+            Self::TurboShake128 => 0u8,
+            Self::HmacSha256Aes128 => 1u8,
+            Self::Aes128Ctr => 2u8,
+        }
+        .encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+impl Decode for SeedExpansion {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        match u8::decode(bytes)? {
+            0 => Ok(Self::TurboShake128),
+            1 => Ok(Self::HmacSha256Aes128),
+            2 => Ok(Self::Aes128Ctr),
+            _ => Err(CodecError::UnexpectedValue),
+        }
+    }
+}
+
+/// The shape of a helper's input share. See the [module documentation](self) for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HelperShareConfig {
+    /// How the seeds are expanded.
+    pub expansion: SeedExpansion,
+
+    /// The length in bytes of each seed.
+    pub seed_len: u8,
+
+    /// The number of seeds in a share.
+    pub num_seeds: u8,
+
+    /// The number of field elements the seeds are expanded into.
+    pub expanded_len: u32,
+}
+
+impl HelperShareConfig {
+    /// Returns the shape of the helpers' input shares of `vdaf`.
+    #[cfg(feature = "crypto-dependencies")]
+    pub fn for_prio3<T: Type>(vdaf: &Prio3<T, XofTurboShake128, 16>) -> Result<Self, TaskError> {
+        Self::new(
+            SeedExpansion::TurboShake128,
+            vdaf.input_share_len(1) / 16,
+            vdaf.expanded_share_len(),
+        )
+    }
+
+    /// Returns the shape of the helper's input share of `vdaf`.
+    #[cfg(feature = "crypto-dependencies")]
+    pub fn for_prio2(vdaf: &Prio2) -> Result<Self, TaskError> {
+        Self::new(
+            SeedExpansion::Aes128Ctr,
+            1,
+            vdaf.input_share_len(0) / ELEMENT_LEN,
+        )
+    }
+
+    #[cfg(feature = "crypto-dependencies")]
+    fn new(
+        expansion: SeedExpansion,
+        num_seeds: usize,
+        expanded_len: usize,
+    ) -> Result<Self, TaskError> {
+        Ok(Self {
+            expansion,
+            // Unwrap safety: every seed length fits in a byte.
+            seed_len: u8::try_from(expansion.seed_len()).unwrap(),
+            num_seeds: u8::try_from(num_seeds)
+                .map_err(|_| TaskError::InvalidConfig("too many seeds"))?,
+            expanded_len: u32::try_from(expanded_len)
+                .map_err(|_| TaskError::InvalidConfig("expanded share too long"))?,
+        })
+    }
+
+    /// Returns the length in bytes of an encoded helper input share.
+    pub fn share_len(&self) -> usize {
+        usize::from(self.seed_len) * usize::from(self.num_seeds)
+    }
+
+    /// Checks, before expanding it, that a decrypted helper input share has the length the task
+    /// config specifies.
+    pub fn check_share(&self, encoded_share: &[u8]) -> Result<(), TaskError> {
+        if encoded_share.len() != self.share_len() {
+            return Err(TaskError::HelperShare(format!(
+                "share has length {}, expected {}",
+                encoded_share.len(),
+                self.share_len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that `vdaf_config`, the shape of the helper shares of the VDAF an Aggregator runs
+    /// the task with (e.g., from [`HelperShareConfig::for_prio3`]), is the one in the task config.
+    pub fn check_vdaf(&self, vdaf_config: &HelperShareConfig) -> Result<(), TaskError> {
+        if vdaf_config != self {
+            return Err(TaskError::HelperShare(format!(
+                "VDAF expands {} seeds of {} bytes with {:?} into {} elements, task config \
+                 expects {} seeds of {} bytes with {:?} into {} elements",
+                vdaf_config.num_seeds,
+                vdaf_config.seed_len,
+                vdaf_config.expansion,
+                vdaf_config.expanded_len,
+                self.num_seeds,
+                self.seed_len,
+                self.expansion,
+                self.expanded_len
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Encode for HelperShareConfig {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.expansion.encode(bytes)?;
+        self.seed_len.encode(bytes)?;
+        self.num_seeds.encode(bytes)?;
+        self.expanded_len.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(1 + 1 + 1 + 4)
+    }
+}
+
+impl Decode for HelperShareConfig {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            expansion: SeedExpansion::decode(bytes)?,
+            seed_len: u8::decode(bytes)?,
+            num_seeds: u8::decode(bytes)?,
+            expanded_len: u32::decode(bytes)?,
+        })
+    }
+}
+
+/// The parameters of a task. See the [module documentation](self) for how the Aggregators agree
+/// on them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskConfig {
+    /// The type of the measurements.
+    pub measurement_type: MeasurementType,
+
+    /// The length of each measurement. This is 1 for scalar measurement types, and the length of
+    /// the vector or the number of buckets or registers otherwise.
+    pub dimension: u32,
+
+    /// The field the shares live in.
+    pub field: FieldId,
+
+    /// The differential privacy parameters, or `None` if no noise is added.
+    pub dp: Option<DpConfig>,
+
+    /// The fingerprints of the keys used by the task, in an order fixed by the application, for
+    /// example the verification key followed by each Aggregator's encryption key.
+    pub key_fingerprints: Vec<KeyFingerprint>,
+
+    /// When batches may be collected.
+    pub batch_policy: BatchPolicy,
+
+    /// The shape of the helpers' input shares.
+    pub helper_shares: HelperShareConfig,
+
+    /// The protocol version of the task, negotiated by the Aggregators before provisioning.
+    pub version: ProtocolVersion,
+}
+
+impl TaskConfig {
+    /// Checks that the parameters are consistent with each other.
+    pub fn validate(&self) -> Result<(), TaskError> {
+        match self.measurement_type {
+            MeasurementType::Count
+            | MeasurementType::Sum { .. }
+            | MeasurementType::Average { .. }
+            | MeasurementType::SignedSum { .. }
+                if self.dimension != 1 =>
+            {
+                return Err(TaskError::InvalidConfig(
+                    "scalar measurement types must have dimension 1",
+                ))
+            }
+            MeasurementType::Sum { bits }
+            | MeasurementType::SumVec { bits, .. }
+            | MeasurementType::Average { bits }
+            | MeasurementType::SignedSum { bits }
+                if bits == 0 || bits > 64 =>
+            {
+                return Err(TaskError::InvalidConfig("bits must be between 1 and 64"))
+            }
+            MeasurementType::FixedPointBoundedL2VecSum { bits }
+                if !matches!(bits, 16 | 32 | 64) =>
+            {
+                return Err(TaskError::InvalidConfig(
+                    "fixed-point entries must have 16, 32 or 64 bits",
+                ))
+            }
+            MeasurementType::SumVec { chunk_length, .. }
+            | MeasurementType::Histogram { chunk_length }
+            | MeasurementType::MultihotCountVec { chunk_length, .. }
+                if chunk_length == 0 || chunk_length > self.dimension =>
+            {
+                return Err(TaskError::InvalidConfig(
+                    "chunk length must be between 1 and the dimension",
+                ))
+            }
+            MeasurementType::MultihotCountVec { max_weight, .. }
+                if max_weight == 0 || max_weight > self.dimension =>
+            {
+                return Err(TaskError::InvalidConfig(
+                    "maximum weight must be between 1 and the dimension",
+                ))
+            }
+            MeasurementType::RangeSumVec { min, max } if min >= max || max - min >= 64 => {
+                return Err(TaskError::InvalidConfig(
+                    "range must contain between 2 and 64 integers",
+                ))
+            }
+            MeasurementType::AndCountVec { ref pairs }
+                if pairs
+                    .iter()
+                    .any(|(i, j)| *i >= self.dimension || *j >= self.dimension || i == j) =>
+            {
+                return Err(TaskError::InvalidConfig(
+                    "pairs must be of distinct positions in range",
+                ))
+            }
+            MeasurementType::DistinctCount {
+                bucket_bits,
+                max_rank,
+                chunk_length,
+            } if !(4..=16).contains(&bucket_bits)
+                || max_rank == 0
+                || max_rank > 65 - bucket_bits
+                || chunk_length == 0
+                || self.dimension != 1 << bucket_bits =>
+            {
+                return Err(TaskError::InvalidConfig(
+                    "distinct count parameters are out of range or do not match the dimension",
+                ))
+            }
+            MeasurementType::Custom { code } if code < FIRST_CUSTOM_CODE => {
+                return Err(TaskError::InvalidConfig(
+                    "custom measurement types must have a code of at least 256",
+                ))
+            }
+            _ => (),
+        }
+        if self.dimension == 0 {
+            return Err(TaskError::InvalidConfig("dimension must be positive"));
+        }
+        if let Some(dp) = &self.dp {
+            if dp.epsilon_numerator == 0 || dp.epsilon_denominator == 0 {
+                return Err(TaskError::InvalidConfig("epsilon must be positive"));
+            }
+            if gcd(dp.epsilon_numerator, dp.epsilon_denominator) != 1 {
+                return Err(TaskError::InvalidConfig("epsilon must be in lowest terms"));
+            }
+        }
+        if self.key_fingerprints.is_empty() {
+            return Err(TaskError::InvalidConfig(
+                "at least one key fingerprint is required",
+            ));
+        }
+        let policy = &self.batch_policy;
+        if policy.min_batch_size == 0 {
+            return Err(TaskError::InvalidConfig(
+                "minimum batch size must be positive",
+            ));
+        }
+        if policy
+            .max_batch_size
+            .is_some_and(|max| max < policy.min_batch_size)
+        {
+            return Err(TaskError::InvalidConfig(
+                "maximum batch size must be at least the minimum",
+            ));
+        }
+        if policy.time_precision == 0 {
+            return Err(TaskError::InvalidConfig("time precision must be positive"));
+        }
+        let helper_shares = &self.helper_shares;
+        if usize::from(helper_shares.seed_len) != helper_shares.expansion.seed_len() {
+            return Err(TaskError::InvalidConfig(
+                "seed length does not match the seed expansion",
+            ));
+        }
+        if (helper_shares.expansion == SeedExpansion::Aes128Ctr)
+            != (self.field == FieldId::FieldPrio2)
+        {
+            return Err(TaskError::InvalidConfig(
+                "AES-128-CTR seed expansion is used exactly with FieldPrio2",
+            ));
+        }
+        if helper_shares.num_seeds == 0 {
+            return Err(TaskError::InvalidConfig("helper shares must have a seed"));
+        }
+        if helper_shares.expanded_len < self.dimension {
+            return Err(TaskError::InvalidConfig(
+                "expanded helper shares must be at least as long as the measurement",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the measurement type is in `registry`, so that this Aggregator knows what it
+    /// means.
+    pub fn check_registered(&self, registry: &MeasurementTypeRegistry) -> Result<(), TaskError> {
+        match registry.name_of(&self.measurement_type) {
+            Some(_) => Ok(()),
+            None => Err(TaskError::UnknownMeasurementType(
+                self.measurement_type.code(),
+            )),
+        }
+    }
+
+    /// Checks that this Aggregator supports the protocol version of the task.
+    pub fn check_version(&self, supported: &SupportedVersions) -> Result<(), TaskError> {
+        Ok(supported.check(self.version)?)
+    }
+
+    /// Returns the task ID: the SHA3-256 hash of the encoded config.
+    pub fn task_id(&self) -> Result<TaskId, TaskError> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(TASK_ID_DST);
+        hasher.update(self.get_encoded()?);
+        Ok(TaskId::from(<[u8; 32]>::from(hasher.finalize())))
+    }
+
+    /// Validates this config, as received from the leader, and returns the acknowledgement the
+    /// helper sends back.
+    pub fn acknowledge(&self) -> Result<TaskAcknowledgement, TaskError> {
+        self.validate()?;
+        Ok(TaskAcknowledgement {
+            task_id: self.task_id()?,
+        })
+    }
+
+    /// Checks that the helper's acknowledgement is for this config.
+    pub fn check_acknowledgement(&self, ack: &TaskAcknowledgement) -> Result<(), TaskError> {
+        if !bool::from(self.task_id()?.ct_eq(&ack.task_id)) {
+            return Err(TaskError::Mismatch);
+        }
+        Ok(())
+    }
+}
+
+impl Encode for TaskConfig {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.measurement_type.encode(bytes)?;
+        self.dimension.encode(bytes)?;
+        self.field.encode(bytes)?;
+        encode_option(bytes, &self.dp)?;
+        encode_u16_items(bytes, &(), &self.key_fingerprints)?;
+        self.batch_policy.encode(bytes)?;
+        self.helper_shares.encode(bytes)?;
+        self.version.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(
+            self.measurement_type.encoded_len()?
+                + 4
+                + 1
+                + 1
+                + self.dp.as_ref().map_or(0, |_| 17)
+                + 2
+                + 32 * self.key_fingerprints.len()
+                + self.batch_policy.encoded_len()?
+                + self.helper_shares.encoded_len()?
+                + 2,
+        )
+    }
+}
+
+impl Decode for TaskConfig {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            measurement_type: MeasurementType::decode(bytes)?,
+            dimension: u32::decode(bytes)?,
+            field: FieldId::decode(bytes)?,
+            dp: decode_option(bytes)?,
+            key_fingerprints: decode_u16_items(&(), bytes)?,
+            batch_policy: BatchPolicy::decode(bytes)?,
+            helper_shares: HelperShareConfig::decode(bytes)?,
+            version: ProtocolVersion::decode(bytes)?,
+        })
+    }
+}
+
+/// The helper's reply to a [`TaskConfig`], carrying the task ID it computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskAcknowledgement {
+    /// The task ID of the config the helper accepted.
+    pub task_id: TaskId,
+}
+
+impl Encode for TaskAcknowledgement {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.task_id.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        self.task_id.encoded_len()
+    }
+}
+
+impl Decode for TaskAcknowledgement {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            task_id: TaskId::decode(bytes)?,
+        })
+    }
+}
+
+/// Encodes an optional value as a presence byte, followed by the value if it is present.
+fn encode_option<E: Encode>(bytes: &mut Vec<u8>, value: &Option<E>) -> Result<(), CodecError> {
+    match value {
+        Some(value) => {
+            1u8.encode(bytes)?;
+            value.encode(bytes)
+        }
+        None => 0u8.encode(bytes),
+    }
+}
+
+/// Decodes an optional value encoded by [`encode_option`]. Presence bytes other than 0 and 1 are
+/// rejected, so that the encoding is canonical.
+fn decode_option<D: Decode>(bytes: &mut Cursor<&[u8]>) -> Result<Option<D>, CodecError> {
+    match u8::decode(bytes)? {
+        0 => Ok(None),
+        1 => Ok(Some(D::decode(bytes)?)),
+        _ => Err(CodecError::UnexpectedValue),
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn config() -> TaskConfig {
+        TaskConfig {
+            measurement_type: MeasurementType::Histogram { chunk_length: 4 },
+            dimension: 16,
+            field: FieldId::Field128,
+            dp: Some(DpConfig {
+                mechanism: DpMechanism::DiscreteGaussian,
+                epsilon_numerator: 1,
+                epsilon_denominator: 2,
+            }),
+            key_fingerprints: vec![
+                KeyFingerprint::new(&[0; 16]),
+                KeyFingerprint::new(b"leader public key"),
+            ],
+            batch_policy: BatchPolicy {
+                min_batch_size: 100,
+                max_batch_size: None,
+                time_precision: 3600,
+            },
+            helper_shares: HelperShareConfig {
+                expansion: SeedExpansion::TurboShake128,
+                seed_len: 16,
+                num_seeds: 2,
+                expanded_len: 48,
+            },
+            version: ProtocolVersion::CURRENT,
+        }
+    }
+
+    #[test]
+    fn task_handshake() {
+        let leader_config = config();
+        let encoded = leader_config.get_encoded().unwrap();
+        assert_eq!(Some(encoded.len()), leader_config.encoded_len());
+
+        // The helper decodes the leader's config and acknowledges it.
+        let helper_config = TaskConfig::get_decoded(&encoded).unwrap();
+        assert_eq!(helper_config, leader_config);
+        let ack = helper_config.acknowledge().unwrap();
+        let ack = TaskAcknowledgement::get_decoded(&ack.get_encoded().unwrap()).unwrap();
+        leader_config.check_acknowledgement(&ack).unwrap();
+
+        // Any change to the parameters changes the task ID.
+        let mut other = leader_config.clone();
+        other.batch_policy.max_batch_size = Some(1000);
+        assert_ne!(other.task_id().unwrap(), leader_config.task_id().unwrap());
+        assert_matches!(other.check_acknowledgement(&ack), Err(TaskError::Mismatch));
+        let mut other = leader_config.clone();
+        other.key_fingerprints[1] = KeyFingerprint::new(b"another key");
+        assert_matches!(other.check_acknowledgement(&ack), Err(TaskError::Mismatch));
+
+        // Non-canonical encodings are rejected. Byte 10 is the presence byte of the DP config.
+        let mut bad = encoded;
+        assert_eq!(bad[10], 1);
+        bad[10] = 2;
+        assert!(TaskConfig::get_decoded(&bad).is_err());
+    }
+
+    #[test]
+    fn task_version() {
+        let v = ProtocolVersion::new;
+        let leader = SupportedVersions::new([v(1), v(2)]).unwrap();
+        let helper = SupportedVersions::new([v(1)]).unwrap();
+        let config = TaskConfig {
+            version: leader.negotiate(&helper).unwrap(),
+            ..config()
+        };
+        assert_eq!(config.version, v(1));
+        config.check_version(&helper).unwrap();
+
+        // The version is part of the encoding, and hence of the task ID.
+        let encoded = config.get_encoded().unwrap();
+        assert_eq!(&encoded[encoded.len() - 2..], [0, 1]);
+        let upgraded = TaskConfig {
+            version: v(2),
+            ..config.clone()
+        };
+        assert_ne!(upgraded.task_id().unwrap(), config.task_id().unwrap());
+        assert_eq!(
+            TaskConfig::get_decoded(&upgraded.get_encoded().unwrap()).unwrap(),
+            upgraded
+        );
+        assert_matches!(
+            upgraded.check_version(&helper),
+            Err(TaskError::Version(VersionError::Unsupported { .. }))
+        );
+    }
+
+    #[test]
+    fn custom_measurement_type() {
+        let config = TaskConfig {
+            measurement_type: MeasurementType::Custom { code: 1000 },
+            ..config()
+        };
+        config.validate().unwrap();
+        let encoded = config.get_encoded().unwrap();
+        assert_eq!(Some(encoded.len()), config.encoded_len());
+        assert_eq!(&encoded[..2], [0xe8, 0x07]);
+        assert_eq!(TaskConfig::get_decoded(&encoded).unwrap(), config);
+
+        let mut registry = MeasurementTypeRegistry::with_builtins();
+        assert_matches!(
+            config.check_registered(&registry),
+            Err(TaskError::UnknownMeasurementType(1000))
+        );
+        registry.register("example.com/custom", 1000).unwrap();
+        config.check_registered(&registry).unwrap();
+        self::config().check_registered(&registry).unwrap();
+
+        // Reserved codes without a built-in type do not decode.
+        let mut bad = self::config().get_encoded().unwrap();
+        bad[0] = 11;
+        assert!(TaskConfig::get_decoded(&bad).is_err());
+    }
+
+    #[test]
+    fn builtin_measurement_types() {
+        let registry = MeasurementTypeRegistry::with_builtins();
+        for (measurement_type, dimension, name) in [
+            (MeasurementType::Count, 1, "count"),
+            (MeasurementType::Sum { bits: 8 }, 1, "sum"),
+            (
+                MeasurementType::SumVec {
+                    bits: 8,
+                    chunk_length: 4,
+                },
+                16,
+                "sumvec",
+            ),
+            (
+                MeasurementType::Histogram { chunk_length: 4 },
+                16,
+                "histogram",
+            ),
+            (
+                MeasurementType::FixedPointBoundedL2VecSum { bits: 16 },
+                16,
+                "fixedpoint-boundedl2-vec-sum",
+            ),
+            (MeasurementType::Average { bits: 8 }, 1, "average"),
+            (
+                MeasurementType::MultihotCountVec {
+                    max_weight: 3,
+                    chunk_length: 4,
+                },
+                16,
+                "multihot-count-vec",
+            ),
+            (
+                MeasurementType::RangeSumVec { min: 1, max: 5 },
+                16,
+                "range-sum-vec",
+            ),
+            (
+                MeasurementType::AndCountVec {
+                    pairs: vec![(0, 1), (3, 15)],
+                },
+                16,
+                "and-count-vec",
+            ),
+            (
+                MeasurementType::DistinctCount {
+                    bucket_bits: 4,
+                    max_rank: 10,
+                    chunk_length: 8,
+                },
+                16,
+                "distinct-count",
+            ),
+            (MeasurementType::SignedSum { bits: 8 }, 1, "signed-sum"),
+        ] {
+            let config = TaskConfig {
+                measurement_type,
+                dimension,
+                ..config()
+            };
+            config.validate().unwrap();
+            config.check_registered(&registry).unwrap();
+            assert_eq!(registry.name_of(&config.measurement_type), Some(name));
+
+            let encoded = config.get_encoded().unwrap();
+            assert_eq!(Some(encoded.len()), config.encoded_len(), "{name}");
+            assert_eq!(TaskConfig::get_decoded(&encoded).unwrap(), config);
+        }
+
+        // Every built-in code is a measurement type.
+        assert_eq!(
+            registry.iter().map(|(_, code)| code).collect::<Vec<_>>(),
+            (0..11).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn invalid_task_configs() {
+        let config = config();
+        for bad in [
+            TaskConfig {
+                measurement_type: MeasurementType::Count,
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::SumVec {
+                    bits: 0,
+                    chunk_length: 4,
+                },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::Histogram { chunk_length: 17 },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::Custom { code: 5 },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::SignedSum { bits: 8 },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::FixedPointBoundedL2VecSum { bits: 8 },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::MultihotCountVec {
+                    max_weight: 17,
+                    chunk_length: 4,
+                },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::RangeSumVec { min: 0, max: 64 },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::AndCountVec {
+                    pairs: vec![(0, 16)],
+                },
+                ..config.clone()
+            },
+            TaskConfig {
+                measurement_type: MeasurementType::DistinctCount {
+                    bucket_bits: 5,
+                    max_rank: 10,
+                    chunk_length: 8,
+                },
+                ..config.clone()
+            },
+            TaskConfig {
+                dp: Some(DpConfig {
+                    mechanism: DpMechanism::DiscreteLaplace,
+                    epsilon_numerator: 2,
+                    epsilon_denominator: 4,
+                }),
+                ..config.clone()
+            },
+            TaskConfig {
+                key_fingerprints: Vec::new(),
+                ..config.clone()
+            },
+            TaskConfig {
+                batch_policy: BatchPolicy {
+                    max_batch_size: Some(10),
+                    ..config.batch_policy
+                },
+                ..config.clone()
+            },
+            TaskConfig {
+                helper_shares: HelperShareConfig {
+                    seed_len: 32,
+                    ..config.helper_shares
+                },
+                ..config.clone()
+            },
+            TaskConfig {
+                helper_shares: HelperShareConfig {
+                    expansion: SeedExpansion::Aes128Ctr,
+                    seed_len: 32,
+                    ..config.helper_shares
+                },
+                ..config.clone()
+            },
+            TaskConfig {
+                helper_shares: HelperShareConfig {
+                    expanded_len: 15,
+                    ..config.helper_shares
+                },
+                ..config.clone()
+            },
+        ] {
+            assert_matches!(bad.acknowledge(), Err(TaskError::InvalidConfig(_)));
+        }
+    }
+
+    #[test]
+    fn helper_share_config() {
+        use crate::vdaf::{prio3::Prio3, Client};
+
+        let vdaf = Prio3::new_histogram(2, 16, 4).unwrap();
+        let task = HelperShareConfig::for_prio3(&vdaf).unwrap();
+        // A measurement seed, a proof seed, and a joint randomness blind.
+        assert_eq!(task.num_seeds, 3);
+        assert_eq!(task.share_len(), vdaf.input_share_len(1));
+        task.check_vdaf(&task).unwrap();
+
+        let (_, input_shares) = vdaf.shard(&3, &[0; 16]).unwrap();
+        task.check_share(&input_shares[1].get_encoded().unwrap())
+            .unwrap();
+        assert_matches!(
+            task.check_share(&input_shares[0].get_encoded().unwrap()),
+            Err(TaskError::HelperShare(_))
+        );
+
+        // A helper running a VDAF with different parameters notices at provisioning time.
+        let other = HelperShareConfig::for_prio3(&Prio3::new_histogram(2, 16, 5).unwrap()).unwrap();
+        assert_ne!(other.expanded_len, task.expanded_len);
+        assert_matches!(task.check_vdaf(&other), Err(TaskError::HelperShare(_)));
+        let count = HelperShareConfig::for_prio3(&Prio3::new_count(2).unwrap()).unwrap();
+        assert_eq!(count.num_seeds, 2);
+        assert_matches!(task.check_vdaf(&count), Err(TaskError::HelperShare(_)));
+
+        let prio2 = Prio2::new(10).unwrap();
+        let prio2_task = HelperShareConfig::for_prio2(&prio2).unwrap();
+        assert_eq!(prio2_task.share_len(), 32);
+        assert_eq!(
+            prio2_task.expanded_len as usize * 4,
+            prio2.input_share_len(0)
+        );
+        let config = TaskConfig {
+            measurement_type: MeasurementType::SumVec {
+                bits: 1,
+                chunk_length: 10,
+            },
+            dimension: 10,
+            field: FieldId::FieldPrio2,
+            helper_shares: prio2_task,
+            ..config()
+        };
+        config.validate().unwrap();
+        assert_eq!(
+            TaskConfig::get_decoded(&config.get_encoded().unwrap()).unwrap(),
+            config
+        );
+    }
+}
@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Metrics emitted by the VDAFs in this crate.
+//!
+//! With the `metrics` feature enabled, preparation and aggregation report to the [`metrics`]
+//! facade; the embedding application chooses the recorder (e.g., a Prometheus exporter). Without
+//! it, every function here compiles to nothing. Each metric is labeled with `vdaf`, which is one of
+//! `prio2`, `prio3`, `poplar1` or `mastic`:
+//!
+//! * `prio_reports_verified_total`: reports whose prepare shares were combined successfully in the
+//!   last round of verification.
+//! * `prio_reports_rejected_total`: reports rejected during preparation, further labeled with the
+//!   `reason`, which is one of the [`RejectionReason`](crate::vdaf::RejectionReason) names.
+//! * `prio_verification_duration_seconds`: a histogram of the time taken to combine a set of
+//!   prepare shares, whether or not they are accepted.
+//! * `prio_output_shares_aggregated_total`: output shares added to aggregate shares, i.e. the
+//!   number of measurements in the accumulators.
+//!
+//! The [`Ingestor`](crate::vdaf::ingest::Ingestor) additionally reports the gauge
+//! `prio_ingest_queue_depth`, the number of items waiting in its queue, with no labels.
+//!
+//! [`metrics`]: https://docs.rs/metrics
+
+use crate::vdaf::{RejectionReason, VdafError};
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Records the duration of one call to `prepare_shares_to_prepare_message()` when dropped.
+pub(crate) struct VerificationTimer {
+    #[cfg(feature = "metrics")]
+    vdaf: &'static str,
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl VerificationTimer {
+    pub(crate) fn start(_vdaf: &'static str) -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            vdaf: _vdaf,
+            #[cfg(feature = "metrics")]
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for VerificationTimer {
+    fn drop(&mut self) {
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("prio_verification_duration_seconds", "vdaf" => self.vdaf)
+            .record(self.start.elapsed());
+    }
+}
+
+/// Records that a report was verified.
+pub(crate) fn verified(_vdaf: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("prio_reports_verified_total", "vdaf" => _vdaf).increment(1);
+}
+
+/// Records that a report was rejected for `reason`, and wraps `error` in
+/// [`VdafError::Rejected`].
+pub(crate) fn rejected(
+    _vdaf: &'static str,
+    reason: RejectionReason,
+    error: VdafError,
+) -> VdafError {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("prio_reports_rejected_total", "vdaf" => _vdaf, "reason" => reason.as_str())
+        .increment(1);
+    VdafError::Rejected {
+        reason,
+        source: Box::new(error),
+    }
+}
+
+/// Records that `count` output shares were aggregated.
+pub(crate) fn aggregated(_vdaf: &'static str, _count: u64) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("prio_output_shares_aggregated_total", "vdaf" => _vdaf).increment(_count);
+}
+
+/// Records the number of items waiting in an ingestion queue.
+#[cfg(feature = "experimental")]
+pub(crate) fn queue_depth(_depth: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!("prio_ingest_queue_depth").set(_depth as f64);
+}
+
+#[cfg(all(test, feature = "metrics", feature = "test-util"))]
+mod tests {
+    use crate::vdaf::{prio3::Prio3, test_utils::run_vdaf_prepare, Aggregator, Client};
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Records the value of every counter and the number of samples in every histogram.
+    #[derive(Clone, Default)]
+    struct TestRecorder(Arc<Mutex<Vec<(String, u64)>>>);
+
+    struct TestMetric {
+        name: String,
+        values: Arc<Mutex<Vec<(String, u64)>>>,
+    }
+
+    impl TestMetric {
+        fn update(&self, f: impl FnOnce(&mut u64)) {
+            let mut values = self.values.lock().unwrap();
+            match values.iter_mut().find(|(name, _)| *name == self.name) {
+                Some((_, value)) => f(value),
+                None => {
+                    let mut value = 0;
+                    f(&mut value);
+                    values.push((self.name.clone(), value));
+                }
+            }
+        }
+
+        fn add(&self, value: u64) {
+            self.update(|total| *total += value)
+        }
+    }
+
+    impl CounterFn for TestMetric {
+        fn increment(&self, value: u64) {
+            self.add(value)
+        }
+
+        fn absolute(&self, value: u64) {
+            self.update(|total| *total = value)
+        }
+    }
+
+    impl HistogramFn for TestMetric {
+        fn record(&self, _value: f64) {
+            self.add(1)
+        }
+    }
+
+    impl TestRecorder {
+        fn metric(&self, key: &Key) -> Arc<TestMetric> {
+            let mut name = key.name().to_string();
+            for label in key.labels() {
+                name += &format!(",{}={}", label.key(), label.value());
+            }
+            Arc::new(TestMetric {
+                name,
+                values: self.0.clone(),
+            })
+        }
+
+        fn get(&self, name: &str) -> u64 {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(n, _)| n == name)
+                .map_or(0, |(_, value)| *value)
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.metric(key))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.metric(key))
+        }
+    }
+
+    #[test]
+    fn prio3_metrics() {
+        let recorder = TestRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            let vdaf = Prio3::new_sum(2, 8).unwrap();
+            let mut leader_out_shares = Vec::new();
+            for (i, measurement) in [1, 2, 3].into_iter().enumerate() {
+                let nonce = [i as u8; 16];
+                let (public_share, input_shares) = vdaf.shard(&measurement, &nonce).unwrap();
+                let out_shares =
+                    run_vdaf_prepare(&vdaf, &[0; 16], &(), &nonce, public_share, input_shares)
+                        .unwrap();
+                leader_out_shares.push(out_shares.into_iter().next().unwrap());
+            }
+            vdaf.aggregate(&(), leader_out_shares).unwrap();
+
+            // Shard with one nonce and prepare with another, so that the proof doesn't verify.
+            let (public_share, input_shares) = vdaf.shard(&1, &[0; 16]).unwrap();
+            run_vdaf_prepare(&vdaf, &[0; 16], &(), &[1; 16], public_share, input_shares)
+                .unwrap_err();
+
+            // Drop a prepare share.
+            let (public_share, input_shares) = vdaf.shard(&1, &[0; 16]).unwrap();
+            let (_, prep_share) = vdaf
+                .prepare_init(&[0; 16], 0, &(), &[0; 16], &public_share, &input_shares[0])
+                .unwrap();
+            vdaf.prepare_shares_to_prepare_message(&(), [prep_share])
+                .unwrap_err();
+        });
+
+        assert_eq!(recorder.get("prio_reports_verified_total,vdaf=prio3"), 3);
+        assert_eq!(
+            recorder.get("prio_reports_rejected_total,vdaf=prio3,reason=invalid_proof"),
+            1
+        );
+        assert_eq!(
+            recorder.get("prio_reports_rejected_total,vdaf=prio3,reason=length_mismatch"),
+            1
+        );
+        assert_eq!(
+            recorder.get("prio_verification_duration_seconds,vdaf=prio3"),
+            5
+        );
+        assert_eq!(
+            recorder.get("prio_output_shares_aggregated_total,vdaf=prio3"),
+            3
+        );
+    }
+
+    #[test]
+    fn test_recorder_counter_absolute() {
+        let recorder = TestRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            let counter = metrics::counter!("counter");
+            counter.increment(2);
+            counter.absolute(7);
+            counter.increment(1);
+        });
+
+        assert_eq!(recorder.get("counter"), 8);
+    }
+}
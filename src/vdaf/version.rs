@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Protocol versions.
+//!
+//! The Aggregators of a deployment are upgraded one at a time, so for a while they run different
+//! versions of the software. Every message they exchange therefore carries a
+//! [`ProtocolVersion`]: a [`TaskConfig`](crate::vdaf::task::TaskConfig) fixes the version of the
+//! task, each [`Report`](crate::vdaf::report::Report) and
+//! [`ReportShare`](crate::vdaf::report::ReportShare) the version the Client produced it with, and
+//! prepare shares and messages are wrapped in a [`Versioned`] envelope.
+//!
+//! Before provisioning a task, the Aggregators exchange their [`SupportedVersions`] and pick the
+//! highest version both support with [`SupportedVersions::negotiate`]; a peer that sends a message
+//! of a version this Aggregator does not support is refused with [`VersionError::Unsupported`]. An
+//! Aggregator that is upgraded first keeps supporting the old version until its peer has caught
+//! up, and a [`VersionDispatcher`] routes each message to the code for its version.
+//!
+//! ```
+//! use prio::vdaf::version::{ProtocolVersion, SupportedVersions};
+//!
+//! let upgraded = SupportedVersions::new([ProtocolVersion::new(1), ProtocolVersion::new(2)]).unwrap();
+//! let not_yet = SupportedVersions::new([ProtocolVersion::new(1)]).unwrap();
+//! assert_eq!(upgraded.negotiate(&not_yet).unwrap(), ProtocolVersion::new(1));
+//! ```
+
+use crate::codec::{
+    decode_u8_items, encode_u8_items, CodecError, Decode, Encode, ParameterizedDecode,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    io::Cursor,
+};
+
+/// Errors returned by this module.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum VersionError {
+    /// A message has a version this Aggregator does not support.
+    #[error("unsupported protocol version {version}")]
+    Unsupported {
+        /// The version of the message.
+        version: ProtocolVersion,
+    },
+
+    /// The Aggregators have no version in common.
+    #[error("no protocol version in common")]
+    NoCommonVersion,
+
+    /// A set of supported versions is empty.
+    #[error("no supported protocol versions")]
+    Empty,
+
+    /// A message is too short to carry a protocol version.
+    #[error("message has no protocol version")]
+    Missing,
+}
+
+/// The version of the messages exchanged by Clients and Aggregators, encoded as a `u16`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion(u16);
+
+impl ProtocolVersion {
+    /// The version this crate produces by default.
+    pub const CURRENT: Self = Self(1);
+
+    /// Returns version `version`.
+    pub const fn new(version: u16) -> Self {
+        Self(version)
+    }
+
+    /// Returns the version number.
+    pub fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        Self::CURRENT
+    }
+}
+
+impl Display for ProtocolVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+impl Encode for ProtocolVersion {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.0.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(2)
+    }
+}
+
+impl Decode for ProtocolVersion {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        u16::decode(bytes).map(Self)
+    }
+}
+
+/// The non-empty set of protocol versions an Aggregator supports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SupportedVersions(BTreeSet<ProtocolVersion>);
+
+impl SupportedVersions {
+    /// Returns the set of `versions`. Returns an error if there are none.
+    pub fn new<I>(versions: I) -> Result<Self, VersionError>
+    where
+        I: IntoIterator<Item = ProtocolVersion>,
+    {
+        let versions: BTreeSet<_> = versions.into_iter().collect();
+        if versions.is_empty() {
+            return Err(VersionError::Empty);
+        }
+        Ok(Self(versions))
+    }
+
+    /// Returns whether `version` is supported.
+    pub fn contains(&self, version: ProtocolVersion) -> bool {
+        self.0.contains(&version)
+    }
+
+    /// Returns an error unless `version`, received from a peer, is supported.
+    pub fn check(&self, version: ProtocolVersion) -> Result<(), VersionError> {
+        if !self.contains(version) {
+            return Err(VersionError::Unsupported { version });
+        }
+        Ok(())
+    }
+
+    /// Returns the highest version supported both by this Aggregator and by its `peer`.
+    pub fn negotiate(&self, peer: &Self) -> Result<ProtocolVersion, VersionError> {
+        self.0
+            .iter()
+            .rev()
+            .find(|version| peer.contains(**version))
+            .copied()
+            .ok_or(VersionError::NoCommonVersion)
+    }
+
+    /// Returns the supported versions in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = ProtocolVersion> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl Default for SupportedVersions {
+    fn default() -> Self {
+        Self(BTreeSet::from([ProtocolVersion::CURRENT]))
+    }
+}
+
+impl Encode for SupportedVersions {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_u8_items(bytes, &(), &self.0.iter().copied().collect::<Vec<_>>())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(1 + 2 * self.0.len())
+    }
+}
+
+impl Decode for SupportedVersions {
+    fn decode(bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let versions: Vec<ProtocolVersion> = decode_u8_items(&(), bytes)?;
+        // The versions are encoded in increasing order, so that the encoding is canonical.
+        if versions.is_empty() || versions.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(CodecError::UnexpectedValue);
+        }
+        Ok(Self(versions.into_iter().collect()))
+    }
+}
+
+/// A message prefixed with its protocol version, such as a prepare share or prepare message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Versioned<T> {
+    /// The version of the message.
+    pub version: ProtocolVersion,
+
+    /// The message.
+    pub message: T,
+}
+
+impl<T> Versioned<T> {
+    /// Wraps `message` with `version`.
+    pub fn new(version: ProtocolVersion, message: T) -> Self {
+        Self { version, message }
+    }
+
+    /// Reads the version of an encoded message without decoding the rest, so that the message can
+    /// be handed to the code for its version.
+    pub fn peek_version(encoded: &[u8]) -> Result<ProtocolVersion, CodecError> {
+        ProtocolVersion::decode(&mut Cursor::new(encoded))
+    }
+}
+
+impl<T: Encode> Encode for Versioned<T> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.version.encode(bytes)?;
+        self.message.encode(bytes)
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(2 + self.message.encoded_len()?)
+    }
+}
+
+impl<P, T: ParameterizedDecode<P>> ParameterizedDecode<P> for Versioned<T> {
+    fn decode_with_param(param: &P, bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        Ok(Self {
+            version: ProtocolVersion::decode(bytes)?,
+            message: T::decode_with_param(param, bytes)?,
+        })
+    }
+}
+
+/// Routes messages to a handler for their protocol version, e.g. a VDAF configured the way that
+/// version specifies.
+#[derive(Clone, Debug)]
+pub struct VersionDispatcher<H> {
+    handlers: BTreeMap<ProtocolVersion, H>,
+}
+
+impl<H> VersionDispatcher<H> {
+    /// Creates a dispatcher with no handlers.
+    pub fn new() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the handler for `version`, returning the previous one, if any.
+    pub fn register(&mut self, version: ProtocolVersion, handler: H) -> Option<H> {
+        self.handlers.insert(version, handler)
+    }
+
+    /// Removes the handler for `version`, e.g. once every peer has been upgraded past it.
+    pub fn unregister(&mut self, version: ProtocolVersion) -> Option<H> {
+        self.handlers.remove(&version)
+    }
+
+    /// Returns the versions that have a handler, to send to a peer for negotiation.
+    pub fn supported(&self) -> Result<SupportedVersions, VersionError> {
+        SupportedVersions::new(self.handlers.keys().copied())
+    }
+
+    /// Returns the handler for `version`.
+    pub fn handler(&self, version: ProtocolVersion) -> Result<&H, VersionError> {
+        self.handlers
+            .get(&version)
+            .ok_or(VersionError::Unsupported { version })
+    }
+
+    /// Returns the handler for an encoded [`Versioned`] message, along with its version.
+    pub fn handler_for(&self, encoded: &[u8]) -> Result<(ProtocolVersion, &H), VersionError> {
+        let version = Versioned::<()>::peek_version(encoded).map_err(|_| VersionError::Missing)?;
+        Ok((version, self.handler(version)?))
+    }
+}
+
+impl<H> Default for VersionDispatcher<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdaf::{
+        prio3::{Prio3, Prio3PrepareShare},
+        Aggregator, Client, Vdaf,
+    };
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn negotiate() {
+        let v = ProtocolVersion::new;
+        let ours = SupportedVersions::new([v(1), v(2), v(3)]).unwrap();
+        let theirs = SupportedVersions::new([v(2), v(3), v(4)]).unwrap();
+        assert_eq!(ours.negotiate(&theirs).unwrap(), v(3));
+        assert_eq!(theirs.negotiate(&ours).unwrap(), v(3));
+        assert_eq!(
+            ours.negotiate(&SupportedVersions::new([v(5)]).unwrap()),
+            Err(VersionError::NoCommonVersion)
+        );
+        assert!(ours.check(v(2)).is_ok());
+        assert_eq!(
+            ours.check(v(4)),
+            Err(VersionError::Unsupported { version: v(4) })
+        );
+        assert_eq!(SupportedVersions::new([]), Err(VersionError::Empty));
+        assert_eq!(
+            SupportedVersions::default().iter().collect::<Vec<_>>(),
+            [ProtocolVersion::CURRENT]
+        );
+
+        let encoded = ours.get_encoded().unwrap();
+        assert_eq!(encoded, [6, 0, 1, 0, 2, 0, 3]);
+        assert_eq!(SupportedVersions::get_decoded(&encoded).unwrap(), ours);
+        for bad in [&[0][..], &[4, 0, 2, 0, 1], &[4, 0, 1, 0, 1]] {
+            assert_matches!(
+                SupportedVersions::get_decoded(bad),
+                Err(CodecError::UnexpectedValue)
+            );
+        }
+    }
+
+    #[test]
+    fn dispatch_prepare_shares() {
+        // Two versions of a task that differ in the chunk length of the validity circuit.
+        let mut dispatcher = VersionDispatcher::new();
+        dispatcher.register(
+            ProtocolVersion::new(1),
+            Prio3::new_sum_vec(2, 1, 4, 2).unwrap(),
+        );
+        dispatcher.register(
+            ProtocolVersion::new(2),
+            Prio3::new_sum_vec(2, 1, 4, 4).unwrap(),
+        );
+        assert_eq!(
+            dispatcher.supported().unwrap().iter().count(),
+            2,
+            "both versions are supported"
+        );
+
+        let version = ProtocolVersion::new(2);
+        let vdaf = dispatcher.handler(version).unwrap();
+        let nonce = [1; 16];
+        let (public_share, input_shares) = vdaf.shard(&vec![1, 0, 1, 1], &nonce).unwrap();
+        let (state, share) = vdaf
+            .prepare_init(&[2; 16], 1, &(), &nonce, &public_share, &input_shares[1])
+            .unwrap();
+        let encoded = Versioned::new(version, share.clone())
+            .get_encoded()
+            .unwrap();
+
+        // The receiver picks the VDAF of the message's version to decode it.
+        let (got_version, vdaf) = dispatcher.handler_for(&encoded).unwrap();
+        assert_eq!(got_version, version);
+        let decoded: Versioned<Prio3PrepareShare<_, 16>> =
+            Versioned::get_decoded_with_param(&state, &encoded).unwrap();
+        assert_eq!(decoded, Versioned::new(version, share));
+        assert_eq!(vdaf.num_aggregators(), 2);
+
+        dispatcher.unregister(ProtocolVersion::new(2));
+        assert_matches!(
+            dispatcher.handler_for(&encoded),
+            Err(VersionError::Unsupported { version }) if version == ProtocolVersion::new(2)
+        );
+        assert_matches!(dispatcher.handler_for(&[0]), Err(VersionError::Missing));
+        assert_matches!(
+            VersionDispatcher::<()>::new().supported(),
+            Err(VersionError::Empty)
+        );
+    }
+}
@@ -51,6 +51,17 @@ use subtle::{Choice, ConstantTimeEq};
 #[derive(Clone, Debug)]
 pub struct Seed<const SEED_SIZE: usize>(pub(crate) [u8; SEED_SIZE]);
 
+#[cfg(feature = "test-util")]
+impl<'a, const SEED_SIZE: usize> arbitrary::Arbitrary<'a> for Seed<SEED_SIZE> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary().map(Self)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <[u8; SEED_SIZE] as arbitrary::Arbitrary>::size_hint(depth)
+    }
+}
+
 impl<const SEED_SIZE: usize> Seed<SEED_SIZE> {
     /// Generate a uniform random seed.
     pub fn generate() -> Result<Self, getrandom::Error> {
@@ -116,6 +127,44 @@ impl<S: RngCore> IntoFieldVec for S {
     }
 }
 
+/// Hashes `seed`, domain separated by `dst` and bound to the concatenation of the `binder`
+/// fragments, to a vector of `length` field elements.
+///
+/// This is how the VDAFs in this crate derive the randomness of their validity circuits, such as
+/// the joint randomness, the prover randomness and the query randomness from which the
+/// Fiat-Shamir evaluation points are taken. Custom circuits should derive any further randomness
+/// the same way. The hash is given by the XOF `P`: [`XofTurboShake128`] is based on SHAKE, and
+/// [`XofHmacSha256Aes128`] on SHA-256. The field elements are obtained by rejection sampling, so
+/// they are uniformly distributed.
+///
+/// ```
+/// use prio::{
+///     field::Field64,
+///     vdaf::xof::{hash_to_field, Seed, XofTurboShake128},
+/// };
+///
+/// let seed = Seed::generate().unwrap();
+/// let challenge: Vec<Field64> =
+///     hash_to_field::<XofTurboShake128, _, 16>(&seed, b"my circuit", &[b"nonce"], 3);
+/// assert_eq!(challenge.len(), 3);
+/// ```
+pub fn hash_to_field<P, F, const SEED_SIZE: usize>(
+    seed: &Seed<SEED_SIZE>,
+    dst: &[u8],
+    binder: &[&[u8]],
+    length: usize,
+) -> Vec<F>
+where
+    P: Xof<SEED_SIZE>,
+    F: FieldElement,
+{
+    let mut xof = P::init(seed.as_ref(), dst);
+    for fragment in binder {
+        xof.update(fragment);
+    }
+    xof.into_seed_stream().into_field_vec(length)
+}
+
 /// An extendable output function (XOF) with the interface specified in [[draft-irtf-cfrg-vdaf-08]].
 ///
 /// [draft-irtf-cfrg-vdaf-08]: https://datatracker.ietf.org/doc/draft-irtf-cfrg-vdaf/08/
@@ -484,7 +533,10 @@ impl Xof<32> for XofHmacSha256Aes128 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{field::Field128, vdaf::equality_comparison_test};
+    use crate::{
+        field::{Field128, Field64},
+        vdaf::equality_comparison_test,
+    };
     use serde::{Deserialize, Serialize};
     use std::{convert::TryInto, io::Cursor};
 
@@ -527,6 +579,33 @@ mod tests {
         assert_eq!(got, want);
     }
 
+    #[test]
+    fn hash_to_field_derivation() {
+        let seed = Seed::generate().unwrap();
+        let want: Vec<Field128> =
+            XofTurboShake128::seed_stream(&seed, b"dst", b"binder").into_field_vec(10);
+        assert_eq!(
+            hash_to_field::<XofTurboShake128, Field128, 16>(&seed, b"dst", &[b"binder"], 10),
+            want
+        );
+        assert_eq!(
+            hash_to_field::<XofTurboShake128, Field128, 16>(&seed, b"dst", &[b"bin", b"der"], 10),
+            want
+        );
+        assert_ne!(
+            hash_to_field::<XofTurboShake128, Field128, 16>(&seed, b"other", &[b"binder"], 10),
+            want
+        );
+
+        let seed = Seed::generate().unwrap();
+        let want: Vec<Field64> =
+            XofHmacSha256Aes128::seed_stream(&seed, b"dst", &[]).into_field_vec(3);
+        assert_eq!(
+            hash_to_field::<XofHmacSha256Aes128, Field64, 32>(&seed, b"dst", &[], 3),
+            want
+        );
+    }
+
     #[test]
     fn xof_turboshake128() {
         let t: XofTestVector =
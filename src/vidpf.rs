@@ -16,11 +16,14 @@ use core::{
 
 use bitvec::field::BitField;
 use rand_core::RngCore;
-use std::io::Cursor;
+use std::{
+    fmt::{self, Debug},
+    io::{Cursor, Read},
+};
 use subtle::{Choice, ConditionallyNegatable, ConditionallySelectable};
 
 use crate::{
-    codec::{CodecError, Encode, ParameterizedDecode},
+    codec::{CodecError, Decode, Encode, ParameterizedDecode},
     field::FieldElement,
     idpf::{
         conditional_select_seed, conditional_swap_seed, conditional_xor_seeds, xor_seeds,
@@ -349,6 +352,7 @@ impl VidpfDomainSepTag {
 }
 
 /// Private key of an aggregation server.
+#[derive(Clone)]
 pub struct VidpfKey {
     id: VidpfServerId,
     value: [u8; 16],
@@ -366,8 +370,41 @@ impl VidpfKey {
     }
 }
 
+impl Debug for VidpfKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VidpfKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Encode for VidpfKey {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        bytes.extend_from_slice(&self.value);
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        Some(16)
+    }
+}
+
+/// Decoding takes the index of the aggregation server the key belongs to, 0 or 1, as a parameter.
+impl ParameterizedDecode<usize> for VidpfKey {
+    fn decode_with_param(server: &usize, bytes: &mut Cursor<&[u8]>) -> Result<Self, CodecError> {
+        let id = match server {
+            0 => VidpfServerId::S0,
+            1 => VidpfServerId::S1,
+            _ => return Err(CodecError::UnexpectedValue),
+        };
+        let mut value = [0; 16];
+        bytes.read_exact(&mut value)?;
+        Ok(Self { id, value })
+    }
+}
+
 /// Identifies the two aggregation servers.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum VidpfServerId {
     /// S0 is the first server.
     S0,
@@ -385,7 +422,7 @@ impl From<VidpfServerId> for Choice {
 }
 
 /// Adjusts values of shares during the VIDPF evaluation.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct VidpfCorrectionWord<W: VidpfValue> {
     seed: VidpfSeed,
     left_control_bit: Choice,
@@ -394,12 +431,69 @@ struct VidpfCorrectionWord<W: VidpfValue> {
 }
 
 /// Common public information used by aggregation servers.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct VidpfPublicShare<W: VidpfValue> {
     cw: Vec<VidpfCorrectionWord<W>>,
     cs: Vec<VidpfProof>,
 }
 
+/// The public share is encoded as the correction word of each level, its seed, a byte holding the
+/// left and right control bits in its two least significant bits, and its weight, followed by the
+/// proof correction of each level.
+impl<W: VidpfValue> Encode for VidpfPublicShare<W> {
+    fn encode(&self, bytes: &mut Vec<u8>) -> Result<(), CodecError> {
+        for cw in &self.cw {
+            bytes.extend_from_slice(&cw.seed);
+            (cw.left_control_bit.unwrap_u8() | cw.right_control_bit.unwrap_u8() << 1)
+                .encode(bytes)?;
+            cw.weight.encode(bytes)?;
+        }
+        for cs in &self.cs {
+            bytes.extend_from_slice(cs);
+        }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> Option<usize> {
+        let mut len = (16 + 1 + VIDPF_PROOF_SIZE) * self.cw.len();
+        for cw in &self.cw {
+            len += cw.weight.encoded_len()?;
+        }
+        Some(len)
+    }
+}
+
+/// Decoding takes the number of levels and the weight parameter as a parameter.
+impl<W: VidpfValue> ParameterizedDecode<(usize, W::ValueParameter)> for VidpfPublicShare<W> {
+    fn decode_with_param(
+        (bits, weight_parameter): &(usize, W::ValueParameter),
+        bytes: &mut Cursor<&[u8]>,
+    ) -> Result<Self, CodecError> {
+        let mut cw = Vec::new();
+        for _ in 0..*bits {
+            let mut seed = VidpfSeed::default();
+            bytes.read_exact(&mut seed)?;
+            let control_bits = u8::decode(bytes)?;
+            if control_bits > 3 {
+                return Err(CodecError::UnexpectedValue);
+            }
+            cw.push(VidpfCorrectionWord {
+                seed,
+                left_control_bit: Choice::from(control_bits & 1),
+                right_control_bit: Choice::from(control_bits >> 1),
+                weight: W::decode_with_param(weight_parameter, bytes)?,
+            });
+        }
+        let mut cs = Vec::new();
+        for _ in 0..*bits {
+            let mut proof = VidpfProof::default();
+            bytes.read_exact(&mut proof)?;
+            cs.push(proof);
+        }
+        Ok(Self { cw, cs })
+    }
+}
+
 /// Contains the values produced during input evaluation at a given level.
 pub struct VidpfEvalState {
     seed: VidpfSeed,
@@ -462,6 +556,12 @@ impl<F: FieldElement> From<Vec<F>> for VidpfWeight<F> {
     }
 }
 
+impl<F: FieldElement> AsRef<[F]> for VidpfWeight<F> {
+    fn as_ref(&self) -> &[F] {
+        &self.0
+    }
+}
+
 impl<F: FieldElement> VidpfValue for VidpfWeight<F> {}
 
 impl<F: FieldElement> IdpfValue for VidpfWeight<F> {
@@ -612,6 +712,45 @@ mod tests {
             (vidpf, public, keys, *TEST_NONCE)
         }
 
+        #[test]
+        fn roundtrip_public_share_and_keys() {
+            use crate::codec::{Encode, ParameterizedDecode};
+
+            let input = VidpfInput::from_bytes(&[0xA5]);
+            let weight = TestWeight::from(vec![21.into(), 22.into(), 23.into()]);
+            let (vidpf, public, keys, nonce) = vidpf_gen_setup(&input, &weight);
+
+            let encoded = public.get_encoded().unwrap();
+            assert_eq!(encoded.len(), public.encoded_len().unwrap());
+            let decoded_public = VidpfPublicShare::<TestWeight>::get_decoded_with_param(
+                &(input.len(), TEST_WEIGHT_LEN),
+                &encoded,
+            )
+            .unwrap();
+            assert_eq!(decoded_public.get_encoded().unwrap(), encoded);
+
+            for (server, key) in keys.iter().enumerate() {
+                let decoded_key =
+                    VidpfKey::get_decoded_with_param(&server, &key.get_encoded().unwrap()).unwrap();
+                let value_share = vidpf.eval(key, &public, &input, &nonce).unwrap();
+                let decoded_share = vidpf
+                    .eval(&decoded_key, &decoded_public, &input, &nonce)
+                    .unwrap();
+                assert_eq!(value_share.share, decoded_share.share);
+                assert_eq!(value_share.proof, decoded_share.proof);
+            }
+            assert!(VidpfKey::get_decoded_with_param(&2, &[0; 16]).is_err());
+
+            // Control bits are the two least significant bits of their byte.
+            let mut bad = encoded;
+            bad[16] = 4;
+            assert!(VidpfPublicShare::<TestWeight>::get_decoded_with_param(
+                &(input.len(), TEST_WEIGHT_LEN),
+                &bad
+            )
+            .is_err());
+        }
+
         #[test]
         fn gen_with_keys() {
             let input = VidpfInput::from_bytes(&[0xFF]);